@@ -0,0 +1,274 @@
+// Annotations point out what values in common formats mean, such as how
+// long ago an ISO 8601 date and time was, after the value. Like type hints,
+// they're only printed, and never change the value, so searching and
+// copying still use the original. They're shown with --annotate, or by
+// pressing 'zA'.
+//
+// Recognizing a format is a guess, so it's kept conservative: values that
+// could reasonably be something else, like dates without a time zone, are
+// left alone. Which formats are recognized is set with --annotate-formats.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::ArgEnum;
+
+use crate::decoders::{self, Scalar};
+
+/// A format of values that can be recognized and annotated.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, ArgEnum)]
+pub enum AnnotationFormat {
+    /// ISO 8601 dates and times with a time zone, like
+    /// "2021-02-03T04:05:06Z", annotated with how long ago they are.
+    Dates,
+    /// Integers with 13 digits that are milliseconds since the Unix epoch
+    /// between 2001 and 2100, annotated with the date and time.
+    EpochMillis,
+}
+
+pub struct Annotator {
+    pub formats: Vec<AnnotationFormat>,
+    // The time that relative times are relative to, in seconds since the
+    // Unix epoch.
+    pub now: i64,
+}
+
+impl Annotator {
+    pub fn new(formats: Vec<AnnotationFormat>) -> Annotator {
+        Annotator { formats, now: 0 }
+    }
+
+    /// The annotation for a value, e.g., " (3 days ago)", if it's in one of
+    /// the formats.
+    pub fn annotate(&self, value: Scalar) -> Option<String> {
+        self.formats.iter().find_map(|format| {
+            let annotation = match (format, value) {
+                (AnnotationFormat::Dates, Scalar::String(s)) => {
+                    relative_time(parse_date_time(s)?, self.now)
+                }
+                (AnnotationFormat::EpochMillis, Scalar::Number(s)) => {
+                    let secs = parse_epoch_millis(s)?;
+                    format!(
+                        "{}, {}",
+                        decoders::decode_unix_time(value)?,
+                        relative_time(secs, self.now)
+                    )
+                }
+                _ => return None,
+            };
+            Some(format!(" ({})", annotation))
+        })
+    }
+}
+
+// The current time, in seconds since the Unix epoch.
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64)
+}
+
+// Parses an RFC 3339 date and time, the common profile of ISO 8601, e.g.,
+// "2021-02-03T04:05:06.789+01:00", into seconds since the Unix epoch.
+// Fractions of a second are ignored.
+fn parse_date_time(s: &str) -> Option<i64> {
+    let bytes = s.as_bytes();
+    if bytes.len() < 20
+        || !bytes[..19].is_ascii()
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || bytes[10] != b'T'
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return None;
+    }
+
+    let year = digits(&s[0..4])?;
+    let month = digits(&s[5..7])?;
+    let day = digits(&s[8..10])?;
+    let hour = digits(&s[11..13])?;
+    let minute = digits(&s[14..16])?;
+    // Leap seconds are allowed.
+    let second = digits(&s[17..19])?;
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    let mut rest = &s[19..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let len = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if len == 0 {
+            return None;
+        }
+        rest = &fraction[len..];
+    }
+
+    let offset = match rest {
+        "Z" => 0,
+        _ if rest.len() == 6 && rest.is_ascii() && rest.as_bytes()[3] == b':' => {
+            let offset = digits(&rest[1..3])? * 3600 + digits(&rest[4..6])? * 60;
+            match rest.as_bytes()[0] {
+                b'+' => offset,
+                b'-' => -offset,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    let days = days_from_civil(year, month, day);
+    Some(days * 86_400 + hour * 3600 + minute * 60 + second - offset)
+}
+
+// Parses a number made of only ASCII digits.
+fn digits(s: &str) -> Option<i64> {
+    if !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Converts a date in the proleptic Gregorian calendar to a number of days
+// since 1970-01-01; the inverse of decoders::civil_from_days.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+// Milliseconds since the Unix epoch are only recognized between these
+// times (2001-09-09 and 2100-01-01), so that they have 13 digits, and
+// other large numbers, like IDs, are less likely to be mistaken for them.
+const MIN_EPOCH_MILLIS: i64 = 1_000_000_000_000;
+const MAX_EPOCH_MILLIS: i64 = 4_102_444_800_000;
+
+// Parses an integer number of milliseconds since the Unix epoch into
+// seconds since the epoch.
+fn parse_epoch_millis(s: &str) -> Option<i64> {
+    let millis = digits(s)?;
+    if !(MIN_EPOCH_MILLIS..MAX_EPOCH_MILLIS).contains(&millis) {
+        return None;
+    }
+    Some(millis / 1000)
+}
+
+// Describes a time relative to the current time, e.g., "3 days ago" or
+// "in 2 hours", in the largest unit that it's at least one of.
+fn relative_time(secs: i64, now: i64) -> String {
+    const UNITS: &[(i64, &str)] = &[
+        (365 * 86_400, "year"),
+        (30 * 86_400, "month"),
+        (86_400, "day"),
+        (3600, "hour"),
+        (60, "minute"),
+    ];
+
+    let diff = now - secs;
+    let (size, unit) = match UNITS.iter().find(|(size, _)| diff.abs() >= *size) {
+        Some(unit) => unit,
+        None => return "just now".to_owned(),
+    };
+
+    let count = diff.abs() / size;
+    let plural = if count == 1 { "" } else { "s" };
+    if diff > 0 {
+        format!("{} {}{} ago", count, unit, plural)
+    } else {
+        format!("in {} {}{}", count, unit, plural)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2021-02-03T04:05:06Z
+    const NOW: i64 = 1_612_325_106;
+
+    #[test]
+    fn test_parse_date_time() {
+        assert_eq!(Some(NOW), parse_date_time("2021-02-03T04:05:06Z"));
+        assert_eq!(Some(NOW), parse_date_time("2021-02-03T04:05:06.789Z"));
+        assert_eq!(Some(NOW), parse_date_time("2021-02-03T05:05:06+01:00"));
+        assert_eq!(Some(NOW), parse_date_time("2021-02-02T23:35:06-04:30"));
+        assert_eq!(Some(0), parse_date_time("1970-01-01T00:00:00Z"));
+        assert_eq!(Some(951_868_799), parse_date_time("2000-02-29T23:59:59Z"));
+
+        // Without a time zone, it could be in any of them.
+        assert_eq!(None, parse_date_time("2021-02-03T04:05:06"));
+        assert_eq!(None, parse_date_time("2021-02-03"));
+        assert_eq!(None, parse_date_time("2021-02-03 04:05:06Z"));
+        assert_eq!(None, parse_date_time("2021-02-29T04:05:06Z"));
+        assert_eq!(None, parse_date_time("2021-13-03T04:05:06Z"));
+        assert_eq!(None, parse_date_time("2021-02-03T24:05:06Z"));
+        assert_eq!(None, parse_date_time("2021-02-03T04:05:06.Z"));
+        assert_eq!(None, parse_date_time("2021-02-03T04:05:06+0100"));
+        assert_eq!(None, parse_date_time("2021-02-03T04:05:06Z, later"));
+        assert_eq!(None, parse_date_time("+021-02-03T04:05:06Z"));
+        assert_eq!(None, parse_date_time("2021-02-03T04:05:0日本Z"));
+    }
+
+    #[test]
+    fn test_relative_time() {
+        assert_eq!("just now", relative_time(NOW - 59, NOW));
+        assert_eq!("1 minute ago", relative_time(NOW - 60, NOW));
+        assert_eq!("2 hours ago", relative_time(NOW - 3 * 3600 + 1, NOW));
+        assert_eq!("3 days ago", relative_time(NOW - 3 * 86_400, NOW));
+        assert_eq!("10 months ago", relative_time(NOW - 300 * 86_400, NOW));
+        assert_eq!("2 years ago", relative_time(NOW - 800 * 86_400, NOW));
+        assert_eq!("in 1 day", relative_time(NOW + 86_400, NOW));
+    }
+
+    #[test]
+    fn test_annotate() {
+        let annotator = Annotator {
+            formats: vec![AnnotationFormat::Dates, AnnotationFormat::EpochMillis],
+            now: NOW,
+        };
+        assert_eq!(
+            Some(" (3 days ago)".to_owned()),
+            annotator.annotate(Scalar::String("2021-01-31T04:05:06Z"))
+        );
+        assert_eq!(
+            Some(" (2021-02-03T04:05:06.789Z, just now)".to_owned()),
+            annotator.annotate(Scalar::Number("1612325106789"))
+        );
+
+        // Seconds since the epoch are too easily confused with other
+        // numbers, and numbers in strings aren't annotated.
+        assert_eq!(None, annotator.annotate(Scalar::Number("1612325106")));
+        assert_eq!(None, annotator.annotate(Scalar::Number("99999999999999")));
+        assert_eq!(None, annotator.annotate(Scalar::Number("-1612325106789")));
+        assert_eq!(None, annotator.annotate(Scalar::Number("1612325106789.5")));
+        assert_eq!(None, annotator.annotate(Scalar::String("1612325106789")));
+        assert_eq!(None, annotator.annotate(Scalar::Number("2021")));
+
+        let annotator = Annotator {
+            formats: vec![AnnotationFormat::EpochMillis],
+            now: NOW,
+        };
+        assert_eq!(
+            None,
+            annotator.annotate(Scalar::String("2021-01-31T04:05:06Z"))
+        );
+    }
+}
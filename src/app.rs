@@ -1,34 +1,477 @@
+use std::cmp::Ordering;
+use std::collections::VecDeque;
 use std::error::Error;
+use std::fs::File;
 use std::io;
-use std::io::Write;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, TryRecvError};
+use std::time::{Duration, Instant, SystemTime};
 
 use clipboard::{ClipboardContext, ClipboardProvider};
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 use termion::event::Key;
-use termion::event::MouseButton::{Left, WheelDown, WheelUp};
+use termion::event::MouseButton::Left;
 use termion::event::MouseEvent::Press;
 use termion::screen::{ToAlternateScreen, ToMainScreen};
 
+use crate::annotations::Annotator;
+use crate::csvparser::CsvOptions;
+use crate::datamodel::DataModel;
+use crate::diff::{Diff, MatchedRows};
+use crate::export;
+use crate::export::ExportFormat;
 use crate::flatjson;
 use crate::input::TuiEvent;
 use crate::input::TuiEvent::{KeyEvent, MouseEvent, WinChEvent};
+use crate::jq;
+use crate::jsonparser;
+use crate::jsonparser::ParseEvent;
+use crate::keybindings;
+use crate::keybindings::{BoundKey, KeyCommand, Keybindings, Lookup, MacroStep};
 use crate::lineprinter::JS_IDENTIFIER;
-use crate::options::{DataFormat, Opt};
+use crate::mmap::MappedFile;
+use crate::ndjson;
+use crate::options::{
+    ClipboardBackend, DataFormat, FormatSource, Indent, LineNumbers, NumberBase, Opt, PathFormat,
+    ToggleScope, ValueFormat, YamlAliases,
+};
+use crate::osc52;
+use crate::palette::{Palette, PaletteEntry, PaletteEvent};
+use crate::progress;
+use crate::progress::{Progress, ProgressLine, PROGRESS_INTERVAL, SPINNER};
+use crate::prompt::{Prompt, PromptEvent};
 use crate::screenwriter::{MessageSeverity, ScreenWriter};
-use crate::search::{JumpDirection, SearchDirection, SearchState};
+use crate::search::{JumpDirection, SearchDirection, SearchHistory, SearchState, SearchStep};
+use crate::statecache::{FileVersion, StateCache};
+use crate::theme::Theme;
 use crate::types::TTYDimensions;
-use crate::viewer::{Action, JsonViewer, Mode};
+use crate::viewer::{Action, HiddenValues, JsonViewer, Mode};
 
 pub struct App {
     viewer: JsonViewer,
     screen_writer: ScreenWriter,
     input_state: InputState,
     input_buffer: Vec<u8>,
+    keybindings: Keybindings,
+    // The keys typed so far of a binding that requires a sequence of
+    // keys, e.g., 'zt', and the count entered before them, e.g., the 2 in
+    // '2zM'.
+    pending_keys: Vec<BoundKey>,
+    pending_count: Option<usize>,
+    // The first line of the list of keybindings shown, while it's shown.
+    cheatsheet_top: usize,
+    // The steps of a macro that haven't been run yet, and the line to
+    // enter at the ':' prompt for the step being run, if it's a command.
+    macro_steps: VecDeque<MacroStep>,
+    macro_command_line: Option<String>,
+    // With --incsearch, the search whose pattern is being typed.
+    incremental_search: Option<IncrementalSearch>,
+    // The command palette, while it's open.
+    palette: Option<Palette>,
     input_filename: String,
     search_state: SearchState,
+    search_history: SearchHistory,
     message: Option<(String, MessageSeverity)>,
     clipboard_context: Result<ClipboardContext, Box<dyn Error>>,
+    // Copy using OSC 52 escape sequences instead of the system clipboard.
+    use_osc52: bool,
+    // Path format used by 'yp'.
+    path_format: PathFormat,
+    // Views that have been replaced by the output of a jq program, along
+    // with their filenames, which are restored by ':undo'.
+    previous_views: Vec<(JsonViewer, String)>,
+    // Receives more rows while the input is still being parsed in the
+    // background.
+    loading: Option<Receiver<ParseEvent>>,
+    loading_progress: Progress,
+    loading_ticks: usize,
+    // When viewing multiple files, the state of each file other than the
+    // current one, which is None.
+    files: Vec<Option<FileState>>,
+    current_file: usize,
+    // Where each file was read from, for saving its state on exit with
+    // --remember-state.
+    file_paths: Vec<Option<PathBuf>>,
+    state_cache: StateCache,
+    // With --watch, the file being viewed, which is reloaded when it
+    // changes.
+    watched: Option<WatchedFile>,
+    // With --follow, the file being viewed, which is appended to when
+    // records are added to it.
+    followed: Option<FollowedFile>,
+    source: Option<InputSource>,
+    // With --diff, how the rows of the two files match up, for showing the
+    // same rows in both.
+    matched_rows: MatchedRows,
+    // Used when reloading files.
+    opt: Opt,
+    // The size of the screen when rendering to a string (see
+    // App::new_offscreen) instead of to the terminal, which isn't used at
+    // all then.
+    offscreen: Option<TTYDimensions>,
+}
+
+/// The node that was focused when quitting, which is printed after jless
+/// exits.
+pub struct Selection {
+    /// The path to the node, for --print-path-on-exit.
+    pub path: Option<String>,
+    /// The node's value as JSON, with --print-value-on-exit, or after
+    /// quitting with 'ZP', or why it can't be printed, i.e., that it
+    /// contains values nested too deeply to be parsed.
+    pub value: Option<Result<String, String>>,
+}
+
+// An input to view, which has been read but not parsed yet.
+pub struct InputFile {
+    data: String,
+    data_format: DataFormat,
+    format_source: FormatSource,
+    filename: String,
+    // Where the input was read from, unless it was STDIN.
+    path: Option<PathBuf>,
+    // Set instead of data for values that have already been parsed (see
+    // InputFile::from_value).
+    parsed: Option<flatjson::FlatJson>,
+    // Set instead of data for files that are memory mapped with --mmap.
+    mapped: Option<MappedFile>,
+    replacements: Replacements,
+}
+
+impl InputFile {
+    /// An input that's been read into memory. The path is None if the
+    /// input was read from STDIN.
+    pub fn new(
+        data: String,
+        data_format: DataFormat,
+        format_source: FormatSource,
+        filename: String,
+        path: Option<PathBuf>,
+    ) -> InputFile {
+        InputFile {
+            data,
+            data_format,
+            format_source,
+            filename,
+            path,
+            parsed: None,
+            mapped: None,
+            replacements: Replacements::default(),
+        }
+    }
+
+    /// Where invalid UTF-8 in the data was replaced when it was decoded by
+    /// decode_input, for showing where values are in the original input.
+    pub fn with_replacements(mut self, replacements: Replacements) -> InputFile {
+        self.replacements = replacements;
+        self
+    }
+
+    /// A file that's been memory mapped with --mmap.
+    pub fn mapped(
+        mapped: MappedFile,
+        data_format: DataFormat,
+        format_source: FormatSource,
+        filename: String,
+        path: Option<PathBuf>,
+    ) -> InputFile {
+        InputFile {
+            mapped: Some(mapped),
+            ..InputFile::new(String::new(), data_format, format_source, filename, path)
+        }
+    }
+
+    /// An input that's already been parsed, e.g., by a program embedding
+    /// jless. The name is shown in place of a filename.
+    pub fn from_value<M: DataModel>(model: &M, value: &M::Value, name: &str) -> InputFile {
+        InputFile {
+            data: String::new(),
+            data_format: DataFormat::Json,
+            format_source: FormatSource::Flag,
+            filename: name.to_owned(),
+            path: None,
+            parsed: Some(flatjson::FlatJson::from_value(model, value)),
+            mapped: None,
+            replacements: Replacements::default(),
+        }
+    }
+
+    #[cfg(feature = "serde_json")]
+    pub fn from_serde_json(value: &serde_json::Value, name: &str) -> InputFile {
+        InputFile::from_value(&crate::datamodel::SerdeJson, value, name)
+    }
+
+    pub fn data_format(&self) -> DataFormat {
+        self.data_format
+    }
+
+    /// The contents of the input, which are copied if the input is memory
+    /// mapped.
+    pub fn into_data(self) -> String {
+        match self.mapped {
+            Some(mapped) => mapped.as_str().to_owned(),
+            None => self.data,
+        }
+    }
+}
+
+// The warning shown when objects and arrays in the input were replaced by
+// a placeholder because they were nested too deeply.
+fn too_deep_message(max_depth: usize) -> String {
+    format!(
+        "Values nested more than {} levels deep aren't shown (see --max-depth)",
+        max_depth
+    )
+}
+
+// Converts the contents of an input to a String, replacing any invalid
+// UTF-8 with the replacement character rather than refusing to show it,
+// along with where the replacements are.
+pub fn decode_input(data: Vec<u8>) -> (String, Replacements) {
+    let data = match String::from_utf8(data) {
+        Ok(text) => return (text, Replacements::default()),
+        Err(err) => err.into_bytes(),
+    };
+
+    let mut text = String::with_capacity(data.len());
+    let mut replacements = vec![];
+    let mut rest = &data[..];
+    let mut original_offset = 0;
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => {
+                text.push_str(valid);
+                return (text, Replacements(replacements));
+            }
+            Err(err) => {
+                let (valid, invalid) = rest.split_at(err.valid_up_to());
+                text.push_str(std::str::from_utf8(valid).unwrap());
+                text.push(std::char::REPLACEMENT_CHARACTER);
+                // Like String::from_utf8_lossy, each invalid sequence is
+                // replaced by a single replacement character.
+                let invalid_len = err.error_len().unwrap_or(invalid.len());
+                original_offset += valid.len() + invalid_len;
+                replacements.push((text.len(), original_offset));
+                rest = &invalid[invalid_len..];
+            }
+        }
+    }
+}
+
+/// Where invalid UTF-8 in an input was replaced by decode_input, so that
+/// the byte offsets of values in the decoded text can be mapped back to
+/// where they are in the original input.
+#[derive(Clone, Debug, Default)]
+pub struct Replacements(
+    // The offsets just after each replacement character, in the decoded
+    // text and in the original input.
+    Vec<(usize, usize)>,
+);
+
+impl Replacements {
+    // The offset in the original input of an offset in the decoded text
+    // that isn't inside a replacement character.
+    fn original_offset(&self, offset: usize) -> usize {
+        match self.0.partition_point(|&(decoded, _)| decoded <= offset) {
+            0 => offset,
+            i => {
+                let (decoded, original) = self.0[i - 1];
+                original + (offset - decoded)
+            }
+        }
+    }
+
+    fn original_range(&self, range: &Range<usize>) -> Range<usize> {
+        self.original_offset(range.start)..self.original_offset(range.end)
+    }
+}
+
+// The state of a file being viewed, which is kept while viewing another
+// file.
+struct FileState {
+    viewer: JsonViewer,
+    input_filename: String,
+    search_state: SearchState,
+    previous_views: Vec<(JsonViewer, String)>,
+    loading: Option<Receiver<ParseEvent>>,
+    loading_progress: Progress,
+    watched: Option<WatchedFile>,
+    followed: Option<FollowedFile>,
+    source: Option<InputSource>,
+}
+
+// What an input was parsed as, and how to read it again, for parsing it as
+// another format with :format. Inputs that were already parsed (see
+// InputFile::from_value) don't have one.
+struct InputSource {
+    data_format: DataFormat,
+    format_source: FormatSource,
+    filename: String,
+    path: Option<PathBuf>,
+    // Inputs that can't be read again, i.e., STDIN and URLs, are kept,
+    // unless their format was chosen with a flag.
+    data: Option<String>,
+    replacements: Replacements,
+}
+
+impl InputSource {
+    // The (decoded) input up to the given byte of it, for finding where a
+    // value in it is, or None if it wasn't kept and can't be read again.
+    fn read_up_to(&self, end: usize) -> Result<Option<String>, String> {
+        if let Some(data) = &self.data {
+            return Ok(data.get(..end).map(str::to_owned));
+        }
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        // The file is read as it was when it was decoded, so that invalid
+        // UTF-8 is replaced the same way.
+        let original_end = self.replacements.original_offset(end);
+        let mut data = vec![];
+        File::open(path)
+            .and_then(|file| file.take(original_end as u64).read_to_end(&mut data))
+            .map_err(|err| format!("Unable to read {}: {}", self.filename, err))?;
+        let (mut text, _) = decode_input(data);
+        if text.len() < end || !text.is_char_boundary(end) {
+            return Err(format!("{} has changed since it was read", self.filename));
+        }
+        text.truncate(end);
+        Ok(Some(text))
+    }
+}
+
+struct WatchedFile {
+    path: PathBuf,
+    data_format: DataFormat,
+    // Used to detect when the file has changed.
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+// A search whose pattern is still being typed, which shows the first match
+// of the pattern typed so far.
+struct IncrementalSearch {
+    prompt: Prompt,
+    direction: SearchDirection,
+    jumps: usize,
+    // Where the focus was, and the previous search, before the search
+    // started, which are restored if it's cancelled.
+    focused_row: flatjson::Index,
+    top_row: flatjson::Index,
+    search_state: SearchState,
+    // Set when the pattern has changed, but the match shown hasn't been
+    // updated yet.
+    preview_pending: bool,
+}
+
+// Searching inputs larger than this can take long enough to make typing
+// feel sluggish, so incremental searches over them are only updated once
+// typing pauses.
+const INCREMENTAL_SEARCH_DEBOUNCE_SIZE: usize = 4 * 1024 * 1024;
+
+// A JSON Lines file that's being followed via --follow, like 'tail -f'.
+struct FollowedFile {
+    file: File,
+    filename: String,
+    // The start of a line that hasn't been completely written yet.
+    partial_line: Vec<u8>,
+    num_lines: usize,
+    num_records: usize,
+    num_malformed: usize,
+}
+
+impl FollowedFile {
+    // Starts following a file after the data that's already been read
+    // from it. If the data ends in the middle of a line, that part of it
+    // is removed, and will be parsed once the rest of the line is written.
+    fn new(path: &Path, data: &mut String, filename: &str) -> Result<FollowedFile, String> {
+        let open_error = |err: io::Error| format!("Unable to follow {}: {}", filename, err);
+
+        let mut file = File::open(path).map_err(open_error)?;
+        file.seek(SeekFrom::Start(data.len() as u64))
+            .map_err(open_error)?;
+
+        let complete_len = data.rfind('\n').map_or(0, |i| i + 1);
+        let partial_line = data.as_bytes()[complete_len..].to_vec();
+        data.truncate(complete_len);
+
+        Ok(FollowedFile {
+            file,
+            filename: filename.to_owned(),
+            partial_line,
+            num_lines: data.matches('\n').count(),
+            num_records: 0,
+            num_malformed: 0,
+        })
+    }
+
+    // Reads any complete lines that have been added to the file, as an
+    // array of their records.
+    fn read_new_lines(&mut self, max_depth: usize) -> io::Result<Option<flatjson::FlatJson>> {
+        let mut new_data = vec![];
+        self.file.read_to_end(&mut new_data)?;
+        self.partial_line.extend_from_slice(&new_data);
+
+        let complete_len = match self.partial_line.iter().rposition(|&b| b == b'\n') {
+            Some(i) => i + 1,
+            None => return Ok(None),
+        };
+        let complete: Vec<u8> = self.partial_line.drain(..complete_len).collect();
+        let text = String::from_utf8_lossy(&complete);
+
+        let lines = ndjson::parse_lines_starting_at(&text, self.num_lines + 1, max_depth);
+        let (num_records, num_malformed) = ndjson::count_records(&lines);
+        self.num_lines += text.matches('\n').count();
+        self.num_records += num_records;
+        self.num_malformed += num_malformed;
+
+        Ok(Some(lines).filter(|_| num_records > 0))
+    }
+
+    fn filename_with_summary(&self) -> String {
+        format!(
+            "{} ({})",
+            self.filename,
+            ndjson::summary(self.num_records, self.num_malformed)
+        )
+    }
+}
+
+impl WatchedFile {
+    fn new(path: PathBuf, data_format: DataFormat) -> WatchedFile {
+        let mut watched = WatchedFile {
+            path,
+            data_format,
+            modified: None,
+            len: 0,
+        };
+        watched.changed();
+        watched
+    }
+
+    // Returns whether the file has changed since the last time this was
+    // called. If the file can't be accessed (e.g., because it's being
+    // replaced), it isn't considered changed until it can be.
+    fn changed(&mut self) -> bool {
+        let metadata = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(_) => return false,
+        };
+
+        let modified = metadata.modified().ok();
+        if modified == self.modified && metadata.len() == self.len {
+            return false;
+        }
+
+        self.modified = modified;
+        self.len = metadata.len();
+        true
+    }
 }
 
 // State to determine how to process the next event input.
@@ -43,23 +486,97 @@ pub struct App {
 #[derive(PartialEq)]
 enum InputState {
     Default,
-    PendingYCommand,
-    PendingZCommand,
+    PendingSetMark,
+    PendingJumpToMark,
+    ShowingKeybindings,
+    ShowingStats,
+    // Left and right scroll the focused value a chunk at a time, until
+    // any other key is pressed.
+    ScrubbingValue,
+    // With --confirm-quit, waiting for 'y' to confirm quitting.
+    ConfirmingQuit,
+}
+
+const SCRUBBING_MESSAGE: &str = "Scrubbing: ←/→ or h/l to scroll, any other key to stop";
+const CONFIRM_QUIT_MESSAGE: &str = "Quit jless? (y/n)";
+
+fn terminal_dimensions() -> Option<TTYDimensions> {
+    termion::terminal_size().ok().map(TTYDimensions::from_size)
+}
+
+fn is_scrubbing_key(event: &TuiEvent) -> bool {
+    matches!(
+        event,
+        KeyEvent(Key::Left | Key::Right | Key::Char('h') | Key::Char('l'))
+    )
 }
 
 // Various things that can be copied
 enum CopyTarget {
     PrettyPrintedValue,
     OneLineValue,
+    // The value as it's displayed: with keys sorted, and without values
+    // that are hidden or filtered out.
+    DisplayedValue,
+    DecodedValue,
     Key,
     DotPath,
     BracketPath,
     QueryPath,
+    GronPath,
+    PlainPath,
+    // The text of the value in the input, exactly as it was written.
+    Source,
+}
+
+impl CopyTarget {
+    fn path(format: PathFormat) -> CopyTarget {
+        match format {
+            PathFormat::Dot => CopyTarget::DotPath,
+            PathFormat::Bracket => CopyTarget::BracketPath,
+            PathFormat::Query => CopyTarget::QueryPath,
+            PathFormat::Gron => CopyTarget::GronPath,
+            PathFormat::Plain => CopyTarget::PlainPath,
+        }
+    }
 }
 
 enum Command {
     Quit,
     Help,
+    JumpToLine(usize),
+    Marks,
+    Stats,
+    // Write the input to a file, as JSON, or the format given. The whole
+    // input is written as it was originally, rather than as displayed, if
+    // whole_input is set.
+    Export {
+        include_collapsed: bool,
+        whole_input: bool,
+        format: Option<String>,
+    },
+    // Collapse or expand containers with matching keys. If the key pattern
+    // isn't provided, the user will be prompted for it.
+    SetCollapsedByKey {
+        collapsed: bool,
+        pattern: Option<String>,
+    },
+    // Replace the input with the output of a jq program. If the program
+    // isn't provided, the user will be prompted for it.
+    Jq {
+        program: Option<String>,
+    },
+    Undo,
+    ClearState,
+    // Set how many rows to keep visible around the focused row. If the
+    // number of rows isn't provided, the current setting is shown.
+    SetScrolloff(Option<String>),
+    // Run a macro defined in the keybindings file. If the name isn't
+    // provided, the names of the macros are shown.
+    RunMacro(Option<String>),
+    // Parse the input again as another format. If the format isn't
+    // provided, the format the input was parsed as is shown.
+    SetFormat(Option<String>),
     Unknown,
 }
 
@@ -69,51 +586,707 @@ const HELP: &str = std::include_str!("./jless.help");
 pub const MAX_BUFFER_SIZE: usize = 9;
 const BELL: &str = "\x07";
 
+// How often to check for more rows while the input is being parsed,
+// whether watched files have changed, and whether records have been added
+// to followed files.
+pub const TICK_MS: u16 = 100;
+// How long to wait for the whole input to be parsed before showing the
+// part that's been parsed so far.
+const INITIAL_PARSE_WAIT: Duration = Duration::from_millis(200);
+
 impl App {
-    pub fn new(
+    pub fn new(opt: &Opt, inputs: Vec<InputFile>, stdout: Box<dyn Write>) -> Result<App, String> {
+        Self::init(opt, inputs, stdout, None)
+    }
+
+    /// An app that renders a screen of the given size, e.g., to a buffer,
+    /// without using the terminal. The inputs are completely loaded first,
+    /// so that the same screen is always rendered.
+    pub fn new_offscreen(
         opt: &Opt,
-        data: String,
-        data_format: DataFormat,
-        input_filename: String,
+        inputs: Vec<InputFile>,
         stdout: Box<dyn Write>,
+        width: u16,
+        height: u16,
     ) -> Result<App, String> {
-        let flatjson = match Self::parse_input(data, data_format) {
-            Ok(flatjson) => flatjson,
-            Err(err) => return Err(format!("Unable to parse input: {:?}", err)),
+        let dimensions = TTYDimensions::from_size((width, height));
+        Self::init(opt, inputs, stdout, Some(dimensions))
+    }
+
+    fn init(
+        opt: &Opt,
+        inputs: Vec<InputFile>,
+        mut stdout: Box<dyn Write>,
+        offscreen: Option<TTYDimensions>,
+    ) -> Result<App, String> {
+        // Followed files always start at the end.
+        let state_cache = StateCache::new(
+            StateCache::default_file(),
+            opt.remember_state && !opt.no_remember_state && !opt.follow,
+        );
+
+        if opt.diff && inputs.len() != 2 {
+            return Err("--diff requires exactly two files".to_string());
+        }
+
+        let multiple_files = inputs.len() > 1;
+        let mut files = vec![];
+        let mut file_paths = vec![];
+        for input in inputs {
+            let filename = input.filename.clone();
+            let path = input.path.clone();
+            let saved_state = match &path {
+                Some(path) if state_cache.enabled() => FileVersion::of(path)
+                    .and_then(|version| state_cache.get(&version))
+                    .cloned(),
+                _ => None,
+            };
+
+            // Restoring the saved state, and comparing files, requires all
+            // the rows.
+            let wait_for_all = saved_state.is_some() || opt.diff || offscreen.is_some();
+            match Self::load_file(opt, input, &mut *stdout, wait_for_all) {
+                Ok(mut file) => {
+                    if let Some(mut state) = saved_state {
+                        // --depth has already collapsed the containers, but
+                        // the focused row is still restored.
+                        if opt.depth.is_some() {
+                            state.collapsed.clear();
+                        }
+                        if let Some(dimensions) = offscreen.or_else(terminal_dimensions) {
+                            file.viewer.dimensions = dimensions.without_status_bar();
+                        }
+                        file.viewer.restore_saved_state(&state);
+                    }
+                    files.push(Some(file));
+                    file_paths.push(path);
+                }
+                Err(err) if multiple_files => return Err(format!("{}: {}", filename, err)),
+                Err(err) => return Err(err),
+            }
+        }
+        let diff = match (opt.diff, &files[..]) {
+            (true, [Some(old), Some(new)]) => {
+                Some(Diff::new(&old.viewer.flatjson, &new.viewer.flatjson))
+            }
+            _ => None,
         };
+        let file = files[0].take().unwrap();
 
-        let mut viewer = JsonViewer::new(flatjson, opt.mode);
-        viewer.scrolloff_setting = opt.scrolloff;
+        let search_history_file = if opt.no_search_history_file {
+            None
+        } else {
+            SearchHistory::default_file()
+        };
 
-        let screen_writer =
+        let mut screen_writer =
             ScreenWriter::init(stdout, Editor::<()>::new(), TTYDimensions::default());
+        screen_writer.kitty_keyboard = opt.kitty_keyboard;
+        screen_writer.mouse_reporting = !opt.no_mouse;
+        screen_writer.wrap_strings = opt.wrap;
+        screen_writer.show_sizes = opt.show_sizes;
+        screen_writer.show_types = opt.show_types;
+        screen_writer.annotate = opt.annotate;
+        screen_writer.annotator = Annotator::new(opt.annotate_formats.clone());
+        screen_writer.align_values = opt.align_values;
+        screen_writer.line_numbers = opt.line_numbers;
+        screen_writer.truncation = opt.truncate;
+        screen_writer.ellipsis = opt.ellipsis;
+        screen_writer.max_value_width = Some(opt.max_value_width).filter(|width| *width > 0);
+        screen_writer.number_base = opt.number_base;
+        screen_writer.show_minimap = opt.minimap;
+        screen_writer.fold_column = opt.fold_column;
+        let message = match &diff {
+            _ if file.viewer.flatjson.reached_max_depth() => {
+                Some((too_deep_message(opt.max_depth), MessageSeverity::Warn))
+            }
+            Some(diff) if diff.is_empty() => {
+                Some(("The files are the same".to_string(), MessageSeverity::Info))
+            }
+            _ => None,
+        };
+        screen_writer.diff = diff;
+        screen_writer.indent_width = opt.indent.width();
+        screen_writer.indent_guides = opt.indent_guides || opt.indent == Indent::Tabs;
+        screen_writer.theme = if opt.no_color || Theme::no_color_requested() {
+            Theme::monochrome()
+        } else {
+            Theme::load(opt.theme.as_deref())?
+        };
+        let keybindings = Keybindings::load(opt.keybindings.as_deref())?;
+
+        let clipboard_context = ClipboardProvider::new();
+        let use_osc52 = match opt.clipboard {
+            ClipboardBackend::System => false,
+            ClipboardBackend::Osc52 => true,
+            ClipboardBackend::Auto => osc52::is_remote_session() || clipboard_context.is_err(),
+        };
 
         Ok(App {
-            viewer,
+            viewer: file.viewer,
             screen_writer,
             input_state: InputState::Default,
             input_buffer: vec![],
+            keybindings,
+            pending_keys: vec![],
+            pending_count: None,
+            cheatsheet_top: 0,
+            macro_steps: VecDeque::new(),
+            macro_command_line: None,
+            incremental_search: None,
+            palette: None,
+            input_filename: file.input_filename,
+            search_state: file.search_state,
+            search_history: SearchHistory::new(search_history_file),
+            message,
+            clipboard_context,
+            use_osc52,
+            path_format: opt.path_format,
+            previous_views: file.previous_views,
+            loading: file.loading,
+            loading_progress: file.loading_progress,
+            loading_ticks: 0,
+            files,
+            current_file: 0,
+            file_paths,
+            state_cache,
+            watched: file.watched,
+            followed: file.followed,
+            source: file.source,
+            matched_rows: MatchedRows::default(),
+            opt: opt.clone(),
+            offscreen,
+        })
+    }
+
+    // Parses an input, drawing the progress to stdout while waiting. Large
+    // JSON inputs will still be loading afterwards unless wait_for_all is
+    // set.
+    fn load_file(
+        opt: &Opt,
+        input: InputFile,
+        stdout: &mut dyn Write,
+        wait_for_all: bool,
+    ) -> Result<FileState, String> {
+        let InputFile {
+            mut data,
+            data_format,
+            format_source,
+            filename: mut input_filename,
+            path,
+            parsed,
+            mapped,
+            replacements,
+        } = input;
+
+        // Only JSON is parsed straight from the memory mapped file; other
+        // formats, and followed files, which are appended to, are copied.
+        let mapped = match mapped {
+            Some(mapped) if data_format == DataFormat::Json && !opt.follow => Some(mapped),
+            Some(mapped) => {
+                data = mapped.as_str().to_owned();
+                None
+            }
+            None => None,
+        };
+
+        let source = if parsed.is_none() {
+            let keep_data = path.is_none() && format_source != FormatSource::Flag;
+            Some(InputSource {
+                data_format,
+                format_source,
+                filename: input_filename.clone(),
+                path: path.clone(),
+                data: if keep_data { Some(data.clone()) } else { None },
+                replacements,
+            })
+        } else {
+            None
+        };
+
+        let mut followed = None;
+        let watched = match path {
+            Some(path) if opt.follow => {
+                if data_format != DataFormat::Ndjson {
+                    return Err(
+                        "--follow can only be used with JSON Lines input (see --jsonl)".to_string(),
+                    );
+                }
+                followed = Some(FollowedFile::new(&path, &mut data, &input_filename)?);
+                None
+            }
+            Some(path) if opt.watch => Some(WatchedFile::new(path, data_format)),
+            _ => None,
+        };
+
+        // Parsing a large input can take a while, so show how much has been
+        // parsed so far, both while waiting to show the viewer, and while
+        // the rest of the input is being loaded.
+        let loading_progress = Progress::new(Some(
+            mapped
+                .as_ref()
+                .map_or(data.len(), |mapped| mapped.as_str().len()),
+        ));
+        let mut progress_line = ProgressLine::new(stdout, format!("Parsing {}", input_filename));
+
+        // Large JSON inputs are parsed in the background so that the start
+        // of the input can be viewed without waiting for the rest. (Sorting
+        // keys, and collapsing containers with --depth, requires all the
+        // rows, though.)
+        let mut loading = None;
+        let mut viewer = if let Some(flatjson) = parsed {
+            JsonViewer::new(flatjson, opt.mode)
+        } else if data_format == DataFormat::Json {
+            let receiver = match mapped {
+                Some(mapped) => jsonparser::parse_in_background(mapped, opt.max_depth),
+                None => jsonparser::parse_in_background(data, opt.max_depth),
+            };
+            let mut viewer =
+                JsonViewer::new(flatjson::FlatJson(vec![], String::new(), 0), opt.mode);
+            let parsed_all = Self::receive_initial_rows(
+                &mut viewer,
+                &receiver,
+                &loading_progress,
+                &mut progress_line,
+                wait_for_all || opt.sort_keys || opt.depth.is_some() || followed.is_some(),
+            )?;
+            if !parsed_all {
+                loading = Some(receiver);
+            }
+            viewer
+        } else {
+            // Other formats aren't parsed incrementally, so all we can show
+            // is how big the input is.
+            let size = progress::format_bytes(data.len());
+            let csv_options = opt.csv_options(if data_format == DataFormat::Tsv {
+                '\t'
+            } else {
+                ','
+            });
+            let yaml_comments = opt.yaml_comments;
+            let max_depth = opt.max_depth;
+            let parsed = progress::run_with_progress(
+                &mut progress_line,
+                move || Self::parse_input(data, data_format, csv_options, yaml_comments, max_depth),
+                || format!("({})", size),
+                Some(flatjson::parser_stack_size(max_depth)),
+            );
+            match parsed {
+                Ok(flatjson) => JsonViewer::new(flatjson, opt.mode),
+                Err(err) => return Err(format!("Unable to parse input: {:?}", err)),
+            }
+        };
+
+        if data_format == DataFormat::Ndjson {
+            let (num_records, num_malformed) = ndjson::count_records(viewer.original_flatjson());
+            input_filename = format!(
+                "{} ({})",
+                input_filename,
+                ndjson::summary(num_records, num_malformed)
+            );
+            if let Some(followed) = &mut followed {
+                followed.num_records = num_records;
+                followed.num_malformed = num_malformed;
+            }
+        }
+
+        viewer.scrolloff_setting = opt.scrolloff;
+        if opt.sort_keys {
+            viewer.perform_action(Action::ToggleSortedKeys);
+        }
+        if let Some(depth) = opt.depth {
+            viewer.perform_action(Action::CollapseToDepth(depth));
+        }
+        if opt.yaml_aliases == YamlAliases::Collapse {
+            viewer.flatjson.collapse_aliases();
+        }
+
+        // Like 'tail -f', start at the end of the file.
+        if followed.is_some() {
+            viewer.perform_action(Action::FocusBottom);
+        }
+
+        let mut search_state = SearchState::empty();
+        search_state.persist_highlights = opt.hlsearch;
+
+        Ok(FileState {
+            viewer,
             input_filename,
-            search_state: SearchState::empty(),
-            message: None,
-            clipboard_context: ClipboardProvider::new(),
+            search_state,
+            previous_views: vec![],
+            loading,
+            loading_progress,
+            watched,
+            followed,
+            source,
+        })
+    }
+
+    // Waits for the input to be parsed in the background, drawing its
+    // progress. Unless wait_for_all is set, this only waits until at least
+    // one row has been parsed and INITIAL_PARSE_WAIT has passed. Returns
+    // whether the whole input has been parsed.
+    fn receive_initial_rows(
+        viewer: &mut JsonViewer,
+        receiver: &Receiver<ParseEvent>,
+        progress: &Progress,
+        progress_line: &mut ProgressLine,
+        wait_for_all: bool,
+    ) -> Result<bool, String> {
+        let deadline = Instant::now() + INITIAL_PARSE_WAIT;
+
+        let result = loop {
+            match receiver.recv_timeout(PROGRESS_INTERVAL) {
+                Ok(ParseEvent::Rows(parsed)) => {
+                    progress.set_consumed(parsed.parsed_bytes);
+                    viewer.append_parsed_rows(parsed);
+                }
+                Ok(ParseEvent::Done(Ok(()))) => break Ok(true),
+                Ok(ParseEvent::Done(Err(err))) => {
+                    break Err(format!("Unable to parse input: {:?}", err))
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    break Err("Unable to parse input".to_string())
+                }
+            }
+
+            progress_line.draw(&progress.describe());
+
+            if !wait_for_all && !viewer.flatjson.0.is_empty() && Instant::now() >= deadline {
+                break Ok(false);
+            }
+        };
+
+        progress_line.clear();
+        result
+    }
+
+    fn warn_if_too_deep(&mut self) {
+        if self.viewer.original_flatjson().reached_max_depth() {
+            self.set_warning_message(too_deep_message(self.opt.max_depth));
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loading.is_some()
+            || self
+                .files
+                .iter()
+                .flatten()
+                .any(|file| file.loading.is_some())
+    }
+
+    // Adds any rows that have been parsed in the background since the
+    // last time this was called.
+    fn receive_parsed_rows(&mut self) {
+        while let Some(receiver) = &self.loading {
+            match receiver.try_recv() {
+                Ok(ParseEvent::Rows(parsed)) => {
+                    self.loading_progress.set_consumed(parsed.parsed_bytes);
+                    let parsed_len = self.viewer.parsed_len();
+                    self.viewer.append_parsed_rows(parsed);
+                    // Searches made while loading also find matches in the
+                    // rows parsed afterwards.
+                    self.search_state
+                        .search_appended_rows(&self.viewer.flatjson, parsed_len);
+                }
+                Ok(ParseEvent::Done(result)) => {
+                    if let Err(err) = result {
+                        self.set_error_message(format!(
+                            "Unable to parse the rest of the input: {}",
+                            err
+                        ));
+                    } else {
+                        self.warn_if_too_deep();
+                    }
+                    self.loading = None;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.loading = None,
+            }
+        }
+
+        self.screen_writer.loading_status = if self.loading.is_some() {
+            self.loading_ticks += 1;
+            Some(format!(
+                "{} Loading… {}",
+                SPINNER[self.loading_ticks % SPINNER.len()],
+                self.loading_progress.describe()
+            ))
+        } else {
+            None
+        };
+    }
+
+    // Moves forward (or backward, if offset is negative) through the files
+    // being viewed, wrapping around at the ends.
+    fn switch_files(&mut self, offset: isize) {
+        let num_files = self.files.len() as isize;
+        if num_files == 1 {
+            self.set_warning_message("Only one file is being viewed".to_string());
+            return;
+        }
+
+        let index = (self.current_file as isize + offset).rem_euclid(num_files) as usize;
+        self.switch_to_file(index);
+    }
+
+    // Switches to viewing another file, keeping the state of the current
+    // one so that it's restored when switching back to it.
+    fn switch_to_file(&mut self, index: usize) {
+        if index == self.current_file {
+            return;
+        }
+
+        let file = self.files[index].take().unwrap();
+        let dimensions = self.viewer.dimensions;
+
+        let previous = FileState {
+            viewer: std::mem::replace(&mut self.viewer, file.viewer),
+            input_filename: std::mem::replace(&mut self.input_filename, file.input_filename),
+            search_state: std::mem::replace(&mut self.search_state, file.search_state),
+            previous_views: std::mem::replace(&mut self.previous_views, file.previous_views),
+            loading: std::mem::replace(&mut self.loading, file.loading),
+            loading_progress: std::mem::replace(&mut self.loading_progress, file.loading_progress),
+            watched: std::mem::replace(&mut self.watched, file.watched),
+            followed: std::mem::replace(&mut self.followed, file.followed),
+            source: std::mem::replace(&mut self.source, file.source),
+        };
+        self.files[self.current_file] = Some(previous);
+        self.current_file = index;
+
+        // The window may have been resized, or scrolloff changed, while
+        // viewing the other file.
+        self.viewer.scrolloff_setting = self.opt.scrolloff;
+        self.viewer
+            .perform_action(Action::ResizeViewerDimensions(dimensions));
+        self.screen_writer.reset_row_state();
+        // This also updates the loading status for the new file.
+        self.receive_parsed_rows();
+    }
+
+    // With --watch, reloads the file being viewed if it has changed,
+    // keeping the same rows focused and collapsed. If the new version
+    // can't be parsed, the previous version is still shown. Returns
+    // whether the screen needs to be redrawn.
+    fn reload_if_changed(&mut self) -> bool {
+        let watched = match &mut self.watched {
+            // Wait until the current version is completely loaded.
+            Some(watched) if self.loading.is_none() => watched,
+            _ => return false,
+        };
+        if !watched.changed() {
+            return false;
+        }
+
+        let path = watched.path.clone();
+        let data_format = watched.data_format;
+        let filename = String::from(path.file_name().unwrap().to_string_lossy());
+
+        let (data, replacements) = match std::fs::read(&path) {
+            Ok(data) => decode_input(data),
+            Err(err) => {
+                self.set_warning_message(format!("Unable to reload {}: {}", filename, err));
+                return true;
+            }
+        };
+
+        let format_source = self
+            .source
+            .as_ref()
+            .map_or(FormatSource::Flag, |source| source.format_source);
+        let input = InputFile::new(
+            data,
+            data_format,
+            format_source,
+            filename.clone(),
+            Some(path),
+        )
+        .with_replacements(replacements);
+        let file = match Self::load_file(&self.opt, input, &mut *self.screen_writer.stdout, true) {
+            Ok(file) => file,
+            Err(err) => {
+                self.set_warning_message(format!(
+                    "{} changed, but still showing the previous version. {}",
+                    filename, err
+                ));
+                return true;
+            }
+        };
+
+        // The output of jq programs was based on the previous version of
+        // the input, so it's discarded.
+        let old_viewer = match self.previous_views.first() {
+            Some((viewer, _)) => viewer,
+            None => &self.viewer,
+        };
+        let mut viewer = file.viewer;
+        viewer.restore_state_from(old_viewer);
+        viewer.perform_action(Action::ResizeViewerDimensions(self.viewer.dimensions));
+
+        self.viewer = viewer;
+        self.input_filename = file.input_filename;
+        self.previous_views.clear();
+        self.watched = file.watched;
+        self.source = file.source;
+
+        self.reset_search_state();
+        self.screen_writer.reset_row_state();
+        self.set_info_message(format!("Reloaded {}", filename));
+        self.warn_if_too_deep();
+        true
+    }
+
+    // With --follow, adds any records that have been added to the end of
+    // the file being viewed. Returns whether the screen needs to be
+    // redrawn.
+    fn read_followed_records(&mut self) -> bool {
+        let followed = match &mut self.followed {
+            // Records can't be added while the view is filtered, or is
+            // showing the output of jq; they'll be added afterwards.
+            Some(followed)
+                if self.loading.is_none()
+                    && !self.viewer.filtered()
+                    && self.previous_views.is_empty() =>
+            {
+                followed
+            }
+            _ => return false,
+        };
+
+        let records = match followed.read_new_lines(self.opt.max_depth) {
+            Ok(Some(records)) => records,
+            Ok(None) => return false,
+            Err(err) => {
+                let message = format!("Unable to read {}: {}", followed.filename, err);
+                self.set_error_message(message);
+                return true;
+            }
+        };
+        self.input_filename = followed.filename_with_summary();
+        self.viewer.append_array_elements(records);
+        true
+    }
+
+    // The filename shown in the status bar, along with which file is being
+    // viewed when viewing multiple files.
+    fn displayed_filename(&self) -> String {
+        // Inputs containing a stream of values, rather than a single one,
+        // show how many there are (so far, while they're being loaded).
+        let num_values = self.viewer.original_flatjson().num_top_level_values();
+        let mut filename = if num_values > 1 {
+            format!("{} ({} values)", self.input_filename, num_values)
+        } else {
+            self.input_filename.clone()
+        };
+        // e.g., [YAML: extension], so that it's clear which format the
+        // input was parsed as, and why.
+        if let Some(source) = &self.source {
+            filename = format!(
+                "{} [{}: {}]",
+                filename,
+                source.data_format.name(),
+                source.format_source.label()
+            );
+        }
+
+        if self.files.len() > 1 {
+            format!(
+                "file {}/{}: {}",
+                self.current_file + 1,
+                self.files.len(),
+                filename
+            )
+        } else {
+            filename
+        }
+    }
+
+    // Sorting, filtering, and running jq replace the rows being viewed,
+    // so they can't be done while more rows are still being added.
+    fn still_loading(&mut self) -> bool {
+        if self.loading.is_some() {
+            self.set_warning_message("The input is still being loaded".to_string());
+        }
+        self.loading.is_some()
+    }
+
+    // Parses a whole input at once, for printing it instead of viewing it.
+    pub fn parse_input_file(opt: &Opt, input: InputFile) -> Result<flatjson::FlatJson, String> {
+        if let Some(flatjson) = input.parsed {
+            return Ok(flatjson);
+        }
+
+        let data_format = input.data_format;
+        let data = input.into_data();
+
+        let csv_options = opt.csv_options(if data_format == DataFormat::Tsv {
+            '\t'
+        } else {
+            ','
+        });
+        let flatjson = Self::parse_input(
+            data,
+            data_format,
+            csv_options,
+            opt.yaml_comments,
+            opt.max_depth,
+        )
+        .map_err(|err| format!("Unable to parse input: {:?}", err))?;
+
+        Ok(if opt.sort_keys {
+            flatjson.sorted_by_key().0
+        } else {
+            flatjson
         })
     }
 
-    fn parse_input(data: String, data_format: DataFormat) -> Result<flatjson::FlatJson, String> {
+    fn parse_input(
+        data: String,
+        data_format: DataFormat,
+        csv_options: CsvOptions,
+        yaml_comments: bool,
+        max_depth: usize,
+    ) -> Result<flatjson::FlatJson, String> {
         match data_format {
-            DataFormat::Json => flatjson::parse_top_level_json(data),
-            DataFormat::Yaml => flatjson::parse_top_level_yaml(data),
+            DataFormat::Json => flatjson::parse_top_level_json_to_depth(data, max_depth),
+            DataFormat::Ndjson => Ok(ndjson::parse_lines(&data, max_depth)),
+            DataFormat::Yaml => flatjson::parse_top_level_yaml(data, yaml_comments, max_depth),
+            DataFormat::Toml => flatjson::parse_top_level_toml(data, max_depth),
+            DataFormat::Csv | DataFormat::Tsv => flatjson::parse_top_level_csv(data, csv_options),
         }
     }
 
-    pub fn run(&mut self, input: Box<dyn Iterator<Item = io::Result<TuiEvent>>>) {
-        let dimensions = TTYDimensions::from_size(termion::terminal_size().unwrap());
-        self.viewer.dimensions = dimensions.without_status_bar();
+    // Returns the focused node when quitting, for --print-path-on-exit and
+    // --print-value-on-exit, unless quitting with Ctrl-C or 'ZQ'.
+    pub fn run(
+        &mut self,
+        mut input: Box<dyn Iterator<Item = io::Result<TuiEvent>>>,
+    ) -> Option<Selection> {
+        let dimensions = self.screen_dimensions();
+        self.viewer.perform_action(Action::ResizeViewerDimensions(
+            dimensions.without_status_bar(),
+        ));
         self.screen_writer.dimensions = dimensions;
         self.draw_screen();
 
-        for event in input {
+        // Whether to remember the state of files on exit, with
+        // --remember-state, whether to return the focused node, and
+        // whether to include its value.
+        let mut save_state = true;
+        let mut return_path = true;
+        let mut return_value = self.opt.print_value_on_exit.is_some();
+
+        loop {
+            let event = match self.next_macro_step() {
+                Some(step) => Ok(TuiEvent::MacroStep(step)),
+                None => match input.next() {
+                    Some(event) => event,
+                    None => break,
+                },
+            };
+
             if let Err(io_error) = event {
                 self.set_error_message(format!("Error: {}", io_error));
                 self.draw_status_bar();
@@ -140,66 +1313,138 @@ impl App {
             // Error case checked above.
             let event = event.unwrap();
 
+            // Any other input stops scrubbing through a value, and is then
+            // handled as usual.
+            if self.input_state == InputState::ScrubbingValue
+                && matches!(event, KeyEvent(_) | MouseEvent(_))
+                && !is_scrubbing_key(&event)
+            {
+                self.input_state = InputState::Default;
+            }
+
+            // Any key other than 'y' cancels quitting. Keys other than 'n'
+            // or Escape are then handled as usual, rather than being lost.
+            if self.input_state == InputState::ConfirmingQuit {
+                if let KeyEvent(key) = &event {
+                    self.input_state = InputState::Default;
+                    self.message = None;
+                    match key {
+                        Key::Char('y') | Key::Char('Y') => break,
+                        Key::Char('n') | Key::Char('N') | Key::Esc => {
+                            self.draw_status_bar();
+                            continue;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
             let action = match event {
                 // Put this first so the current input state doesn't get reset
                 // when resizing the window.
                 WinChEvent => {
-                    let dimensions = TTYDimensions::from_size(termion::terminal_size().unwrap());
-                    self.screen_writer.dimensions = dimensions;
-                    Some(Action::ResizeViewerDimensions(
-                        dimensions.without_status_bar(),
-                    ))
+                    let dimensions = self.screen_dimensions();
+                    self.screen_writer.resize(&mut self.viewer, dimensions);
+                    None
                 }
-                // Handle special input states:
-                // y commands:
-                event if self.input_state == InputState::PendingYCommand => {
-                    let copy_target = match event {
-                        KeyEvent(Key::Char('y')) => Some(CopyTarget::PrettyPrintedValue),
-                        KeyEvent(Key::Char('v')) => Some(CopyTarget::OneLineValue),
-                        KeyEvent(Key::Char('k')) => Some(CopyTarget::Key),
-                        KeyEvent(Key::Char('p')) => Some(CopyTarget::DotPath),
-                        KeyEvent(Key::Char('b')) => Some(CopyTarget::BracketPath),
-                        KeyEvent(Key::Char('q')) => Some(CopyTarget::QueryPath),
-                        _ => None,
-                    };
-
-                    if let Some(copy_target) = copy_target {
-                        self.copy_content(copy_target);
+                // Focus changes also shouldn't reset the input state.
+                TuiEvent::FocusGained | TuiEvent::FocusLost => {
+                    self.screen_writer.focused = matches!(event, TuiEvent::FocusGained);
+                    self.draw_status_bar();
+                    continue;
+                }
+                // While the input is being parsed, periodically show the
+                // newly parsed rows. This shouldn't reset the input state
+                // or clear messages.
+                TuiEvent::Tick => {
+                    let loading = self.loading.is_some();
+                    if loading {
+                        self.receive_parsed_rows();
                     }
-
+                    // Reloading would change the rows that an incremental
+                    // search refers to, so it waits until it's done.
+                    let reloaded = self.incremental_search.is_none()
+                        && (self.reload_if_changed() || self.read_followed_records());
+                    let previewed = self.preview_pending_incremental_search();
+                    if reloaded || previewed || loading {
+                        self.draw_screen();
+                    }
+                    continue;
+                }
+                // We don't query the terminal for anything yet, so there's
+                // nothing to do with these.
+                TuiEvent::TerminalReport(_) => continue,
+                // Typing the pattern of an incremental search:
+                event if self.incremental_search.is_some() => {
+                    let action = self.edit_incremental_search(event);
+                    jumped_to_search_match = action.is_some();
+                    action
+                }
+                // Finding a command in the command palette:
+                event if self.palette.is_some() => {
+                    self.edit_palette(event);
+                    None
+                }
+                // Handle special input states:
+                // Scrubbing through a truncated value:
+                KeyEvent(key) if self.input_state == InputState::ScrubbingValue => {
+                    let to_right = matches!(key, Key::Right | Key::Char('l'));
+                    self.screen_writer
+                        .scroll_focused_line_by_chunk(&self.viewer, to_right);
+                    self.set_info_message(SCRUBBING_MESSAGE.to_string());
+                    None
+                }
+                // Setting marks:
+                event if self.input_state == InputState::PendingSetMark => {
                     self.input_state = InputState::Default;
                     self.input_buffer.clear();
 
-                    None
-                }
-                // z commands:
-                event if self.input_state == InputState::PendingZCommand => {
-                    let z_action = match event {
-                        KeyEvent(Key::Char('t')) => Some(Action::MoveFocusedLineToTop),
-                        KeyEvent(Key::Char('z')) => Some(Action::MoveFocusedLineToCenter),
-                        KeyEvent(Key::Char('b')) => Some(Action::MoveFocusedLineToBottom),
+                    match event {
+                        KeyEvent(Key::Char(ch)) if ch.is_ascii_alphabetic() => {
+                            Some(Action::SetMark(ch))
+                        }
                         _ => None,
-                    };
-
+                    }
+                }
+                // Jumping to marks:
+                event if self.input_state == InputState::PendingJumpToMark => {
                     self.input_state = InputState::Default;
                     self.input_buffer.clear();
 
-                    z_action
+                    match event {
+                        KeyEvent(Key::Char(ch)) if ch.is_ascii_alphabetic() => {
+                            match self.viewer.get_mark(ch) {
+                                Some(index) => Some(Action::RevealAndMoveTo(index)),
+                                None => {
+                                    self.set_warning_message(format!("Mark not set: {}", ch));
+                                    None
+                                }
+                            }
+                        }
+                        _ => None,
+                    }
+                }
+                // Ctrl-C always quits, regardless of the keybindings.
+                KeyEvent(Key::Ctrl('c')) => {
+                    return_path = false;
+                    break;
                 }
-                // These inputs quit.
-                KeyEvent(Key::Ctrl('c') | Key::Char('q')) => break,
-                // Show the help page
-                KeyEvent(Key::F(1)) => {
-                    self.show_help();
+                // Scrolling through the list of keybindings:
+                event if self.input_state == InputState::ShowingKeybindings => {
+                    self.scroll_cheatsheet(event);
                     None
                 }
-                KeyEvent(Key::Esc) => {
-                    self.input_buffer.clear();
-                    self.search_state.set_no_longer_actively_searching();
+                // Any key returns from the statistics to the viewer.
+                event if self.input_state == InputState::ShowingStats => {
+                    if event.bound_key().is_some() {
+                        self.input_buffer.clear();
+                        self.pending_keys.clear();
+                        self.input_state = InputState::Default;
+                    }
                     None
                 }
                 // These inputs may be buffered.
-                KeyEvent(Key::Char(ch @ '0'..='9')) => {
+                KeyEvent(Key::Char(ch @ '0'..='9')) if self.pending_keys.is_empty() => {
                     if ch == '0' && self.input_buffer.is_empty() {
                         Some(Action::FocusFirstSibling)
                     } else {
@@ -207,196 +1452,594 @@ impl App {
                         None
                     }
                 }
-                KeyEvent(Key::Char('y')) => {
-                    match &self.clipboard_context {
-                        Ok(_) => {
-                            self.input_state = InputState::PendingYCommand;
-                            self.input_buffer.clear();
-                            self.buffer_input(b'y');
-                        }
-                        Err(err) => {
-                            let msg = format!("Unable to access clipboard: {}", err);
-                            self.set_error_message(msg);
-                        }
-                    }
-
-                    None
-                }
-                KeyEvent(Key::Char('z')) => {
-                    self.input_state = InputState::PendingZCommand;
-                    self.input_buffer.clear();
-                    self.buffer_input(b'z');
-                    None
-                }
-                // These inputs always clear the input_buffer (but may use its current contents).
-                KeyEvent(key) => {
-                    let action = match key {
-                        // These interpret the input buffer as a number.
-                        Key::Up | Key::Char('k') | Key::Ctrl('p') | Key::Backspace => {
-                            let lines = self.parse_input_buffer_as_number();
-                            Some(Action::MoveUp(lines))
-                        }
-                        Key::Down | Key::Char('j') | Key::Ctrl('n') | Key::Char('\n') => {
-                            let lines = self.parse_input_buffer_as_number();
-                            Some(Action::MoveDown(lines))
-                        }
-                        Key::Ctrl('e') => {
-                            let lines = self.parse_input_buffer_as_number();
-                            Some(Action::ScrollDown(lines))
-                        }
-                        Key::Ctrl('y') => {
-                            let lines = self.parse_input_buffer_as_number();
-                            Some(Action::ScrollUp(lines))
-                        }
-                        Key::Ctrl('d') => {
-                            let maybe_distance = self.maybe_parse_input_buffer_as_number();
-                            Some(Action::JumpDown(maybe_distance))
-                        }
-                        Key::Ctrl('u') => {
-                            let maybe_distance = self.maybe_parse_input_buffer_as_number();
-                            Some(Action::JumpUp(maybe_distance))
-                        }
-                        Key::Ctrl('b') | Key::PageUp => {
-                            let count = self.parse_input_buffer_as_number();
-                            Some(Action::PageUp(count))
-                        }
-                        Key::Ctrl('f') | Key::PageDown => {
-                            let count = self.parse_input_buffer_as_number();
-                            Some(Action::PageDown(count))
-                        }
-                        Key::Char('K') => {
-                            let lines = self.parse_input_buffer_as_number();
-                            Some(Action::FocusPrevSibling(lines))
-                        }
-                        Key::Char('J') => {
-                            let lines = self.parse_input_buffer_as_number();
-                            Some(Action::FocusNextSibling(lines))
-                        }
-                        Key::Char('n') => {
-                            let count = self.parse_input_buffer_as_number();
-                            jumped_to_search_match = true;
-                            self.jump_to_search_match(JumpDirection::Next, count)
-                        }
-                        Key::Char('N') => {
-                            let count = self.parse_input_buffer_as_number();
-                            jumped_to_search_match = true;
-                            self.jump_to_search_match(JumpDirection::Prev, count)
-                        }
-                        Key::Char('.') => {
-                            let count = self.parse_input_buffer_as_number();
-                            self.screen_writer
-                                .scroll_focused_line_right(&self.viewer, count);
-                            None
-                        }
-                        Key::Char(',') => {
-                            let count = self.parse_input_buffer_as_number();
-                            self.screen_writer
-                                .scroll_focused_line_left(&self.viewer, count);
-                            None
-                        }
-                        Key::Char('/') => {
-                            let count = self.parse_input_buffer_as_number();
-                            let action = self
-                                .get_search_input_and_start_search(SearchDirection::Forward, count);
-                            jumped_to_search_match = action.is_some();
-                            action
-                        }
-                        Key::Char('?') => {
-                            let count = self.parse_input_buffer_as_number();
-                            let action = self
-                                .get_search_input_and_start_search(SearchDirection::Reverse, count);
-                            jumped_to_search_match = action.is_some();
-                            action
-                        }
-                        Key::Char('*') => {
-                            let count = self.parse_input_buffer_as_number();
-                            let action =
-                                self.start_object_key_search(SearchDirection::Forward, count);
-                            jumped_to_search_match = action.is_some();
-                            action
-                        }
-                        Key::Char('#') => {
-                            let count = self.parse_input_buffer_as_number();
-                            let action =
-                                self.start_object_key_search(SearchDirection::Reverse, count);
-                            jumped_to_search_match = action.is_some();
-                            action
-                        }
-                        // These ignore the input buffer
-                        Key::Char('w') => Some(Action::MoveDownUntilDepthChange),
-                        Key::Char('b') => Some(Action::MoveUpUntilDepthChange),
-                        Key::Left | Key::Char('h') => Some(Action::MoveLeft),
-                        Key::Right | Key::Char('l') => Some(Action::MoveRight),
-                        Key::Char('H') => Some(Action::FocusParent),
-                        Key::Char('c') => Some(Action::CollapseNodeAndSiblings),
-                        Key::Char('e') => Some(Action::ExpandNodeAndSiblings),
-                        Key::Char(' ') => Some(Action::ToggleCollapsed),
-                        Key::Char('^') => Some(Action::FocusFirstSibling),
-                        Key::Char('$') => Some(Action::FocusLastSibling),
-                        Key::Char('g') | Key::Home => Some(Action::FocusTop),
-                        Key::Char('G') | Key::End => Some(Action::FocusBottom),
-                        Key::Char('%') => Some(Action::FocusMatchingPair),
-                        Key::Char('m') => Some(Action::ToggleMode),
-                        Key::Char('<') => {
-                            self.screen_writer
-                                .decrease_indentation_level(self.viewer.flatjson.2 as u16);
-                            None
-                        }
-                        Key::Char('>') => {
-                            self.screen_writer.increase_indentation_level();
-                            None
-                        }
-                        Key::Char(';') => {
-                            self.screen_writer
-                                .scroll_focused_line_to_an_end(&self.viewer);
-                            None
-                        }
-                        Key::Char(':') => {
-                            if let Some(command) = self.readline(":", "command") {
-                                match Self::parse_command(&command) {
-                                    Command::Quit => break,
-                                    Command::Help => self.show_help(),
-                                    Command::Unknown => {
-                                        self.set_warning_message(format!(
-                                            "Unknown command: {}",
-                                            command
+                event @ (KeyEvent(_) | TuiEvent::ModifiedKeyEvent(..) | TuiEvent::MacroStep(_)) => {
+                    match self.command_for_event(&event) {
+                        None => None,
+                        Some((command, count)) => match command {
+                            KeyCommand::Quit if self.opt.confirm_quit => {
+                                self.input_state = InputState::ConfirmingQuit;
+                                self.set_info_message(CONFIRM_QUIT_MESSAGE.to_string());
+                                None
+                            }
+                            KeyCommand::Quit | KeyCommand::ForceQuit => break,
+                            KeyCommand::QuitWithoutSavingState => {
+                                save_state = false;
+                                return_path = false;
+                                break;
+                            }
+                            KeyCommand::QuitAndPrintValue => {
+                                let (flatjson, index) = self
+                                    .viewer
+                                    .original_flatjson_and_index(self.viewer.focused_row);
+                                match flatjson.check_parsed(index) {
+                                    Ok(()) => {
+                                        return_value = true;
+                                        break;
+                                    }
+                                    Err(err) => {
+                                        self.set_error_message(format!(
+                                            "Unable to print value: {}",
+                                            err
                                         ));
+                                        None
+                                    }
+                                }
+                            }
+                            KeyCommand::Help => {
+                                self.show_help();
+                                None
+                            }
+                            KeyCommand::ShowKeybindings => {
+                                self.input_state = InputState::ShowingKeybindings;
+                                self.cheatsheet_top = 0;
+                                None
+                            }
+                            KeyCommand::CommandPalette => {
+                                self.open_palette();
+                                None
+                            }
+                            KeyCommand::RunMacro(index) => {
+                                self.run_macro(index, count.unwrap_or(1));
+                                None
+                            }
+                            KeyCommand::Cancel => {
+                                self.search_state.dismiss_highlights();
+                                None
+                            }
+                            KeyCommand::Up => Some(Action::MoveUp(count.unwrap_or(1))),
+                            KeyCommand::Down => Some(Action::MoveDown(count.unwrap_or(1))),
+                            KeyCommand::ScrollDown => Some(Action::ScrollDown(count.unwrap_or(1))),
+                            KeyCommand::ScrollUp => Some(Action::ScrollUp(count.unwrap_or(1))),
+                            KeyCommand::HalfPageDown => Some(Action::JumpDown(count)),
+                            KeyCommand::HalfPageUp => Some(Action::JumpUp(count)),
+                            KeyCommand::PageUp => self.page(false, count),
+                            KeyCommand::PageDown => self.page(true, count),
+                            KeyCommand::PrevSibling => {
+                                Some(Action::FocusPrevSibling(count.unwrap_or(1)))
+                            }
+                            KeyCommand::NextSibling => {
+                                Some(Action::FocusNextSibling(count.unwrap_or(1)))
+                            }
+                            KeyCommand::SkipToPrevSibling => {
+                                Some(Action::SkipToPrevSibling(count.unwrap_or(1)))
+                            }
+                            KeyCommand::SkipToNextSibling => {
+                                Some(Action::SkipToNextSibling(count.unwrap_or(1)))
+                            }
+                            KeyCommand::NextMatch => {
+                                jumped_to_search_match = true;
+                                self.jump_to_search_match(JumpDirection::Next, count.unwrap_or(1))
+                            }
+                            KeyCommand::PrevMatch => {
+                                jumped_to_search_match = true;
+                                self.jump_to_search_match(JumpDirection::Prev, count.unwrap_or(1))
+                            }
+                            KeyCommand::ScrollRight => {
+                                self.screen_writer
+                                    .scroll_focused_line_right(&self.viewer, count.unwrap_or(1));
+                                None
+                            }
+                            KeyCommand::ScrollLeft => {
+                                self.screen_writer
+                                    .scroll_focused_line_left(&self.viewer, count.unwrap_or(1));
+                                None
+                            }
+                            KeyCommand::SearchForward | KeyCommand::SearchBackward => {
+                                let direction = if command == KeyCommand::SearchForward {
+                                    SearchDirection::Forward
+                                } else {
+                                    SearchDirection::Reverse
+                                };
+                                let action = self.get_search_input_and_start_search(
+                                    direction,
+                                    count.unwrap_or(1),
+                                    "",
+                                );
+                                jumped_to_search_match = action.is_some();
+                                action
+                            }
+                            KeyCommand::SearchKeyForward | KeyCommand::SearchKeyBackward => {
+                                let direction = if command == KeyCommand::SearchKeyForward {
+                                    SearchDirection::Forward
+                                } else {
+                                    SearchDirection::Reverse
+                                };
+                                let action =
+                                    self.start_object_key_search(direction, count.unwrap_or(1));
+                                jumped_to_search_match = action.is_some();
+                                action
+                            }
+                            // With a count, these jump to a line; otherwise, they go
+                            // to the top or bottom.
+                            KeyCommand::Top => match count {
+                                Some(line) => self.jump_to_line(line),
+                                None => Some(Action::FocusTop),
+                            },
+                            KeyCommand::Bottom => match count {
+                                Some(line) => self.jump_to_line(line),
+                                None => Some(Action::FocusBottom),
+                            },
+                            // With a count, this jumps to a percentage of the way
+                            // through the input, like vim.
+                            KeyCommand::MatchingPair => match count {
+                                Some(percent) => self.jump_to_percentage(percent),
+                                None => Some(Action::FocusMatchingPair),
+                            },
+                            // Switch between files when viewing multiple files.
+                            KeyCommand::NextFile => {
+                                self.switch_files(count.unwrap_or(1) as isize);
+                                None
+                            }
+                            KeyCommand::PrevFile => {
+                                self.switch_files(-(count.unwrap_or(1) as isize));
+                                None
+                            }
+                            KeyCommand::DownUntilDepthChange => {
+                                self.repeat_action(Action::MoveDownUntilDepthChange, count)
+                            }
+                            KeyCommand::UpUntilDepthChange => {
+                                self.repeat_action(Action::MoveUpUntilDepthChange, count)
+                            }
+                            KeyCommand::Left => self.repeat_action(Action::MoveLeft, count),
+                            KeyCommand::Right => self.repeat_action(Action::MoveRight, count),
+                            KeyCommand::Parent => self.repeat_action(Action::FocusParent, count),
+                            KeyCommand::CollapseParent => {
+                                let focused_row = &self.viewer.flatjson[self.viewer.focused_row];
+                                if focused_row.parent.is_nil()
+                                    && !focused_row.is_closing_of_container()
+                                {
+                                    self.set_info_message("Already at the top level".to_string());
+                                    None
+                                } else {
+                                    self.repeat_action(Action::CollapseParent, count)
+                                }
+                            }
+                            KeyCommand::CollapseSiblings => Some(Action::CollapseNodeAndSiblings),
+                            KeyCommand::ExpandSiblings => Some(Action::ExpandNodeAndSiblings),
+                            KeyCommand::ToggleCollapsed => match self.opt.toggle {
+                                ToggleScope::Node => Some(Action::ToggleCollapsed),
+                                ToggleScope::Subtree => {
+                                    let focused_row =
+                                        &self.viewer.flatjson[self.viewer.focused_row];
+                                    if focused_row.is_collapsed() {
+                                        Some(Action::ExpandSubtree)
+                                    } else {
+                                        Some(Action::CollapseSubtree)
                                     }
                                 }
+                            },
+                            KeyCommand::ExpandSubtree => Some(Action::ExpandSubtree),
+                            KeyCommand::CollapseSubtree => Some(Action::CollapseSubtree),
+                            KeyCommand::FirstSibling => Some(Action::FocusFirstSibling),
+                            KeyCommand::LastSibling => Some(Action::FocusLastSibling),
+                            KeyCommand::ToggleMode => Some(Action::ToggleMode),
+                            KeyCommand::DecreaseIndentation => {
+                                self.screen_writer.decrease_indentation_level(
+                                    self.viewer.flatjson.2 as u16,
+                                    count.unwrap_or(1).min(u16::MAX as usize) as u16,
+                                );
+                                None
+                            }
+                            KeyCommand::IncreaseIndentation => {
+                                self.screen_writer.increase_indentation_level(
+                                    count.unwrap_or(1).min(u16::MAX as usize) as u16,
+                                );
+                                None
+                            }
+                            KeyCommand::ScrollScreenLeft | KeyCommand::ScrollScreenRight => {
+                                let to_right = command == KeyCommand::ScrollScreenRight;
+                                self.screen_writer.scroll_horizontally(
+                                    &self.viewer,
+                                    count.unwrap_or(1),
+                                    to_right,
+                                );
+                                None
+                            }
+                            KeyCommand::ScrollScreenHalfLeft
+                            | KeyCommand::ScrollScreenHalfRight => {
+                                let to_right = command == KeyCommand::ScrollScreenHalfRight;
+                                let half_width = self.viewer.dimensions.width as usize / 2;
+                                self.screen_writer.scroll_horizontally(
+                                    &self.viewer,
+                                    count.unwrap_or(1).saturating_mul(half_width),
+                                    to_right,
+                                );
+                                None
+                            }
+                            KeyCommand::ScrollToEnd => {
+                                self.screen_writer
+                                    .scroll_focused_line_to_an_end(&self.viewer);
+                                None
+                            }
+                            KeyCommand::ScrubValue => {
+                                if self.screen_writer.focused_value_is_truncated(&self.viewer) {
+                                    self.input_state = InputState::ScrubbingValue;
+                                    self.set_info_message(SCRUBBING_MESSAGE.to_string());
+                                } else {
+                                    self.set_warning_message(
+                                        "The focused value isn't truncated".to_string(),
+                                    );
+                                }
+                                None
+                            }
+                            KeyCommand::CycleTruncation => {
+                                if self
+                                    .screen_writer
+                                    .cycle_focused_value_truncation(&self.viewer)
+                                    .is_none()
+                                {
+                                    self.set_warning_message(
+                                        "The focused value isn't truncated".to_string(),
+                                    );
+                                }
+                                None
+                            }
+                            KeyCommand::FocusedLineToTop => Some(Action::MoveFocusedLineToTop),
+                            KeyCommand::FocusedLineToCenter => {
+                                Some(Action::MoveFocusedLineToCenter)
+                            }
+                            KeyCommand::FocusedLineToBottom => {
+                                Some(Action::MoveFocusedLineToBottom)
+                            }
+                            KeyCommand::MatchSiblings => {
+                                Some(Action::MatchSiblingsToCollapsedState)
+                            }
+                            KeyCommand::CollapseOtherSiblings => {
+                                Some(Action::CollapseSiblingsExceptFocused)
+                            }
+                            KeyCommand::CollapseToDepth => {
+                                Some(Action::CollapseToDepth(count.unwrap_or(1)))
+                            }
+                            KeyCommand::ExpandToDepth => {
+                                Some(Action::ExpandToDepth(count.unwrap_or(usize::MAX)))
+                            }
+                            KeyCommand::CycleHiddenValues if self.still_loading() => None,
+                            KeyCommand::CycleHiddenValues => {
+                                self.cycle_hidden_values();
+                                None
+                            }
+                            KeyCommand::CycleNumberBase => {
+                                let number_base = self.screen_writer.number_base.next();
+                                self.screen_writer.number_base = number_base;
+                                let msg = match number_base {
+                                    NumberBase::Decimal => "Showing integers in decimal",
+                                    NumberBase::Hex => "Showing integers in hex",
+                                    NumberBase::Binary => "Showing integers in binary",
+                                };
+                                self.set_info_message(msg.to_string());
+                                None
+                            }
+                            KeyCommand::ToggleLineNumbers => {
+                                let line_numbers = self.screen_writer.line_numbers.next();
+                                self.screen_writer.line_numbers = line_numbers;
+                                let msg = match line_numbers {
+                                    LineNumbers::Off => "Hiding line numbers",
+                                    LineNumbers::Absolute => "Showing absolute line numbers",
+                                    LineNumbers::Relative => "Showing relative line numbers",
+                                    LineNumbers::Hybrid => "Showing hybrid line numbers",
+                                };
+                                self.set_info_message(msg.to_string());
+                                None
+                            }
+                            KeyCommand::ToggleSizes => {
+                                self.screen_writer.show_sizes = !self.screen_writer.show_sizes;
+                                None
+                            }
+                            KeyCommand::ToggleTypes => {
+                                self.screen_writer.show_types = !self.screen_writer.show_types;
+                                None
+                            }
+                            KeyCommand::ToggleAnnotations => {
+                                self.screen_writer.annotate = !self.screen_writer.annotate;
+                                None
+                            }
+                            KeyCommand::ToggleAlignValues => {
+                                self.screen_writer.align_values = !self.screen_writer.align_values;
+                                None
+                            }
+                            KeyCommand::ToggleTableView => {
+                                self.toggle_table_view();
+                                None
+                            }
+                            KeyCommand::ToggleWrap => {
+                                let focused_row = self.viewer.focused_row;
+                                if self.viewer.flatjson[focused_row].is_string() {
+                                    self.screen_writer.toggle_wrapped_row(focused_row);
+                                }
+                                None
+                            }
+                            KeyCommand::ToggleWrapAll => {
+                                self.screen_writer.toggle_wrap_strings();
+                                let msg = if self.screen_writer.wrap_strings {
+                                    "Wrapping long strings"
+                                } else {
+                                    "Truncating long strings"
+                                };
+                                self.set_info_message(msg.to_string());
+                                None
+                            }
+                            KeyCommand::ToggleMinimap => {
+                                self.screen_writer.show_minimap = !self.screen_writer.show_minimap;
+                                None
+                            }
+                            KeyCommand::ToggleIndentGuides => {
+                                self.screen_writer.indent_guides =
+                                    !self.screen_writer.indent_guides;
+                                None
+                            }
+                            KeyCommand::ToggleFoldColumn => {
+                                self.screen_writer.fold_column = !self.screen_writer.fold_column;
+                                None
+                            }
+                            KeyCommand::ToggleMouse => {
+                                self.toggle_mouse_reporting();
+                                None
+                            }
+                            KeyCommand::ToggleSortKeys if self.still_loading() => None,
+                            KeyCommand::ToggleSortKeys => {
+                                // Search matches refer to locations in the displayed
+                                // input, and the state kept for rows to the rows,
+                                // which change when keys are sorted.
+                                self.reset_search_state();
+                                self.screen_writer.reset_row_state();
+
+                                let msg = if self.viewer.sorted_keys() {
+                                    "Showing object keys in their original order"
+                                } else {
+                                    "Sorting object keys alphabetically"
+                                };
+                                self.set_info_message(msg.to_string());
+
+                                Some(Action::ToggleSortedKeys)
+                            }
+                            KeyCommand::TransformValue => {
+                                let was_decoded = self
+                                    .screen_writer
+                                    .focused_value_decoder(&self.viewer)
+                                    .is_some();
+                                match self.screen_writer.cycle_focused_value_decoder(&self.viewer) {
+                                    Some(decoder) => self.set_info_message(format!(
+                                        "Showing the value decoded from {}",
+                                        decoder.name
+                                    )),
+                                    None if was_decoded => self
+                                        .set_info_message("Showing the original value".to_string()),
+                                    None => self.set_warning_message(
+                                        "None of the decoders apply to the focused value"
+                                            .to_string(),
+                                    ),
+                                }
+                                None
+                            }
+                            KeyCommand::ToggleDecodedString if self.still_loading() => None,
+                            KeyCommand::ToggleDecodedString => {
+                                match self.viewer.toggle_decoded_string() {
+                                    Ok(decoded) => {
+                                        // Search matches and the state kept for
+                                        // rows refer to the rows before the
+                                        // string was replaced.
+                                        self.reset_search_state();
+                                        self.screen_writer.reset_row_state();
+
+                                        let msg = if decoded {
+                                            "Decoded the JSON in the string; press zj again to restore it"
+                                        } else {
+                                            "Restored the original string"
+                                        };
+                                        self.set_info_message(msg.to_string());
+                                    }
+                                    Err(err) => self.set_warning_message(err),
+                                }
+                                None
+                            }
+                            KeyCommand::SetMark => {
+                                self.input_state = InputState::PendingSetMark;
+                                if let Some(key) = event.bound_key() {
+                                    self.buffer_key(key);
+                                }
+                                None
+                            }
+                            KeyCommand::JumpToMark => {
+                                self.input_state = InputState::PendingJumpToMark;
+                                if let Some(key) = event.bound_key() {
+                                    self.buffer_key(key);
+                                }
+                                None
                             }
+                            KeyCommand::JumpToAnchor => self.jump_to_anchor(),
+                            KeyCommand::CopyValue => {
+                                self.copy_content(CopyTarget::PrettyPrintedValue);
+                                None
+                            }
+                            KeyCommand::CopyOneLineValue => {
+                                self.copy_content(CopyTarget::OneLineValue);
+                                None
+                            }
+                            KeyCommand::CopyDisplayedValue => {
+                                self.copy_content(CopyTarget::DisplayedValue);
+                                None
+                            }
+                            KeyCommand::CopyDecodedValue => {
+                                self.copy_content(CopyTarget::DecodedValue);
+                                None
+                            }
+                            KeyCommand::CopyKey => {
+                                self.copy_content(CopyTarget::Key);
+                                None
+                            }
+                            KeyCommand::CopyPath => {
+                                self.copy_content(CopyTarget::path(self.path_format));
+                                None
+                            }
+                            KeyCommand::CopyDotPath => {
+                                self.copy_content(CopyTarget::DotPath);
+                                None
+                            }
+                            KeyCommand::CopyBracketPath => {
+                                self.copy_content(CopyTarget::BracketPath);
+                                None
+                            }
+                            KeyCommand::CopyJqPath => {
+                                self.copy_content(CopyTarget::QueryPath);
+                                None
+                            }
+                            KeyCommand::CopyGronPath => {
+                                self.copy_content(CopyTarget::GronPath);
+                                None
+                            }
+                            KeyCommand::CopyPlainPath => {
+                                self.copy_content(CopyTarget::PlainPath);
+                                None
+                            }
+                            KeyCommand::CopySource => {
+                                self.copy_content(CopyTarget::Source);
+                                None
+                            }
+                            KeyCommand::ShowSourcePosition => {
+                                self.show_source_position();
+                                None
+                            }
+                            KeyCommand::Filter => {
+                                self.filter_rows();
+                                None
+                            }
+                            KeyCommand::Command => {
+                                let mut action = None;
 
-                            None
-                        }
-                        _ => {
-                            eprint!("{}\r", BELL);
-                            None
-                        }
-                    };
-
-                    self.input_buffer.clear();
+                                let command = match self.macro_command_line.take() {
+                                    Some(command) => Some(command),
+                                    None => self.readline(":", "", &[], "command"),
+                                };
+                                if let Some(command) = command {
+                                    match Self::parse_command(&command) {
+                                        Command::Quit => break,
+                                        Command::Help => self.show_help(),
+                                        Command::JumpToLine(line) => {
+                                            action = self.jump_to_line(line);
+                                        }
+                                        Command::Marks => self.show_marks(),
+                                        Command::Stats => {
+                                            self.input_state = InputState::ShowingStats
+                                        }
+                                        Command::Export {
+                                            include_collapsed,
+                                            whole_input,
+                                            format,
+                                        } => self.export(include_collapsed, whole_input, format),
+                                        Command::SetCollapsedByKey { collapsed, pattern } => {
+                                            self.set_collapsed_state_by_key(collapsed, pattern)
+                                        }
+                                        Command::Jq { program } => self.transform_with_jq(program),
+                                        Command::Undo => self.undo_jq(),
+                                        Command::ClearState => self.clear_saved_state(),
+                                        Command::SetScrolloff(rows) => self.set_scrolloff(rows),
+                                        Command::SetFormat(format) => self.set_format(format),
+                                        Command::RunMacro(name) => self.run_macro_by_name(name),
+                                        Command::Unknown => {
+                                            self.set_warning_message(format!(
+                                                "Unknown command: {}",
+                                                command
+                                            ));
+                                        }
+                                    }
+                                }
 
-                    action
+                                action
+                            }
+                        },
+                    }
                 }
                 MouseEvent(me) => {
                     self.input_buffer.clear();
+                    self.pending_keys.clear();
 
                     match me {
                         Press(Left, _, h) => {
                             // Ignore clicks on status bar or below.
                             if h > self.screen_writer.dimensions.without_status_bar().height {
                                 continue;
-                            } else {
-                                Some(Action::Click(h))
+                            }
+
+                            // With --diff, clicks next to rows only in the
+                            // other file are ignored.
+                            match self.screen_writer.screen_row_to_viewer_row(&self.viewer, h) {
+                                Some(row) => Some(Action::Click(row)),
+                                None => continue,
                             }
                         }
-                        Press(WheelUp, _, _) => Some(Action::MoveUp(3)),
-                        Press(WheelDown, _, _) => Some(Action::MoveDown(3)),
                         // Ignore all other mouse events and don't redraw the screen.
                         _ => {
                             continue;
                         }
                     }
                 }
+                TuiEvent::Scroll { lines } => {
+                    self.input_buffer.clear();
+                    self.pending_keys.clear();
+
+                    match lines.cmp(&0) {
+                        Ordering::Less => Some(Action::MoveUp(-lines as usize)),
+                        Ordering::Greater => Some(Action::MoveDown(lines as usize)),
+                        Ordering::Equal => continue,
+                    }
+                }
+                TuiEvent::DoubleClick { row, .. } => {
+                    self.input_buffer.clear();
+                    self.pending_keys.clear();
+
+                    // Ignore clicks on status bar or below.
+                    if row > self.screen_writer.dimensions.without_status_bar().height {
+                        continue;
+                    }
+
+                    match self
+                        .screen_writer
+                        .screen_row_to_viewer_row(&self.viewer, row)
+                    {
+                        Some(row) => Some(Action::DoubleClick(row)),
+                        None => continue,
+                    }
+                }
+                // Pasted text shouldn't be interpreted as a series of commands,
+                // so instead we use it to pre-fill the search prompt.
+                TuiEvent::Paste(text) => {
+                    self.input_buffer.clear();
+                    self.pending_keys.clear();
+
+                    let initial_search_input = text
+                        .split(|c| c == '\r' || c == '\n')
+                        .filter(|line| !line.is_empty())
+                        .collect::<Vec<&str>>()
+                        .join(" ");
+
+                    let action = self.get_search_input_and_start_search(
+                        SearchDirection::Forward,
+                        1,
+                        &initial_search_input,
+                    );
+                    jumped_to_search_match = action.is_some();
+                    action
+                }
                 TuiEvent::Unknown(bytes) => {
                     self.set_error_message(format!("Unknown byte sequence: {:?}", bytes));
                     None
@@ -423,28 +2066,277 @@ impl App {
             }
 
             self.draw_screen();
-            self.message = None;
+            if self.input_state != InputState::ConfirmingQuit {
+                self.message = None;
+            }
+        }
+
+        if save_state && self.offscreen.is_none() {
+            self.save_state_of_files();
+        }
+
+        if !return_path {
+            return None;
+        }
+        let index = self.viewer.focused_row;
+        let flatjson = &self.viewer.flatjson;
+        let path = match self.path_format {
+            PathFormat::Dot => flatjson.build_path_to_node(flatjson::PathType::Dot, index),
+            PathFormat::Bracket => flatjson.build_path_to_node(flatjson::PathType::Bracket, index),
+            PathFormat::Query => flatjson.build_path_to_node(flatjson::PathType::Query, index),
+            PathFormat::Plain => flatjson.build_path_to_node(flatjson::PathType::Plain, index),
+            PathFormat::Gron => flatjson.build_gron_assignment(index),
+        };
+
+        // Values are printed with their object keys in the original order,
+        // like when they're copied.
+        let value = if return_value {
+            let compact = self.opt.print_value_on_exit == Some(ValueFormat::Compact);
+            let (flatjson, index) = self.viewer.original_flatjson_and_index(index);
+            Some(export::json_value(flatjson, index, compact))
+        } else {
+            None
+        };
+
+        Some(Selection {
+            path: path.ok(),
+            value,
+        })
+    }
+
+    // The size of the screen: the terminal's, unless rendering offscreen.
+    fn screen_dimensions(&self) -> TTYDimensions {
+        self.offscreen.or_else(terminal_dimensions).unwrap()
+    }
+
+    // With --remember-state, saves which rows are collapsed and focused in
+    // each file being viewed, so that they're restored the next time it's
+    // viewed. Files that are still loading or filtered are skipped.
+    fn save_state_of_files(&mut self) {
+        if !self.state_cache.enabled() {
+            return;
+        }
+
+        let mut states = vec![];
+        for (index, path) in self.file_paths.iter().enumerate() {
+            let (viewer, previous_views, loading) = if index == self.current_file {
+                (&self.viewer, &self.previous_views, self.loading.is_some())
+            } else {
+                let file = self.files[index].as_ref().unwrap();
+                (&file.viewer, &file.previous_views, file.loading.is_some())
+            };
+            // Save the state of the input, rather than the output of jq.
+            let viewer = match previous_views.first() {
+                Some((viewer, _)) => viewer,
+                None => viewer,
+            };
+
+            if let Some(version) = path.as_deref().and_then(FileVersion::of) {
+                if !loading && !viewer.filtered() {
+                    states.push((version, viewer.saved_state()));
+                }
+            }
+        }
+
+        for (version, state) in states {
+            self.state_cache.insert(version, state);
         }
+        // Failing to save the state shouldn't get in the way of exiting.
+        let _ = self.state_cache.save();
+    }
+
+    fn reset_search_state(&mut self) {
+        let persist_highlights = self.search_state.persist_highlights;
+        self.search_state = SearchState::empty();
+        self.search_state.persist_highlights = persist_highlights;
     }
 
     fn draw_screen(&mut self) {
+        if self.palette.is_some() {
+            self.draw_palette();
+            return;
+        }
+        if self.input_state == InputState::ShowingKeybindings {
+            self.draw_cheatsheet();
+            return;
+        }
+        if self.input_state == InputState::ShowingStats {
+            self.draw_stats();
+            return;
+        }
+
+        self.viewer.top_row = self.screen_writer.top_row_for_wrapped_lines(&self.viewer);
+        let filename = self.displayed_filename();
+        if self.screen_writer.diff.is_some() {
+            self.draw_diff(&filename);
+            return;
+        }
         self.screen_writer.print(
             &self.viewer,
             &self.input_buffer,
-            &self.input_filename,
+            &filename,
             &self.search_state,
             &self.message,
         );
+        if let Some(search) = &self.incremental_search {
+            self.screen_writer.print_prompt(&search.prompt);
+        }
+    }
+
+    // With --diff, draws both files side by side, with the first one on
+    // the left, and rows with the same path next to each other. The one
+    // that isn't focused shows the same rows as the focused one.
+    fn draw_diff(&mut self, filename: &str) {
+        let other_file = 1 - self.current_file;
+        let other = self.files[other_file].as_mut().unwrap();
+        self.matched_rows
+            .sync(&self.viewer, self.current_file, &mut other.viewer);
+
+        let mut panes = [
+            (&self.viewer, &self.search_state),
+            (&other.viewer, &other.search_state),
+        ];
+        if other_file == 0 {
+            panes.swap(0, 1);
+        }
+        self.screen_writer.print_diff(
+            panes,
+            self.matched_rows.lines(),
+            self.current_file,
+            &self.input_buffer,
+            filename,
+            &self.message,
+        );
+        if let Some(search) = &self.incremental_search {
+            self.screen_writer.print_prompt(&search.prompt);
+        }
     }
 
     fn draw_status_bar(&mut self) {
+        if self.palette.is_some() {
+            self.draw_palette();
+            return;
+        }
+        if self.input_state == InputState::ShowingKeybindings {
+            self.draw_cheatsheet();
+            return;
+        }
+        if self.input_state == InputState::ShowingStats {
+            self.draw_stats();
+            return;
+        }
+
+        let filename = self.displayed_filename();
         self.screen_writer.print_status_bar(
             &self.viewer,
             &self.input_buffer,
-            &self.input_filename,
+            &filename,
             &self.search_state,
             &self.message,
         );
+        if let Some(search) = &self.incremental_search {
+            self.screen_writer.print_prompt(&search.prompt);
+        }
+    }
+
+    fn draw_cheatsheet(&mut self) {
+        let cheatsheet = self.keybindings.cheatsheet();
+        let height = self.viewer.dimensions.height as usize;
+        self.cheatsheet_top = self
+            .cheatsheet_top
+            .min(cheatsheet.len().saturating_sub(height));
+        self.screen_writer
+            .print_cheatsheet(&cheatsheet, self.cheatsheet_top);
+    }
+
+    // Shows the size of the input, which is counted again each time it's
+    // drawn, so that it's kept up to date while the input is loading.
+    fn draw_stats(&mut self) {
+        let stats = self.viewer.original_flatjson().stats();
+        let mut rows = vec![
+            ("Rows", stats.rows.to_string()),
+            ("Objects", stats.objects.to_string()),
+            ("Arrays", stats.arrays.to_string()),
+            ("Strings", stats.strings.to_string()),
+            ("Numbers", stats.numbers.to_string()),
+            ("Booleans", stats.booleans.to_string()),
+            ("Nulls", stats.nulls.to_string()),
+            ("Max depth", stats.max_depth.to_string()),
+            (
+                "Memory used",
+                format!("~{}", progress::format_bytes(stats.memory_size)),
+            ),
+        ];
+        if self.loading.is_some() {
+            rows.push(("", "(still loading)".to_string()));
+        }
+
+        let title = format!("Statistics for {}", self.input_filename);
+        self.screen_writer.print_stats(&title, &rows);
+    }
+
+    // While the list of keybindings is shown, the usual movement commands
+    // scroll through it. Pressing '?' or Escape (or whatever the cancel,
+    // help, or quit commands are bound to) returns to the viewer, exactly
+    // as it was left.
+    fn scroll_cheatsheet(&mut self, event: TuiEvent) {
+        let height = self.viewer.dimensions.height as usize;
+        let top = self.cheatsheet_top;
+
+        match event {
+            KeyEvent(Key::Char('?')) | KeyEvent(Key::Esc) => {
+                self.input_buffer.clear();
+                self.pending_keys.clear();
+                self.input_state = InputState::Default;
+            }
+            KeyEvent(Key::Char(ch @ '0'..='9')) if self.pending_keys.is_empty() => {
+                self.buffer_input(ch as u8);
+            }
+            event @ (KeyEvent(_) | TuiEvent::ModifiedKeyEvent(..)) => {
+                if let Some((command, count)) = self.command_for_event(&event) {
+                    let lines = count.unwrap_or(1);
+                    self.cheatsheet_top = match command {
+                        KeyCommand::Up | KeyCommand::ScrollUp => top.saturating_sub(lines),
+                        KeyCommand::Down | KeyCommand::ScrollDown => top + lines,
+                        KeyCommand::HalfPageUp => top.saturating_sub(height / 2),
+                        KeyCommand::HalfPageDown => top + height / 2,
+                        KeyCommand::PageUp => top.saturating_sub(height),
+                        KeyCommand::PageDown | KeyCommand::ToggleCollapsed => top + height,
+                        KeyCommand::Top => 0,
+                        KeyCommand::Bottom => usize::MAX,
+                        KeyCommand::Quit
+                        | KeyCommand::Cancel
+                        | KeyCommand::Help
+                        | KeyCommand::ShowKeybindings => {
+                            self.input_state = InputState::Default;
+                            top
+                        }
+                        _ => top,
+                    };
+                }
+            }
+            TuiEvent::Scroll { lines } => {
+                self.cheatsheet_top = if lines < 0 {
+                    top.saturating_sub(-lines as usize)
+                } else {
+                    top + lines as usize
+                };
+            }
+            _ => {}
+        }
+    }
+
+    // Toggle whether jless captures the mouse, or lets the terminal handle
+    // it, so that text can be selected without holding a modifier key.
+    fn toggle_mouse_reporting(&mut self) {
+        let enabled = !self.screen_writer.mouse_reporting;
+        match self.screen_writer.set_mouse_reporting(enabled) {
+            Ok(()) if enabled => self.set_info_message("Capturing the mouse".to_string()),
+            Ok(()) => self.set_info_message(
+                "Not capturing the mouse; text can be selected (zm to undo)".to_string(),
+            ),
+            Err(err) => self.set_error_message(format!("Unable to toggle the mouse: {}", err)),
+        }
     }
 
     fn set_info_message(&mut self, s: String) {
@@ -462,8 +2354,20 @@ impl App {
     // Get user input via a readline prompt. May fail to return input if
     // the user deliberately cancels the prompt via Ctrl-C or Ctrl-D, or
     // if an actual error occurs, in which case an error message is set.
-    fn readline(&mut self, prompt: &str, purpose: &str) -> Option<String> {
-        match self.screen_writer.get_command(prompt) {
+    fn readline(
+        &mut self,
+        prompt: &str,
+        initial: &str,
+        history: &[String],
+        purpose: &str,
+    ) -> Option<String> {
+        // Lines are read from the terminal by rustyline.
+        if self.offscreen.is_some() {
+            self.set_warning_message("Prompts can't be used offscreen".to_string());
+            return None;
+        }
+
+        match self.screen_writer.get_command(prompt, initial, history) {
             Ok(s) => Some(s),
             // User hit Ctrl-C or Ctrl-D to cancel prompt
             Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => None,
@@ -474,6 +2378,189 @@ impl App {
         }
     }
 
+    // Finds the command bound to a key, or to a sequence of keys ending
+    // with it, along with the count entered before the keys. When the key
+    // is the start of a longer sequence, it's shown in the status bar
+    // until the sequence is finished.
+    fn command_for_key(&mut self, key: BoundKey) -> Option<(KeyCommand, Option<usize>)> {
+        if self.pending_keys.is_empty() {
+            self.pending_count = self.maybe_parse_input_buffer_as_number();
+        }
+        self.pending_keys.push(key);
+
+        match self.keybindings.lookup(&self.pending_keys) {
+            Lookup::Command(command) => {
+                self.pending_keys.clear();
+                self.input_buffer.clear();
+                Some((command, self.pending_count.take()))
+            }
+            Lookup::Prefix => {
+                self.buffer_key(key);
+                // Remind the user of what can follow longer sequences.
+                if self.pending_keys.len() > 1 {
+                    let continuations = self
+                        .keybindings
+                        .continuations(&self.pending_keys)
+                        .into_iter()
+                        .map(|(key, command)| {
+                            format!(
+                                "{} ({})",
+                                keybindings::format_key(key),
+                                self.keybindings.command_name(command)
+                            )
+                        })
+                        .collect::<Vec<String>>();
+                    self.set_info_message(continuations.join(", "));
+                }
+                None
+            }
+            Lookup::Unbound => {
+                // Typing an unbound key partway through a sequence just
+                // cancels it.
+                if self.pending_keys.len() == 1 {
+                    eprint!("{}\r", BELL);
+                }
+                self.pending_keys.clear();
+                self.pending_count = None;
+                self.input_buffer.clear();
+                None
+            }
+        }
+    }
+
+    // The command to run for a key, or for a step of a macro, which runs
+    // without a count.
+    fn command_for_event(&mut self, event: &TuiEvent) -> Option<(KeyCommand, Option<usize>)> {
+        match event {
+            KeyEvent(_) | TuiEvent::ModifiedKeyEvent(..) => {
+                self.command_for_key(event.bound_key()?)
+            }
+            TuiEvent::MacroStep(MacroStep::Command(command)) => Some((*command, None)),
+            TuiEvent::MacroStep(MacroStep::CommandLine(line)) => {
+                self.macro_command_line = Some(line.clone());
+                Some((KeyCommand::Command, None))
+            }
+            _ => None,
+        }
+    }
+
+    // Queues the steps of a macro, count times, to be run before reading
+    // any more input.
+    fn run_macro(&mut self, index: usize, count: usize) {
+        let steps = &self.keybindings.macros()[index].steps;
+        for _ in 0..count {
+            self.macro_steps.extend(steps.iter().cloned());
+        }
+    }
+
+    fn run_macro_by_name(&mut self, name: Option<String>) {
+        let name = match name {
+            Some(name) => name,
+            None => {
+                let names = self
+                    .keybindings
+                    .macros()
+                    .iter()
+                    .map(|m| m.name.as_str())
+                    .collect::<Vec<&str>>();
+                if names.is_empty() {
+                    self.set_info_message("No macros are defined in keys.toml".to_string());
+                } else {
+                    self.set_info_message(format!("Macros: {}", names.join(", ")));
+                }
+                return;
+            }
+        };
+
+        match self.keybindings.macro_by_name(&name) {
+            Some(KeyCommand::RunMacro(index)) => self.run_macro(index, 1),
+            _ => self.set_error_message(format!("Unknown macro: {}", name)),
+        }
+    }
+
+    // The next step of the macro being run. A step that waits for more
+    // input, e.g., searching or setting a mark, gets it from the keyboard,
+    // and the rest of the steps run once it's done.
+    fn next_macro_step(&mut self) -> Option<MacroStep> {
+        if self.input_state != InputState::Default
+            || self.incremental_search.is_some()
+            || self.palette.is_some()
+            || !self.pending_keys.is_empty()
+        {
+            return None;
+        }
+        self.macro_steps.pop_front()
+    }
+
+    fn open_palette(&mut self) {
+        let entries = self
+            .keybindings
+            .commands()
+            .into_iter()
+            .map(|(command, keys, name, description)| PaletteEntry {
+                command,
+                keys,
+                name: name.to_owned(),
+                description: description.to_owned(),
+            })
+            .collect();
+        self.palette = Some(Palette::new(entries));
+    }
+
+    // The command selected in the palette is run like a step of a macro,
+    // i.e., as if the keys bound to it had been pressed.
+    fn edit_palette(&mut self, event: TuiEvent) {
+        let palette = self.palette.as_mut().unwrap();
+        let palette_event = match event {
+            KeyEvent(key) => palette.handle_key(key),
+            TuiEvent::Paste(text) => {
+                palette.insert_str(text.trim());
+                PaletteEvent::Changed
+            }
+            _ => PaletteEvent::Changed,
+        };
+
+        match palette_event {
+            PaletteEvent::Changed => {}
+            PaletteEvent::Run(command) => {
+                self.palette = None;
+                self.screen_writer.hide_cursor();
+                self.macro_steps.push_front(MacroStep::Command(command));
+            }
+            PaletteEvent::Cancel => {
+                self.palette = None;
+                self.screen_writer.hide_cursor();
+            }
+        }
+    }
+
+    fn draw_palette(&mut self) {
+        let palette = self.palette.as_ref().unwrap();
+        let commands = palette
+            .matches()
+            .map(|entry| {
+                (
+                    entry.keys.clone(),
+                    entry.name.as_str(),
+                    entry.description.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let height = self.viewer.dimensions.height as usize;
+        // Keep the selected command on the screen.
+        let top = (palette.selected() + 1).saturating_sub(height);
+        self.screen_writer
+            .print_palette(&commands, top, palette.selected(), &palette.prompt);
+    }
+
+    // Shows a key in the status bar, along with any other keys typed
+    // before it.
+    fn buffer_key(&mut self, key: BoundKey) {
+        for byte in keybindings::format_key(key).bytes() {
+            self.buffer_input(byte);
+        }
+    }
+
     fn buffer_input(&mut self, ch: u8) {
         // Don't buffer leading 0s.
         if self.input_buffer.is_empty() && ch == b'0' {
@@ -493,44 +2580,279 @@ impl App {
         n.ok()
     }
 
-    fn parse_input_buffer_as_number(&mut self) -> usize {
-        self.maybe_parse_input_buffer_as_number().unwrap_or(1)
-    }
-
     fn get_search_input_and_start_search(
         &mut self,
         direction: SearchDirection,
         jumps: usize,
+        initial_input: &str,
     ) -> Option<Action> {
         let prompt_str = match direction {
             SearchDirection::Forward => "/",
             SearchDirection::Reverse => "?",
         };
 
-        let search_term = self.readline(prompt_str, "search input")?;
+        let history = self.search_history.entries().to_vec();
+
+        if self.opt.incsearch {
+            self.incremental_search = Some(IncrementalSearch {
+                prompt: Prompt::new(prompt_str, initial_input, history),
+                direction,
+                jumps,
+                focused_row: self.viewer.focused_row,
+                top_row: self.viewer.top_row,
+                search_state: std::mem::replace(&mut self.search_state, SearchState::empty()),
+                preview_pending: false,
+            });
+            self.preview_incremental_search();
+            return None;
+        }
+
+        let search_term = self.readline(prompt_str, initial_input, &history, "search input")?;
+        self.start_search(direction, jumps, search_term)
+    }
+
+    fn start_search(
+        &mut self,
+        direction: SearchDirection,
+        jumps: usize,
+        search_term: String,
+    ) -> Option<Action> {
+        self.search_history.add(&search_term);
+
+        // In vim, /<CR> or ?<CR> is a longcut for repeating the previous search.
+        if search_term.is_empty() {
+            // This will actually set the direction of a search going forward.
+            self.search_state.direction = direction;
+            self.jump_to_search_match(JumpDirection::Next, jumps)
+        } else {
+            if self.initialize_search(direction, search_term) {
+                if !self.search_state.any_matches() {
+                    self.set_warning_message(self.search_state.no_matches_message());
+                    None
+                } else {
+                    self.jump_to_search_match(JumpDirection::Next, jumps)
+                }
+            } else {
+                None
+            }
+        }
+    }
+
+    // Handles input while typing the pattern of an incremental search,
+    // returning the action that jumps to the first match once the pattern
+    // is submitted.
+    fn edit_incremental_search(&mut self, event: TuiEvent) -> Option<Action> {
+        let search = self.incremental_search.as_mut().unwrap();
+
+        let prompt_event = match event {
+            KeyEvent(key) => search.prompt.handle_key(key),
+            TuiEvent::Paste(text) => {
+                let text = text
+                    .split(|c| c == '\r' || c == '\n')
+                    .filter(|line| !line.is_empty())
+                    .collect::<Vec<&str>>()
+                    .join(" ");
+                search.prompt.insert_str(&text);
+                PromptEvent::Changed
+            }
+            _ => PromptEvent::Unchanged,
+        };
+
+        match prompt_event {
+            PromptEvent::Changed => {
+                if self.viewer.flatjson.1.len() > INCREMENTAL_SEARCH_DEBOUNCE_SIZE {
+                    search.preview_pending = true;
+                } else {
+                    self.preview_incremental_search();
+                }
+                None
+            }
+            PromptEvent::Unchanged => None,
+            PromptEvent::Submit(search_term) => {
+                let (direction, jumps) = self.finish_incremental_search();
+                self.start_search(direction, jumps, search_term)
+            }
+            PromptEvent::Cancel => {
+                self.finish_incremental_search();
+                None
+            }
+        }
+    }
+
+    fn preview_pending_incremental_search(&mut self) -> bool {
+        match &self.incremental_search {
+            Some(search) if search.preview_pending => {
+                self.preview_incremental_search();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Focuses the first match of the pattern typed so far, starting from
+    // where the search started. Nothing is shown for patterns without any
+    // matches, or that aren't valid yet (e.g., while typing "(a|b)").
+    fn preview_incremental_search(&mut self) {
+        let search = self.incremental_search.as_mut().unwrap();
+        search.preview_pending = false;
+        self.viewer.focused_row = search.focused_row;
+        self.viewer.top_row = search.top_row;
+
+        let preview = SearchState::initialize_search(
+            search.prompt.input().to_owned(),
+            &self.viewer.flatjson,
+            search.direction,
+            search.focused_row,
+        );
+
+        let mut preview = match preview {
+            Ok(preview) => preview,
+            Err(_) => {
+                self.search_state = SearchState::empty();
+                return;
+            }
+        };
+
+        self.search_state = match preview.step(
+            search.focused_row,
+            &self.viewer.flatjson,
+            JumpDirection::Next,
+            search.jumps,
+            !self.opt.no_wrapscan,
+        ) {
+            SearchStep::Jumped { row, .. } => {
+                preview.persist_highlights = search.search_state.persist_highlights;
+                self.viewer.perform_action(Action::MoveTo(row));
+                self.screen_writer
+                    .scroll_line_to_search_match(&self.viewer, preview.current_match_range());
+                preview
+            }
+            _ => SearchState::empty(),
+        };
+    }
+
+    // Stops showing the prompt for an incremental search, and restores the
+    // focus and search from before it started.
+    fn finish_incremental_search(&mut self) -> (SearchDirection, usize) {
+        let search = self.incremental_search.take().unwrap();
+        self.viewer.focused_row = search.focused_row;
+        self.viewer.top_row = search.top_row;
+        self.search_state = search.search_state;
+        self.screen_writer.hide_cursor();
+        (search.direction, search.jumps)
+    }
+
+    // Like less, '&' prompts for a pattern and only shows the rows that
+    // match it (along with their ancestors, so the structure is still
+    // visible). An empty pattern shows all the rows again.
+    fn filter_rows(&mut self) {
+        if self.still_loading() {
+            return;
+        }
+
+        let history = self.search_history.entries().to_vec();
+        let pattern = match self.readline("&", "", &history, "filter") {
+            Some(pattern) => pattern,
+            None => return,
+        };
+
+        let was_filtered = self.viewer.filtered();
+        self.viewer.clear_filter();
+        self.reset_search_state();
+        self.screen_writer.reset_row_state();
+
+        if pattern.is_empty() {
+            if was_filtered {
+                self.set_info_message("Showing all rows".to_string());
+            }
+            return;
+        }
+        self.search_history.add(&pattern);
+
+        let filter = match SearchState::initialize_search(
+            pattern,
+            &self.viewer.flatjson,
+            SearchDirection::Forward,
+            self.viewer.focused_row,
+        ) {
+            Ok(filter) => filter,
+            Err(err) => {
+                self.set_error_message(err);
+                return;
+            }
+        };
+
+        let rows = filter.matching_rows(&self.viewer.flatjson);
+        if rows.is_empty() {
+            self.set_warning_message(filter.no_matches_message());
+            return;
+        }
 
-        // In vim, /<CR> or ?<CR> is a longcut for repeating the previous search.
-        if search_term.is_empty() {
-            // This will actually set the direction of a search going forward.
-            self.search_state.direction = direction;
-            self.jump_to_search_match(JumpDirection::Next, jumps)
+        self.viewer.filter_rows(&rows);
+        self.set_info_message(format!(
+            "Showing {} matching {}; press & and Enter to show all rows",
+            rows.len(),
+            if rows.len() == 1 { "row" } else { "rows" }
+        ));
+    }
+
+    // Cycles between showing every value, hiding object members whose
+    // values are null, and hiding empty objects and arrays too, skipping
+    // the ones that wouldn't hide anything. This replaces any filter.
+    // Turning on the table view collapses the objects in arrays that can be
+    // shown as tables, so that each of them is a row of the table. They're
+    // left collapsed when it's turned off again.
+    fn toggle_table_view(&mut self) {
+        self.screen_writer.table_view = !self.screen_writer.table_view;
+        if !self.screen_writer.table_view {
+            self.set_info_message("Showing arrays of objects as usual".to_string());
+            return;
+        }
+
+        let num_tables = self.viewer.collapse_tables();
+        if num_tables == 0 {
+            self.set_warning_message("No arrays of objects with the same keys".to_string());
+        } else if self.viewer.mode != Mode::Data {
+            self.set_info_message("Tables are only shown in data mode".to_string());
         } else {
-            if self.initialize_search(direction, search_term) {
-                if !self.search_state.any_matches() {
-                    self.set_warning_message(self.search_state.no_matches_message());
-                    None
-                } else {
-                    self.jump_to_search_match(JumpDirection::Next, jumps)
-                }
-            } else {
-                None
+            let plural = if num_tables == 1 { "" } else { "s" };
+            self.set_info_message(format!(
+                "Showing {} array{} of objects as table{}",
+                num_tables, plural, plural
+            ));
+        }
+    }
+
+    fn cycle_hidden_values(&mut self) {
+        let mut hidden_values = self.viewer.hidden_values().next();
+        self.viewer.clear_filter();
+        self.reset_search_state();
+        self.screen_writer.reset_row_state();
+
+        while hidden_values != HiddenValues::Nothing {
+            if self.viewer.hide_values(hidden_values) {
+                break;
             }
+            hidden_values = hidden_values.next();
         }
+
+        let msg = match hidden_values {
+            HiddenValues::Nothing => "Showing all values",
+            HiddenValues::Nulls => "Hiding null values",
+            HiddenValues::NullsAndEmpty => "Hiding null values and empty objects and arrays",
+        };
+        self.set_info_message(msg.to_string());
     }
 
     fn initialize_search(&mut self, direction: SearchDirection, search_term: String) -> bool {
-        match SearchState::initialize_search(search_term, &self.viewer.flatjson.1, direction) {
-            Ok(ss) => {
+        match SearchState::initialize_search(
+            search_term,
+            &self.viewer.flatjson,
+            direction,
+            self.viewer.focused_row,
+        ) {
+            Ok(mut ss) => {
+                ss.persist_highlights = self.search_state.persist_highlights;
                 self.search_state = ss;
                 true
             }
@@ -576,29 +2898,519 @@ impl App {
         if !self.search_state.ever_searched {
             self.set_info_message("Type / to search".to_string());
             return None;
-        } else if !self.search_state.any_matches() {
-            self.set_warning_message(self.search_state.no_matches_message());
-            return None;
         }
 
-        let destination = self.search_state.jump_to_match(
+        match self.search_state.step(
             self.viewer.focused_row,
             &self.viewer.flatjson,
             jump_direction,
             jumps,
-        );
-        Some(Action::MoveTo(destination))
+            !self.opt.no_wrapscan,
+        ) {
+            SearchStep::Jumped { row, wrapped } => {
+                match wrapped {
+                    Some(SearchDirection::Forward) => {
+                        self.set_info_message("Search hit BOTTOM, continuing at TOP".to_string())
+                    }
+                    Some(SearchDirection::Reverse) => {
+                        self.set_info_message("Search hit TOP, continuing at BOTTOM".to_string())
+                    }
+                    None => {}
+                }
+                Some(Action::MoveTo(row))
+            }
+            SearchStep::NoMoreMatches(direction) => {
+                let end = match direction {
+                    SearchDirection::Forward => "BOTTOM",
+                    SearchDirection::Reverse => "TOP",
+                };
+                self.set_warning_message(format!(
+                    "Search hit {}, no more matches: {}",
+                    end, self.search_state.search_term
+                ));
+                None
+            }
+            SearchStep::NoMatches => {
+                self.set_warning_message(self.search_state.no_matches_message());
+                None
+            }
+        }
     }
 
     fn parse_command(command: &str) -> Command {
+        let (name, argument) = match command.trim().split_once(' ') {
+            Some((name, argument)) => (name, Some(argument.trim().to_string())),
+            None => (command.trim(), None),
+        };
+
+        match name {
+            "collapse" => {
+                return Command::SetCollapsedByKey {
+                    collapsed: true,
+                    pattern: argument,
+                }
+            }
+            "expand" => {
+                return Command::SetCollapsedByKey {
+                    collapsed: false,
+                    pattern: argument,
+                }
+            }
+            "jq" => return Command::Jq { program: argument },
+            "export" | "export-all" | "export-full" => {
+                return Command::Export {
+                    include_collapsed: name != "export",
+                    whole_input: name == "export-full",
+                    format: argument,
+                }
+            }
+            "scrolloff" | "so" => return Command::SetScrolloff(argument),
+            "format" => return Command::SetFormat(argument),
+            "run" => return Command::RunMacro(argument),
+            _ => {}
+        }
+
         match command {
             "h" | "he" | "hel" | "help" => Command::Help,
             "q" | "qu" | "qui" | "quit" | "quit()" | "exit" | "exit()" => Command::Quit,
-            _ => Command::Unknown,
+            "marks" => Command::Marks,
+            "stats" => Command::Stats,
+            "u" | "undo" => Command::Undo,
+            "clear-state" => Command::ClearState,
+            _ => match command.parse::<usize>() {
+                Ok(line) => Command::JumpToLine(line),
+                Err(_) => Command::Unknown,
+            },
+        }
+    }
+
+    fn set_scrolloff(&mut self, rows: Option<String>) {
+        let rows = match rows {
+            Some(rows) => rows,
+            None => {
+                self.set_info_message(format!("scrolloff is {}", self.opt.scrolloff));
+                return;
+            }
+        };
+
+        match rows.parse::<u16>() {
+            Ok(rows) => {
+                // The setting is kept in the options so that it applies to
+                // every file, and to views replaced by :jq.
+                self.opt.scrolloff = rows;
+                self.viewer.scrolloff_setting = rows;
+                self.viewer
+                    .perform_action(Action::ResizeViewerDimensions(self.viewer.dimensions));
+            }
+            Err(_) => self.set_error_message(format!("Invalid number of rows: {}", rows)),
+        }
+    }
+
+    fn set_format(&mut self, format: Option<String>) {
+        let source = match &self.source {
+            Some(source) => source,
+            None => {
+                self.set_warning_message("The input wasn't parsed by jless".to_string());
+                return;
+            }
+        };
+
+        let format = match format {
+            Some(format) => format,
+            None => {
+                let message = format!(
+                    "Parsed as {} ({})",
+                    source.data_format.name(),
+                    source.format_source.description()
+                );
+                self.set_info_message(message);
+                return;
+            }
+        };
+        let data_format = match DataFormat::from_name(&format) {
+            Some(data_format) => data_format,
+            None => {
+                self.set_error_message(format!(
+                    "Unknown format: {} (expected json, jsonl, yaml, toml, csv, or tsv)",
+                    format
+                ));
+                return;
+            }
+        };
+
+        if self.still_loading() {
+            return;
+        }
+
+        let source = self.source.as_ref().unwrap();
+        let (data, replacements) = match (&source.data, &source.path) {
+            (Some(data), _) => (data.clone(), source.replacements.clone()),
+            (None, Some(path)) => match std::fs::read(path) {
+                Ok(data) => decode_input(data),
+                Err(err) => {
+                    let message = format!("Unable to read {}: {}", source.filename, err);
+                    self.set_error_message(message);
+                    return;
+                }
+            },
+            (None, None) => {
+                let message = format!(
+                    "{} can't be read again, since its format was set with a flag",
+                    source.filename
+                );
+                self.set_warning_message(message);
+                return;
+            }
+        };
+
+        let filename = source.filename.clone();
+        let input = InputFile::new(
+            data,
+            data_format,
+            FormatSource::Command,
+            filename.clone(),
+            source.path.clone(),
+        )
+        .with_replacements(replacements);
+        let file = match Self::load_file(&self.opt, input, &mut *self.screen_writer.stdout, true) {
+            Ok(file) => file,
+            Err(err) => {
+                let message = format!("Unable to parse as {}: {}", data_format.name(), err);
+                self.set_error_message(message);
+                return;
+            }
+        };
+
+        // Nothing in the previous view, or the output of jq programs based
+        // on it, corresponds to the new one, so it starts at the top.
+        let mut viewer = file.viewer;
+        viewer.scrolloff_setting = self.opt.scrolloff;
+        viewer.perform_action(Action::ResizeViewerDimensions(self.viewer.dimensions));
+
+        self.viewer = viewer;
+        self.input_filename = file.input_filename;
+        self.previous_views.clear();
+        self.loading_progress = file.loading_progress;
+        self.watched = file.watched;
+        self.followed = file.followed;
+        self.source = file.source;
+
+        self.reset_search_state();
+        self.screen_writer.reset_row_state();
+        self.set_info_message(format!("Parsed {} as {}", filename, data_format.name()));
+        self.warn_if_too_deep();
+    }
+
+    fn clear_saved_state(&mut self) {
+        match self.state_cache.clear() {
+            Ok(()) => self.set_info_message("Forgot the saved state of all files".to_string()),
+            Err(err) => self.set_error_message(format!("Unable to clear saved state: {}", err)),
+        }
+    }
+
+    fn set_collapsed_state_by_key(&mut self, collapsed: bool, pattern: Option<String>) {
+        let (verb, past_tense) = if collapsed {
+            ("Collapse", "Collapsed")
+        } else {
+            ("Expand", "Expanded")
+        };
+
+        let pattern = match pattern {
+            Some(pattern) => pattern,
+            None => match self.readline(&format!("{} key: ", verb), "", &[], "key") {
+                Some(pattern) => pattern,
+                None => return,
+            },
+        };
+
+        if pattern.is_empty() {
+            return;
+        }
+
+        let num_matches = self.viewer.set_collapsed_state_by_key(&pattern, collapsed);
+        if num_matches == 0 {
+            self.set_warning_message(format!("No objects or arrays with key: {}", pattern));
+        } else {
+            self.set_info_message(format!(
+                "{} {} objects and arrays with key: {}",
+                past_tense, num_matches, pattern
+            ));
+        }
+    }
+
+    fn transform_with_jq(&mut self, program: Option<String>) {
+        if self.still_loading() {
+            return;
+        }
+        // jq would be given the placeholders of containers that weren't
+        // parsed, which aren't JSON.
+        if let Err(err) = self.viewer.flatjson.check_all_parsed() {
+            self.set_error_message(format!("Unable to run jq: {}", err));
+            return;
+        }
+
+        let program = match program {
+            Some(program) => program,
+            None => match self.readline("jq ", "", &[], "jq program") {
+                Some(program) => program,
+                None => return,
+            },
+        };
+
+        if program.is_empty() {
+            return;
+        }
+
+        let output = match jq::run(&program, &self.viewer.flatjson.1) {
+            Ok(output) => output,
+            Err(err) => {
+                self.set_error_message(err);
+                return;
+            }
+        };
+
+        let flatjson = match flatjson::parse_top_level_json_to_depth(output, self.opt.max_depth) {
+            Ok(flatjson) => flatjson,
+            Err(err) => {
+                self.set_error_message(format!("Unable to parse jq output: {}", err));
+                return;
+            }
+        };
+
+        let mut viewer = JsonViewer::new(flatjson, self.viewer.mode);
+        viewer.dimensions = self.viewer.dimensions;
+        viewer.scrolloff_setting = self.viewer.scrolloff_setting;
+
+        let filename = format!("{} | jq {}", self.input_filename, program);
+        let previous_viewer = std::mem::replace(&mut self.viewer, viewer);
+        let previous_filename = std::mem::replace(&mut self.input_filename, filename);
+        self.previous_views
+            .push((previous_viewer, previous_filename));
+
+        self.reset_search_state();
+        self.screen_writer.reset_row_state();
+        self.set_info_message("Use :undo to return to the previous input".to_string());
+    }
+
+    fn undo_jq(&mut self) {
+        let (mut viewer, filename) = match self.previous_views.pop() {
+            Some(previous) => previous,
+            None => {
+                self.set_warning_message("Nothing to undo".to_string());
+                return;
+            }
+        };
+
+        // The window may have been resized, or scrolloff changed, since
+        // this view was replaced.
+        viewer.scrolloff_setting = self.opt.scrolloff;
+        viewer.perform_action(Action::ResizeViewerDimensions(self.viewer.dimensions));
+        if viewer.mode != self.viewer.mode {
+            viewer.perform_action(Action::ToggleMode);
+        }
+        self.viewer = viewer;
+        self.input_filename = filename;
+
+        self.reset_search_state();
+        self.screen_writer.reset_row_state();
+    }
+
+    fn export(&mut self, include_collapsed: bool, whole_input: bool, format: Option<String>) {
+        let format = match format.as_deref().map_or(Ok(ExportFormat::Json), str::parse) {
+            Ok(format) => format,
+            Err(err) => {
+                self.set_error_message(err);
+                return;
+            }
+        };
+
+        let flatjson = if whole_input {
+            self.viewer.original_flatjson()
+        } else {
+            &self.viewer.flatjson
+        };
+        let contents = match export::export(flatjson, format, include_collapsed) {
+            Ok(contents) => contents,
+            Err(err) => {
+                self.set_error_message(format!("Unable to export {}: {}", format.name(), err));
+                return;
+            }
+        };
+
+        let prompt = format!("Export {} to (+ for the clipboard): ", format.name());
+        let path = match self.readline(&prompt, "", &[], "filename") {
+            Some(path) if !path.is_empty() => path,
+            _ => return,
+        };
+
+        if path == "+" {
+            if let Err(err) = &self.clipboard_context {
+                if !self.use_osc52 {
+                    self.set_error_message(format!("Unable to access clipboard: {}", err));
+                    return;
+                }
+            }
+            match self.set_clipboard_contents(contents) {
+                Ok(()) => self.set_info_message(format!("Copied {} to clipboard", format.name())),
+                Err(err) => self.set_error_message(format!(
+                    "Unable to copy {} to clipboard: {}",
+                    format.name(),
+                    err
+                )),
+            }
+            return;
+        }
+
+        match std::fs::write(&path, contents) {
+            Ok(()) => self.set_info_message(format!("Exported {} to {}", format.name(), path)),
+            Err(err) => self.set_error_message(format!("Unable to export to {}: {}", path, err)),
+        }
+    }
+
+    fn show_marks(&mut self) {
+        let marks = self
+            .viewer
+            .marks()
+            .map(|(mark, index)| {
+                let path = self
+                    .viewer
+                    .flatjson
+                    .build_path_to_node(flatjson::PathType::DotWithTopLevelIndex, index)
+                    .unwrap();
+                format!("{}: input{}", mark, path)
+            })
+            .collect::<Vec<String>>();
+
+        if marks.is_empty() {
+            self.set_info_message("No marks set".to_string());
+        } else {
+            self.set_info_message(marks.join("  "));
+        }
+    }
+
+    // Performs an action that doesn't take a count itself N times, e.g.,
+    // '3h' collapses the focused container, then moves to its parent and
+    // collapses that. The last repetition is returned to be performed like
+    // any other action. Repeating stops early once the action stops doing
+    // anything.
+    // Scrolls up or down by a page (or count pages). When strings are
+    // wrapped, rows can take up multiple lines of the screen, so a page is
+    // however many rows fit on the screen, and the top row of the new page
+    // is focused, rather than one scrolloff rows below it, which could be
+    // past the bottom of the screen.
+    fn page(&mut self, down: bool, count: Option<usize>) -> Option<Action> {
+        let rows = self.screen_writer.rows_per_page(&self.viewer, down) * count.unwrap_or(1);
+        let action = if down {
+            Action::PageDown(rows)
+        } else {
+            Action::PageUp(rows)
+        };
+        if !self.screen_writer.wraps_any_rows() {
+            return Some(action);
+        }
+
+        let top_row = self.viewer.top_row;
+        self.viewer.perform_action(action);
+        if self.viewer.top_row != top_row {
+            self.viewer.focused_row = self.viewer.top_row;
+        }
+        None
+    }
+
+    // Jumps from a YAML alias (or a value in one) to the value its anchor
+    // is defined on, or from an anchor to the next alias that refers to it.
+    fn jump_to_anchor(&mut self) -> Option<Action> {
+        let flatjson = &self.viewer.flatjson;
+        let focused_row = self.viewer.focused_row;
+
+        let mut alias = flatjson::OptionIndex::Index(focused_row);
+        while let flatjson::OptionIndex::Index(index) = alias {
+            match &flatjson[index].anchor {
+                Some(anchor) if anchor.is_alias || index == focused_row => break,
+                _ => alias = flatjson[index].parent,
+            }
+        }
+
+        let anchor = match alias {
+            flatjson::OptionIndex::Index(index) => flatjson[index].anchor.clone().unwrap(),
+            flatjson::OptionIndex::Nil => {
+                self.set_warning_message("Not a YAML alias or anchor".to_string());
+                return None;
+            }
+        };
+
+        let refers_to_anchor = |index: flatjson::Index| {
+            flatjson[index].anchor.as_ref().map_or(false, |other| {
+                other.id == anchor.id && other.is_alias != anchor.is_alias
+            })
+        };
+        let target = if anchor.is_alias {
+            (0..flatjson.0.len()).find(|&index| refers_to_anchor(index))
+        } else {
+            // Cycle through the aliases.
+            (focused_row + 1..flatjson.0.len())
+                .chain(0..focused_row)
+                .find(|&index| refers_to_anchor(index))
+        };
+
+        match target {
+            Some(index) => Some(Action::RevealAndMoveTo(index)),
+            None => {
+                let message = if anchor.is_alias {
+                    // e.g., if it's been removed by a filter.
+                    format!("The anchor &{} isn't shown", anchor.name)
+                } else {
+                    format!("There are no aliases of &{} shown", anchor.name)
+                };
+                self.set_warning_message(message);
+                None
+            }
+        }
+    }
+
+    fn repeat_action(&mut self, action: Action, count: Option<usize>) -> Option<Action> {
+        for _ in 1..count.unwrap_or(1) {
+            let focused_row = self.viewer.focused_row;
+            let collapsed = self.viewer.flatjson[focused_row].is_collapsed();
+            self.viewer.perform_action(action);
+            if self.viewer.focused_row == focused_row
+                && self.viewer.flatjson[focused_row].is_collapsed() == collapsed
+            {
+                break;
+            }
+        }
+        Some(action)
+    }
+
+    fn jump_to_percentage(&mut self, percent: usize) -> Option<Action> {
+        let num_lines = self.viewer.flatjson.0.len();
+        let percent = percent.min(100);
+        // Same formula as vim, which rounds up.
+        let line = (percent * num_lines + 99) / 100;
+        self.jump_to_line(line)
+    }
+
+    // Line numbers are 1-based, and refer to lines of the input when
+    // pretty printed (i.e., as displayed in line mode).
+    fn jump_to_line(&mut self, line: usize) -> Option<Action> {
+        let num_lines = self.viewer.flatjson.0.len();
+
+        if line > num_lines {
+            self.set_info_message(format!(
+                "Line {} is past the end of the input; there are only {} lines",
+                line, num_lines
+            ));
         }
+
+        let index = line.clamp(1, num_lines) - 1;
+        Some(Action::RevealAndMoveTo(index))
     }
 
     fn show_help(&mut self) {
+        if self.offscreen.is_some() {
+            return;
+        }
+
         let _ = write!(self.screen_writer.stdout, "{}", ToMainScreen);
         let child = std::process::Command::new("less")
             .arg("-r")
@@ -623,24 +3435,71 @@ impl App {
     }
 
     fn copy_content(&mut self, copy_target: CopyTarget) {
-        // Checked when the user first hits 'y'.
-        let clipboard = self.clipboard_context.as_mut().unwrap();
+        if let Err(err) = &self.clipboard_context {
+            if !self.use_osc52 {
+                let msg = format!("Unable to access clipboard: {}", err);
+                self.set_error_message(msg);
+                return;
+            }
+        }
 
         let json = &self.viewer.flatjson.1;
         let focused_row_index = self.viewer.focused_row;
         let focused_row = &self.viewer.flatjson[focused_row_index];
 
+        // Values are copied with their object keys in the original order,
+        // even if keys are being displayed sorted.
+        let (original_flatjson, original_index) =
+            self.viewer.original_flatjson_and_index(focused_row_index);
+
+        // Placeholders for containers that weren't parsed aren't copied as
+        // if they were values.
+        let parsed = match copy_target {
+            CopyTarget::PrettyPrintedValue | CopyTarget::OneLineValue => {
+                original_flatjson.check_parsed(original_index)
+            }
+            CopyTarget::DisplayedValue | CopyTarget::GronPath => {
+                self.viewer.flatjson.check_parsed(focused_row_index)
+            }
+            _ => Ok(()),
+        };
+        if let Err(err) = parsed {
+            self.set_error_message(format!("Unable to copy value: {}", err));
+            return;
+        }
+
         let (content_desc, content) = match copy_target {
             CopyTarget::PrettyPrintedValue if focused_row.is_container() => (
                 "pretty-printed value",
+                original_flatjson
+                    .pretty_printed_value(original_index)
+                    .unwrap(),
+            ),
+            CopyTarget::PrettyPrintedValue | CopyTarget::OneLineValue => {
+                let range = original_flatjson[original_index].range.clone();
+                ("value", original_flatjson.1[range].to_string())
+            }
+            CopyTarget::DisplayedValue if focused_row.is_container() => (
+                "displayed value",
                 self.viewer
                     .flatjson
                     .pretty_printed_value(focused_row_index)
                     .unwrap(),
             ),
-            CopyTarget::PrettyPrintedValue | CopyTarget::OneLineValue => {
-                let range = focused_row.range.clone();
-                ("value", json[range].to_string())
+            CopyTarget::DisplayedValue => ("value", json[focused_row.range.clone()].to_string()),
+            CopyTarget::DecodedValue => {
+                match self
+                    .screen_writer
+                    .transformed_value(&self.viewer, focused_row_index)
+                {
+                    Some((_, decoded)) => ("decoded value", decoded),
+                    None => {
+                        self.set_warning_message(
+                            "The focused value isn't decoded; press zd to decode it".to_string(),
+                        );
+                        return;
+                    }
+                }
             }
             CopyTarget::Key => {
                 if let Some(key_range) = &focused_row.key_range {
@@ -656,16 +3515,33 @@ impl App {
                     };
 
                     ("key", copied_key)
+                } else if focused_row.parent.is_some() && !focused_row.is_closing_of_container() {
+                    ("index", focused_row.index.to_string())
                 } else {
-                    self.set_warning_message("No object key to copy".to_string());
+                    self.set_warning_message("No object key or array index to copy".to_string());
                     return;
                 }
             }
-            ct @ (CopyTarget::DotPath | CopyTarget::BracketPath | CopyTarget::QueryPath) => {
+            CopyTarget::GronPath => match self
+                .viewer
+                .flatjson
+                .build_gron_assignment(focused_row_index)
+            {
+                Ok(line) => ("gron path", line),
+                Err(err) => {
+                    self.set_error_message(err);
+                    return;
+                }
+            },
+            ct @ (CopyTarget::DotPath
+            | CopyTarget::BracketPath
+            | CopyTarget::QueryPath
+            | CopyTarget::PlainPath) => {
                 let (content_desc, path_type) = match ct {
                     CopyTarget::DotPath => ("path", flatjson::PathType::Dot),
                     CopyTarget::BracketPath => ("bracketed path", flatjson::PathType::Bracket),
                     CopyTarget::QueryPath => ("query path", flatjson::PathType::Query),
+                    CopyTarget::PlainPath => ("plain path", flatjson::PathType::Plain),
                     _ => unreachable!(),
                 };
 
@@ -681,9 +3557,20 @@ impl App {
                     }
                 }
             }
+            CopyTarget::Source => match self.focused_source() {
+                Some((range, Some(text))) => ("source", text[range].to_owned()),
+                Some((_, None)) => {
+                    self.set_warning_message(
+                        "The input wasn't kept, so the focused value can't be copied from it"
+                            .to_string(),
+                    );
+                    return;
+                }
+                None => return,
+            },
         };
 
-        if let Err(err) = clipboard.set_contents(content) {
+        if let Err(err) = self.set_clipboard_contents(content) {
             self.set_error_message(format!(
                 "Unable to copy {} to clipboard: {}",
                 content_desc, err
@@ -692,4 +3579,112 @@ impl App {
             self.set_info_message(format!("Copied {} to clipboard", content_desc));
         }
     }
+
+    // Where the focused value is in the input, as a range of bytes, along
+    // with the input up to the end of it, if it can be read. Only values
+    // parsed from JSON inputs (not JSON Lines, or the output of jq) know
+    // where they are.
+    fn focused_source(&mut self) -> Option<(Range<usize>, Option<String>)> {
+        if self.still_loading() {
+            return None;
+        }
+        let is_json =
+            matches!(&self.source, Some(source) if source.data_format == DataFormat::Json);
+        if !is_json || !self.previous_views.is_empty() {
+            self.set_warning_message(
+                "Only values parsed from JSON input know where they are in it".to_string(),
+            );
+            return None;
+        }
+
+        // The opening and closing rows of a container are both the whole
+        // container.
+        let (flatjson, index) = self
+            .viewer
+            .original_flatjson_and_index(self.viewer.focused_row);
+        let index = if flatjson[index].is_closing_of_container() {
+            flatjson[index].pair_index().unwrap()
+        } else {
+            index
+        };
+        let range = match &flatjson[index].source_range {
+            Some(range) => range.clone(),
+            None => {
+                self.set_warning_message("The focused value isn't in the input".to_string());
+                return None;
+            }
+        };
+
+        match self.source.as_ref().unwrap().read_up_to(range.end) {
+            Ok(text) => Some((range, text)),
+            Err(err) => {
+                self.set_error_message(err);
+                None
+            }
+        }
+    }
+
+    // Shows where the focused value is in the input, e.g., "Line 7, column
+    // 5 to line 12, column 2 of the input (bytes 120..245)", for finding it
+    // from the errors of other tools.
+    fn show_source_position(&mut self) {
+        let (range, text) = match self.focused_source() {
+            Some(source) => source,
+            None => return,
+        };
+        // The range is in the decoded input, so it's different when invalid
+        // UTF-8 in the input was replaced.
+        let original_range = self
+            .source
+            .as_ref()
+            .unwrap()
+            .replacements
+            .original_range(&range);
+
+        let message = match text {
+            Some(text) => {
+                let (start_line, start_column) = jsonparser::line_and_column(&text, range.start);
+                // Where the last character of the value is.
+                let last = text.char_indices().next_back().map_or(0, |(i, _)| i);
+                let (end_line, end_column) = jsonparser::line_and_column(&text, last);
+                let position = if (start_line, start_column) == (end_line, end_column) {
+                    format!("Line {}, column {}", start_line, start_column)
+                } else if start_line == end_line {
+                    format!(
+                        "Line {}, columns {} to {}",
+                        start_line, start_column, end_column
+                    )
+                } else {
+                    format!(
+                        "Line {}, column {} to line {}, column {}",
+                        start_line, start_column, end_line, end_column
+                    )
+                };
+                format!(
+                    "{} of the input (bytes {}..{})",
+                    position, original_range.start, original_range.end
+                )
+            }
+            // Inputs from STDIN whose format was chosen with a flag
+            // aren't kept.
+            None => format!(
+                "Bytes {}..{} of the input",
+                original_range.start, original_range.end
+            ),
+        };
+        self.set_info_message(message);
+    }
+
+    fn set_clipboard_contents(&mut self, content: String) -> Result<(), Box<dyn Error>> {
+        if self.use_osc52 {
+            let stdout = &mut self.screen_writer.stdout;
+            write!(stdout, "{}", osc52::copy_sequence(&content)?)?;
+            stdout.flush()?;
+            return Ok(());
+        }
+
+        // Checked before copying anything.
+        let clipboard = self.clipboard_context.as_mut().unwrap();
+        clipboard.set_contents(content)
+    }
 }
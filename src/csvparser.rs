@@ -0,0 +1,240 @@
+use std::fmt::Write;
+
+// Options controlling how CSV (or TSV) input is converted to JSON.
+#[derive(Copy, Clone, Debug)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    // When true, the first record contains the names of each column,
+    // and each subsequent record is converted to an object. Otherwise
+    // each record is converted to an array.
+    pub has_header: bool,
+    // Whether empty fields are converted to null instead of "".
+    pub empty_as_null: bool,
+}
+
+// Converts CSV input to an equivalent JSON array of records, which can
+// then be parsed normally. All (non-empty) fields are converted to JSON
+// strings, since CSV values don't have types.
+pub fn csv_to_json(csv: &str, options: CsvOptions) -> Result<String, String> {
+    let mut records = parse_records(csv, options.delimiter)?.into_iter();
+
+    let header = if options.has_header {
+        match records.next() {
+            Some(header) => header,
+            None => return Err("Missing header row in CSV input".to_owned()),
+        }
+    } else {
+        vec![]
+    };
+
+    let mut json = String::new();
+    json.push('[');
+
+    for (i, record) in records.enumerate() {
+        if i != 0 {
+            json.push(',');
+        }
+
+        if options.has_header {
+            json.push('{');
+            for (j, field) in record.iter().enumerate() {
+                if j != 0 {
+                    json.push(',');
+                }
+                // Key extra fields by their (zero-based) position.
+                match header.get(j) {
+                    Some(key) => push_json_string(&mut json, key),
+                    None => push_json_string(&mut json, &j.to_string()),
+                }
+                json.push(':');
+                push_json_field(&mut json, field, options.empty_as_null);
+            }
+            json.push('}');
+        } else {
+            json.push('[');
+            for (j, field) in record.iter().enumerate() {
+                if j != 0 {
+                    json.push(',');
+                }
+                push_json_field(&mut json, field, options.empty_as_null);
+            }
+            json.push(']');
+        }
+    }
+
+    json.push(']');
+    Ok(json)
+}
+
+// Splits CSV input into records of fields, handling fields surrounded by
+// double quotes, which may contain delimiters, newlines, or escaped
+// double quotes ("").
+fn parse_records(csv: &str, delimiter: char) -> Result<Vec<Vec<String>>, String> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    // Whether the current field started with a quote; used to avoid
+    // treating an empty quoted field at the end of a line as an empty line.
+    let mut field_was_quoted = false;
+    let mut line = 1;
+
+    let mut chars = csv.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if in_quotes {
+            match ch {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                '\n' => {
+                    line += 1;
+                    field.push(ch);
+                }
+                _ => field.push(ch),
+            }
+            continue;
+        }
+
+        match ch {
+            '"' if field.is_empty() && !field_was_quoted => {
+                in_quotes = true;
+                field_was_quoted = true;
+            }
+            '"' => {
+                return Err(format!(
+                    "Unexpected quote in unquoted field on line {}",
+                    line
+                ))
+            }
+            ch if ch == delimiter => {
+                record.push(std::mem::take(&mut field));
+                field_was_quoted = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                line += 1;
+                // Skip blank lines.
+                if !record.is_empty() || !field.is_empty() || field_was_quoted {
+                    record.push(std::mem::take(&mut field));
+                    records.push(std::mem::take(&mut record));
+                }
+                field_was_quoted = false;
+            }
+            _ => field.push(ch),
+        }
+    }
+
+    if in_quotes {
+        return Err("Unterminated quoted field in CSV input".to_owned());
+    }
+
+    if !record.is_empty() || !field.is_empty() || field_was_quoted {
+        record.push(field);
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+fn push_json_field(json: &mut String, field: &str, empty_as_null: bool) {
+    if field.is_empty() && empty_as_null {
+        json.push_str("null");
+    } else {
+        push_json_string(json, field);
+    }
+}
+
+fn push_json_string(json: &mut String, s: &str) {
+    json.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            ch if ch.is_control() => {
+                let _ = write!(json, "\\u{:04x}", ch as u32);
+            }
+            ch => json.push(ch),
+        }
+    }
+    json.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: CsvOptions = CsvOptions {
+        delimiter: ',',
+        has_header: true,
+        empty_as_null: false,
+    };
+
+    #[test]
+    fn test_csv_with_header() {
+        let csv = "name,age\nalice,30\r\nbob,\n";
+        assert_eq!(
+            Ok(r#"[{"name":"alice","age":"30"},{"name":"bob","age":""}]"#.to_owned()),
+            csv_to_json(csv, CSV),
+        );
+
+        let options = CsvOptions {
+            empty_as_null: true,
+            ..CSV
+        };
+        assert_eq!(
+            Ok(r#"[{"name":"alice","age":"30"},{"name":"bob","age":null}]"#.to_owned()),
+            csv_to_json(csv, options),
+        );
+
+        // Extra fields are keyed by position; missing fields are omitted.
+        assert_eq!(
+            Ok(r#"[{"a":"1","b":"2","2":"3"},{"a":"4"}]"#.to_owned()),
+            csv_to_json("a,b\n1,2,3\n4", CSV),
+        );
+
+        assert!(csv_to_json("", CSV).is_err());
+        assert_eq!(Ok("[]".to_owned()), csv_to_json("a,b\n", CSV));
+    }
+
+    #[test]
+    fn test_csv_without_header() {
+        let options = CsvOptions {
+            has_header: false,
+            ..CSV
+        };
+        assert_eq!(
+            Ok(r#"[["a","b"],["1",""]]"#.to_owned()),
+            csv_to_json("a,b\n\n1,\n", options),
+        );
+    }
+
+    #[test]
+    fn test_quoted_fields() {
+        let csv = "a,b\n\"x, y\",\"line 1\nline 2\"\n\"say \"\"hi\"\"\",\"\"\n";
+        assert_eq!(
+            Ok(r#"[{"a":"x, y","b":"line 1\nline 2"},{"a":"say \"hi\"","b":""}]"#.to_owned()),
+            csv_to_json(csv, CSV),
+        );
+
+        assert!(csv_to_json("a\n\"unterminated\n", CSV).is_err());
+        assert!(csv_to_json("a\nb\"c\n", CSV).is_err());
+    }
+
+    #[test]
+    fn test_tsv() {
+        let options = CsvOptions {
+            delimiter: '\t',
+            ..CSV
+        };
+        assert_eq!(
+            Ok(r#"[{"a,b":"1","c":"2\\3"}]"#.to_owned()),
+            csv_to_json("a,b\tc\n1\t2\\3\n", options),
+        );
+    }
+}
@@ -0,0 +1,390 @@
+// Values from other data models, e.g., already parsed TOML documents, or
+// a serde_json::Value in a program embedding jless, can be converted to a
+// FlatJson directly, rather than being printed as JSON and parsed again.
+//
+// Implementing DataModel only requires describing what kind of value each
+// node is; building the rows is handled here.
+
+use std::borrow::Cow;
+
+use crate::flatjson::{
+    too_deep_placeholder, ContainerType, FlatJson, Index, OptionIndex, Row, Value,
+    DEFAULT_MAX_DEPTH,
+};
+
+/// A data model whose values can be viewed by jless. This is implemented
+/// by a separate type, rather than by the values themselves, so that it
+/// can be implemented for values defined by other crates.
+pub trait DataModel {
+    type Value: ?Sized;
+
+    fn node<'a>(&self, value: &'a Self::Value) -> Node<'a, Self::Value>;
+}
+
+/// What kind of value a value is, along with its contents.
+pub enum Node<'a, V: ?Sized> {
+    Null,
+    Boolean(bool),
+    /// The text of a number, e.g., "1.5e3", which should be a valid JSON
+    /// number.
+    Number(Cow<'a, str>),
+    /// The contents of a string, which will be escaped as necessary.
+    String(Cow<'a, str>),
+    Array(Vec<&'a V>),
+    /// The entries of an object, in the order they should be shown.
+    Object(Vec<(Cow<'a, str>, &'a V)>),
+}
+
+/// The data model of serde_json, for viewing a serde_json::Value.
+#[cfg(feature = "serde_json")]
+pub struct SerdeJson;
+
+#[cfg(feature = "serde_json")]
+impl DataModel for SerdeJson {
+    type Value = serde_json::Value;
+
+    fn node<'a>(&self, value: &'a serde_json::Value) -> Node<'a, serde_json::Value> {
+        match value {
+            serde_json::Value::Null => Node::Null,
+            serde_json::Value::Bool(b) => Node::Boolean(*b),
+            serde_json::Value::Number(n) => Node::Number(Cow::Owned(n.to_string())),
+            serde_json::Value::String(s) => Node::String(Cow::Borrowed(s)),
+            serde_json::Value::Array(elements) => Node::Array(elements.iter().collect()),
+            serde_json::Value::Object(entries) => Node::Object(
+                entries
+                    .iter()
+                    .map(|(key, value)| (Cow::Borrowed(key.as_str()), value))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl FlatJson {
+    pub fn from_value<M: DataModel>(model: &M, value: &M::Value) -> FlatJson {
+        FlatJson::from_value_to_depth(model, value, DEFAULT_MAX_DEPTH)
+    }
+
+    /// Like from_value, but replacing containers nested more than
+    /// max_depth levels deep with a placeholder.
+    pub fn from_value_to_depth<M: DataModel>(
+        model: &M,
+        value: &M::Value,
+        max_depth: usize,
+    ) -> FlatJson {
+        let mut builder = Builder {
+            parents: vec![],
+            rows: vec![],
+            pretty_printed: String::new(),
+            max_depth: 0,
+            depth_limit: max_depth,
+        };
+        builder.add_value(model, value);
+        FlatJson(builder.rows, builder.pretty_printed, builder.max_depth)
+    }
+
+    #[cfg(feature = "serde_json")]
+    pub fn from_serde_json(value: &serde_json::Value) -> FlatJson {
+        FlatJson::from_value(&SerdeJson, value)
+    }
+}
+
+struct Builder {
+    parents: Vec<Index>,
+    rows: Vec<Row>,
+    pretty_printed: String,
+    max_depth: usize,
+    depth_limit: usize,
+}
+
+impl Builder {
+    fn add_value<M: DataModel>(&mut self, model: &M, value: &M::Value) -> Index {
+        self.max_depth = self.max_depth.max(self.parents.len());
+
+        match model.node(value) {
+            Node::Null => self.add_scalar(Value::Null, "null"),
+            Node::Boolean(b) => self.add_scalar(Value::Boolean, if b { "true" } else { "false" }),
+            Node::Number(n) => self.add_scalar(Value::Number, &n),
+            Node::String(s) => {
+                let row_index = self.create_row(Value::String);
+                self.push_quoted_string(&s);
+                self.rows[row_index].range.end = self.pretty_printed.len();
+                row_index
+            }
+            Node::Array(elements) => {
+                let elements = elements.into_iter().map(|element| (None, element));
+                self.add_container(model, ContainerType::Array, elements)
+            }
+            Node::Object(entries) => {
+                let entries = entries.into_iter().map(|(key, value)| (Some(key), value));
+                self.add_container(model, ContainerType::Object, entries)
+            }
+        }
+    }
+
+    fn add_scalar(&mut self, value: Value, text: &str) -> Index {
+        let row_index = self.create_row(value);
+        self.pretty_printed.push_str(text);
+        self.rows[row_index].range.end = self.pretty_printed.len();
+        row_index
+    }
+
+    // Adds the rows for an array or object, whose children have keys if
+    // it's an object.
+    fn add_container<'a, M: DataModel>(
+        &mut self,
+        model: &M,
+        container_type: ContainerType,
+        children: impl ExactSizeIterator<Item = (Option<Cow<'a, str>>, &'a M::Value)>,
+    ) -> Index
+    where
+        M::Value: 'a,
+    {
+        let is_object = container_type == ContainerType::Object;
+
+        if children.len() == 0 {
+            let (value, text) = if is_object {
+                (Value::EmptyObject, "{}")
+            } else {
+                (Value::EmptyArray, "[]")
+            };
+            return self.add_scalar(value, text);
+        }
+        if self.parents.len() >= self.depth_limit {
+            return self.add_scalar(Value::TooDeep, &too_deep_placeholder(self.depth_limit));
+        }
+
+        let open_index = self.create_row(Value::OpenContainer {
+            container_type,
+            collapsed: false,
+            // To be set once the children have been added.
+            first_child: 0,
+            close_index: 0,
+        });
+
+        self.parents.push(open_index);
+        self.pretty_printed.push_str(container_type.open_str());
+
+        let mut prev_sibling = OptionIndex::Nil;
+
+        for (i, (key, child)) in children.enumerate() {
+            if i != 0 {
+                self.pretty_printed.push_str(", ");
+            } else if is_object {
+                // Add space inside objects.
+                self.pretty_printed.push(' ');
+            }
+
+            let key_range = key.map(|key| {
+                let key_range_start = self.pretty_printed.len();
+                self.push_quoted_string(&key);
+                let key_range = key_range_start..self.pretty_printed.len();
+                self.pretty_printed.push_str(": ");
+                key_range
+            });
+
+            let child_index = self.add_value(model, child);
+
+            if i == 0 {
+                if let Value::OpenContainer {
+                    ref mut first_child,
+                    ..
+                } = self.rows[open_index].value
+                {
+                    *first_child = child_index;
+                }
+            }
+
+            let row = &mut self.rows[child_index];
+            row.key_range = key_range;
+            row.prev_sibling = prev_sibling;
+            row.index = i;
+            if let OptionIndex::Index(prev) = prev_sibling {
+                self.rows[prev].next_sibling = OptionIndex::Index(child_index);
+            }
+
+            prev_sibling = OptionIndex::Index(child_index);
+        }
+
+        self.parents.pop();
+
+        if is_object {
+            // Print space inside closing brace.
+            self.pretty_printed.push(' ');
+        }
+
+        let close_index = self.create_row(Value::CloseContainer {
+            container_type,
+            collapsed: false,
+            last_child: prev_sibling.unwrap(),
+            open_index,
+        });
+        self.pretty_printed.push_str(container_type.close_str());
+
+        let end = self.pretty_printed.len();
+        self.rows[close_index].range.end = end;
+        self.rows[open_index].range.end = end;
+        if let Value::OpenContainer {
+            close_index: ref mut open_close_index,
+            ..
+        } = self.rows[open_index].value
+        {
+            *open_close_index = close_index;
+        }
+
+        open_index
+    }
+
+    // Writes a string surrounded by quotes, escaping characters as
+    // necessary so that it is a valid JSON string.
+    fn push_quoted_string(&mut self, s: &str) {
+        self.pretty_printed.push('"');
+        for ch in s.chars() {
+            match ch {
+                '"' => self.pretty_printed.push_str("\\\""),
+                '\\' => self.pretty_printed.push_str("\\\\"),
+                '\n' => self.pretty_printed.push_str("\\n"),
+                '\r' => self.pretty_printed.push_str("\\r"),
+                '\t' => self.pretty_printed.push_str("\\t"),
+                ch if ch.is_control() => {
+                    self.pretty_printed
+                        .push_str(&format!("\\u{:04x}", ch as u32));
+                }
+                ch => self.pretty_printed.push(ch),
+            }
+        }
+        self.pretty_printed.push('"');
+    }
+
+    // Adds a new row, starting at the end of self.pretty_printed, which is
+    // linked to its siblings by the caller. Its range is updated once the
+    // rest of it has been added to self.pretty_printed.
+    fn create_row(&mut self, value: Value) -> Index {
+        let index = self.rows.len();
+
+        let parent = match self.parents.last() {
+            None => OptionIndex::Nil,
+            Some(row_index) => OptionIndex::Index(*row_index),
+        };
+
+        let range_start = self.pretty_printed.len();
+
+        self.rows.push(Row {
+            parent,
+            prev_sibling: OptionIndex::Nil,
+            next_sibling: OptionIndex::Nil,
+            depth: self.parents.len(),
+            index: 0,
+            range: range_start..range_start + 1,
+            key_range: None,
+            source_range: None,
+            key_occurrence: 0,
+            anchor: None,
+            comment: None,
+            value,
+        });
+
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatjson::{parse_top_level_json, parse_top_level_json_to_depth};
+
+    // A minimal data model, like a program embedding jless might have.
+    enum Data {
+        Null,
+        Flag(bool),
+        Int(i64),
+        Text(String),
+        List(Vec<Data>),
+        Map(Vec<(String, Data)>),
+    }
+
+    struct DataDataModel;
+
+    impl DataModel for DataDataModel {
+        type Value = Data;
+
+        fn node<'a>(&self, value: &'a Data) -> Node<'a, Data> {
+            match value {
+                Data::Null => Node::Null,
+                Data::Flag(b) => Node::Boolean(*b),
+                Data::Int(i) => Node::Number(Cow::Owned(i.to_string())),
+                Data::Text(s) => Node::String(Cow::Borrowed(s)),
+                Data::List(elements) => Node::Array(elements.iter().collect()),
+                Data::Map(entries) => Node::Object(
+                    entries
+                        .iter()
+                        .map(|(key, value)| (Cow::Borrowed(key.as_str()), value))
+                        .collect(),
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_value() {
+        let data = Data::Map(vec![
+            ("a".to_owned(), Data::Int(1)),
+            (
+                "b".to_owned(),
+                Data::List(vec![Data::Flag(true), Data::Null, Data::List(vec![])]),
+            ),
+            ("c\"".to_owned(), Data::Text("x\ny".to_owned())),
+            ("d".to_owned(), Data::Map(vec![])),
+        ]);
+        let json = r#"{"a": 1, "b": [true, null, []], "c\"": "x\ny", "d": {}}"#;
+
+        let fj = FlatJson::from_value(&DataDataModel, &data);
+        let parsed = parse_top_level_json(json.to_owned()).unwrap();
+
+        assert_eq!(fj.1, parsed.1);
+        assert_eq!(fj.2, parsed.2);
+        assert_eq!(fj.0.len(), parsed.0.len());
+        for (row, parsed_row) in fj.0.iter().zip(parsed.0.iter()) {
+            assert_eq!(row.parent, parsed_row.parent);
+            assert_eq!(row.prev_sibling, parsed_row.prev_sibling);
+            assert_eq!(row.next_sibling, parsed_row.next_sibling);
+            assert_eq!(row.depth, parsed_row.depth);
+            assert_eq!(row.index, parsed_row.index);
+            assert_eq!(row.range, parsed_row.range);
+            assert_eq!(row.key_range, parsed_row.key_range);
+            assert_eq!(
+                format!("{:?}", row.value),
+                format!("{:?}", parsed_row.value)
+            );
+        }
+
+        let fj = FlatJson::from_value(&DataDataModel, &Data::Text("only".to_owned()));
+        assert_eq!(fj.1, r#""only""#);
+        assert_eq!(fj.0.len(), 1);
+
+        // Containers nested too deeply are replaced the same way as when
+        // parsing JSON.
+        let fj = FlatJson::from_value_to_depth(&DataDataModel, &data, 1);
+        let parsed = parse_top_level_json_to_depth(json.to_owned(), 1).unwrap();
+        assert_eq!(fj.1, parsed.1);
+        assert_eq!(fj.2, 1);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_from_serde_json() {
+        let json = r#"{"a": 1.5, "b": [true, null, []], "c\"": "x\ny", "d": {}}"#;
+        let value: serde_json::Value = serde_json::from_str(json).unwrap();
+
+        let fj = FlatJson::from_serde_json(&value);
+        let parsed = parse_top_level_json(json.to_owned()).unwrap();
+
+        assert_eq!(fj.1, parsed.1);
+        assert_eq!(fj.2, parsed.2);
+        assert_eq!(fj.0.len(), parsed.0.len());
+        for (row, parsed_row) in fj.0.iter().zip(parsed.0.iter()) {
+            assert_eq!(row.range, parsed_row.range);
+            assert_eq!(row.key_range, parsed_row.key_range);
+        }
+    }
+}
@@ -0,0 +1,250 @@
+// Decoders for values that are commonly encoded in JSON, such as base64
+// strings or Unix timestamps, which can be applied to the focused value to
+// show its decoded form in place of the original (see
+// ScreenWriter::cycle_focused_value_decoder). Decoding doesn't change the
+// input itself, so searching and copying still use the original value.
+//
+// To add a decoder, add it to DECODERS; 'zd' tries them in order.
+
+use crate::flatjson::{FlatJson, Row, Value};
+use crate::jsonparser::unescape_string;
+
+/// A scalar value passed to a decoder: the contents of a string (with
+/// escape sequences replaced), or the text of a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scalar<'a> {
+    String(&'a str),
+    Number(&'a str),
+}
+
+pub struct Decoder {
+    pub name: &'static str,
+    // Returns the decoded form of a value, or None if the value can't be
+    // decoded by this decoder.
+    pub decode: fn(Scalar) -> Option<String>,
+}
+
+pub const DECODERS: &[Decoder] = &[
+    Decoder {
+        name: "base64",
+        decode: decode_base64,
+    },
+    Decoder {
+        name: "percent-encoding",
+        decode: decode_percent_encoding,
+    },
+    Decoder {
+        name: "Unix time",
+        decode: decode_unix_time,
+    },
+];
+
+/// Returns the decoded form of a row's value, if it's a string or number
+/// that the decoder can decode.
+pub fn decode_value(decoder: &Decoder, flatjson: &FlatJson, row: &Row) -> Option<String> {
+    let text = &flatjson.1[row.range.clone()];
+    match row.value {
+        Value::String => (decoder.decode)(Scalar::String(&unescape_string(text)?)),
+        Value::Number => (decoder.decode)(Scalar::Number(text)),
+        _ => None,
+    }
+}
+
+// Decodes text encoded using either the standard or the URL-safe base64
+// alphabet, with or without padding. The decoded bytes must be UTF-8 text,
+// rather than arbitrary binary data.
+fn decode_base64(value: Scalar) -> Option<String> {
+    let encoded = match value {
+        Scalar::String(s) => s.trim_end_matches('='),
+        Scalar::Number(_) => return None,
+    };
+    if encoded.is_empty() || encoded.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut bits: u32 = 0;
+    let mut num_bits = 0;
+    for ch in encoded.bytes() {
+        let sextet = match ch {
+            b'A'..=b'Z' => ch - b'A',
+            b'a'..=b'z' => ch - b'a' + 26,
+            b'0'..=b'9' => ch - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        bits = (bits << 6) | sextet as u32;
+        num_bits += 6;
+        if num_bits >= 8 {
+            num_bits -= 8;
+            bytes.push((bits >> num_bits) as u8);
+            bits &= (1 << num_bits) - 1;
+        }
+    }
+
+    decoded_text(bytes)
+}
+
+// Decodes %XX escape sequences, as used in URLs. The value must contain
+// at least one of them.
+fn decode_percent_encoding(value: Scalar) -> Option<String> {
+    let encoded = match value {
+        Scalar::String(s) if s.contains('%') => s.as_bytes(),
+        _ => return None,
+    };
+
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        if encoded[i] == b'%' {
+            let hex = std::str::from_utf8(encoded.get(i + 1..i + 3)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            bytes.push(encoded[i]);
+            i += 1;
+        }
+    }
+
+    decoded_text(bytes)
+}
+
+// Only text is shown in place of a value; control characters other than
+// whitespace suggest binary data that just happened to decode.
+fn decoded_text(bytes: Vec<u8>) -> Option<String> {
+    let text = String::from_utf8(bytes).ok()?;
+    if text
+        .chars()
+        .any(|ch| ch.is_control() && !matches!(ch, '\n' | '\r' | '\t'))
+    {
+        return None;
+    }
+    Some(text)
+}
+
+// Numbers of seconds at least this large are assumed to be milliseconds
+// instead; as seconds, they'd be after the year 5000.
+const MIN_UNIX_TIME_MILLIS: i64 = 100_000_000_000;
+
+// Formats an integer number of seconds or milliseconds since the Unix
+// epoch as an RFC 3339 date and time in UTC, e.g., 2021-02-03T04:05:06Z.
+pub fn decode_unix_time(value: Scalar) -> Option<String> {
+    let n: i64 = match value {
+        Scalar::Number(s) => s.parse().ok()?,
+        Scalar::String(_) => return None,
+    };
+
+    let (secs, millis) = if n.abs() >= MIN_UNIX_TIME_MILLIS {
+        (n.div_euclid(1000), Some(n.rem_euclid(1000)))
+    } else {
+        (n, None)
+    };
+
+    let days = secs.div_euclid(86_400);
+    let secs_of_day = secs.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    if !(0..=9999).contains(&year) {
+        return None;
+    }
+
+    let mut formatted = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    );
+    if let Some(millis) = millis {
+        formatted.push_str(&format!(".{:03}", millis));
+    }
+    formatted.push('Z');
+    Some(formatted)
+}
+
+// Converts a number of days since 1970-01-01 to a (year, month, day) in
+// the proleptic Gregorian calendar, using Howard Hinnant's algorithm.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_base64() {
+        let decode = |s| decode_base64(Scalar::String(s));
+        assert_eq!(Some("hello world".to_owned()), decode("aGVsbG8gd29ybGQ="));
+        assert_eq!(Some("hello world".to_owned()), decode("aGVsbG8gd29ybGQ"));
+        // URL-safe alphabet.
+        assert_eq!(Some("??>".to_owned()), decode("Pz8-"));
+        assert_eq!(Some("??>".to_owned()), decode("Pz8+"));
+
+        assert_eq!(None, decode(""));
+        assert_eq!(None, decode("abcde"));
+        assert_eq!(None, decode("not base64!"));
+        // Binary data isn't shown.
+        assert_eq!(None, decode("AAEC"));
+        assert_eq!(None, decode_base64(Scalar::Number("1234")));
+    }
+
+    #[test]
+    fn test_decode_percent_encoding() {
+        let decode = |s| decode_percent_encoding(Scalar::String(s));
+        assert_eq!(Some("a b/ü?".to_owned()), decode("a%20b%2F%C3%BC%3f"));
+
+        assert_eq!(None, decode("no escapes"));
+        assert_eq!(None, decode("100%"));
+        assert_eq!(None, decode("%zz"));
+        // Not UTF-8.
+        assert_eq!(None, decode("%ff"));
+    }
+
+    #[test]
+    fn test_decode_unix_time() {
+        let decode = |s| decode_unix_time(Scalar::Number(s));
+        assert_eq!(Some("1970-01-01T00:00:00Z".to_owned()), decode("0"));
+        assert_eq!(
+            Some("2021-02-03T04:05:06Z".to_owned()),
+            decode("1612325106")
+        );
+        assert_eq!(
+            Some("2021-02-03T04:05:06.789Z".to_owned()),
+            decode("1612325106789")
+        );
+        assert_eq!(Some("2000-02-29T23:59:59Z".to_owned()), decode("951868799"));
+        assert_eq!(Some("1969-12-31T23:59:59Z".to_owned()), decode("-1"));
+
+        assert_eq!(None, decode("1.5"));
+        assert_eq!(None, decode("99999999999999999"));
+        assert_eq!(None, decode_unix_time(Scalar::String("0")));
+    }
+
+    #[test]
+    fn test_decode_value() {
+        let fj = crate::flatjson::parse_top_level_json(r#"["aGk=", 0, true]"#.to_owned()).unwrap();
+        assert_eq!(
+            Some("hi".to_owned()),
+            decode_value(&DECODERS[0], &fj, &fj[1])
+        );
+        assert_eq!(None, decode_value(&DECODERS[0], &fj, &fj[2]));
+        assert_eq!(
+            Some("1970-01-01T00:00:00Z".to_owned()),
+            decode_value(&DECODERS[2], &fj, &fj[2])
+        );
+        assert_eq!(None, decode_value(&DECODERS[2], &fj, &fj[3]));
+    }
+}
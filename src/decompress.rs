@@ -0,0 +1,299 @@
+// Compressed inputs, like log dumps saved as .json.gz or .json.zst, are
+// decompressed as they're read by running gzip or zstd, rather than by
+// linking a decoder into jless, like URLs are fetched with curl. Each
+// format has a feature ("gzip" and "zstd"); builds without them read such
+// inputs as is.
+//
+// Compression is detected from the first bytes of the input, so it works
+// for STDIN too. The extension (.gz or .zst) is ignored when picking the
+// format of the decompressed data from the filename.
+
+use std::io;
+use std::io::{Cursor, Read};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::thread::JoinHandle;
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Compression {
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+const COMPRESSIONS: &[Compression] = &[
+    #[cfg(feature = "gzip")]
+    Compression::Gzip,
+    #[cfg(feature = "zstd")]
+    Compression::Zstd,
+];
+
+// The longest magic number.
+const MAGIC_LEN: usize = 4;
+
+impl Compression {
+    fn magic(self) -> &'static [u8] {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => b"\x1f\x8b",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => b"\x28\xb5\x2f\xfd",
+        }
+    }
+
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => &[".gz"],
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => &[".zst", ".zstd"],
+        }
+    }
+
+    fn command(self) -> &'static str {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => "gzip",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    /// The name of a compressed file without its compression extension,
+    /// e.g., "data.json" for "data.json.gz", for picking the format of the
+    /// decompressed data.
+    pub fn strip_extension(self, filename: &str) -> &str {
+        let lowercase = filename.to_ascii_lowercase();
+        self.extensions()
+            .iter()
+            .find(|extension| lowercase.ends_with(*extension))
+            .map_or(filename, |extension| {
+                &filename[..filename.len() - extension.len()]
+            })
+    }
+}
+
+/// The compression of an input that starts with the given bytes, if any.
+pub fn detect(start: &[u8]) -> Option<Compression> {
+    COMPRESSIONS
+        .iter()
+        .copied()
+        .find(|compression| start.starts_with(compression.magic()))
+}
+
+/// Decompressed data, which is streamed from gzip or zstd as it's read.
+/// An error is returned at the end if the input couldn't be decompressed,
+/// e.g., because it was truncated, so that it isn't silently cut short.
+pub struct Decompressed {
+    stdout: ChildStdout,
+    child: Child,
+    // Copies the compressed input to the decompressor, returning any error
+    // reading it.
+    writer: Option<JoinHandle<io::Result<()>>>,
+}
+
+/// Decompresses the input if it's compressed. Returns the input as is
+/// otherwise, along with how it was compressed.
+pub fn decompress(
+    mut reader: Box<dyn Read + Send>,
+) -> io::Result<(Box<dyn Read + Send>, Option<Compression>)> {
+    let mut start = vec![];
+    (&mut reader)
+        .take(MAGIC_LEN as u64)
+        .read_to_end(&mut start)?;
+    let compression = detect(&start);
+    let reader = Box::new(Cursor::new(start).chain(reader));
+
+    match compression {
+        Some(compression) => Ok((Box::new(spawn(compression, reader)?), Some(compression))),
+        None => Ok((reader, None)),
+    }
+}
+
+fn spawn(compression: Compression, mut reader: Box<dyn Read + Send>) -> io::Result<Decompressed> {
+    let command = compression.command();
+    let mut child = Command::new(command)
+        .args(["--decompress", "--stdout"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| spawn_error(command, err))?;
+
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || match io::copy(&mut reader, &mut stdin) {
+        // The decompressor stopped reading, which it reports itself.
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.map(|_| ()),
+    });
+
+    Ok(Decompressed {
+        stdout: child.stdout.take().unwrap(),
+        child,
+        writer: Some(writer),
+    })
+}
+
+impl Read for Decompressed {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.stdout.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            // Errors reading the input come first, since they're what
+            // would make the decompressor fail.
+            if let Some(writer) = self.writer.take() {
+                writer.join().unwrap()?;
+            }
+            if !self.child.wait()?.success() {
+                return Err(decompress_error(&mut self.child));
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for Decompressed {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn spawn_error(command: &str, err: io::Error) -> io::Error {
+    let message = if err.kind() == io::ErrorKind::NotFound {
+        format!(
+            "Unable to decompress input: {} needs to be installed to view {} compressed inputs",
+            command, command,
+        )
+    } else {
+        format!("Unable to run {}: {}", command, err)
+    };
+    io::Error::new(err.kind(), message)
+}
+
+// The error the decompressor printed, e.g., "gzip: stdin: unexpected end
+// of file".
+fn decompress_error(child: &mut Child) -> io::Error {
+    let status = child.wait();
+    let mut message = String::new();
+    if let Some(stderr) = &mut child.stderr {
+        let _ = stderr.read_to_string(&mut message);
+    }
+
+    let message = message.trim();
+    let message = if !message.is_empty() {
+        message.to_owned()
+    } else {
+        match status {
+            Ok(status) => format!("Unable to decompress input ({})", status),
+            Err(err) => format!("Unable to decompress input: {}", err),
+        }
+    };
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // {"a": [1, 2]}, compressed with gzip and zstd.
+    #[cfg(feature = "gzip")]
+    const GZIP: &[u8] = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\xabVJT\xb2R\x886\xd4Q0\x8a\xad\x05\x00\xd5iar\r\x00\x00\x00";
+    #[cfg(feature = "zstd")]
+    const ZSTD: &[u8] = b"\x28\xb5\x2f\xfd\x04\x58\x69\x00\x00{\"a\": [1, 2]}\x88\xdf\x3a\x94";
+
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    fn is_installed(compression: Compression) -> bool {
+        Command::new(compression.command())
+            .arg("--version")
+            .output()
+            .is_ok()
+    }
+
+    fn read(data: &'static [u8]) -> (io::Result<String>, Option<Compression>) {
+        let (mut reader, compression) = decompress(Box::new(data)).unwrap();
+        let mut output = String::new();
+        (
+            reader.read_to_string(&mut output).map(|_| output),
+            compression,
+        )
+    }
+
+    #[test]
+    fn test_decompress() {
+        let (output, compression) = read(b"{\"a\": [1, 2]}");
+        assert_eq!("{\"a\": [1, 2]}", output.unwrap());
+        assert_eq!(None, compression);
+
+        let (output, _) = read(b"1");
+        assert_eq!("1", output.unwrap());
+
+        #[cfg(feature = "gzip")]
+        if is_installed(Compression::Gzip) {
+            let (output, compression) = read(GZIP);
+            assert_eq!("{\"a\": [1, 2]}", output.unwrap());
+            assert_eq!(Some(Compression::Gzip), compression);
+
+            // Truncated archives are errors, not partial inputs.
+            let (output, _) = read(&GZIP[..20]);
+            assert!(output
+                .unwrap_err()
+                .to_string()
+                .contains("unexpected end of file"));
+        }
+
+        #[cfg(feature = "zstd")]
+        if is_installed(Compression::Zstd) {
+            let (output, compression) = read(ZSTD);
+            assert_eq!("{\"a\": [1, 2]}", output.unwrap());
+            assert_eq!(Some(Compression::Zstd), compression);
+            assert!(read(&ZSTD[..12]).0.is_err());
+        }
+    }
+
+    #[test]
+    fn test_spawn_error() {
+        let not_found = io::Error::from(io::ErrorKind::NotFound);
+        assert_eq!(
+            "Unable to decompress input: gzip needs to be installed to view gzip compressed inputs",
+            spawn_error("gzip", not_found).to_string(),
+        );
+
+        let denied = io::Error::new(io::ErrorKind::PermissionDenied, "Permission denied");
+        assert_eq!(
+            "Unable to run zstd: Permission denied",
+            spawn_error("zstd", denied).to_string(),
+        );
+    }
+
+    #[test]
+    fn test_strip_extension() {
+        #[cfg(feature = "gzip")]
+        {
+            assert_eq!(
+                "data.json",
+                Compression::Gzip.strip_extension("data.json.gz")
+            );
+            assert_eq!(
+                "data.json",
+                Compression::Gzip.strip_extension("data.json.GZ")
+            );
+            assert_eq!("data", Compression::Gzip.strip_extension("data"));
+        }
+
+        #[cfg(feature = "zstd")]
+        {
+            assert_eq!(
+                "logs.jsonl",
+                Compression::Zstd.strip_extension("logs.jsonl.zst")
+            );
+            assert_eq!(
+                "logs.jsonl",
+                Compression::Zstd.strip_extension("logs.jsonl.zstd")
+            );
+            assert_eq!(
+                "logs.jsonl.gz",
+                Compression::Zstd.strip_extension("logs.jsonl.gz")
+            );
+        }
+    }
+}
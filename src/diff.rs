@@ -0,0 +1,495 @@
+// With --diff, two inputs are shown side by side, and the differences
+// between them are marked. Rows are matched up by their paths (see
+// viewer::path_hashes), rather than by their positions, so a value that
+// moved to a different index of an array shows up as a change, but
+// reordering the keys of an object doesn't.
+
+use std::collections::HashMap;
+use std::mem::discriminant;
+
+use crate::flatjson::{FlatJson, Index, OptionIndex, NIL};
+use crate::viewer::{path_hashes, JsonViewer, Mode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    // Only in the second input.
+    Added,
+    // Only in the first input.
+    Removed,
+    // A different value in each input, or an object or array that
+    // contains a change.
+    Changed,
+}
+
+impl Change {
+    /// The character shown in the gutter next to changed rows.
+    pub fn marker(self) -> char {
+        match self {
+            Change::Added => '+',
+            Change::Removed => '-',
+            Change::Changed => '!',
+        }
+    }
+}
+
+/// The changes between two inputs, keyed by the path hashes of the rows
+/// that changed. Unchanged rows aren't included.
+pub struct Diff {
+    changes: HashMap<u64, Change>,
+}
+
+impl Diff {
+    pub fn new(old: &FlatJson, new: &FlatJson) -> Diff {
+        let old_hashes = path_hashes(old);
+        let new_hashes = path_hashes(new);
+        let old_rows = rows_by_hash(old, &old_hashes);
+        let new_rows = rows_by_hash(new, &new_hashes);
+
+        let mut changes = HashMap::new();
+        for (&hash, &index) in old_rows.iter() {
+            let change = match new_rows.get(&hash) {
+                None => Change::Removed,
+                Some(&new_index) if !same_value(old, index, new, new_index) => Change::Changed,
+                Some(_) => continue,
+            };
+            changes.insert(hash, change);
+        }
+        for &hash in new_rows.keys() {
+            if !old_rows.contains_key(&hash) {
+                changes.insert(hash, Change::Added);
+            }
+        }
+
+        // Mark the objects and arrays that contain changes, so that changes
+        // inside of collapsed containers can be seen.
+        let mut diff = Diff { changes };
+        diff.mark_ancestors(old, &old_hashes);
+        diff.mark_ancestors(new, &new_hashes);
+        diff
+    }
+
+    pub fn change(&self, path_hash: u64) -> Option<Change> {
+        self.changes.get(&path_hash).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    fn mark_ancestors(&mut self, flatjson: &FlatJson, hashes: &[u64]) {
+        // Children come after their parents, so each container's change is
+        // known before its own parent is marked.
+        for (index, row) in flatjson.0.iter().enumerate().rev() {
+            if let (OptionIndex::Index(parent), true) =
+                (row.parent, self.changes.contains_key(&hashes[index]))
+            {
+                self.changes
+                    .entry(hashes[parent])
+                    .or_insert(Change::Changed);
+            }
+        }
+    }
+}
+
+/// Keeps the file that isn't focused showing the same rows as the focused
+/// one, and lines up the rows of both files so that rows with the same path
+/// are shown next to each other. Rows are matched up by their paths once,
+/// and again only when either file's rows change, and the other file is
+/// only updated, and the rows lined up again, when what the focused one
+/// shows has changed, since each takes time proportional to the size of the
+/// files.
+#[derive(Default)]
+pub struct MatchedRows {
+    // The IDs of the rows of each file (see JsonViewer::rows_id) when they
+    // were matched up.
+    rows_ids: Option<[usize; 2]>,
+    // For each file, the index of the row of the other file with the same
+    // path as each of its rows, if there is one.
+    matching_rows: [Vec<OptionIndex>; 2],
+    // The focused file, how many times it had collapsed or expanded
+    // anything, its mode, and its focused row and top row, when the other
+    // file was last updated.
+    synced: Option<(usize, usize, Mode, Index, Index)>,
+    // The rows of both files that are shown, in order, lined up so that
+    // rows with the same path are on the same line, and with Nil next to
+    // rows that aren't next to a row of the other file.
+    lines: Vec<[OptionIndex; 2]>,
+    // For each file, the index in lines of each of its rows, or NIL for
+    // rows that aren't shown.
+    line_of_row: [Vec<usize>; 2],
+    // The focused file, how many times it had collapsed or expanded
+    // anything, and its mode, when the rows were lined up.
+    aligned: Option<(usize, usize, Mode)>,
+    // The first of the lines on the screen, and the focused file and its
+    // top row when it was last chosen.
+    top_line: usize,
+    top_row: Option<(usize, Index)>,
+}
+
+impl MatchedRows {
+    /// Makes the other file show the same rows as the focused one, the
+    /// focused_file'th, if they might not already.
+    pub fn sync(&mut self, focused: &JsonViewer, focused_file: usize, other: &mut JsonViewer) {
+        let other_file = 1 - focused_file;
+        let mut rows_ids = [0; 2];
+        rows_ids[focused_file] = focused.rows_id();
+        rows_ids[other_file] = other.rows_id();
+
+        let mut flatjsons = [&focused.flatjson, &other.flatjson];
+        if focused_file == 1 {
+            flatjsons.swap(0, 1);
+        }
+        if self.rows_ids != Some(rows_ids) {
+            self.matching_rows = match_rows(flatjsons[0], flatjsons[1]);
+            self.rows_ids = Some(rows_ids);
+            self.synced = None;
+            self.aligned = None;
+        }
+
+        let state = (
+            focused_file,
+            focused.collapse_changes(),
+            focused.mode,
+            focused.focused_row,
+            focused.top_row,
+        );
+        let collapsed = match self.synced {
+            Some(synced) if synced == state => None,
+            // Only the focus moved.
+            Some((file, collapse_changes, ..)) => {
+                Some((file, collapse_changes) != (state.0, state.1))
+            }
+            None => Some(true),
+        };
+        if let Some(collapsed) = collapsed {
+            other.show_same_rows_as(focused, &self.matching_rows[focused_file], collapsed);
+            self.synced = Some(state);
+        }
+
+        let aligned = (state.0, state.1, state.2);
+        if self.aligned != Some(aligned) {
+            let mut viewers = [focused, &*other];
+            if focused_file == 1 {
+                viewers.swap(0, 1);
+            }
+            let (lines, line_of_row) = align_rows(viewers, &self.matching_rows);
+            self.lines = lines;
+            self.line_of_row = line_of_row;
+            self.aligned = Some(aligned);
+            self.top_row = None;
+        }
+        self.scroll_to_focused_row(focused, focused_file);
+    }
+
+    /// The lines of rows of both files shown from the top of the screen
+    /// down, with the first file's row first in each.
+    pub fn lines(&self) -> &[[OptionIndex; 2]] {
+        &self.lines[self.top_line.min(self.lines.len())..]
+    }
+
+    // Chooses the first line on the screen like the focused file chooses
+    // its top row: it starts at the focused file's top row, and only moves
+    // to keep the focused row at least scrolloff lines from the top and
+    // bottom of the screen, since the blank lines next to rows only in the
+    // other file may push it down.
+    fn scroll_to_focused_row(&mut self, focused: &JsonViewer, focused_file: usize) {
+        let line_of_row = &self.line_of_row[focused_file];
+        let focused_line = line_of_row[focused.focused_row];
+        if focused_line == NIL {
+            return;
+        }
+        let top_line = match line_of_row[focused.top_row] {
+            NIL => focused_line,
+            top_line => top_line,
+        };
+
+        if self.top_row != Some((focused_file, focused.top_row)) {
+            self.top_line = top_line;
+            self.top_row = Some((focused_file, focused.top_row));
+        }
+
+        let height = focused.dimensions.height as usize;
+        let scrolloff = focused.scrolloff() as usize;
+        let last_line = (focused_line + scrolloff).min(self.lines.len() - 1);
+        if last_line >= self.top_line + height {
+            self.top_line = last_line + 1 - height;
+        }
+        if focused_line < self.top_line + scrolloff {
+            self.top_line = focused_line.saturating_sub(scrolloff).max(top_line);
+        }
+    }
+}
+
+// Lines up the rows of two files that are shown, returning the lines and,
+// for each file, the line each row is on. Rows with the same path are put
+// on the same line, unless they're in a different order in each file, in
+// which case as few rows as possible are put next to blank lines instead.
+fn align_rows(
+    viewers: [&JsonViewer; 2],
+    matching_rows: &[Vec<OptionIndex>; 2],
+) -> (Vec<[OptionIndex; 2]>, [Vec<usize>; 2]) {
+    // The rows of a file that are shown, and the position of each row among
+    // them.
+    let shown_rows = |viewer: &JsonViewer| {
+        let mut rows = vec![];
+        let mut positions = vec![None; viewer.flatjson.0.len()];
+        let mut row = OptionIndex::from(if positions.is_empty() { NIL } else { 0 });
+        while let OptionIndex::Index(index) = row {
+            positions[index] = Some(rows.len());
+            rows.push(index);
+            row = match viewer.mode {
+                Mode::Line => viewer.flatjson.next_visible_row(index),
+                Mode::Data => viewer.flatjson.next_item(index),
+            };
+        }
+        (rows, positions)
+    };
+    let (first_rows, first_positions) = shown_rows(viewers[0]);
+    let (second_rows, second_positions) = shown_rows(viewers[1]);
+    let rows = [first_rows, second_rows];
+    let positions = [first_positions, second_positions];
+
+    // How many rows of the other file come before the row with the same
+    // path as a row, starting from the next one, if it's still to come.
+    let ahead = |file: usize, row: Index, next: usize| match matching_rows[file][row] {
+        OptionIndex::Index(other_row) => positions[1 - file][other_row]?.checked_sub(next),
+        OptionIndex::Nil => None,
+    };
+
+    let mut lines = Vec::with_capacity(rows[0].len().max(rows[1].len()));
+    let mut line_of_row = [vec![NIL; positions[0].len()], vec![NIL; positions[1].len()]];
+    let mut next = [0, 0];
+    while next[0] < rows[0].len() || next[1] < rows[1].len() {
+        let first = rows[0].get(next[0]).copied();
+        let second = rows[1].get(next[1]).copied();
+        let first_ahead = first.and_then(|row| ahead(0, row, next[1]));
+        let second_ahead = second.and_then(|row| ahead(1, row, next[0]));
+        let line = match (first_ahead, second_ahead) {
+            (Some(0), _) => [first, second],
+            (None, _) if first.is_some() => [first, None],
+            // Put the rows of whichever file are fewer next to blank lines.
+            (Some(first_ahead), Some(second_ahead)) if first_ahead > second_ahead => [first, None],
+            _ => [None, second],
+        };
+
+        let mut indexes = [OptionIndex::Nil; 2];
+        for file in 0..2 {
+            if let Some(row) = line[file] {
+                line_of_row[file][row] = lines.len();
+                indexes[file] = OptionIndex::Index(row);
+                next[file] += 1;
+            }
+        }
+        lines.push(indexes);
+    }
+
+    (lines, line_of_row)
+}
+
+// Matches up the rows of two inputs by their paths, returning, for each
+// input, the index of the row with the same path in the other one for
+// each of its rows.
+fn match_rows(first: &FlatJson, second: &FlatJson) -> [Vec<OptionIndex>; 2] {
+    let first_hashes = path_hashes(first);
+    let second_hashes = path_hashes(second);
+    let index_of_hash = |hashes: &[u64]| {
+        let mut indexes = HashMap::with_capacity(hashes.len());
+        for (index, &hash) in hashes.iter().enumerate() {
+            indexes.entry(hash).or_insert(index);
+        }
+        indexes
+    };
+    let matching = |hashes: &[u64], other_indexes: &HashMap<u64, Index>| {
+        hashes
+            .iter()
+            .map(|hash| match other_indexes.get(hash) {
+                Some(&index) => OptionIndex::Index(index),
+                None => OptionIndex::Nil,
+            })
+            .collect()
+    };
+
+    [
+        matching(&first_hashes, &index_of_hash(&second_hashes)),
+        matching(&second_hashes, &index_of_hash(&first_hashes)),
+    ]
+}
+
+// Closing rows are left out, since they're identified by their opening
+// rows.
+fn rows_by_hash(flatjson: &FlatJson, hashes: &[u64]) -> HashMap<u64, Index> {
+    let mut rows = HashMap::new();
+    for (index, row) in flatjson.0.iter().enumerate() {
+        if !row.is_closing_of_container() {
+            rows.entry(hashes[index]).or_insert(index);
+        }
+    }
+    rows
+}
+
+// Objects and arrays are the same if they're the same type of container;
+// differences between their elements are found separately.
+fn same_value(old: &FlatJson, old_index: Index, new: &FlatJson, new_index: Index) -> bool {
+    let (old_row, new_row) = (&old[old_index], &new[new_index]);
+    if old_row.is_container() || new_row.is_container() {
+        return old_row.value.container_type() == new_row.value.container_type();
+    }
+
+    discriminant(&old_row.value) == discriminant(&new_row.value)
+        && old.1[old_row.range.clone()] == new.1[new_row.range.clone()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatjson::parse_top_level_json;
+    use crate::viewer::{Action, Mode};
+
+    #[test]
+    fn test_diff() {
+        let old = parse_top_level_json(
+            r#"{"a": 1, "b": {"c": [1, 2], "d": "x"}, "e": [], "f": null}"#.to_owned(),
+        )
+        .unwrap();
+        let new = parse_top_level_json(
+            r#"{"f": null, "b": {"c": [1, 3, 4], "d": "x"}, "e": {}, "g": true}"#.to_owned(),
+        )
+        .unwrap();
+        let diff = Diff::new(&old, &new);
+        let old_hashes = path_hashes(&old);
+        let new_hashes = path_hashes(&new);
+        let old_change = |index: Index| diff.change(old_hashes[index]);
+        let new_change = |index: Index| diff.change(new_hashes[index]);
+
+        // The root object, "b", and "b.c" contain changes.
+        assert_eq!(old_change(0), Some(Change::Changed));
+        assert_eq!(old_change(2), Some(Change::Changed));
+        assert_eq!(old_change(3), Some(Change::Changed));
+        assert_eq!(new_change(2), Some(Change::Changed));
+
+        // "a" was removed, and "g" was added.
+        assert_eq!(old_change(1), Some(Change::Removed));
+        assert_eq!(new_change(11), Some(Change::Added));
+
+        // b.c[0] is the same, b.c[1] changed, and b.c[2] was added.
+        assert_eq!(old_change(4), None);
+        assert_eq!(old_change(5), Some(Change::Changed));
+        assert_eq!(new_change(6), Some(Change::Added));
+
+        // An empty array became an empty object, while "b.d" and "f" are
+        // the same, even though "f" moved.
+        assert_eq!(old_change(9), Some(Change::Changed));
+        assert_eq!(old_change(7), None);
+        assert_eq!(old_change(10), None);
+        assert_eq!(new_change(1), None);
+
+        assert!(Diff::new(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_matched_rows() {
+        let viewer = |json: &str| {
+            let mut viewer =
+                JsonViewer::new(parse_top_level_json(json.to_owned()).unwrap(), Mode::Line);
+            viewer.dimensions.height = 20;
+            viewer
+        };
+        let mut focused =
+            viewer(r#"{"1": 1, "2": [3, "4"], "6": {"7": null, "8": true, "9": 9}, "11": 11}"#);
+        focused.flatjson.collapse(6);
+        focused.focused_row = 11;
+        let mut other = viewer(r#"{"1": 1, "2": [3, "4", 5], "6": {"8": false}, "11": 11}"#);
+        other.flatjson.collapse(2);
+        other.perform_action(Action::SetMark('a'));
+
+        // The same containers are collapsed, and the row with the same path
+        // is focused, but marks aren't copied.
+        let mut matched_rows = MatchedRows::default();
+        matched_rows.sync(&focused, 0, &mut other);
+        assert!(other.flatjson[2].is_expanded());
+        assert!(other.flatjson[7].is_collapsed());
+        assert_eq!(other.focused_row, 10);
+        assert_eq!(other.marks().collect::<Vec<_>>(), vec![('a', 0)]);
+
+        // Moving the focus only moves the other file's focus.
+        other.flatjson.collapse(2);
+        focused.perform_action(Action::MoveUp(1));
+        matched_rows.sync(&focused, 0, &mut other);
+        assert_eq!(other.focused_row, 7);
+        assert!(other.flatjson[2].is_collapsed());
+
+        // Collapsing or expanding anything updates which containers are
+        // collapsed in the other file, too.
+        focused.perform_action(Action::ToggleCollapsed);
+        matched_rows.sync(&focused, 0, &mut other);
+        assert!(other.flatjson[2].is_expanded());
+        assert!(other.flatjson[7].is_expanded());
+
+        // Rows are matched up again once they change, but whether keys are
+        // sorted isn't copied.
+        focused.perform_action(Action::ToggleSortedKeys);
+        focused.perform_action(Action::FocusTop);
+        focused.perform_action(Action::MoveDown(2));
+        matched_rows.sync(&focused, 0, &mut other);
+        assert_eq!(other.focused_row, 10);
+        assert!(!other.sorted_keys());
+
+        // The other way around.
+        matched_rows.sync(&other, 1, &mut focused);
+        assert_eq!(focused.focused_row, 2);
+    }
+
+    #[test]
+    fn test_lined_up_rows() {
+        let viewer = |json: &str| {
+            let mut viewer =
+                JsonViewer::new(parse_top_level_json(json.to_owned()).unwrap(), Mode::Line);
+            viewer.dimensions.height = 5;
+            viewer.scrolloff_setting = 1;
+            viewer
+        };
+        let (i, nil) = (OptionIndex::Index, OptionIndex::Nil);
+
+        // When keys are in a different order, only one of them can be next
+        // to the same key in the other file.
+        let focused = viewer(r#"{"a": 1, "f": 2}"#);
+        let mut other = viewer(r#"{"f": 2, "a": 1}"#);
+        let mut matched_rows = MatchedRows::default();
+        matched_rows.sync(&focused, 0, &mut other);
+        assert_eq!(
+            matched_rows.lines(),
+            &[
+                [i(0), i(0)],
+                [nil, i(1)],
+                [i(1), i(2)],
+                [i(2), nil],
+                [i(3), i(3)]
+            ]
+        );
+
+        // The focused row stays on the screen when the rows next to it are
+        // pushed down by rows only in the other file.
+        let mut focused = viewer(r#"{"a": 1, "z": 1}"#);
+        let mut other = viewer(r#"{"a": 1, "b": 1, "c": 1, "d": 1, "e": 1, "z": 1}"#);
+        matched_rows.sync(&focused, 0, &mut other);
+        assert_eq!(matched_rows.lines()[0], [i(0), i(0)]);
+        focused.perform_action(Action::MoveDown(2));
+        matched_rows.sync(&focused, 0, &mut other);
+        assert_eq!(focused.top_row, 0);
+        assert_eq!(
+            matched_rows.lines(),
+            &[
+                [nil, i(3)],
+                [nil, i(4)],
+                [nil, i(5)],
+                [i(2), i(6)],
+                [i(3), i(7)]
+            ]
+        );
+
+        // Moving back up to "a" scrolls back up to it.
+        focused.perform_action(Action::MoveUp(1));
+        matched_rows.sync(&focused, 0, &mut other);
+        assert_eq!(matched_rows.lines()[0], [i(0), i(0)]);
+    }
+}
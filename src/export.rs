@@ -0,0 +1,555 @@
+// Writes the input as JSON or YAML, for :export, so that a YAML file can
+// be converted to JSON, and vice versa, without leaving jless. The contents
+// of collapsed objects and arrays can be omitted, like when copying what's
+// visible.
+//
+// Values are written from the pretty printed text of the rows, which is
+// JSON, except for the non-string keys that YAML allows, which are shown
+// in brackets, e.g., [1], and control characters in YAML strings, which
+// are only escaped when they're printed.
+
+use yaml_rust::Yaml;
+
+use crate::flatjson;
+use crate::flatjson::{ContainerType, FlatJson, Index, OptionIndex, Row, Value};
+use crate::jsonparser::unescape_string;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Yaml,
+}
+
+impl ExportFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "JSON",
+            ExportFormat::Yaml => "YAML",
+        }
+    }
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ExportFormat, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(ExportFormat::Json),
+            "yaml" | "yml" => Ok(ExportFormat::Yaml),
+            _ => Err(format!("Unknown format: {} (expected json or yaml)", s)),
+        }
+    }
+}
+
+// Strings that YAML 1.1 parsers load as booleans or null, regardless of
+// case, which need to be quoted to be loaded as strings.
+const AMBIGUOUS_STRINGS: [&str; 11] = [
+    "~", "null", "true", "false", "yes", "no", "on", "off", "y", "n", "nan",
+];
+
+/// Writes the input as JSON or YAML. Returns an error, rather than writing
+/// the placeholders, if any containers that would be written were nested
+/// too deeply to be parsed, or any lines that would be written are
+/// malformed.
+pub fn export(
+    flatjson: &FlatJson,
+    format: ExportFormat,
+    include_collapsed: bool,
+) -> Result<String, String> {
+    let mut exporter = Exporter {
+        flatjson,
+        include_collapsed,
+        compact: false,
+        unwritable: None,
+        buf: String::new(),
+    };
+
+    // There's a top-level value for each document in a YAML file, or line
+    // of newline-delimited JSON.
+    let mut top_level = if flatjson.0.is_empty() {
+        OptionIndex::Nil
+    } else {
+        OptionIndex::Index(0)
+    };
+    while let OptionIndex::Index(index) = top_level {
+        match format {
+            ExportFormat::Json => {
+                exporter.json(index, 0);
+                exporter.buf.push('\n');
+            }
+            ExportFormat::Yaml => {
+                if index != 0 {
+                    exporter.buf.push_str("---\n");
+                }
+                if let Some(comment) = &flatjson[index].comment {
+                    if exporter.is_expanded(&flatjson[index]) {
+                        exporter.buf.push_str(comment);
+                        exporter.buf.push('\n');
+                    }
+                }
+                exporter.yaml(index, 0, false);
+            }
+        }
+        top_level = flatjson[index].next_sibling;
+    }
+
+    exporter.finish()
+}
+
+/// Writes a single value, including the contents of collapsed objects and
+/// arrays, as JSON, for --print-value-on-exit: pretty printed, or compact,
+/// on one line without any spaces.
+pub fn json_value(flatjson: &FlatJson, index: Index, compact: bool) -> Result<String, String> {
+    let mut exporter = Exporter {
+        flatjson,
+        include_collapsed: true,
+        compact,
+        unwritable: None,
+        buf: String::new(),
+    };
+    exporter.json(index, 0);
+    exporter.buf.push('\n');
+    exporter.finish()
+}
+
+struct Exporter<'a> {
+    flatjson: &'a FlatJson,
+    include_collapsed: bool,
+    // Whether JSON is written on one line, without any spaces.
+    compact: bool,
+    // Why the first row written that isn't a value from the input (see
+    // flatjson::unwritable_error) can't be written, if there are any.
+    unwritable: Option<String>,
+    buf: String,
+}
+
+impl<'a> Exporter<'a> {
+    fn finish(self) -> Result<String, String> {
+        match self.unwritable {
+            Some(err) => Err(err),
+            None => Ok(self.buf),
+        }
+    }
+
+    // Whether the contents of an object or array are written.
+    fn is_expanded(&self, row: &Row) -> bool {
+        row.is_opening_of_container() && (self.include_collapsed || !row.is_collapsed())
+    }
+
+    fn children(&self, index: Index) -> impl Iterator<Item = Index> + 'a {
+        let flatjson = self.flatjson;
+        let mut child = match flatjson[index].value {
+            Value::OpenContainer { first_child, .. } => OptionIndex::Index(first_child),
+            _ => OptionIndex::Nil,
+        };
+        std::iter::from_fn(move || match child {
+            OptionIndex::Index(index) => {
+                child = flatjson[index].next_sibling;
+                Some(index)
+            }
+            OptionIndex::Nil => None,
+        })
+    }
+
+    fn indent(&mut self, indentation: usize) {
+        for _ in 0..indentation {
+            self.buf.push(' ');
+        }
+    }
+
+    fn json(&mut self, index: Index, depth: usize) {
+        let row = &self.flatjson[index];
+        if !self.is_expanded(row) {
+            if self.unwritable.is_none() {
+                self.unwritable = flatjson::unwritable_error(row);
+            }
+            let scalar = self.json_scalar(row);
+            self.buf.push_str(&scalar);
+            return;
+        }
+
+        let container_type = row.value.container_type().unwrap();
+        self.buf.push_str(container_type.open_str());
+        for (i, child) in self.children(index).enumerate() {
+            if !self.compact {
+                self.buf.push_str(if i == 0 { "\n" } else { ",\n" });
+                self.indent((depth + 1) * 2);
+            } else if i != 0 {
+                self.buf.push(',');
+            }
+            if let Some(key) = self.json_key(&self.flatjson[child]) {
+                self.buf.push_str(&key);
+                self.buf.push_str(if self.compact { ":" } else { ": " });
+            }
+            self.json(child, depth + 1);
+        }
+        if !self.compact {
+            self.buf.push('\n');
+            self.indent(depth * 2);
+        }
+        self.buf.push_str(container_type.close_str());
+    }
+
+    fn json_scalar(&self, row: &Row) -> String {
+        let text = &self.flatjson.1[row.range.clone()];
+        match &row.value {
+            Value::String if text.contains(char::is_control) => match unescape_string(text) {
+                Some(s) => quoted(&s),
+                None => text.to_owned(),
+            },
+            Value::Number => json_number(text),
+            Value::OpenContainer { container_type, .. } => collapsed(*container_type),
+            _ => text.to_owned(),
+        }
+    }
+
+    fn json_key(&self, row: &Row) -> Option<String> {
+        let key = &self.flatjson.1[row.key_range.clone()?];
+        Some(match non_string_key(key) {
+            Some(key) => quoted(key),
+            None if key.contains(char::is_control) => quoted(&unescape_string(key)?),
+            None => key.to_owned(),
+        })
+    }
+
+    // Writes a value starting at the current position, with its contents
+    // indented by the given amount, except that the first line of them
+    // continues the current line if inline is set, e.g., after "- ".
+    fn yaml(&mut self, index: Index, indentation: usize, inline: bool) {
+        let row = &self.flatjson[index];
+        if !self.is_expanded(row) {
+            if self.unwritable.is_none() {
+                self.unwritable = flatjson::unwritable_error(row);
+            }
+            let scalar = self.yaml_scalar(row);
+            self.buf.push_str(&scalar);
+            self.yaml_comment(row);
+            self.buf.push('\n');
+            return;
+        }
+
+        let is_object = row.value.container_type() == Some(ContainerType::Object);
+        for (i, child) in self.children(index).enumerate() {
+            let child_row = &self.flatjson[child];
+            let child_is_expanded = self.is_expanded(child_row);
+            if i != 0 || !inline {
+                self.indent(indentation);
+            }
+            // Comments on objects and arrays in arrays are written on the
+            // line above them.
+            if let (false, true, Some(comment)) = (is_object, child_is_expanded, &child_row.comment)
+            {
+                self.buf.push_str(comment);
+                self.buf.push('\n');
+                self.indent(indentation);
+            }
+
+            if is_object {
+                let key = self.yaml_key(child_row);
+                self.buf.push_str(&key);
+                self.buf.push(':');
+                if child_is_expanded {
+                    self.yaml_comment(child_row);
+                    self.buf.push('\n');
+                    self.yaml(child, indentation + 2, false);
+                } else {
+                    self.buf.push(' ');
+                    self.yaml(child, indentation + 2, false);
+                }
+            } else {
+                self.buf.push_str("- ");
+                self.yaml(child, indentation + 2, child_is_expanded);
+            }
+        }
+    }
+
+    fn yaml_scalar(&self, row: &Row) -> String {
+        let text = &self.flatjson.1[row.range.clone()];
+        match &row.value {
+            Value::String => match unescape_string(text) {
+                Some(s) => yaml_string(&s),
+                None => text.to_owned(),
+            },
+            Value::OpenContainer { container_type, .. } => collapsed(*container_type),
+            _ => text.to_owned(),
+        }
+    }
+
+    fn yaml_key(&self, row: &Row) -> String {
+        let key = match &row.key_range {
+            Some(key_range) => &self.flatjson.1[key_range.clone()],
+            None => return String::new(),
+        };
+        match non_string_key(key) {
+            Some(key) => key.to_owned(),
+            None => match unescape_string(key) {
+                Some(key) => yaml_string(&key),
+                None => key.to_owned(),
+            },
+        }
+    }
+
+    fn yaml_comment(&mut self, row: &Row) {
+        if let Some(comment) = &row.comment {
+            self.buf.push_str("  ");
+            self.buf.push_str(comment);
+        }
+    }
+}
+
+// The text of a key in YAML input that isn't a string, which is shown in
+// brackets, e.g., [1] or [[1, 2]].
+fn non_string_key(key: &str) -> Option<&str> {
+    key.strip_prefix('[')?.strip_suffix(']')
+}
+
+fn collapsed(container_type: ContainerType) -> String {
+    format!(
+        "{}{}",
+        container_type.open_str(),
+        container_type.close_str()
+    )
+}
+
+// A string as a JSON string literal, which is also a valid YAML double
+// quoted string.
+fn quoted(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            ch if ch.is_control() => quoted.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// Strings are written without quotes when they can't be mistaken for
+// anything else, e.g., a number, or a key followed by a value.
+fn yaml_string(s: &str) -> String {
+    let plain = s.starts_with(|ch: char| ch.is_alphabetic() || ch == '_' || ch == '/')
+        && !s.ends_with(char::is_whitespace)
+        && !s.contains(|ch: char| ch.is_control() || ":#,[]{}&*!|>'\"%@`".contains(ch))
+        && !AMBIGUOUS_STRINGS.contains(&s.to_ascii_lowercase().as_str())
+        && matches!(Yaml::from_str(s), Yaml::String(_));
+
+    if plain {
+        s.to_owned()
+    } else {
+        quoted(s)
+    }
+}
+
+// Numbers in YAML input that aren't valid JSON numbers, e.g., "+1.", are
+// normalized, and infinity and NaN are written as strings, since JSON
+// doesn't have them.
+fn json_number(number: &str) -> String {
+    if is_json_number(number) {
+        return number.to_owned();
+    }
+
+    match number.parse::<f64>() {
+        Ok(n) if n.is_finite() => format!("{:?}", n),
+        _ => quoted(number),
+    }
+}
+
+fn is_json_number(number: &str) -> bool {
+    fn digits(s: &str) -> (&str, &str) {
+        let end = s.find(|ch: char| !ch.is_ascii_digit()).unwrap_or(s.len());
+        s.split_at(end)
+    }
+
+    let (integer, rest) = digits(number.strip_prefix('-').unwrap_or(number));
+    if integer.is_empty() || (integer.len() > 1 && integer.starts_with('0')) {
+        return false;
+    }
+
+    let rest = match rest.strip_prefix('.') {
+        Some(rest) => match digits(rest) {
+            ("", _) => return false,
+            (_, rest) => rest,
+        },
+        None => rest,
+    };
+
+    match rest.strip_prefix(&['e', 'E'][..]) {
+        Some(exponent) => {
+            let exponent = exponent.strip_prefix(&['+', '-'][..]).unwrap_or(exponent);
+            matches!(digits(exponent), (digits, "") if !digits.is_empty())
+        }
+        None => rest.is_empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::flatjson::{
+        parse_top_level_json, parse_top_level_json_to_depth, parse_top_level_yaml,
+        DEFAULT_MAX_DEPTH,
+    };
+
+    #[test]
+    fn test_yaml_to_json() {
+        let yaml = indoc! {r#"
+            name: jless
+            quote: 'say "hi"\path'
+            tab: "a\tb"
+            numbers: [1, +1.5, .inf]
+            1: one
+            empty: {}
+        "#};
+        let fj = parse_top_level_yaml(yaml.to_owned(), false, DEFAULT_MAX_DEPTH).unwrap();
+        let json = indoc! {r#"
+            {
+              "name": "jless",
+              "quote": "say \"hi\"\\path",
+              "tab": "a\tb",
+              "numbers": [
+                1,
+                1.5,
+                ".inf"
+              ],
+              "1": "one",
+              "empty": {}
+            }
+        "#};
+        assert_eq!(json, export(&fj, ExportFormat::Json, true).unwrap());
+    }
+
+    #[test]
+    fn test_json_to_yaml() {
+        let json = r#"{
+            "name": "jless",
+            "version": "1.0",
+            "flag": "yes",
+            "url": "https://jless.io",
+            "multiline": "a\nb",
+            "tags": ["viewer", {"a": 1, "b": [true, null]}, [1, 2], []],
+            "nested": {"key with spaces": " padded "}
+        }"#;
+        let fj = parse_top_level_json(json.to_owned()).unwrap();
+        let yaml = indoc! {r#"
+            name: jless
+            version: "1.0"
+            flag: "yes"
+            url: "https://jless.io"
+            multiline: "a\nb"
+            tags:
+              - viewer
+              - a: 1
+                b:
+                  - true
+                  - null
+              - - 1
+                - 2
+              - []
+            nested:
+              key with spaces: " padded "
+        "#};
+        assert_eq!(yaml, export(&fj, ExportFormat::Yaml, true).unwrap());
+
+        // The YAML is loaded as the same values.
+        let round_tripped =
+            parse_top_level_yaml(yaml.to_owned(), false, DEFAULT_MAX_DEPTH).unwrap();
+        assert_eq!(
+            export(&fj, ExportFormat::Json, true).unwrap(),
+            export(&round_tripped, ExportFormat::Json, true).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_collapsed_contents_and_comments() {
+        let yaml = indoc! {r#"
+            # Settings
+            a:  # A
+              - 1  # One
+            b: [2]
+        "#};
+        let mut fj = parse_top_level_yaml(yaml.to_owned(), true, DEFAULT_MAX_DEPTH).unwrap();
+        fj.collapse(4);
+
+        assert_eq!(
+            "# Settings\na:  # A\n  - 1  # One\nb: []\n",
+            export(&fj, ExportFormat::Yaml, false).unwrap()
+        );
+        assert_eq!(
+            "{\n  \"a\": [\n    1\n  ],\n  \"b\": [\n    2\n  ]\n}\n",
+            export(&fj, ExportFormat::Json, true).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_multiple_documents() {
+        let fj =
+            parse_top_level_yaml("a: 1\n---\n- 2\n".to_owned(), false, DEFAULT_MAX_DEPTH).unwrap();
+        assert_eq!(
+            "a: 1\n---\n- 2\n",
+            export(&fj, ExportFormat::Yaml, true).unwrap()
+        );
+        assert_eq!(
+            "{\n  \"a\": 1\n}\n[\n  2\n]\n",
+            export(&fj, ExportFormat::Json, true).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_json_value() {
+        let yaml = "a: 1\nb:\n  c: [+2, 'x']\n  1: {}\n";
+        let mut fj = parse_top_level_yaml(yaml.to_owned(), false, DEFAULT_MAX_DEPTH).unwrap();
+        // Collapsed values are still written in full.
+        fj.collapse(2);
+
+        assert_eq!(
+            "{\n  \"c\": [\n    2,\n    \"x\"\n  ],\n  \"1\": {}\n}\n",
+            json_value(&fj, 2, false).unwrap()
+        );
+        assert_eq!(
+            "{\"c\":[2,\"x\"],\"1\":{}}\n",
+            json_value(&fj, 2, true).unwrap()
+        );
+        assert_eq!(
+            "{\"a\":1,\"b\":{\"c\":[2,\"x\"],\"1\":{}}}\n",
+            json_value(&fj, 0, true).unwrap()
+        );
+        assert_eq!("1\n", json_value(&fj, 1, true).unwrap());
+    }
+
+    #[test]
+    fn test_is_json_number() {
+        for number in ["0", "-1", "1.5", "1e3", "1.5E-3", "-0.0"].iter() {
+            assert!(is_json_number(number), "{}", number);
+        }
+        for number in ["+1", "01", "1.", ".5", "1e", "inf", ""].iter() {
+            assert!(!is_json_number(number), "{}", number);
+        }
+    }
+
+    #[test]
+    fn test_too_deep() {
+        let mut fj =
+            parse_top_level_json_to_depth(r#"{"a": [[1]], "b": 2}"#.to_owned(), 2).unwrap();
+        let err = "Values nested more than 2 levels deep weren't parsed (see --max-depth)";
+        assert_eq!(Err(err.to_owned()), export(&fj, ExportFormat::Json, true));
+        assert_eq!(Err(err.to_owned()), export(&fj, ExportFormat::Yaml, true));
+        assert_eq!(Err(err.to_owned()), json_value(&fj, 1, false));
+        assert_eq!(Ok("2\n".to_owned()), json_value(&fj, 4, false));
+
+        // Unless it's in a collapsed container that's left out.
+        fj.collapse(1);
+        assert_eq!(
+            "{\n  \"a\": [],\n  \"b\": 2\n}\n",
+            export(&fj, ExportFormat::Json, false).unwrap()
+        );
+    }
+}
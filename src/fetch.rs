@@ -0,0 +1,336 @@
+// Inputs can be URLs, which are fetched by running curl, rather than by
+// linking an HTTP client (and a TLS implementation) into jless; builds
+// without the "url" feature treat URLs as paths to files.
+//
+// The headers of the response are read first, to pick the format of the
+// input based on its Content-Type, and then the body is read like any
+// other input.
+
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+use crate::options::DataFormat;
+
+// Fail if a connection can't be made within this long, or if nothing is
+// received for STALLED_SECS once the transfer has started. Large inputs
+// can legitimately take a long time, so there's no limit on the total time.
+const CONNECT_TIMEOUT_SECS: u32 = 10;
+const STALLED_SECS: u32 = 30;
+
+pub struct Response {
+    pub body: Body,
+    pub content_type: Option<String>,
+    // The size of the body, if it's known.
+    pub content_length: Option<usize>,
+}
+
+// The body of a response, which is streamed from curl as it's read. An
+// error is returned at the end if curl fails partway through, e.g., because
+// the connection was lost, so that the input isn't silently truncated.
+pub struct Body {
+    reader: BufReader<ChildStdout>,
+    curl: Child,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Headers {
+    status: u16,
+    reason: String,
+    content_type: Option<String>,
+    content_length: Option<usize>,
+    location: bool,
+}
+
+pub fn is_url(path: &Path) -> bool {
+    path.to_str().map_or(false, |path| {
+        let path = path.to_ascii_lowercase();
+        path.starts_with("http://") || path.starts_with("https://")
+    })
+}
+
+pub fn fetch(url: &str) -> io::Result<Response> {
+    let mut curl = Command::new("curl")
+        .args(["--silent", "--show-error", "--location"])
+        .args(["--connect-timeout", &CONNECT_TIMEOUT_SECS.to_string()])
+        .args([
+            "--speed-limit",
+            "1",
+            "--speed-time",
+            &STALLED_SECS.to_string(),
+        ])
+        // Print the headers before the body.
+        .args(["--dump-header", "-"])
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(spawn_error)?;
+
+    let mut reader = BufReader::new(curl.stdout.take().unwrap());
+    let headers = match read_headers(&mut reader)? {
+        Some(headers) => headers,
+        None => return Err(curl_error(&mut curl)),
+    };
+
+    if headers.status >= 400 {
+        let _ = curl.kill();
+        let _ = curl.wait();
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("HTTP {} {}", headers.status, headers.reason)
+                .trim_end()
+                .to_owned(),
+        ));
+    }
+
+    Ok(Response {
+        body: Body { reader, curl },
+        content_type: headers.content_type,
+        content_length: headers.content_length,
+    })
+}
+
+impl Read for Body {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        if n == 0 && !buf.is_empty() && !self.curl.wait()?.success() {
+            return Err(curl_error(&mut self.curl));
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for Body {
+    fn drop(&mut self) {
+        let _ = self.curl.kill();
+        let _ = self.curl.wait();
+    }
+}
+
+fn spawn_error(err: io::Error) -> io::Error {
+    let message = if err.kind() == io::ErrorKind::NotFound {
+        "Unable to fetch URL: curl needs to be installed to view URLs".to_owned()
+    } else {
+        format!("Unable to run curl: {}", err)
+    };
+    io::Error::new(err.kind(), message)
+}
+
+// The error curl printed, e.g., "curl: (6) Could not resolve host: ...".
+fn curl_error(curl: &mut Child) -> io::Error {
+    let status = curl.wait();
+    let mut message = String::new();
+    if let Some(stderr) = &mut curl.stderr {
+        let _ = stderr.read_to_string(&mut message);
+    }
+
+    let message = message.trim();
+    let message = if !message.is_empty() {
+        message.to_owned()
+    } else {
+        match status {
+            Ok(status) => format!("curl failed ({})", status),
+            Err(err) => format!("curl failed: {}", err),
+        }
+    };
+    io::Error::new(io::ErrorKind::Other, message)
+}
+
+// Reads the headers of the final response, skipping over those of
+// redirects and informational (1xx) responses, which are also printed when
+// following redirects. Returns None if curl didn't receive a response.
+fn read_headers(reader: &mut impl BufRead) -> io::Result<Option<Headers>> {
+    loop {
+        let headers = match read_header_block(reader)? {
+            Some(headers) => headers,
+            None => return Ok(None),
+        };
+
+        let redirected = (300..400).contains(&headers.status) && headers.location;
+        if headers.status >= 200 && !redirected {
+            return Ok(Some(headers));
+        }
+    }
+}
+
+fn read_header_block(reader: &mut impl BufRead) -> io::Result<Option<Headers>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    // e.g., "HTTP/1.1 404 Not Found", or "HTTP/2 200".
+    let mut status_line = line.trim_end().splitn(3, ' ');
+    let status = match (status_line.next(), status_line.next()) {
+        (Some(version), Some(status)) if version.starts_with("HTTP/") => status.parse().ok(),
+        _ => None,
+    };
+    let status = status.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid HTTP status line: {:?}", line.trim_end()),
+        )
+    })?;
+
+    let mut headers = Headers {
+        status,
+        reason: status_line.next().unwrap_or("").to_owned(),
+        content_type: None,
+        content_length: None,
+        location: false,
+    };
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            return Ok(Some(headers));
+        }
+
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.trim().to_ascii_lowercase(), value.trim()),
+            None => continue,
+        };
+        match name.as_str() {
+            "content-type" => headers.content_type = Some(value.to_owned()),
+            "content-length" => headers.content_length = value.parse().ok(),
+            "location" => headers.location = true,
+            _ => {}
+        }
+    }
+}
+
+/// The format of a response with the given Content-Type, e.g.,
+/// "application/json; charset=utf-8", or None if the Content-Type doesn't
+/// say, e.g., "text/plain".
+pub fn data_format_for_content_type(content_type: &str) -> Option<DataFormat> {
+    let media_type = content_type.split(';').next().unwrap().trim();
+    let media_type = media_type.to_ascii_lowercase();
+
+    match media_type.as_str() {
+        "application/x-ndjson" | "application/jsonl" | "application/x-jsonlines" => {
+            Some(DataFormat::Ndjson)
+        }
+        "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => {
+            Some(DataFormat::Yaml)
+        }
+        "application/toml" => Some(DataFormat::Toml),
+        "text/csv" => Some(DataFormat::Csv),
+        "text/tab-separated-values" => Some(DataFormat::Tsv),
+        // e.g., application/geo+json.
+        media_type if media_type == "application/json" || media_type.ends_with("+json") => {
+            Some(DataFormat::Json)
+        }
+        _ => None,
+    }
+}
+
+/// The name shown for a URL: the last component of its path, or its host
+/// if it doesn't have a path.
+pub fn filename_for_url(url: &str) -> String {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let without_query = without_scheme.split(&['?', '#'][..]).next().unwrap();
+
+    let (host, path) = without_query.split_once('/').unwrap_or((without_query, ""));
+    match path.rsplit('/').find(|component| !component.is_empty()) {
+        Some(component) => component.to_owned(),
+        None => host.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_url() {
+        assert!(is_url(Path::new("https://example.com/data.json")));
+        assert!(is_url(Path::new("HTTP://example.com")));
+        assert!(!is_url(Path::new("data.json")));
+        assert!(!is_url(Path::new("ftp://example.com/data.json")));
+        assert!(!is_url(Path::new("./https://example.com")));
+    }
+
+    #[test]
+    fn test_spawn_error() {
+        let not_found = io::Error::from(io::ErrorKind::NotFound);
+        assert_eq!(
+            "Unable to fetch URL: curl needs to be installed to view URLs",
+            spawn_error(not_found).to_string(),
+        );
+
+        let denied = io::Error::new(io::ErrorKind::PermissionDenied, "Permission denied");
+        assert_eq!(
+            "Unable to run curl: Permission denied",
+            spawn_error(denied).to_string(),
+        );
+    }
+
+    #[test]
+    fn test_read_headers() {
+        let response = "HTTP/1.1 301 Moved Permanently\r\n\
+                        Location: https://example.com/data.json\r\n\
+                        Content-Type: text/html\r\n\
+                        \r\n\
+                        HTTP/2 100\r\n\
+                        \r\n\
+                        HTTP/2 200\r\n\
+                        content-type: application/json\r\n\
+                        Content-Length: 8\r\n\
+                        \r\n\
+                        {\"a\": 1}";
+        let mut reader = response.as_bytes();
+        let headers = read_headers(&mut reader).unwrap().unwrap();
+        assert_eq!(
+            headers,
+            Headers {
+                status: 200,
+                reason: "".to_owned(),
+                content_type: Some("application/json".to_owned()),
+                content_length: Some(8),
+                location: false,
+            }
+        );
+        // The body is left to be read.
+        assert_eq!(reader, b"{\"a\": 1}");
+
+        let mut reader = &b"HTTP/1.1 404 Not Found\r\n\r\nNope"[..];
+        let headers = read_headers(&mut reader).unwrap().unwrap();
+        assert_eq!(
+            (headers.status, headers.reason.as_str()),
+            (404, "Not Found")
+        );
+
+        assert_eq!(read_headers(&mut &b""[..]).unwrap(), None);
+        assert!(read_headers(&mut &b"{\"a\": 1}"[..]).is_err());
+    }
+
+    #[test]
+    fn test_data_format_for_content_type() {
+        let format = data_format_for_content_type;
+        assert_eq!(format("application/json"), Some(DataFormat::Json));
+        assert_eq!(
+            format("Application/JSON; charset=utf-8"),
+            Some(DataFormat::Json)
+        );
+        assert_eq!(format("application/geo+json"), Some(DataFormat::Json));
+        assert_eq!(format("application/x-ndjson"), Some(DataFormat::Ndjson));
+        assert_eq!(format("text/yaml"), Some(DataFormat::Yaml));
+        assert_eq!(format("text/csv"), Some(DataFormat::Csv));
+        assert_eq!(format("text/plain"), None);
+        assert_eq!(format("application/octet-stream"), None);
+    }
+
+    #[test]
+    fn test_filename_for_url() {
+        assert_eq!(
+            filename_for_url("https://api.example.com/v1/data.json?page=2"),
+            "data.json"
+        );
+        assert_eq!(filename_for_url("https://example.com/items/"), "items");
+        assert_eq!(filename_for_url("https://example.com"), "example.com");
+        assert_eq!(filename_for_url("http://example.com/#top"), "example.com");
+    }
+}
@@ -1,8 +1,10 @@
 use std::fmt::{Debug, Write};
 use std::ops::Range;
 
+use crate::csvparser::{self, CsvOptions};
 use crate::jsonparser;
 use crate::lineprinter;
+use crate::tomlparser;
 use crate::yamlparser;
 
 pub type Index = usize;
@@ -32,6 +34,12 @@ impl OptionIndex {
 
 pub const NIL: usize = usize::MAX;
 
+/// Containers nested more deeply than this are replaced by a placeholder
+/// (see Value::TooDeep), rather than being parsed, so that
+/// pathological inputs can't overflow the stack, unless a different limit
+/// is set with --max-depth.
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
 impl From<usize> for OptionIndex {
     fn from(i: usize) -> Self {
         if i == NIL {
@@ -47,6 +55,10 @@ pub enum PathType {
     Dot,
     Bracket,
     Query,
+    // Paths like gron uses, which start with "json".
+    Gron,
+    // Dot separated keys and indexes without any quoting, e.g., a.b.0.
+    Plain,
     // Just used for the status bar.
     DotWithTopLevelIndex,
 }
@@ -74,6 +86,71 @@ impl FlatJson {
         }
     }
 
+    /// The number of top-level values, which is more than one when the
+    /// input is a stream of concatenated values, e.g., `{"a": 1} {"b": 2}`.
+    pub fn num_top_level_values(&self) -> usize {
+        // The last row is always either the last top-level value, or the
+        // end of it.
+        match self.0.last() {
+            Some(row) if row.is_closing_of_container() => self[row.pair_index().unwrap()].index + 1,
+            Some(row) => row.index + 1,
+            None => 0,
+        }
+    }
+
+    /// Whether any containers were replaced by a placeholder because they
+    /// were nested too deeply.
+    pub fn reached_max_depth(&self) -> bool {
+        self.0.iter().any(|row| matches!(row.value, Value::TooDeep))
+    }
+
+    /// Returns an error if the value at the index, or anything in it, is a
+    /// placeholder for a container that wasn't parsed, or a malformed line,
+    /// so that they're never written out as if they were values.
+    pub fn check_parsed(&self, index: Index) -> Result<(), String> {
+        let (start, end) = match self[index].pair_index() {
+            OptionIndex::Index(pair) => (index.min(pair), index.max(pair)),
+            OptionIndex::Nil => (index, index),
+        };
+        check_rows_parsed(&self.0[start..=end])
+    }
+
+    /// Like check_parsed, but for the whole input.
+    pub fn check_all_parsed(&self) -> Result<(), String> {
+        check_rows_parsed(&self.0)
+    }
+
+    /// Returns a copy of this FlatJson without any malformed lines of JSON
+    /// Lines input, along with their errors, for printing the rest of the
+    /// input. Returns None if there aren't any.
+    pub fn without_malformed_lines(&self) -> Option<(FlatJson, Vec<&str>)> {
+        let errors: Vec<&str> = self
+            .0
+            .iter()
+            .filter(|row| matches!(row.value, Value::Malformed))
+            .map(|row| row.comment.as_deref().unwrap_or_default())
+            .collect();
+        if errors.is_empty() {
+            return None;
+        }
+
+        let keep = self
+            .0
+            .iter()
+            .map(|row| !matches!(row.value, Value::Malformed))
+            .collect();
+        let (copy, _) = RowCopier {
+            flatjson: self,
+            sort_keys: false,
+            keep: Some(keep),
+            rows: vec![],
+            pretty_printed: String::new(),
+            original_indexes: vec![],
+        }
+        .copy();
+        Some((copy, errors))
+    }
+
     pub fn last_visible_item(&self) -> Index {
         let mut last_index = self.0.len() - 1;
 
@@ -158,6 +235,17 @@ impl FlatJson {
         self.0[index].collapse();
     }
 
+    // Collapses the objects and arrays that YAML aliases were replaced by
+    // (see --yaml-aliases).
+    pub fn collapse_aliases(&mut self) {
+        for index in 0..self.0.len() {
+            let row = &self.0[index];
+            if row.is_opening_of_container() && row.anchor.as_ref().map_or(false, |a| a.is_alias) {
+                self.collapse(index);
+            }
+        }
+    }
+
     pub fn toggle_collapsed(&mut self, index: Index) {
         if let OptionIndex::Index(pair) = self.0[index].pair_index() {
             self.0[pair].toggle_collapsed();
@@ -182,20 +270,43 @@ impl FlatJson {
         // Some special handling for top-level elements.
         if self[index].parent.is_nil() {
             match path_type {
-                PathType::Dot | PathType::Bracket => {
+                PathType::Dot | PathType::Bracket | PathType::Plain => {
                     return Err("Cannot build path to top-level element".to_string());
                 }
                 PathType::Query => {
                     return Ok(".".to_string());
                 }
-                PathType::DotWithTopLevelIndex => { /* Handled in impl */ }
+                PathType::Gron | PathType::DotWithTopLevelIndex => { /* Handled in impl */ }
             }
         }
 
+        if path_type == PathType::Gron {
+            buf.push_str("json");
+        }
+
         self.build_path_to_node_impl(path_type, index, &mut buf)?;
         Ok(buf)
     }
 
+    // Builds a line like gron outputs, assigning the value of the node
+    // to its path, e.g., json.a.b[0] = "value";. Containers are assigned
+    // empty objects or arrays, like gron does.
+    pub fn build_gron_assignment(&self, mut index: Index) -> Result<String, String> {
+        if self[index].is_closing_of_container() {
+            index = self[index].pair_index().unwrap();
+        }
+
+        let path = self.build_path_to_node(PathType::Gron, index)?;
+        let row = &self[index];
+        let value = match row.value.container_type() {
+            Some(ContainerType::Object) => "{}",
+            Some(ContainerType::Array) => "[]",
+            None => &self.1[row.range.clone()],
+        };
+
+        Ok(format!("{} = {};", path, value))
+    }
+
     fn build_path_to_node_impl(
         &self,
         path_type: PathType,
@@ -212,6 +323,11 @@ impl FlatJson {
             self.build_path_to_node_impl(path_type, parent_index, buf)?;
         }
 
+        // Separate each part of plain paths with a period.
+        if path_type == PathType::Plain && !buf.is_empty() {
+            buf.push('.');
+        }
+
         let res = if let Some(key_range) = &row.key_range {
             let key_open_delimiter = &self.1[key_range.start..key_range.start + 1];
             let key = &self.1[key_range.start + 1..key_range.end - 1];
@@ -224,7 +340,13 @@ impl FlatJson {
                     );
                 }
 
-                write!(buf, "[{}]", key)
+                if path_type == PathType::Plain {
+                    write!(buf, "{}", key)
+                } else {
+                    write!(buf, "[{}]", key)
+                }
+            } else if path_type == PathType::Plain {
+                write!(buf, "{}", key)
             } else {
                 if path_type != PathType::Bracket && lineprinter::JS_IDENTIFIER.is_match(key) {
                     write!(buf, ".{}", key)
@@ -237,6 +359,17 @@ impl FlatJson {
                     }
                 }
             }
+            .and_then(|_| {
+                // Disambiguate duplicate keys by which occurrence of the
+                // key this is, e.g., .a#2, except in paths that need to
+                // be valid jq or gron.
+                let disambiguate = !matches!(path_type, PathType::Query | PathType::Gron);
+                if disambiguate && row.key_occurrence > 0 {
+                    write!(buf, "#{}", row.key_occurrence + 1)
+                } else {
+                    Ok(())
+                }
+            })
         } else {
             if row.parent.is_nil() {
                 // We only print the top level index for this PathType,
@@ -259,6 +392,7 @@ impl FlatJson {
                             write!(buf, "[]")
                         }
                     }
+                    PathType::Plain => write!(buf, "{}", row.index),
                     _ => write!(buf, "[{}]", row.index),
                 }
             }
@@ -301,6 +435,54 @@ impl FlatJson {
         Ok(buf)
     }
 
+    // Like pretty_printed, but the contents of collapsed containers are
+    // omitted, so that the output only contains what is visible.
+    pub fn pretty_printed_visible(&self) -> Result<String, std::fmt::Error> {
+        let mut buf = String::new();
+        let mut index = 0;
+
+        while index < self.0.len() {
+            let row = &self.0[index];
+            for _ in 0..row.depth {
+                write!(buf, "  ")?;
+            }
+            if let Some(ref key_range) = row.key_range {
+                write!(buf, "{}: ", &self.1[key_range.clone()])?;
+            }
+            let mut trailing_comma = row.parent.is_some() && row.next_sibling.is_some();
+            if let Some(container_type) = row.value.container_type() {
+                if row.value.is_opening_of_container() && row.is_collapsed() {
+                    write!(
+                        buf,
+                        "{}{}",
+                        container_type.open_str(),
+                        container_type.close_str()
+                    )?;
+                    // Skip the contents and the closing row.
+                    index = row.pair_index().unwrap();
+                } else if row.value.is_opening_of_container() {
+                    write!(buf, "{}", container_type.open_str())?;
+                    // Don't print trailing commas after { or [.
+                    trailing_comma = false;
+                } else {
+                    write!(buf, "{}", container_type.close_str())?;
+                    // Check container opening to see if we have a next sibling.
+                    trailing_comma = row.parent.is_some()
+                        && self[row.pair_index().unwrap()].next_sibling.is_some();
+                }
+            } else {
+                write!(buf, "{}", &self.1[row.range.clone()])?;
+            }
+            if trailing_comma {
+                write!(buf, ",")?;
+            }
+            writeln!(buf)?;
+            index += 1;
+        }
+
+        Ok(buf)
+    }
+
     // A lot of the code here is almost identical to pretty_printed, but
     // there are some subtle enough differences, and the code isn't that
     // complicated, that I don't think it's worth it to try to have them
@@ -354,6 +536,704 @@ impl FlatJson {
 
         Ok(buf)
     }
+
+    // Returns a copy of this FlatJson with the members of every object
+    // sorted by their keys, along with a mapping from each row index in the
+    // sorted copy to the index of the corresponding row in this FlatJson.
+    pub fn sorted_by_key(&self) -> (FlatJson, Vec<Index>) {
+        RowCopier {
+            flatjson: self,
+            sort_keys: true,
+            keep: None,
+            rows: Vec::with_capacity(self.0.len()),
+            pretty_printed: String::with_capacity(self.1.len()),
+            original_indexes: Vec::with_capacity(self.0.len()),
+        }
+        .copy()
+    }
+
+    // Returns a copy of this FlatJson containing only the given rows, their
+    // ancestors, and, for objects and arrays, their descendants, along with
+    // a mapping from each row index in the copy to the index of the
+    // corresponding row in this FlatJson. Array elements keep their
+    // original indexes.
+    pub fn filtered(&self, rows: &[Index]) -> (FlatJson, Vec<Index>) {
+        let mut keep = vec![false; self.0.len()];
+
+        for &index in rows.iter() {
+            let row = &self[index];
+            let index = if row.is_closing_of_container() {
+                row.pair_index().unwrap()
+            } else {
+                index
+            };
+
+            let last = match self[index].pair_index() {
+                OptionIndex::Index(close_index) => close_index,
+                OptionIndex::Nil => index,
+            };
+            for kept in keep[index..=last].iter_mut() {
+                *kept = true;
+            }
+
+            let mut ancestor = self[index].parent;
+            while let OptionIndex::Index(parent) = ancestor {
+                if keep[parent] {
+                    break;
+                }
+                keep[parent] = true;
+                keep[self[parent].pair_index().unwrap()] = true;
+                ancestor = self[parent].parent;
+            }
+        }
+
+        RowCopier {
+            flatjson: self,
+            sort_keys: false,
+            keep: Some(keep),
+            rows: vec![],
+            pretty_printed: String::new(),
+            original_indexes: vec![],
+        }
+        .copy()
+    }
+
+    /// Whether a row is an array of at least two objects, which all have
+    /// the same keys, in the same order, so that it can be shown as a
+    /// table, with a column for each key.
+    pub fn is_table(&self, index: Index) -> bool {
+        let row = &self.0[index];
+        if !row.is_array() || !row.is_opening_of_container() {
+            return false;
+        }
+
+        let first = match row.first_child() {
+            OptionIndex::Index(first) if self.0[first].next_sibling.is_some() => first,
+            _ => return false,
+        };
+        let keys = |object: Index| {
+            let mut keys = vec![];
+            let mut member = match &self.0[object].value {
+                Value::OpenContainer {
+                    container_type: ContainerType::Object,
+                    first_child,
+                    ..
+                } => OptionIndex::Index(*first_child),
+                _ => return None,
+            };
+            while let OptionIndex::Index(i) = member {
+                keys.push(&self.1[self.0[i].key_range.clone()?]);
+                member = self.0[i].next_sibling;
+            }
+            Some(keys)
+        };
+
+        let first_keys = match keys(first) {
+            Some(first_keys) => first_keys,
+            None => return false,
+        };
+        let mut element = self.0[first].next_sibling;
+        while let OptionIndex::Index(i) = element {
+            if keys(i).as_ref() != Some(&first_keys) {
+                return false;
+            }
+            element = self.0[i].next_sibling;
+        }
+
+        true
+    }
+
+    // Returns a copy of this FlatJson without the members of objects whose
+    // values are null, or, if empty_containers is set, empty objects and
+    // arrays too (including ones whose members are all hidden), along with
+    // a mapping like the one returned by filtered. Returns None if there
+    // aren't any values to hide.
+    pub fn without_empty_values(&self, empty_containers: bool) -> Option<(FlatJson, Vec<Index>)> {
+        let mut keep = vec![true; self.0.len()];
+        let mut hides_any = false;
+
+        // Members come after the objects and arrays containing them, so by
+        // the time a container is reached, which of its members are hidden
+        // is already known.
+        for index in (0..self.0.len()).rev() {
+            let row = &self[index];
+            if row.key_range.is_none() {
+                continue;
+            }
+
+            let hidden = match &row.value {
+                Value::Null => true,
+                Value::EmptyObject | Value::EmptyArray => empty_containers,
+                Value::OpenContainer { first_child, .. } if empty_containers => {
+                    let mut child = OptionIndex::Index(*first_child);
+                    let mut has_members = false;
+                    while let OptionIndex::Index(child_index) = child {
+                        if keep[child_index] {
+                            has_members = true;
+                            break;
+                        }
+                        child = self[child_index].next_sibling;
+                    }
+                    !has_members
+                }
+                _ => false,
+            };
+            if hidden {
+                keep[index] = false;
+                hides_any = true;
+            }
+        }
+
+        if !hides_any {
+            return None;
+        }
+
+        let copy = RowCopier {
+            flatjson: self,
+            sort_keys: false,
+            keep: Some(keep),
+            rows: vec![],
+            pretty_printed: String::new(),
+            original_indexes: vec![],
+        }
+        .copy();
+        Some(copy)
+    }
+
+    // Adds rows that have been parsed in the background (see
+    // jsonparser::parse_in_background) to the end of this FlatJson, linking
+    // them to the previously added rows. Any placeholders added by
+    // add_closing_placeholders should be removed first.
+    pub fn append_parsed_rows(&mut self, parsed: jsonparser::ParsedRows) {
+        let start = self.0.len();
+        self.1.push_str(&parsed.pretty_printed);
+        self.2 = self.2.max(parsed.max_depth);
+
+        for mut row in parsed.rows.into_iter() {
+            let index = self.0.len();
+
+            if let OptionIndex::Index(prev) = row.prev_sibling {
+                if prev < start {
+                    self.0[prev].next_sibling = OptionIndex::Index(index);
+                }
+            }
+
+            if let Value::CloseContainer {
+                open_index,
+                ref mut collapsed,
+                ..
+            } = row.value
+            {
+                if open_index < start {
+                    *collapsed = self.0[open_index].is_collapsed();
+                    self.set_close_index(open_index, index, row.range.end);
+                    // The opening row was sent before where the container
+                    // ends in the input was known.
+                    if let (Some(open_source), Some(close_source)) =
+                        (&mut self.0[open_index].source_range, &row.source_range)
+                    {
+                        open_source.end = close_source.end;
+                    }
+                }
+            }
+
+            self.0.push(row);
+        }
+    }
+
+    // Adds closing rows for containers that haven't been completely parsed
+    // yet, innermost first, so that the rows parsed so far can be
+    // displayed.
+    pub fn add_closing_placeholders(&mut self, open_containers: &[(Index, Index)]) {
+        for &(open_index, last_child) in open_containers.iter().rev() {
+            let open = &self.0[open_index];
+            let container_type = open.value.container_type().unwrap();
+            let collapsed = open.is_collapsed();
+            let parent = open.parent;
+            let depth = open.depth;
+
+            if container_type == ContainerType::Object {
+                self.1.push(' ');
+            }
+            let close_start = self.1.len();
+            self.1.push_str(container_type.close_str());
+
+            let close_index = self.0.len();
+            self.0.push(Row {
+                parent,
+                prev_sibling: OptionIndex::Nil,
+                next_sibling: OptionIndex::Nil,
+                depth,
+                index: 0,
+                range: close_start..self.1.len(),
+                key_range: None,
+                source_range: None,
+                key_occurrence: 0,
+                anchor: None,
+                comment: None,
+                value: Value::CloseContainer {
+                    container_type,
+                    collapsed,
+                    last_child,
+                    open_index,
+                },
+            });
+            self.set_close_index(open_index, close_index, self.1.len());
+        }
+    }
+
+    // Adds the elements of another top-level array to the end of this
+    // top-level array, e.g., when more records have been added to a JSON
+    // Lines file.
+    pub fn append_array_elements(&mut self, elements: FlatJson) {
+        debug_assert!(self[0].is_array() || matches!(self[0].value, Value::EmptyArray));
+
+        if !elements[0].is_opening_of_container() {
+            // There's nothing to add.
+            return;
+        }
+        if !self[0].is_opening_of_container() {
+            *self = elements;
+            return;
+        }
+
+        let old_close = self.0.pop().unwrap();
+        let (collapsed, old_last_child) =
+            (old_close.is_collapsed(), old_close.last_child().unwrap());
+        let num_elements = self[old_last_child].index + 1;
+
+        // Replace the closing bracket with a comma, then add everything
+        // after the opening bracket of the other array.
+        self.1.truncate(old_close.range.start);
+        self.1.push_str(", ");
+        let text_offset = self.1.len() - 1;
+        self.1.push_str(&elements.1[1..]);
+        self.2 = self.2.max(elements.2);
+
+        // The other array's opening row becomes this array's opening row.
+        let start = self.0.len();
+        let new_index = |index: Index| if index == 0 { 0 } else { index - 1 + start };
+        let new_option_index = |index: OptionIndex| match index {
+            OptionIndex::Index(index) => OptionIndex::Index(new_index(index)),
+            OptionIndex::Nil => OptionIndex::Nil,
+        };
+
+        for mut row in elements.0.into_iter().skip(1) {
+            let index = self.0.len();
+            let is_element = row.parent == OptionIndex::Index(0) && !row.is_closing_of_container();
+
+            row.parent = new_option_index(row.parent);
+            row.prev_sibling = new_option_index(row.prev_sibling);
+            row.next_sibling = new_option_index(row.next_sibling);
+
+            if is_element {
+                row.index += num_elements;
+                if row.prev_sibling.is_nil() {
+                    row.prev_sibling = OptionIndex::Index(old_last_child);
+                    self.0[old_last_child].next_sibling = OptionIndex::Index(index);
+                }
+            }
+            row.range = row.range.start + text_offset..row.range.end + text_offset;
+            row.key_range = row
+                .key_range
+                .map(|range| range.start + text_offset..range.end + text_offset);
+            // The elements were parsed from other text.
+            row.source_range = None;
+
+            match &mut row.value {
+                Value::OpenContainer {
+                    first_child,
+                    close_index,
+                    ..
+                } => {
+                    *first_child = new_index(*first_child);
+                    *close_index = new_index(*close_index);
+                }
+                Value::CloseContainer {
+                    last_child,
+                    open_index,
+                    collapsed: close_collapsed,
+                    ..
+                } => {
+                    *last_child = new_index(*last_child);
+                    if *open_index == 0 {
+                        *close_collapsed = collapsed;
+                    } else {
+                        *open_index = new_index(*open_index);
+                    }
+                }
+                _ => {}
+            }
+
+            self.0.push(row);
+        }
+
+        let close_index = self.0.len() - 1;
+        self.set_close_index(0, close_index, self.1.len());
+    }
+
+    /// Counts the values of each type, in a single pass over the rows.
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats {
+            rows: self.0.len(),
+            memory_size: std::mem::size_of::<FlatJson>()
+                + self.0.capacity() * std::mem::size_of::<Row>()
+                + self.1.capacity(),
+            ..Stats::default()
+        };
+
+        for row in self.0.iter() {
+            stats.max_depth = stats.max_depth.max(row.depth);
+            match &row.value {
+                Value::Null => stats.nulls += 1,
+                Value::Boolean => stats.booleans += 1,
+                Value::Number => stats.numbers += 1,
+                Value::String => stats.strings += 1,
+                // What was in it wasn't parsed.
+                Value::TooDeep | Value::Malformed => {}
+                Value::EmptyObject => stats.objects += 1,
+                Value::EmptyArray => stats.arrays += 1,
+                Value::OpenContainer { container_type, .. } => match container_type {
+                    ContainerType::Object => stats.objects += 1,
+                    ContainerType::Array => stats.arrays += 1,
+                },
+                Value::CloseContainer { .. } => {}
+            }
+        }
+
+        stats
+    }
+
+    // Replaces a value (and, for objects and arrays, all the rows in it)
+    // with the single top-level value of another FlatJson, e.g., when the
+    // JSON in a string is decoded. The value keeps the key, array index,
+    // and siblings of the row it replaces. Rows after the replaced ones
+    // move by the difference in the number of rows.
+    pub fn replace_value(&mut self, index: Index, value: FlatJson) {
+        debug_assert!(!self[index].is_closing_of_container() && value[0].next_sibling.is_nil());
+
+        let old = self[index].clone();
+        let old_last = match old.pair_index() {
+            OptionIndex::Index(close_index) => close_index,
+            OptionIndex::Nil => index,
+        };
+        let old_len = old.range.len();
+        let new_len = value.1.len();
+        let text_start = old.range.start;
+
+        let row_delta = value.0.len() as isize - (old_last + 1 - index) as isize;
+        let text_delta = new_len as isize - old_len as isize;
+
+        // Indexes of rows before the replaced ones (including references
+        // to the replaced row itself) stay the same.
+        let shift_index = |i: Index| {
+            if i <= index {
+                i
+            } else {
+                (i as isize + row_delta) as usize
+            }
+        };
+        let shift_pos = |pos: usize| {
+            if pos < text_start + old_len {
+                pos
+            } else {
+                (pos as isize + text_delta) as usize
+            }
+        };
+
+        let mut rows: Vec<Row> = Vec::with_capacity((self.0.len() as isize + row_delta) as usize);
+        let mut old_rows = std::mem::take(&mut self.0).into_iter();
+
+        for mut row in old_rows.by_ref().take(index) {
+            // The end of an ancestor is after the replaced value.
+            row.range.end = shift_pos(row.range.end);
+            Self::shift_row_indexes(&mut row, &shift_index);
+            rows.push(row);
+        }
+
+        for (i, mut row) in value.0.into_iter().enumerate() {
+            Self::shift_row_indexes(&mut row, &|i| i + index);
+            if row.parent.is_nil() {
+                row.parent = old.parent;
+            }
+            row.depth += old.depth;
+            row.range = row.range.start + text_start..row.range.end + text_start;
+            row.key_range = row
+                .key_range
+                .map(|range| range.start + text_start..range.end + text_start);
+            // The new value was parsed from other text, e.g., a decoded
+            // string, so only its top-level row, which takes the place of
+            // the old value, has a source.
+            row.source_range = if i == 0 {
+                old.source_range.clone()
+            } else {
+                None
+            };
+            if i == 0 {
+                row.prev_sibling = old.prev_sibling;
+                row.next_sibling = match old.next_sibling {
+                    OptionIndex::Index(next) => OptionIndex::Index(shift_index(next)),
+                    OptionIndex::Nil => OptionIndex::Nil,
+                };
+                row.index = old.index;
+                row.key_range = old.key_range.clone();
+                row.key_occurrence = old.key_occurrence;
+            }
+            rows.push(row);
+        }
+
+        for mut row in old_rows.skip(old_last + 1 - index) {
+            row.range = shift_pos(row.range.start)..shift_pos(row.range.end);
+            row.key_range = row
+                .key_range
+                .map(|range| shift_pos(range.start)..shift_pos(range.end));
+            Self::shift_row_indexes(&mut row, &shift_index);
+            rows.push(row);
+        }
+
+        self.0 = rows;
+        self.1
+            .replace_range(text_start..text_start + old_len, &value.1);
+        self.2 = self.2.max(old.depth + value.2);
+    }
+
+    // Updates the indexes of the other rows that a row refers to.
+    fn shift_row_indexes(row: &mut Row, shift_index: &dyn Fn(Index) -> Index) {
+        let shift_option_index = |i: OptionIndex| match i {
+            OptionIndex::Index(i) => OptionIndex::Index(shift_index(i)),
+            OptionIndex::Nil => OptionIndex::Nil,
+        };
+        row.parent = shift_option_index(row.parent);
+        row.prev_sibling = shift_option_index(row.prev_sibling);
+        row.next_sibling = shift_option_index(row.next_sibling);
+
+        match &mut row.value {
+            Value::OpenContainer {
+                first_child,
+                close_index,
+                ..
+            } => {
+                *first_child = shift_index(*first_child);
+                *close_index = shift_index(*close_index);
+            }
+            Value::CloseContainer {
+                last_child,
+                open_index,
+                ..
+            } => {
+                *last_child = shift_index(*last_child);
+                *open_index = shift_index(*open_index);
+            }
+            _ => {}
+        }
+    }
+
+    fn set_close_index(&mut self, open_index: Index, new_close_index: Index, end: usize) {
+        let open = &mut self.0[open_index];
+        open.range.end = end;
+        if let Value::OpenContainer {
+            ref mut close_index,
+            ..
+        } = open.value
+        {
+            *close_index = new_close_index;
+        }
+    }
+}
+
+/// The size of a FlatJson, shown by ':stats'.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub rows: usize,
+    pub objects: usize,
+    pub arrays: usize,
+    pub strings: usize,
+    pub numbers: usize,
+    pub booleans: usize,
+    pub nulls: usize,
+    pub max_depth: usize,
+    // An estimate of the number of bytes used by the rows and the pretty
+    // printed input they refer to.
+    pub memory_size: usize,
+}
+
+// Helper for FlatJson::sorted_by_key and FlatJson::filtered, which builds
+// a new set of rows (and new pretty printed string that they refer to) by
+// visiting the original rows, possibly in sorted order, and possibly
+// skipping some of them.
+struct RowCopier<'a> {
+    flatjson: &'a FlatJson,
+    sort_keys: bool,
+    // Which rows of the original FlatJson to copy, if not all of them.
+    keep: Option<Vec<bool>>,
+    rows: Vec<Row>,
+    pretty_printed: String,
+    original_indexes: Vec<Index>,
+}
+
+impl<'a> RowCopier<'a> {
+    fn copy(mut self) -> (FlatJson, Vec<Index>) {
+        let flatjson = self.flatjson;
+        let mut top_level = OptionIndex::Index(0);
+        let mut prev_sibling = OptionIndex::Nil;
+
+        while let OptionIndex::Index(index) = top_level {
+            top_level = flatjson[index].next_sibling;
+            if !self.keeps(index) {
+                continue;
+            }
+
+            if prev_sibling.is_some() {
+                self.pretty_printed.push('\n');
+            }
+            let new_index = self.push_row(index, OptionIndex::Nil);
+            self.link_sibling(new_index, prev_sibling);
+            prev_sibling = OptionIndex::Index(new_index);
+        }
+
+        (
+            FlatJson(self.rows, self.pretty_printed, flatjson.2),
+            self.original_indexes,
+        )
+    }
+
+    fn keeps(&self, index: Index) -> bool {
+        self.keep.as_ref().map_or(true, |keep| keep[index])
+    }
+
+    // Adds the given row (and its descendants) of the original FlatJson,
+    // returning the index of the new row.
+    fn push_row(&mut self, index: Index, parent: OptionIndex) -> Index {
+        let flatjson = self.flatjson;
+        let row = &flatjson[index];
+
+        let key_range = row.key_range.as_ref().map(|key_range| {
+            let start = self.pretty_printed.len();
+            self.pretty_printed.push_str(&flatjson.1[key_range.clone()]);
+            let range = start..self.pretty_printed.len();
+            self.pretty_printed.push_str(": ");
+            range
+        });
+
+        let new_index = self.rows.len();
+        let start = self.pretty_printed.len();
+        self.original_indexes.push(index);
+        self.rows.push(Row {
+            parent,
+            prev_sibling: OptionIndex::Nil,
+            next_sibling: OptionIndex::Nil,
+            depth: row.depth,
+            index: row.index,
+            range: start..start,
+            key_range,
+            source_range: row.source_range.clone(),
+            key_occurrence: row.key_occurrence,
+            anchor: row.anchor.clone(),
+            comment: row.comment.clone(),
+            value: row.value.clone(),
+        });
+
+        let container_type = match row.value.container_type() {
+            Some(container_type) => container_type,
+            None => {
+                self.pretty_printed.push_str(&flatjson.1[row.range.clone()]);
+                self.rows[new_index].range.end = self.pretty_printed.len();
+                return new_index;
+            }
+        };
+
+        let mut children = vec![];
+        let mut child = row.first_child();
+        while let OptionIndex::Index(child_index) = child {
+            if self.keeps(child_index) {
+                children.push(child_index);
+            }
+            child = flatjson[child_index].next_sibling;
+        }
+
+        // Objects and arrays whose members are all left out are empty.
+        if children.is_empty() {
+            self.pretty_printed.push_str(container_type.open_str());
+            self.pretty_printed.push_str(container_type.close_str());
+            self.rows[new_index].range.end = self.pretty_printed.len();
+            self.rows[new_index].value = match container_type {
+                ContainerType::Object => Value::EmptyObject,
+                ContainerType::Array => Value::EmptyArray,
+            };
+            return new_index;
+        }
+
+        if self.sort_keys && container_type == ContainerType::Object {
+            // Sort by the key, without the quotes.
+            children.sort_by_key(|&child_index| {
+                let key_range = flatjson[child_index].key_range.clone().unwrap();
+                &flatjson.1[key_range.start + 1..key_range.end - 1]
+            });
+        }
+
+        self.pretty_printed.push_str(container_type.open_str());
+        if container_type == ContainerType::Object {
+            self.pretty_printed.push(' ');
+        }
+
+        let mut prev_sibling = OptionIndex::Nil;
+        for (i, child_index) in children.into_iter().enumerate() {
+            if i != 0 {
+                self.pretty_printed.push_str(", ");
+            }
+            let new_child_index = self.push_row(child_index, OptionIndex::Index(new_index));
+            if self.sort_keys {
+                self.rows[new_child_index].index = i;
+            }
+            self.link_sibling(new_child_index, prev_sibling);
+            prev_sibling = OptionIndex::Index(new_child_index);
+        }
+
+        if container_type == ContainerType::Object {
+            self.pretty_printed.push(' ');
+        }
+
+        let close_index = self.rows.len();
+        let close_start = self.pretty_printed.len();
+        self.pretty_printed.push_str(container_type.close_str());
+        self.original_indexes.push(row.pair_index().unwrap());
+        self.rows.push(Row {
+            parent,
+            prev_sibling: OptionIndex::Nil,
+            next_sibling: OptionIndex::Nil,
+            depth: row.depth,
+            index: 0,
+            range: close_start..self.pretty_printed.len(),
+            key_range: None,
+            source_range: flatjson[row.pair_index().unwrap()].source_range.clone(),
+            key_occurrence: 0,
+            anchor: None,
+            comment: None,
+            value: Value::CloseContainer {
+                container_type,
+                collapsed: row.is_collapsed(),
+                last_child: prev_sibling.unwrap(),
+                open_index: new_index,
+            },
+        });
+
+        self.rows[new_index].range.end = self.pretty_printed.len();
+        self.rows[new_index].value = Value::OpenContainer {
+            container_type,
+            collapsed: row.is_collapsed(),
+            first_child: new_index + 1,
+            close_index,
+        };
+
+        new_index
+    }
+
+    fn link_sibling(&mut self, index: Index, prev_sibling: OptionIndex) {
+        self.rows[index].prev_sibling = prev_sibling;
+        if let OptionIndex::Index(prev) = prev_sibling {
+            self.rows[prev].next_sibling = OptionIndex::Index(index);
+        }
+    }
 }
 
 impl std::ops::Index<usize> for FlatJson {
@@ -370,7 +1250,7 @@ impl std::ops::IndexMut<usize> for FlatJson {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Row {
     pub parent: OptionIndex,
     // Should these also be set on the CloseContainers?
@@ -381,9 +1261,33 @@ pub struct Row {
     pub index: Index,
     pub range: Range<usize>,
     pub key_range: Option<Range<usize>>,
+    // For rows parsed by the JSON parser, the bytes of the JSON text that
+    // the value was parsed from, from its first character to its last
+    // (including the closing bracket of objects and arrays).
+    pub source_range: Option<Range<usize>>,
+    // How many earlier members of the same object have the same key as
+    // this one; non-zero for the second and later uses of a duplicate key.
+    pub key_occurrence: usize,
+    // For YAML inputs, the anchor defined on this value, or the alias it's
+    // a copy of. Only set on the opening row of containers.
+    pub anchor: Option<Box<Anchor>>,
+    // For YAML inputs parsed with --yaml-comments, the comments next to
+    // the value, or on the lines above it.
+    pub comment: Option<Box<str>>,
     pub value: Value,
 }
 
+// A YAML anchor (&name) on a value that an alias (*name) refers to, or an
+// alias, which is replaced by a copy of the value its anchor is on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Anchor {
+    pub name: String,
+    // Identifies which value an alias refers to, since the same name can
+    // be used for more than one anchor.
+    pub id: usize,
+    pub is_alias: bool,
+}
+
 impl Row {
     pub fn is_primitive(&self) -> bool {
         self.value.is_primitive()
@@ -438,7 +1342,7 @@ impl Row {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ContainerType {
     Object,
     Array,
@@ -467,12 +1371,20 @@ impl ContainerType {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Value {
     Null,
     Boolean,
     Number,
     String,
+    // A container nested more deeply than --max-depth, which wasn't parsed,
+    // shown as a placeholder (see too_deep_placeholder). Its rows aren't
+    // valid JSON, so it's never written out (see check_parsed).
+    TooDeep,
+    // A line of JSON Lines input that isn't valid JSON, shown as it is,
+    // with the error as its comment (see jsonparser::parse_lines). It's
+    // never written out either.
+    Malformed,
     EmptyObject,
     EmptyArray,
     OpenContainer {
@@ -593,13 +1505,74 @@ impl Value {
     }
 }
 
+// Parsers recurse into each container, so the threads they run on need a
+// big enough stack for the deepest ones allowed. This is (generously)
+// enough for each level of nesting in debug builds.
+pub fn parser_stack_size(max_depth: usize) -> usize {
+    const MIN_STACK_SIZE: usize = 1 << 20;
+    const STACK_SIZE_PER_LEVEL: usize = 4096;
+    MIN_STACK_SIZE + max_depth * STACK_SIZE_PER_LEVEL
+}
+
+// The text shown in place of a container that's nested too deeply. It
+// isn't quoted, since it isn't a string, or valid JSON.
+pub fn too_deep_placeholder(max_depth: usize) -> String {
+    format!("… max depth {} reached", max_depth)
+}
+
+// The error for writing out containers that were nested too deeply to be
+// parsed, given how deeply.
+pub fn too_deep_error(max_depth: usize) -> String {
+    format!(
+        "Values nested more than {} levels deep weren't parsed (see --max-depth)",
+        max_depth
+    )
+}
+
+// The error for writing out a row that isn't a value from the input, i.e.,
+// a placeholder for a container that wasn't parsed, or a malformed line.
+pub fn unwritable_error(row: &Row) -> Option<String> {
+    match row.value {
+        Value::TooDeep => Some(too_deep_error(row.depth)),
+        Value::Malformed => Some(format!(
+            "Malformed lines can't be written out ({})",
+            row.comment.as_deref().unwrap_or_default()
+        )),
+        _ => None,
+    }
+}
+
+fn check_rows_parsed(rows: &[Row]) -> Result<(), String> {
+    match rows.iter().find_map(unwritable_error) {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
 pub fn parse_top_level_json(json: String) -> Result<FlatJson, String> {
-    let (rows, pretty, depth) = jsonparser::parse(json)?;
+    parse_top_level_json_to_depth(json, DEFAULT_MAX_DEPTH)
+}
+
+pub fn parse_top_level_json_to_depth(json: String, max_depth: usize) -> Result<FlatJson, String> {
+    let (rows, pretty, depth) = jsonparser::parse(json, max_depth)?;
+    Ok(FlatJson(rows, pretty, depth))
+}
+
+pub fn parse_top_level_yaml(
+    yaml: String,
+    comments: bool,
+    max_depth: usize,
+) -> Result<FlatJson, String> {
+    let (rows, pretty, depth) = yamlparser::parse(yaml, comments, max_depth)?;
     Ok(FlatJson(rows, pretty, depth))
 }
 
-pub fn parse_top_level_yaml(yaml: String) -> Result<FlatJson, String> {
-    let (rows, pretty, depth) = yamlparser::parse(yaml)?;
+pub fn parse_top_level_csv(csv: String, options: CsvOptions) -> Result<FlatJson, String> {
+    parse_top_level_json(csvparser::csv_to_json(&csv, options)?)
+}
+
+pub fn parse_top_level_toml(toml: String, max_depth: usize) -> Result<FlatJson, String> {
+    let (rows, pretty, depth) = tomlparser::parse(toml, max_depth)?;
     Ok(FlatJson(rows, pretty, depth))
 }
 
@@ -782,7 +1755,7 @@ mod tests {
         movement_name: &'static str,
         fj: &FlatJson,
         start_index: Index,
-        expected_visited_rows: &Vec<usize>,
+        expected_visited_rows: &[usize],
         movement_fn: fn(&FlatJson, Index) -> OptionIndex,
     ) {
         let mut curr_index = start_index;
@@ -818,7 +1791,7 @@ mod tests {
         assert_prev_visited_rows(fj, start, &expected);
     }
 
-    fn assert_next_visited_rows(fj: &FlatJson, start_index: Index, expected: &Vec<usize>) {
+    fn assert_next_visited_rows(fj: &FlatJson, start_index: Index, expected: &[usize]) {
         assert_row_iter(
             "next_visible_row",
             fj,
@@ -828,7 +1801,7 @@ mod tests {
         );
     }
 
-    fn assert_prev_visited_rows(fj: &FlatJson, start_index: Index, expected: &Vec<usize>) {
+    fn assert_prev_visited_rows(fj: &FlatJson, start_index: Index, expected: &[usize]) {
         assert_row_iter(
             "prev_visible_row",
             fj,
@@ -854,11 +1827,11 @@ mod tests {
         assert_prev_visited_items(fj, start, &expected);
     }
 
-    fn assert_next_visited_items(fj: &FlatJson, start_index: Index, expected: &Vec<usize>) {
+    fn assert_next_visited_items(fj: &FlatJson, start_index: Index, expected: &[usize]) {
         assert_row_iter("next_item", fj, start_index, expected, FlatJson::next_item);
     }
 
-    fn assert_prev_visited_items(fj: &FlatJson, start_index: Index, expected: &Vec<usize>) {
+    fn assert_prev_visited_items(fj: &FlatJson, start_index: Index, expected: &[usize]) {
         assert_row_iter("prev_item", fj, start_index, expected, FlatJson::prev_item);
     }
 
@@ -989,12 +1962,76 @@ mod tests {
         const YAML: &str = r#"{
             [1, 1]: 1,
         }"#;
-        let fj = parse_top_level_yaml(YAML.to_owned()).unwrap();
+        let fj = parse_top_level_yaml(YAML.to_owned(), false, DEFAULT_MAX_DEPTH).unwrap();
         assert_eq!("[[1, 1]]", fj.build_path_to_node(Dot, 1).unwrap());
         assert_eq!("[[1, 1]]", fj.build_path_to_node(Bracket, 1).unwrap());
         assert!(fj.build_path_to_node(Query, 1).is_err());
     }
 
+    #[test]
+    fn test_gron_and_plain_build_path_to_node() {
+        use PathType::*;
+
+        const JSON: &str = r#"{
+            "non js key": 1,
+            "plain_key": [
+                {},
+                {
+                    "nested": "five",
+                },
+            ],
+        }"#;
+
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        assert_eq!("json", fj.build_path_to_node(Gron, 0).unwrap());
+        assert!(fj.build_path_to_node(Plain, 0).is_err());
+
+        assert_eq!(
+            r#"json["non js key"]"#,
+            fj.build_path_to_node(Gron, 1).unwrap()
+        );
+        assert_eq!("non js key", fj.build_path_to_node(Plain, 1).unwrap());
+
+        assert_eq!(
+            "json.plain_key[1].nested",
+            fj.build_path_to_node(Gron, 5).unwrap()
+        );
+        assert_eq!(
+            "plain_key.1.nested",
+            fj.build_path_to_node(Plain, 5).unwrap()
+        );
+
+        assert_eq!("json = {};", fj.build_gron_assignment(0).unwrap());
+        assert_eq!("json = {};", fj.build_gron_assignment(8).unwrap());
+        assert_eq!(
+            r#"json["non js key"] = 1;"#,
+            fj.build_gron_assignment(1).unwrap()
+        );
+        assert_eq!("json.plain_key = [];", fj.build_gron_assignment(2).unwrap());
+        assert_eq!(
+            "json.plain_key[0] = {};",
+            fj.build_gron_assignment(3).unwrap()
+        );
+        assert_eq!(
+            r#"json.plain_key[1].nested = "five";"#,
+            fj.build_gron_assignment(5).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_build_path_to_node_duplicate_keys() {
+        use PathType::*;
+
+        const JSON: &str = r#"{"a": 1, "a": {"b": 2}, "a": 3}"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        assert_paths_to_node(&fj, 1, (".a", r#"["a"]"#, ".a", ".a"));
+        assert_paths_to_node(&fj, 3, (".a#2.b", r#"["a"]#2["b"]"#, ".a.b", ".a#2.b"));
+        assert_eq!("a#3", fj.build_path_to_node(Plain, 5).unwrap());
+        assert_eq!("json.a", fj.build_path_to_node(Gron, 5).unwrap());
+    }
+
     #[track_caller]
     fn assert_paths_to_node(fj: &FlatJson, index: Index, paths: (&str, &str, &str, &str)) {
         use PathType::*;
@@ -1015,6 +2052,261 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_pretty_print_visible() {
+        const JSON: &str = r#"{"a":1,"b":[2,{"c":3},[],false],"d":{"e":null}}"#;
+
+        let mut fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        assert_eq!(
+            fj.pretty_printed().unwrap(),
+            fj.pretty_printed_visible().unwrap()
+        );
+
+        fj.collapse(4);
+        fj.collapse(10);
+
+        let expected = r#"{
+  "a": 1,
+  "b": [
+    2,
+    {},
+    [],
+    false
+  ],
+  "d": {}
+}
+"#;
+        assert_eq!(expected, fj.pretty_printed_visible().unwrap());
+
+        fj.collapse(0);
+        assert_eq!("{}\n", fj.pretty_printed_visible().unwrap());
+    }
+
+    #[test]
+    fn test_sorted_by_key() {
+        const JSON: &str = r#"{"b":1,"a":[{"d":2,"c":3},{}],"c":null}
+[{"z":1,"y":2}]"#;
+        const SORTED_JSON: &str = r#"{"a":[{"c":3,"d":2},{}],"b":1,"c":null}
+[{"y":2,"z":1}]"#;
+
+        let mut fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        fj.collapse(3);
+
+        let (sorted, original_indexes) = fj.sorted_by_key();
+        let expected = parse_top_level_json(SORTED_JSON.to_owned()).unwrap();
+
+        assert_eq!(expected.1, sorted.1);
+        assert_eq!(expected.0.len(), sorted.0.len());
+        for (row, expected_row) in sorted.0.iter().zip(expected.0.iter()) {
+            assert_eq!(expected_row.parent, row.parent);
+            assert_eq!(expected_row.prev_sibling, row.prev_sibling);
+            assert_eq!(expected_row.next_sibling, row.next_sibling);
+            assert_eq!(expected_row.depth, row.depth);
+            assert_eq!(expected_row.range, row.range);
+            assert_eq!(expected_row.key_range, row.key_range);
+            assert_eq!(expected_row.first_child(), row.first_child());
+            assert_eq!(expected_row.last_child(), row.last_child());
+            assert_eq!(expected_row.pair_index(), row.pair_index());
+        }
+
+        assert_eq!(
+            vec![0, 2, 3, 5, 4, 6, 7, 8, 1, 9, 10, 11, 12, 14, 13, 15, 16],
+            original_indexes
+        );
+
+        // Collapsed state is preserved.
+        assert!(sorted[2].is_collapsed());
+        assert!(sorted[5].is_collapsed());
+        assert!(sorted[0].is_expanded());
+    }
+
+    // The rows, without where they were in the input, for comparing rows
+    // spliced from one input with rows parsed from another.
+    fn format_without_source_ranges(rows: &[Row]) -> String {
+        let rows = rows
+            .iter()
+            .cloned()
+            .map(|mut row| {
+                row.source_range = None;
+                row
+            })
+            .collect::<Vec<Row>>();
+        format!("{:?}", rows)
+    }
+
+    #[test]
+    fn test_append_array_elements() {
+        let mut fj = parse_top_level_json(r#"[1, {"a": [2]}]"#.to_owned()).unwrap();
+        fj.collapse(0);
+        fj.append_array_elements(parse_top_level_json(r#"[3, [4, {}]]"#.to_owned()).unwrap());
+
+        let mut expected =
+            parse_top_level_json(r#"[1, {"a": [2]}, 3, [4, {}]]"#.to_owned()).unwrap();
+        expected.collapse(0);
+        assert_eq!(expected.1, fj.1);
+        assert_eq!(
+            format_without_source_ranges(&expected.0),
+            format_without_source_ranges(&fj.0)
+        );
+        assert_eq!(expected.2, fj.2);
+        assert_eq!(Some(0..15), fj[0].source_range);
+        assert_eq!(Some(1..2), fj[1].source_range);
+        assert_eq!(None, fj[7].source_range);
+
+        // Appending to, or appending, an empty array.
+        let mut fj = parse_top_level_json("[]".to_owned()).unwrap();
+        fj.append_array_elements(parse_top_level_json("[]".to_owned()).unwrap());
+        assert_eq!(1, fj.0.len());
+        fj.append_array_elements(parse_top_level_json("[1, 2]".to_owned()).unwrap());
+        assert_eq!("[1, 2]", fj.1);
+        assert_eq!(4, fj.0.len());
+    }
+
+    #[test]
+    fn test_replace_value() {
+        const JSON: &str = r#"{"a": "x", "b": [1, "{\"c\": [true]}", 2], "d": {"e": 3}}"#;
+        let mut fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        let string = fj.1[fj[4].range.clone()].to_owned();
+        fj.replace_value(
+            4,
+            parse_top_level_json(r#"{"c": [true]}"#.to_owned()).unwrap(),
+        );
+
+        let expected = parse_top_level_json(
+            r#"{"a": "x", "b": [1, {"c": [true]}, 2], "d": {"e": 3}}"#.to_owned(),
+        )
+        .unwrap();
+        assert_eq!(expected.1, fj.1);
+        assert_eq!(
+            format_without_source_ranges(&expected.0),
+            format_without_source_ranges(&fj.0)
+        );
+        assert_eq!(expected.2, fj.2);
+        // The new value came from the string, which is what's in the input.
+        assert_eq!(Some(20..37), fj[4].source_range);
+        assert_eq!(None, fj[5].source_range);
+
+        // And back again.
+        fj.replace_value(4, parse_top_level_json(string).unwrap());
+        let expected = parse_top_level_json(JSON.to_owned()).unwrap();
+        assert_eq!(expected.1, fj.1);
+        assert_eq!(format!("{:?}", expected.0), format!("{:?}", fj.0));
+
+        // Replacing the top-level value.
+        fj.replace_value(0, parse_top_level_json("[]".to_owned()).unwrap());
+        assert_eq!("[]", fj.1);
+        assert_eq!(1, fj.0.len());
+    }
+
+    #[test]
+    fn test_stats() {
+        let fj = parse_top_level_json(
+            r#"{"a": [1, 2.5, {}], "b": {"c": "d", "e": [true, null]}, "f": []}"#.to_owned(),
+        )
+        .unwrap();
+        let stats = fj.stats();
+        assert!(stats.memory_size >= fj.1.len() + fj.0.len() * std::mem::size_of::<Row>());
+        assert_eq!(
+            stats,
+            Stats {
+                rows: 15,
+                objects: 3,
+                arrays: 3,
+                strings: 1,
+                numbers: 2,
+                booleans: 1,
+                nulls: 1,
+                max_depth: 3,
+                memory_size: stats.memory_size,
+            }
+        );
+    }
+
+    #[test]
+    fn test_num_top_level_values() {
+        let fj = parse_top_level_json(r#"{"a": [1, 2]}"#.to_owned()).unwrap();
+        assert_eq!(fj.num_top_level_values(), 1);
+
+        let fj = parse_top_level_json(r#"{"a": 1} 2 "three" [4, [5]]"#.to_owned()).unwrap();
+        assert_eq!(fj.num_top_level_values(), 4);
+
+        let fj = parse_top_level_json(r#"1 [] {"a": true}"#.to_owned()).unwrap();
+        assert_eq!(fj.num_top_level_values(), 3);
+        assert_eq!(FlatJson(vec![], String::new(), 0).num_top_level_values(), 0);
+    }
+
+    #[test]
+    fn test_filtered() {
+        // 0   1      2       3  4    5      6   7  8  9       10     11 12
+        // { "a": 1, "b": [ 2, { "c": 3 }, 4 ], "d": { "e": 5 } }
+        const JSON: &str = r#"{"a":1,"b":[2,{"c":3},4],"d":{"e":5}}"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        let (filtered, original_indexes) = fj.filtered(&[5, 9]);
+        assert_eq!(r#"{ "b": [{ "c": 3 }], "d": { "e": 5 } }"#, filtered.1);
+        assert_eq!(vec![0, 2, 4, 5, 6, 8, 9, 10, 11, 12], original_indexes);
+
+        // Array elements keep their original index.
+        assert_eq!(1, filtered[2].index);
+        assert_eq!(OptionIndex::Index(1), filtered[2].parent);
+        assert_eq!(OptionIndex::Index(6), filtered[1].next_sibling);
+        assert_eq!(OptionIndex::Nil, filtered[2].next_sibling);
+        assert_eq!(4, filtered[2].pair_index().unwrap());
+
+        // Filtering by a closing delimiter keeps the whole container.
+        let (filtered, _) = fj.filtered(&[11]);
+        assert_eq!(r#"{ "d": { "e": 5 } }"#, filtered.1);
+    }
+
+    #[test]
+    fn test_without_empty_values() {
+        // 0   1       2        3    4     5    6       7 8     9     10
+        // { "a": 1, "b": null, "c": [ null, { "d": null } ], "e": {} }
+        const JSON: &str = r#"{"a":1,"b":null,"c":[null,{"d":null}],"e":{}}"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        // Array elements aren't hidden, but objects whose members are all
+        // hidden are empty.
+        let (hidden, original_indexes) = fj.without_empty_values(false).unwrap();
+        assert_eq!(r#"{ "a": 1, "c": [null, {}], "e": {} }"#, hidden.1);
+        assert_eq!(vec![0, 1, 3, 4, 5, 8, 9, 10], original_indexes);
+        assert!(matches!(hidden[4].value, Value::EmptyObject));
+        assert_eq!(OptionIndex::Index(6), hidden[2].next_sibling);
+        assert_eq!(5, hidden[2].pair_index().unwrap());
+
+        let (hidden, _) = fj.without_empty_values(true).unwrap();
+        assert_eq!(r#"{ "a": 1, "c": [null, {}] }"#, hidden.1);
+
+        // Containers whose members are all hidden are hidden too.
+        let fj = parse_top_level_json(r#"{"a":{"b":{"c":null}},"d":[]}"#.to_owned()).unwrap();
+        let (hidden, _) = fj.without_empty_values(true).unwrap();
+        assert_eq!("{}", hidden.1);
+
+        let fj = parse_top_level_json(r#"[null, {}]"#.to_owned()).unwrap();
+        assert!(fj.without_empty_values(true).is_none());
+    }
+
+    #[test]
+    fn test_is_table() {
+        // 0 1 2       3      4  5 6       7      8 9
+        // [ { "a": 1, "b": 2 }, { "a": 3, "b": 4 } ]
+        const JSON: &str = r#"[{"a":1,"b":2},{"a":3,"b":4}]"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        assert!(fj.is_table(0));
+        assert!(!fj.is_table(1));
+        assert!(!fj.is_table(8));
+
+        let is_table = |json: &str| parse_top_level_json(json.to_owned()).unwrap().is_table(0);
+        // The keys have to be in the same order.
+        assert!(!is_table(r#"[{"a":1,"b":2},{"b":3,"a":4}]"#));
+        assert!(!is_table(r#"[{"a":1,"b":2},{"a":3}]"#));
+        assert!(!is_table(r#"[{"a":1},{"a":2},3]"#));
+        assert!(!is_table(r#"[{"a":1}]"#));
+        assert!(!is_table(r#"[{},{}]"#));
+        assert!(!is_table(r#"[[1],[2]]"#));
+        assert!(is_table(r#"[{"a":{"b":1}},{"a":[]}]"#));
+    }
+
     #[test]
     fn test_pretty_print() {
         const JSON: &str = r#"{"a":1,"b":[2,{},[],false],"c":null}
@@ -1075,4 +2367,31 @@ mod tests {
         const PRETTY_NESTED_OBJ: &str = "{\n  \"8\": false\n}\n";
         assert_eq!(PRETTY_NESTED_OBJ, fj.pretty_printed_value(7).unwrap());
     }
+
+    #[test]
+    fn test_numbers_are_shown_as_written() {
+        // Numbers are never converted to floats, so they keep their original
+        // formatting, and large integers don't lose precision.
+        const JSON: &str = r#"{"b":1.0,"a":[1e3,-0.0,12345678901234567890,1.50E-7]}"#;
+        const PRETTY: &str = r#"{
+  "b": 1.0,
+  "a": [
+    1e3,
+    -0.0,
+    12345678901234567890,
+    1.50E-7
+  ]
+}
+"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        assert_eq!(PRETTY, fj.pretty_printed().unwrap());
+        assert_eq!("1.0", fj.pretty_printed_value(1).unwrap());
+        assert_eq!("12345678901234567890", fj.pretty_printed_value(5).unwrap());
+
+        let (sorted, _) = fj.sorted_by_key();
+        assert_eq!(
+            r#"{ "a": [1e3, -0.0, 12345678901234567890, 1.50E-7], "b": 1.0 }"#,
+            sorted.1
+        );
+    }
 }
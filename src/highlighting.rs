@@ -2,8 +2,8 @@ use std::fmt;
 use std::iter::Peekable;
 use std::ops::Range;
 
+use crate::printable::Printable;
 use crate::search::MatchRangeIter;
-use crate::terminal;
 use crate::terminal::{Style, Terminal};
 use crate::truncatedstrview::TruncatedStrView;
 
@@ -61,6 +61,9 @@ use crate::truncatedstrview::TruncatedStrView;
 //
 //    Container    |      Gray       |     Default     | Inverted Gray  |        Inverted
 //     Previews
+//
+// The colors above are the defaults; styles that use colors come from the
+// Theme, so they can be customized. The styles below don't use colors.
 
 pub const DEFAULT_STYLE: Style = Style::default();
 
@@ -75,37 +78,11 @@ pub const BOLD_INVERTED_STYLE: Style = Style {
     ..Style::default()
 };
 
-pub const GRAY_INVERTED_STYLE: Style = Style {
-    fg: terminal::LIGHT_BLACK,
-    inverted: true,
-    ..Style::default()
-};
-
-pub const SEARCH_MATCH_HIGHLIGHTED: Style = Style {
-    fg: terminal::YELLOW,
-    inverted: true,
-    ..Style::default()
-};
-
 pub const DIMMED_STYLE: Style = Style {
     dimmed: true,
     ..Style::default()
 };
 
-pub const PREVIEW_STYLES: (&Style, &Style) = (&DIMMED_STYLE, &GRAY_INVERTED_STYLE);
-
-pub const BLUE_STYLE: Style = Style {
-    fg: terminal::LIGHT_BLUE,
-    ..Style::default()
-};
-
-pub const INVERTED_BOLD_BLUE_STYLE: Style = Style {
-    bg: terminal::BLUE,
-    inverted: true,
-    bold: true,
-    ..Style::default()
-};
-
 #[allow(clippy::too_many_arguments)]
 pub fn highlight_truncated_str_view<'a>(
     out: &mut dyn Terminal,
@@ -116,11 +93,35 @@ pub fn highlight_truncated_str_view<'a>(
     highlight_style: &Style,
     matches_iter: &mut Option<&mut Peekable<MatchRangeIter<'a>>>,
     focused_search_match: &Range<usize>,
+    ellipsis: char,
 ) -> fmt::Result {
     let mut leading_ellipsis = false;
     let mut replacement_character = false;
     let mut trailing_ellipsis = false;
 
+    if let Some((hidden_start, hidden_end)) = str_view.range.and_then(|tr| tr.elided_middle) {
+        highlight_matches(
+            out,
+            &s[..hidden_start],
+            str_range_start,
+            style,
+            highlight_style,
+            matches_iter,
+            focused_search_match,
+        )?;
+        out.set_style(&DIMMED_STYLE)?;
+        out.write_char(ellipsis)?;
+        return highlight_matches(
+            out,
+            &s[hidden_end..],
+            str_range_start.map(|start| start + hidden_end),
+            style,
+            highlight_style,
+            matches_iter,
+            focused_search_match,
+        );
+    }
+
     if let Some(tr) = str_view.range {
         leading_ellipsis = tr.print_leading_ellipsis();
         replacement_character = tr.showing_replacement_character;
@@ -131,7 +132,7 @@ pub fn highlight_truncated_str_view<'a>(
 
     if leading_ellipsis {
         out.set_style(&DIMMED_STYLE)?;
-        out.write_char('…')?;
+        out.write_char(ellipsis)?;
     }
 
     // Print replacement character
@@ -157,7 +158,7 @@ pub fn highlight_truncated_str_view<'a>(
     // Print trailing ellipsis
     if trailing_ellipsis {
         out.set_style(&DIMMED_STYLE)?;
-        out.write_char('…')?;
+        out.write_char(ellipsis)?;
     }
 
     Ok(())
@@ -174,7 +175,7 @@ pub fn highlight_matches<'a>(
 ) -> fmt::Result {
     if str_range_start.is_none() {
         out.set_style(style)?;
-        write!(out, "{}", s)?;
+        write!(out, "{}", Printable(s))?;
         return Ok(());
     }
 
@@ -205,7 +206,7 @@ pub fn highlight_matches<'a>(
         if start_index < match_start {
             let print_end = match_start - start_index;
             out.set_style(style)?;
-            write!(out, "{}", &s[..print_end])?;
+            write!(out, "{}", Printable(&s[..print_end]))?;
         }
 
         // Highlight the matching substring.
@@ -217,7 +218,7 @@ pub fn highlight_matches<'a>(
             }
             let print_start = match_start - start_index;
             let print_end = match_end - start_index;
-            write!(out, "{}", &s[print_start..print_end])?;
+            write!(out, "{}", Printable(&s[print_start..print_end]))?;
         }
 
         // Update start_index and s
@@ -1,19 +1,63 @@
 use signal_hook::consts::SIGWINCH;
 use signal_hook::low_level::pipe;
-use termion::event::{parse_event, Event, Key, MouseEvent};
+use termion::event::{parse_event, Event, Key, MouseButton, MouseEvent};
 
+use std::fs::{File, OpenOptions};
 use std::io;
-use std::io::{stdin, Read, Stdin};
-use std::os::unix::io::AsRawFd;
+use std::io::{stdin, Read, Stdin, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::os::unix::net::UnixStream;
+use std::time::{Duration, Instant};
+
+use crate::keybindings::{BoundKey, MacroStep};
 
 const POLL_INFINITE_TIMEOUT: i32 = -1;
 const SIGWINCH_PIPE_INDEX: usize = 0;
-const BUFFER_SIZE: usize = 1024;
+const STDIN_INDEX: usize = 1;
 
 const ESCAPE: u8 = 0o33;
 
-pub fn remap_dev_tty_to_stdin() {
+const LINES_PER_WHEEL_EVENT: i32 = 3;
+
+// How much of a partially read escape sequence to buffer while waiting for
+// the rest of it (see BufferedInput::read_rest_of_partial_sequence).
+const MAX_PARTIAL_SEQUENCE_LEN: usize = 64;
+
+// When bracketed paste mode is enabled, terminals will surround pasted
+// text with these sequences so that it can be distinguished from typed
+// input.
+const BRACKETED_PASTE_START: &[u8] = b"\x1b[200~";
+const BRACKETED_PASTE_END: &[u8] = b"\x1b[201~";
+
+pub const ENABLE_BRACKETED_PASTE: &str = "\x1b[?2004h";
+pub const DISABLE_BRACKETED_PASTE: &str = "\x1b[?2004l";
+
+// When focus reporting is enabled, the terminal sends these sequences
+// when the window gains or loses focus.
+const FOCUS_GAINED: &[u8] = b"\x1b[I";
+const FOCUS_LOST: &[u8] = b"\x1b[O";
+const ENABLE_FOCUS_REPORTING: &str = "\x1b[?1004h";
+const DISABLE_FOCUS_REPORTING: &str = "\x1b[?1004l";
+
+// Enable/disable reporting mouse clicks, drags, and wheel events (the
+// same sequences as termion's MouseTerminal). While mouse reporting is
+// enabled, the terminal doesn't handle selecting text itself, but most
+// terminals (e.g., xterm, GNOME Terminal, Konsole, Alacritty, kitty,
+// WezTerm, and Windows Terminal) still do when Shift is held, as does tmux
+// when its mouse mode is on. iTerm2 uses Option instead, and Terminal.app
+// uses Fn. For other terminals, mouse reporting can be toggled off.
+pub const ENABLE_MOUSE_REPORTING: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
+pub const DISABLE_MOUSE_REPORTING: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l";
+
+// Push/pop the "disambiguate escape codes" enhancement of the kitty
+// keyboard protocol. Terminals that don't support the protocol ignore
+// these, and continue sending the legacy sequences that termion parses.
+//
+// https://sw.kovidgoyal.net/kitty/keyboard-protocol/
+pub const ENABLE_KITTY_KEYBOARD: &str = "\x1b[>1u";
+pub const DISABLE_KITTY_KEYBOARD: &str = "\x1b[<u";
+
+pub fn remap_dev_tty_to_stdin() -> io::Result<()> {
     // The readline library we use, rustyline, always gets its input from STDIN.
     // If jless accepts its input from STDIN, then rustyline can't accept input.
     // To fix this, we open up /dev/tty, and remap it to STDIN, as suggested in
@@ -28,21 +72,429 @@ pub fn remap_dev_tty_to_stdin() {
         // freopen(3) docs: https://linux.die.net/man/3/freopen
         let filename = std::ffi::CString::new("/dev/tty").unwrap();
         let path = std::ffi::CString::new("r").unwrap();
-        let _ = libc::freopen(filename.as_ptr(), path.as_ptr(), libc_stdhandle::stdin());
+        if libc::freopen(filename.as_ptr(), path.as_ptr(), libc_stdhandle::stdin()).is_null() {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+// Remaps /dev/tty to STDOUT, so that jless can be shown on the terminal
+// even while STDOUT is piped to another command, and returns the original
+// STDOUT.
+pub fn remap_dev_tty_to_stdout() -> io::Result<File> {
+    let tty = OpenOptions::new().write(true).open("/dev/tty")?;
+
+    unsafe {
+        let original = libc::dup(libc::STDOUT_FILENO);
+        if original < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let original = File::from_raw_fd(original);
+
+        if libc::dup2(tty.as_raw_fd(), libc::STDOUT_FILENO) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(original)
     }
 }
 
-pub fn get_input() -> impl Iterator<Item = io::Result<TuiEvent>> {
-    let (sigwinch_read, sigwinch_write) = UnixStream::pair().unwrap();
+// If tick_ms is set, a TuiEvent::Tick will be emitted whenever that many
+// milliseconds pass without any other input.
+pub fn get_input(
+    esc_delay_ms: u16,
+    double_click_interval: Duration,
+    buffer_size: usize,
+    tick_ms: Option<u16>,
+) -> io::Result<impl Iterator<Item = io::Result<TuiEvent>>> {
+    let (sigwinch_read, sigwinch_write) = UnixStream::pair()?;
     // NOTE: This overrides the SIGWINCH handler registered by rustyline.
     // We should maybe get a reference to the existing signal handler
     // and call it when appropriate, but it seems to only be used to handle
     // line wrapping, and it seems to work fine without it.
-    pipe::register(SIGWINCH, sigwinch_write).unwrap();
-    TuiInput::new(stdin(), sigwinch_read)
+    pipe::register(SIGWINCH, sigwinch_write)?;
+
+    let buffer_size = buffer_size.max(1);
+
+    Ok(match tick_ms {
+        Some(tick_ms) => TuiInput::with_tick(
+            stdin(),
+            sigwinch_read,
+            esc_delay_ms,
+            double_click_interval,
+            buffer_size,
+            tick_ms,
+        ),
+        None => TuiInput::new(
+            stdin(),
+            sigwinch_read,
+            esc_delay_ms,
+            double_click_interval,
+            buffer_size,
+        ),
+    })
+}
+
+/// A terminal that enables bracketed paste mode while it is alive, and
+/// disables it when it is dropped. This mirrors termion's MouseTerminal.
+pub struct BracketedPasteTerminal<W: Write> {
+    term: W,
+}
+
+impl<W: Write> From<W> for BracketedPasteTerminal<W> {
+    fn from(mut from: W) -> BracketedPasteTerminal<W> {
+        from.write_all(ENABLE_BRACKETED_PASTE.as_bytes()).unwrap();
+
+        BracketedPasteTerminal { term: from }
+    }
+}
+
+impl<W: Write> Drop for BracketedPasteTerminal<W> {
+    fn drop(&mut self) {
+        let _ = self.term.write_all(DISABLE_BRACKETED_PASTE.as_bytes());
+    }
+}
+
+impl<W: Write> Write for BracketedPasteTerminal<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.term.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.term.flush()
+    }
+}
+
+/// A terminal that enables focus reporting while it is alive, and
+/// disables it when it is dropped.
+pub struct FocusReportingTerminal<W: Write> {
+    term: W,
+}
+
+impl<W: Write> From<W> for FocusReportingTerminal<W> {
+    fn from(mut from: W) -> FocusReportingTerminal<W> {
+        from.write_all(ENABLE_FOCUS_REPORTING.as_bytes()).unwrap();
+        FocusReportingTerminal { term: from }
+    }
+}
+
+impl<W: Write> Drop for FocusReportingTerminal<W> {
+    fn drop(&mut self) {
+        let _ = self.term.write_all(DISABLE_FOCUS_REPORTING.as_bytes());
+    }
+}
+
+impl<W: Write> Write for FocusReportingTerminal<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.term.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.term.flush()
+    }
+}
+
+/// A terminal that enables the kitty keyboard protocol while it is
+/// alive, if requested, and disables it when it is dropped.
+pub struct KittyKeyboardTerminal<W: Write> {
+    term: W,
+    enabled: bool,
+}
+
+impl<W: Write> KittyKeyboardTerminal<W> {
+    pub fn new(mut term: W, enabled: bool) -> KittyKeyboardTerminal<W> {
+        if enabled {
+            term.write_all(ENABLE_KITTY_KEYBOARD.as_bytes()).unwrap();
+        }
+        KittyKeyboardTerminal { term, enabled }
+    }
+}
+
+impl<W: Write> Drop for KittyKeyboardTerminal<W> {
+    fn drop(&mut self) {
+        if self.enabled {
+            let _ = self.term.write_all(DISABLE_KITTY_KEYBOARD.as_bytes());
+        }
+    }
+}
+
+impl<W: Write> Write for KittyKeyboardTerminal<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.term.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.term.flush()
+    }
+}
+
+/// A terminal that enables mouse reporting (unless it's disabled via
+/// --no-mouse) while it is alive, and disables it when it is dropped.
+/// Unlike termion's MouseTerminal, mouse reporting may be toggled while
+/// it's alive (see ScreenWriter::set_mouse_reporting).
+pub struct MouseReportingTerminal<W: Write> {
+    term: W,
+}
+
+impl<W: Write> MouseReportingTerminal<W> {
+    pub fn new(mut term: W, enabled: bool) -> MouseReportingTerminal<W> {
+        if enabled {
+            term.write_all(ENABLE_MOUSE_REPORTING.as_bytes()).unwrap();
+        }
+        MouseReportingTerminal { term }
+    }
+}
+
+impl<W: Write> Drop for MouseReportingTerminal<W> {
+    fn drop(&mut self) {
+        let _ = self.term.write_all(DISABLE_MOUSE_REPORTING.as_bytes());
+    }
+}
+
+impl<W: Write> Write for MouseReportingTerminal<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.term.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.term.flush()
+    }
+}
+
+/// Modifiers reported alongside a key by the kitty keyboard protocol.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct KeyModifiers(u8);
+
+impl KeyModifiers {
+    pub const NONE: KeyModifiers = KeyModifiers(0);
+    pub const SHIFT: KeyModifiers = KeyModifiers(0b1);
+    pub const ALT: KeyModifiers = KeyModifiers(0b10);
+    pub const CTRL: KeyModifiers = KeyModifiers(0b100);
+    // Caps Lock and Num Lock are also reported, but we don't want them
+    // to affect how keys are interpreted.
+    const LOCK_KEYS: u8 = 0b1100_0000;
+
+    pub fn contains(self, modifiers: KeyModifiers) -> bool {
+        self.0 & modifiers.0 == modifiers.0
+    }
+
+    pub fn union(self, modifiers: KeyModifiers) -> KeyModifiers {
+        KeyModifiers(self.0 | modifiers.0)
+    }
+
+    fn without_lock_keys(self) -> KeyModifiers {
+        KeyModifiers(self.0 & !KeyModifiers::LOCK_KEYS)
+    }
+}
+
+// Parses a CSI u sequence ("\x1b[<code>;<modifiers>u") sent by terminals
+// using the kitty keyboard protocol. Returns the length of the sequence,
+// the key code, and the modifiers.
+fn parse_csi_u(bytes: &[u8]) -> Option<(usize, u32, KeyModifiers)> {
+    let params = bytes.strip_prefix(b"\x1b[")?;
+    let params_len = params
+        .iter()
+        .position(|&b| !(b.is_ascii_digit() || b == b';' || b == b':'))?;
+    if params[params_len] != b'u' {
+        return None;
+    }
+
+    let params = std::str::from_utf8(&params[..params_len]).ok()?;
+    let mut fields = params.split(';');
+    // Each field may contain extra sub-fields separated by ':' (alternate
+    // key codes, event types), but we only care about the first one.
+    let code = fields.next()?.split(':').next()?.parse::<u32>().ok()?;
+    let modifiers = match fields.next() {
+        // Modifiers are encoded as 1 + a bit field.
+        Some(field) => field
+            .split(':')
+            .next()?
+            .parse::<u16>()
+            .ok()?
+            .saturating_sub(1) as u8,
+        None => 0,
+    };
+
+    Some((2 + params_len + 1, code, KeyModifiers(modifiers)))
+}
+
+// Converts a key reported by the kitty keyboard protocol into a regular
+// KeyEvent when termion's Key can represent it, and a ModifiedKeyEvent
+// otherwise.
+fn kitty_key_event(code: u32, modifiers: KeyModifiers) -> Option<TuiEvent> {
+    let key = match code {
+        9 => Key::Char('\t'),
+        13 => Key::Char('\n'),
+        27 => Key::Esc,
+        127 => Key::Backspace,
+        // Functional keys (e.g., keypad keys) are reported using code
+        // points in the Unicode Private Use Area; we don't handle them.
+        57344..=63743 => return None,
+        _ => Key::Char(std::char::from_u32(code)?),
+    };
+
+    let event = match modified_key(key, modifiers) {
+        BoundKey::Key(key) => TuiEvent::KeyEvent(key),
+        BoundKey::Modified(key, modifiers) => TuiEvent::ModifiedKeyEvent(key, modifiers),
+    };
+
+    Some(event)
+}
+
+/// A key pressed along with some modifiers, as a regular Key when
+/// termion's Key can represent it, e.g., Key::Ctrl('x') for Ctrl-X, and
+/// along with the modifiers otherwise, e.g., for Shift-Enter.
+pub fn modified_key(key: Key, modifiers: KeyModifiers) -> BoundKey {
+    match (key, modifiers.without_lock_keys()) {
+        (key, KeyModifiers::NONE) => BoundKey::Key(key),
+        (Key::Char('\t'), KeyModifiers::SHIFT) => BoundKey::Key(Key::BackTab),
+        (Key::Char(c), KeyModifiers::SHIFT) if !c.is_control() => {
+            let mut upper = c.to_uppercase();
+            match (upper.next(), upper.next()) {
+                (Some(upper), None) => BoundKey::Key(Key::Char(upper)),
+                _ => BoundKey::Modified(key, KeyModifiers::SHIFT),
+            }
+        }
+        (Key::Char(c), KeyModifiers::CTRL) if !c.is_control() => BoundKey::Key(Key::Ctrl(c)),
+        (Key::Char(c), KeyModifiers::ALT) if !c.is_control() => BoundKey::Key(Key::Alt(c)),
+        (key, modifiers) => BoundKey::Modified(key, modifiers),
+    }
+}
+
+/// Responses to queries sent to the terminal.
+#[derive(Debug, PartialEq, Eq)]
+// Nothing sends these queries yet.
+#[allow(dead_code)]
+pub enum TerminalReport {
+    /// A cursor position report ("\x1b[<row>;<col>R"), sent in
+    /// response to "\x1b[6n".
+    CursorPosition { row: u16, col: u16 },
+    /// A response to a window manipulation query ("\x1b[<params>t"),
+    /// e.g., "\x1b[4;<height>;<width>t" in response to "\x1b[14t".
+    WindowOps(Vec<u16>),
+}
+
+// Parses a CSI sequence consisting of only numeric parameters separated
+// by ';', and ending with the given final byte. Returns the length of the
+// sequence and the parameters.
+fn parse_csi_numeric_params(bytes: &[u8], final_byte: u8) -> Option<(usize, Vec<u16>)> {
+    let params = bytes.strip_prefix(b"\x1b[")?;
+    let params_len = params
+        .iter()
+        .position(|&b| !(b.is_ascii_digit() || b == b';'))?;
+    if params_len == 0 || params[params_len] != final_byte {
+        return None;
+    }
+
+    let params = std::str::from_utf8(&params[..params_len])
+        .ok()?
+        .split(';')
+        .map(|param| param.parse::<u16>().ok())
+        .collect::<Option<Vec<u16>>>()?;
+
+    Some((2 + params_len + 1, params))
+}
+
+fn parse_terminal_report(bytes: &[u8]) -> Option<(usize, TerminalReport)> {
+    if let Some((len, params)) = parse_csi_numeric_params(bytes, b'R') {
+        // NOTE: This is ambiguous with Shift/Ctrl/Alt-F3 in some terminals
+        // ("\x1b[1;2R"), but those aren't bound to anything.
+        if let [row, col] = params[..] {
+            return Some((len, TerminalReport::CursorPosition { row, col }));
+        }
+    }
+
+    if let Some((len, params)) = parse_csi_numeric_params(bytes, b't') {
+        return Some((len, TerminalReport::WindowOps(params)));
+    }
+
+    None
+}
+
+// Whether the bytes could be the start of one of the escape sequences that
+// are matched as a whole, rather than being parsed byte by byte, i.e., focus
+// events, bracketed pastes, terminal reports, and CSI u sequences.
+fn is_partial_sequence(bytes: &[u8]) -> bool {
+    if bytes.is_empty() {
+        return false;
+    }
+    if [FOCUS_GAINED, FOCUS_LOST, BRACKETED_PASTE_START]
+        .iter()
+        .any(|sequence| sequence.len() > bytes.len() && sequence.starts_with(bytes))
+    {
+        return true;
+    }
+
+    // A CSI sequence that hasn't reached its final byte yet.
+    match bytes.strip_prefix(b"\x1b[") {
+        Some(params) => params
+            .iter()
+            .all(|&b| b.is_ascii_digit() || b == b';' || b == b':'),
+        None => false,
+    }
+}
+
+/// Parses input that would be read from the terminal into events, e.g.,
+/// the keys passed to jless::render. Unlike when reading from the
+/// terminal, an Escape followed by another key is always treated as Alt
+/// plus that key.
+pub fn events_from_bytes(bytes: &[u8]) -> Vec<TuiEvent> {
+    let mut bytes = bytes.iter().map(|&b| Ok(b));
+    let mut events = vec![];
+    while let Some(Ok(byte)) = bytes.next() {
+        // Like when reading from the terminal, ESC on its own is the escape
+        // key, rather than the start of an escape sequence.
+        if byte == b'\x1b' && bytes.len() == 0 {
+            events.push(TuiEvent::KeyEvent(Key::Esc));
+            break;
+        }
+        events.push(match parse_event(byte, &mut bytes) {
+            Ok(Event::Key(key)) => TuiEvent::KeyEvent(key),
+            Ok(Event::Mouse(m)) => match wheel_event_lines(&m) {
+                Some(lines) => TuiEvent::Scroll { lines },
+                None => TuiEvent::MouseEvent(m),
+            },
+            Ok(Event::Unsupported(bytes)) => TuiEvent::Unknown(bytes),
+            Err(_) => continue,
+        });
+    }
+    events
+}
+
+// Returns how many lines a mouse event scrolls, if it's a wheel event.
+fn wheel_event_lines(mouse_event: &MouseEvent) -> Option<i32> {
+    match mouse_event {
+        MouseEvent::Press(MouseButton::WheelUp, _, _) => Some(-LINES_PER_WHEEL_EVENT),
+        MouseEvent::Press(MouseButton::WheelDown, _, _) => Some(LINES_PER_WHEEL_EVENT),
+        _ => None,
+    }
 }
 
-fn read_and_retry_on_interrupt(input: &mut Stdin, buf: &mut [u8]) -> io::Result<usize> {
+// Returns whether any of the file descriptors are ready to be read.
+fn poll_and_retry_on_interrupt(poll_fds: &mut [libc::pollfd], timeout: i32) -> io::Result<bool> {
+    loop {
+        match unsafe {
+            libc::poll(
+                poll_fds.as_mut_ptr(),
+                poll_fds.len() as libc::nfds_t,
+                timeout,
+            )
+        } {
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+                // Try poll again.
+            }
+            0 => return Ok(false),
+            _ => return Ok(true),
+        }
+    }
+}
+
+fn read_and_retry_on_interrupt<R: Read>(input: &mut R, buf: &mut [u8]) -> io::Result<usize> {
     loop {
         match input.read(buf) {
             res @ Ok(_) => {
@@ -58,19 +510,19 @@ fn read_and_retry_on_interrupt(input: &mut Stdin, buf: &mut [u8]) -> io::Result<
     }
 }
 
-struct BufferedInput<const N: usize> {
-    input: Stdin,
-    buffer: [u8; N],
+struct BufferedInput<R: Read> {
+    input: R,
+    buffer: Vec<u8>,
     buffer_size: usize,
     buffer_index: usize,
     might_have_more_data: bool,
 }
 
-impl<const N: usize> BufferedInput<N> {
-    fn new(input: Stdin) -> BufferedInput<N> {
+impl<R: Read> BufferedInput<R> {
+    fn new(input: R, buffer_size: usize) -> BufferedInput<R> {
         BufferedInput {
             input,
-            buffer: [0; N],
+            buffer: vec![0; buffer_size],
             buffer_size: 0,
             buffer_index: 0,
             might_have_more_data: false,
@@ -108,8 +560,12 @@ impl<const N: usize> BufferedInput<N> {
         self.buffer_index < self.buffer_size
     }
 
+    fn is_pure_escape(&self) -> bool {
+        self.buffer_index == 0 && self.buffer_size == 1 && self.buffer[0] == ESCAPE
+    }
+
     fn take_pure_escape(&mut self) -> bool {
-        if self.buffer_index == 0 && self.buffer_size == 1 && self.buffer[0] == ESCAPE {
+        if self.is_pure_escape() {
             // This will set self.might_have_more_data = true, which is fine,
             // because that only gets set to true when buffer_size == N, but
             // we just checked that it is 1 and not N.
@@ -120,6 +576,117 @@ impl<const N: usize> BufferedInput<N> {
         false
     }
 
+    // When the last read filled the buffer, an escape sequence at the end
+    // of it may have been split across reads, so this reads the rest of it,
+    // moving it to the start of the buffer (and growing the buffer if it's
+    // too small to hold it) so that it can be matched as a whole. More input
+    // is only read if more_input_ready returns true.
+    fn read_rest_of_partial_sequence(
+        &mut self,
+        mut more_input_ready: impl FnMut() -> io::Result<bool>,
+    ) -> Option<io::Error> {
+        while self.might_have_more_data
+            && self.buffer_size - self.buffer_index < MAX_PARTIAL_SEQUENCE_LEN
+            && is_partial_sequence(&self.buffer[self.buffer_index..self.buffer_size])
+        {
+            match more_input_ready() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(err) => return Some(err),
+            }
+
+            self.buffer
+                .copy_within(self.buffer_index..self.buffer_size, 0);
+            self.buffer_size -= self.buffer_index;
+            self.buffer_index = 0;
+            if self.buffer_size == self.buffer.len() {
+                self.buffer.resize(self.buffer.len() * 2, 0);
+            }
+
+            if let Some(err) = self.read_more_into_buffer() {
+                return Some(err);
+            }
+        }
+
+        None
+    }
+
+    fn take_csi_u_sequence(&mut self) -> Option<(u32, KeyModifiers)> {
+        let buffered = &self.buffer[self.buffer_index..self.buffer_size];
+        let (len, code, modifiers) = parse_csi_u(buffered)?;
+        self.buffer_index += len;
+        Some((code, modifiers))
+    }
+
+    fn take_terminal_report(&mut self) -> Option<TerminalReport> {
+        let buffered = &self.buffer[self.buffer_index..self.buffer_size];
+        let (len, report) = parse_terminal_report(buffered)?;
+        self.buffer_index += len;
+        Some(report)
+    }
+
+    // If the next event in the buffer is a mouse wheel event, consumes it
+    // and returns how many lines it scrolls. This never reads more input,
+    // so it only looks at events that have already been read.
+    fn take_buffered_wheel_event(&mut self) -> Option<i32> {
+        let buffered = &self.buffer[self.buffer_index..self.buffer_size];
+        let mut bytes = buffered.iter().map(|&b| Ok(b));
+        let first_byte = bytes.next()?.ok()?;
+        let lines = match parse_event(first_byte, &mut bytes) {
+            Ok(Event::Mouse(mouse_event)) => wheel_event_lines(&mouse_event)?,
+            _ => return None,
+        };
+
+        self.buffer_index += buffered.len() - bytes.len();
+        Some(lines)
+    }
+
+    fn take_sequence(&mut self, sequence: &[u8]) -> bool {
+        let buffered = &self.buffer[self.buffer_index..self.buffer_size];
+        if buffered.starts_with(sequence) {
+            self.buffer_index += sequence.len();
+            return true;
+        }
+
+        false
+    }
+
+    // Reads the contents of a bracketed paste, after the start marker has
+    // already been consumed. Pasted text may be much longer than our
+    // buffer, so we keep reading until we see the end marker (or EOF).
+    fn read_bracketed_paste(&mut self) -> io::Result<Vec<u8>> {
+        let mut pasted = vec![];
+
+        while !pasted.ends_with(BRACKETED_PASTE_END) {
+            if let Some(err) = self.read_more_if_needed() {
+                return Err(err);
+            }
+
+            // EOF before the end of the paste.
+            if !self.has_buffered_data() {
+                return Ok(pasted);
+            }
+
+            pasted.push(self.next_u8());
+        }
+
+        pasted.truncate(pasted.len() - BRACKETED_PASTE_END.len());
+        Ok(pasted)
+    }
+
+    // Read more data into the buffer without discarding the data that is
+    // already in there.
+    fn read_more_into_buffer(&mut self) -> Option<io::Error> {
+        match read_and_retry_on_interrupt(&mut self.input, &mut self.buffer[self.buffer_size..]) {
+            Ok(bytes_read) => {
+                self.buffer_size += bytes_read;
+                self.might_have_more_data = self.buffer_size == self.buffer.len();
+                None
+            }
+            Err(err) => Some(err),
+        }
+    }
+
     fn read_more_if_needed(&mut self) -> Option<io::Error> {
         if self.has_buffered_data() {
             return None;
@@ -130,7 +697,7 @@ impl<const N: usize> BufferedInput<N> {
         match read_and_retry_on_interrupt(&mut self.input, &mut self.buffer) {
             Ok(bytes_read) => {
                 self.buffer_size = bytes_read;
-                self.might_have_more_data = bytes_read == N;
+                self.might_have_more_data = bytes_read == self.buffer.len();
                 None
             }
             Err(err) => Some(err),
@@ -138,10 +705,18 @@ impl<const N: usize> BufferedInput<N> {
     }
 }
 
-impl<const N: usize> Iterator for BufferedInput<N> {
+impl<R: Read> Iterator for BufferedInput<R> {
     type Item = io::Result<u8>;
 
     fn next(&mut self) -> Option<io::Result<u8>> {
+        // An escape sequence may be split across multiple reads, so if
+        // we filled the buffer last time, try reading more.
+        if !self.has_buffered_data() && self.might_have_more_data {
+            if let Some(err) = self.read_more_if_needed() {
+                return Some(Err(err));
+            }
+        }
+
         if !self.has_buffered_data() {
             return None;
         }
@@ -153,11 +728,26 @@ impl<const N: usize> Iterator for BufferedInput<N> {
 struct TuiInput {
     poll_fds: [libc::pollfd; 2],
     sigwinch_pipe: UnixStream,
-    buffered_input: BufferedInput<BUFFER_SIZE>,
+    buffered_input: BufferedInput<Stdin>,
+    // How long to wait after receiving a lone ESC to see if it's actually
+    // the start of a longer sequence, e.g., an Alt-modified key.
+    esc_delay_ms: u16,
+    // The time and position (col, row) of the last left click, so that
+    // we can detect double clicks.
+    last_click: Option<(Instant, u16, u16)>,
+    double_click_interval: Duration,
+    // How long to wait for input before emitting a Tick event.
+    poll_timeout: i32,
 }
 
 impl TuiInput {
-    fn new(input: Stdin, sigwinch_pipe: UnixStream) -> TuiInput {
+    fn new(
+        input: Stdin,
+        sigwinch_pipe: UnixStream,
+        esc_delay_ms: u16,
+        double_click_interval: Duration,
+        buffer_size: usize,
+    ) -> TuiInput {
         let sigwinch_fd = sigwinch_pipe.as_raw_fd();
         let stdin_fd = input.as_raw_fd();
 
@@ -177,8 +767,52 @@ impl TuiInput {
         TuiInput {
             poll_fds,
             sigwinch_pipe,
-            buffered_input: BufferedInput::new(input),
+            buffered_input: BufferedInput::new(input, buffer_size),
+            esc_delay_ms,
+            last_click: None,
+            double_click_interval,
+            poll_timeout: POLL_INFINITE_TIMEOUT,
+        }
+    }
+
+    // Like new, but emits a TuiEvent::Tick every tick_ms milliseconds
+    // that pass without any input arriving.
+    fn with_tick(
+        input: Stdin,
+        sigwinch_pipe: UnixStream,
+        esc_delay_ms: u16,
+        double_click_interval: Duration,
+        buffer_size: usize,
+        tick_ms: u16,
+    ) -> TuiInput {
+        let mut tui_input = TuiInput::new(
+            input,
+            sigwinch_pipe,
+            esc_delay_ms,
+            double_click_interval,
+            buffer_size,
+        );
+        tui_input.poll_timeout = tick_ms as i32;
+        tui_input
+    }
+
+    // Converts a second left click on the same cell within the double
+    // click interval into a DoubleClick event. A third click will be
+    // treated as a single click again.
+    fn detect_double_click(&mut self, mouse_event: MouseEvent) -> TuiEvent {
+        if let MouseEvent::Press(MouseButton::Left, col, row) = mouse_event {
+            let now = Instant::now();
+            if let Some((last_time, last_col, last_row)) = self.last_click.take() {
+                if (last_col, last_row) == (col, row)
+                    && now.duration_since(last_time) <= self.double_click_interval
+                {
+                    return TuiEvent::DoubleClick { row, col };
+                }
+            }
+            self.last_click = Some((now, col, row));
         }
+
+        TuiEvent::MouseEvent(mouse_event)
     }
 
     fn get_event_from_buffered_input(&mut self) -> Option<io::Result<TuiEvent>> {
@@ -188,14 +822,86 @@ impl TuiInput {
             }
         }
 
+        // Don't wait for the rest of a split sequence any longer than we
+        // would for the rest of one following an ESC.
+        let stdin_poll_fd = &mut self.poll_fds[STDIN_INDEX..];
+        let esc_delay_ms = self.esc_delay_ms as i32;
+        let more_input_ready = || poll_and_retry_on_interrupt(stdin_poll_fd, esc_delay_ms);
+        if let Some(err) = self
+            .buffered_input
+            .read_rest_of_partial_sequence(more_input_ready)
+        {
+            return Some(Err(err));
+        }
+
+        // Over slow connections, the bytes following an ESC may arrive
+        // later, so wait a bit before deciding that the user actually
+        // just pressed Escape.
+        if self.buffered_input.is_pure_escape() && self.esc_delay_ms > 0 {
+            let stdin_poll_fd = &mut self.poll_fds[STDIN_INDEX..];
+            match poll_and_retry_on_interrupt(stdin_poll_fd, self.esc_delay_ms as i32) {
+                Ok(true) => {
+                    if let Some(err) = self.buffered_input.read_more_into_buffer() {
+                        return Some(Err(err));
+                    }
+                }
+                Ok(false) => {}
+                Err(err) => return Some(Err(err)),
+            }
+        }
+
         if self.buffered_input.take_pure_escape() {
             return Some(Ok(TuiEvent::KeyEvent(Key::Esc)));
         }
 
+        if self.buffered_input.take_sequence(FOCUS_GAINED) {
+            return Some(Ok(TuiEvent::FocusGained));
+        }
+
+        if self.buffered_input.take_sequence(FOCUS_LOST) {
+            return Some(Ok(TuiEvent::FocusLost));
+        }
+
+        if self.buffered_input.take_sequence(BRACKETED_PASTE_START) {
+            return match self.buffered_input.read_bracketed_paste() {
+                Ok(bytes) => Some(Ok(TuiEvent::Paste(
+                    String::from_utf8_lossy(&bytes).into_owned(),
+                ))),
+                Err(err) => Some(Err(err)),
+            };
+        }
+
+        if let Some(report) = self.buffered_input.take_terminal_report() {
+            return Some(Ok(TuiEvent::TerminalReport(report)));
+        }
+
+        let buffer_index_before_csi_u = self.buffered_input.buffer_index;
+        if let Some((code, modifiers)) = self.buffered_input.take_csi_u_sequence() {
+            return match kitty_key_event(code, modifiers) {
+                Some(event) => Some(Ok(event)),
+                None => {
+                    let bytes = &self.buffered_input.buffer
+                        [buffer_index_before_csi_u..self.buffered_input.buffer_index];
+                    Some(Ok(TuiEvent::Unknown(bytes.to_vec())))
+                }
+            };
+        }
+
         match self.buffered_input.next() {
             Some(Ok(byte)) => match parse_event(byte, &mut self.buffered_input) {
                 Ok(Event::Key(k)) => Some(Ok(TuiEvent::KeyEvent(k))),
-                Ok(Event::Mouse(m)) => Some(Ok(TuiEvent::MouseEvent(m))),
+                Ok(Event::Mouse(m)) => match wheel_event_lines(&m) {
+                    // Coalesce consecutive wheel events to avoid redrawing
+                    // the screen for each one when scrolling quickly.
+                    Some(mut lines) => {
+                        while let Some(more_lines) = self.buffered_input.take_buffered_wheel_event()
+                        {
+                            lines += more_lines;
+                        }
+                        Some(Ok(TuiEvent::Scroll { lines }))
+                    }
+                    None => Some(Ok(self.detect_double_click(m))),
+                },
                 Ok(Event::Unsupported(bytes)) => Some(Ok(TuiEvent::Unknown(bytes))),
                 Err(err) => Some(Err(err)),
             },
@@ -213,27 +919,12 @@ impl Iterator for TuiInput {
             return self.get_event_from_buffered_input();
         }
 
-        let poll_res: Option<io::Error>;
-
-        loop {
-            match unsafe { libc::poll(self.poll_fds.as_mut_ptr(), 2, POLL_INFINITE_TIMEOUT) } {
-                -1 => {
-                    let err = io::Error::last_os_error();
-                    if err.kind() != io::ErrorKind::Interrupted {
-                        poll_res = Some(err);
-                        break;
-                    }
-                    // Try poll again.
-                }
-                _ => {
-                    poll_res = None;
-                    break;
-                }
-            };
-        }
-
-        if let Some(poll_err) = poll_res {
-            return Some(Err(poll_err));
+        // We only poll when there's no buffered input, so a timeout can't
+        // occur in the middle of a partially read escape sequence.
+        match poll_and_retry_on_interrupt(&mut self.poll_fds, self.poll_timeout) {
+            Ok(true) => {}
+            Ok(false) => return Some(Ok(TuiEvent::Tick)),
+            Err(poll_err) => return Some(Err(poll_err)),
         }
 
         if self.poll_fds[SIGWINCH_PIPE_INDEX].revents & libc::POLLIN != 0 {
@@ -251,6 +942,204 @@ impl Iterator for TuiInput {
 pub enum TuiEvent {
     WinChEvent,
     KeyEvent(Key),
+    // A key with a combination of modifiers that termion's Key can't
+    // represent, e.g., Shift+Enter. These are only reported when the
+    // kitty keyboard protocol is enabled.
+    ModifiedKeyEvent(Key, KeyModifiers),
     MouseEvent(MouseEvent),
+    DoubleClick {
+        row: u16,
+        // Not currently used by the viewer, which only cares about rows.
+        #[allow(dead_code)]
+        col: u16,
+    },
+    // Net number of lines scrolled by one or more consecutive mouse
+    // wheel events; negative values scroll up.
+    Scroll {
+        lines: i32,
+    },
+    Paste(String),
+    FocusGained,
+    FocusLost,
+    Tick,
+    // Not read from the terminal: a step of a macro being run, which is
+    // handled like the keys bound to the command.
+    MacroStep(MacroStep),
+    #[allow(dead_code)]
+    TerminalReport(TerminalReport),
     Unknown(Vec<u8>),
 }
+
+impl TuiEvent {
+    // The key pressed, for events that can be bound to commands.
+    pub fn bound_key(&self) -> Option<BoundKey> {
+        match self {
+            TuiEvent::KeyEvent(key) => Some(BoundKey::Key(*key)),
+            TuiEvent::ModifiedKeyEvent(key, modifiers) => {
+                Some(BoundKey::Modified(*key, *modifiers))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+
+    // Returns a BufferedInput backed by a file containing the given bytes.
+    fn buffered_input_from_bytes(
+        name: &str,
+        bytes: &[u8],
+        buffer_size: usize,
+    ) -> BufferedInput<File> {
+        let path =
+            std::env::temp_dir().join(format!("jless-input-test-{}-{}", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let file = File::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        BufferedInput::new(file, buffer_size)
+    }
+
+    fn parse_keys(buffered_input: &mut BufferedInput<File>) -> Vec<Key> {
+        let mut keys = vec![];
+
+        assert!(buffered_input.read_more_if_needed().is_none());
+        while let Some(byte) = buffered_input.next() {
+            match parse_event(byte.unwrap(), buffered_input) {
+                Ok(Event::Key(k)) => keys.push(k),
+                event => panic!("Unexpected event: {:?}", event),
+            }
+        }
+
+        keys
+    }
+
+    #[test]
+    fn test_parse_terminal_report() {
+        assert_eq!(
+            Some((7, TerminalReport::CursorPosition { row: 12, col: 3 })),
+            parse_terminal_report(b"\x1b[12;3Rj"),
+        );
+        assert_eq!(
+            Some((12, TerminalReport::WindowOps(vec![4, 600, 800]))),
+            parse_terminal_report(b"\x1b[4;600;800t"),
+        );
+        assert_eq!(None, parse_terminal_report(b"\x1b[A"));
+        assert_eq!(None, parse_terminal_report(b"\x1b[5~"));
+        assert_eq!(None, parse_terminal_report(b"\x1b[Rj"));
+        assert_eq!(None, parse_terminal_report(b"\x1b[1;2;3R"));
+    }
+
+    #[test]
+    fn test_events_from_bytes() {
+        let events = events_from_bytes(b"j\x1b[B\x1bx\r\x1b[<65;10;5M\x1b");
+        assert_eq!(
+            format!("{:?}", events),
+            "[KeyEvent(Char('j')), KeyEvent(Down), KeyEvent(Alt('x')), \
+             KeyEvent(Char('\\n')), Scroll { lines: 3 }, KeyEvent(Esc)]"
+        );
+    }
+
+    #[test]
+    fn test_take_buffered_wheel_event() {
+        // Wheel down, wheel down, wheel up, click, wheel down
+        let mut buffered_input = buffered_input_from_bytes(
+            "wheel",
+            b"\x1b[<65;10;5M\x1b[<65;10;5M\x1b[<64;10;5M\x1b[<0;10;5M\x1b[<65;10;5M",
+            1024,
+        );
+        assert!(buffered_input.read_more_if_needed().is_none());
+
+        assert_eq!(Some(3), buffered_input.take_buffered_wheel_event());
+        assert_eq!(Some(3), buffered_input.take_buffered_wheel_event());
+        assert_eq!(Some(-3), buffered_input.take_buffered_wheel_event());
+        // Click isn't consumed.
+        assert_eq!(None, buffered_input.take_buffered_wheel_event());
+        buffered_input.buffer_index += b"\x1b[<0;10;5M".len();
+        assert_eq!(Some(3), buffered_input.take_buffered_wheel_event());
+        assert!(!buffered_input.has_buffered_data());
+    }
+
+    #[test]
+    fn test_escape_sequences_split_across_reads() {
+        // Read in 4-byte chunks, so that the escape sequences will be split
+        // across multiple reads: "ab\x1b[" "A\x1b[5" "~\x1bO" "Pc"
+        let mut buffered_input = buffered_input_from_bytes("split", b"ab\x1b[A\x1b[5~\x1bOPc", 4);
+
+        assert_eq!(
+            vec![
+                Key::Char('a'),
+                Key::Char('b'),
+                Key::Up,
+                Key::PageUp,
+                Key::F(1),
+                Key::Char('c'),
+            ],
+            parse_keys(&mut buffered_input),
+        );
+    }
+
+    #[test]
+    fn test_whole_sequences_split_across_reads() {
+        // Read in 4-byte chunks, e.g., like with --input-buffer-size 4:
+        // "j\x1b[2" "00~h" "i\x1b[2" "01~\x1b" "[I\x1b[" "97;5" "u\x1b[1" "2;3R"
+        let mut buffered_input = buffered_input_from_bytes(
+            "split-sequences",
+            b"j\x1b[200~hi\x1b[201~\x1b[I\x1b[97;5u\x1b[12;3R",
+            4,
+        );
+        let read_rest = |buffered_input: &mut BufferedInput<File>| {
+            assert!(buffered_input.read_more_if_needed().is_none());
+            assert!(buffered_input
+                .read_rest_of_partial_sequence(|| Ok(true))
+                .is_none());
+        };
+
+        read_rest(&mut buffered_input);
+        assert_eq!(Some(b'j'), buffered_input.next().map(Result::unwrap));
+
+        read_rest(&mut buffered_input);
+        assert!(buffered_input.take_sequence(BRACKETED_PASTE_START));
+        assert_eq!(
+            b"hi".to_vec(),
+            buffered_input.read_bracketed_paste().unwrap()
+        );
+
+        read_rest(&mut buffered_input);
+        assert!(buffered_input.take_sequence(FOCUS_GAINED));
+
+        read_rest(&mut buffered_input);
+        assert_eq!(
+            Some((97, KeyModifiers::CTRL)),
+            buffered_input.take_csi_u_sequence()
+        );
+
+        read_rest(&mut buffered_input);
+        assert_eq!(
+            Some(TerminalReport::CursorPosition { row: 12, col: 3 }),
+            buffered_input.take_terminal_report()
+        );
+        assert!(!buffered_input.has_buffered_data());
+    }
+
+    #[test]
+    fn test_escape_sequences_in_single_read() {
+        let mut buffered_input =
+            buffered_input_from_bytes("single", b"ab\x1b[A\x1b[5~\x1bOPc", 1024);
+
+        assert_eq!(
+            vec![
+                Key::Char('a'),
+                Key::Char('b'),
+                Key::Up,
+                Key::PageUp,
+                Key::F(1),
+                Key::Char('c'),
+            ],
+            parse_keys(&mut buffered_input),
+        );
+    }
+}
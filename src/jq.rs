@@ -0,0 +1,95 @@
+// Support for transforming the input using jq, via the jq binary.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// Runs a jq program over the input, returning its output, which will
+// have one (compact) JSON value per line.
+pub fn run(program: &str, input: &str) -> Result<String, String> {
+    let mut child = Command::new("jq")
+        .arg("--compact-output")
+        .arg(program)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Unable to run jq: {}", err))?;
+
+    // Write the input from another thread so that jq can't block on
+    // writing its output while we're still writing the input.
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_owned();
+    let writer = std::thread::spawn(move || {
+        // If jq exits early (e.g., because the program is invalid),
+        // writing will fail, but the error will be reported below.
+        let _ = stdin.write_all(input.as_bytes());
+    });
+
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("Unable to run jq: {}", err))?;
+    let _ = writer.join();
+
+    if !output.status.success() {
+        return Err(error_message(&output.stderr));
+    }
+
+    let stdout =
+        String::from_utf8(output.stdout).map_err(|_| "jq output is not valid UTF-8".to_owned())?;
+
+    if stdout.trim().is_empty() {
+        return Err("jq produced no output".to_owned());
+    }
+
+    Ok(stdout)
+}
+
+// Converts the error output of jq into a single line that can be shown
+// in the status bar.
+fn error_message(stderr: &[u8]) -> String {
+    let stderr = String::from_utf8_lossy(stderr);
+    let message = stderr
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+    if message.is_empty() {
+        "jq failed".to_owned()
+    } else {
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jq_is_installed() -> bool {
+        Command::new("jq").arg("--version").output().is_ok()
+    }
+
+    #[test]
+    fn test_error_message() {
+        assert_eq!(
+            "jq: error: syntax error jq: 1 compile error",
+            error_message(b"jq: error: syntax error\n  \njq: 1 compile error\n")
+        );
+        assert_eq!("jq failed", error_message(b"\n"));
+    }
+
+    #[test]
+    fn test_run() {
+        if !jq_is_installed() {
+            return;
+        }
+
+        assert_eq!(
+            Ok("1\n[2,3]\n".to_owned()),
+            run(".a, .b", r#"{"a": 1, "b": [2, 3]}"#)
+        );
+        assert_eq!(Err("jq produced no output".to_owned()), run("empty", "{}"));
+        assert!(run(".[", "{}").is_err());
+    }
+}
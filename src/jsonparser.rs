@@ -1,34 +1,297 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::{Duration, Instant};
+
 use logos::{Lexer, Logos};
 
-use crate::flatjson::{ContainerType, Index, OptionIndex, Row, Value};
+use crate::flatjson::{self, ContainerType, Index, OptionIndex, Row, Value};
 use crate::jsontokenizer::JsonToken;
 
 struct JsonParser<'a> {
     tokenizer: Lexer<'a, JsonToken>,
     parents: Vec<Index>,
+    // For the top level, and then each container in parents, the last
+    // child added so far, and the number of children.
+    siblings: Vec<(OptionIndex, usize)>,
+    // The key of the object member that's about to be parsed, and how
+    // many earlier members of the object have the same key.
+    pending_key_range: Option<Range<usize>>,
+    pending_key_occurrence: usize,
+    // The keys seen so far in each of the objects being parsed, for
+    // counting duplicate keys.
+    object_keys: Vec<&'a str>,
     rows: Vec<Row>,
     pretty_printed: String,
     max_depth: usize,
+    // Containers nested more deeply than this are skipped over, and
+    // replaced by a placeholder.
+    depth_limit: usize,
 
     peeked_token: Option<Option<JsonToken>>,
+    // Where the line being parsed starts, when parsing JSON Lines, so that
+    // errors say which column of it they're in.
+    line_start: Option<usize>,
+
+    stream: Option<RowStream>,
 }
 
-pub fn parse(json: String) -> Result<(Vec<Row>, String, usize), String> {
-    let mut parser = JsonParser {
-        tokenizer: JsonToken::lexer(&json),
-        parents: vec![],
-        rows: vec![],
-        pretty_printed: String::new(),
-        max_depth: 0,
-        peeked_token: None,
-    };
+// Rows parsed so far while parsing in the background, along with the
+// pretty printed input that they refer to.
+//
+// The rows may end in the middle of some containers, whose closing rows
+// haven't been parsed yet; their opening rows won't have their close_index
+// or the end of their range set.
+pub struct ParsedRows {
+    pub rows: Vec<Row>,
+    pub pretty_printed: String,
+    pub max_depth: usize,
+    // The containers that haven't been closed yet, outermost first, along
+    // with the last child of each so far.
+    pub open_containers: Vec<(Index, Index)>,
+    // How many bytes of the input have been parsed.
+    pub parsed_bytes: usize,
+}
+
+pub enum ParseEvent {
+    Rows(ParsedRows),
+    Done(Result<(), String>),
+}
+
+// Objects with fewer keys than this are checked for duplicate keys by
+// comparing each key to the earlier ones, which is faster than hashing
+// every key for the small objects that make up most inputs.
+const MAX_SCANNED_KEYS: usize = 16;
+
+// Tokens in errors are cut off after this many characters, since strings
+// can be long.
+const MAX_ERROR_TOKEN_CHARS: usize = 20;
+
+// How often to send rows parsed in the background.
+const STREAM_INTERVAL: Duration = Duration::from_millis(50);
+// Checking the time after every row would slow down parsing.
+const ROWS_PER_TIME_CHECK: usize = 256;
+
+struct RowStream {
+    sender: Sender<ParseEvent>,
+    interval: Duration,
+    sent_rows: usize,
+    sent_len: usize,
+    last_sent: Instant,
+    checkpoints: usize,
+}
+
+pub fn parse(json: String, depth_limit: usize) -> Result<(Vec<Row>, String, usize), String> {
+    let mut parser = JsonParser::new(&json, None, depth_limit);
 
     parser.parse_top_level_json()?;
 
     Ok((parser.rows, parser.pretty_printed, parser.max_depth))
 }
 
+// Parses JSON Lines input, which has a value on each line, as an array of
+// them, with a row for each line. Blank lines are skipped, and lines that
+// aren't a single valid JSON value are kept as they are, as Value::Malformed
+// rows, with what's wrong with them as their comment, so that one bad line
+// doesn't prevent viewing the rest of the input. Source ranges are relative
+// to the whole input, but line numbers in errors start at first_line, for
+// parsing lines that have been added to the end of a file.
+pub fn parse_lines(
+    input: &str,
+    first_line: usize,
+    depth_limit: usize,
+) -> (Vec<Row>, String, usize) {
+    let mut parser = JsonParser::new(input, None, depth_limit);
+
+    parser.parse_json_lines(input, first_line);
+
+    (parser.rows, parser.pretty_printed, parser.max_depth)
+}
+
+// Parses the input in a background thread, periodically sending the rows
+// parsed so far, so that they can be viewed before the whole input has
+// been parsed. The input is either a String, or a memory mapped file.
+pub fn parse_in_background<S>(json: S, depth_limit: usize) -> Receiver<ParseEvent>
+where
+    S: AsRef<str> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    std::thread::Builder::new()
+        .stack_size(flatjson::parser_stack_size(depth_limit))
+        .spawn(move || parse_and_stream(json.as_ref(), sender, STREAM_INTERVAL, depth_limit))
+        .unwrap();
+    receiver
+}
+
+fn parse_and_stream(
+    json: &str,
+    sender: Sender<ParseEvent>,
+    interval: Duration,
+    depth_limit: usize,
+) {
+    let stream = RowStream {
+        sender: sender.clone(),
+        interval,
+        sent_rows: 0,
+        sent_len: 0,
+        last_sent: Instant::now(),
+        checkpoints: 0,
+    };
+    let mut parser = JsonParser::new(json, Some(stream), depth_limit);
+
+    let result = parser
+        .parse_top_level_json()
+        .and_then(|_| parser.send_rows());
+    // If this fails, the receiver doesn't care anymore.
+    let _ = sender.send(ParseEvent::Done(result));
+}
+
+// Returns the text of a JSON string literal (including its quotes), with
+// its escape sequences replaced by the characters they stand for, or None
+// if it contains an invalid escape sequence.
+pub fn unescape_string(literal: &str) -> Option<String> {
+    let inner = literal.strip_prefix('"')?.strip_suffix('"')?;
+    let mut unescaped = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            unescaped.push(ch);
+            continue;
+        }
+
+        let escaped = match chars.next()? {
+            '"' => '"',
+            '\\' => '\\',
+            '/' => '/',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                let high = parse_hex_escape(&mut chars)?;
+                if (0xd800..0xdc00).contains(&high) {
+                    // A UTF-16 surrogate pair, e.g., "\ud83e\udd80".
+                    if chars.next()? != '\\' || chars.next()? != 'u' {
+                        return None;
+                    }
+                    let low = parse_hex_escape(&mut chars)?;
+                    if !(0xdc00..0xe000).contains(&low) {
+                        return None;
+                    }
+                    char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))?
+                } else {
+                    char::from_u32(high)?
+                }
+            }
+            _ => return None,
+        };
+        unescaped.push(escaped);
+    }
+
+    Some(unescaped)
+}
+
+// Parses the four hex digits following "\u".
+fn parse_hex_escape(chars: &mut std::str::Chars) -> Option<u32> {
+    let digits: String = chars.take(4).collect();
+    if digits.len() != 4 {
+        return None;
+    }
+    u32::from_str_radix(&digits, 16).ok()
+}
+
+// The token that closes a container opened by the given token.
+fn close_token(open_token: Option<JsonToken>) -> JsonToken {
+    match open_token {
+        Some(JsonToken::OpenCurly) => JsonToken::CloseCurly,
+        _ => JsonToken::CloseSquare,
+    }
+}
+
+// The line and column, both starting at 1, of a byte offset in some text,
+// e.g., for showing where a row's source_range is. Columns count
+// characters, rather than bytes, like most editors do.
+pub fn line_and_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
 impl<'a> JsonParser<'a> {
+    fn new(json: &'a str, stream: Option<RowStream>, depth_limit: usize) -> JsonParser<'a> {
+        JsonParser {
+            tokenizer: JsonToken::lexer(json),
+            parents: vec![],
+            siblings: vec![(OptionIndex::Nil, 0)],
+            pending_key_range: None,
+            pending_key_occurrence: 0,
+            object_keys: vec![],
+            rows: vec![],
+            pretty_printed: String::new(),
+            max_depth: 0,
+            depth_limit,
+            peeked_token: None,
+            line_start: None,
+            stream,
+        }
+    }
+
+    // Called after each value is parsed, when all the rows before it are
+    // complete (except for the containers it's in), to send the newly
+    // parsed rows if we're parsing in the background.
+    fn checkpoint(&mut self) -> Result<(), String> {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => return Ok(()),
+        };
+
+        stream.checkpoints += 1;
+        if stream.checkpoints % ROWS_PER_TIME_CHECK != 0 && stream.interval > Duration::from_secs(0)
+        {
+            return Ok(());
+        }
+
+        if stream.last_sent.elapsed() < stream.interval {
+            return Ok(());
+        }
+
+        self.send_rows()
+    }
+
+    fn send_rows(&mut self) -> Result<(), String> {
+        let stream = self.stream.as_mut().unwrap();
+
+        let open_containers = self
+            .parents
+            .iter()
+            .zip(self.siblings[1..].iter())
+            .map(|(&open_index, &(last_child, _))| (open_index, last_child.unwrap()))
+            .collect();
+
+        let parsed = ParsedRows {
+            rows: self.rows[stream.sent_rows..].to_vec(),
+            pretty_printed: self.pretty_printed[stream.sent_len..].to_owned(),
+            max_depth: self.max_depth,
+            open_containers,
+            parsed_bytes: self.tokenizer.span().end,
+        };
+
+        stream.sent_rows = self.rows.len();
+        stream.sent_len = self.pretty_printed.len();
+        stream.last_sent = Instant::now();
+
+        stream
+            .sender
+            .send(ParseEvent::Rows(parsed))
+            .map_err(|_| "Parsing was cancelled".to_string())
+    }
+
     fn next_token(&mut self) -> Option<JsonToken> {
         if self.peeked_token.is_some() {
             self.peeked_token.take().unwrap()
@@ -60,7 +323,37 @@ impl<'a> JsonParser<'a> {
     }
 
     fn unexpected_token(&mut self) -> Result<usize, String> {
-        Err(format!("Unexpected token: {:?}", self.peek_token()))
+        match self.peek_token_or_eof() {
+            Some(_) => Err(self.unexpected()),
+            None => Err("Unexpected EOF".to_string()),
+        }
+    }
+
+    // Describes the current token, which isn't allowed where it is, and
+    // where it is in the input, e.g., "Unexpected ',' at line 3, column 5".
+    fn unexpected(&self) -> String {
+        let span = self.tokenizer.span();
+        let mut token: String = self
+            .tokenizer
+            .slice()
+            .chars()
+            .take(MAX_ERROR_TOKEN_CHARS)
+            .collect();
+        if token.len() < span.len() {
+            token.push('…');
+        }
+
+        match self.line_start {
+            Some(line_start) => {
+                let line = &self.tokenizer.source()[line_start..];
+                let (_, column) = line_and_column(line, span.start - line_start);
+                format!("Unexpected '{}' at column {}", token, column)
+            }
+            None => {
+                let (line, column) = line_and_column(self.tokenizer.source(), span.start);
+                format!("Unexpected '{}' at line {}, column {}", token, line, column)
+            }
+        }
     }
 
     fn consume_whitespace(&mut self) {
@@ -71,8 +364,8 @@ impl<'a> JsonParser<'a> {
 
     fn parse_top_level_json(&mut self) -> Result<(), String> {
         self.consume_whitespace();
-        let mut prev_top_level = self.parse_elem()?;
-        let mut num_child = 0;
+        self.parse_elem()?;
+        self.checkpoint()?;
 
         loop {
             self.consume_whitespace();
@@ -82,14 +375,8 @@ impl<'a> JsonParser<'a> {
             }
 
             self.pretty_printed.push('\n');
-            let next_top_level = self.parse_elem()?;
-            num_child += 1;
-
-            self.rows[next_top_level].prev_sibling = OptionIndex::Index(prev_top_level);
-            self.rows[next_top_level].index = num_child;
-            self.rows[prev_top_level].next_sibling = OptionIndex::Index(next_top_level);
-
-            prev_top_level = next_top_level;
+            self.parse_elem()?;
+            self.checkpoint()?;
         }
 
         Ok(())
@@ -100,44 +387,29 @@ impl<'a> JsonParser<'a> {
 
         self.max_depth = self.max_depth.max(self.parents.len());
 
-        loop {
-            match self.peek_token()? {
-                JsonToken::OpenCurly => {
-                    return self.parse_object();
-                }
-                JsonToken::OpenSquare => {
-                    return self.parse_array();
-                }
-                JsonToken::Null => {
-                    return self.parse_null();
-                }
-                JsonToken::True => {
-                    return self.parse_bool(true);
-                }
-                JsonToken::False => {
-                    return self.parse_bool(false);
-                }
-                JsonToken::Number => {
-                    return self.parse_number();
-                }
-                JsonToken::String => {
-                    return self.parse_string();
-                }
-
-                JsonToken::Whitespace | JsonToken::Newline => {
-                    panic!("Should have just consumed whitespace");
-                }
-
-                JsonToken::Error => {
-                    return Err("Parse error".to_string());
-                }
-                JsonToken::CloseCurly
-                | JsonToken::CloseSquare
-                | JsonToken::Colon
-                | JsonToken::Comma => {
-                    return Err(format!("Unexpected character: {:?}", self.tokenizer.span()));
-                }
+        match self.peek_token()? {
+            JsonToken::OpenCurly | JsonToken::OpenSquare
+                if self.parents.len() >= self.depth_limit =>
+            {
+                self.skip_container()
+            }
+            JsonToken::OpenCurly => self.parse_object(),
+            JsonToken::OpenSquare => self.parse_array(),
+            JsonToken::Null => self.parse_null(),
+            JsonToken::True => self.parse_bool(true),
+            JsonToken::False => self.parse_bool(false),
+            JsonToken::Number => self.parse_number(),
+            JsonToken::String => self.parse_string(),
+
+            JsonToken::Whitespace | JsonToken::Newline => {
+                panic!("Should have just consumed whitespace");
             }
+
+            JsonToken::Error
+            | JsonToken::CloseCurly
+            | JsonToken::CloseSquare
+            | JsonToken::Colon
+            | JsonToken::Comma => Err(self.unexpected()),
         }
     }
 
@@ -153,10 +425,10 @@ impl<'a> JsonParser<'a> {
         let array_open_index = self.create_row(open_value);
 
         self.parents.push(array_open_index);
+        self.siblings.push((OptionIndex::Nil, 0));
         self.pretty_printed.push('[');
         self.advance_and_consume_whitespace();
 
-        let mut prev_sibling = OptionIndex::Nil;
         let mut num_children = 0;
 
         loop {
@@ -182,32 +454,23 @@ impl<'a> JsonParser<'a> {
                 self.pretty_printed.push_str(", ");
             }
 
-            let child = self.parse_elem()?;
+            self.parse_elem()?;
             self.consume_whitespace();
-
-            if num_children == 0 {
-                match self.rows[array_open_index].value {
-                    Value::OpenContainer {
-                        ref mut first_child,
-                        ..
-                    } => {
-                        *first_child = child;
-                    }
-                    _ => panic!("Must be Array!"),
-                }
-            }
-
-            self.rows[child].prev_sibling = prev_sibling;
-            self.rows[child].index = num_children;
-            if let OptionIndex::Index(prev) = prev_sibling {
-                self.rows[prev].next_sibling = OptionIndex::Index(child);
-            }
+            self.checkpoint()?;
 
             num_children += 1;
-            prev_sibling = OptionIndex::Index(child);
         }
 
+        self.set_source_end(array_open_index);
+        self.close_array(array_open_index, num_children);
+        Ok(array_open_index)
+    }
+
+    // Adds the closing row of the array that's currently being parsed, or
+    // makes it an empty array if it doesn't have any children.
+    fn close_array(&mut self, array_open_index: Index, num_children: usize) {
         self.parents.pop();
+        let (prev_sibling, _) = self.siblings.pop().unwrap();
 
         if num_children == 0 {
             self.rows[array_open_index].value = Value::EmptyArray;
@@ -220,7 +483,7 @@ impl<'a> JsonParser<'a> {
                 open_index: array_open_index,
             };
 
-            let array_close_index = self.create_row(close_value);
+            let array_close_index = self.create_close_row(close_value);
 
             // Update end of the Array range; we add the ']' to pretty_printed
             // below, hence the + 1.
@@ -238,7 +501,96 @@ impl<'a> JsonParser<'a> {
         }
 
         self.pretty_printed.push(']');
-        Ok(array_open_index)
+    }
+
+    fn parse_json_lines(&mut self, input: &'a str, first_line: usize) {
+        let array_open_index = self.create_row(Value::OpenContainer {
+            container_type: ContainerType::Array,
+            collapsed: false,
+            // To be set when parsing is complete.
+            first_child: 0,
+            close_index: 0,
+        });
+        self.parents.push(array_open_index);
+        self.siblings.push((OptionIndex::Nil, 0));
+        self.pretty_printed.push('[');
+
+        let mut num_children = 0;
+        let mut line_start = 0;
+        for (i, line) in input.split_inclusive('\n').enumerate() {
+            let start = line_start + (line.len() - line.trim_start().len());
+            let end = line_start + line.trim_end().len();
+            line_start += line.len();
+            if start >= end {
+                continue;
+            }
+
+            if num_children != 0 {
+                self.pretty_printed.push_str(", ");
+            }
+            num_children += 1;
+
+            // Each line is parsed on its own, so that parsing it can stop
+            // at the end of it.
+            self.tokenizer = JsonToken::lexer(&input[..end]);
+            self.tokenizer.bump(start);
+            self.peeked_token = None;
+            self.line_start = Some(start);
+
+            let rows_len = self.rows.len();
+            let pretty_printed_len = self.pretty_printed.len();
+            let max_depth = self.max_depth;
+            let siblings = *self.siblings.last().unwrap();
+
+            if let Err(err) = self.parse_line() {
+                // Forget whatever was parsed before the error.
+                self.rows.truncate(rows_len);
+                self.pretty_printed.truncate(pretty_printed_len);
+                self.max_depth = max_depth;
+                self.parents.truncate(1);
+                self.siblings.truncate(2);
+                self.siblings[1] = siblings;
+                if let (OptionIndex::Index(prev), _) = siblings {
+                    self.rows[prev].next_sibling = OptionIndex::Nil;
+                }
+                self.object_keys.clear();
+                self.pending_key_range = None;
+                self.pending_key_occurrence = 0;
+
+                let row_index = self.create_row(Value::Malformed);
+                self.pretty_printed.push_str(&input[start..end]);
+                self.rows[row_index].range.end = self.pretty_printed.len();
+                self.rows[row_index].source_range = Some(start..end);
+                self.rows[row_index].comment =
+                    Some(format!("Line {}: {}", first_line + i, err).into_boxed_str());
+            }
+        }
+
+        self.line_start = None;
+        self.rows[array_open_index].source_range = Some(0..input.len());
+        self.close_array(array_open_index, num_children);
+        // There isn't a closing bracket in the input.
+        if num_children != 0 {
+            self.rows.last_mut().unwrap().source_range = None;
+        }
+    }
+
+    // Parses the value on a line of JSON Lines input, which must be the
+    // only thing on it.
+    fn parse_line(&mut self) -> Result<(), String> {
+        self.parse_elem()?;
+        self.consume_whitespace();
+        match self.peek_token_or_eof() {
+            None => Ok(()),
+            Some(
+                JsonToken::Error
+                | JsonToken::CloseCurly
+                | JsonToken::CloseSquare
+                | JsonToken::Colon
+                | JsonToken::Comma,
+            ) => Err(self.unexpected()),
+            Some(_) => Err("Line contains multiple JSON values".to_string()),
+        }
     }
 
     fn parse_object(&mut self) -> Result<usize, String> {
@@ -253,11 +605,15 @@ impl<'a> JsonParser<'a> {
         let object_open_index = self.create_row(open_value);
 
         self.parents.push(object_open_index);
+        self.siblings.push((OptionIndex::Nil, 0));
         self.pretty_printed.push('{');
         self.advance_and_consume_whitespace();
 
-        let mut prev_sibling = OptionIndex::Nil;
         let mut num_children = 0;
+        // Duplicate keys are kept, but we count how many times each key
+        // has appeared so that they can be told apart.
+        let keys_start = self.object_keys.len();
+        let mut key_counts = None;
 
         loop {
             if num_children != 0 {
@@ -294,7 +650,10 @@ impl<'a> JsonParser<'a> {
                 let key_span_len = self.tokenizer.span().len();
                 let key_range = key_range_start..key_range_start + key_span_len;
 
-                self.pretty_printed.push_str(self.tokenizer.slice());
+                let key = self.tokenizer.slice();
+                self.pending_key_occurrence = self.key_occurrence(key, keys_start, &mut key_counts);
+
+                self.pretty_printed.push_str(key);
                 self.advance_and_consume_whitespace();
                 key_range
             };
@@ -305,33 +664,18 @@ impl<'a> JsonParser<'a> {
             self.advance_and_consume_whitespace();
             self.pretty_printed.push_str(": ");
 
-            let child = self.parse_elem()?;
-            self.rows[child].key_range = Some(key_range);
+            self.pending_key_range = Some(key_range);
+            self.parse_elem()?;
             self.consume_whitespace();
-
-            if num_children == 0 {
-                match self.rows[object_open_index].value {
-                    Value::OpenContainer {
-                        ref mut first_child,
-                        ..
-                    } => {
-                        *first_child = child;
-                    }
-                    _ => panic!("Must be Object!"),
-                }
-            }
-
-            self.rows[child].prev_sibling = prev_sibling;
-            self.rows[child].index = num_children;
-            if let OptionIndex::Index(prev) = prev_sibling {
-                self.rows[prev].next_sibling = OptionIndex::Index(child);
-            }
+            self.checkpoint()?;
 
             num_children += 1;
-            prev_sibling = OptionIndex::Index(child);
         }
 
+        self.object_keys.truncate(keys_start);
         self.parents.pop();
+        let (prev_sibling, _) = self.siblings.pop().unwrap();
+        self.set_source_end(object_open_index);
 
         if num_children == 0 {
             self.rows[object_open_index].value = Value::EmptyObject;
@@ -347,7 +691,7 @@ impl<'a> JsonParser<'a> {
                 open_index: object_open_index,
             };
 
-            let object_close_index = self.create_row(close_value);
+            let object_close_index = self.create_close_row(close_value);
 
             // Update end of the Object range; we add the '}' to pretty_printed
             // below, hence the + 1.
@@ -368,6 +712,81 @@ impl<'a> JsonParser<'a> {
         Ok(object_open_index)
     }
 
+    // Returns how many times a key has already been used in the object
+    // whose keys start at keys_start in object_keys. Once the object has
+    // too many keys to scan, they're moved into key_counts instead.
+    fn key_occurrence(
+        &mut self,
+        key: &'a str,
+        keys_start: usize,
+        key_counts: &mut Option<HashMap<&'a str, usize>>,
+    ) -> usize {
+        if key_counts.is_none() && self.object_keys.len() - keys_start < MAX_SCANNED_KEYS {
+            let occurrence = self.object_keys[keys_start..]
+                .iter()
+                .filter(|&&earlier_key| earlier_key == key)
+                .count();
+            self.object_keys.push(key);
+            return occurrence;
+        }
+
+        let object_keys = &mut self.object_keys;
+        let counts = key_counts.get_or_insert_with(|| {
+            let mut counts = HashMap::new();
+            for earlier_key in object_keys.drain(keys_start..) {
+                *counts.entry(earlier_key).or_insert(0) += 1;
+            }
+            counts
+        });
+
+        let count = counts.entry(key).or_insert(0);
+        *count += 1;
+        *count - 1
+    }
+    // Skips over a container that's nested too deeply, without recursing
+    // into it (or checking more than that its brackets match), and adds a
+    // placeholder in its place. Empty containers aren't any deeper,
+    // though, so they're still shown.
+    fn skip_container(&mut self) -> Result<usize, String> {
+        let source_start = self.tokenizer.span().start;
+        let open_token = self.next_token();
+        // The closing bracket expected for each container that's open.
+        let mut closes = vec![close_token(open_token)];
+        let mut empty = true;
+        while let Some(&close) = closes.last() {
+            match self.next_token() {
+                Some(JsonToken::Whitespace | JsonToken::Newline) => continue,
+                Some(token @ (JsonToken::OpenCurly | JsonToken::OpenSquare)) => {
+                    closes.push(close_token(Some(token)))
+                }
+                Some(token @ (JsonToken::CloseCurly | JsonToken::CloseSquare)) => {
+                    if token != close {
+                        return Err(self.unexpected());
+                    }
+                    closes.pop();
+                }
+                Some(JsonToken::Error) => return Err(self.unexpected()),
+                Some(_) => {}
+                None => return Err("Unexpected EOF".to_string()),
+            }
+            empty = empty && closes.is_empty();
+        }
+
+        let (value, text) = match open_token {
+            _ if !empty => (
+                Value::TooDeep,
+                flatjson::too_deep_placeholder(self.depth_limit),
+            ),
+            Some(JsonToken::OpenCurly) => (Value::EmptyObject, "{}".to_owned()),
+            _ => (Value::EmptyArray, "[]".to_owned()),
+        };
+        let row_index = self.create_row(value);
+        self.pretty_printed.push_str(&text);
+        self.rows[row_index].range.end = self.pretty_printed.len();
+        self.rows[row_index].source_range = Some(source_start..self.tokenizer.span().end);
+        Ok(row_index)
+    }
+
     fn parse_null(&mut self) -> Result<usize, String> {
         self.advance();
         let row_index = self.create_row(Value::Null);
@@ -390,6 +809,8 @@ impl<'a> JsonParser<'a> {
 
     fn parse_number(&mut self) -> Result<usize, String> {
         let row_index = self.create_row(Value::Number);
+        // Numbers are kept exactly as they're written, rather than parsed,
+        // so that we don't turn "1.0" into "1" or lose precision.
         self.pretty_printed.push_str(self.tokenizer.slice());
 
         self.rows[row_index].range.end =
@@ -411,12 +832,53 @@ impl<'a> JsonParser<'a> {
         Ok(row_index)
     }
 
-    // Add a new row to the FlatJson representation.
+    // Add a new row for a value to the FlatJson representation, as the
+    // next child of the current container, or the next top-level value.
     //
     // self.pretty_printed should NOT include the added row yet;
     // we use the current length of self.pretty_printed as the
     // starting index of the row's range.
     fn create_row(&mut self, value: Value) -> usize {
+        let index = self.create_close_row(value);
+        let (prev_sibling, num_siblings) = *self.siblings.last().unwrap();
+
+        self.rows[index].prev_sibling = prev_sibling;
+        self.rows[index].index = num_siblings;
+        self.rows[index].key_range = self.pending_key_range.take();
+        self.rows[index].key_occurrence = std::mem::take(&mut self.pending_key_occurrence);
+
+        match prev_sibling {
+            OptionIndex::Index(prev) => self.rows[prev].next_sibling = OptionIndex::Index(index),
+            OptionIndex::Nil => {
+                if let Some(&parent) = self.parents.last() {
+                    if let Value::OpenContainer {
+                        ref mut first_child,
+                        ..
+                    } = self.rows[parent].value
+                    {
+                        *first_child = index;
+                    }
+                }
+            }
+        }
+
+        *self.siblings.last_mut().unwrap() = (OptionIndex::Index(index), num_siblings + 1);
+
+        index
+    }
+
+    // Sets the end of the source range of a container to the end of the
+    // current token, its closing bracket.
+    fn set_source_end(&mut self, index: Index) {
+        let end = self.tokenizer.span().end;
+        if let Some(source_range) = &mut self.rows[index].source_range {
+            source_range.end = end;
+        }
+    }
+
+    // Add the row for the end of a container, which isn't linked to any
+    // siblings.
+    fn create_close_row(&mut self, value: Value) -> usize {
         let index = self.rows.len();
 
         let parent = match self.parents.last() {
@@ -438,11 +900,17 @@ impl<'a> JsonParser<'a> {
             // update it after ']' and '}'.
             range: range_start..range_start + 1,
 
-            // To be filled in by caller
+            // Filled in by create_row
             prev_sibling: OptionIndex::Nil,
             next_sibling: OptionIndex::Nil,
             index: 0,
             key_range: None,
+            // The current token is where the value starts, and for
+            // containers, the end is set once they're closed.
+            source_range: Some(self.tokenizer.span()),
+            key_occurrence: 0,
+            anchor: None,
+            comment: None,
         });
 
         index
@@ -451,12 +919,13 @@ impl<'a> JsonParser<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::flatjson::{FlatJson, DEFAULT_MAX_DEPTH};
 
     #[test]
     fn test_row_ranges() {
         //            0 2    7  10   15    21   26    32     39 42
         let json = r#"{ "a": 1, "b": true, "c": null, "ddd": [] }"#.to_owned();
-        let (rows, _, _) = parse(json).unwrap();
+        let (rows, _, _) = parse(json, DEFAULT_MAX_DEPTH).unwrap();
 
         assert_eq!(rows[0].range, 0..43); // Object
         assert_eq!(rows[1].key_range, Some(2..5)); // "a": 1
@@ -470,7 +939,7 @@ mod tests {
 
         //            01   5        14     21 23
         let json = r#"[14, "apple", false, {}]"#.to_owned();
-        let (rows, _, _) = parse(json).unwrap();
+        let (rows, _, _) = parse(json, DEFAULT_MAX_DEPTH).unwrap();
 
         assert_eq!(rows[0].range, 0..24); // Array
         assert_eq!(rows[1].range, 1..3); // 14
@@ -481,7 +950,7 @@ mod tests {
 
         //            01 3      10     17    23  27   32   37 40    46   51
         let json = r#"[{ "abc": "str", "de": 14, "f": null }, true, false]"#.to_owned();
-        let (rows, _, _) = parse(json).unwrap();
+        let (rows, _, _) = parse(json, DEFAULT_MAX_DEPTH).unwrap();
 
         assert_eq!(rows[0].range, 0..52); // Array
         assert_eq!(rows[1].range, 1..38); // Object
@@ -496,4 +965,222 @@ mod tests {
         assert_eq!(rows[7].range, 46..51); // false
         assert_eq!(rows[8].range, 51..52); // ]
     }
+
+    #[test]
+    fn test_source_ranges() {
+        let source_ranges = |json: &str, depth_limit| {
+            let (rows, _, _) = parse(json.to_owned(), depth_limit).unwrap();
+            rows.into_iter()
+                .map(|row| row.source_range.unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        //          0       9 11     15 17      24      34   37
+        let json = "{\n  \"a\": [ 1,\n 2 ],\"b\":\t\"x\" ,\"c\": {}\n}\n";
+        assert_eq!(
+            vec![0..38, 9..18, 11..12, 15..16, 17..18, 24..27, 34..36, 37..38],
+            source_ranges(json, DEFAULT_MAX_DEPTH),
+        );
+
+        // Containers that are too deep are still where they were.
+        assert_eq!(
+            vec![0..13, 1..12, 12..13],
+            source_ranges(r#"[[[1], [[]]]] "#, 1)
+        );
+
+        // Each value of a stream of values is in its own place.
+        assert_eq!(
+            vec![0..4, 5..7, 8..11],
+            source_ranges("true {}\n[1]", DEFAULT_MAX_DEPTH)[..3].to_vec()
+        );
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let json = r#"{"a": 1, "b": 2, "a": [3], "a": "four"}"#.to_owned();
+        let (rows, pretty_printed, _) = parse(json, DEFAULT_MAX_DEPTH).unwrap();
+
+        // All of the members are kept, in order.
+        assert_eq!(
+            r#"{ "a": 1, "b": 2, "a": [3], "a": "four" }"#,
+            pretty_printed
+        );
+        let keys: Vec<(&str, usize)> = rows
+            .iter()
+            .filter_map(|row| {
+                let key_range = row.key_range.clone()?;
+                Some((&pretty_printed[key_range], row.key_occurrence))
+            })
+            .collect();
+        assert_eq!(
+            vec![("\"a\"", 0), ("\"b\"", 0), ("\"a\"", 1), ("\"a\"", 2)],
+            keys
+        );
+
+        assert_eq!(rows[1].next_sibling, OptionIndex::Index(2));
+        assert_eq!(rows[3].index, 2);
+        assert_eq!(rows[6].index, 3);
+
+        // Large objects count keys differently.
+        let mut members: Vec<String> = (0..20).map(|i| format!("\"k{}\": {}", i, i)).collect();
+        members.push("\"k3\": 20".to_owned());
+        members.push("\"k3\": 21".to_owned());
+        let (rows, _, _) = parse(format!("{{{}}}", members.join(", ")), DEFAULT_MAX_DEPTH).unwrap();
+        let occurrences: Vec<usize> = rows.iter().map(|row| row.key_occurrence).collect();
+        assert_eq!(&[0; 21][..], &occurrences[..21]);
+        assert_eq!(&[1, 2, 0], &occurrences[21..]);
+    }
+
+    fn format_without_source_ranges(rows: &[Row]) -> String {
+        let rows = rows
+            .iter()
+            .cloned()
+            .map(|mut row| {
+                row.source_range = None;
+                row
+            })
+            .collect::<Vec<Row>>();
+        format!("{:?}", rows)
+    }
+
+    #[test]
+    fn test_parse_and_stream() {
+        let json = r#"{"a": [1, {"b": [2, 3]}, []], "c": {"d": null}} [4] "e""#;
+        let (sender, receiver) = mpsc::channel();
+        parse_and_stream(json, sender, Duration::from_secs(0), DEFAULT_MAX_DEPTH);
+
+        let mut flatjson = FlatJson(vec![], String::new(), 0);
+        let mut parsed_prefix = (0, 0);
+        let mut num_batches = 0;
+
+        for event in receiver.iter() {
+            let mut parsed = match event {
+                ParseEvent::Rows(parsed) => parsed,
+                ParseEvent::Done(result) => {
+                    assert_eq!(Ok(()), result);
+                    break;
+                }
+            };
+            num_batches += 1;
+
+            flatjson.0.truncate(parsed_prefix.0);
+            flatjson.1.truncate(parsed_prefix.1);
+            let open_containers = std::mem::take(&mut parsed.open_containers);
+            flatjson.append_parsed_rows(parsed);
+            parsed_prefix = (flatjson.0.len(), flatjson.1.len());
+            flatjson.add_closing_placeholders(&open_containers);
+
+            // The rows parsed so far, with the placeholders, should be the
+            // same as if the input had ended there, apart from where they
+            // are in the input, since the pretty printed text is parsed.
+            let (rows, _, _) = parse(flatjson.1.clone(), DEFAULT_MAX_DEPTH).unwrap();
+            assert_eq!(
+                format_without_source_ranges(&rows),
+                format_without_source_ranges(&flatjson.0)
+            );
+        }
+
+        assert!(num_batches > 5);
+
+        let (rows, pretty_printed, max_depth) = parse(json.to_owned(), DEFAULT_MAX_DEPTH).unwrap();
+        assert_eq!(pretty_printed, flatjson.1);
+        assert_eq!(format!("{:?}", rows), format!("{:?}", flatjson.0));
+        assert_eq!(max_depth, flatjson.2);
+    }
+
+    #[test]
+    fn test_line_and_column() {
+        let text = "{\n  \"é\": [1,\n    2]\n}";
+        assert_eq!((1, 1), line_and_column(text, 0));
+        assert_eq!((2, 3), line_and_column(text, 4));
+        // é is two bytes, but one column.
+        assert_eq!((2, 8), line_and_column(text, 10));
+        assert_eq!((3, 5), line_and_column(text, 18));
+        assert_eq!((4, 1), line_and_column(text, text.len() - 1));
+    }
+
+    #[test]
+    fn test_unescape_string() {
+        assert_eq!(
+            Some(r#"{"a": [1]}"#.to_owned()),
+            unescape_string(r#""{\"a\": [1]}""#)
+        );
+        assert_eq!(
+            Some("tab\t\\/\u{e9}🦀".to_owned()),
+            unescape_string(r#""tab\t\\\/\u00e9\ud83e\udd80""#)
+        );
+
+        assert_eq!(None, unescape_string(r#""\x""#));
+        assert_eq!(None, unescape_string(r#""\u12""#));
+        assert_eq!(None, unescape_string(r#""\ud83e""#));
+        assert_eq!(None, unescape_string("unquoted"));
+    }
+
+    #[test]
+    fn test_parse_and_stream_error() {
+        let (sender, receiver) = mpsc::channel();
+        parse_and_stream(
+            "[1, 2, }",
+            sender,
+            Duration::from_secs(0),
+            DEFAULT_MAX_DEPTH,
+        );
+
+        let events: Vec<ParseEvent> = receiver.iter().collect();
+        assert!(matches!(events.last(), Some(ParseEvent::Done(Err(_)))));
+    }
+
+    #[test]
+    fn test_error_messages() {
+        let error = |json: &str| parse(json.to_owned(), DEFAULT_MAX_DEPTH).unwrap_err();
+
+        assert_eq!("Unexpected '1' at line 1, column 6", error(r#"{"a" 1}"#));
+        assert_eq!("Unexpected 'x' at line 2, column 3", error("[1,\n  x]"));
+        assert_eq!("Unexpected ',' at line 1, column 4", error("[1,,2]"));
+        assert_eq!(
+            "Unexpected '\"aaaaaaaaaaaaaaaaaaa…' at line 1, column 4",
+            error(&format!(r#"[1 "{}"]"#, "a".repeat(30)))
+        );
+        assert_eq!("Unexpected EOF", error("[1, 2"));
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let json = r#"{"a": [1, [2, {"b": [3]}]], "c": [[]]}"#;
+        let (rows, pretty_printed, max_depth) = parse(json.to_owned(), 2).unwrap();
+        assert_eq!(
+            pretty_printed,
+            r#"{ "a": [1, … max depth 2 reached], "c": [[]] }"#
+        );
+        assert_eq!(max_depth, 2);
+        assert_eq!(rows[3].depth, 2);
+        assert!(matches!(rows[3].value, Value::TooDeep));
+        let flatjson = FlatJson(rows, pretty_printed, max_depth);
+        assert!(flatjson.reached_max_depth());
+
+        // The placeholder isn't written out as if it were a value.
+        assert_eq!(
+            Err(
+                "Values nested more than 2 levels deep weren't parsed (see --max-depth)".to_owned()
+            ),
+            flatjson.check_all_parsed()
+        );
+        assert!(flatjson.check_parsed(1).is_err());
+        assert!(flatjson.check_parsed(4).is_err());
+        assert_eq!(Ok(()), flatjson.check_parsed(5));
+
+        // Values other than objects and arrays can be at the limit.
+        let (rows, pretty_printed, max_depth) = parse(json.to_owned(), 5).unwrap();
+        assert_eq!(max_depth, 5);
+        assert!(!FlatJson(rows, pretty_printed, max_depth).reached_max_depth());
+
+        // Deeply nested inputs can be parsed without overflowing the stack,
+        // but the brackets still need to match up.
+        let deep = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        assert!(parse(deep.clone(), DEFAULT_MAX_DEPTH).is_ok());
+        assert!(parse(deep[1..].to_owned(), DEFAULT_MAX_DEPTH).is_err());
+        assert!(parse(r#"[[{]}]"#.to_owned(), 1).is_err());
+        assert!(parse(r#"[[[}]]"#.to_owned(), 1).is_err());
+        assert!(parse(r#"[[{"a": []}]]"#.to_owned(), 1).is_ok());
+    }
 }
@@ -0,0 +1,1377 @@
+use std::path::{Path, PathBuf};
+
+use termion::event::Key;
+use toml::Value as Toml;
+
+use crate::input::{self, KeyModifiers};
+use crate::options;
+
+// Keybindings map keys, or sequences of keys, to the commands they run.
+// The defaults are listed in DEFAULT_BINDINGS, and can be changed by a
+// TOML file that maps keys to the names of commands, e.g.:
+//
+//   "ctrl-n" = "down"
+//   "z t" = "focused-line-to-top"
+//   "x" = "none"
+//
+// A sequence of keys is written with spaces between them. Binding a key
+// replaces any other bindings that conflict with it, e.g., binding "y"
+// removes the bindings for "y y", "y k", etc., and binding a key to
+// "none" just removes its bindings.
+//
+// The file can also define macros, which run a sequence of commands, or
+// of lines entered at the ':' prompt, and can be bound to keys like any
+// other command, or run with :run, e.g.:
+//
+//   "z 1" = "tidy"
+//
+//   [macros]
+//   tidy = ["collapse-siblings", ":collapse metadata", "top"]
+pub struct Keybindings {
+    bindings: Vec<(Vec<BoundKey>, KeyCommand)>,
+    macros: Vec<Macro>,
+}
+
+// A key that can be bound to a command. Keys pressed with combinations of
+// modifiers that termion's Key can't represent, e.g., shift-enter, are only
+// reported by terminals using the kitty keyboard protocol (--kitty-keyboard).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BoundKey {
+    Key(Key),
+    Modified(Key, KeyModifiers),
+}
+
+pub struct Macro {
+    pub name: String,
+    pub steps: Vec<MacroStep>,
+    // The steps, as written in the keybindings file.
+    description: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MacroStep {
+    Command(KeyCommand),
+    // A line entered at the ':' prompt, without the ':'.
+    CommandLine(String),
+}
+
+// The commands that can be bound to keys. Many of them can be preceded
+// by a count, e.g., 5j.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeyCommand {
+    Quit,
+    ForceQuit,
+    QuitWithoutSavingState,
+    QuitAndPrintValue,
+    Help,
+    Cancel,
+    Command,
+    Filter,
+
+    Up,
+    Down,
+    ScrollUp,
+    ScrollDown,
+    HalfPageUp,
+    HalfPageDown,
+    PageUp,
+    PageDown,
+    PrevSibling,
+    NextSibling,
+    SkipToPrevSibling,
+    SkipToNextSibling,
+    NextMatch,
+    PrevMatch,
+    ScrollRight,
+    ScrollLeft,
+    SearchForward,
+    SearchBackward,
+    SearchKeyForward,
+    SearchKeyBackward,
+    // With a count, these jump to line N, or N% of the way through the
+    // input, respectively.
+    Top,
+    Bottom,
+    MatchingPair,
+    NextFile,
+    PrevFile,
+    DecreaseIndentation,
+    CollapseToDepth,
+    ExpandToDepth,
+
+    Left,
+    Right,
+    Parent,
+    CollapseParent,
+    UpUntilDepthChange,
+    DownUntilDepthChange,
+    FirstSibling,
+    LastSibling,
+    ToggleCollapsed,
+    ExpandSubtree,
+    CollapseSubtree,
+    CollapseSiblings,
+    ExpandSiblings,
+    MatchSiblings,
+    CollapseOtherSiblings,
+    ToggleMode,
+    IncreaseIndentation,
+    ScrollScreenLeft,
+    ScrollScreenRight,
+    ScrollScreenHalfLeft,
+    ScrollScreenHalfRight,
+    ScrollToEnd,
+    ScrubValue,
+    CycleTruncation,
+    FocusedLineToTop,
+    FocusedLineToCenter,
+    FocusedLineToBottom,
+
+    ToggleSortKeys,
+    ToggleDecodedString,
+    TransformValue,
+    ToggleWrap,
+    ToggleWrapAll,
+    ToggleSizes,
+    ToggleTypes,
+    ToggleAnnotations,
+    ToggleAlignValues,
+    ToggleTableView,
+    CycleHiddenValues,
+    CycleNumberBase,
+    ToggleLineNumbers,
+    ToggleMinimap,
+    ToggleIndentGuides,
+    ToggleFoldColumn,
+    ToggleMouse,
+
+    SetMark,
+    JumpToMark,
+    JumpToAnchor,
+
+    CopyValue,
+    CopyOneLineValue,
+    CopyDisplayedValue,
+    CopyDecodedValue,
+    CopyKey,
+    CopyPath,
+    CopyDotPath,
+    CopyBracketPath,
+    CopyJqPath,
+    CopyGronPath,
+    CopyPlainPath,
+    CopySource,
+
+    ShowSourcePosition,
+    ShowKeybindings,
+    CommandPalette,
+
+    // Runs the steps of the nth macro defined in the keybindings file.
+    RunMacro(usize),
+}
+
+// The name of each command, as used in the keybindings file, and a
+// description of it for the list of keybindings.
+const COMMANDS: &[(&str, KeyCommand, &str)] = &[
+    ("quit", KeyCommand::Quit, "Exit jless"),
+    (
+        "force-quit",
+        KeyCommand::ForceQuit,
+        "Exit jless without asking for confirmation",
+    ),
+    (
+        "quit-without-saving-state",
+        KeyCommand::QuitWithoutSavingState,
+        "Exit jless without remembering the state of files",
+    ),
+    (
+        "quit-and-print-value",
+        KeyCommand::QuitAndPrintValue,
+        "Exit jless and print the focused value as JSON",
+    ),
+    ("help", KeyCommand::Help, "Show the help page"),
+    (
+        "cancel",
+        KeyCommand::Cancel,
+        "Cancel a count or command, and stop highlighting matches",
+    ),
+    (
+        "command",
+        KeyCommand::Command,
+        "Enter a command, e.g., :export",
+    ),
+    (
+        "filter",
+        KeyCommand::Filter,
+        "Only show lines matching a pattern",
+    ),
+    ("up", KeyCommand::Up, "Move focus up one line"),
+    ("down", KeyCommand::Down, "Move focus down one line"),
+    ("scroll-up", KeyCommand::ScrollUp, "Scroll up one line"),
+    (
+        "scroll-down",
+        KeyCommand::ScrollDown,
+        "Scroll down one line",
+    ),
+    (
+        "half-page-up",
+        KeyCommand::HalfPageUp,
+        "Scroll up half a screen",
+    ),
+    (
+        "half-page-down",
+        KeyCommand::HalfPageDown,
+        "Scroll down half a screen",
+    ),
+    ("page-up", KeyCommand::PageUp, "Scroll up one screen"),
+    ("page-down", KeyCommand::PageDown, "Scroll down one screen"),
+    (
+        "prev-sibling",
+        KeyCommand::PrevSibling,
+        "Move to the previous sibling",
+    ),
+    (
+        "next-sibling",
+        KeyCommand::NextSibling,
+        "Move to the next sibling",
+    ),
+    (
+        "skip-to-prev-sibling",
+        KeyCommand::SkipToPrevSibling,
+        "Move to the previous sibling, staying in the same parent",
+    ),
+    (
+        "skip-to-next-sibling",
+        KeyCommand::SkipToNextSibling,
+        "Move to the next sibling, staying in the same parent",
+    ),
+    (
+        "next-match",
+        KeyCommand::NextMatch,
+        "Jump to the next search match",
+    ),
+    (
+        "prev-match",
+        KeyCommand::PrevMatch,
+        "Jump to the previous search match",
+    ),
+    (
+        "scroll-right",
+        KeyCommand::ScrollRight,
+        "Scroll a truncated value to the right",
+    ),
+    (
+        "scroll-left",
+        KeyCommand::ScrollLeft,
+        "Scroll a truncated value to the left",
+    ),
+    (
+        "search-forward",
+        KeyCommand::SearchForward,
+        "Search forward",
+    ),
+    (
+        "search-backward",
+        KeyCommand::SearchBackward,
+        "Search backward",
+    ),
+    (
+        "search-key-forward",
+        KeyCommand::SearchKeyForward,
+        "Search forward for the focused key",
+    ),
+    (
+        "search-key-backward",
+        KeyCommand::SearchKeyBackward,
+        "Search backward for the focused key",
+    ),
+    ("top", KeyCommand::Top, "Jump to the top, or to line N"),
+    (
+        "bottom",
+        KeyCommand::Bottom,
+        "Jump to the bottom, or to line N",
+    ),
+    (
+        "matching-pair",
+        KeyCommand::MatchingPair,
+        "Jump to the matching delimiter, or N% of the way through",
+    ),
+    ("next-file", KeyCommand::NextFile, "Switch to the next file"),
+    (
+        "prev-file",
+        KeyCommand::PrevFile,
+        "Switch to the previous file",
+    ),
+    (
+        "decrease-indentation",
+        KeyCommand::DecreaseIndentation,
+        "Decrease the indentation",
+    ),
+    (
+        "collapse-to-depth",
+        KeyCommand::CollapseToDepth,
+        "Collapse everything at depth N or deeper",
+    ),
+    (
+        "expand-to-depth",
+        KeyCommand::ExpandToDepth,
+        "Expand everything shallower than depth N",
+    ),
+    (
+        "left",
+        KeyCommand::Left,
+        "Collapse the focused node, or move to its parent",
+    ),
+    (
+        "right",
+        KeyCommand::Right,
+        "Expand the focused node, or move to its first child",
+    ),
+    (
+        "parent",
+        KeyCommand::Parent,
+        "Move to the parent of the focused node",
+    ),
+    (
+        "collapse-parent",
+        KeyCommand::CollapseParent,
+        "Move to the parent of the focused node and collapse it",
+    ),
+    (
+        "up-until-depth-change",
+        KeyCommand::UpUntilDepthChange,
+        "Move up until the depth changes",
+    ),
+    (
+        "down-until-depth-change",
+        KeyCommand::DownUntilDepthChange,
+        "Move down until the depth changes",
+    ),
+    (
+        "first-sibling",
+        KeyCommand::FirstSibling,
+        "Move to the first sibling",
+    ),
+    (
+        "last-sibling",
+        KeyCommand::LastSibling,
+        "Move to the last sibling",
+    ),
+    (
+        "toggle-collapsed",
+        KeyCommand::ToggleCollapsed,
+        "Collapse or expand the focused node",
+    ),
+    (
+        "expand-subtree",
+        KeyCommand::ExpandSubtree,
+        "Expand the focused node and everything inside it",
+    ),
+    (
+        "collapse-subtree",
+        KeyCommand::CollapseSubtree,
+        "Collapse the focused node and everything inside it",
+    ),
+    (
+        "collapse-siblings",
+        KeyCommand::CollapseSiblings,
+        "Collapse the focused node and its siblings",
+    ),
+    (
+        "expand-siblings",
+        KeyCommand::ExpandSiblings,
+        "Expand the focused node and its siblings",
+    ),
+    (
+        "match-siblings",
+        KeyCommand::MatchSiblings,
+        "Collapse or expand siblings to match the focused node",
+    ),
+    (
+        "collapse-other-siblings",
+        KeyCommand::CollapseOtherSiblings,
+        "Collapse the siblings of the focused node",
+    ),
+    (
+        "toggle-mode",
+        KeyCommand::ToggleMode,
+        "Switch between data mode and line mode",
+    ),
+    (
+        "increase-indentation",
+        KeyCommand::IncreaseIndentation,
+        "Increase the indentation",
+    ),
+    (
+        "scroll-screen-left",
+        KeyCommand::ScrollScreenLeft,
+        "Scroll the screen one column to the left",
+    ),
+    (
+        "scroll-screen-right",
+        KeyCommand::ScrollScreenRight,
+        "Scroll the screen one column to the right",
+    ),
+    (
+        "scroll-screen-half-left",
+        KeyCommand::ScrollScreenHalfLeft,
+        "Scroll the screen half a screen width to the left",
+    ),
+    (
+        "scroll-screen-half-right",
+        KeyCommand::ScrollScreenHalfRight,
+        "Scroll the screen half a screen width to the right",
+    ),
+    (
+        "scroll-to-end",
+        KeyCommand::ScrollToEnd,
+        "Scroll a truncated value to its end, or back",
+    ),
+    (
+        "scrub-value",
+        KeyCommand::ScrubValue,
+        "Scroll a truncated value a chunk at a time with left and right",
+    ),
+    (
+        "cycle-truncation",
+        KeyCommand::CycleTruncation,
+        "Elide the middle, start, or end of a truncated value",
+    ),
+    (
+        "focused-line-to-top",
+        KeyCommand::FocusedLineToTop,
+        "Move the focused line to the top of the screen",
+    ),
+    (
+        "focused-line-to-center",
+        KeyCommand::FocusedLineToCenter,
+        "Move the focused line to the center of the screen",
+    ),
+    (
+        "focused-line-to-bottom",
+        KeyCommand::FocusedLineToBottom,
+        "Move the focused line to the bottom of the screen",
+    ),
+    (
+        "toggle-sort-keys",
+        KeyCommand::ToggleSortKeys,
+        "Toggle sorting object keys",
+    ),
+    (
+        "toggle-decoded-string",
+        KeyCommand::ToggleDecodedString,
+        "Show the JSON in a string as an object or array, or the string again",
+    ),
+    (
+        "transform-value",
+        KeyCommand::TransformValue,
+        "Show the focused value decoded, e.g., from base64, or the original",
+    ),
+    (
+        "toggle-wrap",
+        KeyCommand::ToggleWrap,
+        "Toggle wrapping the focused string",
+    ),
+    (
+        "toggle-wrap-all",
+        KeyCommand::ToggleWrapAll,
+        "Toggle wrapping all strings",
+    ),
+    (
+        "toggle-sizes",
+        KeyCommand::ToggleSizes,
+        "Toggle showing sizes of truncated values",
+    ),
+    (
+        "toggle-types",
+        KeyCommand::ToggleTypes,
+        "Toggle showing the types of values",
+    ),
+    (
+        "toggle-annotations",
+        KeyCommand::ToggleAnnotations,
+        "Toggle annotating values like dates with what they mean",
+    ),
+    (
+        "toggle-align-values",
+        KeyCommand::ToggleAlignValues,
+        "Toggle lining up the values of each object's members",
+    ),
+    (
+        "toggle-table-view",
+        KeyCommand::ToggleTableView,
+        "Toggle showing arrays of objects with the same keys as tables",
+    ),
+    (
+        "cycle-hidden-values",
+        KeyCommand::CycleHiddenValues,
+        "Hide null values, then empty objects and arrays too, or show them",
+    ),
+    (
+        "cycle-number-base",
+        KeyCommand::CycleNumberBase,
+        "Show integers in decimal, hex, or binary",
+    ),
+    (
+        "toggle-line-numbers",
+        KeyCommand::ToggleLineNumbers,
+        "Cycle the line number mode",
+    ),
+    (
+        "toggle-minimap",
+        KeyCommand::ToggleMinimap,
+        "Toggle showing a scrollbar with search matches",
+    ),
+    (
+        "toggle-indent-guides",
+        KeyCommand::ToggleIndentGuides,
+        "Toggle showing a guide line at each level of indentation",
+    ),
+    (
+        "toggle-fold-column",
+        KeyCommand::ToggleFoldColumn,
+        "Toggle marking which objects and arrays are collapsed in the gutter",
+    ),
+    (
+        "toggle-mouse",
+        KeyCommand::ToggleMouse,
+        "Toggle capturing the mouse",
+    ),
+    (
+        "set-mark",
+        KeyCommand::SetMark,
+        "Set a mark at the focused line",
+    ),
+    ("jump-to-mark", KeyCommand::JumpToMark, "Jump to a mark"),
+    (
+        "jump-to-anchor",
+        KeyCommand::JumpToAnchor,
+        "Jump from a YAML alias to its anchor, or from an anchor to an alias",
+    ),
+    (
+        "copy-value",
+        KeyCommand::CopyValue,
+        "Copy the focused value",
+    ),
+    (
+        "copy-one-line-value",
+        KeyCommand::CopyOneLineValue,
+        "Copy the focused value on one line",
+    ),
+    (
+        "copy-displayed-value",
+        KeyCommand::CopyDisplayedValue,
+        "Copy the focused value as displayed, without hidden values",
+    ),
+    (
+        "copy-decoded-value",
+        KeyCommand::CopyDecodedValue,
+        "Copy the decoded form of the focused value",
+    ),
+    (
+        "copy-key",
+        KeyCommand::CopyKey,
+        "Copy the focused key, or array index",
+    ),
+    (
+        "copy-path",
+        KeyCommand::CopyPath,
+        "Copy the path to the focused node",
+    ),
+    (
+        "copy-dot-path",
+        KeyCommand::CopyDotPath,
+        "Copy the .key style path to the focused node",
+    ),
+    (
+        "copy-bracket-path",
+        KeyCommand::CopyBracketPath,
+        "Copy the bracket style path to the focused node",
+    ),
+    (
+        "copy-jq-path",
+        KeyCommand::CopyJqPath,
+        "Copy the jq path to the focused node",
+    ),
+    (
+        "copy-gron-path",
+        KeyCommand::CopyGronPath,
+        "Copy a gron style assignment of the focused value",
+    ),
+    (
+        "copy-plain-path",
+        KeyCommand::CopyPlainPath,
+        "Copy the plain path to the focused node",
+    ),
+    (
+        "copy-source",
+        KeyCommand::CopySource,
+        "Copy the focused value's text from the input, as is",
+    ),
+    (
+        "show-source-position",
+        KeyCommand::ShowSourcePosition,
+        "Show where the focused value is in the input",
+    ),
+    (
+        "show-keybindings",
+        KeyCommand::ShowKeybindings,
+        "Show these keybindings",
+    ),
+    (
+        "command-palette",
+        KeyCommand::CommandPalette,
+        "Find a command by its name or description, and run it",
+    ),
+];
+
+// Digits are always used for counts, and typing 0 without a count
+// focuses the first sibling, like ^. Ctrl-C always quits.
+const DEFAULT_BINDINGS: &[(&str, KeyCommand)] = &[
+    ("q", KeyCommand::Quit),
+    ("Z Z", KeyCommand::ForceQuit),
+    ("Z Q", KeyCommand::QuitWithoutSavingState),
+    ("Z P", KeyCommand::QuitAndPrintValue),
+    ("f1", KeyCommand::Help),
+    ("esc", KeyCommand::Cancel),
+    (":", KeyCommand::Command),
+    ("&", KeyCommand::Filter),
+    ("k", KeyCommand::Up),
+    ("up", KeyCommand::Up),
+    ("ctrl-p", KeyCommand::Up),
+    ("backspace", KeyCommand::Up),
+    ("j", KeyCommand::Down),
+    ("down", KeyCommand::Down),
+    ("ctrl-n", KeyCommand::Down),
+    ("enter", KeyCommand::Down),
+    ("ctrl-y", KeyCommand::ScrollUp),
+    ("ctrl-e", KeyCommand::ScrollDown),
+    ("ctrl-u", KeyCommand::HalfPageUp),
+    ("ctrl-d", KeyCommand::HalfPageDown),
+    ("ctrl-b", KeyCommand::PageUp),
+    ("pageup", KeyCommand::PageUp),
+    ("ctrl-f", KeyCommand::PageDown),
+    ("pagedown", KeyCommand::PageDown),
+    ("K", KeyCommand::PrevSibling),
+    ("J", KeyCommand::NextSibling),
+    ("{", KeyCommand::SkipToPrevSibling),
+    ("}", KeyCommand::SkipToNextSibling),
+    ("n", KeyCommand::NextMatch),
+    ("N", KeyCommand::PrevMatch),
+    (".", KeyCommand::ScrollRight),
+    (",", KeyCommand::ScrollLeft),
+    (";", KeyCommand::ScrollToEnd),
+    ("/", KeyCommand::SearchForward),
+    ("?", KeyCommand::SearchBackward),
+    ("*", KeyCommand::SearchKeyForward),
+    ("#", KeyCommand::SearchKeyBackward),
+    ("g", KeyCommand::Top),
+    ("home", KeyCommand::Top),
+    ("G", KeyCommand::Bottom),
+    ("end", KeyCommand::Bottom),
+    ("%", KeyCommand::MatchingPair),
+    ("]", KeyCommand::NextFile),
+    ("[", KeyCommand::PrevFile),
+    ("<", KeyCommand::DecreaseIndentation),
+    (">", KeyCommand::IncreaseIndentation),
+    ("h", KeyCommand::Left),
+    ("left", KeyCommand::Left),
+    ("l", KeyCommand::Right),
+    ("right", KeyCommand::Right),
+    ("H", KeyCommand::Parent),
+    ("b", KeyCommand::UpUntilDepthChange),
+    ("w", KeyCommand::DownUntilDepthChange),
+    ("^", KeyCommand::FirstSibling),
+    ("$", KeyCommand::LastSibling),
+    ("space", KeyCommand::ToggleCollapsed),
+    ("c", KeyCommand::CollapseSiblings),
+    ("e", KeyCommand::ExpandSiblings),
+    ("m", KeyCommand::ToggleMode),
+    ("S", KeyCommand::ToggleSortKeys),
+    ("W", KeyCommand::ToggleWrap),
+    ("I", KeyCommand::ToggleSizes),
+    ("T", KeyCommand::ToggleTypes),
+    ("L", KeyCommand::ToggleLineNumbers),
+    // 'm' is already used for toggling the mode, so unlike vim, marks
+    // are set using 'M'.
+    ("M", KeyCommand::SetMark),
+    ("'", KeyCommand::JumpToMark),
+    ("`", KeyCommand::JumpToMark),
+    ("z a", KeyCommand::JumpToAnchor),
+    ("z t", KeyCommand::FocusedLineToTop),
+    ("z z", KeyCommand::FocusedLineToCenter),
+    ("z b", KeyCommand::FocusedLineToBottom),
+    ("z s", KeyCommand::MatchSiblings),
+    ("z f", KeyCommand::CollapseOtherSiblings),
+    ("z O", KeyCommand::ExpandSubtree),
+    ("z C", KeyCommand::CollapseSubtree),
+    ("z M", KeyCommand::CollapseToDepth),
+    ("z R", KeyCommand::ExpandToDepth),
+    ("z h", KeyCommand::ScrollScreenLeft),
+    ("z l", KeyCommand::ScrollScreenRight),
+    ("z H", KeyCommand::ScrollScreenHalfLeft),
+    ("z L", KeyCommand::ScrollScreenHalfRight),
+    ("z W", KeyCommand::ToggleWrapAll),
+    ("z m", KeyCommand::ToggleMouse),
+    ("z |", KeyCommand::ToggleMinimap),
+    ("z i", KeyCommand::ToggleIndentGuides),
+    ("z F", KeyCommand::ToggleFoldColumn),
+    ("z #", KeyCommand::CycleNumberBase),
+    ("z =", KeyCommand::ToggleAlignValues),
+    ("z T", KeyCommand::ToggleTableView),
+    ("z A", KeyCommand::ToggleAnnotations),
+    ("z n", KeyCommand::CycleHiddenValues),
+    ("z j", KeyCommand::ToggleDecodedString),
+    ("z d", KeyCommand::TransformValue),
+    ("z ;", KeyCommand::CycleTruncation),
+    ("z .", KeyCommand::ScrubValue),
+    ("z c", KeyCommand::CollapseParent),
+    ("z ?", KeyCommand::ShowKeybindings),
+    ("z p", KeyCommand::CommandPalette),
+    ("y y", KeyCommand::CopyValue),
+    ("y v", KeyCommand::CopyOneLineValue),
+    ("y Y", KeyCommand::CopyDisplayedValue),
+    ("y d", KeyCommand::CopyDecodedValue),
+    ("y k", KeyCommand::CopyKey),
+    ("y p", KeyCommand::CopyPath),
+    ("y b", KeyCommand::CopyBracketPath),
+    ("y q", KeyCommand::CopyJqPath),
+    ("y P p", KeyCommand::CopyDotPath),
+    ("y P b", KeyCommand::CopyBracketPath),
+    ("y P q", KeyCommand::CopyJqPath),
+    ("y P g", KeyCommand::CopyGronPath),
+    ("y P .", KeyCommand::CopyPlainPath),
+    ("y r", KeyCommand::CopySource),
+    ("ctrl-g", KeyCommand::ShowSourcePosition),
+];
+
+// Names of keys other than characters, which are written as
+// themselves, and ctrl-x and alt-x.
+const KEY_NAMES: &[(&str, Key)] = &[
+    ("space", Key::Char(' ')),
+    ("enter", Key::Char('\n')),
+    ("tab", Key::Char('\t')),
+    ("backtab", Key::BackTab),
+    ("esc", Key::Esc),
+    ("backspace", Key::Backspace),
+    ("delete", Key::Delete),
+    ("insert", Key::Insert),
+    ("up", Key::Up),
+    ("down", Key::Down),
+    ("left", Key::Left),
+    ("right", Key::Right),
+    ("home", Key::Home),
+    ("end", Key::End),
+    ("pageup", Key::PageUp),
+    ("pagedown", Key::PageDown),
+];
+
+// Prefixes of keys pressed with other modifiers, e.g., "ctrl-shift-x", in
+// the order they're written.
+const MODIFIER_NAMES: &[(&str, KeyModifiers)] = &[
+    ("ctrl-", KeyModifiers::CTRL),
+    ("alt-", KeyModifiers::ALT),
+    ("shift-", KeyModifiers::SHIFT),
+];
+
+pub enum Lookup {
+    Command(KeyCommand),
+    // The keys are the start of at least one longer binding.
+    Prefix,
+    Unbound,
+}
+
+impl Keybindings {
+    pub fn default() -> Keybindings {
+        let mut keybindings = Keybindings {
+            bindings: vec![],
+            macros: vec![],
+        };
+        for (keys, command) in DEFAULT_BINDINGS.iter() {
+            keybindings.bind(parse_keys(keys).unwrap(), Some(*command));
+        }
+        keybindings
+    }
+
+    // Load the keybindings from the given file, or from the default
+    // location if no file is specified. Keys that aren't bound by the
+    // file keep their default bindings.
+    pub fn load(path: Option<&Path>) -> Result<Keybindings, String> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_file() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Keybindings::default()),
+            },
+        };
+
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|err| format!("Unable to read keybindings {}: {}", path.display(), err))?;
+
+        Self::parse(&contents)
+            .map_err(|err| format!("Invalid keybindings {}: {}", path.display(), err))
+    }
+
+    /// Returns the default location of the keybindings file:
+    /// $XDG_CONFIG_HOME/jless/keys.toml, or ~/.config/jless/keys.toml.
+    pub fn default_file() -> Option<PathBuf> {
+        options::config_file("keys.toml")
+    }
+
+    pub fn parse(contents: &str) -> Result<Keybindings, String> {
+        let table = match contents.parse::<Toml>() {
+            Ok(Toml::Table(table)) => table,
+            Ok(_) => return Err("Expected a table of keybindings".to_owned()),
+            Err(err) => return Err(format!("{}", err)),
+        };
+
+        let mut keybindings = Keybindings::default();
+
+        // Macros are defined first, so that keys can be bound to them.
+        if let Some(macros) = table.get("macros") {
+            let macros = match macros {
+                Toml::Table(macros) => macros,
+                _ => return Err("macros: Expected a table of macros".to_owned()),
+            };
+            for (name, steps) in macros.iter() {
+                let steps = parse_macro(name, steps)?;
+                keybindings.macros.push(steps);
+            }
+        }
+
+        for (keys, value) in table.iter() {
+            if keys == "macros" {
+                continue;
+            }
+            let command = match value {
+                Toml::String(name) if name == "none" => None,
+                Toml::String(name) => match keybindings.command_by_name(name) {
+                    Some(command) => Some(command),
+                    None => return Err(format!("{}: Unknown command: {}", keys, name)),
+                },
+                _ => return Err(format!("{}: Expected the name of a command", keys)),
+            };
+            let keys = parse_keys(keys).map_err(|err| format!("{}: {}", keys, err))?;
+            keybindings.bind(keys, command);
+        }
+
+        Ok(keybindings)
+    }
+
+    // Binds a sequence of keys to a command, or unbinds them, removing
+    // any bindings that start with the keys, or that the keys start with.
+    fn bind(&mut self, keys: Vec<BoundKey>, command: Option<KeyCommand>) {
+        self.bindings.retain(|(bound_keys, _)| {
+            !bound_keys.starts_with(&keys) && !keys.starts_with(bound_keys)
+        });
+
+        if let Some(command) = command {
+            self.bindings.push((keys, command));
+        }
+    }
+
+    // Finds a command, or a macro, by name.
+    fn command_by_name(&self, name: &str) -> Option<KeyCommand> {
+        command_by_name(name).or_else(|| self.macro_by_name(name))
+    }
+
+    pub fn macro_by_name(&self, name: &str) -> Option<KeyCommand> {
+        self.macros
+            .iter()
+            .position(|m| m.name == name)
+            .map(KeyCommand::RunMacro)
+    }
+
+    pub fn macros(&self) -> &[Macro] {
+        &self.macros
+    }
+
+    // The name of a command, or of a macro, as used in the keybindings
+    // file.
+    pub fn command_name(&self, command: KeyCommand) -> &str {
+        match command {
+            KeyCommand::RunMacro(index) => &self.macros[index].name,
+            _ => command_name(command),
+        }
+    }
+
+    pub fn lookup(&self, keys: &[BoundKey]) -> Lookup {
+        let mut is_prefix = false;
+
+        for (bound_keys, command) in self.bindings.iter() {
+            if bound_keys.as_slice() == keys {
+                return Lookup::Command(*command);
+            }
+            is_prefix |= bound_keys.starts_with(keys);
+        }
+
+        if is_prefix {
+            Lookup::Prefix
+        } else {
+            Lookup::Unbound
+        }
+    }
+
+    // The keys that can follow a sequence of keys, and the commands that
+    // they run, when they finish a binding.
+    pub fn continuations(&self, keys: &[BoundKey]) -> Vec<(BoundKey, KeyCommand)> {
+        self.bindings
+            .iter()
+            .filter(|(bound_keys, _)| {
+                bound_keys.len() == keys.len() + 1 && bound_keys.starts_with(keys)
+            })
+            .map(|(bound_keys, command)| (*bound_keys.last().unwrap(), *command))
+            .collect()
+    }
+
+    // Lists the keys bound to each command, along with the name and
+    // description of the command, e.g.:
+    //
+    //   ("j, down, ^n, enter", "down", "Move focus down one line")
+    //
+    // Commands that aren't bound to any keys are omitted. Macros are
+    // listed after the other commands, with their steps.
+    pub fn cheatsheet(&self) -> Vec<(String, &str, &str)> {
+        self.commands()
+            .into_iter()
+            .filter(|(_, keys, _, _)| !keys.is_empty())
+            .map(|(_, keys, name, description)| (keys, name, description))
+            .collect()
+    }
+
+    // Every command, and macro, along with the keys bound to it (which
+    // may be none), and its name and description.
+    pub fn commands(&self) -> Vec<(KeyCommand, String, &str, &str)> {
+        let commands = COMMANDS
+            .iter()
+            .map(|(name, command, description)| (*command, *name, *description));
+        let macros = self.macros.iter().enumerate().map(|(index, m)| {
+            (
+                KeyCommand::RunMacro(index),
+                m.name.as_str(),
+                m.description.as_str(),
+            )
+        });
+
+        commands
+            .chain(macros)
+            .map(|(command, name, description)| {
+                let keys = self
+                    .bindings
+                    .iter()
+                    .filter(|(_, bound)| *bound == command)
+                    .map(|(keys, _)| format_keys(keys))
+                    .collect::<Vec<String>>();
+
+                (command, keys.join(", "), name, description)
+            })
+            .collect()
+    }
+}
+
+fn command_by_name(name: &str) -> Option<KeyCommand> {
+    COMMANDS
+        .iter()
+        .find(|(command_name, _, _)| *command_name == name)
+        .map(|(_, command, _)| *command)
+}
+
+fn command_name(command: KeyCommand) -> &'static str {
+    COMMANDS
+        .iter()
+        .find(|(_, named_command, _)| *named_command == command)
+        .map(|(name, _, _)| *name)
+        .unwrap()
+}
+
+// A macro is a list of the names of commands, and of lines to enter at
+// the ':' prompt, which start with a ':'. Macros can't run other macros,
+// so they can't run forever.
+fn parse_macro(name: &str, steps: &Toml) -> Result<Macro, String> {
+    if command_by_name(name).is_some() || name == "none" {
+        return Err(format!("{}: Macros can't have the name of a command", name));
+    }
+    let steps = match steps {
+        Toml::Array(steps) if !steps.is_empty() => steps,
+        _ => return Err(format!("{}: Expected a list of commands", name)),
+    };
+
+    let mut parsed_steps = vec![];
+    let mut descriptions = vec![];
+    for step in steps.iter() {
+        let step = match step {
+            Toml::String(step) => step.trim(),
+            _ => return Err(format!("{}: Expected the name of a command", name)),
+        };
+        let parsed_step = match step.strip_prefix(':') {
+            Some(line) if line.split_whitespace().next() == Some("run") => {
+                return Err(format!("{}: Macros can't run other macros", name));
+            }
+            Some(line) => MacroStep::CommandLine(line.trim().to_owned()),
+            None => match command_by_name(step) {
+                Some(command) => MacroStep::Command(command),
+                None => return Err(format!("{}: Unknown command: {}", name, step)),
+            },
+        };
+        parsed_steps.push(parsed_step);
+        descriptions.push(step);
+    }
+
+    Ok(Macro {
+        name: name.to_owned(),
+        steps: parsed_steps,
+        description: format!("Run: {}", descriptions.join(", ")),
+    })
+}
+
+fn parse_keys(keys: &str) -> Result<Vec<BoundKey>, String> {
+    let keys = keys
+        .split_whitespace()
+        .map(parse_key)
+        .collect::<Result<Vec<BoundKey>, String>>()?;
+
+    if keys.is_empty() {
+        return Err("Expected at least one key".to_owned());
+    }
+    if let BoundKey::Key(Key::Char('0'..='9')) = keys[0] {
+        return Err("Digits are used for counts, and can't be bound".to_owned());
+    }
+
+    Ok(keys)
+}
+
+fn parse_key(key: &str) -> Result<BoundKey, String> {
+    let mut chars = key.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return Ok(BoundKey::Key(Key::Char(ch)));
+    }
+
+    let lowercase = key.to_ascii_lowercase();
+
+    if let Some((_, named_key)) = KEY_NAMES.iter().find(|(name, _)| *name == lowercase) {
+        return Ok(BoundKey::Key(*named_key));
+    }
+
+    if let Some(n) = lowercase.strip_prefix('f') {
+        if let Ok(n @ 1..=12) = n.parse::<u8>() {
+            return Ok(BoundKey::Key(Key::F(n)));
+        }
+    }
+
+    let modified_char = |prefix: &str| {
+        let mut chars = key.get(prefix.len()..)?.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => Some(ch),
+            _ => None,
+        }
+    };
+
+    if lowercase.starts_with("ctrl-") {
+        if let Some(ch) = modified_char("ctrl-") {
+            return Ok(BoundKey::Key(Key::Ctrl(ch.to_ascii_lowercase())));
+        }
+    } else if lowercase.starts_with("alt-") {
+        if let Some(ch) = modified_char("alt-") {
+            return Ok(BoundKey::Key(Key::Alt(ch)));
+        }
+    }
+
+    // Other combinations of modifiers, e.g., "shift-enter" or "ctrl-alt-x",
+    // of characters and the keys the kitty keyboard protocol reports them
+    // for.
+    let mut modifiers = KeyModifiers::NONE;
+    let mut unmodified = key;
+    while let Some((name, modifier)) = MODIFIER_NAMES
+        .iter()
+        .find(|(name, _)| unmodified.to_ascii_lowercase().starts_with(name))
+    {
+        modifiers = modifiers.union(*modifier);
+        unmodified = &unmodified[name.len()..];
+    }
+    if modifiers != KeyModifiers::NONE {
+        let unmodified = match parse_key(unmodified) {
+            // Shifted characters are reported as the unshifted character.
+            Ok(BoundKey::Key(Key::Char(ch))) if modifiers.contains(KeyModifiers::SHIFT) => {
+                Some(Key::Char(ch.to_ascii_lowercase()))
+            }
+            Ok(BoundKey::Key(key @ (Key::Char(_) | Key::Esc | Key::Backspace))) => Some(key),
+            _ => None,
+        };
+        if let Some(unmodified) = unmodified {
+            return Ok(input::modified_key(unmodified, modifiers));
+        }
+    }
+
+    Err(format!("Unknown key: {}", key))
+}
+
+// How a key is shown in the status bar while typing a sequence of keys.
+pub fn format_key(key: BoundKey) -> String {
+    match key {
+        BoundKey::Key(key) => format_unmodified_key(key),
+        BoundKey::Modified(key, modifiers) => {
+            let mut formatted = MODIFIER_NAMES
+                .iter()
+                .filter(|(_, modifier)| modifiers.contains(*modifier))
+                .map(|(name, _)| *name)
+                .collect::<String>();
+            formatted.push_str(&format_unmodified_key(key));
+            formatted
+        }
+    }
+}
+
+fn format_unmodified_key(key: Key) -> String {
+    if let Some((name, _)) = KEY_NAMES.iter().find(|(_, named_key)| *named_key == key) {
+        return (*name).to_owned();
+    }
+
+    match key {
+        Key::Char(ch) => ch.to_string(),
+        Key::Ctrl(ch) => format!("^{}", ch),
+        Key::Alt(ch) => format!("M-{}", ch),
+        Key::F(n) => format!("F{}", n),
+        _ => format!("{:?}", key),
+    }
+}
+
+// Sequences of characters are shown together, like "yP", but sequences
+// including named keys are separated by spaces, like "z esc".
+fn format_keys(keys: &[BoundKey]) -> String {
+    let formatted = keys
+        .iter()
+        .map(|key| format_key(*key))
+        .collect::<Vec<String>>();
+
+    if formatted.iter().all(|key| key.chars().count() == 1) {
+        formatted.concat()
+    } else {
+        formatted.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[track_caller]
+    fn assert_command(keybindings: &Keybindings, keys: &str, expected: Option<KeyCommand>) {
+        let command = match keybindings.lookup(&parse_keys(keys).unwrap()) {
+            Lookup::Command(command) => Some(command),
+            _ => None,
+        };
+        assert_eq!(expected, command, "{}", keys);
+    }
+
+    #[test]
+    fn test_default_bindings() {
+        let keybindings = Keybindings::default();
+
+        // None of the defaults conflict with each other.
+        assert_eq!(DEFAULT_BINDINGS.len(), keybindings.bindings.len());
+
+        assert_command(&keybindings, "j", Some(KeyCommand::Down));
+        assert_command(&keybindings, "enter", Some(KeyCommand::Down));
+        assert_command(&keybindings, "ctrl-d", Some(KeyCommand::HalfPageDown));
+        assert_command(&keybindings, "y P g", Some(KeyCommand::CopyGronPath));
+        assert_command(
+            &keybindings,
+            "Z Q",
+            Some(KeyCommand::QuitWithoutSavingState),
+        );
+        assert!(matches!(
+            keybindings.lookup(&[BoundKey::Key(Key::Char('y')), BoundKey::Key(Key::Char('P'))]),
+            Lookup::Prefix
+        ));
+        assert!(matches!(
+            keybindings.lookup(&[BoundKey::Key(Key::Char('z')), BoundKey::Key(Key::Char('x'))]),
+            Lookup::Unbound
+        ));
+
+        for (_, command, _) in COMMANDS.iter() {
+            assert!(DEFAULT_BINDINGS.iter().any(|(_, bound)| bound == command));
+        }
+    }
+
+    #[test]
+    fn test_parse_keybindings() {
+        let keybindings = Keybindings::parse(indoc! {r#"
+            "ctrl-j" = "down"
+            "space" = "page-down"
+            "y" = "copy-value"
+            "j" = "none"
+            "g g" = "top"
+            "F2" = "help"
+        "#})
+        .unwrap();
+
+        assert_command(&keybindings, "ctrl-j", Some(KeyCommand::Down));
+        assert_command(&keybindings, "down", Some(KeyCommand::Down));
+        assert_command(&keybindings, "j", None);
+        assert_command(&keybindings, "space", Some(KeyCommand::PageDown));
+        assert_command(&keybindings, "y", Some(KeyCommand::CopyValue));
+        assert_command(&keybindings, "y y", None);
+        assert_command(&keybindings, "g g", Some(KeyCommand::Top));
+        assert_command(&keybindings, "f2", Some(KeyCommand::Help));
+        // Unchanged.
+        assert_command(&keybindings, "z t", Some(KeyCommand::FocusedLineToTop));
+
+        assert_eq!(
+            vec![(BoundKey::Key(Key::Char('g')), KeyCommand::Top)],
+            keybindings.continuations(&[BoundKey::Key(Key::Char('g'))])
+        );
+    }
+
+    #[test]
+    fn test_parse_modified_keys() {
+        let ctrl_shift = KeyModifiers::CTRL.union(KeyModifiers::SHIFT);
+        assert_eq!(
+            Ok(BoundKey::Modified(Key::Char('\n'), KeyModifiers::SHIFT)),
+            parse_key("shift-enter")
+        );
+        assert_eq!(
+            Ok(BoundKey::Modified(Key::Char('x'), ctrl_shift)),
+            parse_key("Ctrl-Shift-X")
+        );
+        assert_eq!(
+            Ok(BoundKey::Modified(Key::Esc, KeyModifiers::ALT)),
+            parse_key("alt-esc")
+        );
+        // Combinations termion's Key can represent are parsed the same way
+        // as when they're reported by the terminal.
+        assert_eq!(Ok(BoundKey::Key(Key::Ctrl('i'))), parse_key("ctrl-i"));
+        assert_eq!(Ok(BoundKey::Key(Key::Char('X'))), parse_key("shift-x"));
+        assert_eq!(Ok(BoundKey::Key(Key::BackTab)), parse_key("shift-tab"));
+        assert_eq!(Err("Unknown key: ctrl-up".to_owned()), parse_key("ctrl-up"));
+
+        let keybindings = Keybindings::parse(indoc! {r#"
+            "shift-enter" = "up"
+            "ctrl-i" = "top"
+            "z ctrl-enter" = "bottom"
+        "#})
+        .unwrap();
+
+        assert_command(&keybindings, "shift-enter", Some(KeyCommand::Up));
+        assert_command(&keybindings, "enter", Some(KeyCommand::Down));
+        assert_command(&keybindings, "ctrl-i", Some(KeyCommand::Top));
+        assert_command(&keybindings, "z ctrl-enter", Some(KeyCommand::Bottom));
+        assert_eq!(
+            "z ctrl-enter",
+            format_keys(&parse_keys("z ctrl-enter").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_keybindings() {
+        let error = |contents| Keybindings::parse(contents).map(|_| ()).unwrap_err();
+
+        assert_eq!("j: Unknown command: dwon", error(r#"j = "dwon""#));
+        assert_eq!("ctrl-: Unknown key: ctrl-", error(r#""ctrl-" = "down""#));
+        assert_eq!("j: Expected the name of a command", error("j = 1"));
+        assert_eq!(
+            "5: Digits are used for counts, and can't be bound",
+            error(r#"5 = "down""#)
+        );
+        assert_eq!(": Expected at least one key", error(r#""" = "down""#));
+
+        assert_eq!(
+            "m: Unknown command: dwon",
+            error("macros = { m = [\"dwon\"] }")
+        );
+        assert_eq!(
+            "m: Expected a list of commands",
+            error("macros = { m = [] }")
+        );
+        assert_eq!(
+            "top: Macros can't have the name of a command",
+            error("macros = { top = [\"bottom\"] }")
+        );
+        assert_eq!(
+            "m: Macros can't run other macros",
+            error("macros = { m = [\":run m\"] }")
+        );
+    }
+
+    #[test]
+    fn test_parse_macros() {
+        let keybindings = Keybindings::parse(indoc! {r#"
+            "z 1" = "tidy"
+
+            [macros]
+            tidy = ["collapse-siblings", ":collapse metadata ", "top"]
+        "#})
+        .unwrap();
+
+        assert_command(&keybindings, "z 1", Some(KeyCommand::RunMacro(0)));
+        assert_eq!(
+            Some(KeyCommand::RunMacro(0)),
+            keybindings.macro_by_name("tidy")
+        );
+        assert_eq!(None, keybindings.macro_by_name("top"));
+        assert_eq!("tidy", keybindings.command_name(KeyCommand::RunMacro(0)));
+        assert_eq!(
+            vec![
+                MacroStep::Command(KeyCommand::CollapseSiblings),
+                MacroStep::CommandLine("collapse metadata".to_owned()),
+                MacroStep::Command(KeyCommand::Top),
+            ],
+            keybindings.macros()[0].steps
+        );
+
+        let cheatsheet = keybindings.cheatsheet();
+        assert_eq!(
+            (
+                "z1".to_owned(),
+                "tidy",
+                "Run: collapse-siblings, :collapse metadata, top"
+            ),
+            *cheatsheet.last().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_format_key() {
+        assert_eq!("z", format_key(BoundKey::Key(Key::Char('z'))));
+        assert_eq!("space", format_key(BoundKey::Key(Key::Char(' '))));
+        assert_eq!("^d", format_key(BoundKey::Key(Key::Ctrl('d'))));
+        assert_eq!("F1", format_key(BoundKey::Key(Key::F(1))));
+        assert_eq!(
+            "ctrl-shift-enter",
+            format_key(BoundKey::Modified(
+                Key::Char('\n'),
+                KeyModifiers::CTRL.union(KeyModifiers::SHIFT)
+            ))
+        );
+
+        assert_eq!("yPg", format_keys(&parse_keys("y P g").unwrap()));
+        assert_eq!("z space", format_keys(&parse_keys("z space").unwrap()));
+    }
+
+    #[test]
+    fn test_cheatsheet() {
+        let keybindings = Keybindings::default();
+        let cheatsheet = keybindings.cheatsheet();
+        assert_eq!(COMMANDS.len(), cheatsheet.len());
+        assert_eq!(
+            (
+                "j, down, ^n, enter".to_owned(),
+                "down",
+                "Move focus down one line"
+            ),
+            cheatsheet[9]
+        );
+
+        let keybindings = Keybindings::parse(indoc! {r#"
+            "ctrl-j" = "down"
+            "enter" = "none"
+            "q" = "none"
+        "#})
+        .unwrap();
+        let cheatsheet = keybindings.cheatsheet();
+        assert_eq!(COMMANDS.len() - 1, cheatsheet.len());
+        assert_eq!("force-quit", cheatsheet[0].1);
+        assert_eq!("j, down, ^n, ^j", cheatsheet[8].0);
+    }
+}
@@ -0,0 +1,334 @@
+//! jless is a command-line JSON viewer, but its viewer can also be used by
+//! other programs, e.g., to view values they've already parsed, by
+//! implementing [`DataModel`] for them and calling [`view`]:
+//!
+//! ```no_run
+//! use std::borrow::Cow;
+//!
+//! use clap::Parser;
+//! use jless::options::Opt;
+//! use jless::{DataModel, Node};
+//!
+//! enum Config {
+//!     Port(u16),
+//!     Hosts(Vec<Config>),
+//!     Host(String),
+//! }
+//!
+//! struct ConfigDataModel;
+//!
+//! impl DataModel for ConfigDataModel {
+//!     type Value = Config;
+//!
+//!     fn node<'a>(&self, value: &'a Config) -> Node<'a, Config> {
+//!         match value {
+//!             Config::Port(port) => Node::Number(Cow::Owned(port.to_string())),
+//!             Config::Hosts(hosts) => Node::Array(hosts.iter().collect()),
+//!             Config::Host(host) => Node::String(Cow::Borrowed(host)),
+//!         }
+//!     }
+//! }
+//!
+//! let hosts = Config::Hosts(vec![Config::Host("localhost".to_owned())]);
+//! // Options are the same as jless's command line options.
+//! let opt = Opt::parse_from(["jless", "--mode", "line"]);
+//! jless::view(&ConfigDataModel, &hosts, "hosts", &opt).unwrap();
+//! ```
+
+// I don't like this rule because it changes the semantic
+// structure of the code.
+#![allow(clippy::collapsible_else_if)]
+
+extern crate lazy_static;
+extern crate libc_stdhandle;
+
+use std::io;
+use std::time::Duration;
+
+use termion::cursor::HideCursor;
+use termion::raw::IntoRawMode;
+use termion::screen::AlternateScreen;
+
+mod annotations;
+pub mod app;
+mod csvparser;
+pub mod datamodel;
+mod decoders;
+pub mod decompress;
+mod diff;
+mod export;
+#[cfg(feature = "url")]
+pub mod fetch;
+pub mod flatjson;
+mod highlighting;
+pub mod input;
+mod jq;
+mod jsonparser;
+mod jsontokenizer;
+mod keybindings;
+mod lineprinter;
+pub mod mmap;
+mod ndjson;
+pub mod options;
+mod osc52;
+mod palette;
+mod printable;
+pub mod printer;
+pub mod progress;
+mod prompt;
+mod screenwriter;
+mod search;
+mod snapshot;
+pub mod sniff;
+mod statecache;
+pub mod terminal;
+pub mod theme;
+mod tomlparser;
+mod truncatedstrview;
+mod types;
+mod viewer;
+mod yamlparser;
+
+use app::{App, InputFile, Selection};
+#[cfg(feature = "serde_json")]
+pub use datamodel::SerdeJson;
+pub use datamodel::{DataModel, Node};
+pub use flatjson::FlatJson;
+use input::{
+    BracketedPasteTerminal, FocusReportingTerminal, KittyKeyboardTerminal, MouseReportingTerminal,
+};
+use options::Opt;
+use snapshot::{Screen, SharedOutput};
+
+/// Views a value that's already been parsed, without printing it as JSON
+/// and parsing it again, until the user quits. The name is shown in place
+/// of a filename. Returns the focused node, to print with
+/// --print-path-on-exit or --print-value-on-exit.
+pub fn view<M: DataModel>(
+    model: &M,
+    value: &M::Value,
+    name: &str,
+    opt: &Opt,
+) -> Result<Option<Selection>, String> {
+    run(opt, vec![InputFile::from_value(model, value, name)])
+}
+
+/// Views inputs in the terminal until the user quits. Returns the focused
+/// node, to print with --print-path-on-exit or --print-value-on-exit.
+pub fn run(opt: &Opt, inputs: Vec<InputFile>) -> Result<Option<Selection>, String> {
+    // We use freopen to remap /dev/tty to STDIN so that rustyline works when
+    // JSON input is provided via STDIN. rustyline gets initialized when we
+    // create the App, so by putting this before creating the app, we make
+    // sure rustyline gets the /dev/tty input.
+    input::remap_dev_tty_to_stdin().map_err(interactive_terminal_error)?;
+
+    let raw_stdout = io::stdout()
+        .into_raw_mode()
+        .map_err(interactive_terminal_error)?;
+
+    let stdout = KittyKeyboardTerminal::new(
+        FocusReportingTerminal::from(BracketedPasteTerminal::from(MouseReportingTerminal::new(
+            HideCursor::from(AlternateScreen::from(raw_stdout)),
+            !opt.no_mouse,
+        ))),
+        opt.kitty_keyboard,
+    );
+
+    let mut app = App::new(opt, inputs, Box::new(stdout))?;
+
+    // The app needs to periodically check for more input while it's
+    // still being parsed, whether watched files have changed, and whether
+    // followed files have been added to. Searches on large inputs are also
+    // only updated while typing a pattern once there's a pause in the
+    // input.
+    let tick_ms = if app.is_loading() || opt.watch || opt.follow || opt.incsearch {
+        Some(app::TICK_MS)
+    } else {
+        None
+    };
+
+    // Dropping the app restores the terminal, including before returning
+    // an error.
+    let input = input::get_input(
+        opt.esc_delay_ms,
+        Duration::from_millis(opt.double_click_interval_ms as u64),
+        opt.input_buffer_size,
+        tick_ms,
+    )
+    .map_err(interactive_terminal_error)?;
+    Ok(app.run(Box::new(input)))
+}
+
+/// Renders what would be shown on a screen of the given size after the
+/// given keys are pressed, without using the terminal, e.g., for snapshot
+/// tests, or to generate images for documentation. The keys are the input
+/// a terminal would send, e.g., "jj\x1b[B" presses j twice and then the
+/// down arrow; prompts, e.g., for searches, can't be used. The screen is
+/// rendered as text with a line for each row, and escape sequences to
+/// style the text.
+pub fn render(
+    opt: &Opt,
+    inputs: Vec<InputFile>,
+    keys: &str,
+    width: u16,
+    height: u16,
+) -> Result<String, String> {
+    let output = SharedOutput::default();
+    let mut app = App::new_offscreen(opt, inputs, Box::new(output.clone()), width, height)?;
+    let events = input::events_from_bytes(keys.as_bytes());
+    app.run(Box::new(events.into_iter().map(Ok)));
+
+    let mut screen = Screen::new(width, height);
+    screen.write(&output.contents());
+    Ok(screen.contents())
+}
+
+pub fn interactive_terminal_error(err: io::Error) -> String {
+    format!("jless requires an interactive terminal: {}", err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use clap::Parser;
+
+    use super::*;
+    use options::{DataFormat, FormatSource};
+
+    #[test]
+    fn test_render() {
+        let opt = Opt::parse_from(["jless", "--no-color", "--mode", "line"]);
+        let input = InputFile::new(
+            r#"{"a": [1, 2], "b": "c"}"#.to_owned(),
+            DataFormat::Json,
+            FormatSource::Extension,
+            "input.json".to_owned(),
+            None,
+        );
+
+        // Move down to the array, and collapse it.
+        let screen = render(&opt, vec![input], "jh", 40, 6).unwrap();
+        assert_eq!(
+            screen,
+            "  {\n\
+             ▶   \x1b[0;1;7m\"a\"\x1b[0m: [1, 2],\n\
+             \x20   \"b\": \"c\"\n\
+             \x20 }\n\
+             \x1b[0;7minput.a     input.json [JSON: extension]\x1b[0m\n\
+             :"
+        );
+    }
+
+    #[test]
+    fn test_quit_and_print_value() {
+        let run = |args: &[&str], keys: &str| {
+            let opt = Opt::parse_from(args);
+            let input = InputFile::new(
+                r#"{"a": [1, {"b": 2}], "c": null}"#.to_owned(),
+                DataFormat::Json,
+                FormatSource::Extension,
+                "input.json".to_owned(),
+                None,
+            );
+            let mut app =
+                App::new_offscreen(&opt, vec![input], Box::new(SharedOutput::default()), 40, 6)
+                    .unwrap();
+            let events = input::events_from_bytes(keys.as_bytes());
+            app.run(Box::new(events.into_iter().map(Ok))).unwrap()
+        };
+
+        // Move down to the array, and quit.
+        let selection = run(&["jless"], "jZP");
+        assert_eq!(Some(".a".to_owned()), selection.path);
+        assert_eq!(
+            Some(Ok("[\n  1,\n  {\n    \"b\": 2\n  }\n]\n".to_owned())),
+            selection.value
+        );
+
+        let selection = run(&["jless", "--print-value-on-exit=compact"], "jq");
+        assert_eq!(Some(Ok("[1,{\"b\":2}]\n".to_owned())), selection.value);
+
+        // The value is only printed when asked for.
+        assert_eq!(None, run(&["jless"], "jq").value);
+    }
+
+    #[test]
+    fn test_source_position_with_invalid_utf8() {
+        let data = b"{\"a\":\"\xff\xfe\xfd\",\"b\":[1,2]}";
+        let path = std::env::temp_dir().join(format!("jless-invalid-{}.json", std::process::id()));
+        std::fs::write(&path, &data[..]).unwrap();
+
+        let status_line = |path: Option<PathBuf>| {
+            let opt = Opt::parse_from(["jless", "--no-color", "--mode", "line"]);
+            let (text, replacements) = app::decode_input(data.to_vec());
+            let input = InputFile::new(
+                text,
+                DataFormat::Json,
+                FormatSource::Extension,
+                "bad.json".to_owned(),
+                path,
+            )
+            .with_replacements(replacements);
+
+            // Move down to b[0], and show where it is.
+            let screen = render(&opt, vec![input], "jjj\x07", 60, 6).unwrap();
+            screen.lines().last().unwrap().to_owned()
+        };
+
+        // The offsets are in the original input, rather than in the input
+        // with the invalid UTF-8 replaced, both when the input was kept, and
+        // when the file is read again.
+        let expected = "Line 1, column 17 of the input (bytes 16..17)";
+        assert_eq!(expected, status_line(None));
+        assert_eq!(expected, status_line(Some(path.clone())));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_render_diff() {
+        let opt = Opt::parse_from(["jless", "--no-color", "--mode", "line", "--diff"]);
+        let input = |json: &str, filename: &str| {
+            InputFile::new(
+                json.to_owned(),
+                DataFormat::Json,
+                FormatSource::Extension,
+                filename.to_owned(),
+                None,
+            )
+        };
+        let inputs = vec![
+            input(r#"{"a":1,"b":[1,2,3],"c":{"d":"x"}}"#, "old.json"),
+            input(r#"{"a":2,"b":[1,3],"e":true,"c":{"d":"x"}}"#, "new.json"),
+        ];
+
+        // Rows with the same path are next to each other, and the other
+        // side of rows only in one input is left blank.
+        let screen = render(&opt, inputs, "", 60, 14).unwrap();
+        let lines: Vec<(&str, &str)> = screen
+            .lines()
+            .skip(1)
+            .take(10)
+            .map(|line| {
+                let (left, right) = line.split_at(line.find('│').unwrap());
+                (left.trim_end(), right['│'.len_utf8()..].trim_end())
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                ("!     \"a\": 1,", "!     \"a\": 2,"),
+                ("!     \"b\": [", "!     \"b\": ["),
+                ("        1,", "        1,"),
+                ("!       2,", "!       3"),
+                ("-       3", ""),
+                ("!     ],", "!     ],"),
+                ("", "+     \"e\": true,"),
+                ("      \"c\": {", "      \"c\": {"),
+                ("        \"d\": \"x\"", "        \"d\": \"x\""),
+                ("      }", "      }"),
+            ]
+        );
+    }
+}
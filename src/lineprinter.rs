@@ -5,13 +5,18 @@ use std::iter::Peekable;
 use std::ops::Range;
 
 use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
 
-use crate::flatjson::{FlatJson, OptionIndex, Row, Value};
+use crate::annotations::Annotator;
+use crate::decoders::Scalar;
+use crate::flatjson::{FlatJson, Index, OptionIndex, Row, Value};
 use crate::highlighting;
+use crate::options::NumberBase;
+use crate::printable;
 use crate::search::MatchRangeIter;
-use crate::terminal;
-use crate::terminal::{Color, Style, Terminal};
-use crate::truncatedstrview::TruncatedStrView;
+use crate::terminal::{Style, Terminal};
+use crate::theme::Theme;
+use crate::truncatedstrview::{TruncatedStrSlice, TruncatedStrView, Truncation};
 use crate::viewer::Mode;
 
 // This module is responsible for printing single lines of JSON to
@@ -57,7 +62,7 @@ use crate::viewer::Mode;
 //
 //
 // Naturally, there may be cases where an entire line does not fit
-// on the screen without wrapping. Rather than implement line
+// on the screen without wrapping. Rather than implement general line
 // wrapping (which seems difficult), we truncate values and show
 // ellipses to indicate truncated content. When printing out multiple
 // values, such as the key and value of an Object entry, the index
@@ -77,13 +82,29 @@ use crate::viewer::Mode;
 //                   [10…]: >|
 //                     "d": >|
 //                          >|
+//
+// The one exception is that long string values can optionally be
+// wrapped across multiple lines of the screen. The continuation
+// lines are aligned with the start of the string:
+//
+//     key: "long text that  |
+//           wraps onto mul  |
+//           tiple lines"    |
 
 const FOCUSED_LINE: &str = "▶ ";
 const FOCUSED_COLLAPSED_CONTAINER: &str = "▶ ";
 const FOCUSED_EXPANDED_CONTAINER: &str = "▼ ";
 const COLLAPSED_CONTAINER: &str = "▷ ";
 const EXPANDED_CONTAINER: &str = "▽ ";
-const INDICATOR_WIDTH: usize = 2;
+pub const INDICATOR_WIDTH: usize = 2;
+const INDENT_GUIDE: &str = "│";
+const COMMENT_SEPARATOR: &str = "  ";
+// Don't bother wrapping strings if there's less than this much room
+// for each line.
+const MIN_WRAP_WIDTH: isize = 10;
+// The most a column of a table can be widened to fit the values in it.
+const MAX_TABLE_COLUMN_WIDTH: usize = 30;
+const TABLE_COLUMN_SEPARATOR: &str = "  ";
 
 lazy_static::lazy_static! {
     pub static ref JS_IDENTIFIER: Regex = Regex::new("^[_$a-zA-Z][_$a-zA-Z0-9]*$").unwrap();
@@ -138,6 +159,16 @@ pub struct LinePrinter<'a, 'b> {
     // Width of the terminal and how much we should indent the line.
     pub width: usize,
     pub indentation: usize,
+    // The indentation of each level of nesting to draw a guide line at.
+    pub indent_guides: &'a [usize],
+
+    // Number of columns at the start of the line used by the line number
+    // gutter; the rest of the line is shifted over by this much. (This is
+    // not included in the width.)
+    pub gutter_width: usize,
+
+    // Colors to use when printing.
+    pub theme: &'a Theme,
 
     // Line-by-line formatting options
     pub focused: bool,
@@ -150,12 +181,86 @@ pub struct LinePrinter<'a, 'b> {
 
     // For remembering horizontal scroll positions of long lines.
     pub cached_truncated_value: Option<Entry<'a, usize, TruncatedStrView>>,
+
+    // Whether a string value that doesn't fit on the screen should be
+    // wrapped across multiple lines instead of truncated.
+    pub wrap: bool,
+
+    // Whether to show the length of truncated strings and the number
+    // of elements in collapsed containers.
+    pub show_sizes: bool,
+
+    // Whether to show the type of scalar values after them.
+    pub show_types: bool,
+
+    // Annotates values in common formats after them, when annotations
+    // are shown.
+    pub annotator: Option<&'a Annotator>,
+
+    // When values are aligned, the width of the widest label among the
+    // row's siblings (see label_width), which shorter labels are padded to.
+    pub aligned_label_width: Option<usize>,
+
+    // Which part of a value that doesn't fit is elided, and the character
+    // shown in place of elided text.
+    pub truncation: Truncation,
+    pub ellipsis: char,
+
+    // How many columns of long values are looked at, at most, so that
+    // drawing them doesn't take longer the longer they are, or None to
+    // always look at the whole value.
+    pub max_value_width: Option<usize>,
+
+    // The base integer values are shown in.
+    pub number_base: NumberBase,
+
+    // Whether the row is an object or array that was decoded from the JSON
+    // in a string, which is marked after its opening character or preview.
+    pub decoded: bool,
+
+    // The name of a decoder and the decoded form of a scalar value, which
+    // is shown in place of the value, followed by the decoder's name.
+    pub transformed_value: Option<(&'static str, String)>,
+
+    // The table the row is the header, or a row, of.
+    pub table: Option<&'a Table>,
+}
+
+// With the table view, an array of objects with the same keys (see
+// FlatJson::is_table) is shown as a table: the row of the array shows the
+// keys in place of its preview, and each collapsed object in it shows
+// just its values, lined up in columns under the keys.
+pub struct Table {
+    // The column the table starts at, after the labels of the array and
+    // of its elements.
+    pub start_col: usize,
+    pub column_widths: Vec<usize>,
+}
+
+// How a long string value is split across multiple lines.
+struct WrappedString {
+    // The column each line of the string starts at (after the opening
+    // quote on the first line).
+    col: usize,
+    // The ranges of the string (not including its quotes) shown on each
+    // line.
+    segments: Vec<Range<usize>>,
+    // Whether the closing quote (and trailing comma) didn't fit after the
+    // last segment, and needs to be printed on its own line.
+    close_on_own_line: bool,
+}
+
+impl WrappedString {
+    fn num_lines(&self) -> usize {
+        self.segments.len() + self.close_on_own_line as usize
+    }
 }
 
 impl<'a, 'b> LinePrinter<'a, 'b> {
     pub fn print_line(&mut self) -> fmt::Result {
         self.terminal.reset_style()?;
 
+        self.print_indent_guides()?;
         self.print_focus_and_container_indicators()?;
 
         let label_depth = INDICATOR_WIDTH + self.indentation;
@@ -165,8 +270,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         // will still be cases where this condition is true, but we still end
         // up printing the truncated indicator, but that's fine.
         if label_depth < self.width {
-            self.terminal
-                .position_cursor_col((1 + label_depth) as u16)?;
+            self.position_cursor_col(1 + label_depth)?;
         }
 
         let mut available_space = self.width as isize - label_depth as isize;
@@ -177,12 +281,65 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
 
         if self.has_label() && space_used_for_label == 0 {
             self.print_truncated_indicator()?;
+        } else if let Some(table) = self.table {
+            let col = label_depth + space_used_for_label as usize;
+            self.fill_in_table_row(table, col, available_space)?;
+        } else if let Some(wrapped) = self.wrapped_string() {
+            self.print_wrapped_string_line(&wrapped, 0)?;
         } else {
+            available_space -= self.print_anchor_marker(available_space)?;
             let space_used_for_value = self.fill_in_value(available_space)?;
 
             if space_used_for_value == 0 {
                 self.print_truncated_indicator()?;
+            } else {
+                self.print_comment(available_space - space_used_for_value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // The number of lines on the screen needed to print this line, which
+    // is only more than one when wrapping a long string.
+    pub fn num_lines(&self) -> usize {
+        self.wrapped_string()
+            .map_or(1, |wrapped| wrapped.num_lines())
+    }
+
+    // Print one of the lines after the first of a wrapped string. The
+    // cursor should already be at the start of the line.
+    pub fn print_wrapped_line(&mut self, line: usize) -> fmt::Result {
+        self.terminal.reset_style()?;
+        self.print_indent_guides()?;
+
+        match self.wrapped_string() {
+            Some(wrapped) => self.print_wrapped_string_line(&wrapped, line),
+            None => Ok(()),
+        }
+    }
+
+    // Move the cursor to a (1-based) column of the line, after the gutter.
+    fn position_cursor_col(&mut self, col: usize) -> fmt::Result {
+        self.terminal
+            .position_cursor_col((self.gutter_width + col) as u16)
+    }
+
+    // Guide lines are drawn below the container indicators in data mode,
+    // and below the opening braces and brackets in line mode.
+    fn print_indent_guides(&mut self) -> fmt::Result {
+        let offset = match self.mode {
+            Mode::Line => INDICATOR_WIDTH,
+            Mode::Data => 0,
+        };
+
+        for &indentation in self.indent_guides {
+            if offset + indentation >= self.width {
+                break;
             }
+            self.position_cursor_col(1 + offset + indentation)?;
+            self.terminal.set_style(&self.theme.dimmed)?;
+            write!(self.terminal, "{}", INDENT_GUIDE)?;
         }
 
         Ok(())
@@ -197,7 +354,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
 
     fn print_focused_line_indicator(&mut self) -> fmt::Result {
         if self.focused {
-            self.terminal.position_cursor_col(1)?;
+            self.position_cursor_col(1)?;
             write!(self.terminal, "{}", FOCUSED_LINE)?;
         }
 
@@ -208,7 +365,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         if self.row.is_primitive() {
             // Print a focused indicator for top-level primitives.
             if self.focused && self.row.depth == 0 {
-                self.terminal.position_cursor_col(0)?;
+                self.position_cursor_col(1)?;
                 write!(self.terminal, "{}", FOCUSED_COLLAPSED_CONTAINER)?;
             }
             return Ok(());
@@ -223,8 +380,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             return Ok(());
         }
 
-        let container_indicator_col = (1 + self.indentation) as u16;
-        self.terminal.position_cursor_col(container_indicator_col)?;
+        self.position_cursor_col(1 + self.indentation)?;
 
         let indicator = match (self.focused, collapsed) {
             (true, true) => FOCUSED_COLLAPSED_CONTAINER,
@@ -251,6 +407,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         let mut dummy_search_matches = None;
 
         let (style, highlighted_style) = self.get_label_styles();
+        let duplicate_key_marker = self.duplicate_key_marker();
         let matches_iter = if self.row.key_range.is_some() {
             &mut self.search_matches
         } else {
@@ -263,6 +420,10 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         // Remove two characters for ": "
         available_space -= 2;
 
+        if let Some(marker) = &duplicate_key_marker {
+            available_space -= marker.len() as isize;
+        }
+
         // Remove one character for either ">" or a single character
         // of the value.
         available_space -= 1;
@@ -311,6 +472,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             highlighted_style,
             &mut matches,
             self.focused_search_match,
+            self.ellipsis,
         )?;
 
         // Print out end of label
@@ -324,13 +486,19 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             self.focused_search_match,
         )?;
 
+        if let Some(marker) = duplicate_key_marker {
+            self.terminal.set_style(&self.theme.dimmed)?;
+            write!(self.terminal, "{}", marker)?;
+            used_space += marker.len() as isize;
+        }
+
         // Print out separator between label and value
         highlighting::highlight_matches(
             self.terminal,
             ": ",
             object_separator_range_start,
             &highlighting::DEFAULT_STYLE,
-            &highlighting::SEARCH_MATCH_HIGHLIGHTED,
+            &self.theme.search_match,
             &mut matches,
             self.focused_search_match,
         )?;
@@ -338,9 +506,78 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         used_space += delimiter.width();
         used_space += 2;
 
+        // Line the value up with its siblings', as long as part of it still
+        // fits after the padding.
+        let padding = (self.label_padding() as isize).min(available_space - space_used_for_label);
+        for _ in 0..padding {
+            self.terminal.write_char(' ')?;
+        }
+        used_space += padding.max(0);
+
         Ok(used_space)
     }
 
+    // Marks a YAML anchor that aliases refer to, as "&name", or an alias,
+    // as "*name", before the value, if there's still room for part of the
+    // value after it. Returns how much space was used.
+    fn print_anchor_marker(&mut self, available_space: isize) -> Result<isize, fmt::Error> {
+        let anchor = match &self.row.anchor {
+            Some(anchor) => anchor,
+            None => return Ok(0),
+        };
+
+        let sigil = if anchor.is_alias { '*' } else { '&' };
+        let marker = format!("{}{} ", sigil, anchor.name);
+        let width = printable::width(&marker) as isize;
+        if available_space - width < 4 {
+            return Ok(0);
+        }
+
+        self.terminal.set_style(&self.theme.dimmed)?;
+        write!(self.terminal, "{}", marker)?;
+        Ok(width)
+    }
+
+    // Comments in YAML inputs (see --yaml-comments) are shown after the
+    // value, truncated to fit, if at least a few characters of them fit.
+    fn print_comment(&mut self, available_space: isize) -> fmt::Result {
+        let comment = match &self.row.comment {
+            Some(comment) => comment,
+            None => return Ok(()),
+        };
+
+        let available_space = available_space - COMMENT_SEPARATOR.len() as isize;
+        if available_space < 4 {
+            return Ok(());
+        }
+
+        let truncated_view = TruncatedStrView::init_start(comment, available_space);
+        self.terminal.set_style(&self.theme.dimmed)?;
+        write!(
+            self.terminal,
+            "{}{}",
+            COMMENT_SEPARATOR,
+            TruncatedStrSlice {
+                s: comment,
+                truncated_view: &truncated_view,
+            }
+        )
+    }
+
+    // A marker shown after the second and later uses of a duplicate key
+    // in an object, e.g., "#2", matching how they're shown in paths.
+    fn duplicate_key_marker(&self) -> Option<String> {
+        duplicate_key_marker(self.row)
+    }
+
+    // How many spaces to print after the label so that the value lines up
+    // with those of its siblings.
+    fn label_padding(&self) -> usize {
+        self.aligned_label_width.map_or(0, |width| {
+            width.saturating_sub(label_width(self.flatjson, self.mode, self.row))
+        })
+    }
+
     // Check if a line has a label. A line has a label if it has
     // a key, or if we are in data mode and we have a parent.
     fn has_label(&self) -> bool {
@@ -367,15 +604,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
 
         if let Some(key_range) = &self.row.key_range {
             let key_without_delimiter = &pretty_printed[key_range.start + 1..key_range.end - 1];
-            let key_open_delimiter = &pretty_printed[key_range.start..key_range.start + 1];
-
-            let mut delimiter = DelimiterPair::None;
-
-            if key_open_delimiter == "[" {
-                delimiter = DelimiterPair::Square;
-            } else if self.mode == Mode::Line || !JS_IDENTIFIER.is_match(key_without_delimiter) {
-                delimiter = DelimiterPair::Quote;
-            }
+            let delimiter = key_delimiter(self.mode, pretty_printed, key_range);
 
             (key_without_delimiter, Some(key_range.clone()), delimiter)
         } else {
@@ -388,26 +617,22 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         }
     }
 
-    fn get_label_styles(&self) -> (&'static Style, &'static Style) {
+    fn get_label_styles(&self) -> (&'a Style, &'a Style) {
+        let theme = self.theme;
+
         match self.label_type() {
             LabelType::Key => {
                 if self.focused {
-                    (
-                        &highlighting::INVERTED_BOLD_BLUE_STYLE,
-                        &highlighting::BOLD_INVERTED_STYLE,
-                    )
+                    (&theme.focused_key, &highlighting::BOLD_INVERTED_STYLE)
                 } else {
-                    (
-                        &highlighting::BLUE_STYLE,
-                        &highlighting::SEARCH_MATCH_HIGHLIGHTED,
-                    )
+                    (&theme.key, &theme.search_match)
                 }
             }
             LabelType::Index => {
                 let style = if self.focused {
                     &highlighting::BOLD_STYLE
                 } else {
-                    &highlighting::DIMMED_STYLE
+                    &theme.dimmed
                 };
 
                 // No match highlighting for index labels.
@@ -425,7 +650,6 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
 
         let mut value_ref = &self.flatjson.1[self.row.range.clone()];
         let mut quoted = false;
-        let color = Self::color_for_value_type(&self.row.value);
 
         // Strip quotes from strings.
         if self.row.is_string() {
@@ -433,6 +657,16 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             quoted = true;
         }
 
+        // Decoded values, and numbers shown in another base, are shown in
+        // place of the value as it appears in the input.
+        let (replacement, decoder_annotation) = match &self.transformed_value {
+            Some((name, value)) => (Some(value.clone()), Some(format!(" ({})", name))),
+            None => (self.integer_in_base(self.row), None),
+        };
+        if let Some(replacement) = &replacement {
+            value_ref = replacement;
+        }
+
         let mut used_space = 0;
 
         if quoted {
@@ -443,7 +677,60 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             available_space -= 1;
         }
 
-        let truncated_view = self.initialize_value_truncated_view_or_update_cached(available_space);
+        let mut size_annotation = None;
+        if self.show_sizes && quoted && !Self::fits(value_ref, available_space) {
+            let annotation = self.string_size_annotation(value_ref, available_space);
+            // Only show the annotation if we can still show part of the string.
+            if available_space - annotation.len() as isize >= 2 {
+                available_space -= annotation.len() as isize;
+                size_annotation = Some(annotation);
+            }
+        }
+
+        // Only show the type, or the decoder, if we can still show part of
+        // the string, or the entire value otherwise.
+        let needed_space = if quoted {
+            2
+        } else {
+            printable::width(value_ref) as isize
+        };
+        let mut type_hint = self.type_hint();
+        if let Some(hint) = &type_hint {
+            if available_space - hint.len() as isize >= needed_space {
+                available_space -= hint.len() as isize;
+            } else {
+                type_hint = None;
+            }
+        }
+        let mut decoder_annotation = decoder_annotation;
+        if let Some(annotation) = &decoder_annotation {
+            if available_space - annotation.len() as isize >= needed_space {
+                available_space -= annotation.len() as isize;
+            } else {
+                decoder_annotation = None;
+            }
+        }
+        // Values are only annotated if they can be shown in full.
+        let mut value_annotation = if replacement.is_some() {
+            None
+        } else {
+            self.value_annotation()
+        };
+        if let Some(annotation) = &value_annotation {
+            let needed_space = printable::width(value_ref) as isize;
+            if available_space - annotation.len() as isize >= needed_space {
+                available_space -= annotation.len() as isize;
+            } else {
+                value_annotation = None;
+            }
+        }
+
+        // The cached view refers to the value as it appears in the input.
+        let truncated_view = if replacement.is_some() {
+            TruncatedStrView::init(value_ref, available_space, self.truncation)
+        } else {
+            self.initialize_value_truncated_view_or_update_cached(available_space)
+        };
 
         let space_used_for_value = truncated_view.used_space();
         if space_used_for_value.is_none() {
@@ -459,10 +746,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         }
 
         // Print out the value.
-        let style = Style {
-            fg: color,
-            ..Style::default()
-        };
+        let mut style = *self.theme.value_style(&self.row.value);
 
         let delimiter = if quoted {
             DelimiterPair::Quote
@@ -474,12 +758,20 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             used_space += 2;
         }
 
+        let mut value_range = Some(self.row.range.clone());
+        if replacement.is_some() {
+            value_range = None;
+            if self.search_match_overlaps(&self.row.range) {
+                style = self.theme.search_match;
+            }
+        }
+
         self.highlight_delimited_and_truncated_item(
             delimiter,
             value_ref,
             &truncated_view,
-            Some(self.row.range.clone()),
-            (&style, &highlighting::SEARCH_MATCH_HIGHLIGHTED),
+            value_range,
+            (&style, &self.theme.search_match),
         )?;
 
         if self.trailing_comma {
@@ -487,16 +779,140 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             self.highlight_str(
                 ",",
                 Some(self.row.range.end),
-                (
-                    &highlighting::DEFAULT_STYLE,
-                    &highlighting::SEARCH_MATCH_HIGHLIGHTED,
-                ),
+                (&highlighting::DEFAULT_STYLE, &self.theme.search_match),
             )?;
         }
 
+        if let Some(annotation) = size_annotation {
+            used_space += self.print_annotation(&annotation)?;
+        }
+
+        if let Some(hint) = type_hint {
+            used_space += self.print_annotation(&hint)?;
+        }
+
+        if let Some(annotation) = decoder_annotation {
+            used_space += self.print_annotation(&annotation)?;
+        }
+
+        if let Some(annotation) = value_annotation {
+            used_space += self.print_annotation(&annotation)?;
+        }
+
         Ok(used_space)
     }
 
+    // With a number base other than decimal, the text shown for an integer
+    // value instead of the decimal form in the input.
+    fn integer_in_base(&self, row: &Row) -> Option<String> {
+        if self.number_base == NumberBase::Decimal || !matches!(row.value, Value::Number) {
+            return None;
+        }
+        integer_in_base(&self.flatjson.1[row.range.clone()], self.number_base)
+    }
+
+    // Numbers shown in a different base don't line up with the input, so
+    // they're highlighted as a whole if any search match overlaps them.
+    // This skips past matches before the range.
+    fn search_match_overlaps(&mut self, range: &Range<usize>) -> bool {
+        let matches = match self.search_matches.as_mut() {
+            Some(matches) => matches,
+            None => return false,
+        };
+
+        while let Some(search_match) = matches.peek() {
+            if range.start < search_match.end {
+                return search_match.start < range.end;
+            }
+            matches.next();
+        }
+
+        false
+    }
+
+    // The type of a scalar value, e.g., " str", shown after the value when
+    // show_types is set. Hints are only printed, and aren't part of the
+    // pretty printed input, so they don't affect searching.
+    fn type_hint(&self) -> Option<String> {
+        if !self.show_types {
+            return None;
+        }
+
+        let hint = match self.row.value {
+            Value::Null => "null",
+            Value::Boolean => "bool",
+            Value::String => "str",
+            Value::Number => {
+                let number = &self.flatjson.1[self.row.range.clone()];
+                if number.contains(|c| c == '.' || c == 'e' || c == 'E') {
+                    "float"
+                } else {
+                    "int"
+                }
+            }
+            _ => return None,
+        };
+
+        Some(format!(" {}", hint))
+    }
+
+    // What a value in a common format means, e.g., " (3 days ago)" for a
+    // date, when annotations are shown (see annotations).
+    fn value_annotation(&self) -> Option<String> {
+        let annotator = self.annotator?;
+        let text = &self.flatjson.1[self.row.range.clone()];
+        match self.row.value {
+            Value::String => annotator.annotate(Scalar::String(&text[1..text.len() - 1])),
+            Value::Number => annotator.annotate(Scalar::Number(text)),
+            _ => None,
+        }
+    }
+
+    // Whether a value fits in the available space, without looking past
+    // the end of the space.
+    fn fits(value: &str, available_space: isize) -> bool {
+        available_space >= 0
+            && printable::width_up_to(value, available_space as usize) as isize <= available_space
+    }
+
+    // The number of characters in a truncated string. With a maximum value
+    // width, only that many columns of it (or the available space, if
+    // that's more) are counted, and longer strings are shown like
+    // "(10000+ chars)".
+    fn string_size_annotation(&self, value: &str, available_space: isize) -> String {
+        let limit = match self.max_value_width {
+            Some(max_value_width) => max_value_width.max(available_space.max(0) as usize),
+            None => return Self::size_annotation(value.chars().count(), "char"),
+        };
+        match printable::count_chars_up_to(value, limit) {
+            (count, true) => Self::size_annotation(count, "char"),
+            (count, false) => format!(" ({}+ chars)", count),
+        }
+    }
+
+    // An annotation showing the size of a value, e.g., " (57 items)".
+    fn size_annotation(size: usize, unit: &str) -> String {
+        if size == 1 {
+            format!(" (1 {})", unit)
+        } else {
+            format!(" ({} {}s)", size, unit)
+        }
+    }
+
+    fn decoded_annotation(&self) -> Option<&'static str> {
+        if self.decoded && self.row.is_opening_of_container() {
+            Some(" (decoded)")
+        } else {
+            None
+        }
+    }
+
+    fn print_annotation(&mut self, annotation: &str) -> Result<isize, fmt::Error> {
+        self.terminal.set_style(&self.theme.dimmed)?;
+        write!(self.terminal, "{}", annotation)?;
+        Ok(annotation.len() as isize)
+    }
+
     // We use TruncatedStrViews to manage truncating values when they
     // are too long for the screen, and also to handle scrolling
     // horizontally through those long values.
@@ -540,7 +956,8 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
                         *tsv = tsv.resize(value_ref, available_space);
                     })
                     .or_insert_with(|| {
-                        let tsv = TruncatedStrView::init_start(value_ref, available_space);
+                        let tsv =
+                            TruncatedStrView::init(value_ref, available_space, self.truncation);
 
                         // If we're showing a line for the first time, we might
                         // need to focus on a search match that we just jumped to.
@@ -569,21 +986,230 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
                         tsv.focus(value_ref, &offset_focused_range)
                     })
             })
-            .unwrap_or_else(|| TruncatedStrView::init_start(value_ref, available_space))
+            .unwrap_or_else(|| TruncatedStrView::init(value_ref, available_space, self.truncation))
+    }
+
+    // Determine how to split a string value across multiple lines, if
+    // wrapping is enabled and it doesn't fit on one line.
+    fn wrapped_string(&self) -> Option<WrappedString> {
+        if !self.wrap || !self.row.is_string() {
+            return None;
+        }
+
+        let mut value_col = (INDICATOR_WIDTH + self.indentation) as isize;
+
+        if self.has_label() {
+            value_col += (label_width(self.flatjson, self.mode, self.row) + 2) as isize;
+            value_col += self.label_padding() as isize;
+        }
+
+        // Skip past the opening quote.
+        let col = value_col + 1;
+        let available_space = self.width as isize - col;
+        if available_space < MIN_WRAP_WIDTH {
+            return None;
+        }
+        let available_space = available_space as usize;
+
+        let range = &self.row.range;
+        let value_ref = &self.flatjson.1[range.start + 1..range.end - 1];
+        let closing_width =
+            1 + self.trailing_comma as usize + self.type_hint().map_or(0, |hint| hint.len());
+
+        if printable::width_up_to(value_ref, available_space) + closing_width <= available_space {
+            return None;
+        }
+
+        let mut segments = vec![];
+        let mut segment_start = 0;
+        let mut segment_width = 0;
+
+        for (i, grapheme) in value_ref.grapheme_indices(true) {
+            let width = printable::width(grapheme);
+            if segment_width + width > available_space && segment_width > 0 {
+                segments.push(segment_start..i);
+                segment_start = i;
+                segment_width = 0;
+            }
+            segment_width += width;
+        }
+        segments.push(segment_start..value_ref.len());
+
+        Some(WrappedString {
+            col: col as usize,
+            segments,
+            close_on_own_line: segment_width + closing_width > available_space,
+        })
+    }
+
+    // Print a single line of a wrapped string. The first line should be
+    // printed right after the label, and includes the opening quote.
+    fn print_wrapped_string_line(&mut self, wrapped: &WrappedString, line: usize) -> fmt::Result {
+        let flatjson = self.flatjson;
+        let range = self.row.range.clone();
+        let style = *self.theme.value_style(&self.row.value);
+        let styles = (&style, &self.theme.search_match);
+
+        if line == 0 {
+            self.highlight_str("\"", Some(range.start), styles)?;
+        } else {
+            self.position_cursor_col(1 + wrapped.col)?;
+        }
+
+        if let Some(segment) = wrapped.segments.get(line) {
+            let start = range.start + 1 + segment.start;
+            let end = range.start + 1 + segment.end;
+            self.highlight_str(&flatjson.1[start..end], Some(start), styles)?;
+        }
+
+        if line + 1 == wrapped.num_lines() {
+            self.highlight_str("\"", Some(range.end - 1), styles)?;
+
+            if self.trailing_comma {
+                self.highlight_str(
+                    ",",
+                    Some(range.end),
+                    (&highlighting::DEFAULT_STYLE, &self.theme.search_match),
+                )?;
+            }
+
+            if let Some(hint) = self.type_hint() {
+                self.print_annotation(&hint)?;
+            }
+        }
+
+        Ok(())
     }
 
-    fn color_for_value_type(value: &Value) -> Color {
-        debug_assert!(value.is_primitive());
+    // Prints the keys of a table, for the row of the array, or the values
+    // of one of its objects, each padded to the width of its column. The
+    // cursor should be at the given column, after the label.
+    fn fill_in_table_row(
+        &mut self,
+        table: &Table,
+        col: usize,
+        available_space: isize,
+    ) -> fmt::Result {
+        let mut available_space = available_space;
+        let padding = table.start_col.saturating_sub(col) as isize;
+        if padding >= available_space {
+            return self.print_truncated_indicator();
+        }
+        write!(self.terminal, "{:1$}", "", padding as usize)?;
+        available_space -= padding;
+
+        let is_header = self.row.is_array();
+        let mut member = if is_header {
+            self.flatjson[self.row.first_child().unwrap()].first_child()
+        } else {
+            self.row.first_child()
+        };
+
+        for (column, width) in table.column_widths.iter().enumerate() {
+            let index = match member {
+                OptionIndex::Index(index) => index,
+                OptionIndex::Nil => break,
+            };
+            member = self.flatjson[index].next_sibling;
+
+            if column > 0 {
+                // Leave room for at least one character of the value.
+                if available_space <= TABLE_COLUMN_SEPARATOR.len() as isize {
+                    return self.print_truncated_indicator();
+                }
+                self.terminal.reset_style()?;
+                self.terminal.write_str(TABLE_COLUMN_SEPARATOR)?;
+                available_space -= TABLE_COLUMN_SEPARATOR.len() as isize;
+            }
+
+            let space = (*width as isize).min(available_space);
+            let row = &self.flatjson[index];
+            let used_space = if is_header {
+                self.fill_in_table_key(row, space)?
+            } else {
+                self.fill_in_table_value(row, space)?
+            };
+            if used_space == 0 {
+                return self.print_truncated_indicator();
+            }
+            available_space -= used_space;
+
+            if member.is_some() {
+                let padding = (*width as isize - used_space).min(available_space).max(0);
+                self.terminal.reset_style()?;
+                write!(self.terminal, "{:1$}", "", padding as usize)?;
+                available_space -= padding;
+            }
+        }
+
+        Ok(())
+    }
 
-        match value {
-            Value::Null => terminal::LIGHT_BLACK,
-            Value::Boolean => terminal::YELLOW,
-            Value::Number => terminal::MAGENTA,
-            Value::String => terminal::GREEN,
-            Value::EmptyObject => terminal::WHITE,
-            Value::EmptyArray => terminal::WHITE,
-            _ => unreachable!(),
+    fn fill_in_table_key(
+        &mut self,
+        row: &Row,
+        available_space: isize,
+    ) -> Result<isize, fmt::Error> {
+        let key_range = row.key_range.clone().unwrap();
+        let key = &self.flatjson.1[key_range.start + 1..key_range.end - 1];
+        let truncated_view = TruncatedStrView::init_start(key, available_space);
+        let used_space = truncated_view.used_space().unwrap_or(0);
+        if used_space > 0 {
+            // The keys aren't part of the row's range, so they aren't
+            // highlighted when they match a search.
+            self.highlight_delimited_and_truncated_item(
+                DelimiterPair::None,
+                key,
+                &truncated_view,
+                None,
+                (&self.theme.key, &self.theme.key),
+            )?;
         }
+        Ok(used_space)
+    }
+
+    fn fill_in_table_value(
+        &mut self,
+        row: &Row,
+        available_space: isize,
+    ) -> Result<isize, fmt::Error> {
+        let (value, delimiter) = table_cell(self.flatjson, row, self.number_base);
+        let truncated_view =
+            TruncatedStrView::init_start(&value, available_space - delimiter.width());
+        let used_space = match truncated_view.used_space() {
+            Some(used_space) => used_space + delimiter.width(),
+            None => return Ok(0),
+        };
+
+        let (style, range) = if row.is_container() {
+            (self.theme.dimmed, None)
+        } else if self.integer_in_base(row).is_some() {
+            // Like other numbers shown in another base, these are
+            // highlighted as a whole if they match a search.
+            let style = if self.search_match_overlaps(&row.range) {
+                self.theme.search_match
+            } else {
+                *self.theme.value_style(&row.value)
+            };
+            (style, None)
+        } else {
+            (*self.theme.value_style(&row.value), Some(row.range.clone()))
+        };
+
+        self.highlight_delimited_and_truncated_item(
+            delimiter,
+            &value,
+            &truncated_view,
+            range,
+            (&style, &self.theme.search_match),
+        )?;
+
+        Ok(used_space)
+    }
+
+    fn preview_styles(&self) -> (&'a Style, &'a Style) {
+        let theme = self.theme;
+        (&theme.dimmed, &theme.preview_search_match)
     }
 
     // Print out an object value on a line. There are three main variables at
@@ -650,10 +1276,17 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             self.highlight_str(
                 row.value.container_type().unwrap().open_str(),
                 Some(self.row.range.start),
-                (style, &highlighting::SEARCH_MATCH_HIGHLIGHTED),
+                (style, &self.theme.search_match),
             )?;
 
-            Ok(1)
+            let mut used_space = 1;
+            if let Some(annotation) = self.decoded_annotation() {
+                if available_space > annotation.len() as isize {
+                    used_space += self.print_annotation(annotation)?;
+                }
+            }
+
+            Ok(used_space)
         } else {
             Ok(0)
         }
@@ -676,17 +1309,14 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             self.highlight_str(
                 row.value.container_type().unwrap().close_str(),
                 Some(self.row.range.start),
-                (style, &highlighting::SEARCH_MATCH_HIGHLIGHTED),
+                (style, &self.theme.search_match),
             )?;
 
             if self.trailing_comma {
                 self.highlight_str(
                     ",",
                     Some(self.row.range.end),
-                    (
-                        &highlighting::DEFAULT_STYLE,
-                        &highlighting::SEARCH_MATCH_HIGHLIGHTED,
-                    ),
+                    (&highlighting::DEFAULT_STYLE, &self.theme.search_match),
                 )?;
             }
 
@@ -705,6 +1335,31 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             available_space -= 1;
         }
 
+        let mut size_annotation = None;
+        if self.show_sizes && row.is_collapsed() {
+            let mut num_children = 0;
+            let mut child = row.first_child();
+            while let OptionIndex::Index(index) = child {
+                num_children += 1;
+                child = self.flatjson[index].next_sibling;
+            }
+
+            let annotation = Self::size_annotation(num_children, "item");
+            // Only show the annotation if there's room for at least "[…]".
+            if available_space - annotation.len() as isize >= 3 {
+                available_space -= annotation.len() as isize;
+                size_annotation = Some(annotation);
+            }
+        }
+
+        let mut decoded_annotation = None;
+        if let Some(annotation) = self.decoded_annotation() {
+            if available_space - annotation.len() as isize >= 3 {
+                available_space -= annotation.len() as isize;
+                decoded_annotation = Some(annotation);
+            }
+        }
+
         let always_quote_string_object_keys = self.mode == Mode::Line;
         let mut used_space =
             self.generate_container_preview(row, available_space, always_quote_string_object_keys)?;
@@ -715,14 +1370,18 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
                 self.highlight_str(
                     ",",
                     Some(self.row.range.end),
-                    (
-                        &highlighting::DEFAULT_STYLE,
-                        &highlighting::SEARCH_MATCH_HIGHLIGHTED,
-                    ),
+                    (&highlighting::DEFAULT_STYLE, &self.theme.search_match),
                 )?;
             }
         }
 
+        if let Some(annotation) = size_annotation {
+            used_space += self.print_annotation(&annotation)?;
+        }
+        if let Some(annotation) = decoded_annotation {
+            used_space += self.print_annotation(annotation)?;
+        }
+
         Ok(used_space)
     }
 
@@ -749,7 +1408,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         self.highlight_str(
             container_type.open_str(),
             Some(self.row.range.start),
-            highlighting::PREVIEW_STYLES,
+            self.preview_styles(),
         )?;
 
         num_printed += 1;
@@ -775,7 +1434,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
                 // No room for anything else, let's close out the object.
                 // If we're not the first child, the previous elem will have
                 // printed the ", " separator.
-                self.highlight_str("…", None, highlighting::PREVIEW_STYLES)?;
+                self.highlight_str("…", None, self.preview_styles())?;
 
                 // This variable isn't used again, but if it were, we'd need this
                 // line for correctness. Unfortunately Cargo check complains about it,
@@ -791,7 +1450,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
                     self.highlight_str(
                         ", ",
                         Some(self.flatjson[child].range.end),
-                        highlighting::PREVIEW_STYLES,
+                        self.preview_styles(),
                     )?;
                     available_space -= 2;
                     num_printed += 2;
@@ -807,7 +1466,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         self.highlight_str(
             container_type.close_str(),
             Some(self.row.range.end - 1),
-            highlighting::PREVIEW_STYLES,
+            self.preview_styles(),
         )?;
         num_printed += 1;
 
@@ -864,12 +1523,12 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
                 key_ref,
                 &truncated_view,
                 Some(key_range.clone()),
-                highlighting::PREVIEW_STYLES,
+                self.preview_styles(),
             )?;
 
             used_space += 2;
             available_space -= 2;
-            self.highlight_str(": ", Some(key_range.end), highlighting::PREVIEW_STYLES)?;
+            self.highlight_str(": ", Some(key_range.end), self.preview_styles())?;
         }
 
         let space_used_for_value = if is_only_child && row.value.is_container() {
@@ -883,7 +1542,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         // object key, but couldn't print out the value. Space was already
         // allocated for this at the start of the function.
         if row.key_range.is_some() && space_used_for_value == 0 {
-            self.terminal.write_char('…')?;
+            self.terminal.write_char(self.ellipsis)?;
             used_space += 1;
         }
 
@@ -899,6 +1558,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         let mut can_be_truncated = true;
         let mut showing_collapsed_preview = false;
 
+        let converted_number = self.integer_in_base(row);
         let value_ref = match &row.value {
             Value::OpenContainer { container_type, .. } => {
                 can_be_truncated = false;
@@ -911,7 +1571,10 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
                 let range = row.range.clone();
                 &self.flatjson.1[range.start + 1..range.end - 1]
             }
-            _ => &self.flatjson.1[row.range.clone()],
+            _ => match &converted_number {
+                Some(number) => number,
+                None => &self.flatjson.1[row.range.clone()],
+            },
         };
 
         if quoted {
@@ -920,7 +1583,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
 
         let space_used_for_quotes = if quoted { 2 } else { 0 };
 
-        let truncated_view = TruncatedStrView::init_start(value_ref, available_space);
+        let truncated_view = TruncatedStrView::init(value_ref, available_space, self.truncation);
         let space_used_for_value = truncated_view.used_space();
 
         if space_used_for_value.is_none() || truncated_view.is_completely_elided() {
@@ -940,32 +1603,38 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             self.highlight_str(
                 "\"",
                 Some(value_open_quote_range_start),
-                highlighting::PREVIEW_STYLES,
+                self.preview_styles(),
             )?;
         }
 
+        let mut style = self.theme.dimmed;
+        if converted_number.is_some() && self.search_match_overlaps(&row.range) {
+            style = self.theme.preview_search_match;
+        }
+
         highlighting::highlight_truncated_str_view(
             self.terminal,
             value_ref,
             &truncated_view,
             // Technically could try to highlight open and close delimiters
             // of the collapsed container, but not really worth it right now.
-            if showing_collapsed_preview {
+            if showing_collapsed_preview || converted_number.is_some() {
                 None
             } else {
                 Some(value_range_start)
             },
-            &highlighting::DIMMED_STYLE,
-            &highlighting::GRAY_INVERTED_STYLE,
+            &style,
+            &self.theme.preview_search_match,
             &mut self.search_matches.as_mut(),
             self.focused_search_match,
+            self.ellipsis,
         )?;
 
         if quoted {
             self.highlight_str(
                 "\"",
                 Some(value_close_quote_range_start),
-                highlighting::PREVIEW_STYLES,
+                self.preview_styles(),
             )?;
         }
 
@@ -973,12 +1642,12 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
     }
 
     fn print_truncated_indicator(&mut self) -> fmt::Result {
-        self.terminal.position_cursor_col(self.width as u16)?;
+        self.position_cursor_col(self.width)?;
         if self.focused {
             self.terminal.reset_style()?;
             self.terminal.set_bold(true)?;
         } else {
-            self.terminal.set_fg(terminal::LIGHT_BLACK)?;
+            self.terminal.set_fg(self.theme.muted_color)?;
         }
         write!(self.terminal, ">")
     }
@@ -1017,6 +1686,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             styles.1,
             &mut self.search_matches.as_mut(),
             self.focused_search_match,
+            self.ellipsis,
         )?;
 
         self.highlight_str(delimiter.right(), str_close_delimiter_range_start, styles)?;
@@ -1043,17 +1713,136 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use unicode_width::UnicodeWidthStr;
+// The delimiters shown around a key: object keys are only quoted in data
+// mode when they aren't identifiers.
+fn key_delimiter(mode: Mode, pretty_printed: &str, key_range: &Range<usize>) -> DelimiterPair {
+    let key_without_delimiter = &pretty_printed[key_range.start + 1..key_range.end - 1];
+    let key_open_delimiter = &pretty_printed[key_range.start..key_range.start + 1];
+
+    if key_open_delimiter == "[" {
+        DelimiterPair::Square
+    } else if mode == Mode::Line || !JS_IDENTIFIER.is_match(key_without_delimiter) {
+        DelimiterPair::Quote
+    } else {
+        DelimiterPair::None
+    }
+}
+
+fn duplicate_key_marker(row: &Row) -> Option<String> {
+    if row.key_occurrence == 0 {
+        return None;
+    }
+
+    Some(format!("#{}", row.key_occurrence + 1))
+}
+
+/// The width of the label a row is shown with, i.e., its key or index,
+/// including delimiters and the marker on duplicate keys, but not the ": "
+/// after it.
+pub fn label_width(flatjson: &FlatJson, mode: Mode, row: &Row) -> usize {
+    let width = match &row.key_range {
+        Some(key_range) => {
+            let key = &flatjson.1[key_range.start + 1..key_range.end - 1];
+            key_delimiter(mode, &flatjson.1, key_range).width() as usize + printable::width(key)
+        }
+        None => row.index.to_string().len() + 2,
+    };
+    width + duplicate_key_marker(row).map_or(0, |marker| marker.len())
+}
+
+// The width of each column of a table (see Table): the widest of its key
+// and the values under it, up to MAX_TABLE_COLUMN_WIDTH.
+pub fn table_column_widths(
+    flatjson: &FlatJson,
+    array: Index,
+    number_base: NumberBase,
+) -> Vec<usize> {
+    let mut widths = vec![];
+    let mut member = flatjson[flatjson[array].first_child().unwrap()].first_child();
+    while let OptionIndex::Index(index) = member {
+        let key_range = flatjson[index].key_range.as_ref().unwrap();
+        widths.push(printable::width(
+            &flatjson.1[key_range.start + 1..key_range.end - 1],
+        ));
+        member = flatjson[index].next_sibling;
+    }
+
+    let mut element = flatjson[array].first_child();
+    while let OptionIndex::Index(object) = element {
+        let mut member = flatjson[object].first_child();
+        for width in widths.iter_mut() {
+            let index = member.unwrap();
+            let (value, delimiter) = table_cell(flatjson, &flatjson[index], number_base);
+            *width = (*width).max(printable::width(&value) + delimiter.width() as usize);
+            member = flatjson[index].next_sibling;
+        }
+        element = flatjson[object].next_sibling;
+    }
+
+    widths
+        .into_iter()
+        .map(|width| width.min(MAX_TABLE_COLUMN_WIDTH))
+        .collect()
+}
+
+// How a value is shown in a table: scalars as they are in data mode, and
+// objects and arrays by just their delimiters.
+fn table_cell(flatjson: &FlatJson, row: &Row, number_base: NumberBase) -> (String, DelimiterPair) {
+    if let Some(container_type) = row.value.container_type() {
+        return (
+            container_type.collapsed_preview().to_owned(),
+            DelimiterPair::None,
+        );
+    }
+
+    let value = &flatjson.1[row.range.clone()];
+    if row.is_string() {
+        return (value[1..value.len() - 1].to_owned(), DelimiterPair::Quote);
+    }
+    if let Value::Number = row.value {
+        if let Some(value) = integer_in_base(value, number_base) {
+            return (value, DelimiterPair::None);
+        }
+    }
+    (value.to_owned(), DelimiterPair::None)
+}
+
+// Formats an integer in the given base, with a 0x or 0b prefix. Returns None
+// for decimal, for numbers that aren't integers, and for integers that
+// don't fit in 64 bits.
+fn integer_in_base(number: &str, base: NumberBase) -> Option<String> {
+    let digits = number.strip_prefix('-').unwrap_or(number);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let n: i128 = number.parse().ok()?;
+    if n < i64::MIN as i128 || n > u64::MAX as i128 {
+        return None;
+    }
+
+    let sign = if n < 0 { "-" } else { "" };
+    let magnitude = n.unsigned_abs();
+    match base {
+        NumberBase::Decimal => None,
+        NumberBase::Hex => Some(format!("{}0x{:x}", sign, magnitude)),
+        NumberBase::Binary => Some(format!("{}0b{:b}", sign, magnitude)),
+    }
+}
 
-    use crate::flatjson::{parse_top_level_json, parse_top_level_yaml};
+#[cfg(test)]
+mod tests {
+    use unicode_width::UnicodeWidthStr;
+
+    use crate::annotations::AnnotationFormat;
+    use crate::flatjson::{parse_top_level_json, parse_top_level_yaml, DEFAULT_MAX_DEPTH};
     use crate::terminal::test::{TextOnlyTerminal, VisibleEscapesTerminal};
     use crate::terminal::{BLUE, LIGHT_BLUE};
 
     use super::*;
 
     const DUMMY_RANGE: Range<usize> = 0..0;
+    static THEME: Theme = Theme::default();
 
     fn default_line_printer<'a>(
         terminal: &'a mut dyn Terminal,
@@ -1066,13 +1855,28 @@ mod tests {
             flatjson,
             row: &flatjson[index],
             indentation: 0,
+            indent_guides: &[],
             width: 100,
+            gutter_width: 0,
+            theme: &THEME,
             focused: false,
             focused_because_matching_container_pair: false,
             trailing_comma: false,
             search_matches: None,
             focused_search_match: &DUMMY_RANGE,
             cached_truncated_value: None,
+            wrap: false,
+            show_sizes: false,
+            show_types: false,
+            annotator: None,
+            aligned_label_width: None,
+            truncation: Truncation::End,
+            ellipsis: '…',
+            max_value_width: None,
+            number_base: NumberBase::Decimal,
+            decoded: false,
+            transformed_value: None,
+            table: None,
         }
     }
 
@@ -1102,6 +1906,36 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_indent_guides() -> std::fmt::Result {
+        const JSON: &str = r#"{ "a": { "b": 1 } }"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        let mut term = VisibleEscapesTerminal::new(true, false);
+        let mut line: LinePrinter = LinePrinter {
+            indentation: 8,
+            indent_guides: &[0, 4],
+            ..default_line_printer(&mut term, &fj, 2)
+        };
+
+        line.print_indent_guides()?;
+        assert_eq!("_C(1)_│_C(5)_│", line.terminal.output());
+        line.terminal.clear_output();
+
+        // In line mode, they're drawn below the opening braces.
+        line.mode = Mode::Line;
+        line.print_indent_guides()?;
+        assert_eq!("_C(3)_│_C(7)_│", line.terminal.output());
+        line.terminal.clear_output();
+
+        // Guides past the width of the screen aren't drawn.
+        line.width = 5;
+        line.print_indent_guides()?;
+        assert_eq!("_C(3)_│", line.terminal.output());
+
+        Ok(())
+    }
+
     #[test]
     fn test_data_mode_focus_indicators() -> std::fmt::Result {
         const JSON: &str = r#"{
@@ -1240,7 +2074,7 @@ mod tests {
             3: 3,
             null: 4,
         }"#;
-        let fj = parse_top_level_yaml(YAML.to_owned()).unwrap();
+        let fj = parse_top_level_yaml(YAML.to_owned(), false, DEFAULT_MAX_DEPTH).unwrap();
 
         let mut term = VisibleEscapesTerminal::new(false, false);
         let mut line: LinePrinter = LinePrinter {
@@ -1288,6 +2122,86 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_fill_aligned_key_label() -> std::fmt::Result {
+        const JSON: &str = r#"{ "a": 1, "french fry": 2, "a": 3 }"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        let widths: Vec<usize> = (1..4)
+            .map(|i| label_width(&fj, Mode::Data, &fj[i]))
+            .collect();
+        assert_eq!(vec![1, 12, 3], widths);
+        assert_eq!(3, label_width(&fj, Mode::Line, &fj[1]));
+
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = LinePrinter {
+            aligned_label_width: Some(12),
+            ..default_line_printer(&mut term, &fj, 1)
+        };
+
+        let used_space = line.fill_in_label(100)?;
+        assert_eq!("a:            ", line.terminal.output());
+        assert_eq!(14, used_space);
+
+        // Duplicate key markers are part of the label.
+        line.row = &line.flatjson[3];
+        line.terminal.clear_output();
+        let used_space = line.fill_in_label(100)?;
+        assert_eq!("a#2:          ", line.terminal.output());
+        assert_eq!(14, used_space);
+
+        // Labels aren't padded past the point where the value would no longer
+        // fit.
+        line.row = &line.flatjson[1];
+        line.terminal.clear_output();
+        let used_space = line.fill_in_label(8)?;
+        assert_eq!("a:     ", line.terminal.output());
+        assert_eq!(7, used_space);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_rows() -> std::fmt::Result {
+        // 0 1 2           3              4  5 6       7           8 9
+        // [ { "id": 1, "name": "alice" }, { "id": 22, "name": [] } ]
+        const JSON: &str = r#"[{"id": 1, "name": "alice"}, {"id": 22, "name": []}]"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        let column_widths = table_column_widths(&fj, 0, NumberBase::Decimal);
+        assert_eq!(vec![2, 7], column_widths);
+        let table = Table {
+            start_col: 4,
+            column_widths,
+        };
+
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = LinePrinter {
+            table: Some(&table),
+            ..default_line_printer(&mut term, &fj, 0)
+        };
+        line.fill_in_table_row(&table, 2, 100)?;
+        assert_eq!("  id  name", line.terminal.output());
+
+        line.row = &line.flatjson[1];
+        line.terminal.clear_output();
+        line.fill_in_table_row(&table, 4, 100)?;
+        assert_eq!("1   \"alice\"", line.terminal.output());
+
+        line.row = &line.flatjson[5];
+        line.terminal.clear_output();
+        line.fill_in_table_row(&table, 4, 100)?;
+        assert_eq!("22  []", line.terminal.output());
+
+        // Values that don't fit are truncated.
+        line.row = &line.flatjson[1];
+        line.terminal.clear_output();
+        line.fill_in_table_row(&table, 4, 9)?;
+        assert_eq!("1   \"al…\"", line.terminal.output());
+
+        Ok(())
+    }
+
     #[test]
     fn test_fill_index_label_basic() -> std::fmt::Result {
         const JSON: &str = r#"[
@@ -1427,6 +2341,121 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_fill_value_truncation() -> std::fmt::Result {
+        let fj = parse_top_level_json(r#"["/home/user/file.json"]"#.to_owned()).unwrap();
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = LinePrinter {
+            truncation: Truncation::Start,
+            ellipsis: '~',
+            ..default_line_printer(&mut term, &fj, 1)
+        };
+
+        line.fill_in_value(12)?;
+        assert_eq!("\"~file.json\"", line.terminal.output());
+
+        line.truncation = Truncation::Middle;
+        line.terminal.clear_output();
+        line.fill_in_value(12)?;
+        assert_eq!("\"/home~json\"", line.terminal.output());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_integer_in_base() {
+        assert_eq!(
+            Some("0xff".to_owned()),
+            integer_in_base("255", NumberBase::Hex)
+        );
+        assert_eq!(
+            Some("-0b101".to_owned()),
+            integer_in_base("-5", NumberBase::Binary)
+        );
+        assert_eq!(
+            Some("0x0".to_owned()),
+            integer_in_base("-0", NumberBase::Hex)
+        );
+        assert_eq!(
+            Some("0xffffffffffffffff".to_owned()),
+            integer_in_base("18446744073709551615", NumberBase::Hex)
+        );
+        assert_eq!(
+            Some("-0x8000000000000000".to_owned()),
+            integer_in_base("-9223372036854775808", NumberBase::Hex)
+        );
+
+        assert_eq!(None, integer_in_base("255", NumberBase::Decimal));
+        assert_eq!(None, integer_in_base("1.5", NumberBase::Hex));
+        assert_eq!(None, integer_in_base("1e3", NumberBase::Hex));
+        assert_eq!(None, integer_in_base("-", NumberBase::Hex));
+        assert_eq!(
+            None,
+            integer_in_base("18446744073709551616", NumberBase::Hex)
+        );
+        assert_eq!(
+            None,
+            integer_in_base("-9223372036854775809", NumberBase::Hex)
+        );
+    }
+
+    #[test]
+    fn test_fill_value_number_base() -> std::fmt::Result {
+        let fj = parse_top_level_json(r#"[255, 2.5, "255"]"#.to_owned()).unwrap();
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = LinePrinter {
+            number_base: NumberBase::Hex,
+            ..default_line_printer(&mut term, &fj, 1)
+        };
+
+        line.fill_in_value(100)?;
+        assert_eq!("0xff", line.terminal.output());
+
+        line.terminal.clear_output();
+        line.fill_in_value(3)?;
+        assert_eq!("0x…", line.terminal.output());
+
+        for (index, expected) in vec![(2, "2.5"), (3, "\"255\"")].into_iter() {
+            line.row = &line.flatjson[index];
+            line.terminal.clear_output();
+            line.fill_in_value(100)?;
+            assert_eq!(expected, line.terminal.output());
+        }
+
+        line.terminal.clear_output();
+        line.generate_container_preview(&line.flatjson[0], 100, false)?;
+        assert_eq!("[0xff, 2.5, \"255\"]", line.terminal.output());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fill_value_transformed() -> std::fmt::Result {
+        let fj = parse_top_level_json(r#"["aGVsbG8=", 0]"#.to_owned()).unwrap();
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = LinePrinter {
+            transformed_value: Some(("base64", "hello".to_owned())),
+            ..default_line_printer(&mut term, &fj, 1)
+        };
+
+        let used_space = line.fill_in_value(100)?;
+        assert_eq!("\"hello\" (base64)", line.terminal.output());
+        assert_eq!(16, used_space);
+
+        // The decoder's name is left off if there isn't room for it.
+        line.terminal.clear_output();
+        line.fill_in_value(8)?;
+        assert_eq!("\"hello\"", line.terminal.output());
+
+        line.row = &line.flatjson[2];
+        line.transformed_value = Some(("Unix time", "1970-01-01T00:00:00Z".to_owned()));
+        line.terminal.clear_output();
+        line.fill_in_value(100)?;
+        assert_eq!("1970-01-01T00:00:00Z (Unix time)", line.terminal.output());
+
+        Ok(())
+    }
+
     #[test]
     fn test_fill_value_not_enough_space() -> std::fmt::Result {
         let fj = parse_top_level_json(r#"["hello", "", true]"#.to_owned()).unwrap();
@@ -1508,6 +2537,339 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_wrapped_string() -> std::fmt::Result {
+        let fj = parse_top_level_json(
+            r#"{"a": "abcdefghijklmnopqrstuvwxyz", "b": "abcdefghijklmnopqrstuvwx", "c": "abc"}"#
+                .to_owned(),
+        )
+        .unwrap();
+        let mut term = VisibleEscapesTerminal::new(true, false);
+        let mut line: LinePrinter = LinePrinter {
+            width: 18,
+            wrap: true,
+            ..default_line_printer(&mut term, &fj, 1)
+        };
+
+        // Each line has 12 columns available after `a: "`.
+        assert_eq!(3, line.num_lines());
+
+        line.print_line()?;
+        assert_eq!("_C(3)_a: \"abcdefghijkl", line.terminal.output());
+        line.terminal.clear_output();
+
+        line.print_wrapped_line(1)?;
+        assert_eq!("_C(7)_mnopqrstuvwx", line.terminal.output());
+        line.terminal.clear_output();
+
+        line.print_wrapped_line(2)?;
+        assert_eq!("_C(7)_yz\"", line.terminal.output());
+        line.terminal.clear_output();
+
+        // Closing quote and comma don't fit after the last segment.
+        line.row = &line.flatjson[2];
+        line.trailing_comma = true;
+        assert_eq!(3, line.num_lines());
+        line.print_wrapped_line(2)?;
+        assert_eq!("_C(7)_\",", line.terminal.output());
+
+        // Lines are shifted over by the gutter.
+        line.gutter_width = 4;
+        line.terminal.clear_output();
+        line.print_wrapped_line(2)?;
+        assert_eq!("_C(11)_\",", line.terminal.output());
+        line.gutter_width = 0;
+
+        // Strings that fit aren't wrapped.
+        line.row = &line.flatjson[3];
+        assert_eq!(1, line.num_lines());
+
+        // Not enough room to bother wrapping.
+        line.row = &line.flatjson[1];
+        line.width = 14;
+        assert_eq!(1, line.num_lines());
+
+        line.width = 18;
+        line.wrap = false;
+        assert_eq!(1, line.num_lines());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_size_annotations() -> std::fmt::Result {
+        let mut fj = parse_top_level_json(
+            r#"{"a": "abcdefghijklmnopqrstuvwxyz", "b": [1, 2, 3], "c": {"d": 4}, "e": "e"}"#
+                .to_owned(),
+        )
+        .unwrap();
+        fj.collapse(2);
+        fj.collapse(7);
+
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = LinePrinter {
+            show_sizes: true,
+            ..default_line_printer(&mut term, &fj, 1)
+        };
+
+        line.fill_in_value(20)?;
+        assert_eq!("\"abcdef…\" (26 chars)", line.terminal.output());
+        line.terminal.clear_output();
+
+        // Only as much of a long string as fits on the screen is counted
+        // with a lower maximum value width.
+        line.max_value_width = Some(10);
+        line.fill_in_value(20)?;
+        assert_eq!("\"abcde…\" (18+ chars)", line.terminal.output());
+        line.terminal.clear_output();
+        line.max_value_width = Some(30);
+        line.fill_in_value(20)?;
+        assert_eq!("\"abcdef…\" (26 chars)", line.terminal.output());
+        line.terminal.clear_output();
+        line.max_value_width = None;
+
+        // Strings that aren't truncated aren't annotated.
+        line.row = &line.flatjson[10];
+        line.fill_in_value(20)?;
+        assert_eq!("\"e\"", line.terminal.output());
+        line.terminal.clear_output();
+
+        line.row = &line.flatjson[2];
+        line.fill_in_value(20)?;
+        assert_eq!("[1, 2, 3] (3 items)", line.terminal.output());
+        line.terminal.clear_output();
+
+        line.row = &line.flatjson[7];
+        line.trailing_comma = true;
+        line.fill_in_value(20)?;
+        assert_eq!("{d: 4}, (1 item)", line.terminal.output());
+        line.terminal.clear_output();
+
+        // Not enough room for the annotation.
+        line.fill_in_value(12)?;
+        assert_eq!("{d: 4},", line.terminal.output());
+        line.terminal.clear_output();
+
+        line.show_sizes = false;
+        line.row = &line.flatjson[2];
+        line.trailing_comma = false;
+        line.fill_in_value(20)?;
+        assert_eq!("[1, 2, 3]", line.terminal.output());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_hints() -> std::fmt::Result {
+        let fj = parse_top_level_json(
+            r#"["42", 42, 4.2, 1e3, true, null, [], "abcdefghijklmnopqrstuvwxyz"]"#.to_owned(),
+        )
+        .unwrap();
+
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = LinePrinter {
+            show_types: true,
+            trailing_comma: true,
+            ..default_line_printer(&mut term, &fj, 1)
+        };
+
+        let expected = [
+            "\"42\", str",
+            "42, int",
+            "4.2, float",
+            "1e3, float",
+            "true, bool",
+        ];
+        for (i, expected) in expected.iter().enumerate() {
+            line.row = &line.flatjson[i + 1];
+            line.fill_in_value(20)?;
+            assert_eq!(*expected, line.terminal.output());
+            line.terminal.clear_output();
+        }
+
+        line.trailing_comma = false;
+        line.row = &line.flatjson[6];
+        line.fill_in_value(20)?;
+        assert_eq!("null null", line.terminal.output());
+        line.terminal.clear_output();
+
+        // Empty containers don't get a type.
+        line.row = &line.flatjson[7];
+        line.fill_in_value(20)?;
+        assert_eq!("[]", line.terminal.output());
+        line.terminal.clear_output();
+
+        // Long strings are truncated to make room for the type.
+        line.row = &line.flatjson[8];
+        line.fill_in_value(14)?;
+        assert_eq!("\"abcdefg…\" str", line.terminal.output());
+        line.terminal.clear_output();
+
+        // But other values aren't.
+        line.row = &line.flatjson[4];
+        line.fill_in_value(5)?;
+        assert_eq!("1e3", line.terminal.output());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_value_annotations() -> std::fmt::Result {
+        let fj = parse_top_level_json(
+            r#"["2021-01-31T04:05:06Z", 1612325106789, "2021-01-31", 1612325106]"#.to_owned(),
+        )
+        .unwrap();
+        let annotator = Annotator {
+            formats: vec![AnnotationFormat::Dates, AnnotationFormat::EpochMillis],
+            // 2021-02-03T04:05:06Z
+            now: 1_612_325_106,
+        };
+
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = LinePrinter {
+            annotator: Some(&annotator),
+            ..default_line_printer(&mut term, &fj, 1)
+        };
+
+        line.fill_in_value(40)?;
+        assert_eq!(
+            "\"2021-01-31T04:05:06Z\" (3 days ago)",
+            line.terminal.output()
+        );
+        line.terminal.clear_output();
+
+        line.row = &line.flatjson[2];
+        line.fill_in_value(60)?;
+        assert_eq!(
+            "1612325106789 (2021-02-03T04:05:06.789Z, just now)",
+            line.terminal.output()
+        );
+        line.terminal.clear_output();
+
+        // Values that aren't clearly dates aren't annotated.
+        for index in [3, 4] {
+            line.row = &line.flatjson[index];
+            line.fill_in_value(40)?;
+            assert!(!line.terminal.output().contains('('));
+            line.terminal.clear_output();
+        }
+
+        // Values aren't truncated to make room for the annotation.
+        line.row = &line.flatjson[1];
+        line.fill_in_value(30)?;
+        assert_eq!("\"2021-01-31T04:05:06Z\"", line.terminal.output());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_control_characters_are_escaped() -> std::fmt::Result {
+        let fj = parse_top_level_yaml(
+            r#"{"k\e": "\e[31mred\tone"}"#.to_owned(),
+            false,
+            DEFAULT_MAX_DEPTH,
+        )
+        .unwrap();
+
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 1);
+
+        let used_space = line.fill_in_label(100)?;
+        assert_eq!("\"k\\u001b\": ", line.terminal.output());
+        assert_eq!(11, used_space);
+        line.terminal.clear_output();
+
+        let used_space = line.fill_in_value(100)?;
+        assert_eq!("\"\\u001b[31mred\\tone\"", line.terminal.output());
+        assert_eq!(20, used_space);
+        line.terminal.clear_output();
+
+        // Escapes are never split when truncating.
+        let used_space = line.fill_in_value(9)?;
+        assert_eq!("\"\\u001b…\"", line.terminal.output());
+        assert_eq!(9, used_space);
+        line.terminal.clear_output();
+
+        line.fill_in_value(8)?;
+        assert_eq!("\"�…\"", line.terminal.output());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_key_markers() -> std::fmt::Result {
+        let fj = parse_top_level_json(r#"{"a": 1, "a": 2, "a": 3}"#.to_owned()).unwrap();
+
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 1);
+
+        let used_space = line.fill_in_label(100)?;
+        assert_eq!("a: ", line.terminal.output());
+        assert_eq!(3, used_space);
+
+        line.row = &line.flatjson[3];
+        line.terminal.clear_output();
+        let used_space = line.fill_in_label(100)?;
+        assert_eq!("a#3: ", line.terminal.output());
+        assert_eq!(5, used_space);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_yaml_comments() -> std::fmt::Result {
+        let fj = parse_top_level_yaml(
+            "a: 1 # One, the loneliest number\n".to_owned(),
+            true,
+            DEFAULT_MAX_DEPTH,
+        )
+        .unwrap();
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 1);
+
+        line.print_comment(100)?;
+        assert_eq!("  # One, the loneliest number", line.terminal.output());
+
+        line.terminal.clear_output();
+        line.print_comment(12)?;
+        assert_eq!("  # One, th…", line.terminal.output());
+
+        // At least a few characters of the comment need to fit.
+        line.terminal.clear_output();
+        line.print_comment(5)?;
+        assert_eq!("", line.terminal.output());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decoded_annotation() -> std::fmt::Result {
+        let fj = parse_top_level_json(r#"{"a": [1, 2]}"#.to_owned()).unwrap();
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = LinePrinter {
+            decoded: true,
+            ..default_line_printer(&mut term, &fj, 1)
+        };
+
+        let row = line.row;
+        let used_space = line.fill_in_container_value(100, row)?;
+        assert_eq!("[1, 2] (decoded)", line.terminal.output());
+        assert_eq!(16, used_space);
+
+        // The annotation is left off if there isn't room for it.
+        line.terminal.clear_output();
+        line.fill_in_container_value(10, row)?;
+        assert_eq!("[1, 2]", line.terminal.output());
+
+        line.mode = Mode::Line;
+        line.terminal.clear_output();
+        let used_space = line.fill_in_container_value(100, row)?;
+        assert_eq!("[ (decoded)", line.terminal.output());
+        assert_eq!(11, used_space);
+
+        Ok(())
+    }
+
     #[test]
     fn test_generate_object_preview() -> std::fmt::Result {
         let json = r#"{"a": 1, "d": {"x": true}, "b c": null}"#;
@@ -1657,7 +3019,7 @@ mod tests {
             3: 3,
             null: 4,
         }"#;
-        let fj = parse_top_level_yaml(YAML.to_owned()).unwrap();
+        let fj = parse_top_level_yaml(YAML.to_owned(), false, DEFAULT_MAX_DEPTH).unwrap();
 
         let mut term = TextOnlyTerminal::new();
         let mut line: LinePrinter = default_line_printer(&mut term, &fj, 0);
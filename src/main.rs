@@ -2,137 +2,332 @@
 // structure of the code.
 #![allow(clippy::collapsible_else_if)]
 
-extern crate lazy_static;
-extern crate libc_stdhandle;
-
 use std::fs::File;
 use std::io;
-use std::io::Read;
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::path::Path;
 
 use clap::Parser;
-use termion::cursor::HideCursor;
-use termion::input::MouseTerminal;
-use termion::raw::IntoRawMode;
-use termion::screen::AlternateScreen;
-
-mod app;
-mod flatjson;
-mod highlighting;
-mod input;
-mod jsonparser;
-mod jsontokenizer;
-mod lineprinter;
-mod options;
-mod screenwriter;
-mod search;
-mod terminal;
-mod truncatedstrview;
-mod types;
-mod viewer;
-mod yamlparser;
-
-use app::App;
-use options::{DataFormat, Opt};
+
+use jless::app::{App, InputFile};
+#[cfg(feature = "url")]
+use jless::fetch;
+use jless::mmap::MappedFile;
+use jless::options::{DataFormat, FormatSource, Opt};
+use jless::progress::{Progress, ProgressLine, ProgressReader};
+use jless::terminal::AnsiTerminal;
+use jless::theme::Theme;
+use jless::{app, decompress, flatjson, input, printer, progress, sniff};
 
 fn main() {
     let opt = Opt::parse();
 
-    let (input_string, input_filename) = match get_input_and_filename(&opt) {
-        Ok(input_and_filename) => input_and_filename,
+    let inputs = match get_inputs(&opt) {
+        Ok(inputs) => inputs,
         Err(err) => {
             eprintln!("Unable to get input: {}", err);
             std::process::exit(1);
         }
     };
 
-    let data_format = determine_data_format(opt.data_format(), &input_filename);
+    if opt.print {
+        print_inputs(&opt, inputs);
+    }
+
+    // The path printed by --print-path-on-exit, or the value printed by
+    // --print-value-on-exit, may be piped to another command, in which case
+    // jless is shown on the terminal instead, and only what's printed after
+    // quitting goes to the pipe.
+    let mut selection_stdout = None;
+    let path_to_stdout = matches!(&opt.print_path_on_exit, Some(file) if file.as_os_str() == "-");
+    if path_to_stdout || opt.print_value_on_exit.is_some() {
+        match input::remap_dev_tty_to_stdout() {
+            Ok(stdout) => selection_stdout = Some(stdout),
+            Err(err) => exit_requiring_interactive_terminal(err),
+        }
+    }
 
     if !isatty::stdout_isatty() {
-        print_pretty_printed_input(input_string, data_format);
+        for input in inputs {
+            let data_format = input.data_format();
+            print_pretty_printed_input(input.into_data(), data_format, opt.max_depth);
+        }
         std::process::exit(0);
     }
 
-    // We use freopen to remap /dev/tty to STDIN so that rustyline works when
-    // JSON input is provided via STDIN. rustyline gets initialized when we
-    // create the App, so by putting this before creating the app, we make
-    // sure rustyline gets the /dev/tty input.
-    input::remap_dev_tty_to_stdin();
-
-    let stdout = MouseTerminal::from(HideCursor::from(AlternateScreen::from(
-        io::stdout().into_raw_mode().unwrap(),
-    )));
-
-    let mut app = match App::new(
-        &opt,
-        input_string,
-        data_format,
-        input_filename,
-        Box::new(stdout),
-    ) {
-        Ok(jl) => jl,
+    let selection = match jless::run(&opt, inputs) {
+        Ok(Some(selection)) => selection,
+        Ok(None) => return,
         Err(err) => {
             eprintln!("{}", err);
-            return;
+            std::process::exit(1);
+        }
+    };
+
+    if let (Some(file), Some(path)) = (&opt.print_path_on_exit, selection.path) {
+        let result = match &mut selection_stdout {
+            Some(stdout) if path_to_stdout => writeln!(stdout, "{}", path),
+            _ => std::fs::write(file, format!("{}\n", path)),
+        };
+        if let Err(err) = result {
+            eprintln!("Unable to print path: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    // The terminal has been restored by now, so nothing else is printed
+    // with the value.
+    if let Some(value) = selection.value {
+        let value = match value {
+            Ok(value) => value,
+            Err(err) => {
+                eprintln!("Unable to print value: {}", err);
+                std::process::exit(1);
+            }
+        };
+        let result = match &mut selection_stdout {
+            Some(stdout) => stdout.write_all(value.as_bytes()),
+            None => io::stdout().write_all(value.as_bytes()),
+        };
+        if let Err(err) = result {
+            // Stop quietly when piped into something like head.
+            if err.kind() != io::ErrorKind::BrokenPipe {
+                eprintln!("Unable to print value: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn exit_requiring_interactive_terminal(err: io::Error) -> ! {
+    eprintln!("{}", jless::interactive_terminal_error(err));
+    std::process::exit(1);
+}
+
+// Prints every input, for --print, and exits.
+fn print_inputs(opt: &Opt, inputs: Vec<InputFile>) -> ! {
+    let theme = if opt.no_color || Theme::no_color_requested() || !isatty::stdout_isatty() {
+        None
+    } else {
+        match Theme::load(opt.theme.as_deref()) {
+            Ok(theme) => Some(theme),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
         }
     };
 
-    app.run(Box::new(input::get_input()));
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let mut skipped_lines = false;
+    for input in inputs {
+        let mut flatjson = match App::parse_input_file(opt, input) {
+            Ok(flatjson) => flatjson,
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        };
+        // The rest of JSON Lines input is still printed when some of its
+        // lines are malformed, but they're reported, and the exit status
+        // shows that they were skipped.
+        if let Some((valid, errors)) = flatjson.without_malformed_lines() {
+            for err in errors {
+                eprintln!("Skipped malformed line: {}", err);
+            }
+            skipped_lines = true;
+            flatjson = valid;
+        }
+        if let Err(err) = flatjson.check_all_parsed() {
+            eprintln!("Unable to print input: {}", err);
+            std::process::exit(1);
+        }
+
+        let output = match &theme {
+            Some(theme) => {
+                let mut terminal = AnsiTerminal::new(String::new());
+                printer::print_highlighted(&flatjson, theme, &mut terminal).unwrap();
+                terminal.output
+            }
+            None => flatjson.pretty_printed().unwrap(),
+        };
+
+        if let Err(err) = stdout.write_all(output.as_bytes()) {
+            // Stop quietly when piped into something like head.
+            if err.kind() == io::ErrorKind::BrokenPipe {
+                std::process::exit(0);
+            }
+            eprintln!("Unable to print input: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    std::process::exit(if skipped_lines { 1 } else { 0 });
 }
 
-fn print_pretty_printed_input(input: String, data_format: DataFormat) {
-    // Don't try to pretty print YAML input; just pass it through.
-    if data_format == DataFormat::Yaml {
+fn print_pretty_printed_input(input: String, data_format: DataFormat, max_depth: usize) {
+    // Only pretty print JSON input; pass everything else through.
+    if data_format != DataFormat::Json {
         print!("{}", input);
         return;
     }
 
-    let flatjson = match flatjson::parse_top_level_json(input) {
+    let flatjson = match flatjson::parse_top_level_json_to_depth(input, max_depth) {
         Ok(flatjson) => flatjson,
         Err(err) => {
             eprintln!("Unable to parse input: {:?}", err);
             std::process::exit(1);
         }
     };
+    if let Err(err) = flatjson.check_all_parsed() {
+        eprintln!("Unable to print input: {}", err);
+        std::process::exit(1);
+    }
 
     print!("{}", flatjson.pretty_printed().unwrap());
 }
 
-fn get_input_and_filename(opt: &Opt) -> io::Result<(String, String)> {
-    let mut input_string = String::new();
+fn get_inputs(opt: &Opt) -> io::Result<Vec<InputFile>> {
+    if opt.inputs.is_empty() {
+        if isatty::stdin_isatty() {
+            println!("Missing filename (\"jless --help\" for help)");
+            std::process::exit(1);
+        }
+        return Ok(vec![get_input(opt, None)?]);
+    }
+
+    opt.inputs
+        .iter()
+        .map(|path| {
+            get_input(opt, Some(path))
+                .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))
+        })
+        .collect()
+}
+
+// Reads an input from a file, a URL, or from STDIN if path is None or "-".
+fn get_input(opt: &Opt, path: Option<&Path>) -> io::Result<InputFile> {
+    let reader: Box<dyn Read + Send>;
+    // The size of the input, if it's known.
+    let size;
     let filename;
+    let file_path;
+    // The format of the input according to where it was read from,
+    // rather than its filename.
+    let source_data_format;
+    let mut mapped = None;
 
-    match &opt.input {
-        None => {
-            if isatty::stdin_isatty() {
-                println!("Missing filename (\"jless --help\" for help)");
-                std::process::exit(1);
-            }
-            filename = "STDIN".to_string();
-            io::stdin().read_to_string(&mut input_string)?;
+    match path {
+        #[cfg(feature = "url")]
+        Some(path) if fetch::is_url(path) => {
+            let url = path.to_str().unwrap();
+            let response = fetch::fetch(url)?;
+            size = response.content_length;
+            source_data_format = response
+                .content_type
+                .as_deref()
+                .and_then(fetch::data_format_for_content_type);
+            reader = Box::new(response.body);
+            filename = fetch::filename_for_url(url);
+            // URLs aren't watched or remembered like files.
+            file_path = None;
         }
-        Some(path) => {
-            if *path == PathBuf::from("-") {
-                filename = "STDIN".to_string();
-                io::stdin().read_to_string(&mut input_string)?;
-            } else {
-                File::open(path)?.read_to_string(&mut input_string)?;
-                filename = String::from(path.file_name().unwrap().to_string_lossy());
+        Some(path) if path.as_os_str() != "-" => {
+            let file = File::open(path)?;
+            if opt.mmap {
+                // Files that can't be mapped are read normally instead,
+                // including compressed files, which aren't valid UTF-8.
+                mapped = MappedFile::new(&file).ok();
             }
+            size = Some(file.metadata()?.len() as usize);
+            reader = Box::new(file);
+            filename = String::from(path.file_name().unwrap().to_string_lossy());
+            file_path = Some(path.to_path_buf());
+            source_data_format = None;
+        }
+        _ => {
+            filename = "STDIN".to_string();
+            reader = Box::new(io::stdin());
+            size = None;
+            file_path = None;
+            source_data_format = None;
         }
     }
 
-    Ok((input_string, filename))
-}
+    // Reading a large input can take a while, so show how much has been
+    // read so far. (Only when it won't end up mixed in with the output.)
+    let progress = Progress::new(size);
+    let reader: Box<dyn Read + Send> = Box::new(ProgressReader::new(reader, progress.clone()));
 
-fn determine_data_format(format: Option<DataFormat>, filename: &str) -> DataFormat {
-    format.unwrap_or_else(|| {
-        match std::path::Path::new(filename)
-            .extension()
-            .and_then(std::ffi::OsStr::to_str)
-        {
-            Some("yml") | Some("yaml") => DataFormat::Yaml,
-            _ => DataFormat::Json,
-        }
+    let (reader, compression) = if mapped.is_some() {
+        (reader, None)
+    } else {
+        decompress::decompress(reader)?
+    };
+    // The format of compressed files is picked by the name without the
+    // compression extension, e.g., "data.json" for "data.json.gz". They're
+    // treated like STDIN afterwards, rather than being watched, followed,
+    // or read again, which would mean decompressing them again.
+    let (format_filename, file_path) = match compression {
+        Some(compression) => (compression.strip_extension(&filename).to_owned(), None),
+        None => (filename.clone(), file_path),
+    };
+
+    let read_input = move || {
+        let mut reader = reader;
+        let mut input = vec![];
+        reader
+            .read_to_end(&mut input)
+            .map(|_| app::decode_input(input))
+    };
+
+    let (input_string, replacements) = if mapped.is_some() {
+        (String::new(), app::Replacements::default())
+    } else if isatty::stdout_isatty() && isatty::stderr_isatty() {
+        let mut stderr = io::stderr();
+        let mut line = ProgressLine::new(&mut stderr, format!("Reading {}", filename));
+        progress::run_with_progress(&mut line, read_input, || progress.describe(), None)?
+    } else {
+        read_input()?
+    };
+
+    let contents = mapped
+        .as_ref()
+        .map_or(&input_string[..], MappedFile::as_str);
+    let (data_format, format_source) =
+        determine_data_format(opt, source_data_format, &format_filename, contents);
+    Ok(match mapped {
+        Some(mapped) => InputFile::mapped(mapped, data_format, format_source, filename, file_path),
+        None => InputFile::new(
+            input_string,
+            data_format,
+            format_source,
+            filename,
+            file_path,
+        )
+        .with_replacements(replacements),
     })
 }
+
+fn determine_data_format(
+    opt: &Opt,
+    source_data_format: Option<DataFormat>,
+    filename: &str,
+    data: &str,
+) -> (DataFormat, FormatSource) {
+    if let Some(format) = opt.data_format() {
+        return (format, FormatSource::Flag);
+    }
+    if let Some(format) = source_data_format {
+        return (format, FormatSource::ContentType);
+    }
+
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(std::ffi::OsStr::to_str);
+    match extension.and_then(DataFormat::from_name) {
+        Some(format) => (format, FormatSource::Extension),
+        None => (sniff::sniff_data_format(data), FormatSource::Content),
+    }
+}
@@ -0,0 +1,111 @@
+// With --mmap, local files are memory mapped rather than read into a
+// String, so that parsing a large JSON file only needs memory for the rows
+// and the pretty printed copy of it, not for the file itself too; the
+// kernel pages the file in (and out) as it's read.
+//
+// The mapping is only valid while the file isn't truncated, which is why
+// it's not the default: if another program truncates the file while it's
+// being parsed, reading past the new end of the file crashes jless.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+/// The contents of a file, mapped into memory read-only. Files that aren't
+/// valid UTF-8 can't be mapped, since they need to be converted anyway.
+pub struct MappedFile {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// The mapping is never written to, so it can be read from any thread.
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    pub fn new(file: &File) -> io::Result<MappedFile> {
+        let len = file.metadata()?.len() as usize;
+        // mmap fails for empty files.
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Empty files can't be mapped",
+            ));
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        // The file is read from start to end (and only once), so the
+        // kernel can read ahead, and drop pages once they've been read.
+        unsafe {
+            libc::madvise(ptr, len, libc::MADV_SEQUENTIAL);
+        }
+
+        let mapped = MappedFile { ptr, len };
+        if std::str::from_utf8(mapped.as_bytes()).is_err() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The file isn't valid UTF-8",
+            ));
+        }
+        Ok(mapped)
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Checked in MappedFile::new.
+        unsafe { std::str::from_utf8_unchecked(self.as_bytes()) }
+    }
+}
+
+impl AsRef<str> for MappedFile {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mapped_file() {
+        let dir = std::env::temp_dir().join(format!("jless-mmap-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("input.json");
+
+        std::fs::write(&path, "{\"a\": \"é\"}").unwrap();
+        let mapped = MappedFile::new(&File::open(&path).unwrap()).unwrap();
+        assert_eq!(mapped.as_str(), "{\"a\": \"é\"}");
+
+        // Files that can't be mapped are read normally instead.
+        std::fs::write(&path, "").unwrap();
+        assert!(MappedFile::new(&File::open(&path).unwrap()).is_err());
+        std::fs::write(&path, b"\"\xff\"").unwrap();
+        assert!(MappedFile::new(&File::open(&path).unwrap()).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,113 @@
+use crate::flatjson::{FlatJson, OptionIndex, Value};
+use crate::jsonparser;
+
+// Summary shown in the status bar next to the filename.
+pub fn summary(num_records: usize, num_malformed: usize) -> String {
+    let records = if num_records == 1 {
+        "1 record".to_owned()
+    } else {
+        format!("{} records", num_records)
+    };
+
+    if num_malformed == 0 {
+        records
+    } else {
+        format!("{}, {} malformed", records, num_malformed)
+    }
+}
+
+// Parses each line of the input independently, as the elements of an
+// array. Blank lines are skipped, and lines that aren't a single valid JSON
+// value are kept as they are, as Value::Malformed rows, with the error as
+// their comment, e.g., "Line 3: Unexpected EOF", so that one bad line
+// doesn't prevent viewing the rest of the input.
+pub fn parse_lines(input: &str, max_depth: usize) -> FlatJson {
+    parse_lines_starting_at(input, 1, max_depth)
+}
+
+// Like parse_lines, but for input that starts at the given line number,
+// e.g., lines that were added to the end of a file.
+pub fn parse_lines_starting_at(input: &str, first_line: usize, max_depth: usize) -> FlatJson {
+    let (rows, pretty_printed, depth) = jsonparser::parse_lines(input, first_line, max_depth);
+    FlatJson(rows, pretty_printed, depth)
+}
+
+// Returns how many records parsed JSON Lines input has, and how many of
+// them are malformed.
+pub fn count_records(flatjson: &FlatJson) -> (usize, usize) {
+    let mut num_records = 0;
+    let mut num_malformed = 0;
+
+    let mut record = flatjson[0].first_child();
+    while let OptionIndex::Index(index) = record {
+        num_records += 1;
+        if let Value::Malformed = flatjson[index].value {
+            num_malformed += 1;
+        }
+        record = flatjson[index].next_sibling;
+    }
+
+    (num_records, num_malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatjson::DEFAULT_MAX_DEPTH;
+
+    #[test]
+    fn test_parse_lines() {
+        let input = "{\"a\": 1}\n\n[2]\r\n \"three\"\n";
+        let lines = parse_lines(input, DEFAULT_MAX_DEPTH);
+        assert_eq!("[{ \"a\": 1 }, [2], \"three\"]", lines.1);
+        assert_eq!((3, 0), count_records(&lines));
+        assert_eq!("3 records", summary(3, 0));
+
+        // Each record knows where it is in the input.
+        assert_eq!(Some(0..8), lines[1].source_range);
+        assert_eq!(Some(10..13), lines[4].source_range);
+        assert_eq!(Some(16..23), lines[7].source_range);
+        assert_eq!(Some(0..input.len()), lines[0].source_range);
+
+        let lines = parse_lines("", DEFAULT_MAX_DEPTH);
+        assert_eq!("[]", lines.1);
+        assert_eq!((0, 0), count_records(&lines));
+        assert_eq!("0 records", summary(0, 0));
+    }
+
+    #[test]
+    fn test_malformed_lines() {
+        let lines = parse_lines("{\"a\": 1}\n{\"a\": \n1 2\n[3}\n4", DEFAULT_MAX_DEPTH);
+        assert_eq!((5, 3), count_records(&lines));
+        assert_eq!("5 records, 3 malformed", summary(5, 3));
+
+        // Malformed lines are kept as they are, and nothing parsed from
+        // them before the error is.
+        assert_eq!("[{ \"a\": 1 }, {\"a\":, 1 2, [3}, 4]", lines.1);
+        let malformed: Vec<(&str, Option<&str>)> = lines
+            .0
+            .iter()
+            .filter(|row| matches!(row.value, Value::Malformed))
+            .map(|row| (&lines.1[row.range.clone()], row.comment.as_deref()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("{\"a\":", Some("Line 2: Unexpected EOF")),
+                ("1 2", Some("Line 3: Line contains multiple JSON values")),
+                ("[3}", Some("Line 4: Unexpected '}' at column 3")),
+            ],
+            malformed
+        );
+
+        // The valid records are still linked to each other.
+        let indexes: Vec<usize> = lines.0.iter().map(|row| row.index).collect();
+        assert_eq!(vec![0, 0, 0, 0, 1, 2, 3, 4, 0], indexes);
+        assert_eq!(OptionIndex::Index(4), lines[1].next_sibling);
+        assert_eq!(OptionIndex::Index(1), lines[4].prev_sibling);
+        assert_eq!(OptionIndex::Index(5), lines[4].next_sibling);
+
+        // Line numbers of lines that are added later.
+        let lines = parse_lines_starting_at("1\n{\n", 10, DEFAULT_MAX_DEPTH);
+        assert_eq!(Some("Line 11: Unexpected EOF"), lines[2].comment.as_deref());
+    }
+}
@@ -2,26 +2,225 @@ use std::path::PathBuf;
 
 use clap::ArgEnum;
 use clap::Parser;
+use unicode_width::UnicodeWidthChar;
 
+use crate::annotations::AnnotationFormat;
+use crate::csvparser::CsvOptions;
+use crate::flatjson::DEFAULT_MAX_DEPTH;
+use crate::truncatedstrview::Truncation;
 use crate::viewer::Mode;
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug, ArgEnum)]
 pub enum DataFormat {
     Json,
+    Ndjson,
     Yaml,
+    Toml,
+    Csv,
+    Tsv,
 }
 
-/// A pager for JSON (or YAML) data
-#[derive(Debug, Parser)]
+impl DataFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            DataFormat::Json => "JSON",
+            DataFormat::Ndjson => "JSON Lines",
+            DataFormat::Yaml => "YAML",
+            DataFormat::Toml => "TOML",
+            DataFormat::Csv => "CSV",
+            DataFormat::Tsv => "TSV",
+        }
+    }
+
+    /// The format with the given name, or file extension, e.g., "yml".
+    pub fn from_name(name: &str) -> Option<DataFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(DataFormat::Json),
+            "jsonl" | "ndjson" => Some(DataFormat::Ndjson),
+            "yaml" | "yml" => Some(DataFormat::Yaml),
+            "toml" => Some(DataFormat::Toml),
+            "csv" => Some(DataFormat::Csv),
+            "tsv" => Some(DataFormat::Tsv),
+            _ => None,
+        }
+    }
+}
+
+/// How the format of an input was chosen.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum FormatSource {
+    // One of the format flags, e.g., --yaml.
+    Flag,
+    // The Content-Type of a URL.
+    ContentType,
+    Extension,
+    // Guessed from the start of the input (see sniff::sniff_data_format).
+    Content,
+    // The :format command.
+    Command,
+}
+
+impl FormatSource {
+    // Shown in the status bar after the name of the format.
+    pub fn label(self) -> &'static str {
+        match self {
+            FormatSource::Flag => "flag",
+            FormatSource::ContentType => "Content-Type",
+            FormatSource::Extension => "extension",
+            FormatSource::Content => "sniffed",
+            FormatSource::Command => ":format",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            FormatSource::Flag => "set with a flag",
+            FormatSource::ContentType => "based on the Content-Type of the response",
+            FormatSource::Extension => "based on the file extension",
+            FormatSource::Content => "guessed from the contents",
+            FormatSource::Command => "set with :format",
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, ArgEnum)]
+pub enum ClipboardBackend {
+    Auto,
+    System,
+    Osc52,
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, ArgEnum)]
+pub enum PathFormat {
+    Dot,
+    Bracket,
+    Query,
+    Gron,
+    Plain,
+}
+
+// How the focused value is printed with --print-value-on-exit.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, ArgEnum)]
+pub enum ValueFormat {
+    Pretty,
+    Compact,
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, ArgEnum)]
+pub enum LineNumbers {
+    Off,
+    Absolute,
+    Relative,
+    Hybrid,
+}
+
+impl LineNumbers {
+    // The mode to switch to when cycling through them.
+    pub fn next(self) -> LineNumbers {
+        match self {
+            LineNumbers::Off => LineNumbers::Absolute,
+            LineNumbers::Absolute => LineNumbers::Relative,
+            LineNumbers::Relative => LineNumbers::Hybrid,
+            LineNumbers::Hybrid => LineNumbers::Off,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, ArgEnum)]
+pub enum ToggleScope {
+    Node,
+    Subtree,
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, ArgEnum)]
+pub enum YamlAliases {
+    Expand,
+    Collapse,
+}
+
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug, ArgEnum)]
+pub enum NumberBase {
+    Decimal,
+    Hex,
+    Binary,
+}
+
+impl NumberBase {
+    // The base to switch to when cycling through them.
+    pub fn next(self) -> NumberBase {
+        match self {
+            NumberBase::Decimal => NumberBase::Hex,
+            NumberBase::Hex => NumberBase::Binary,
+            NumberBase::Binary => NumberBase::Decimal,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Indent {
+    Spaces(usize),
+    // Indents by TAB_WIDTH columns, with a guide line at each level.
+    Tabs,
+}
+
+const MAX_INDENT: usize = 8;
+const TAB_WIDTH: usize = 4;
+
+impl Indent {
+    // The number of columns each level of nesting is indented by.
+    pub fn width(self) -> usize {
+        match self {
+            Indent::Spaces(width) => width,
+            Indent::Tabs => TAB_WIDTH,
+        }
+    }
+}
+
+impl std::str::FromStr for Indent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Indent, String> {
+        if s == "tabs" || s == "tab" {
+            return Ok(Indent::Tabs);
+        }
+
+        match s.parse::<usize>() {
+            Ok(width) if width <= MAX_INDENT => Ok(Indent::Spaces(width)),
+            _ => Err(format!(
+                "expected a number of spaces from 0 to {}, or \"tabs\"",
+                MAX_INDENT
+            )),
+        }
+    }
+}
+
+// Truncated strings leave exactly one column for each ellipsis.
+fn parse_ellipsis(s: &str) -> Result<char, String> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) if UnicodeWidthChar::width(ch) == Some(1) => Ok(ch),
+        _ => Err("expected a single character that's one column wide".to_string()),
+    }
+}
+
+/// A pager for JSON (or YAML, TOML, CSV, or TSV) data
+#[derive(Clone, Debug, Parser)]
 #[clap(name = "jless", version)]
 pub struct Opt {
-    /// Input file. jless will read from stdin if no input file is
+    /// Input files. jless will read from stdin if no input file is
     /// provided, or '-' is specified. If a filename is provided, jless
     /// will check the extension to determine what the input format is,
     /// and by default will assume JSON. Can specify input format
-    /// explicitly using --json or --yaml.
+    /// explicitly using --json, --yaml, --toml, --csv, or --tsv. When
+    /// multiple files are provided, use ']' and '[' to switch between
+    /// them. Inputs can also be http:// or https:// URLs, which are
+    /// fetched using curl, which needs to be installed; their format is
+    /// determined by the Content-Type of the response, if it's specific
+    /// enough, or else their extension.
+    /// gzip and zstd compressed inputs are decompressed using the gzip and
+    /// zstd commands, which need to be installed.
     #[clap(parse(from_os_str))]
-    pub input: Option<PathBuf>,
+    pub inputs: Vec<PathBuf>,
 
     /// Initial viewing mode. In line mode (--mode line), opening
     /// and closing curly and square brackets are shown and all
@@ -31,30 +230,436 @@ pub struct Opt {
     #[clap(short, long, arg_enum, hide_possible_values = true, default_value_t = Mode::Data)]
     pub mode: Mode,
 
+    /// Open with only the first N levels of nesting expanded, and every
+    /// object and array below them collapsed (0 collapses everything).
+    /// This takes precedence over which objects and arrays were collapsed
+    /// in the state restored by --remember-state. Press 'zM' and 'zR' with
+    /// a count to do the same while viewing.
+    #[clap(long = "depth", value_name = "N")]
+    pub depth: Option<usize>,
+
+    /// Show objects and arrays nested more than N levels deep as a
+    /// placeholder, rather than parsing them, so that pathologically deep
+    /// inputs can't crash jless. Printing, exporting, or copying values
+    /// containing placeholders fails instead of writing them.
+    #[clap(long = "max-depth", value_name = "N", default_value_t = DEFAULT_MAX_DEPTH)]
+    pub max_depth: usize,
+
+    /// What space (toggle-collapsed) expands or collapses: just the focused
+    /// object or array with 'node', or every object and array inside it
+    /// too with 'subtree'. 'zO' and 'zC' expand and collapse a whole
+    /// subtree either way.
+    #[clap(long = "toggle", arg_enum, default_value_t = ToggleScope::Node)]
+    pub toggle: ToggleScope,
+
+    /// How YAML aliases (*name) are shown. They're replaced by a copy of
+    /// the value their anchor (&name) is on, which is shown expanded with
+    /// 'expand', or collapsed with 'collapse', so that it's clear that
+    /// it's a reference. Press 'za' to jump from an alias to its anchor.
+    #[clap(long = "yaml-aliases", arg_enum, default_value_t = YamlAliases::Expand)]
+    pub yaml_aliases: YamlAliases,
+
+    /// Show the comments in YAML input, dimmed, after the values they're
+    /// on. Comments on the lines above a value are shown after it too.
+    #[clap(long = "yaml-comments")]
+    pub yaml_comments: bool,
+
     /// Number of lines to maintain as padding between the currently
     /// focused row and the top or bottom of the screen. Setting this to
     /// a large value will keep the focused in the middle of the screen
-    /// (except at the start or end of a file).
+    /// (except at the start or end of a file). It can be changed while
+    /// viewing with :scrolloff N.
     #[clap(long = "scrolloff", default_value_t = 3)]
     pub scrolloff: u16,
 
+    /// Number of milliseconds to wait after receiving an Escape character
+    /// to see if it is the start of a longer escape sequence (such as
+    /// an Alt-modified key), rather than a standalone press of the
+    /// Escape key. Increase this if Alt-modified keys are misinterpreted
+    /// over slow connections.
+    #[clap(long = "esc-delay", default_value_t = 25)]
+    pub esc_delay_ms: u16,
+
+    /// Maximum number of milliseconds between two clicks on the same
+    /// spot for them to be treated as a double click. Double clicking
+    /// on a collapsed container will expand it.
+    #[clap(long = "double-click-interval", default_value_t = 400)]
+    pub double_click_interval_ms: u16,
+
+    /// Enable the kitty keyboard protocol, which allows jless to
+    /// distinguish between keys that are normally indistinguishable,
+    /// such as Tab and Ctrl-I, and keys with other combinations of
+    /// modifiers, such as Shift-Enter, which can then be bound to commands
+    /// (see --keybindings). This is ignored by terminals
+    /// that don't support the protocol.
+    #[clap(long = "kitty-keyboard")]
+    pub kitty_keyboard: bool,
+
+    /// Don't capture the mouse, so that text can be selected with the
+    /// mouse without holding a modifier key (usually Shift). Clicking
+    /// won't focus lines. Press zm to toggle capturing the mouse while
+    /// viewing.
+    #[clap(long = "no-mouse")]
+    pub no_mouse: bool,
+
+    /// Size, in bytes, of the buffer used to read input from the
+    /// terminal. A larger buffer may improve performance when pasting
+    /// large amounts of text.
+    #[clap(long = "input-buffer-size", default_value_t = 1024)]
+    pub input_buffer_size: usize,
+
+    /// Don't save search history to disk. By default, search history is
+    /// saved to $XDG_DATA_HOME/jless/search_history (or
+    /// ~/.local/share/jless/search_history) so that it can be recalled
+    /// in future sessions.
+    #[clap(long = "no-search-history-file")]
+    pub no_search_history_file: bool,
+
+    /// Remember which objects and arrays were collapsed, and which row was
+    /// focused, when viewing a file, and restore them the next time the
+    /// same file is viewed, as long as it hasn't changed. The state is
+    /// saved to $XDG_STATE_HOME/jless/view_state (or
+    /// ~/.local/state/jless/view_state) on exit, and can be forgotten
+    /// using the :clear-state command.
+    #[clap(long = "remember-state", overrides_with = "no_remember_state")]
+    pub remember_state: bool,
+
+    /// Don't remember the state of files, overriding --remember-state.
+    #[clap(long = "no-remember-state", overrides_with = "remember_state")]
+    pub no_remember_state: bool,
+
+    /// Ask for confirmation before quitting when 'q' is pressed. 'ZZ'
+    /// always quits without asking, and 'ZQ' quits without remembering the
+    /// state of files (see --remember-state).
+    #[clap(long = "confirm-quit")]
+    pub confirm_quit: bool,
+
+    /// Keep highlighting search matches after moving away from them, like
+    /// vim's 'hlsearch' option. Highlighting can be dismissed by pressing
+    /// Escape, and will reappear after jumping to another match.
+    #[clap(long = "hlsearch")]
+    pub hlsearch: bool,
+
+    /// Show where the first match of a search is while its pattern is
+    /// being typed, like vim's 'incsearch' option. Pressing Escape returns
+    /// to where the search started. (This uses a simpler prompt than
+    /// usual, which supports fewer editing commands.)
+    #[clap(long = "incsearch")]
+    pub incsearch: bool,
+
+    /// Stop at the last (or first) match when jumping between search
+    /// matches, instead of wrapping around to the other end of the file,
+    /// like turning off vim's 'wrapscan' option.
+    #[clap(long = "no-wrapscan")]
+    pub no_wrapscan: bool,
+
+    /// Show the members of objects sorted by their keys, instead of in
+    /// the order they appear in the input. This can be toggled by
+    /// pressing 'S'. Copied values will still have their keys in the
+    /// original order.
+    #[clap(long = "sort-keys")]
+    pub sort_keys: bool,
+
+    /// Reload the input files when they change. The focused line and
+    /// collapsed objects and arrays are preserved, when possible. If a
+    /// file can no longer be parsed, the previous version of it is still
+    /// shown. Has no effect when reading from STDIN.
+    #[clap(long = "watch")]
+    pub watch: bool,
+
+    /// Like 'tail -f', keep reading records as they're added to the end of
+    /// a JSON Lines file. If the last line is focused, the newest records
+    /// will be scrolled into view.
+    #[clap(short = 'f', long = "follow")]
+    pub follow: bool,
+
+    /// Memory map local files instead of reading them into memory. For
+    /// JSON files, this saves keeping a copy of the raw input in memory;
+    /// the pretty printed copy of the file, and the parsed rows, are still
+    /// built in memory. Other formats, and files viewed with --follow, are
+    /// still copied into memory. jless may crash if a file is truncated
+    /// while it's being viewed. STDIN and URLs, and files that can't be
+    /// mapped, are read normally.
+    #[clap(long = "mmap")]
+    pub mmap: bool,
+
+    /// Compare two files, showing them side by side. Values are matched up
+    /// by their paths: ones only in the first file are marked with '-',
+    /// ones only in the second with '+', and changed values (and the
+    /// objects and arrays containing them) with '!'. Rows with the same path
+    /// are lined up next to each other, with blank lines next to rows only
+    /// in one file, and the other file follows the focused one, with the
+    /// same objects and arrays collapsed. Press ']' to move the focus to the
+    /// other file.
+    #[clap(long = "diff")]
+    pub diff: bool,
+
+    /// Wrap string values that are too long to fit on the screen across
+    /// multiple lines, instead of truncating them. This can be toggled
+    /// by pressing 'zW', and individual strings can be wrapped (or
+    /// unwrapped) by pressing 'W'.
+    #[clap(long = "wrap")]
+    pub wrap: bool,
+
+    /// Show the length of truncated strings and the number of elements in
+    /// collapsed objects and arrays. This can be toggled by pressing 'I'.
+    #[clap(long = "show-sizes")]
+    pub show_sizes: bool,
+
+    /// Show the type of each string, number, boolean, and null value after
+    /// it (e.g., to tell the string "42" apart from the number 42). This
+    /// can be toggled by pressing 'T'.
+    #[clap(long = "show-types")]
+    pub show_types: bool,
+
+    /// Annotate values in common formats with what they mean, e.g., how
+    /// long ago an ISO 8601 date and time was. Annotations are only shown
+    /// after values, and never change them. This can be toggled by
+    /// pressing 'zA'.
+    #[clap(long = "annotate")]
+    pub annotate: bool,
+
+    /// Which formats of values are annotated, separated by commas: 'dates'
+    /// (ISO 8601 dates and times with a time zone, annotated with how long
+    /// ago they are), and 'epoch-millis' (13 digit integers that look like
+    /// milliseconds since the Unix epoch, annotated with the date and
+    /// time). Both are annotated by default.
+    #[clap(
+        long = "annotate-formats",
+        value_name = "FORMATS",
+        arg_enum,
+        use_delimiter = true,
+        hide_possible_values = true,
+        default_value = "dates,epoch-millis"
+    )]
+    pub annotate_formats: Vec<AnnotationFormat>,
+
+    /// Line up the values of each object's members in a column, after the
+    /// object's longest key. This can be toggled by pressing 'z='.
+    #[clap(long = "align-values")]
+    pub align_values: bool,
+
+    /// Show line numbers in a gutter on the left side of the screen.
+    /// 'absolute' numbers match the line numbers used by ':N', 'relative'
+    /// numbers show the distance from the focused line (useful for
+    /// commands like '10j'), and 'hybrid' shows relative numbers except
+    /// for an absolute number on the focused line. Press 'L' to cycle
+    /// through these.
+    #[clap(long = "line-numbers", arg_enum, default_value_t = LineNumbers::Off)]
+    pub line_numbers: LineNumbers,
+
+    /// Show a minimap on the right side of the screen, which shows what
+    /// part of the input is on the screen, and where search matches are.
+    /// This can be toggled by pressing 'z|'.
+    #[clap(long = "minimap")]
+    pub minimap: bool,
+
+    /// Show a column in the gutter with a marker next to each object and
+    /// array, like a code editor's fold markers: '▸' if it's collapsed,
+    /// and '▾' if it's expanded. Clicking a row toggles it. The markers can
+    /// be changed by a theme. This can be toggled by pressing 'zF'.
+    #[clap(long = "fold-column")]
+    pub fold_column: bool,
+
+    /// How far each level of nesting is indented: a number of spaces (up
+    /// to 8), or "tabs", which indents by 4 columns and draws a guide line
+    /// at each level.
+    #[clap(long = "indent", default_value = "2")]
+    pub indent: Indent,
+
+    /// Draw a faint guide line at each level of indentation, to make it
+    /// easier to see which lines are nested in which objects and arrays.
+    /// This can be toggled by pressing 'zi'.
+    #[clap(long = "indent-guides")]
+    pub indent_guides: bool,
+
+    /// Which part of a value that doesn't fit on the screen is elided: its
+    /// 'end' (the default), its 'middle', or its 'start', which is useful
+    /// for seeing the ends of long file paths and URLs. This can be
+    /// changed for the focused value by pressing 'z;'.
+    #[clap(long = "truncate", arg_enum, default_value_t = Truncation::End)]
+    pub truncate: Truncation,
+
+    /// Show integers in 'hex' (e.g., 0xff) or 'binary' (e.g., 0b101)
+    /// instead of 'decimal'. Other numbers, and integers that don't fit in
+    /// 64 bits, are shown as they are. Copied values and searches still
+    /// use the decimal form. Press 'z#' to cycle through these.
+    #[clap(long = "number-base", arg_enum, default_value_t = NumberBase::Decimal)]
+    pub number_base: NumberBase,
+
+    /// The character shown in place of the elided part of a truncated key
+    /// or value, which must be one column wide.
+    #[clap(long = "ellipsis", default_value = "…", parse(try_from_str = parse_ellipsis))]
+    pub ellipsis: char,
+
+    /// Only look at the first N columns of long values when drawing them
+    /// (or as many as fit on the screen, if that's more), so that giant
+    /// values like base64 blobs don't slow jless down. The lengths shown by
+    /// --show-sizes are then at least N, like "(10000+ chars)", for longer
+    /// strings. 0 always looks at entire values.
+    #[clap(long = "max-value-width", value_name = "N", default_value_t = 10000)]
+    pub max_value_width: usize,
+
+    /// Load colors from a TOML theme file, which maps roles (key, string,
+    /// number, boolean, null, focused, search-match, gutter, etc.) to
+    /// colors, given as names like "light-blue" or as 256 color palette
+    /// indexes. By default, $XDG_CONFIG_HOME/jless/theme.toml (or
+    /// ~/.config/jless/theme.toml) is loaded if it exists.
+    #[clap(long = "theme", parse(from_os_str))]
+    pub theme: Option<PathBuf>,
+
+    /// Load keybindings from a TOML file, which maps keys to the names of
+    /// commands, e.g., "ctrl-j" = "down" or "z t" = "focused-line-to-top".
+    /// Keys that aren't mentioned keep their default bindings. By default,
+    /// $XDG_CONFIG_HOME/jless/keys.toml (or ~/.config/jless/keys.toml) is
+    /// loaded if it exists.
+    #[clap(long = "keybindings", parse(from_os_str))]
+    pub keybindings: Option<PathBuf>,
+
+    /// Don't use any colors, and only use bold and inverted text to
+    /// highlight the focused line and search matches. This is also enabled
+    /// when the NO_COLOR environment variable is set, and overrides any
+    /// theme.
+    #[clap(long = "no-color", alias = "monochrome")]
+    pub no_color: bool,
+
+    /// Print the input to stdout, pretty printed and highlighted, instead
+    /// of viewing it. Colors aren't used if stdout isn't a terminal, or
+    /// with --no-color. Other input formats are printed as JSON.
+    #[clap(short = 'p', long = "print")]
+    pub print: bool,
+
+    /// How to copy text to the clipboard. 'system' uses the system
+    /// clipboard, while 'osc52' uses OSC 52 escape sequences to ask the
+    /// terminal emulator to set the clipboard, which works over SSH (and
+    /// in tmux, if passthrough is allowed). 'auto' (the default) uses OSC
+    /// 52 when running over SSH or when the system clipboard is
+    /// unavailable.
+    #[clap(long = "clipboard", arg_enum, default_value_t = ClipboardBackend::Auto)]
+    pub clipboard: ClipboardBackend,
+
+    /// Format of the path copied by 'yp'. 'dot' paths look like
+    /// .a.b[0], 'bracket' paths look like ["a"]["b"][0], 'query' paths
+    /// can be used with jq to filter the input, 'gron' copies an
+    /// assignment like gron outputs (json.a.b[0] = "value";), and 'plain'
+    /// paths look like a.b.0. Any format can be copied with 'yP' followed
+    /// by a key selecting the format.
+    #[clap(long = "path-format", arg_enum, default_value_t = PathFormat::Dot)]
+    pub path_format: PathFormat,
+
+    /// When quitting, print the path to the focused node (in the format
+    /// given by --path-format) to stdout, or write it to FILE with
+    /// --print-path-on-exit=FILE, so that jless can be used to pick a node
+    /// in a shell pipeline. jless is shown on the terminal even if stdout
+    /// isn't one. Nothing is printed after quitting with Ctrl-C or 'ZQ'.
+    #[clap(
+        long = "print-path-on-exit",
+        value_name = "FILE",
+        parse(from_os_str),
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "-"
+    )]
+    pub print_path_on_exit: Option<PathBuf>,
+
+    /// When quitting, print the focused value as JSON to stdout, either
+    /// 'pretty' printed (the default), or 'compact', on one line, with
+    /// --print-value-on-exit=compact, so that jless can be used to pick
+    /// out part of the input in a shell pipeline. jless is shown on the
+    /// terminal even if stdout isn't one. Nothing is printed after quitting
+    /// with Ctrl-C or 'ZQ'. 'ZP' quits and prints the value even without
+    /// this option.
+    #[clap(
+        long = "print-value-on-exit",
+        value_name = "FORMAT",
+        arg_enum,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "pretty"
+    )]
+    pub print_value_on_exit: Option<ValueFormat>,
+
     /// Parse input as JSON, regardless of file extension.
     #[clap(long = "json", group = "data-format", display_order = 1000)]
     pub json: bool,
 
+    /// Parse input as newline-delimited JSON (JSON Lines), regardless of
+    /// file extension. Each line is parsed separately and shown as an
+    /// element of a top-level array. Lines that can't be parsed are shown
+    /// as they are, followed by the error, and are skipped (and reported
+    /// on stderr) when printing the input.
+    #[clap(
+        long = "jsonl",
+        alias = "ndjson",
+        group = "data-format",
+        display_order = 1000
+    )]
+    pub jsonl: bool,
+
     /// Parse input as YAML, regardless of file extension.
     #[clap(long = "yaml", group = "data-format", display_order = 1000)]
     pub yaml: bool,
+
+    /// Parse input as TOML, regardless of file extension.
+    #[clap(long = "toml", group = "data-format", display_order = 1000)]
+    pub toml: bool,
+
+    /// Parse input as CSV, regardless of file extension. Each record
+    /// is shown as an object, keyed by the names in the header row.
+    #[clap(long = "csv", group = "data-format", display_order = 1000)]
+    pub csv: bool,
+
+    /// Parse input as TSV (tab-separated values), regardless of file
+    /// extension.
+    #[clap(long = "tsv", group = "data-format", display_order = 1000)]
+    pub tsv: bool,
+
+    /// Treat the first record of CSV or TSV input as data, rather than as
+    /// a header row. Each record will be shown as an array instead.
+    #[clap(long = "no-header", display_order = 1001)]
+    pub no_header: bool,
+
+    /// Show empty fields in CSV or TSV input as null instead of as empty
+    /// strings.
+    #[clap(long = "empty-as-null", display_order = 1001)]
+    pub empty_as_null: bool,
 }
 
 impl Opt {
     pub fn data_format(&self) -> Option<DataFormat> {
         if self.json {
             Some(DataFormat::Json)
+        } else if self.jsonl {
+            Some(DataFormat::Ndjson)
         } else if self.yaml {
             Some(DataFormat::Yaml)
+        } else if self.toml {
+            Some(DataFormat::Toml)
+        } else if self.csv {
+            Some(DataFormat::Csv)
+        } else if self.tsv {
+            Some(DataFormat::Tsv)
         } else {
             None
         }
     }
+
+    pub fn csv_options(&self, delimiter: char) -> CsvOptions {
+        CsvOptions {
+            delimiter,
+            has_header: !self.no_header,
+            empty_as_null: self.empty_as_null,
+        }
+    }
+}
+
+/// Returns the location of a configuration file in the jless config
+/// directory: $XDG_CONFIG_HOME/jless, or ~/.config/jless.
+pub fn config_file(name: &str) -> Option<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config_dir.join("jless").join(name))
 }
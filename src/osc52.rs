@@ -0,0 +1,109 @@
+// Support for copying text to the clipboard of the terminal emulator
+// using the OSC 52 escape sequence. Unlike the system clipboard, this
+// works when jless is being run on a remote machine, e.g., over SSH.
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Many terminals limit how long an OSC 52 sequence can be, and will
+// silently ignore (or truncate) longer sequences, so we refuse to copy
+// text whose encoded form is longer than this. (This matches the limit
+// used by hterm, which is one of the more restrictive terminals.)
+const MAX_ENCODED_LENGTH: usize = 74_994;
+
+// Returns the escape sequence that will set the clipboard contents to
+// the given text, taking into account whether we're running inside tmux.
+pub fn copy_sequence(text: &str) -> Result<String, String> {
+    copy_sequence_with_passthrough(text, std::env::var_os("TMUX").is_some())
+}
+
+fn copy_sequence_with_passthrough(text: &str, tmux_passthrough: bool) -> Result<String, String> {
+    let encoded = base64_encode(text.as_bytes());
+    if encoded.len() > MAX_ENCODED_LENGTH {
+        return Err(format!(
+            "{} bytes is too large to copy using OSC 52",
+            text.len()
+        ));
+    }
+
+    let sequence = format!("\x1b]52;c;{}\x07", encoded);
+
+    if tmux_passthrough {
+        // tmux requires sequences to be wrapped in a DCS passthrough
+        // sequence, with any escape characters doubled, to send them to
+        // the outer terminal. (This also requires `set -g allow-passthrough
+        // on` in newer versions of tmux.)
+        Ok(format!(
+            "\x1bPtmux;{}\x1b\\",
+            sequence.replace('\x1b', "\x1b\x1b")
+        ))
+    } else {
+        Ok(sequence)
+    }
+}
+
+// Whether we should try to copy using OSC 52 instead of using the system
+// clipboard, because we're running in a remote session.
+pub fn is_remote_session() -> bool {
+    ["SSH_CONNECTION", "SSH_CLIENT", "SSH_TTY"]
+        .iter()
+        .any(|var| std::env::var_os(var).is_some())
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+
+    for chunk in bytes.chunks(3) {
+        let b0 = chunk[0] as u32;
+        let b1 = chunk.get(1).copied().unwrap_or(0) as u32;
+        let b2 = chunk.get(2).copied().unwrap_or(0) as u32;
+        let triple = (b0 << 16) | (b1 << 8) | b2;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let sextet = (triple >> (18 - 6 * i)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[sextet as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!("", base64_encode(b""));
+        assert_eq!("Zg==", base64_encode(b"f"));
+        assert_eq!("Zm8=", base64_encode(b"fo"));
+        assert_eq!("Zm9v", base64_encode(b"foo"));
+        assert_eq!("Zm9vYg==", base64_encode(b"foob"));
+        assert_eq!("Zm9vYmE=", base64_encode(b"fooba"));
+        assert_eq!("Zm9vYmFy", base64_encode(b"foobar"));
+        assert_eq!("/+8=", base64_encode(&[0xff, 0xef]));
+    }
+
+    #[test]
+    fn test_copy_sequence() {
+        assert_eq!(
+            Ok("\x1b]52;c;eyJhIjogMX0=\x07".to_string()),
+            copy_sequence_with_passthrough("{\"a\": 1}", false),
+        );
+        assert_eq!(
+            Ok("\x1bPtmux;\x1b\x1b]52;c;eyJhIjogMX0=\x07\x1b\\".to_string()),
+            copy_sequence_with_passthrough("{\"a\": 1}", true),
+        );
+    }
+
+    #[test]
+    fn test_copy_sequence_too_large() {
+        let max_len = MAX_ENCODED_LENGTH / 4 * 3;
+        assert!(copy_sequence_with_passthrough(&"a".repeat(max_len), false).is_ok());
+        assert!(copy_sequence_with_passthrough(&"a".repeat(max_len + 1), false).is_err());
+    }
+}
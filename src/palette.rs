@@ -0,0 +1,243 @@
+// The command palette lists every command, including macros, and filters
+// them by what's typed into its prompt, so that commands can be found and
+// run without remembering the keys bound to them. The typed characters
+// only have to appear in order in a command's name (or description), so
+// "clsib" finds collapse-siblings.
+
+use termion::event::Key;
+
+use crate::keybindings::KeyCommand;
+use crate::prompt::{Prompt, PromptEvent};
+
+pub struct Palette {
+    pub prompt: Prompt,
+    entries: Vec<PaletteEntry>,
+    // The indexes of the entries matching the input, best match first,
+    // and which of them is selected.
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+pub struct PaletteEntry {
+    pub command: KeyCommand,
+    // The keys bound to the command, if any.
+    pub keys: String,
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PaletteEvent {
+    Changed,
+    Run(KeyCommand),
+    Cancel,
+}
+
+impl Palette {
+    pub fn new(entries: Vec<PaletteEntry>) -> Palette {
+        let mut palette = Palette {
+            prompt: Prompt::new(":: ", "", vec![]),
+            entries,
+            matches: vec![],
+            selected: 0,
+        };
+        palette.update_matches();
+        palette
+    }
+
+    pub fn handle_key(&mut self, key: Key) -> PaletteEvent {
+        match key {
+            Key::Up | Key::Ctrl('p') | Key::BackTab => {
+                self.selected = self.selected.saturating_sub(1);
+                return PaletteEvent::Changed;
+            }
+            Key::Down | Key::Ctrl('n') | Key::Char('\t') => {
+                self.selected = (self.selected + 1).min(self.matches.len().saturating_sub(1));
+                return PaletteEvent::Changed;
+            }
+            Key::Char('\n') | Key::Char('\r') => {
+                return match self.selected_entry() {
+                    Some(entry) => PaletteEvent::Run(entry.command),
+                    None => PaletteEvent::Cancel,
+                };
+            }
+            _ => {}
+        }
+
+        match self.prompt.handle_key(key) {
+            PromptEvent::Changed => {
+                self.update_matches();
+                PaletteEvent::Changed
+            }
+            PromptEvent::Unchanged | PromptEvent::Submit(_) => PaletteEvent::Changed,
+            PromptEvent::Cancel => PaletteEvent::Cancel,
+        }
+    }
+
+    pub fn insert_str(&mut self, s: &str) {
+        self.prompt.insert_str(s);
+        self.update_matches();
+    }
+
+    pub fn matches(&self) -> impl Iterator<Item = &PaletteEntry> {
+        self.matches.iter().map(move |index| &self.entries[*index])
+    }
+
+    // The index of the selected entry among the matches.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    fn selected_entry(&self) -> Option<&PaletteEntry> {
+        self.matches
+            .get(self.selected)
+            .map(|index| &self.entries[*index])
+    }
+
+    fn update_matches(&mut self) {
+        let query = self.prompt.input().trim();
+        let mut scored = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                // Matches in the name of a command are better than matches
+                // in its description.
+                let score = match match_score(query, &entry.name) {
+                    Some(score) => (0, score),
+                    None => (1, match_score(query, &entry.description)?),
+                };
+                Some((score, index))
+            })
+            .collect::<Vec<_>>();
+        // Ties are kept in the order the commands are listed in.
+        scored.sort();
+
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+        self.selected = 0;
+    }
+}
+
+// Whether the characters of the query appear in order in the text, and
+// if so, how good a match it is, as the number of places the match is
+// split, and where it starts; lower is better. Spaces in the query match
+// any non-alphanumeric character, e.g., the '-'s in the names of commands.
+fn match_score(query: &str, text: &str) -> Option<(usize, usize)> {
+    let query = query.chars().collect::<Vec<char>>();
+    let text = text.chars().collect::<Vec<char>>();
+    let first = match query.first() {
+        Some(first) => *first,
+        None => return Some((0, 0)),
+    };
+
+    // The leftmost match isn't always the best one, e.g., "sib" in
+    // "collapse siblings", so each place the query could start is tried.
+    (0..text.len())
+        .filter(|start| chars_match(first, text[*start]))
+        .filter_map(|start| {
+            let mut gaps = 0;
+            let mut next = start + 1;
+            for query_char in query[1..].iter() {
+                let offset = text[next..]
+                    .iter()
+                    .position(|ch| chars_match(*query_char, *ch))?;
+                if offset > 0 {
+                    gaps += 1;
+                }
+                next += offset + 1;
+            }
+            Some((gaps, start))
+        })
+        .min()
+}
+
+fn chars_match(query_char: char, ch: char) -> bool {
+    if query_char == ' ' {
+        !ch.is_alphanumeric()
+    } else {
+        ch.to_lowercase().eq(query_char.to_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: KeyCommand, name: &str, description: &str) -> PaletteEntry {
+        PaletteEntry {
+            command,
+            keys: String::new(),
+            name: name.to_owned(),
+            description: description.to_owned(),
+        }
+    }
+
+    fn type_keys(palette: &mut Palette, keys: &str) {
+        for ch in keys.chars() {
+            palette.handle_key(Key::Char(ch));
+        }
+    }
+
+    fn matching_names(palette: &Palette) -> Vec<&str> {
+        palette.matches().map(|entry| entry.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_match_score() {
+        assert_eq!(Some((0, 0)), match_score("collapse", "collapse siblings"));
+        assert_eq!(Some((3, 0)), match_score("clsib", "collapse siblings"));
+        assert_eq!(Some((0, 9)), match_score("sib", "collapse siblings"));
+        assert_eq!(Some((0, 0)), match_score("collapse s", "collapse-siblings"));
+        assert_eq!(Some((0, 0)), match_score("", "top"));
+        assert_eq!(None, match_score("xyz", "collapse siblings"));
+        assert_eq!(None, match_score("pot", "top"));
+    }
+
+    #[test]
+    fn test_palette() {
+        let mut palette = Palette::new(vec![
+            entry(KeyCommand::Top, "top", "Jump to the top of the input"),
+            entry(
+                KeyCommand::ExpandToDepth,
+                "expand-to-depth",
+                "Expand everything up to a depth",
+            ),
+            entry(
+                KeyCommand::CollapseToDepth,
+                "collapse-to-depth",
+                "Collapse everything below a depth",
+            ),
+            entry(
+                KeyCommand::CollapseSiblings,
+                "collapse-siblings",
+                "Collapse the focused node and its siblings",
+            ),
+        ]);
+        assert_eq!(4, matching_names(&palette).len());
+
+        type_keys(&mut palette, "collapse");
+        assert_eq!(
+            vec!["collapse-to-depth", "collapse-siblings"],
+            matching_names(&palette)
+        );
+
+        palette.handle_key(Key::Down);
+        assert_eq!(1, palette.selected());
+        assert_eq!(
+            PaletteEvent::Run(KeyCommand::CollapseSiblings),
+            palette.handle_key(Key::Char('\n'))
+        );
+
+        palette.handle_key(Key::Ctrl('u'));
+        type_keys(&mut palette, "dep");
+        assert_eq!(0, palette.selected());
+        assert_eq!(
+            vec!["expand-to-depth", "collapse-to-depth"],
+            matching_names(&palette)
+        );
+
+        type_keys(&mut palette, "xyz");
+        assert_eq!(0, matching_names(&palette).len());
+        assert_eq!(PaletteEvent::Cancel, palette.handle_key(Key::Char('\n')));
+    }
+}
@@ -0,0 +1,139 @@
+// Support for printing text from the input that contains control
+// characters. Printed as is, the terminal would interpret them (e.g., an
+// escape character starting an escape sequence), garbling the screen, so
+// they're shown as escapes like \u001b instead. Anything that lays out
+// lines needs to use the width of the escapes, rather than the width of
+// the characters themselves.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::fmt::Write;
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+// A str that escapes any control characters in it when displayed.
+pub struct Printable<'a>(pub &'a str);
+
+impl<'a> fmt::Display for Printable<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rest = self.0;
+
+        while let Some(i) = rest.find(char::is_control) {
+            f.write_str(&rest[..i])?;
+            let ch = rest[i..].chars().next().unwrap();
+            write_escape(ch, f)?;
+            rest = &rest[i + ch.len_utf8()..];
+        }
+
+        f.write_str(rest)
+    }
+}
+
+fn write_escape(ch: char, out: &mut dyn Write) -> fmt::Result {
+    match ch {
+        '\t' => out.write_str("\\t"),
+        '\n' => out.write_str("\\n"),
+        '\r' => out.write_str("\\r"),
+        '\u{8}' => out.write_str("\\b"),
+        '\u{c}' => out.write_str("\\f"),
+        _ => write!(out, "\\u{:04x}", ch as u32),
+    }
+}
+
+fn escape_width(ch: char) -> usize {
+    match ch {
+        '\t' | '\n' | '\r' | '\u{8}' | '\u{c}' => 2,
+        _ => 6,
+    }
+}
+
+// The number of columns a str takes up when printed.
+pub fn width(s: &str) -> usize {
+    if !s.contains(char::is_control) {
+        return UnicodeWidthStr::width(s);
+    }
+
+    s.chars().map(char_width).sum()
+}
+
+fn char_width(ch: char) -> usize {
+    if ch.is_control() {
+        escape_width(ch)
+    } else {
+        UnicodeWidthChar::width(ch).unwrap_or(0)
+    }
+}
+
+// The number of columns a str takes up when printed, but only looking at
+// as much of it as it takes to tell that it's wider than the limit, so
+// that very long strings don't have to be scanned completely. Strs that
+// are wider return something over the limit, rather than their width.
+pub fn width_up_to(s: &str, limit: usize) -> usize {
+    let mut width = 0;
+    for ch in s.chars() {
+        width += char_width(ch);
+        if width > limit {
+            break;
+        }
+    }
+    width
+}
+
+// The number of characters in as much of a str as fits in the limit, and
+// whether that's all of them.
+pub fn count_chars_up_to(s: &str, limit: usize) -> (usize, bool) {
+    let mut width = 0;
+    let mut count = 0;
+    for ch in s.chars() {
+        width += char_width(ch);
+        if width > limit {
+            return (count, false);
+        }
+        count += 1;
+    }
+    (count, true)
+}
+
+// Escapes any control characters in a str, for use when the escaped
+// version needs to be sliced up before being printed.
+pub fn escape(s: &str) -> Cow<'_, str> {
+    if s.contains(char::is_control) {
+        Cow::Owned(Printable(s).to_string())
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_printable() {
+        assert_eq!("abc", Printable("abc").to_string());
+        assert_eq!(
+            "\\u001b[31mred\\u001b[0m",
+            Printable("\x1b[31mred\x1b[0m").to_string()
+        );
+        assert_eq!("a\\tb\\u0000\\u009b", Printable("a\tb\0\u{9b}").to_string());
+        assert_eq!("é\\u007f", Printable("é\u{7f}").to_string());
+    }
+
+    #[test]
+    fn test_width() {
+        for s in ["abc", "日本", "\x1b[31mred", "a\tb\0\u{9b}", "é\u{7f}"].iter() {
+            assert_eq!(escape(s).width(), width(s), "{:?}", s);
+        }
+        assert_eq!(4, width("日本"));
+        assert_eq!(12, width("\x1b\u{0}"));
+
+        assert_eq!(3, width_up_to("abc", 5));
+        assert_eq!(6, width_up_to("abcdefgh", 5));
+        assert_eq!(6, width_up_to("日本語", 5));
+        assert_eq!(7, width_up_to("a\x1b\u{0}", 5));
+
+        assert_eq!((3, true), count_chars_up_to("abc", 5));
+        assert_eq!((5, false), count_chars_up_to("abcdefgh", 5));
+        assert_eq!((2, false), count_chars_up_to("日本語", 5));
+    }
+}
@@ -0,0 +1,88 @@
+// Support for --print, which prints the input to stdout instead of viewing
+// it. The output is pretty printed like FlatJson::pretty_printed, but keys
+// and values are highlighted using the colors of the theme.
+
+use std::fmt;
+
+use crate::flatjson::FlatJson;
+use crate::terminal::Terminal;
+use crate::theme::Theme;
+
+const INDENT: &str = "  ";
+
+pub fn print_highlighted(
+    flatjson: &FlatJson,
+    theme: &Theme,
+    out: &mut dyn Terminal,
+) -> fmt::Result {
+    for row in flatjson.0.iter() {
+        for _ in 0..row.depth {
+            out.write_str(INDENT)?;
+        }
+        if let Some(ref key_range) = row.key_range {
+            out.set_style(&theme.key)?;
+            out.write_str(&flatjson.1[key_range.clone()])?;
+            out.reset_style()?;
+            out.write_str(": ")?;
+        }
+
+        let mut trailing_comma = row.parent.is_some() && row.next_sibling.is_some();
+        if let Some(container_type) = row.value.container_type() {
+            if row.value.is_opening_of_container() {
+                out.write_str(container_type.open_str())?;
+                // Don't print trailing commas after { or [.
+                trailing_comma = false;
+            } else {
+                out.write_str(container_type.close_str())?;
+                // Check container opening to see if we have a next sibling.
+                trailing_comma = row.parent.is_some()
+                    && flatjson[row.pair_index().unwrap()].next_sibling.is_some();
+            }
+        } else {
+            out.set_style(theme.value_style(&row.value))?;
+            out.write_str(&flatjson.1[row.range.clone()])?;
+            out.reset_style()?;
+        }
+        if trailing_comma {
+            out.write_char(',')?;
+        }
+        out.write_char('\n')?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::flatjson::parse_top_level_json;
+    use crate::terminal::test::VisibleEscapesTerminal;
+    use crate::terminal::{GREEN, LIGHT_BLUE, MAGENTA, WHITE};
+
+    use super::*;
+
+    #[test]
+    fn test_print_highlighted() {
+        let fj = parse_top_level_json(r#"{"a": [1, "x"], "b": {}}"#.to_owned()).unwrap();
+        let mut term = VisibleEscapesTerminal::new(false, true);
+        print_highlighted(&fj, &Theme::default(), &mut term).unwrap();
+
+        let expected = format!(
+            indoc! {r#"
+                {{
+                  _FG({key})_"a"_R_: [
+                    _FG({number})_1_R_,
+                    _FG({string})_"x"_R_
+                  ],
+                  _FG({key})_"b"_R_: _FG({empty})_{{}}_R_
+                }}
+            "#},
+            key = LIGHT_BLUE,
+            number = MAGENTA,
+            string = GREEN,
+            empty = WHITE,
+        );
+        assert_eq!(expected, term.output());
+    }
+}
@@ -0,0 +1,249 @@
+// Support for showing how much of the input has been loaded, so that
+// jless doesn't look like it's hung while reading or parsing a large
+// input.
+
+use std::io;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+// Nothing is drawn until loading has taken long enough to be noticeable.
+const PROGRESS_DELAY: Duration = Duration::from_millis(250);
+// How often the progress line is redrawn.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+const PROGRESS_BAR_WIDTH: usize = 20;
+
+// The number of bytes of the input that have been consumed so far, which
+// may be updated from another thread.
+#[derive(Clone)]
+pub struct Progress {
+    consumed: Arc<AtomicUsize>,
+    // Unknown when reading from STDIN.
+    total: Option<usize>,
+}
+
+impl Progress {
+    pub fn new(total: Option<usize>) -> Progress {
+        Progress {
+            consumed: Arc::new(AtomicUsize::new(0)),
+            total,
+        }
+    }
+
+    pub fn consumed(&self) -> usize {
+        self.consumed.load(Ordering::Relaxed)
+    }
+
+    pub fn set_consumed(&self, consumed: usize) {
+        self.consumed.store(consumed, Ordering::Relaxed);
+    }
+
+    fn add_consumed(&self, bytes: usize) {
+        self.consumed.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    // Describes the progress so far, e.g.:
+    //
+    //   [#######             ]  35% (45.2 MB of 129.0 MB)
+    //
+    // or just the number of bytes consumed if the total is unknown.
+    pub fn describe(&self) -> String {
+        let consumed = self.consumed();
+
+        match self.total {
+            Some(total) if total > 0 => {
+                let consumed = consumed.min(total);
+                let filled = consumed * PROGRESS_BAR_WIDTH / total;
+                format!(
+                    "[{}{}] {:>3}% ({} of {})",
+                    "#".repeat(filled),
+                    " ".repeat(PROGRESS_BAR_WIDTH - filled),
+                    consumed * 100 / total,
+                    format_bytes(consumed),
+                    format_bytes(total),
+                )
+            }
+            _ => format_bytes(consumed),
+        }
+    }
+}
+
+// Wraps a reader to count the bytes read from it.
+pub struct ProgressReader<R> {
+    inner: R,
+    progress: Progress,
+}
+
+impl<R> ProgressReader<R> {
+    pub fn new(inner: R, progress: Progress) -> Self {
+        ProgressReader { inner, progress }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes = self.inner.read(buf)?;
+        self.progress.add_consumed(bytes);
+        Ok(bytes)
+    }
+}
+
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+// A single line, redrawn in place, that shows what's being loaded while
+// waiting to show the viewer.
+pub struct ProgressLine<'a> {
+    out: &'a mut dyn Write,
+    label: String,
+    started: Instant,
+    frames: usize,
+    last_drawn: Option<Instant>,
+}
+
+impl<'a> ProgressLine<'a> {
+    pub fn new(out: &'a mut dyn Write, label: String) -> Self {
+        ProgressLine {
+            out,
+            label,
+            started: Instant::now(),
+            frames: 0,
+            last_drawn: None,
+        }
+    }
+
+    // Draws the line, unless it's been drawn too recently.
+    pub fn draw(&mut self, status: &str) {
+        if self.started.elapsed() < PROGRESS_DELAY {
+            return;
+        }
+        if let Some(last_drawn) = self.last_drawn {
+            if last_drawn.elapsed() < PROGRESS_INTERVAL {
+                return;
+            }
+        }
+
+        self.frames += 1;
+        // There's nothing useful to do if this fails; the input will still
+        // be shown once it's loaded.
+        let _ = write!(
+            self.out,
+            "\r{}{} {} {}",
+            termion::clear::CurrentLine,
+            SPINNER[self.frames % SPINNER.len()],
+            self.label,
+            status
+        );
+        let _ = self.out.flush();
+        self.last_drawn = Some(Instant::now());
+    }
+
+    pub fn clear(&mut self) {
+        if self.last_drawn.take().is_some() {
+            let _ = write!(self.out, "\r{}", termion::clear::CurrentLine);
+            let _ = self.out.flush();
+        }
+    }
+}
+
+// Runs work on another thread, drawing a progress line until it finishes.
+// The thread has the default stack size unless one is given.
+pub fn run_with_progress<T, F, D>(
+    line: &mut ProgressLine,
+    work: F,
+    describe: D,
+    stack_size: Option<usize>,
+) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+    D: Fn() -> String,
+{
+    let (sender, receiver) = mpsc::channel();
+    let mut builder = std::thread::Builder::new();
+    if let Some(stack_size) = stack_size {
+        builder = builder.stack_size(stack_size);
+    }
+    let worker = builder
+        .spawn(move || {
+            // The receiver only goes away if the worker panics.
+            let _ = sender.send(work());
+        })
+        .unwrap();
+
+    loop {
+        match receiver.recv_timeout(PROGRESS_INTERVAL) {
+            Ok(result) => {
+                line.clear();
+                return result;
+            }
+            Err(RecvTimeoutError::Timeout) => line.draw(&describe()),
+            Err(RecvTimeoutError::Disconnected) => {
+                line.clear();
+                // Propagate the panic from the worker thread.
+                match worker.join() {
+                    Err(panic) => std::panic::resume_unwind(panic),
+                    Ok(()) => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!("0 B", format_bytes(0));
+        assert_eq!("1023 B", format_bytes(1023));
+        assert_eq!("1.0 KB", format_bytes(1024));
+        assert_eq!("1.5 MB", format_bytes(3 * 512 * 1024));
+        assert_eq!("129.0 GB", format_bytes(129 * 1024 * 1024 * 1024));
+    }
+
+    #[test]
+    fn test_describe_progress() {
+        let progress = Progress::new(Some(4096));
+        progress.set_consumed(1024);
+        assert_eq!(
+            "[#####               ]  25% (1.0 KB of 4.0 KB)",
+            progress.describe()
+        );
+
+        let progress = Progress::new(None);
+        let mut reader = ProgressReader::new(&b"abcdef"[..], progress.clone());
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).unwrap();
+        assert_eq!("6 B", progress.describe());
+    }
+
+    #[test]
+    fn test_progress_line_is_not_drawn_immediately() {
+        let mut out = vec![];
+        let mut line = ProgressLine::new(&mut out, "Reading".to_owned());
+        line.draw("1 B");
+        line.clear();
+        assert!(out.is_empty());
+    }
+}
@@ -0,0 +1,255 @@
+// A single line prompt that's edited using the keys read by the main
+// input loop, instead of by rustyline, so that jless can respond to the
+// input while it's being typed (e.g., to show incremental search results).
+// It supports a small set of readline style editing commands, and moving
+// through previous inputs.
+
+use termion::event::Key;
+use unicode_width::UnicodeWidthChar;
+
+use crate::printable;
+
+pub struct Prompt {
+    pub prompt: String,
+    input: String,
+    // Byte offset of the cursor in the input.
+    cursor: usize,
+    // Previous inputs, oldest first, the one being shown, if any, and the
+    // input that was being typed before moving through them.
+    history: Vec<String>,
+    history_index: Option<usize>,
+    typed_input: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PromptEvent {
+    // The input was edited.
+    Changed,
+    // Only the cursor moved, or the key didn't do anything.
+    Unchanged,
+    Submit(String),
+    Cancel,
+}
+
+impl Prompt {
+    pub fn new(prompt: &str, initial: &str, history: Vec<String>) -> Prompt {
+        Prompt {
+            prompt: prompt.to_owned(),
+            input: initial.to_owned(),
+            cursor: initial.len(),
+            history,
+            history_index: None,
+            typed_input: String::new(),
+        }
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn handle_key(&mut self, key: Key) -> PromptEvent {
+        match key {
+            Key::Char('\n') | Key::Char('\r') => {
+                return PromptEvent::Submit(std::mem::take(&mut self.input))
+            }
+            Key::Esc | Key::Ctrl('c') | Key::Ctrl('g') => return PromptEvent::Cancel,
+            // Like vim, deleting past the start of the input cancels it.
+            Key::Backspace | Key::Ctrl('h') if self.input.is_empty() => return PromptEvent::Cancel,
+            Key::Backspace | Key::Ctrl('h') => match self.prev_char_boundary() {
+                Some(start) => self.delete(start..self.cursor),
+                None => return PromptEvent::Unchanged,
+            },
+            Key::Delete | Key::Ctrl('d') => match self.next_char_boundary() {
+                Some(end) => self.delete(self.cursor..end),
+                None => return PromptEvent::Unchanged,
+            },
+            Key::Ctrl('u') => self.delete(0..self.cursor),
+            Key::Ctrl('k') => self.delete(self.cursor..self.input.len()),
+            Key::Ctrl('w') => {
+                let before_cursor = self.input[..self.cursor].trim_end();
+                let start = before_cursor
+                    .rfind(char::is_whitespace)
+                    .map(|i| i + 1)
+                    .unwrap_or(0);
+                self.delete(start..self.cursor);
+            }
+            Key::Left | Key::Ctrl('b') => {
+                self.cursor = self.prev_char_boundary().unwrap_or(self.cursor);
+                return PromptEvent::Unchanged;
+            }
+            Key::Right | Key::Ctrl('f') => {
+                self.cursor = self.next_char_boundary().unwrap_or(self.cursor);
+                return PromptEvent::Unchanged;
+            }
+            Key::Home | Key::Ctrl('a') => {
+                self.cursor = 0;
+                return PromptEvent::Unchanged;
+            }
+            Key::End | Key::Ctrl('e') => {
+                self.cursor = self.input.len();
+                return PromptEvent::Unchanged;
+            }
+            Key::Up | Key::Ctrl('p') => return self.move_through_history(true),
+            Key::Down | Key::Ctrl('n') => return self.move_through_history(false),
+            Key::Char(ch) => {
+                self.input.insert(self.cursor, ch);
+                self.cursor += ch.len_utf8();
+            }
+            _ => return PromptEvent::Unchanged,
+        }
+
+        PromptEvent::Changed
+    }
+
+    pub fn insert_str(&mut self, s: &str) {
+        self.input.insert_str(self.cursor, s);
+        self.cursor += s.len();
+    }
+
+    fn delete(&mut self, range: std::ops::Range<usize>) {
+        self.cursor = range.start;
+        self.input.replace_range(range, "");
+    }
+
+    fn prev_char_boundary(&self) -> Option<usize> {
+        self.input[..self.cursor]
+            .char_indices()
+            .next_back()
+            .map(|(i, _)| i)
+    }
+
+    fn next_char_boundary(&self) -> Option<usize> {
+        self.input[self.cursor..]
+            .chars()
+            .next()
+            .map(|ch| self.cursor + ch.len_utf8())
+    }
+
+    fn move_through_history(&mut self, older: bool) -> PromptEvent {
+        let index = match (self.history_index, older) {
+            (None, true) if !self.history.is_empty() => Some(self.history.len() - 1),
+            (Some(i), true) if i > 0 => Some(i - 1),
+            (Some(i), false) if i + 1 < self.history.len() => Some(i + 1),
+            (Some(_), false) => None,
+            _ => return PromptEvent::Unchanged,
+        };
+
+        if self.history_index.is_none() {
+            self.typed_input = std::mem::take(&mut self.input);
+        }
+        self.history_index = index;
+        self.input = match index {
+            Some(i) => self.history[i].clone(),
+            None => std::mem::take(&mut self.typed_input),
+        };
+        self.cursor = self.input.len();
+
+        PromptEvent::Changed
+    }
+
+    // The part of the prompt and input that fits in the given width, and
+    // the column of the cursor in it. When everything doesn't fit, the
+    // start is cut off so that the cursor is still visible.
+    pub fn visible(&self, width: usize) -> (String, usize) {
+        let text = format!("{}{}", self.prompt, printable::escape(&self.input));
+        let cursor = self.prompt.len() + printable::escape(&self.input[..self.cursor]).len();
+
+        let mut start = 0;
+        let mut cursor_column = printable::width(&text[..cursor]);
+        // Leave room for the cursor at the end.
+        while cursor_column + 1 > width && start < cursor {
+            let ch = text[start..].chars().next().unwrap();
+            start += ch.len_utf8();
+            cursor_column -= UnicodeWidthChar::width(ch).unwrap_or(0);
+        }
+
+        let mut end = start;
+        let mut used = 0;
+        for ch in text[start..].chars() {
+            let ch_width = UnicodeWidthChar::width(ch).unwrap_or(0);
+            if used + ch_width > width {
+                break;
+            }
+            used += ch_width;
+            end += ch.len_utf8();
+        }
+
+        (text[start..end].to_owned(), cursor_column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_keys(prompt: &mut Prompt, keys: &str) {
+        for ch in keys.chars() {
+            prompt.handle_key(Key::Char(ch));
+        }
+    }
+
+    #[test]
+    fn test_editing() {
+        let mut prompt = Prompt::new("/", "ab", vec![]);
+        type_keys(&mut prompt, "cd");
+        assert_eq!("abcd", prompt.input());
+
+        assert_eq!(PromptEvent::Unchanged, prompt.handle_key(Key::Left));
+        assert_eq!(PromptEvent::Changed, prompt.handle_key(Key::Backspace));
+        assert_eq!("abd", prompt.input());
+        type_keys(&mut prompt, "é");
+        assert_eq!("abéd", prompt.input());
+        prompt.handle_key(Key::Left);
+        prompt.handle_key(Key::Delete);
+        assert_eq!("abd", prompt.input());
+
+        prompt.handle_key(Key::Ctrl('a'));
+        type_keys(&mut prompt, "x ");
+        prompt.handle_key(Key::End);
+        type_keys(&mut prompt, " y");
+        assert_eq!("x abd y", prompt.input());
+        prompt.handle_key(Key::Ctrl('w'));
+        assert_eq!("x abd ", prompt.input());
+        prompt.handle_key(Key::Ctrl('w'));
+        assert_eq!("x ", prompt.input());
+        prompt.handle_key(Key::Ctrl('u'));
+        assert_eq!("", prompt.input());
+
+        assert_eq!(PromptEvent::Cancel, prompt.handle_key(Key::Backspace));
+        type_keys(&mut prompt, "abc");
+        assert_eq!(
+            PromptEvent::Submit("abc".to_owned()),
+            prompt.handle_key(Key::Char('\n'))
+        );
+    }
+
+    #[test]
+    fn test_history() {
+        let history = vec!["first".to_owned(), "second".to_owned()];
+        let mut prompt = Prompt::new("/", "typed", history);
+
+        prompt.handle_key(Key::Up);
+        assert_eq!("second", prompt.input());
+        prompt.handle_key(Key::Up);
+        assert_eq!("first", prompt.input());
+        assert_eq!(PromptEvent::Unchanged, prompt.handle_key(Key::Up));
+        assert_eq!("first", prompt.input());
+        prompt.handle_key(Key::Down);
+        prompt.handle_key(Key::Down);
+        assert_eq!("typed", prompt.input());
+        assert_eq!(PromptEvent::Unchanged, prompt.handle_key(Key::Down));
+    }
+
+    #[test]
+    fn test_visible() {
+        let mut prompt = Prompt::new("/", "abcdef", vec![]);
+        assert_eq!(("/abcdef".to_owned(), 7), prompt.visible(20));
+        assert_eq!(("cdef".to_owned(), 4), prompt.visible(5));
+
+        prompt.handle_key(Key::Home);
+        assert_eq!(("/abc".to_owned(), 1), prompt.visible(4));
+
+        let prompt = Prompt::new("/", "a\tb", vec![]);
+        assert_eq!(("/a\\tb".to_owned(), 5), prompt.visible(20));
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Write;
 use std::iter::Peekable;
 use std::ops::Range;
@@ -7,24 +7,116 @@ use rustyline::Editor;
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
+use crate::annotations::{self, Annotator};
 use crate::app::MAX_BUFFER_SIZE;
+use crate::decoders::{self, Decoder, DECODERS};
+use crate::diff::{Change, Diff};
 use crate::flatjson::{Index, OptionIndex, PathType, Row, Value};
+use crate::input;
 use crate::lineprinter as lp;
-use crate::search::{MatchRangeIter, SearchState};
+use crate::options::{LineNumbers, NumberBase};
+use crate::printable;
+use crate::prompt::Prompt;
+use crate::search::{MatchRangeIter, SearchState, SearchTarget};
 use crate::terminal;
 use crate::terminal::{AnsiTerminal, Terminal};
-use crate::truncatedstrview::{TruncatedStrSlice, TruncatedStrView};
+use crate::theme::Theme;
+use crate::truncatedstrview::{TruncatedStrSlice, TruncatedStrView, Truncation};
 use crate::types::TTYDimensions;
-use crate::viewer::{JsonViewer, Mode};
+use crate::viewer::{Action, JsonViewer, Mode};
 
 pub struct ScreenWriter {
     pub stdout: Box<dyn std::io::Write>,
     pub command_editor: Editor<()>,
     pub dimensions: TTYDimensions,
+    // Whether the terminal window currently has focus; when it doesn't,
+    // we dim the status bar.
+    pub focused: bool,
+    pub kitty_keyboard: bool,
+    // Whether the terminal is reporting mouse events to us, rather than
+    // handling them itself (e.g., to select text).
+    pub mouse_reporting: bool,
     terminal: AnsiTerminal,
 
     indentation_reduction: u16,
+    // The number of columns of indentation that are scrolled off the left
+    // side of the screen, so that deeply nested rows can be read without
+    // changing the indentation of every level.
+    horizontal_scroll: usize,
     truncated_row_value_views: HashMap<Index, TruncatedStrView>,
+    // The decoder (an index into decoders::DECODERS) whose decoded form of
+    // a row's value is shown in place of the value.
+    value_decoders: HashMap<Index, usize>,
+
+    // Whether long strings are wrapped across multiple lines, instead of
+    // truncated. Individual rows can be toggled separately, in which case
+    // they're added to wrapped_rows.
+    pub wrap_strings: bool,
+    wrapped_rows: HashSet<Index>,
+
+    // Whether to show the length of truncated strings and the number of
+    // elements in collapsed containers.
+    pub show_sizes: bool,
+    // Whether to show the type of each scalar value.
+    pub show_types: bool,
+    // Whether values in common formats, like dates, are annotated with
+    // what they mean, and which formats.
+    pub annotate: bool,
+    pub annotator: Annotator,
+    // Whether the values of an object's members are lined up in a column
+    // after its widest key, and the widest key of each object (in each
+    // mode, since keys are only quoted in line mode), so that it's only
+    // found once per object.
+    pub align_values: bool,
+    label_widths: HashMap<(Index, Mode), usize>,
+    // Whether arrays of objects with the same keys are shown as tables (in
+    // data mode), and the widths of the columns of each array that's been
+    // shown, or None for arrays that can't be shown as tables.
+    pub table_view: bool,
+    table_column_widths: HashMap<(Index, NumberBase), Option<Vec<usize>>>,
+    // Which part of values that don't fit is elided by default (it can
+    // be changed for individual values), and the character shown in place
+    // of elided text.
+    pub truncation: Truncation,
+    pub ellipsis: char,
+    // How many columns of long values are looked at (see LinePrinter).
+    pub max_value_width: Option<usize>,
+    // The base integer values are shown in; copied values are unaffected.
+    pub number_base: NumberBase,
+    pub line_numbers: LineNumbers,
+    // The number of columns each level of nesting is indented by, and
+    // whether to draw a guide line at each level.
+    pub indent_width: usize,
+    pub indent_guides: bool,
+    // Whether to show a one column minimap on the right side of the
+    // screen.
+    pub show_minimap: bool,
+    // Whether to mark which objects and arrays are collapsed in the
+    // gutter.
+    pub fold_column: bool,
+    pub theme: Theme,
+
+    // Shown in the status bar while the input is still being parsed.
+    pub loading_status: Option<String>,
+
+    // With --diff, the changes between the two inputs being compared,
+    // which are marked in the gutter.
+    pub diff: Option<Diff>,
+    // Set while printing one of the inputs being compared.
+    pane: Option<Pane>,
+    // With --diff, the row of the focused input shown on each line of the
+    // screen, for finding which row was clicked.
+    diff_rows: Vec<OptionIndex>,
+}
+
+// One side of the screen, when two inputs are shown side by side.
+struct Pane {
+    // The (0-based) columns of the screen the pane takes up.
+    columns: Range<usize>,
+    // Whether the focused viewer is shown in it. The state kept for
+    // individual rows, e.g., how far long values are scrolled, only
+    // applies to the focused viewer.
+    focused: bool,
 }
 
 pub enum MessageSeverity {
@@ -34,18 +126,22 @@ pub enum MessageSeverity {
 }
 
 impl MessageSeverity {
-    pub fn color(&self) -> terminal::Color {
+    pub fn style<'a>(&self, theme: &'a Theme) -> &'a terminal::Style {
         match self {
-            MessageSeverity::Info => terminal::WHITE,
-            MessageSeverity::Warn => terminal::YELLOW,
-            MessageSeverity::Error => terminal::RED,
+            MessageSeverity::Info => &theme.info_message,
+            MessageSeverity::Warn => &theme.warning_message,
+            MessageSeverity::Error => &theme.error_message,
         }
     }
 }
 
-const TAB_SIZE: usize = 2;
 const PATH_BASE: &str = "input";
 const SPACE_BETWEEN_PATH_AND_FILENAME: isize = 3;
+const ELIDED_PATH_COMPONENTS: &str = ".…";
+// A marker for changed rows, followed by a space.
+const DIFF_GUTTER_WIDTH: usize = 2;
+// A fold marker, followed by a space.
+const FOLD_COLUMN_WIDTH: usize = 2;
 
 impl ScreenWriter {
     pub fn init(
@@ -57,9 +153,38 @@ impl ScreenWriter {
             stdout,
             command_editor,
             dimensions,
+            focused: true,
+            kitty_keyboard: false,
+            mouse_reporting: true,
             terminal: AnsiTerminal::new(String::new()),
             indentation_reduction: 0,
+            horizontal_scroll: 0,
             truncated_row_value_views: HashMap::new(),
+            value_decoders: HashMap::new(),
+            wrap_strings: false,
+            wrapped_rows: HashSet::new(),
+            show_sizes: false,
+            show_types: false,
+            annotate: false,
+            annotator: Annotator::new(vec![]),
+            align_values: false,
+            label_widths: HashMap::new(),
+            table_view: false,
+            table_column_widths: HashMap::new(),
+            truncation: Truncation::End,
+            ellipsis: '…',
+            max_value_width: None,
+            number_base: NumberBase::Decimal,
+            line_numbers: LineNumbers::Off,
+            indent_width: 2,
+            indent_guides: false,
+            show_minimap: false,
+            fold_column: false,
+            theme: Theme::default(),
+            loading_status: None,
+            diff: None,
+            pane: None,
+            diff_rows: vec![],
         }
     }
 
@@ -116,52 +241,539 @@ impl ScreenWriter {
         }
     }
 
+    /// Prints two viewers side by side, with the differences between them
+    /// marked, followed by the status bar of the focused one. Each of the
+    /// lines has the rows of each viewer shown on a line of the screen,
+    /// starting from the top; the side of a row that's only in one of them
+    /// is left blank.
+    pub fn print_diff(
+        &mut self,
+        panes: [(&JsonViewer, &SearchState); 2],
+        lines: &[[OptionIndex; 2]],
+        focused_pane: usize,
+        input_buffer: &[u8],
+        input_filename: &str,
+        message: &Option<(String, MessageSeverity)>,
+    ) {
+        let result = self.print_diff_impl(panes, lines, focused_pane);
+        self.pane = None;
+        match result {
+            Ok(_) => {
+                if let Err(e) = self.terminal.flush_contents(&mut self.stdout) {
+                    eprintln!("Error while printing viewer: {}", e);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error while printing viewer: {}", e);
+            }
+        }
+
+        let (viewer, search_state) = panes[focused_pane];
+        self.print_status_bar(viewer, input_buffer, input_filename, search_state, message);
+    }
+
+    fn print_diff_impl(
+        &mut self,
+        panes: [(&JsonViewer, &SearchState); 2],
+        lines: &[[OptionIndex; 2]],
+        focused_pane: usize,
+    ) -> std::fmt::Result {
+        self.terminal.clear_screen()?;
+        self.annotator.now = annotations::now();
+        self.diff_rows.clear();
+
+        // The panes are separated by a vertical line.
+        let width = self.dimensions.width as usize;
+        let separator = width.saturating_sub(1) / 2;
+        let columns = [0..separator, separator + 1..width];
+        let height = panes[0].0.dimensions.height;
+
+        // The rows of each pane on the screen, for where search matches
+        // start from, relative line numbers, and the minimap.
+        let mut shown_rows = vec![vec![]; 2];
+        for line in lines.iter().take(height as usize) {
+            for (pane, &row) in line.iter().enumerate() {
+                if let OptionIndex::Index(index) = row {
+                    shown_rows[pane].push(index);
+                }
+            }
+        }
+        let relative_line_numbers = matches!(
+            self.line_numbers,
+            LineNumbers::Relative | LineNumbers::Hybrid
+        );
+        let mut search_matches = vec![];
+        let mut focused_positions = vec![];
+        for (pane, (viewer, search_state)) in panes.iter().enumerate() {
+            let top_row = shown_rows[pane].first().copied().unwrap_or(viewer.top_row);
+            search_matches.push(
+                search_state
+                    .matches_iter(viewer.flatjson[top_row].range.start)
+                    .peekable(),
+            );
+            focused_positions.push(if relative_line_numbers {
+                shown_rows[pane]
+                    .iter()
+                    .position(|&index| index == viewer.focused_row)
+            } else {
+                None
+            });
+        }
+
+        let mut positions = [0, 0];
+        let mut lines = lines.iter();
+        let mut row_index = 0;
+        while row_index < height {
+            let line = match lines.next() {
+                Some(line) => line,
+                None => {
+                    // Past the end of both inputs.
+                    for pane_columns in columns.iter() {
+                        self.terminal
+                            .position_cursor(pane_columns.start as u16 + 1, row_index + 1)?;
+                        self.terminal.set_fg(self.theme.muted_color)?;
+                        self.terminal.write_char('~')?;
+                    }
+                    row_index += 1;
+                    continue;
+                }
+            };
+
+            let mut line_height = 1;
+            for (pane, &(viewer, search_state)) in panes.iter().enumerate() {
+                let index = match line[pane] {
+                    OptionIndex::Index(index) => index,
+                    OptionIndex::Nil => continue,
+                };
+                self.pane = Some(Pane {
+                    columns: columns[pane].clone(),
+                    focused: pane == focused_pane,
+                });
+                let line_number = self.line_number(index, positions[pane], focused_positions[pane]);
+                let rows = self.print_line(
+                    viewer,
+                    row_index,
+                    index,
+                    line_number,
+                    &mut search_matches[pane],
+                    &search_state.current_match_range(),
+                )?;
+                line_height = line_height.max(rows);
+                positions[pane] += 1;
+            }
+            for _ in 0..line_height {
+                self.diff_rows.push(line[focused_pane]);
+            }
+            row_index += line_height;
+        }
+
+        if self.show_minimap {
+            for (pane, (viewer, search_state)) in panes.iter().enumerate() {
+                self.pane = Some(Pane {
+                    columns: columns[pane].clone(),
+                    focused: pane == focused_pane,
+                });
+                let top_row = shown_rows[pane].first().copied().unwrap_or(viewer.top_row);
+                let bottom_row = shown_rows[pane].last().copied().unwrap_or(top_row);
+                self.print_minimap(viewer, search_state, top_row, bottom_row)?;
+            }
+        }
+
+        self.terminal.set_style(&self.theme.dimmed)?;
+        for row in 0..height {
+            self.terminal
+                .position_cursor(separator as u16 + 1, row + 1)?;
+            self.terminal.write_char('│')?;
+        }
+
+        Ok(())
+    }
+
+    // Shows the list of keybindings in place of the viewer, starting from
+    // the given line of the list.
+    pub fn print_cheatsheet(&mut self, cheatsheet: &[(String, &str, &str)], top: usize) {
+        match self.print_cheatsheet_impl(cheatsheet, top) {
+            Ok(_) => match self.terminal.flush_contents(&mut self.stdout) {
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("Error while printing keybindings: {}", e);
+                }
+            },
+            Err(e) => {
+                eprintln!("Error while printing keybindings: {}", e);
+            }
+        }
+    }
+
+    fn print_cheatsheet_impl(
+        &mut self,
+        cheatsheet: &[(String, &str, &str)],
+        top: usize,
+    ) -> std::fmt::Result {
+        let height = self.dimensions.without_status_bar().height as usize;
+        self.print_commands(cheatsheet, top, None)?;
+
+        let title = format!(
+            "Keybindings {}-{} of {}",
+            (top + 1).min(cheatsheet.len()),
+            (top + height).min(cheatsheet.len()),
+            cheatsheet.len()
+        );
+        self.print_overlay_status_bar(&title, "Press ? or Esc to return")
+    }
+
+    // Shows the commands matching what's been typed into the command
+    // palette in place of the viewer, with the selected one marked, and
+    // the palette's prompt.
+    pub fn print_palette(
+        &mut self,
+        commands: &[(String, &str, &str)],
+        top: usize,
+        selected: usize,
+        prompt: &Prompt,
+    ) {
+        let title = if commands.is_empty() {
+            "No matching commands".to_owned()
+        } else {
+            format!("Command {} of {}", selected + 1, commands.len())
+        };
+        let result = self
+            .print_commands(commands, top, Some(selected))
+            .and_then(|_| self.print_overlay_status_bar(&title, ""));
+        match result {
+            Ok(_) => self.print_prompt(prompt),
+            Err(e) => {
+                eprintln!("Error while printing commands: {}", e);
+            }
+        }
+    }
+
+    // Lists commands, with the keys bound to them, starting from the given
+    // line of the list.
+    fn print_commands(
+        &mut self,
+        commands: &[(String, &str, &str)],
+        top: usize,
+        selected: Option<usize>,
+    ) -> std::fmt::Result {
+        self.terminal.clear_screen()?;
+
+        let width = self.dimensions.width as isize;
+        let height = self.dimensions.without_status_bar().height as usize;
+        let keys_width = commands
+            .iter()
+            .map(|(keys, _, _)| UnicodeWidthStr::width(keys.as_str()) as isize)
+            .max()
+            .unwrap_or(0)
+            .min(width / 2);
+
+        for (row, (keys, name, description)) in commands.iter().skip(top).take(height).enumerate() {
+            let is_selected = selected == Some(top + row);
+            if is_selected {
+                self.terminal.position_cursor(1, row as u16 + 1)?;
+                self.terminal.set_style(&self.theme.focused_key)?;
+                self.terminal.write_char('>')?;
+            }
+
+            self.terminal.position_cursor(3, row as u16 + 1)?;
+            self.terminal.set_style(if is_selected {
+                &self.theme.focused_key
+            } else {
+                &self.theme.key
+            })?;
+            let keys_view = TruncatedStrView::init_start(keys, keys_width);
+            write!(
+                self.terminal,
+                "{}",
+                TruncatedStrSlice {
+                    s: keys,
+                    truncated_view: &keys_view,
+                }
+            )?;
+
+            self.terminal.reset_style()?;
+            self.terminal
+                .position_cursor(keys_width as u16 + 5, row as u16 + 1)?;
+            let description = format!("{} ({})", description, name);
+            let description_view =
+                TruncatedStrView::init_start(&description, width - keys_width - 4);
+            write!(
+                self.terminal,
+                "{}",
+                TruncatedStrSlice {
+                    s: &description,
+                    truncated_view: &description_view,
+                }
+            )?;
+        }
+
+        for row in commands.len().saturating_sub(top)..height {
+            self.terminal.position_cursor(1, row as u16 + 1)?;
+            self.terminal.set_fg(self.theme.muted_color)?;
+            self.terminal.write_char('~')?;
+        }
+
+        Ok(())
+    }
+
+    /// Prints a list of statistics in place of the viewer, until any key
+    /// is pressed.
+    pub fn print_stats(&mut self, title: &str, stats: &[(&str, String)]) {
+        match self.print_stats_impl(title, stats) {
+            Ok(_) => match self.terminal.flush_contents(&mut self.stdout) {
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("Error while printing statistics: {}", e);
+                }
+            },
+            Err(e) => {
+                eprintln!("Error while printing statistics: {}", e);
+            }
+        }
+    }
+
+    fn print_stats_impl(&mut self, title: &str, stats: &[(&str, String)]) -> std::fmt::Result {
+        self.terminal.clear_screen()?;
+
+        let height = self.dimensions.without_status_bar().height as usize;
+        let labels_width = stats
+            .iter()
+            .map(|(label, _)| UnicodeWidthStr::width(*label))
+            .max()
+            .unwrap_or(0);
+
+        for (row, (label, value)) in stats.iter().take(height).enumerate() {
+            self.terminal.position_cursor(3, row as u16 + 1)?;
+            self.terminal.set_style(&self.theme.key)?;
+            self.terminal.write_str(label)?;
+
+            self.terminal.reset_style()?;
+            self.terminal
+                .position_cursor(labels_width as u16 + 5, row as u16 + 1)?;
+            self.terminal.write_str(value)?;
+        }
+
+        for row in stats.len()..height {
+            self.terminal.position_cursor(1, row as u16 + 1)?;
+            self.terminal.set_fg(self.theme.muted_color)?;
+            self.terminal.write_char('~')?;
+        }
+
+        self.print_overlay_status_bar(title, "Press any key to return")
+    }
+
+    // The status bar shown while something other than the viewer is shown:
+    // a title, and how to return to the viewer.
+    fn print_overlay_status_bar(&mut self, title: &str, hint: &str) -> std::fmt::Result {
+        let width = self.dimensions.width as isize;
+        self.terminal
+            .position_cursor(1, self.dimensions.height - 1)?;
+        self.terminal.set_style(&terminal::Style {
+            inverted: true,
+            dimmed: !self.focused,
+            ..terminal::Style::default()
+        })?;
+        let title_view = TruncatedStrView::init_start(title, width);
+        write!(
+            self.terminal,
+            "{}",
+            TruncatedStrSlice {
+                s: title,
+                truncated_view: &title_view,
+            }
+        )?;
+        for _ in title_view.used_space().unwrap_or(0)..width {
+            self.terminal.write_char(' ')?;
+        }
+
+        self.terminal.reset_style()?;
+        self.terminal.position_cursor(1, self.dimensions.height)?;
+        self.terminal.set_style(&self.theme.dimmed)?;
+        self.terminal.write_str(hint)?;
+
+        Ok(())
+    }
+
     fn print_screen_impl(
         &mut self,
         viewer: &JsonViewer,
         search_state: &SearchState,
     ) -> std::fmt::Result {
         self.terminal.clear_screen()?;
+        self.print_rows(viewer, search_state)
+    }
 
+    fn print_rows(&mut self, viewer: &JsonViewer, search_state: &SearchState) -> std::fmt::Result {
+        // Relative times in annotations are relative to when the rows are
+        // printed.
+        self.annotator.now = annotations::now();
+        let left = self.pane_columns().start as u16;
         let mut line = OptionIndex::Index(viewer.top_row);
         let mut search_matches = search_state
             .matches_iter(viewer.flatjson[line.unwrap()].range.start)
             .peekable();
         let current_match = search_state.current_match_range();
 
-        for row_index in 0..viewer.dimensions.height {
+        // Position of the focused row on the screen, for relative line
+        // numbers.
+        let mut focused_position = None;
+        if matches!(
+            self.line_numbers,
+            LineNumbers::Relative | LineNumbers::Hybrid
+        ) {
+            let mut line = OptionIndex::Index(viewer.top_row);
+            let mut position = 0;
+            while let OptionIndex::Index(index) = line {
+                if index == viewer.focused_row {
+                    focused_position = Some(position);
+                    break;
+                }
+                if position >= viewer.dimensions.height as usize {
+                    break;
+                }
+                position += 1;
+                line = Self::next_row(viewer, index);
+            }
+        }
+
+        let mut position = 0;
+        let mut row_index = 0;
+        let mut bottom_row = viewer.top_row;
+        while row_index < viewer.dimensions.height {
             match line {
                 OptionIndex::Nil => {
-                    self.terminal.position_cursor(1, row_index + 1)?;
-                    self.terminal.set_fg(terminal::LIGHT_BLACK)?;
+                    self.terminal.position_cursor(left + 1, row_index + 1)?;
+                    self.terminal.set_fg(self.theme.muted_color)?;
                     self.terminal.write_char('~')?;
+                    row_index += 1;
                 }
                 OptionIndex::Index(index) => {
-                    self.print_line(
+                    let line_number = self.line_number(index, position, focused_position);
+                    row_index += self.print_line(
                         viewer,
                         row_index,
                         index,
+                        line_number,
                         &mut search_matches,
                         &current_match,
                     )?;
-                    line = match viewer.mode {
-                        Mode::Line => viewer.flatjson.next_visible_row(index),
-                        Mode::Data => viewer.flatjson.next_item(index),
-                    };
+                    bottom_row = index;
+                    line = Self::next_row(viewer, index);
+                    position += 1;
+                }
+            }
+        }
+
+        if self.show_minimap {
+            self.print_minimap(viewer, search_state, viewer.top_row, bottom_row)?;
+        }
+
+        Ok(())
+    }
+
+    // The line number shown next to a row, at the given position on the
+    // screen. Relative line numbers are relative to the position of the
+    // focused row, if it's on the screen, while hybrid line numbers show
+    // the absolute number of the focused line.
+    fn line_number(&self, index: Index, position: usize, focused_position: Option<usize>) -> usize {
+        match focused_position {
+            Some(focused_position)
+                if self.line_numbers == LineNumbers::Relative || position != focused_position =>
+            {
+                if position > focused_position {
+                    position - focused_position
+                } else {
+                    focused_position - position
+                }
+            }
+            _ => index + 1,
+        }
+    }
+
+    // Prints a column on the right side of the screen that represents the
+    // whole input, scaled to the height of the screen. The part of the
+    // input that's on the screen is highlighted, and parts containing
+    // search matches are marked.
+    fn print_minimap(
+        &mut self,
+        viewer: &JsonViewer,
+        search_state: &SearchState,
+        top_row: Index,
+        bottom_row: Index,
+    ) -> std::fmt::Result {
+        let num_rows = viewer.flatjson.0.len();
+        let height = viewer.dimensions.height as usize;
+        let column = self.pane_columns().end as u16;
+
+        for (screen_row, rows) in minimap_cells(num_rows, height).enumerate() {
+            self.terminal
+                .position_cursor(column, screen_row as u16 + 1)?;
+
+            let rows = match rows {
+                Some(rows) => rows,
+                None => {
+                    self.terminal.reset_style()?;
+                    self.terminal.write_char(' ')?;
+                    continue;
                 }
+            };
+
+            let on_screen = rows.start <= bottom_row && top_row < rows.end;
+            let bytes = viewer.flatjson[rows.start].full_range().start
+                ..viewer.flatjson[rows.end - 1].full_range().end;
+            let has_match = search_state.any_match_in(bytes);
+
+            let mut style = terminal::Style {
+                fg: self.theme.muted_color,
+                inverted: on_screen,
+                ..terminal::Style::default()
+            };
+            if has_match && !on_screen {
+                style.fg = self.theme.search_match.fg;
             }
+            self.terminal.set_style(&style)?;
+
+            let ch = match (has_match, on_screen) {
+                (true, _) => '•',
+                (false, true) => ' ',
+                (false, false) => '│',
+            };
+            self.terminal.write_char(ch)?;
         }
 
         Ok(())
     }
 
-    pub fn get_command(&mut self, prompt: &str) -> rustyline::Result<String> {
+    pub fn get_command(
+        &mut self,
+        prompt: &str,
+        initial: &str,
+        history: &[String],
+    ) -> rustyline::Result<String> {
+        // Different prompts have different histories, so the editor's
+        // history gets replaced each time.
+        self.command_editor.clear_history();
+        for entry in history {
+            self.command_editor.add_history_entry(entry.as_str());
+        }
+
         write!(self.stdout, "{}", termion::cursor::Show)?;
+        // rustyline doesn't understand the kitty keyboard protocol.
+        if self.kitty_keyboard {
+            write!(self.stdout, "{}", input::DISABLE_KITTY_KEYBOARD)?;
+        }
         let _ = self.terminal.position_cursor(1, self.dimensions.height);
         self.terminal.flush_contents(&mut self.stdout)?;
 
-        let result = self.command_editor.readline(prompt);
+        let result = self
+            .command_editor
+            .readline_with_initial(prompt, (initial, ""));
         write!(self.stdout, "{}", termion::cursor::Hide)?;
+        // rustyline disables bracketed paste mode when it's done reading a line.
+        write!(self.stdout, "{}", input::ENABLE_BRACKETED_PASTE)?;
+        if self.kitty_keyboard {
+            write!(self.stdout, "{}", input::ENABLE_KITTY_KEYBOARD)?;
+        }
 
         let _ = self.terminal.position_cursor(1, self.dimensions.height);
         let _ = self.terminal.clear_line();
@@ -170,23 +782,128 @@ impl ScreenWriter {
         result
     }
 
+    // Draws a prompt that's being edited by the main input loop in place
+    // of the last line of the status bar, with the cursor shown in it.
+    pub fn print_prompt(&mut self, prompt: &Prompt) {
+        let (text, cursor_column) = prompt.visible(self.dimensions.width as usize);
+        let result = self
+            .terminal
+            .position_cursor(1, self.dimensions.height)
+            .and_then(|_| self.terminal.reset_style())
+            .and_then(|_| self.terminal.clear_line())
+            .and_then(|_| self.terminal.write_str(&text))
+            .and_then(|_| {
+                self.terminal
+                    .position_cursor(cursor_column as u16 + 1, self.dimensions.height)
+            });
+
+        if let Err(e) = result {
+            eprintln!("Error while printing prompt: {}", e);
+            return;
+        }
+
+        let result = self
+            .terminal
+            .flush_contents(&mut self.stdout)
+            .and_then(|_| write!(self.stdout, "{}", termion::cursor::Show))
+            .and_then(|_| self.stdout.flush());
+        if let Err(e) = result {
+            eprintln!("Error while printing prompt: {}", e);
+        }
+    }
+
+    pub fn hide_cursor(&mut self) {
+        let _ = write!(self.stdout, "{}", termion::cursor::Hide);
+    }
+
+    fn next_row(viewer: &JsonViewer, index: Index) -> OptionIndex {
+        match viewer.mode {
+            Mode::Line => viewer.flatjson.next_visible_row(index),
+            Mode::Data => viewer.flatjson.next_item(index),
+        }
+    }
+
+    fn prev_row(viewer: &JsonViewer, index: Index) -> OptionIndex {
+        match viewer.mode {
+            Mode::Line => viewer.flatjson.prev_visible_row(index),
+            Mode::Data => viewer.flatjson.prev_item(index),
+        }
+    }
+
+    // Prints a row of the viewer, and returns the number of lines of the
+    // screen it took up.
     fn print_line(
         &mut self,
         viewer: &JsonViewer,
         screen_index: u16,
         index: Index,
+        line_number: usize,
         search_matches: &mut Peekable<MatchRangeIter>,
         focused_search_match: &Range<usize>,
-    ) -> std::fmt::Result {
+    ) -> Result<u16, std::fmt::Error> {
         let is_focused = index == viewer.focused_row;
+        let left = self.pane_columns().start;
+        let in_focused_pane = self.pane.as_ref().map_or(true, |pane| pane.focused);
 
-        self.terminal.position_cursor(1, screen_index + 1)?;
+        self.terminal
+            .position_cursor(left as u16 + 1, screen_index + 1)?;
         let row = &viewer.flatjson[index];
 
-        let indentation_level = row
-            .depth
-            .saturating_sub(self.indentation_reduction as usize);
-        let indentation = indentation_level * TAB_SIZE;
+        let gutter_width = self.gutter_width(viewer);
+        let width = self.viewer_width().saturating_sub(gutter_width);
+        if let Some(diff) = &self.diff {
+            let opening_index = if row.is_closing_of_container() {
+                row.pair_index().unwrap()
+            } else {
+                index
+            };
+            match diff.change(viewer.path_hash(opening_index)) {
+                Some(change) => {
+                    self.terminal.set_style(match change {
+                        Change::Added => &self.theme.added,
+                        Change::Removed => &self.theme.removed,
+                        Change::Changed => &self.theme.changed,
+                    })?;
+                    self.terminal.write_char(change.marker())?;
+                }
+                None => self.terminal.write_char(' ')?,
+            }
+            self.terminal.reset_style()?;
+            self.terminal.write_char(' ')?;
+        }
+        let gutter_style = if is_focused {
+            &self.theme.focused_gutter
+        } else {
+            &self.theme.gutter
+        };
+        let line_numbers_width = self.line_numbers_width(viewer);
+        if line_numbers_width > 0 {
+            self.terminal.set_style(gutter_style)?;
+            write!(
+                self.terminal,
+                "{:>width$} ",
+                line_number,
+                width = line_numbers_width - 1
+            )?;
+        }
+        if self.fold_column {
+            // Only the opening rows of containers can be clicked to
+            // toggle them.
+            let marker = if !row.is_opening_of_container() {
+                ' '
+            } else if row.is_collapsed() {
+                self.theme.collapsed_marker
+            } else {
+                self.theme.expanded_marker
+            };
+            self.terminal.set_style(gutter_style)?;
+            write!(self.terminal, "{} ", marker)?;
+        }
+
+        let indentation = self.indentation(row);
+        let indent_guides = self.indent_guides(row);
+        let trailing_comma = Self::has_trailing_comma(viewer, row);
+        let wrap = self.wraps_row(viewer, index);
 
         let focused = is_focused;
 
@@ -198,6 +915,263 @@ impl ScreenWriter {
             }
         }
 
+        let search_matches_copy = (*search_matches).clone();
+        let transformed_value = if in_focused_pane {
+            self.transformed_value(viewer, index)
+        } else {
+            None
+        };
+        let aligned_label_width = self.aligned_label_width(viewer, row);
+        // Objects may still gain members while the input is being loaded,
+        // so their widths aren't cached until it's done.
+        if in_focused_pane && self.loading_status.is_none() {
+            if let Some(width) = aligned_label_width {
+                self.label_widths
+                    .insert((row.parent.unwrap(), viewer.mode), width);
+            }
+        }
+        let table_column_widths = self.table_column_widths(viewer, index);
+        // Like the widths of labels, these aren't cached until the input
+        // is done loading.
+        if let (true, None, Some((array, column_widths))) =
+            (in_focused_pane, &self.loading_status, &table_column_widths)
+        {
+            self.table_column_widths
+                .insert((*array, self.number_base), column_widths.clone());
+        }
+        let table = match table_column_widths {
+            Some((array, Some(column_widths))) => Some(lp::Table {
+                start_col: self.table_start_col(viewer, array),
+                column_widths,
+            }),
+            _ => None,
+        };
+        let cached_truncated_value = if in_focused_pane {
+            Some(self.truncated_row_value_views.entry(index))
+        } else {
+            None
+        };
+
+        let mut line = lp::LinePrinter {
+            mode: viewer.mode,
+            terminal: &mut self.terminal,
+
+            flatjson: &viewer.flatjson,
+            row,
+
+            width,
+            indentation,
+            indent_guides: &indent_guides,
+            // Columns are positioned relative to the start of the line.
+            gutter_width: left + gutter_width,
+
+            theme: &self.theme,
+
+            focused,
+            focused_because_matching_container_pair,
+            trailing_comma,
+
+            search_matches: Some(search_matches_copy),
+            focused_search_match,
+
+            cached_truncated_value,
+            wrap,
+            show_sizes: self.show_sizes,
+            show_types: self.show_types,
+            annotator: if self.annotate {
+                Some(&self.annotator)
+            } else {
+                None
+            },
+            aligned_label_width,
+            truncation: self.truncation,
+            ellipsis: self.ellipsis,
+            max_value_width: self.max_value_width,
+            number_base: self.number_base,
+            decoded: viewer.is_decoded(index),
+            transformed_value,
+            table: table.as_ref(),
+        };
+
+        // TODO: Handle error here? Or is never an error because writes
+        // to String should never fail?
+        line.print_line().unwrap();
+
+        // Don't print past the bottom of the screen.
+        let num_lines = line
+            .num_lines()
+            .min((viewer.dimensions.height - screen_index) as usize);
+
+        for i in 1..num_lines {
+            line.terminal
+                .position_cursor(left as u16 + 1, screen_index + i as u16 + 1)?;
+            line.print_wrapped_line(i).unwrap();
+        }
+
+        *search_matches = line.search_matches.unwrap();
+
+        Ok(num_lines as u16)
+    }
+
+    // The columns of the screen that a viewer is printed in: all of them,
+    // unless two inputs are being shown side by side.
+    fn pane_columns(&self) -> Range<usize> {
+        match &self.pane {
+            Some(pane) => pane.columns.clone(),
+            None => 0..self.dimensions.width as usize,
+        }
+    }
+
+    // The number of columns that rows are printed in, which excludes the
+    // minimap.
+    fn viewer_width(&self) -> usize {
+        let width = self.pane_columns().len();
+        if self.show_minimap {
+            width.saturating_sub(1)
+        } else {
+            width
+        }
+    }
+
+    // The width of the gutter, including a space separating it from the
+    // line: the markers for changed rows with --diff, line numbers, and
+    // fold markers.
+    fn gutter_width(&self, viewer: &JsonViewer) -> usize {
+        let diff_gutter_width = if self.diff.is_some() {
+            DIFF_GUTTER_WIDTH
+        } else {
+            0
+        };
+        let fold_column_width = if self.fold_column {
+            FOLD_COLUMN_WIDTH
+        } else {
+            0
+        };
+        diff_gutter_width + self.line_numbers_width(viewer) + fold_column_width
+    }
+
+    // The width of the line numbers, including a space after them.
+    fn line_numbers_width(&self, viewer: &JsonViewer) -> usize {
+        if self.line_numbers == LineNumbers::Off {
+            0
+        } else {
+            viewer.flatjson.0.len().to_string().len() + 1
+        }
+    }
+
+    fn indentation(&self, row: &Row) -> usize {
+        let indentation_level = row
+            .depth
+            .saturating_sub(self.indentation_reduction as usize);
+        (indentation_level * self.indent_width).saturating_sub(self.horizontal_scroll)
+    }
+
+    // The indentation of each of the levels of nesting that a row is in,
+    // which guide lines are drawn at. Levels that have been scrolled off
+    // the screen are left out.
+    fn indent_guides(&self, row: &Row) -> Vec<usize> {
+        if !self.indent_guides || self.indent_width == 0 {
+            return vec![];
+        }
+
+        (self.indentation_reduction as usize..row.depth)
+            .map(|level| (level - self.indentation_reduction as usize) * self.indent_width)
+            .filter(|indentation| *indentation >= self.horizontal_scroll)
+            .map(|indentation| indentation - self.horizontal_scroll)
+            .collect()
+    }
+
+    // When values are aligned, the width of the widest label among the
+    // members of the object a row is in.
+    fn aligned_label_width(&self, viewer: &JsonViewer, row: &Row) -> Option<usize> {
+        if !self.align_values || row.key_range.is_none() {
+            return None;
+        }
+        let parent = row.parent.unwrap();
+        // Only the widths for the focused viewer are cached, since the other
+        // one, with --diff, has different rows.
+        let in_focused_pane = self.pane.as_ref().map_or(true, |pane| pane.focused);
+        if let (true, Some(width)) = (
+            in_focused_pane,
+            self.label_widths.get(&(parent, viewer.mode)),
+        ) {
+            return Some(*width);
+        }
+
+        let flatjson = &viewer.flatjson;
+        let mut width = 0;
+        let mut member = flatjson[parent].first_child();
+        while let OptionIndex::Index(index) = member {
+            width = width.max(lp::label_width(flatjson, viewer.mode, &flatjson[index]));
+            member = flatjson[index].next_sibling;
+        }
+        Some(width)
+    }
+
+    // With the table view, the array a row is the header of, if it's an
+    // expanded array, or is a row of, if it's a collapsed object in one,
+    // along with the widths of the array's columns, if it can be shown as
+    // a table.
+    fn table_column_widths(
+        &self,
+        viewer: &JsonViewer,
+        index: Index,
+    ) -> Option<(Index, Option<Vec<usize>>)> {
+        let flatjson = &viewer.flatjson;
+        let row = &flatjson[index];
+        if !self.table_view || viewer.mode != Mode::Data || !row.is_opening_of_container() {
+            return None;
+        }
+
+        let array = if row.is_array() && row.is_expanded() {
+            index
+        } else {
+            match row.parent {
+                OptionIndex::Index(parent) if !row.is_array() && row.is_collapsed() => parent,
+                _ => return None,
+            }
+        };
+        let in_focused_pane = self.pane.as_ref().map_or(true, |pane| pane.focused);
+        if let (true, Some(widths)) = (
+            in_focused_pane,
+            self.table_column_widths.get(&(array, self.number_base)),
+        ) {
+            return Some((array, widths.clone()));
+        }
+
+        if !flatjson.is_table(array) {
+            return Some((array, None));
+        }
+        Some((
+            array,
+            Some(lp::table_column_widths(flatjson, array, self.number_base)),
+        ))
+    }
+
+    // The column that a table starts at, after both the label of the array
+    // and the widest label of its elements.
+    fn table_start_col(&self, viewer: &JsonViewer, array: Index) -> usize {
+        let flatjson = &viewer.flatjson;
+        let label_end = |index: Index| {
+            let row = &flatjson[index];
+            let mut end = lp::INDICATOR_WIDTH + self.indentation(row);
+            if row.key_range.is_some() || row.parent.is_some() {
+                let width = lp::label_width(flatjson, Mode::Data, row);
+                end += self
+                    .aligned_label_width(viewer, row)
+                    .unwrap_or(0)
+                    .max(width)
+                    + 2;
+            }
+            end
+        };
+
+        // The last element has the widest index.
+        let close_index = flatjson[array].pair_index().unwrap();
+        label_end(array).max(label_end(flatjson[close_index].last_child().unwrap()))
+    }
+
+    fn has_trailing_comma(viewer: &JsonViewer, row: &Row) -> bool {
         let mut trailing_comma = false;
 
         if viewer.mode == Mode::Line {
@@ -222,35 +1196,315 @@ impl ScreenWriter {
             }
         }
 
-        let search_matches_copy = (*search_matches).clone();
+        trailing_comma
+    }
 
-        let mut line = lp::LinePrinter {
-            mode: viewer.mode,
-            terminal: &mut self.terminal,
+    fn wraps_row(&self, viewer: &JsonViewer, index: Index) -> bool {
+        if let Some(Pane { focused: false, .. }) = self.pane {
+            return viewer.flatjson[index].is_string() && self.wrap_strings;
+        }
+
+        viewer.flatjson[index].is_string()
+            && self.wrap_strings != self.wrapped_rows.contains(&index)
+            && !self.value_decoders.contains_key(&index)
+    }
 
+    // The number of lines of the screen a row takes up.
+    fn row_num_lines(&self, viewer: &JsonViewer, index: Index) -> usize {
+        if !self.wraps_row(viewer, index) {
+            return 1;
+        }
+
+        let row = &viewer.flatjson[index];
+        let mut terminal = AnsiTerminal::new(String::new());
+
+        let line = lp::LinePrinter {
+            mode: viewer.mode,
+            terminal: &mut terminal,
             flatjson: &viewer.flatjson,
             row,
+            width: self
+                .viewer_width()
+                .saturating_sub(self.gutter_width(viewer)),
+            indentation: self.indentation(row),
+            indent_guides: &[],
+            gutter_width: self.gutter_width(viewer),
+            theme: &self.theme,
+            focused: false,
+            focused_because_matching_container_pair: false,
+            trailing_comma: Self::has_trailing_comma(viewer, row),
+            search_matches: None,
+            focused_search_match: &(0..0),
+            cached_truncated_value: None,
+            wrap: true,
+            show_sizes: self.show_sizes,
+            show_types: self.show_types,
+            // Wrapped strings aren't annotated.
+            annotator: None,
+            aligned_label_width: self.aligned_label_width(viewer, row),
+            truncation: self.truncation,
+            ellipsis: self.ellipsis,
+            max_value_width: self.max_value_width,
+            number_base: self.number_base,
+            decoded: viewer.is_decoded(index),
+            transformed_value: None,
+            // Only strings are wrapped, and they aren't part of tables.
+            table: None,
+        };
 
-            width: self.dimensions.width as usize,
-            indentation,
+        line.num_lines()
+    }
 
-            focused,
-            focused_because_matching_container_pair,
-            trailing_comma,
+    // Whether any strings are wrapped, so rows may take up more than one
+    // line of the screen.
+    pub fn wraps_any_rows(&self) -> bool {
+        self.wrap_strings || !self.wrapped_rows.is_empty()
+    }
 
-            search_matches: Some(search_matches_copy),
-            focused_search_match,
+    // Toggle whether a long string is wrapped, or truncated.
+    pub fn toggle_wrapped_row(&mut self, index: Index) {
+        if !self.wrapped_rows.remove(&index) {
+            self.wrapped_rows.insert(index);
+        }
+    }
+
+    // Toggle whether all long strings are wrapped. This resets any rows
+    // that were toggled individually.
+    pub fn toggle_wrap_strings(&mut self) {
+        self.wrap_strings = !self.wrap_strings;
+        self.wrapped_rows.clear();
+    }
 
-            cached_truncated_value: Some(self.truncated_row_value_views.entry(index)),
+    pub fn set_mouse_reporting(&mut self, enabled: bool) -> std::io::Result<()> {
+        let sequence = if enabled {
+            input::ENABLE_MOUSE_REPORTING
+        } else {
+            input::DISABLE_MOUSE_REPORTING
         };
+        write!(self.stdout, "{}", sequence)?;
+        self.stdout.flush()?;
+        self.mouse_reporting = enabled;
+        Ok(())
+    }
 
-        // TODO: Handle error here? Or is never an error because writes
-        // to String should never fail?
-        line.print_line().unwrap();
+    // Forget the state kept for individual rows (scroll positions,
+    // wrapping, and decoders), for when the rows of the viewer are replaced.
+    pub fn reset_row_state(&mut self) {
+        self.truncated_row_value_views.clear();
+        self.label_widths.clear();
+        self.table_column_widths.clear();
+        self.wrapped_rows.clear();
+        self.value_decoders.clear();
+    }
 
-        *search_matches = line.search_matches.unwrap();
+    // Switches to showing the focused value decoded by the next decoder
+    // that can decode it, or back to the original value after the last
+    // one. Returns the decoder now being used, if any.
+    pub fn cycle_focused_value_decoder(&mut self, viewer: &JsonViewer) -> Option<&'static Decoder> {
+        let index = viewer.focused_row;
+        let row = &viewer.flatjson[index];
+        let start = self.value_decoders.get(&index).map_or(0, |i| i + 1);
 
-        Ok(())
+        let next = (start..DECODERS.len())
+            .find(|&i| decoders::decode_value(&DECODERS[i], &viewer.flatjson, row).is_some());
+        match next {
+            Some(i) => self.value_decoders.insert(index, i),
+            None => self.value_decoders.remove(&index),
+        };
+        next.map(|i| &DECODERS[i])
+    }
+
+    pub fn focused_value_decoder(&self, viewer: &JsonViewer) -> Option<&'static Decoder> {
+        let i = self.value_decoders.get(&viewer.focused_row)?;
+        Some(&DECODERS[*i])
+    }
+
+    // The name of the decoder used for a row, and the decoded form of its
+    // value that's shown in its place, if it's being decoded. The decoded
+    // form is only derived when the row is shown.
+    pub fn transformed_value(
+        &self,
+        viewer: &JsonViewer,
+        index: Index,
+    ) -> Option<(&'static str, String)> {
+        let decoder = &DECODERS[*self.value_decoders.get(&index)?];
+        let decoded = decoders::decode_value(decoder, &viewer.flatjson, &viewer.flatjson[index])?;
+        Some((decoder.name, decoded))
+    }
+
+    // Resizes the screen and the viewer. When strings are wrapped, a
+    // different width changes how many lines each row takes up, which the
+    // viewer doesn't know about, so the top row is chosen again to keep the
+    // focused row at the same relative position on the screen.
+    pub fn resize(&mut self, viewer: &mut JsonViewer, dimensions: TTYDimensions) {
+        // The lines are counted before the width changes.
+        let old_height = (viewer.dimensions.height as usize).max(1);
+        let focused_line = self.focused_row_line(viewer);
+
+        self.dimensions = dimensions;
+        viewer.perform_action(Action::ResizeViewerDimensions(
+            dimensions.without_status_bar(),
+        ));
+
+        if let Some(line) = focused_line {
+            if self.wraps_any_rows() {
+                let height = viewer.dimensions.height as usize;
+                viewer.top_row = self.top_row_for_focused_line(viewer, line * height / old_height);
+                viewer.top_row = self.top_row_for_wrapped_lines(viewer);
+            }
+        }
+    }
+
+    // The number of lines of the screen above the focused row, if it's on
+    // the screen.
+    fn focused_row_line(&self, viewer: &JsonViewer) -> Option<usize> {
+        let height = viewer.dimensions.height as usize;
+        let mut num_lines = 0;
+        let mut line = OptionIndex::Index(viewer.top_row);
+        while let OptionIndex::Index(index) = line {
+            if num_lines >= height {
+                break;
+            }
+            if index == viewer.focused_row {
+                return Some(num_lines);
+            }
+            num_lines += self.row_num_lines(viewer, index);
+            line = Self::next_row(viewer, index);
+        }
+        None
+    }
+
+    // The top row that puts the focused row the given number of lines from
+    // the top of the screen, or as close to it as the rows before it allow.
+    fn top_row_for_focused_line(&self, viewer: &JsonViewer, focused_line: usize) -> Index {
+        let mut top_row = viewer.focused_row;
+        let mut num_lines = 0;
+        while let OptionIndex::Index(index) = Self::prev_row(viewer, top_row) {
+            num_lines += self.row_num_lines(viewer, index);
+            if num_lines > focused_line {
+                break;
+            }
+            top_row = index;
+        }
+        top_row
+    }
+
+    // The viewer assumes that every row takes up a single line of the
+    // screen, but when strings are wrapped that isn't the case, and the
+    // focused row could end up past the bottom of the screen. This returns
+    // the top row that should be used to keep the focused row (and
+    // scrolloff rows after it) on the screen.
+    pub fn top_row_for_wrapped_lines(&self, viewer: &JsonViewer) -> Index {
+        let mut top_row = viewer.top_row;
+        let focused_row = viewer.focused_row;
+
+        if (!self.wrap_strings && self.wrapped_rows.is_empty()) || focused_row < top_row {
+            return top_row;
+        }
+
+        let mut num_lines = 0;
+        let mut line = OptionIndex::Index(top_row);
+        while let OptionIndex::Index(index) = line {
+            num_lines += self.row_num_lines(viewer, index);
+            if index >= focused_row {
+                break;
+            }
+            line = Self::next_row(viewer, index);
+        }
+
+        let mut padding = 0;
+        let mut line = Self::next_row(viewer, focused_row);
+        while let OptionIndex::Index(index) = line {
+            if padding >= viewer.scrolloff() as usize {
+                break;
+            }
+            padding += 1;
+            line = Self::next_row(viewer, index);
+        }
+
+        let height = viewer.dimensions.height as usize;
+        while top_row < focused_row && num_lines + padding > height {
+            num_lines -= self.row_num_lines(viewer, top_row);
+            top_row = Self::next_row(viewer, top_row).unwrap();
+        }
+
+        top_row
+    }
+
+    // The number of rows to scroll by to move down (or up) by a page, i.e.,
+    // the number of rows on the screen (or that would fit on the screen
+    // above the top row), accounting for wrapped strings that take up
+    // multiple lines of the screen.
+    pub fn rows_per_page(&self, viewer: &JsonViewer, down: bool) -> usize {
+        let height = viewer.dimensions.height as usize;
+        if !self.wraps_any_rows() {
+            return height;
+        }
+
+        let mut rows = 0;
+        let mut num_lines = 0;
+        let mut line = if down {
+            OptionIndex::Index(viewer.top_row)
+        } else {
+            Self::prev_row(viewer, viewer.top_row)
+        };
+        while let OptionIndex::Index(index) = line {
+            num_lines += self.row_num_lines(viewer, index);
+            if num_lines > height {
+                break;
+            }
+            rows += 1;
+            line = if down {
+                Self::next_row(viewer, index)
+            } else {
+                Self::prev_row(viewer, index)
+            };
+        }
+
+        // Still move if there's a row that doesn't fit on the screen.
+        rows.max(1)
+    }
+
+    // Convert a (1-based) row of the screen to the (1-based) row of
+    // the viewer displayed there, accounting for wrapped strings that
+    // take up multiple lines of the screen. With --diff, there's no row
+    // of the focused viewer next to the rows only in the other input.
+    pub fn screen_row_to_viewer_row(&self, viewer: &JsonViewer, screen_row: u16) -> Option<u16> {
+        if self.diff.is_some() {
+            let index = match self.diff_rows.get(screen_row as usize - 1) {
+                Some(&OptionIndex::Index(index)) => index,
+                _ => return None,
+            };
+            let mut viewer_row = 1;
+            let mut line = OptionIndex::Index(viewer.top_row);
+            while let OptionIndex::Index(row) = line {
+                if row >= index {
+                    break;
+                }
+                viewer_row += 1;
+                line = Self::next_row(viewer, row);
+            }
+            return Some(viewer_row);
+        }
+
+        if !self.wrap_strings && self.wrapped_rows.is_empty() {
+            return Some(screen_row);
+        }
+
+        let mut num_lines = 0;
+        let mut viewer_row = 0;
+        let mut line = OptionIndex::Index(viewer.top_row);
+        while let OptionIndex::Index(index) = line {
+            viewer_row += 1;
+            num_lines += self.row_num_lines(viewer, index) as u16;
+            if screen_row <= num_lines {
+                return Some(viewer_row);
+            }
+            line = Self::next_row(viewer, index);
+        }
+
+        // Past the end of the input.
+        Some(viewer_row + (screen_row - num_lines))
     }
 
     fn line_primitive_value_ref<'a, 'b>(
@@ -283,6 +1537,7 @@ impl ScreenWriter {
             .position_cursor(1, self.dimensions.height - 1)?;
         self.terminal.set_style(&terminal::Style {
             inverted: true,
+            dimmed: !self.focused,
             ..terminal::Style::default()
         })?;
         // Need to print a line to ensure the entire bar with the path to
@@ -305,18 +1560,43 @@ impl ScreenWriter {
         self.terminal.position_cursor(1, self.dimensions.height)?;
 
         if let Some((contents, severity)) = message {
-            self.terminal.set_style(&terminal::Style {
-                fg: severity.color(),
-                ..terminal::Style::default()
-            })?;
+            self.terminal.set_style(severity.style(&self.theme))?;
             self.terminal.write_str(contents)?;
         } else if let Some((match_num, just_wrapped)) = search_state.active_search_state() {
             self.terminal
                 .write_char(search_state.direction.prompt_char())?;
             self.terminal.write_str(&search_state.search_term)?;
 
-            // Print out which match we're on:
-            let match_tracker = format!("[{}/{}]", match_num + 1, search_state.num_matches());
+            if let Some(scope) = search_state.scope {
+                let scope_path = viewer
+                    .flatjson
+                    .build_path_to_node(PathType::DotWithTopLevelIndex, scope)
+                    .unwrap();
+                write!(
+                    self.terminal,
+                    " (search scoped to {}{})",
+                    PATH_BASE, scope_path
+                )?;
+            }
+
+            match search_state.target {
+                SearchTarget::Everything => {}
+                SearchTarget::Keys => self.terminal.write_str(" (keys only)")?,
+                SearchTarget::Values => self.terminal.write_str(" (values only)")?,
+            }
+
+            // Print out which match we're on. While the input is still
+            // being loaded, there may be more matches in the rest of it.
+            let match_tracker = format!(
+                "[{}/{}{}]",
+                match_num + 1,
+                search_state.num_matches(),
+                if self.loading_status.is_some() {
+                    "+"
+                } else {
+                    ""
+                }
+            );
             self.terminal.position_cursor(
                 self.dimensions.width
                     - (1 + MAX_BUFFER_SIZE as u16)
@@ -326,6 +1606,9 @@ impl ScreenWriter {
 
             let wrapped_char = if just_wrapped { 'W' } else { ' ' };
             write!(self.terminal, " {} {}", wrapped_char, match_tracker)?;
+        } else if let Some(loading_status) = &self.loading_status {
+            self.terminal.set_style(&self.theme.dimmed)?;
+            self.terminal.write_str(loading_status)?;
         } else {
             write!(self.terminal, ":")?;
         }
@@ -354,10 +1637,22 @@ impl ScreenWriter {
         filename: &str,
         width: isize,
     ) -> std::fmt::Result {
+        // Keys may contain control characters.
+        let path_to_node = printable::escape(path_to_node);
+        let path_to_node: &str = &path_to_node;
         let base_len = PATH_BASE.len() as isize;
         let path_display_width = UnicodeWidthStr::width(path_to_node) as isize;
         let row = self.dimensions.height - 1;
 
+        // If the whole path doesn't fit, we'll try to show the start and end
+        // of it, and elide components from the middle.
+        if base_len + path_display_width > width {
+            let full_path = format!("{}{}", PATH_BASE, path_to_node);
+            if let Some((prefix_end, suffix_start)) = middle_truncated_path(&full_path, width) {
+                return self.print_middle_truncated_path(&full_path, prefix_end, suffix_start);
+            }
+        }
+
         let space_available_for_filename =
             width - base_len - path_display_width - SPACE_BETWEEN_PATH_AND_FILENAME;
         let mut space_available_for_base = width - path_display_width;
@@ -371,7 +1666,7 @@ impl ScreenWriter {
             TruncatedStrView::init_start(filename, space_available_for_filename);
 
         if truncated_filename.any_contents_visible() {
-            let filename_width = truncated_filename.used_space().unwrap() as isize;
+            let filename_width = truncated_filename.used_space().unwrap();
             space_available_for_base -= filename_width - SPACE_BETWEEN_PATH_AND_FILENAME;
         }
 
@@ -379,7 +1674,7 @@ impl ScreenWriter {
 
         self.terminal.position_cursor(1, row)?;
         self.terminal.set_style(&inverted_style)?;
-        self.terminal.set_bg(terminal::LIGHT_BLACK)?;
+        self.terminal.set_bg(self.theme.muted_color)?;
 
         let base_slice = TruncatedStrSlice {
             s: PATH_BASE,
@@ -409,7 +1704,7 @@ impl ScreenWriter {
         }
 
         if truncated_filename.any_contents_visible() {
-            let filename_width = truncated_filename.used_space().unwrap() as isize;
+            let filename_width = truncated_filename.used_space().unwrap();
 
             self.terminal
                 .position_cursor(self.dimensions.width - (filename_width as u16) + 1, row)?;
@@ -426,12 +1721,57 @@ impl ScreenWriter {
         Ok(())
     }
 
-    pub fn decrease_indentation_level(&mut self, max_depth: u16) {
-        self.indentation_reduction = self.indentation_reduction.saturating_add(1).min(max_depth);
+    fn print_middle_truncated_path(
+        &mut self,
+        full_path: &str,
+        prefix_end: usize,
+        suffix_start: usize,
+    ) -> std::fmt::Result {
+        let inverted_style = terminal::Style {
+            inverted: true,
+            ..terminal::Style::default()
+        };
+
+        self.terminal
+            .position_cursor(1, self.dimensions.height - 1)?;
+        self.terminal.set_style(&inverted_style)?;
+
+        let base_end = prefix_end.min(PATH_BASE.len());
+        self.terminal.set_bg(self.theme.muted_color)?;
+        self.terminal.write_str(&full_path[..base_end])?;
+        self.terminal.set_bg(terminal::DEFAULT)?;
+
+        self.terminal.write_str(&full_path[base_end..prefix_end])?;
+        self.terminal.write_str(ELIDED_PATH_COMPONENTS)?;
+        self.terminal.write_str(&full_path[suffix_start..])?;
+
+        Ok(())
     }
 
-    pub fn increase_indentation_level(&mut self) {
-        self.indentation_reduction = self.indentation_reduction.saturating_sub(1)
+    pub fn decrease_indentation_level(&mut self, max_depth: u16, count: u16) {
+        self.indentation_reduction = self
+            .indentation_reduction
+            .saturating_add(count)
+            .min(max_depth);
+    }
+
+    pub fn increase_indentation_level(&mut self, count: u16) {
+        self.indentation_reduction = self.indentation_reduction.saturating_sub(count)
+    }
+
+    // Scrolls the screen to the right (or left) by a number of columns.
+    // Rows are never scrolled past the start of their keys (or values), so
+    // this can only scroll as far as the indentation of the most deeply
+    // nested row.
+    pub fn scroll_horizontally(&mut self, viewer: &JsonViewer, columns: usize, to_right: bool) {
+        let max_scroll = viewer.flatjson.2 * self.indent_width;
+        self.horizontal_scroll = if to_right {
+            self.horizontal_scroll
+                .saturating_add(columns)
+                .min(max_scroll)
+        } else {
+            self.horizontal_scroll.saturating_sub(columns)
+        };
     }
 
     pub fn scroll_focused_line_right(&mut self, viewer: &JsonViewer, count: usize) {
@@ -465,6 +1805,45 @@ impl ScreenWriter {
         }
     }
 
+    pub fn focused_value_is_truncated(&self, viewer: &JsonViewer) -> bool {
+        let row = viewer.focused_row;
+        match (
+            self.truncated_row_value_views.get(&row),
+            self.line_primitive_value_ref(&viewer.flatjson[row], viewer),
+        ) {
+            (Some(tsv), Some(value_ref)) => tsv.truncation(value_ref).is_some(),
+            _ => false,
+        }
+    }
+
+    // Scrolls the focused value by about as many characters as are
+    // visible, so that a long value can be read a chunk at a time.
+    pub fn scroll_focused_line_by_chunk(&mut self, viewer: &JsonViewer, to_right: bool) {
+        if let Some(tsv) = self.truncated_row_value_views.get(&viewer.focused_row) {
+            // Leave room for the ellipses on either side.
+            let chunk = (tsv.available_space() - 2).max(1) as usize;
+            self.scroll_focused_line(viewer, chunk, to_right);
+        }
+    }
+
+    // Changes which part of the focused value is elided, from its end, to
+    // its middle, to its start, and back again. Returns what's elided now,
+    // or None if the value isn't truncated.
+    pub fn cycle_focused_value_truncation(&mut self, viewer: &JsonViewer) -> Option<Truncation> {
+        let row = viewer.focused_row;
+        let tsv = *self.truncated_row_value_views.get(&row)?;
+        let value_ref = self.line_primitive_value_ref(&viewer.flatjson[row], viewer)?;
+
+        let truncation = match tsv.truncation(value_ref)? {
+            Truncation::End => Truncation::Middle,
+            Truncation::Middle => Truncation::Start,
+            Truncation::Start => Truncation::End,
+        };
+        let tsv = TruncatedStrView::init(value_ref, tsv.available_space(), truncation);
+        self.truncated_row_value_views.insert(row, tsv);
+        Some(truncation)
+    }
+
     pub fn scroll_focused_line_to_an_end(&mut self, viewer: &JsonViewer) {
         let row = viewer.focused_row;
         let tsv = self.truncated_row_value_views.get(&row);
@@ -530,3 +1909,149 @@ impl ScreenWriter {
         }
     }
 }
+
+// The range of rows of the input that each line of a minimap of the given
+// height represents. When there are fewer rows than lines, each row gets
+// its own line, and the rest of the minimap is empty.
+fn minimap_cells(num_rows: usize, height: usize) -> impl Iterator<Item = Option<Range<Index>>> {
+    (0..height).map(move |line| {
+        if num_rows <= height {
+            if line < num_rows {
+                Some(line..line + 1)
+            } else {
+                None
+            }
+        } else {
+            Some(line * num_rows / height..(line + 1) * num_rows / height)
+        }
+    })
+}
+
+// Splits a path generated by build_path_to_node into its components, e.g.,
+// `input.a[0]["b.c"]` into `input`, `.a`, `[0]` and `["b.c"]`.
+fn path_components(path: &str) -> Vec<Range<usize>> {
+    let mut components = vec![];
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for (i, ch) in path.char_indices() {
+        if in_quotes {
+            match ch {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_quotes = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match ch {
+            '.' | '[' if i != start => {
+                components.push(start..i);
+                start = i;
+            }
+            '"' => in_quotes = true,
+            _ => {}
+        }
+    }
+
+    if start < path.len() {
+        components.push(start..path.len());
+    }
+
+    components
+}
+
+// Determine how to show a path that doesn't fit in the available width, by
+// keeping as many components as possible from the start and the end of the
+// path, and eliding the components in between, e.g., `input.a.b.….y.z`.
+// Returns the end of the kept prefix and the start of the kept suffix, or
+// None if not even the last component fits.
+fn middle_truncated_path(path: &str, width: isize) -> Option<(usize, usize)> {
+    let components = path_components(path);
+    let component_width = |i: usize| UnicodeWidthStr::width(&path[components[i].clone()]) as isize;
+
+    let mut available_space = width - UnicodeWidthStr::width(ELIDED_PATH_COMPONENTS) as isize;
+    // Components [0, prefix_len) and [suffix_start, len) are shown.
+    let mut prefix_len = 0;
+    let mut suffix_start = components.len();
+    let mut prefix_done = false;
+    let mut suffix_done = false;
+
+    // Alternate between adding components from the end and the start, so
+    // we show a similar amount of each.
+    while prefix_len < suffix_start && !(prefix_done && suffix_done) {
+        if !suffix_done {
+            let w = component_width(suffix_start - 1);
+            if w <= available_space {
+                available_space -= w;
+                suffix_start -= 1;
+            } else {
+                suffix_done = true;
+            }
+        }
+
+        if !prefix_done && prefix_len < suffix_start {
+            let w = component_width(prefix_len);
+            if w <= available_space {
+                available_space -= w;
+                prefix_len += 1;
+            } else {
+                prefix_done = true;
+            }
+        }
+    }
+
+    if suffix_start == components.len() {
+        return None;
+    }
+
+    let prefix_end = if prefix_len == 0 {
+        0
+    } else {
+        components[prefix_len - 1].end
+    };
+
+    Some((prefix_end, components[suffix_start].start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatjson::parse_top_level_json;
+
+    #[test]
+    fn test_resize_with_wrapped_strings() {
+        let strings = (0..30)
+            .map(|i| format!("\"{}{}\"", i, "x".repeat(100)))
+            .collect::<Vec<_>>();
+        let json = format!("[{}]", strings.join(", "));
+        let mut viewer = JsonViewer::new(parse_top_level_json(json).unwrap(), Mode::Data);
+        let mut screen_writer = ScreenWriter::init(
+            Box::new(std::io::sink()),
+            Editor::<()>::new(),
+            TTYDimensions::default(),
+        );
+        screen_writer.wrap_strings = true;
+
+        // Wide enough that nothing wraps.
+        screen_writer.resize(&mut viewer, TTYDimensions::from_size((200, 22)));
+        viewer.perform_action(Action::MoveDown(10));
+        viewer.top_row = screen_writer.top_row_for_wrapped_lines(&viewer);
+        assert_eq!(Some(10), screen_writer.focused_row_line(&viewer));
+
+        // Each string now takes up several lines, so the focused row would
+        // be past the bottom of the screen if the top row didn't change.
+        screen_writer.resize(&mut viewer, TTYDimensions::from_size((40, 22)));
+        let line = screen_writer.focused_row_line(&viewer).unwrap();
+        let num_lines = screen_writer.row_num_lines(&viewer, viewer.focused_row);
+        assert!(num_lines > 1);
+        assert!(line + num_lines <= viewer.dimensions.height as usize);
+        assert!((8..=10).contains(&line), "focused row is on line {}", line);
+
+        // And back again, where it can be put on exactly the same line.
+        screen_writer.resize(&mut viewer, TTYDimensions::from_size((200, 22)));
+        assert_eq!(Some(line), screen_writer.focused_row_line(&viewer));
+    }
+}
@@ -1,9 +1,12 @@
 use std::borrow::Cow;
+use std::fs;
+use std::io;
 use std::ops::Range;
+use std::path::PathBuf;
 
 use regex::{Captures, Regex, RegexBuilder};
 
-use crate::flatjson::{FlatJson, Index};
+use crate::flatjson::{FlatJson, Index, OptionIndex, Row, Value};
 
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum SearchDirection {
@@ -20,21 +23,66 @@ impl SearchDirection {
     }
 }
 
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum SearchMode {
+    // Search patterns are interpreted as regular expressions (with
+    // square and curly bracket escaping inverted).
+    Regex,
+    // Search patterns are matched literally.
+    Literal,
+}
+
+// What parts of the input search patterns may match.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum SearchTarget {
+    Everything,
+    // Only object keys, including their quotes.
+    Keys,
+    // Only strings, numbers, booleans, and nulls.
+    Values,
+}
+
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum JumpDirection {
     Next,
     Prev,
 }
 
+// The result of stepping to the next (or previous) match.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum SearchStep {
+    Jumped {
+        row: Index,
+        // Which end of the file the search wrapped around, if it did.
+        wrapped: Option<SearchDirection>,
+    },
+    // Wrapping around is disabled, and there are no more matches in the
+    // direction being searched.
+    NoMoreMatches(SearchDirection),
+    // The pattern doesn't match anything.
+    NoMatches,
+}
+
 pub struct SearchState {
     pub direction: SearchDirection,
 
     pub search_term: String,
+    // The container that the search is limited to, if any.
+    pub scope: Option<Index>,
+    pub target: SearchTarget,
 
     matches: Vec<Range<usize>>,
+    // The pattern being searched for, so that rows added to the input
+    // while it's still being loaded can be searched too.
+    regex: Option<Regex>,
 
     immediate_state: ImmediateSearchState,
     pub ever_searched: bool,
+
+    // Whether to keep highlighting matches after the user is no longer
+    // actively searching (like vim's 'hlsearch'), until they're dismissed.
+    pub persist_highlights: bool,
+    highlights_dismissed: bool,
 }
 
 pub enum ImmediateSearchState {
@@ -46,6 +94,82 @@ pub enum ImmediateSearchState {
     },
 }
 
+const MAX_SEARCH_HISTORY_SIZE: usize = 100;
+
+/// Previously entered search inputs, oldest first, which may be persisted
+/// to disk so that they are available in future invocations.
+pub struct SearchHistory {
+    entries: Vec<String>,
+    file: Option<PathBuf>,
+}
+
+impl SearchHistory {
+    pub fn new(file: Option<PathBuf>) -> SearchHistory {
+        let mut entries = vec![];
+
+        if let Some(path) = &file {
+            // It's fine if the file doesn't exist yet.
+            if let Ok(contents) = fs::read_to_string(path) {
+                entries = contents.lines().map(|line| line.to_owned()).collect();
+            }
+        }
+
+        let mut history = SearchHistory { entries, file };
+        history.truncate();
+        history
+    }
+
+    /// Returns the default location to persist search history to:
+    /// $XDG_DATA_HOME/jless/search_history, or
+    /// ~/.local/share/jless/search_history.
+    pub fn default_file() -> Option<PathBuf> {
+        let data_dir = match std::env::var_os("XDG_DATA_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+        };
+
+        Some(data_dir.join("jless").join("search_history"))
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    pub fn add(&mut self, entry: &str) {
+        if entry.is_empty() || self.entries.last().map(String::as_str) == Some(entry) {
+            return;
+        }
+
+        self.entries.push(entry.to_owned());
+        self.truncate();
+
+        // Failing to persist history shouldn't get in the way of searching.
+        let _ = self.save();
+    }
+
+    fn truncate(&mut self) {
+        if self.entries.len() > MAX_SEARCH_HISTORY_SIZE {
+            let excess = self.entries.len() - MAX_SEARCH_HISTORY_SIZE;
+            self.entries.drain(..excess);
+        }
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let path = match &self.file {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut contents = self.entries.join("\n");
+        contents.push('\n');
+        fs::write(path, contents)
+    }
+}
+
 pub type MatchRangeIter<'a> = std::slice::Iter<'a, Range<usize>>;
 const STATIC_EMPTY_SLICE: &[Range<usize>] = &[];
 
@@ -62,35 +186,108 @@ impl SearchState {
         SearchState {
             direction: SearchDirection::Forward,
             search_term: "".to_owned(),
+            scope: None,
+            target: SearchTarget::Everything,
             matches: vec![],
+            regex: None,
             immediate_state: ImmediateSearchState::NotSearching,
             ever_searched: false,
+            persist_highlights: false,
+            highlights_dismissed: false,
         }
     }
 
-    fn extract_search_term_and_case_sensitivity(search_input: &str) -> (&str, bool) {
-        let regex_input;
+    // Search input may end with a slash followed by any of the following
+    // flags:
+    //
+    // - 's': Perform a case-sensitive search
+    // - 'l': Match the pattern literally, instead of as a regex
+    // - 't': Only search within the subtree of the focused node
+    // - 'k': Only match object keys
+    // - 'v': Only match values (not keys)
+    //
+    // Like vim, '\c' or '\C' anywhere in the pattern will force a case
+    // insensitive or case sensitive search, respectively, taking precedence
+    // over everything else.
+    fn extract_search_term_and_options(
+        search_input: &str,
+    ) -> (Cow<'_, str>, bool, SearchMode, bool, SearchTarget) {
+        let mut regex_input = search_input;
         let mut case_sensitive_specified = false;
+        let mut mode = SearchMode::Regex;
+        let mut scoped_to_subtree = false;
+        let mut target = SearchTarget::Everything;
+
+        if let Some((before_slash, flags)) = search_input.rsplit_once('/') {
+            if flags.chars().all(|ch| "sltkv".contains(ch)) {
+                regex_input = before_slash;
+                case_sensitive_specified = flags.contains('s');
+                if flags.contains('l') {
+                    mode = SearchMode::Literal;
+                }
+                scoped_to_subtree = flags.contains('t');
+                // Asking for both is the same as asking for neither.
+                target = match (flags.contains('k'), flags.contains('v')) {
+                    (true, false) => SearchTarget::Keys,
+                    (false, true) => SearchTarget::Values,
+                    _ => SearchTarget::Everything,
+                };
+            }
+        }
 
-        if let Some(stripped_of_slash) = search_input.strip_suffix('/') {
-            regex_input = stripped_of_slash;
-        } else if let Some(stripped_of_slash_s) = search_input.strip_suffix("/s") {
-            regex_input = stripped_of_slash_s;
-            case_sensitive_specified = true;
-        } else {
-            regex_input = search_input;
+        let (regex_input, case_override) = Self::extract_case_override(regex_input);
+
+        let case_sensitive = match case_override {
+            Some(case_sensitive) => case_sensitive,
+            None => case_sensitive_specified || UPPER_CASE.is_match(&regex_input),
+        };
+
+        (regex_input, case_sensitive, mode, scoped_to_subtree, target)
+    }
+
+    // Removes any '\c' or '\C' from the pattern, and returns whether the
+    // search should be case sensitive, if either was present. If both are
+    // present, '\c' wins, like in vim.
+    fn extract_case_override(pattern: &str) -> (Cow<'_, str>, Option<bool>) {
+        if !pattern.contains("\\c") && !pattern.contains("\\C") {
+            return (Cow::Borrowed(pattern), None);
+        }
+
+        let mut stripped = String::with_capacity(pattern.len());
+        let mut case_insensitive_specified = false;
+        let mut case_sensitive_specified = false;
+        let mut chars = pattern.chars();
+
+        while let Some(ch) = chars.next() {
+            if ch != '\\' {
+                stripped.push(ch);
+                continue;
+            }
+
+            // Keep other escape sequences (including "\\") intact.
+            match chars.next() {
+                Some('c') => case_insensitive_specified = true,
+                Some('C') => case_sensitive_specified = true,
+                Some(escaped) => {
+                    stripped.push('\\');
+                    stripped.push(escaped);
+                }
+                None => stripped.push('\\'),
+            }
         }
 
-        let case_sensitive = if case_sensitive_specified {
-            true
+        let case_override = if case_insensitive_specified {
+            Some(false)
+        } else if case_sensitive_specified {
+            Some(true)
         } else {
-            UPPER_CASE.is_match(regex_input)
+            None
         };
 
-        (regex_input, case_sensitive)
+        (Cow::Owned(stripped), case_override)
     }
 
-    fn invert_square_and_curly_bracket_escaping(regex: &str) -> Cow<str> {
+    fn invert_square_and_curly_bracket_escaping(regex: &str) -> Cow<'_, str> {
         SQUARE_AND_CURLY_BRACKETS.replace_all(regex, |caps: &Captures| match &caps[0] {
             "\\[" => "[".to_owned(),
             "[" => "\\[".to_owned(),
@@ -106,36 +303,189 @@ impl SearchState {
 
     pub fn initialize_search(
         search_input: String,
-        haystack: &str,
+        flatjson: &FlatJson,
         direction: SearchDirection,
+        focused_row: Index,
     ) -> Result<SearchState, String> {
-        let (regex_input, case_sensitive) =
-            Self::extract_search_term_and_case_sensitivity(&search_input);
+        let (regex_input, case_sensitive, mode, scoped_to_subtree, target) =
+            Self::extract_search_term_and_options(&search_input);
 
         if regex_input.is_empty() {
             return Ok(Self::empty());
         }
 
+        let pattern = match mode {
+            SearchMode::Regex => Self::invert_square_and_curly_bracket_escaping(&regex_input),
+            SearchMode::Literal => Cow::Owned(regex::escape(&regex_input)),
+        };
+
         // The default Display implementation for these errors spills
         // onto multiple lines.
-        let inverted = Self::invert_square_and_curly_bracket_escaping(regex_input);
-
-        let regex = RegexBuilder::new(&inverted)
+        let regex = RegexBuilder::new(&pattern)
             .case_insensitive(!case_sensitive)
             .build()
             .map_err(|e| format!("{}", e).replace('\n', " "))?;
 
-        let matches: Vec<Range<usize>> = regex.find_iter(haystack).map(|m| m.range()).collect();
+        let scope = if scoped_to_subtree {
+            Some(Self::subtree_scope(flatjson, focused_row))
+        } else {
+            None
+        };
+
+        let haystack = &flatjson.1;
+        let matches: Vec<Range<usize>> = match scope {
+            Some(scope) => {
+                let scope_range = &flatjson[scope].range;
+                regex
+                    .find_iter(&haystack[scope_range.clone()])
+                    .map(|m| m.start() + scope_range.start..m.end() + scope_range.start)
+                    .collect()
+            }
+            None => regex.find_iter(haystack).map(|m| m.range()).collect(),
+        };
+        let matches = match target {
+            SearchTarget::Everything => matches,
+            _ => Self::restrict_matches_to_target(matches, &flatjson.0, target),
+        };
+        let matches = Self::drop_placeholder_matches(matches, &flatjson.0);
 
         Ok(SearchState {
             direction,
-            search_term: regex_input.to_owned(),
+            scope,
+            target,
+            search_term: regex_input.into_owned(),
             matches,
+            regex: Some(regex),
             immediate_state: ImmediateSearchState::NotSearching,
             ever_searched: true,
+            persist_highlights: false,
+            highlights_dismissed: false,
         })
     }
 
+    // Only keeps the matches that are entirely within a key, or entirely
+    // within a value. Both the matches and the rows are in order, so they
+    // can be walked through together.
+    fn restrict_matches_to_target(
+        matches: Vec<Range<usize>>,
+        rows: &[Row],
+        target: SearchTarget,
+    ) -> Vec<Range<usize>> {
+        let mut target_ranges = rows
+            .iter()
+            .filter_map(|row| match target {
+                SearchTarget::Keys => row.key_range.clone(),
+                SearchTarget::Values if row.is_primitive() => Some(row.range.clone()),
+                _ => None,
+            })
+            .peekable();
+
+        matches
+            .into_iter()
+            .filter(|m| {
+                while let Some(range) = target_ranges.peek() {
+                    if range.end > m.start {
+                        break;
+                    }
+                    target_ranges.next();
+                }
+
+                match target_ranges.peek() {
+                    Some(range) => range.start <= m.start && m.end <= range.end,
+                    None => false,
+                }
+            })
+            .collect()
+    }
+
+    // Drops matches in the placeholders of containers that were nested too
+    // deeply to be parsed, which aren't in the input.
+    fn drop_placeholder_matches(matches: Vec<Range<usize>>, rows: &[Row]) -> Vec<Range<usize>> {
+        let placeholders = rows
+            .iter()
+            .filter(|row| matches!(row.value, Value::TooDeep))
+            .map(|row| row.range.clone())
+            .collect::<Vec<Range<usize>>>();
+        if placeholders.is_empty() {
+            return matches;
+        }
+
+        matches
+            .into_iter()
+            .filter(|m| {
+                let next = placeholders.partition_point(|range| range.end <= m.start);
+                next == placeholders.len() || placeholders[next].start >= m.end
+            })
+            .collect()
+    }
+
+    /// Searches the rows that have been added to the end of the input,
+    /// while it's being loaded in the background, given where in the
+    /// pretty printed input they start. Any matches after that, i.e., in
+    /// placeholders for the ends of containers that have been replaced,
+    /// are dropped. Searches scoped to a subtree aren't updated.
+    pub fn search_appended_rows(&mut self, flatjson: &FlatJson, start: usize) {
+        let regex = match (&self.regex, self.scope) {
+            (Some(regex), None) => regex,
+            _ => return,
+        };
+
+        self.matches.retain(|m| m.end <= start);
+        let matches: Vec<Range<usize>> = regex
+            .find_iter(&flatjson.1[start..])
+            .map(|m| m.start() + start..m.end() + start)
+            .collect();
+        let first_row = flatjson
+            .0
+            .partition_point(|row| row.full_range().start < start);
+        let rows = &flatjson.0[first_row..];
+        let matches = match self.target {
+            SearchTarget::Everything => matches,
+            target => Self::restrict_matches_to_target(matches, rows, target),
+        };
+        self.matches
+            .extend(Self::drop_placeholder_matches(matches, rows));
+
+        if let ImmediateSearchState::ActivelySearching {
+            last_match_jumped_to,
+            ..
+        } = &mut self.immediate_state
+        {
+            if self.matches.is_empty() {
+                self.immediate_state = ImmediateSearchState::NotSearching;
+            } else {
+                *last_match_jumped_to = (*last_match_jumped_to).min(self.matches.len() - 1);
+            }
+        }
+    }
+
+    // Subtree scoped searches search within the focused container, or the
+    // parent of a focused primitive.
+    fn subtree_scope(flatjson: &FlatJson, focused_row: Index) -> Index {
+        let row = &flatjson[focused_row];
+        if row.is_closing_of_container() {
+            row.pair_index().unwrap()
+        } else if row.is_opening_of_container() {
+            focused_row
+        } else {
+            match row.parent {
+                OptionIndex::Index(parent) => parent,
+                // A top-level primitive; its range is the whole subtree.
+                OptionIndex::Nil => focused_row,
+            }
+        }
+    }
+
+    // The rows containing the start of each match, in order, without
+    // duplicates. (These are the rows that jumping to each match focuses.)
+    pub fn matching_rows(&self, flatjson: &FlatJson) -> Vec<Index> {
+        let mut rows: Vec<Index> = (0..self.matches.len())
+            .map(|match_index| self.compute_destination_row(flatjson, match_index))
+            .collect();
+        rows.dedup();
+        rows
+    }
+
     pub fn active_search_state(&self) -> Option<(usize, bool)> {
         match self.immediate_state {
             ImmediateSearchState::NotSearching => None,
@@ -155,6 +505,22 @@ impl SearchState {
         !self.matches.is_empty()
     }
 
+    /// Whether any of the matches are in the given range of the input,
+    /// unless the highlights have been dismissed.
+    pub fn any_match_in(&self, range: Range<usize>) -> bool {
+        if self.highlights_dismissed {
+            return false;
+        }
+
+        let first_ending_after_start = self
+            .matches
+            .partition_point(|match_range| match_range.end <= range.start);
+        match self.matches.get(first_ending_after_start) {
+            Some(match_range) => match_range.start < range.end,
+            None => false,
+        }
+    }
+
     pub fn no_matches_message(&self) -> String {
         format!("Pattern not found: {}", self.search_term)
     }
@@ -163,6 +529,15 @@ impl SearchState {
         self.immediate_state = ImmediateSearchState::NotSearching;
     }
 
+    /// Stop highlighting matches, even if persist_highlights is set. They
+    /// will be highlighted again after the next jump to a match.
+    pub fn dismiss_highlights(&mut self) {
+        self.set_no_longer_actively_searching();
+        self.highlights_dismissed = true;
+    }
+
+    // Jumps to a match, always wrapping around the ends of the file.
+    #[cfg(test)]
     pub fn jump_to_match(
         &mut self,
         focused_row: Index,
@@ -174,6 +549,27 @@ impl SearchState {
             panic!("Shouldn't call jump_to_match if no matches");
         }
 
+        match self.step(focused_row, flatjson, jump_direction, jumps, true) {
+            SearchStep::Jumped { row, .. } => row,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Jumps to the next (or previous) match, reporting whether the search
+    /// wrapped around the start or end of the file. If `wrap` is false, it
+    /// doesn't jump when it would have wrapped around.
+    pub fn step(
+        &mut self,
+        focused_row: Index,
+        flatjson: &FlatJson,
+        jump_direction: JumpDirection,
+        jumps: usize,
+        wrap: bool,
+    ) -> SearchStep {
+        if self.matches.is_empty() {
+            return SearchStep::NoMatches;
+        }
+
         let true_direction = self.true_direction(jump_direction);
 
         let next_match_index = self.get_next_match(focused_row, flatjson, true_direction, jumps);
@@ -204,6 +600,11 @@ impl SearchState {
             }
         };
 
+        if wrapped && !wrap {
+            return SearchStep::NoMoreMatches(true_direction);
+        }
+
+        self.highlights_dismissed = false;
         self.immediate_state = ImmediateSearchState::ActivelySearching {
             last_match_jumped_to: next_match_index,
             // We keep track of whether we searched into an object, so that
@@ -212,26 +613,35 @@ impl SearchState {
             just_wrapped: wrapped,
         };
 
-        next_focused_row
+        SearchStep::Jumped {
+            row: next_focused_row,
+            wrapped: if wrapped { Some(true_direction) } else { None },
+        }
     }
 
-    /// Return an iterator over all the stored matches. We pass in a
-    /// start index that will be used to efficiently skip any matches
-    /// before that index.
-    pub fn matches_iter(&self, range_start: usize) -> MatchRangeIter {
-        match self.immediate_state {
-            ImmediateSearchState::NotSearching => STATIC_EMPTY_SLICE.iter(),
-            ImmediateSearchState::ActivelySearching { .. } => {
-                let search_result = self
-                    .matches
-                    .binary_search_by(|probe| probe.end.cmp(&range_start));
-                let start_index = match search_result {
-                    Ok(i) => i,
-                    Err(i) => i,
-                };
-                self.matches[start_index..].iter()
+    /// Return an iterator over all the stored matches that should be
+    /// highlighted. We pass in a start index that will be used to
+    /// efficiently skip any matches before that index.
+    pub fn matches_iter(&self, range_start: usize) -> MatchRangeIter<'_> {
+        let highlight_matches = match self.immediate_state {
+            ImmediateSearchState::NotSearching => {
+                self.persist_highlights && !self.highlights_dismissed
             }
+            ImmediateSearchState::ActivelySearching { .. } => true,
+        };
+
+        if !highlight_matches {
+            return STATIC_EMPTY_SLICE.iter();
         }
+
+        let search_result = self
+            .matches
+            .binary_search_by(|probe| probe.end.cmp(&range_start));
+        let start_index = match search_result {
+            Ok(i) => i,
+            Err(i) => i,
+        };
+        self.matches[start_index..].iter()
     }
 
     /// Returns the range of the currently focused match, or an empty range
@@ -256,7 +666,7 @@ impl SearchState {
     }
 
     fn get_next_match(
-        &mut self,
+        &self,
         focused_row: Index,
         flatjson: &FlatJson,
         true_direction: SearchDirection,
@@ -373,7 +783,9 @@ mod tests {
 
     use super::JumpDirection::*;
     use super::SearchDirection::*;
-    use super::SearchState;
+    use super::SearchMode::*;
+    use super::SearchTarget::*;
+    use super::{SearchHistory, SearchState, SearchStep, MAX_SEARCH_HISTORY_SIZE};
 
     const SEARCHABLE: &str = r#"{
         "1": "aaa",
@@ -390,19 +802,44 @@ mod tests {
     }"#;
 
     #[test]
-    fn test_extract_search_term_and_case_sensitivity() {
+    fn test_extract_search_term_and_options() {
         let tests = vec![
-            ("abc", ("abc", false)),
-            ("Abc", ("Abc", true)),
-            ("abc/", ("abc", false)),
-            ("abc/s", ("abc", true)),
-            ("abc/s/", ("abc/s", false)),
+            ("abc", ("abc", false, Regex, false, Everything)),
+            ("Abc", ("Abc", true, Regex, false, Everything)),
+            ("abc/", ("abc", false, Regex, false, Everything)),
+            ("abc/s", ("abc", true, Regex, false, Everything)),
+            ("abc/s/", ("abc/s", false, Regex, false, Everything)),
+            ("abc/l", ("abc", false, Literal, false, Everything)),
+            ("Abc/l", ("Abc", true, Literal, false, Everything)),
+            ("abc/ls", ("abc", true, Literal, false, Everything)),
+            ("abc/sl", ("abc", true, Literal, false, Everything)),
+            ("abc/l/", ("abc/l", false, Regex, false, Everything)),
+            ("a/bc", ("a/bc", false, Regex, false, Everything)),
+            ("abc\\C", ("abc", true, Regex, false, Everything)),
+            ("\\cAbc", ("Abc", false, Regex, false, Everything)),
+            ("Abc\\c/s", ("Abc", false, Regex, false, Everything)),
+            ("a\\Cb\\cc", ("abc", false, Regex, false, Everything)),
+            ("a\\\\c", ("a\\\\c", false, Regex, false, Everything)),
+            ("a\\.c\\C/l", ("a\\.c", true, Literal, false, Everything)),
+            ("abc/t", ("abc", false, Regex, true, Everything)),
+            ("abc/tls", ("abc", true, Literal, true, Everything)),
+            ("abc/k", ("abc", false, Regex, false, Keys)),
+            ("abc/vs", ("abc", true, Regex, false, Values)),
+            ("abc/kv", ("abc", false, Regex, false, Everything)),
         ];
 
-        for (input, search_term_and_case_sensitivity) in tests.into_iter() {
+        for (input, search_term_and_options) in tests.into_iter() {
+            let (search_term, case_sensitive, mode, scoped_to_subtree, target) =
+                SearchState::extract_search_term_and_options(input);
             assert_eq!(
-                search_term_and_case_sensitivity,
-                SearchState::extract_search_term_and_case_sensitivity(input),
+                search_term_and_options,
+                (
+                    search_term.as_ref(),
+                    case_sensitive,
+                    mode,
+                    scoped_to_subtree,
+                    target
+                ),
             );
         }
     }
@@ -428,7 +865,7 @@ mod tests {
     #[test]
     fn test_basic_search_forward() {
         let fj = parse_top_level_json(SEARCHABLE.to_owned()).unwrap();
-        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj.1, Forward).unwrap();
+        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj, Forward, 0).unwrap();
         assert_eq!(search.jump_to_match(0, &fj, Next, 1), 1);
         assert_eq!(search.jump_to_match(1, &fj, Next, 1), 4);
         assert_eq!(search.jump_to_match(4, &fj, Next, 1), 7);
@@ -444,7 +881,7 @@ mod tests {
         assert_eq!(search.jump_to_match(4, &fj, Prev, 1), 1);
         assert_eq!(search.jump_to_match(1, &fj, Prev, 1), 7);
 
-        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj.1, Forward).unwrap();
+        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj, Forward, 0).unwrap();
         assert_eq!(search.jump_to_match(0, &fj, Next, 4), 7);
         assert_eq!(search.jump_to_match(1, &fj, Next, 2), 4);
         assert_eq!(search.jump_to_match(4, &fj, Next, 3), 1);
@@ -459,7 +896,7 @@ mod tests {
     #[test]
     fn test_basic_search_backwards() {
         let fj = parse_top_level_json(SEARCHABLE.to_owned()).unwrap();
-        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj.1, Reverse).unwrap();
+        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj, Reverse, 0).unwrap();
         assert_eq!(search.jump_to_match(0, &fj, Next, 1), 7);
         assert_wrapped_state(&search, true);
         assert_eq!(search.jump_to_match(7, &fj, Next, 1), 7);
@@ -474,7 +911,7 @@ mod tests {
         assert_eq!(search.jump_to_match(1, &fj, Prev, 1), 4);
         assert_wrapped_state(&search, false);
 
-        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj.1, Reverse).unwrap();
+        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj, Reverse, 0).unwrap();
         assert_eq!(search.jump_to_match(0, &fj, Next, 4), 1);
         assert_eq!(search.jump_to_match(1, &fj, Next, 3), 4);
         assert_eq!(search.jump_to_match(4, &fj, Next, 2), 7);
@@ -485,7 +922,7 @@ mod tests {
     #[test]
     fn test_search_collapsed_forward() {
         let mut fj = parse_top_level_json(SEARCHABLE.to_owned()).unwrap();
-        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj.1, Forward).unwrap();
+        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj, Forward, 0).unwrap();
         fj.collapse(6);
         assert_eq!(search.jump_to_match(0, &fj, Next, 1), 1);
         assert_eq!(search.jump_to_match(1, &fj, Next, 1), 4);
@@ -496,7 +933,7 @@ mod tests {
         assert_eq!(search.jump_to_match(1, &fj, Prev, 1), 6);
         assert_eq!(search.jump_to_match(6, &fj, Prev, 1), 4);
 
-        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj.1, Forward).unwrap();
+        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj, Forward, 0).unwrap();
         fj.collapse(6);
         assert_eq!(search.jump_to_match(0, &fj, Next, 4), 6);
         assert_eq!(search.jump_to_match(6, &fj, Next, 1), 1);
@@ -511,7 +948,7 @@ mod tests {
     #[test]
     fn test_search_collapsed_backwards() {
         let mut fj = parse_top_level_json(SEARCHABLE.to_owned()).unwrap();
-        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj.1, Reverse).unwrap();
+        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj, Reverse, 0).unwrap();
         fj.collapse(6);
         assert_eq!(search.jump_to_match(0, &fj, Next, 1), 6);
         assert_eq!(search.jump_to_match(6, &fj, Next, 1), 4);
@@ -522,7 +959,7 @@ mod tests {
         assert_eq!(search.jump_to_match(4, &fj, Prev, 1), 6);
         assert_eq!(search.jump_to_match(6, &fj, Prev, 1), 1);
 
-        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj.1, Reverse).unwrap();
+        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj, Reverse, 0).unwrap();
         fj.collapse(6);
         assert_eq!(search.jump_to_match(0, &fj, Prev, 4), 6);
         assert_eq!(search.jump_to_match(6, &fj, Prev, 1), 1);
@@ -534,6 +971,103 @@ mod tests {
         assert_eq!(search.jump_to_match(1, &fj, Next, 3), 4);
     }
 
+    #[test]
+    fn test_literal_search() {
+        const TEST: &str = r#"{
+            "a.c": "a+",
+            "abc": "aa"
+        }"#;
+        let fj = parse_top_level_json(TEST.to_owned()).unwrap();
+
+        let search = SearchState::initialize_search("a.c".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.num_matches(), 2);
+        let search = SearchState::initialize_search("a.c/l".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.num_matches(), 1);
+
+        let search = SearchState::initialize_search("a+/l".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.num_matches(), 1);
+
+        // Brackets are always literal, and escaping them does nothing.
+        let search = SearchState::initialize_search("{/l".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.num_matches(), 1);
+        let search = SearchState::initialize_search("\\{/l".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.num_matches(), 0);
+
+        // Invalid regexes are fine.
+        assert!(SearchState::initialize_search("(a".to_owned(), &fj, Forward, 0).is_err());
+        let search = SearchState::initialize_search("(a/l".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.num_matches(), 0);
+    }
+
+    #[test]
+    fn test_subtree_scoped_search() {
+        let fj = parse_top_level_json(SEARCHABLE.to_owned()).unwrap();
+
+        // Focused on container
+        let mut search =
+            SearchState::initialize_search("aaa/t".to_owned(), &fj, Forward, 6).unwrap();
+        assert_eq!(search.scope, Some(6));
+        assert_eq!(search.num_matches(), 2);
+        assert_eq!(search.jump_to_match(6, &fj, Next, 1), 7);
+        assert_eq!(search.jump_to_match(7, &fj, Next, 1), 7);
+        assert_eq!(search.jump_to_match(7, &fj, Next, 1), 7);
+        assert_eq!(search.jump_to_match(7, &fj, Prev, 1), 7);
+
+        // Focused on closing of container
+        let search = SearchState::initialize_search("aaa/t".to_owned(), &fj, Forward, 5).unwrap();
+        assert_eq!(search.scope, Some(2));
+        assert_eq!(search.num_matches(), 1);
+
+        // Focused on primitive
+        let search = SearchState::initialize_search("aaa/t".to_owned(), &fj, Forward, 3).unwrap();
+        assert_eq!(search.scope, Some(2));
+        assert_eq!(search.num_matches(), 1);
+
+        // Key of scoped container isn't included
+        let search = SearchState::initialize_search("6/t".to_owned(), &fj, Forward, 6).unwrap();
+        assert_eq!(search.num_matches(), 0);
+
+        let search = SearchState::initialize_search("aaa".to_owned(), &fj, Forward, 6).unwrap();
+        assert_eq!(search.scope, None);
+        assert_eq!(search.num_matches(), 4);
+    }
+
+    #[test]
+    fn test_key_and_value_search() {
+        let fj = parse_top_level_json(SEARCHABLE.to_owned()).unwrap();
+
+        let search = SearchState::initialize_search("1".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.num_matches(), 3);
+
+        let mut search = SearchState::initialize_search("1/k".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.target, Keys);
+        assert_eq!(search.num_matches(), 3);
+        assert_eq!(search.jump_to_match(0, &fj, Next, 1), 1);
+        assert_eq!(search.jump_to_match(1, &fj, Next, 1), 11);
+
+        let search = SearchState::initialize_search("1/v".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.num_matches(), 0);
+
+        let search = SearchState::initialize_search("b/v".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.num_matches(), 6);
+        let search = SearchState::initialize_search("aaa/k".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.num_matches(), 0);
+
+        // Matches spanning a key and its value aren't in either.
+        let search = SearchState::initialize_search("7.*aaa".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.num_matches(), 1);
+        let search =
+            SearchState::initialize_search("7.*aaa/k".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.num_matches(), 0);
+        let search =
+            SearchState::initialize_search("7.*aaa/v".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.num_matches(), 0);
+
+        // Combined with a subtree scope.
+        let search = SearchState::initialize_search("a/tv".to_owned(), &fj, Forward, 7).unwrap();
+        assert_eq!(search.num_matches(), 6);
+    }
+
     #[test]
     fn test_no_wrap_when_opening_of_collapsed_container_and_contents_match_search() {
         const TEST: &str = r#"{
@@ -543,7 +1077,8 @@ mod tests {
             "key": "term"
         }"#;
         let mut fj = parse_top_level_json(TEST.to_owned()).unwrap();
-        let mut search = SearchState::initialize_search("term".to_owned(), &fj.1, Forward).unwrap();
+        let mut search =
+            SearchState::initialize_search("term".to_owned(), &fj, Forward, 0).unwrap();
         fj.collapse(1);
         assert_eq!(search.jump_to_match(0, &fj, Next, 1), 1);
         assert_wrapped_state(&search, false);
@@ -555,12 +1090,142 @@ mod tests {
         assert_wrapped_state(&search, true);
     }
 
+    #[test]
+    fn test_persist_highlights() {
+        let fj = parse_top_level_json(SEARCHABLE.to_owned()).unwrap();
+
+        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.matches_iter(0).count(), 0);
+        search.jump_to_match(0, &fj, Next, 1);
+        assert_eq!(search.matches_iter(0).count(), 4);
+        search.set_no_longer_actively_searching();
+        assert_eq!(search.matches_iter(0).count(), 0);
+
+        search.persist_highlights = true;
+        assert_eq!(search.matches_iter(0).count(), 4);
+        // The current match is no longer highlighted.
+        assert_eq!(search.current_match_range(), 0..0);
+
+        search.dismiss_highlights();
+        assert_eq!(search.matches_iter(0).count(), 0);
+        search.jump_to_match(0, &fj, Next, 1);
+        search.set_no_longer_actively_searching();
+        assert_eq!(search.matches_iter(0).count(), 4);
+    }
+
+    #[test]
+    fn test_placeholders_arent_searched() {
+        let fj = crate::flatjson::parse_top_level_json_to_depth(
+            r#"["max", [["max"]], "depth"]"#.to_owned(),
+            2,
+        )
+        .unwrap();
+        let search =
+            SearchState::initialize_search("max|depth".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.num_matches(), 2);
+    }
+
+    #[test]
+    fn test_search_appended_rows() {
+        let fj = parse_top_level_json(r#"["aaa", "b"]"#.to_owned()).unwrap();
+        let mut search = SearchState::initialize_search("a|]".to_owned(), &fj, Forward, 0).unwrap();
+        search.jump_to_match(0, &fj, Next, 4);
+        assert_eq!(search.num_matches(), 4);
+
+        // The closing bracket was a placeholder, which is replaced.
+        let fj = parse_top_level_json(r#"["aaa", "b", "aa"]"#.to_owned()).unwrap();
+        search.search_appended_rows(&fj, 11);
+        assert_eq!(search.num_matches(), 6);
+        assert_eq!(search.active_search_state(), Some((3, false)));
+
+        let mut search = SearchState::initialize_search("b/v".to_owned(), &fj, Forward, 0).unwrap();
+        let fj = parse_top_level_json(r#"["aaa", "b", "aa", {"b": "b"}]"#.to_owned()).unwrap();
+        search.search_appended_rows(&fj, 17);
+        assert_eq!(search.num_matches(), 2);
+    }
+
+    #[test]
+    fn test_step_without_wrapping() {
+        let fj = parse_top_level_json(SEARCHABLE.to_owned()).unwrap();
+        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj, Forward, 0).unwrap();
+
+        let jumped = |row, wrapped| SearchStep::Jumped { row, wrapped };
+        assert_eq!(search.step(0, &fj, Next, 1, false), jumped(1, None));
+        assert_eq!(search.step(1, &fj, Next, 3, false), jumped(7, None));
+        assert_eq!(
+            search.step(7, &fj, Next, 1, false),
+            SearchStep::NoMoreMatches(Forward)
+        );
+        // Stopping doesn't change the current match.
+        assert_eq!(search.step(7, &fj, Prev, 1, false), jumped(7, None));
+        assert_eq!(search.step(7, &fj, Next, 1, true), jumped(7, None));
+        assert_eq!(search.step(7, &fj, Next, 1, true), jumped(1, Some(Forward)));
+        assert_eq!(
+            search.step(1, &fj, Prev, 1, false),
+            SearchStep::NoMoreMatches(Reverse)
+        );
+        assert_eq!(search.step(1, &fj, Prev, 1, true), jumped(7, Some(Reverse)));
+
+        let mut search = SearchState::initialize_search("zzz".to_owned(), &fj, Forward, 0).unwrap();
+        assert_eq!(search.step(0, &fj, Next, 1, false), SearchStep::NoMatches);
+        assert_eq!(search.step(0, &fj, Next, 1, true), SearchStep::NoMatches);
+    }
+
+    #[test]
+    fn test_any_match_in() {
+        let fj = parse_top_level_json(r#"["aaa", "bbb", "aaa"]"#.to_owned()).unwrap();
+        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj, Forward, 0).unwrap();
+
+        assert!(search.any_match_in(0..3));
+        assert!(search.any_match_in(2..4));
+        assert!(!search.any_match_in(7..14));
+        assert!(search.any_match_in(7..17));
+        assert!(!search.any_match_in(7..16));
+        assert!(!search.any_match_in(20..30));
+
+        search.dismiss_highlights();
+        assert!(!search.any_match_in(0..3));
+    }
+
+    #[test]
+    fn test_search_history() {
+        let mut history = SearchHistory::new(None);
+        history.add("aaa");
+        history.add("bbb");
+        history.add("bbb");
+        history.add("");
+        history.add("aaa");
+        assert_eq!(history.entries(), &["aaa", "bbb", "aaa"]);
+
+        for i in 0..MAX_SEARCH_HISTORY_SIZE {
+            history.add(&i.to_string());
+        }
+        assert_eq!(history.entries().len(), MAX_SEARCH_HISTORY_SIZE);
+        assert_eq!(history.entries()[0], "0");
+    }
+
+    #[test]
+    fn test_search_history_persistence() {
+        let path = std::env::temp_dir()
+            .join(format!("jless-search-history-test-{}", std::process::id()))
+            .join("search_history");
+
+        let mut history = SearchHistory::new(Some(path.clone()));
+        history.add("aaa");
+        history.add("bbb");
+
+        let history = SearchHistory::new(Some(path.clone()));
+        assert_eq!(history.entries(), &["aaa", "bbb"]);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
     #[track_caller]
     fn assert_wrapped_state(search: &SearchState, expected: bool) {
         if let Some((_, wrapped)) = search.active_search_state() {
             assert_eq!(wrapped, expected);
         } else {
-            assert!(false, "Not in an active search state");
+            panic!("Not in an active search state");
         }
     }
 }
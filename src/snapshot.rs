@@ -0,0 +1,309 @@
+// Renders what jless writes to the terminal to a string, without using a
+// real terminal (see jless::render), by interpreting the escape sequences
+// that AnsiTerminal writes, and keeping track of what's in each cell of
+// the screen.
+//
+// The string has a line for each row of the screen, with only the escape
+// sequences needed to style the text, so that it's the same regardless of
+// how the screen was drawn.
+
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::terminal::{Color, Style};
+
+/// Output that's written to a shared buffer, so that what's been written
+/// can be read while the writer is still in use.
+#[derive(Clone, Default)]
+pub struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl SharedOutput {
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl io::Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct Cell {
+    // Empty for the second column of a wide character.
+    text: String,
+    style: Style,
+}
+
+const BLANK: Cell = Cell {
+    text: String::new(),
+    style: Style::default(),
+};
+
+pub struct Screen {
+    width: usize,
+    cells: Vec<Vec<Cell>>,
+    // The (0-based) row and column the cursor is at.
+    row: usize,
+    col: usize,
+    style: Style,
+}
+
+impl Screen {
+    pub fn new(width: u16, height: u16) -> Screen {
+        let blank = Cell {
+            text: " ".to_owned(),
+            ..BLANK
+        };
+        Screen {
+            width: width as usize,
+            cells: vec![vec![blank; width as usize]; height as usize],
+            row: 0,
+            col: 0,
+            style: Style::default(),
+        }
+    }
+
+    /// Updates the screen with output written to the terminal.
+    pub fn write(&mut self, output: &str) {
+        let mut chars = output.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\x1b' => match chars.next() {
+                    Some('[') => {
+                        let mut params = String::new();
+                        for ch in &mut chars {
+                            if ('\x40'..='\x7e').contains(&ch) {
+                                self.control_sequence(&params, ch);
+                                break;
+                            }
+                            params.push(ch);
+                        }
+                    }
+                    // Operating system commands, e.g., to copy text with
+                    // OSC 52, end with BEL or ESC \.
+                    Some(']') => {
+                        while let Some(ch) = chars.next() {
+                            if ch == '\x07' || (ch == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                                break;
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+                '\r' => self.col = 0,
+                '\n' => self.row += 1,
+                ch if ch.is_control() => {}
+                ch => self.write_char(ch),
+            }
+        }
+    }
+
+    fn write_char(&mut self, ch: char) {
+        let row = match self.cells.get_mut(self.row) {
+            Some(row) => row,
+            None => return,
+        };
+
+        let width = UnicodeWidthChar::width(ch).unwrap_or(0);
+        if width == 0 {
+            // Combining characters are part of the previous character.
+            if let Some(cell) = self.col.checked_sub(1).and_then(|col| row.get_mut(col)) {
+                cell.text.push(ch);
+            }
+            return;
+        }
+
+        // Nothing that doesn't fit is shown, rather than wrapping it.
+        if self.col + width > self.width {
+            self.col = self.width;
+            return;
+        }
+
+        row[self.col] = Cell {
+            text: ch.to_string(),
+            style: self.style,
+        };
+        for cell in row[self.col + 1..self.col + width].iter_mut() {
+            *cell = Cell {
+                style: self.style,
+                ..BLANK
+            };
+        }
+        self.col += width;
+    }
+
+    // Handles an escape sequence, ESC [ <params> <command>, ignoring ones
+    // that don't affect what's shown, e.g., ones that show the cursor or
+    // enable mouse reporting.
+    fn control_sequence(&mut self, params: &str, command: char) {
+        if params.starts_with('?') {
+            return;
+        }
+        let params: Vec<usize> = params
+            .split(';')
+            .map(|param| param.parse().unwrap_or(0))
+            .collect();
+        let param = |i: usize| params.get(i).copied().unwrap_or(0);
+
+        match command {
+            'H' => {
+                self.row = param(0).max(1) - 1;
+                self.col = param(1).max(1) - 1;
+            }
+            'G' => self.col = param(0).max(1) - 1,
+            'J' => {
+                for row in 0..self.cells.len() {
+                    self.clear_row(row, 0);
+                }
+            }
+            'K' => self.clear_row(self.row, if param(0) == 2 { 0 } else { self.col }),
+            'm' => self.set_style(&params),
+            _ => {}
+        }
+    }
+
+    fn clear_row(&mut self, row: usize, from_col: usize) {
+        if let Some(row) = self.cells.get_mut(row) {
+            for cell in row.iter_mut().skip(from_col) {
+                *cell = Cell {
+                    text: " ".to_owned(),
+                    ..BLANK
+                };
+            }
+        }
+    }
+
+    fn set_style(&mut self, params: &[usize]) {
+        let mut params = params.iter().copied();
+        while let Some(param) = params.next() {
+            match param {
+                0 => self.style = Style::default(),
+                1 => self.style.bold = true,
+                2 => self.style.dimmed = true,
+                7 => self.style.inverted = true,
+                22 => {
+                    self.style.bold = false;
+                    self.style.dimmed = false;
+                }
+                27 => self.style.inverted = false,
+                38 | 48 => {
+                    // Only 256 color palette indexes are used: 38;5;<n>.
+                    let color = match (params.next(), params.next()) {
+                        (Some(5), Some(index)) => Color::C16(index as u8),
+                        _ => Color::Default,
+                    };
+                    if param == 38 {
+                        self.style.fg = color;
+                    } else {
+                        self.style.bg = color;
+                    }
+                }
+                39 => self.style.fg = Color::Default,
+                49 => self.style.bg = Color::Default,
+                _ => {}
+            }
+        }
+    }
+
+    /// The contents of the screen, with a line for each row, and escape
+    /// sequences to style the text. Unstyled spaces at the end of rows are
+    /// left out.
+    pub fn contents(&self) -> String {
+        let mut lines = vec![];
+        for row in self.cells.iter() {
+            let end = row
+                .iter()
+                .rposition(|cell| cell.text != " " || cell.style != Style::default())
+                .map_or(0, |i| i + 1);
+
+            let mut line = String::new();
+            let mut style = Style::default();
+            for cell in row[..end].iter() {
+                if cell.style != style {
+                    style = cell.style;
+                    line.push_str(&style_escape_sequence(&style));
+                }
+                line.push_str(&cell.text);
+            }
+            if style != Style::default() {
+                line.push_str("\x1b[0m");
+            }
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+}
+
+// An escape sequence that sets a style, regardless of the current style.
+fn style_escape_sequence(style: &Style) -> String {
+    let mut sequence = "\x1b[0".to_owned();
+    if style.bold {
+        sequence.push_str(";1");
+    }
+    if style.dimmed {
+        sequence.push_str(";2");
+    }
+    if style.inverted {
+        sequence.push_str(";7");
+    }
+    if let Color::C16(c) = style.fg {
+        sequence.push_str(&format!(";38;5;{}", c));
+    }
+    if let Color::C16(c) = style.bg {
+        sequence.push_str(&format!(";48;5;{}", c));
+    }
+    sequence.push('m');
+    sequence
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write;
+
+    use crate::terminal::{AnsiTerminal, Terminal, BLUE};
+
+    #[test]
+    fn test_screen() {
+        let mut terminal = AnsiTerminal::new(String::new());
+        terminal.clear_screen().unwrap();
+        terminal.position_cursor(3, 1).unwrap();
+        terminal.write_str("abc").unwrap();
+        terminal.position_cursor(1, 2).unwrap();
+        terminal
+            .set_style(&Style {
+                fg: BLUE,
+                bold: true,
+                ..Style::default()
+            })
+            .unwrap();
+        terminal.write_str("key").unwrap();
+        terminal.reset_style().unwrap();
+        terminal.write_str(": 界!").unwrap();
+        // Overwrites the end of the first line, but doesn't wrap.
+        terminal.position_cursor(7, 1).unwrap();
+        terminal.set_inverted(true).unwrap();
+        terminal.write_str("xyz").unwrap();
+
+        let mut screen = Screen::new(8, 3);
+        screen.write(&terminal.output);
+        assert_eq!(
+            screen.contents(),
+            "  abc \x1b[0;7mxy\x1b[0m\n\x1b[0;1;38;5;4mkey\x1b[0m: 界!\n"
+        );
+
+        // Clearing the screen, and ignored sequences.
+        screen.write("\x1b[0m\x1b[2J\x1b[?25l\x1b]52;c;YQ==\x07\x1b[2;2Hé\u{301}\x1b[1G.");
+        assert_eq!(screen.contents(), "\n.é\u{301}\n");
+    }
+}
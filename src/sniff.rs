@@ -0,0 +1,99 @@
+// Inputs without a flag, a Content-Type, or an extension saying what format
+// they're in, e.g., STDIN, are assumed to be JSON unless the way they start
+// looks like YAML or TOML instead. Only the start of the input is looked at,
+// so this is just a guess, which can be corrected with :format.
+
+use crate::options::DataFormat;
+
+pub fn sniff_data_format(data: &str) -> DataFormat {
+    let mut has_comments = false;
+    for line in data.trim_start_matches('\u{feff}').lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        // Comments aren't allowed in JSON.
+        if line.starts_with('#') {
+            has_comments = true;
+            continue;
+        }
+
+        let format = sniff_line(line);
+        return if format == DataFormat::Json && has_comments {
+            DataFormat::Yaml
+        } else {
+            format
+        };
+    }
+
+    if has_comments {
+        DataFormat::Yaml
+    } else {
+        DataFormat::Json
+    }
+}
+
+// The format of an input based on its first line (other than comments).
+fn sniff_line(line: &str) -> DataFormat {
+    if line.starts_with('{') || line.starts_with('"') {
+        return DataFormat::Json;
+    }
+    // A table header, e.g., [server] or [[servers]], rather than an array.
+    if line.starts_with('[') {
+        let name = line.trim_start_matches('[').trim_end_matches(']');
+        let is_header = line.ends_with(']')
+            && name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(is_bare_key_char);
+        return if is_header && !matches!(name, "true" | "false" | "null") {
+            DataFormat::Toml
+        } else {
+            DataFormat::Json
+        };
+    }
+    if line.starts_with("---") || line.starts_with('%') || line == "-" || line.starts_with("- ") {
+        return DataFormat::Yaml;
+    }
+
+    // key = value, or key: value.
+    let key_end = line
+        .find(|c: char| !is_bare_key_char(c))
+        .unwrap_or(line.len());
+    let after_key = line[key_end..].trim_start();
+    if key_end > 0 && after_key.starts_with('=') {
+        DataFormat::Toml
+    } else if line.ends_with(':') || line.contains(": ") {
+        DataFormat::Yaml
+    } else {
+        DataFormat::Json
+    }
+}
+
+fn is_bare_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_data_format() {
+        let sniff = sniff_data_format;
+        assert_eq!(sniff("{\"a\": 1}"), DataFormat::Json);
+        assert_eq!(sniff("\n  [1, 2]"), DataFormat::Json);
+        assert_eq!(sniff("[true]"), DataFormat::Json);
+        assert_eq!(sniff("\"a: b\""), DataFormat::Json);
+        assert_eq!(sniff("-12.5"), DataFormat::Json);
+        assert_eq!(sniff(""), DataFormat::Json);
+
+        assert_eq!(sniff("a: 1\nb: 2"), DataFormat::Yaml);
+        assert_eq!(sniff("items:\n  - 1"), DataFormat::Yaml);
+        assert_eq!(sniff("- a\n- b"), DataFormat::Yaml);
+        assert_eq!(sniff("---\n{}"), DataFormat::Yaml);
+        assert_eq!(sniff("# A comment\n{\"a\": 1}"), DataFormat::Yaml);
+
+        assert_eq!(sniff("[server]\nport = 80"), DataFormat::Toml);
+        assert_eq!(sniff("[[servers]]"), DataFormat::Toml);
+        assert_eq!(sniff("# A comment\nname = \"a: b\""), DataFormat::Toml);
+    }
+}
@@ -0,0 +1,267 @@
+// With --remember-state, which objects and arrays are collapsed and which
+// row is focused are saved for each file when jless exits, and restored
+// the next time the same file is viewed, as long as it hasn't been
+// modified since then.
+//
+// Each file's state is stored on its own line, as its modification time,
+// its size, the number of lines above the focused row on the screen, the
+// path hashes of the focused row and its ancestors, the path hashes of the
+// collapsed containers, and finally its path:
+//
+//     1700000000123456789 1024 5 9a3f,1c2b 77e0,12ab /home/user/data.json
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::viewer::SavedState;
+
+const MAX_STATE_CACHE_SIZE: usize = 100;
+
+/// Identifies a version of a file: its canonical path, and its
+/// modification time and size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileVersion {
+    path: String,
+    modified: u128,
+    len: u64,
+}
+
+impl FileVersion {
+    pub fn of(path: &Path) -> Option<FileVersion> {
+        let path = fs::canonicalize(path).ok()?;
+        let metadata = fs::metadata(&path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+        Some(FileVersion {
+            path: path.to_str()?.to_owned(),
+            modified: modified.as_nanos(),
+            len: metadata.len(),
+        })
+    }
+}
+
+/// The saved state of recently viewed files, oldest first, which is only
+/// read and written when enabled.
+pub struct StateCache {
+    entries: Vec<(FileVersion, SavedState)>,
+    file: Option<PathBuf>,
+    enabled: bool,
+}
+
+impl StateCache {
+    pub fn new(file: Option<PathBuf>, enabled: bool) -> StateCache {
+        let mut entries = vec![];
+
+        if let (Some(path), true) = (&file, enabled) {
+            // It's fine if the file doesn't exist yet, and lines that can't
+            // be parsed are ignored.
+            if let Ok(contents) = fs::read_to_string(path) {
+                entries = contents.lines().filter_map(parse_entry).collect();
+            }
+        }
+
+        StateCache {
+            entries,
+            file,
+            enabled,
+        }
+    }
+
+    /// Returns the default location to persist the state of files to:
+    /// $XDG_STATE_HOME/jless/view_state, or ~/.local/state/jless/view_state.
+    pub fn default_file() -> Option<PathBuf> {
+        let state_dir = match std::env::var_os("XDG_STATE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/state"),
+        };
+
+        Some(state_dir.join("jless").join("view_state"))
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns the saved state of a file, if it hasn't been modified since
+    /// the state was saved.
+    pub fn get(&self, version: &FileVersion) -> Option<&SavedState> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(saved_version, _)| saved_version == version)
+            .map(|(_, state)| state)
+    }
+
+    pub fn insert(&mut self, version: FileVersion, state: SavedState) {
+        self.entries
+            .retain(|(saved_version, _)| saved_version.path != version.path);
+        self.entries.push((version, state));
+
+        if self.entries.len() > MAX_STATE_CACHE_SIZE {
+            let excess = self.entries.len() - MAX_STATE_CACHE_SIZE;
+            self.entries.drain(..excess);
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = match &self.file {
+            Some(path) if self.enabled => path,
+            _ => return Ok(()),
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut contents = String::new();
+        for (version, state) in self.entries.iter() {
+            // A path containing a newline can't be stored on one line.
+            if !version.path.contains('\n') {
+                contents.push_str(&format_entry(version, state));
+                contents.push('\n');
+            }
+        }
+        fs::write(path, contents)
+    }
+
+    /// Forgets the state of every file, even if remembering it isn't
+    /// enabled.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.entries.clear();
+
+        match &self.file {
+            Some(path) => match fs::remove_file(path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+fn format_hashes(hashes: &[u64]) -> String {
+    if hashes.is_empty() {
+        return "-".to_owned();
+    }
+
+    let hashes: Vec<String> = hashes.iter().map(|hash| format!("{:x}", hash)).collect();
+    hashes.join(",")
+}
+
+fn parse_hashes(s: &str) -> Option<Vec<u64>> {
+    if s == "-" {
+        return Some(vec![]);
+    }
+
+    s.split(',')
+        .map(|hash| u64::from_str_radix(hash, 16).ok())
+        .collect()
+}
+
+fn format_entry(version: &FileVersion, state: &SavedState) -> String {
+    format!(
+        "{} {} {} {} {} {}",
+        version.modified,
+        version.len,
+        state.lines_above,
+        format_hashes(&state.focused),
+        format_hashes(&state.collapsed),
+        version.path,
+    )
+}
+
+fn parse_entry(line: &str) -> Option<(FileVersion, SavedState)> {
+    let mut fields = line.splitn(6, ' ');
+    let modified = fields.next()?.parse().ok()?;
+    let len = fields.next()?.parse().ok()?;
+    let lines_above = fields.next()?.parse().ok()?;
+    let focused = parse_hashes(fields.next()?)?;
+    let collapsed = parse_hashes(fields.next()?)?;
+    let path = fields.next()?.to_owned();
+
+    let version = FileVersion {
+        path,
+        modified,
+        len,
+    };
+    let state = SavedState {
+        collapsed,
+        focused,
+        lines_above,
+    };
+    Some((version, state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(path: &str, modified: u128) -> FileVersion {
+        FileVersion {
+            path: path.to_owned(),
+            modified,
+            len: 10,
+        }
+    }
+
+    fn state(lines_above: usize) -> SavedState {
+        SavedState {
+            collapsed: vec![0xabc, 1],
+            focused: vec![],
+            lines_above,
+        }
+    }
+
+    #[test]
+    fn test_state_cache() {
+        let mut cache = StateCache::new(None, true);
+        cache.insert(version("/a.json", 1), state(1));
+        cache.insert(version("/b b.json", 1), state(2));
+
+        assert_eq!(cache.get(&version("/a.json", 1)), Some(&state(1)));
+        // The file has been modified since its state was saved.
+        assert_eq!(cache.get(&version("/a.json", 2)), None);
+
+        // Only the latest state of a file is kept.
+        cache.insert(version("/a.json", 2), state(3));
+        assert_eq!(cache.get(&version("/a.json", 1)), None);
+        assert_eq!(cache.get(&version("/a.json", 2)), Some(&state(3)));
+
+        for i in 0..MAX_STATE_CACHE_SIZE {
+            cache.insert(version(&format!("/{}.json", i), 1), state(i));
+        }
+        assert_eq!(cache.entries.len(), MAX_STATE_CACHE_SIZE);
+        assert_eq!(cache.get(&version("/b b.json", 1)), None);
+    }
+
+    #[test]
+    fn test_state_cache_persistence() {
+        let path = std::env::temp_dir()
+            .join(format!("jless-state-cache-test-{}", std::process::id()))
+            .join("view_state");
+
+        let mut cache = StateCache::new(Some(path.clone()), true);
+        cache.insert(version("/a.json", 1), state(1));
+        cache.insert(version("/b b.json", 2), state(2));
+        cache.save().unwrap();
+
+        // Nothing is read or written unless remembering state is enabled.
+        let cache = StateCache::new(Some(path.clone()), false);
+        assert_eq!(cache.get(&version("/a.json", 1)), None);
+        cache.save().unwrap();
+
+        let cache = StateCache::new(Some(path.clone()), true);
+        assert_eq!(cache.get(&version("/a.json", 1)), Some(&state(1)));
+        assert_eq!(cache.get(&version("/b b.json", 2)), Some(&state(2)));
+
+        let mut cache = StateCache::new(Some(path.clone()), false);
+        cache.clear().unwrap();
+        assert!(!path.exists());
+        // Clearing it again is fine.
+        cache.clear().unwrap();
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
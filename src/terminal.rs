@@ -26,7 +26,7 @@ pub const LIGHT_BLUE: Color = Color::C16(12);
 // pub const LIGHT_WHITE: Color = Color::C16(15);
 pub const DEFAULT: Color = Color::Default;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Style {
     pub fg: Color,
     pub bg: Color,
@@ -69,9 +69,10 @@ pub trait Terminal: Write {
     fn set_bold(&mut self, bold: bool) -> Result;
     fn set_dimmed(&mut self, dimmed: bool) -> Result;
 
-    fn output(&self) -> &str;
-
     // Only used for testing.
+    #[allow(dead_code)]
+    fn output(&self) -> &str;
+    #[allow(dead_code)]
     fn clear_output(&mut self);
 }
 
@@ -242,6 +243,7 @@ pub mod test {
         }
     }
 
+    #[derive(Default)]
     pub struct TextOnlyTerminal {
         pub output: String,
     }
@@ -0,0 +1,418 @@
+use std::path::{Path, PathBuf};
+
+use toml::Value as Toml;
+use unicode_width::UnicodeWidthChar;
+
+use crate::flatjson::Value;
+use crate::options;
+use crate::terminal;
+use crate::terminal::{Color, Style};
+
+// A theme determines the colors used to display everything on the screen.
+// All of the styles that involve colors used while rendering come from
+// here, rather than from constants, so that they can be customized.
+//
+// Themes are loaded from TOML files that map roles to colors, e.g.:
+//
+//   key = "light-blue"
+//   string = "green"
+//   number = 5
+//
+// Colors are either one of the names in COLOR_NAMES, or an index into
+// the terminal's 256 color palette. Any roles that aren't specified use
+// the default colors. The characters used for some markers can also be
+// changed, for terminals without good Unicode support, e.g.:
+//
+//   collapsed-marker = "+"
+pub struct Theme {
+    pub null: Style,
+    pub boolean: Style,
+    pub number: Style,
+    pub string: Style,
+    pub empty_container: Style,
+
+    pub key: Style,
+    pub focused_key: Style,
+
+    // Search matches, and matches inside of container previews.
+    pub search_match: Style,
+    pub preview_search_match: Style,
+
+    pub gutter: Style,
+    pub focused_gutter: Style,
+
+    // The markers of rows that were added, removed, or changed, when
+    // comparing two inputs with --diff.
+    pub added: Style,
+    pub removed: Style,
+    pub changed: Style,
+
+    // The markers for collapsed and expanded containers, with
+    // --fold-column.
+    pub collapsed_marker: char,
+    pub expanded_marker: char,
+
+    // Used for less important things: container previews, array indexes,
+    // the '~' shown past the end of the input, etc.
+    pub dimmed: Style,
+    pub muted_color: Color,
+
+    pub info_message: Style,
+    pub warning_message: Style,
+    pub error_message: Style,
+}
+
+const COLOR_NAMES: [&str; 16] = [
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+    "gray",
+    "light-red",
+    "light-green",
+    "light-yellow",
+    "light-blue",
+    "light-magenta",
+    "light-cyan",
+    "light-white",
+];
+
+const fn fg(color: Color) -> Style {
+    Style {
+        fg: color,
+        ..Style::default()
+    }
+}
+
+impl Theme {
+    pub const fn default() -> Theme {
+        Theme {
+            null: fg(terminal::LIGHT_BLACK),
+            boolean: fg(terminal::YELLOW),
+            number: fg(terminal::MAGENTA),
+            string: fg(terminal::GREEN),
+            empty_container: fg(terminal::WHITE),
+
+            key: fg(terminal::LIGHT_BLUE),
+            focused_key: Style {
+                bg: terminal::BLUE,
+                inverted: true,
+                bold: true,
+                ..Style::default()
+            },
+
+            search_match: Style {
+                fg: terminal::YELLOW,
+                inverted: true,
+                ..Style::default()
+            },
+            preview_search_match: Style {
+                fg: terminal::LIGHT_BLACK,
+                inverted: true,
+                ..Style::default()
+            },
+
+            gutter: Style {
+                dimmed: true,
+                ..Style::default()
+            },
+            focused_gutter: Style {
+                bold: true,
+                ..Style::default()
+            },
+
+            added: fg(terminal::GREEN),
+            removed: fg(terminal::RED),
+            changed: fg(terminal::YELLOW),
+
+            collapsed_marker: '▸',
+            expanded_marker: '▾',
+
+            dimmed: Style {
+                dimmed: true,
+                ..Style::default()
+            },
+            muted_color: terminal::LIGHT_BLACK,
+
+            info_message: fg(terminal::WHITE),
+            warning_message: fg(terminal::YELLOW),
+            error_message: fg(terminal::RED),
+        }
+    }
+
+    // A theme that doesn't use any colors, and only uses bold and
+    // inverted text for emphasis.
+    pub const fn monochrome() -> Theme {
+        const PLAIN: Style = Style::default();
+        const INVERTED: Style = Style {
+            inverted: true,
+            ..Style::default()
+        };
+
+        Theme {
+            null: PLAIN,
+            boolean: PLAIN,
+            number: PLAIN,
+            string: PLAIN,
+            empty_container: PLAIN,
+
+            key: PLAIN,
+            focused_key: Style {
+                inverted: true,
+                bold: true,
+                ..Style::default()
+            },
+
+            search_match: INVERTED,
+            preview_search_match: INVERTED,
+
+            gutter: PLAIN,
+            focused_gutter: Style {
+                bold: true,
+                ..Style::default()
+            },
+
+            added: PLAIN,
+            removed: PLAIN,
+            changed: PLAIN,
+
+            collapsed_marker: '▸',
+            expanded_marker: '▾',
+
+            dimmed: PLAIN,
+            muted_color: Color::Default,
+
+            info_message: PLAIN,
+            warning_message: Style {
+                bold: true,
+                ..Style::default()
+            },
+            error_message: Style {
+                bold: true,
+                ..Style::default()
+            },
+        }
+    }
+
+    // Whether the NO_COLOR environment variable is set to a non-empty
+    // value (see https://no-color.org).
+    pub fn no_color_requested() -> bool {
+        matches!(std::env::var_os("NO_COLOR"), Some(value) if !value.is_empty())
+    }
+
+    // Load the theme from the given file, or from the default location if
+    // no file is specified. If there's no theme file at the default
+    // location, the default theme is used.
+    pub fn load(path: Option<&Path>) -> Result<Theme, String> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_file() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Theme::default()),
+            },
+        };
+
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|err| format!("Unable to read theme {}: {}", path.display(), err))?;
+
+        Self::parse(&contents).map_err(|err| format!("Invalid theme {}: {}", path.display(), err))
+    }
+
+    /// Returns the default location of the theme file:
+    /// $XDG_CONFIG_HOME/jless/theme.toml, or ~/.config/jless/theme.toml.
+    pub fn default_file() -> Option<PathBuf> {
+        options::config_file("theme.toml")
+    }
+
+    pub fn parse(contents: &str) -> Result<Theme, String> {
+        let table = match contents.parse::<Toml>() {
+            Ok(Toml::Table(table)) => table,
+            Ok(_) => return Err("Expected a table of colors".to_owned()),
+            Err(err) => return Err(format!("{}", err)),
+        };
+
+        let mut theme = Theme::default();
+
+        for (role, value) in table.iter() {
+            match role.as_str() {
+                "collapsed-marker" | "expanded-marker" => {
+                    let marker = parse_marker(value).map_err(|err| format!("{}: {}", role, err))?;
+                    if role == "collapsed-marker" {
+                        theme.collapsed_marker = marker;
+                    } else {
+                        theme.expanded_marker = marker;
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            let color = parse_color(value).map_err(|err| format!("{}: {}", role, err))?;
+
+            match role.as_str() {
+                "null" => theme.null.fg = color,
+                "boolean" => theme.boolean.fg = color,
+                "number" => theme.number.fg = color,
+                "string" => theme.string.fg = color,
+                "empty-container" => theme.empty_container.fg = color,
+                "key" => theme.key.fg = color,
+                // The focused key is inverted, so its background color
+                // is shown as the foreground color.
+                "focused" => theme.focused_key.bg = color,
+                "search-match" => theme.search_match.fg = color,
+                "preview-search-match" => theme.preview_search_match.fg = color,
+                "gutter" => {
+                    theme.gutter.fg = color;
+                    theme.gutter.dimmed = false;
+                }
+                "added" => theme.added.fg = color,
+                "removed" => theme.removed.fg = color,
+                "changed" => theme.changed.fg = color,
+                "muted" => theme.muted_color = color,
+                "info" => theme.info_message.fg = color,
+                "warning" => theme.warning_message.fg = color,
+                "error" => theme.error_message.fg = color,
+                _ => return Err(format!("Unknown role: {}", role)),
+            }
+        }
+
+        Ok(theme)
+    }
+
+    pub fn value_style(&self, value: &Value) -> &Style {
+        debug_assert!(value.is_primitive());
+
+        match value {
+            Value::Null => &self.null,
+            Value::Boolean => &self.boolean,
+            Value::Number => &self.number,
+            Value::String => &self.string,
+            Value::TooDeep => &self.dimmed,
+            Value::Malformed => &self.error_message,
+            Value::EmptyObject => &self.empty_container,
+            Value::EmptyArray => &self.empty_container,
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn parse_color(value: &Toml) -> Result<Color, String> {
+    match value {
+        Toml::Integer(i) if (0..=255).contains(i) => Ok(Color::C16(*i as u8)),
+        Toml::Integer(i) => Err(format!("Color index {} must be between 0 and 255", i)),
+        Toml::String(name) => {
+            let name = name.to_ascii_lowercase().replace('_', "-");
+            match name.as_str() {
+                "default" => Ok(Color::Default),
+                "grey" | "light-black" => Ok(terminal::LIGHT_BLACK),
+                _ => match COLOR_NAMES.iter().position(|color| *color == name) {
+                    Some(index) => Ok(Color::C16(index as u8)),
+                    None => Err(format!("Unknown color: {}", name)),
+                },
+            }
+        }
+        _ => Err("Expected a color name or index".to_owned()),
+    }
+}
+
+// Markers take up a single column.
+fn parse_marker(value: &Toml) -> Result<char, String> {
+    let marker = match value {
+        Toml::String(s) => s,
+        _ => return Err("Expected a character".to_owned()),
+    };
+
+    let mut chars = marker.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) if UnicodeWidthChar::width(ch) == Some(1) => Ok(ch),
+        _ => Err(format!("Expected a single character: {:?}", marker)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_theme() {
+        let theme = Theme::parse(indoc! {r#"
+            key = "Red"
+            string = 130
+            focused = "light_cyan"
+            gutter = "grey"
+            collapsed-marker = "+"
+        "#})
+        .unwrap();
+
+        assert_eq!(theme.key.fg, terminal::RED);
+        assert_eq!(theme.string.fg, Color::C16(130));
+        assert_eq!(theme.focused_key.bg, Color::C16(14));
+        assert!(theme.focused_key.inverted);
+        assert_eq!(theme.gutter.fg, terminal::LIGHT_BLACK);
+        assert!(!theme.gutter.dimmed);
+        assert_eq!(theme.collapsed_marker, '+');
+        assert_eq!(theme.expanded_marker, '▾');
+        // Unspecified roles use the defaults.
+        assert_eq!(theme.number.fg, terminal::MAGENTA);
+    }
+
+    #[test]
+    fn test_monochrome_theme_has_no_colors() {
+        let theme = Theme::monochrome();
+        let styles = [
+            &theme.null,
+            &theme.boolean,
+            &theme.number,
+            &theme.string,
+            &theme.empty_container,
+            &theme.key,
+            &theme.focused_key,
+            &theme.search_match,
+            &theme.preview_search_match,
+            &theme.gutter,
+            &theme.focused_gutter,
+            &theme.added,
+            &theme.removed,
+            &theme.changed,
+            &theme.dimmed,
+            &theme.info_message,
+            &theme.warning_message,
+            &theme.error_message,
+        ];
+
+        for style in styles.iter() {
+            assert_eq!(style.fg, Color::Default);
+            assert_eq!(style.bg, Color::Default);
+        }
+        assert_eq!(theme.muted_color, Color::Default);
+    }
+
+    #[test]
+    fn test_parse_invalid_theme() {
+        assert_eq!(
+            Err("Unknown role: keys".to_owned()),
+            Theme::parse("keys = \"red\"").map(|_| ())
+        );
+        assert_eq!(
+            Err("key: Unknown color: pink".to_owned()),
+            Theme::parse("key = \"pink\"").map(|_| ())
+        );
+        assert_eq!(
+            Err("key: Color index 256 must be between 0 and 255".to_owned()),
+            Theme::parse("key = 256").map(|_| ())
+        );
+        assert!(Theme::parse("key = true").is_err());
+        assert_eq!(
+            Err("expanded-marker: Expected a single character: \"--\"".to_owned()),
+            Theme::parse("expanded-marker = \"--\"").map(|_| ())
+        );
+        assert!(Theme::parse("expanded-marker = 1").is_err());
+        assert!(Theme::parse("key = ").is_err());
+    }
+}
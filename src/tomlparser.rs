@@ -0,0 +1,136 @@
+use std::borrow::Cow;
+
+use toml::Value as Toml;
+
+use crate::datamodel::{DataModel, Node};
+use crate::flatjson::{FlatJson, Row};
+
+pub fn parse(toml: String, max_depth: usize) -> Result<(Vec<Row>, String, usize), String> {
+    let table = match toml.parse::<Toml>() {
+        Ok(table) => table,
+        Err(err) => return Err(format!("{}", err)),
+    };
+
+    let FlatJson(rows, pretty_printed, max_depth) =
+        FlatJson::from_value_to_depth(&TomlDataModel, &table, max_depth);
+    Ok((rows, pretty_printed, max_depth))
+}
+
+struct TomlDataModel;
+
+impl DataModel for TomlDataModel {
+    type Value = Toml;
+
+    fn node<'a>(&self, value: &'a Toml) -> Node<'a, Toml> {
+        match value {
+            Toml::Boolean(b) => Node::Boolean(*b),
+            Toml::Integer(i) => Node::Number(Cow::Owned(i.to_string())),
+            Toml::Float(f) => float_node(*f),
+            Toml::String(s) => Node::String(Cow::Borrowed(s)),
+            // There's no JSON equivalent of datetimes, so we just show them
+            // as strings.
+            Toml::Datetime(datetime) => Node::String(Cow::Owned(datetime.to_string())),
+            Toml::Array(arr) => Node::Array(arr.iter().collect()),
+            Toml::Table(table) => Node::Object(
+                table
+                    .iter()
+                    .map(|(key, value)| (Cow::Borrowed(key.as_str()), value))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+fn float_node(f: f64) -> Node<'static, Toml> {
+    // Infinity and NaN aren't valid JSON numbers, so show them as
+    // strings, using their TOML representations.
+    if f.is_nan() {
+        Node::String(Cow::Borrowed("nan"))
+    } else if f.is_infinite() {
+        Node::String(Cow::Borrowed(if f > 0.0 { "inf" } else { "-inf" }))
+    } else {
+        // Use the Debug representation so floats with integer values
+        // are still printed with a decimal point.
+        Node::Number(Cow::Owned(format!("{:?}", f)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::flatjson::{Value, DEFAULT_MAX_DEPTH};
+
+    #[test]
+    fn test_basic() {
+        // 0 2    7  10   15     22   27 30
+        // { "a": 1, "b": true, "c": [] }
+        let toml = indoc! {r#"
+            a = 1
+            b = true
+            c = []
+        "#}
+        .to_owned();
+        let (rows, pretty, _) = parse(toml, DEFAULT_MAX_DEPTH).unwrap();
+
+        assert_eq!(r#"{ "a": 1, "b": true, "c": [] }"#, pretty);
+        assert_eq!(rows[0].range, 0..30); // Object
+        assert_eq!(rows[1].key_range, Some(2..5)); // "a": 1
+        assert_eq!(rows[1].range, 7..8); // "a": 1
+        assert_eq!(rows[2].key_range, Some(10..13)); // "b": true
+        assert_eq!(rows[2].range, 15..19); // "b": true
+        assert_eq!(rows[3].key_range, Some(21..24)); // "c": []
+        assert_eq!(rows[3].range, 26..28); // "c": []
+        assert_eq!(rows[4].range, 29..30); // }
+    }
+
+    #[test]
+    fn test_tables_and_arrays_of_tables() {
+        let toml = indoc! {r#"
+            title = "jless"
+
+            [package]
+            version = "0.8.0"
+
+            [[bin]]
+            name = "a"
+
+            [[bin]]
+            name = "b"
+        "#}
+        .to_owned();
+        let pretty = r#"{ "title": "jless", "package": { "version": "0.8.0" }, "bin": [{ "name": "a" }, { "name": "b" }] }"#;
+        let (rows, parsed_pretty, depth) = parse(toml, DEFAULT_MAX_DEPTH).unwrap();
+
+        assert_eq!(pretty, parsed_pretty);
+        assert_eq!(depth, 3);
+        assert!(rows[2].is_opening_of_container());
+        assert!(!rows[2].is_array());
+        assert!(rows[5].is_array());
+        assert_eq!(rows[5].index, 2);
+        assert_eq!(rows[9].index, 1);
+    }
+
+    #[test]
+    fn test_scalars() {
+        let toml = indoc! {r#"
+            float = 1.0
+            inf = -inf
+            date = 1979-05-27T07:32:00Z
+            str = "quote\" newline\n"
+        "#}
+        .to_owned();
+        let pretty = r#"{ "float": 1.0, "inf": "-inf", "date": "1979-05-27T07:32:00Z", "str": "quote\" newline\n" }"#;
+        let (rows, parsed_pretty, _) = parse(toml, DEFAULT_MAX_DEPTH).unwrap();
+
+        assert_eq!(pretty, parsed_pretty);
+        assert!(matches!(rows[1].value, Value::Number));
+        assert!(matches!(rows[2].value, Value::String));
+        assert!(matches!(rows[3].value, Value::String));
+        assert_eq!(
+            &parsed_pretty[rows[4].range.clone()],
+            r#""quote\" newline\n""#
+        );
+    }
+}
@@ -2,12 +2,26 @@ use std::cmp::Ordering;
 use std::fmt;
 use std::ops::Range;
 
+use clap::ArgEnum;
 use unicode_segmentation::UnicodeSegmentation;
-use unicode_width::UnicodeWidthStr;
 
-/// This module provides functionality for truncating strings,
-/// displaying them, and manipulating which portion of the string
-/// is visible.
+use crate::printable;
+use crate::printable::Printable;
+
+// This module provides functionality for truncating strings,
+// displaying them, and manipulating which portion of the string
+// is visible.
+
+/// Which part of a string is elided when it doesn't fit.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, ArgEnum)]
+pub enum Truncation {
+    /// Show the end of the string: "…xyz".
+    Start,
+    /// Show the start and end of the string: "ab…yz".
+    Middle,
+    /// Show the start of the string: "abc…".
+    End,
+}
 
 /// A TruncatedStrView represents an attempt to fit a string within
 /// a given amount of available space. When `range` is None, it
@@ -54,12 +68,17 @@ pub struct TruncatedStrView {
 /// entire available space, rather than including the replacment
 /// character.
 ///
+/// When the middle of a string is elided instead, `start` and `end`
+/// cover the whole string, and `elided_middle` is the range that's
+/// replaced by an ellipsis.
+///
 /// This range also keeps track of how much space it takes up.
 #[derive(Debug, Copy, Clone)]
 pub struct TruncatedRange {
     pub start: usize,
     pub end: usize,
     pub showing_replacement_character: bool,
+    pub elided_middle: Option<(usize, usize)>,
     used_space: isize,
 }
 
@@ -96,7 +115,7 @@ struct RangeAdjuster<'a> {
 impl TruncatedRange {
     // Create a RangeAdjuster representing the current state of the
     // TruncatedRange.
-    fn adjuster<'a, 'b>(&'a self, s: &'b str, available_space: isize) -> RangeAdjuster<'b> {
+    fn adjuster<'b>(&self, s: &'b str, available_space: isize) -> RangeAdjuster<'b> {
         let mut used_space = self.used_space;
         // The adjuster doesn't keep track of the replacement character.
         if self.showing_replacement_character {
@@ -120,7 +139,20 @@ impl TruncatedRange {
 
     /// Check whether this is a truncated view of a string.
     pub fn is_truncated(&self, s: &str) -> bool {
-        self.start != 0 || self.end != s.len() || self.showing_replacement_character
+        self.start != 0
+            || self.end != s.len()
+            || self.showing_replacement_character
+            || self.elided_middle.is_some()
+    }
+
+    /// Check whether a range of the string is completely visible.
+    fn shows(&self, range: &Range<usize>) -> bool {
+        match self.elided_middle {
+            Some((hidden_start, hidden_end)) => {
+                range.end <= hidden_start || hidden_end <= range.start
+            }
+            None => self.start <= range.start && range.end <= self.end,
+        }
     }
 
     pub fn print_leading_ellipsis(&self) -> bool {
@@ -172,6 +204,89 @@ impl TruncatedStrView {
         adj.to_view()
     }
 
+    /// Create a truncated view of a string that shows both the beginning
+    /// and the end of the string, and elides the middle if there is not
+    /// sufficient space. The beginning gets any extra column.
+    pub fn init_middle(s: &str, available_space: isize) -> TruncatedStrView {
+        let start_view = Self::init_start(s, available_space);
+        // With fewer than 3 columns there isn't room for a character on
+        // both sides of the ellipsis.
+        if available_space < 3 || !start_view.range.unwrap().is_truncated(s) {
+            return start_view;
+        }
+
+        let mut graphemes = s.graphemes(true);
+        let space_for_text = available_space - 1;
+        let mut used_space = 0;
+        let mut prefix_end = 0;
+        let mut suffix_start = s.len();
+
+        // Fill the beginning up to half the space, then the end with the
+        // rest.
+        let mut next_at_start = None;
+        for grapheme in graphemes.by_ref() {
+            let width = printable::width(grapheme) as isize;
+            if used_space + width > (space_for_text + 1) / 2 {
+                next_at_start = Some((grapheme, width));
+                break;
+            }
+            used_space += width;
+            prefix_end += grapheme.len();
+        }
+        while let Some(grapheme) = graphemes.next_back() {
+            let width = printable::width(grapheme) as isize;
+            if used_space + width > space_for_text {
+                break;
+            }
+            used_space += width;
+            suffix_start -= grapheme.len();
+        }
+
+        // A wide character at the end may have left a column unused.
+        if let Some((grapheme, width)) = next_at_start {
+            if used_space + width <= space_for_text {
+                used_space += width;
+                prefix_end += grapheme.len();
+            }
+        }
+
+        TruncatedStrView {
+            range: Some(TruncatedRange {
+                start: 0,
+                end: s.len(),
+                showing_replacement_character: false,
+                elided_middle: Some((prefix_end, suffix_start)),
+                used_space: used_space + 1,
+            }),
+            available_space,
+        }
+    }
+
+    /// Create a truncated view of a string that elides the given part of
+    /// the string if there is not sufficient space.
+    pub fn init(s: &str, available_space: isize, truncation: Truncation) -> TruncatedStrView {
+        match truncation {
+            Truncation::Start => Self::init_back(s, available_space),
+            Truncation::Middle => Self::init_middle(s, available_space),
+            Truncation::End => Self::init_start(s, available_space),
+        }
+    }
+
+    /// Which part of the string a view elides, or None if the whole
+    /// string is visible.
+    pub fn truncation(&self, s: &str) -> Option<Truncation> {
+        let range = self.range?;
+        if range.elided_middle.is_some() {
+            Some(Truncation::Middle)
+        } else if range.start > 0 && range.end == s.len() {
+            Some(Truncation::Start)
+        } else if range.is_truncated(s) {
+            Some(Truncation::End)
+        } else {
+            None
+        }
+    }
+
     // Create a TruncatedStrView that indicates that the string cannot
     // be represented in the available space.
     fn init_no_view(available_space: isize) -> TruncatedStrView {
@@ -181,6 +296,10 @@ impl TruncatedStrView {
         }
     }
 
+    pub fn available_space(&self) -> isize {
+        self.available_space
+    }
+
     /// Return the amount of space used by a string view, if the string
     /// is representable.
     pub fn used_space(&self) -> Option<isize> {
@@ -202,12 +321,18 @@ impl TruncatedStrView {
 
     // Creates a RangeAdjuster that represents the current state of
     // the TruncatedStrView. This should only be called when the string
-    // is representable and we have a view.
-    fn range_adjuster<'a, 'b>(&'a self, s: &'b str) -> RangeAdjuster<'b> {
+    // is representable and we have a view that doesn't elide the middle
+    // of the string.
+    fn range_adjuster<'b>(&self, s: &'b str) -> RangeAdjuster<'b> {
         debug_assert!(self.range.is_some());
+        debug_assert!(self.range.unwrap().elided_middle.is_none());
         self.range.unwrap().adjuster(s, self.available_space)
     }
 
+    fn elides_middle(&self) -> bool {
+        self.range.map_or(false, |r| r.elided_middle.is_some())
+    }
+
     /// Scrolls a string view to the right by at least the specified
     /// number of characters (unless the end of the string is reached).
     pub fn scroll_right(&self, s: &str, count: usize) -> TruncatedStrView {
@@ -222,6 +347,12 @@ impl TruncatedStrView {
             return Self::init_back(s, self.available_space);
         }
 
+        // Scrolling a view that elides the middle of the string starts
+        // from its beginning.
+        if self.elides_middle() {
+            return Self::init_start(s, self.available_space).scroll_right(s, count);
+        }
+
         let mut adjuster = self.range_adjuster(s);
         // Show another character on the right.
         adjuster.expand_right(count);
@@ -256,6 +387,11 @@ impl TruncatedStrView {
             return Self::init_start(s, self.available_space);
         }
 
+        // Or from its end, when scrolling left.
+        if self.elides_middle() {
+            return Self::init_back(s, self.available_space).scroll_left(s, count);
+        }
+
         let mut adjuster = self.range_adjuster(s);
         // Show another character on the left.
         adjuster.expand_left(count);
@@ -286,7 +422,7 @@ impl TruncatedStrView {
         match self.range {
             None => *self,
             Some(range) => {
-                if range.end < s.len() {
+                if range.end < s.len() || range.elided_middle.is_some() {
                     TruncatedStrView::init_back(s, self.available_space)
                 } else {
                     TruncatedStrView::init_start(s, self.available_space)
@@ -301,6 +437,10 @@ impl TruncatedStrView {
             return TruncatedStrView::init_start(s, available_space);
         }
 
+        if self.elides_middle() {
+            return TruncatedStrView::init_middle(s, available_space);
+        }
+
         match available_space.cmp(&self.available_space) {
             Ordering::Less => {
                 if !Self::can_str_fit_at_all(s, available_space) {
@@ -393,10 +533,8 @@ impl TruncatedStrView {
         }
         end = end.min(s.len());
 
-        let visible_range = self.range.unwrap();
-
         // If the entire match is already visible, don't do anything.
-        if visible_range.start <= start && end <= visible_range.end {
+        if self.range.unwrap().shows(&(start..end)) {
             return *self;
         }
 
@@ -456,7 +594,7 @@ impl<'a> RangeAdjuster<'a> {
         for _ in 0..count {
             if let Some(grapheme) = right_graphemes.next() {
                 self.end += grapheme.len();
-                self.used_space += UnicodeWidthStr::width(grapheme) as isize;
+                self.used_space += printable::width(grapheme) as isize;
                 if self.end == self.s.len() {
                     // No more trailing ellipsis.
                     self.used_space -= 1;
@@ -473,7 +611,7 @@ impl<'a> RangeAdjuster<'a> {
         for _ in 0..count {
             if let Some(grapheme) = left_graphemes.next_back() {
                 self.start -= grapheme.len();
-                self.used_space += UnicodeWidthStr::width(grapheme) as isize;
+                self.used_space += printable::width(grapheme) as isize;
                 if self.start == 0 {
                     // No more leading ellipsis.
                     self.used_space -= 1;
@@ -504,7 +642,7 @@ impl<'a> RangeAdjuster<'a> {
     // Adds a grapheme to the right side of a view if it will fit.
     fn add_grapheme_to_right_if_it_will_fit(&mut self, grapheme: &str) -> bool {
         let new_end = self.end + grapheme.len();
-        let mut new_used_space = self.used_space + UnicodeWidthStr::width(grapheme) as isize;
+        let mut new_used_space = self.used_space + printable::width(grapheme) as isize;
 
         if new_end == self.s.len() {
             // No more trailing ellipsis.
@@ -541,7 +679,7 @@ impl<'a> RangeAdjuster<'a> {
     // Adds a grapheme to the left side of a view if it will fit.
     fn add_grapheme_to_left_if_it_will_fit(&mut self, grapheme: &str) -> bool {
         let new_start = self.start - grapheme.len();
-        let mut new_used_space = self.used_space + UnicodeWidthStr::width(grapheme) as isize;
+        let mut new_used_space = self.used_space + printable::width(grapheme) as isize;
 
         if new_start == 0 {
             // No more leading ellipsis.
@@ -625,7 +763,7 @@ impl<'a> RangeAdjuster<'a> {
                 self.used_space += 1;
             }
             self.end -= rightmost_grapheme.len();
-            self.used_space -= UnicodeWidthStr::width(rightmost_grapheme) as isize;
+            self.used_space -= printable::width(rightmost_grapheme) as isize;
         }
     }
 
@@ -641,7 +779,7 @@ impl<'a> RangeAdjuster<'a> {
                 self.used_space += 1;
             }
             self.start += leftmost_grapheme.len();
-            self.used_space -= UnicodeWidthStr::width(leftmost_grapheme) as isize;
+            self.used_space -= printable::width(leftmost_grapheme) as isize;
         }
     }
 
@@ -676,6 +814,7 @@ impl<'a> RangeAdjuster<'a> {
                 start: self.start,
                 end: self.end,
                 showing_replacement_character,
+                elided_middle: None,
                 used_space,
             }),
             available_space: self.available_space,
@@ -693,9 +832,19 @@ impl<'a, 'b> fmt::Display for TruncatedStrSlice<'a, 'b> {
             start,
             end,
             showing_replacement_character,
+            elided_middle,
             ..
         } = self.truncated_view.range.unwrap();
 
+        if let Some((hidden_start, hidden_end)) = elided_middle {
+            return write!(
+                f,
+                "{}…{}",
+                Printable(&self.s[..hidden_start]),
+                Printable(&self.s[hidden_end..])
+            );
+        }
+
         if start != 0 {
             f.write_str("…")?;
         }
@@ -704,7 +853,7 @@ impl<'a, 'b> fmt::Display for TruncatedStrSlice<'a, 'b> {
             f.write_str("�")?;
         }
 
-        f.write_str(&self.s[start..end])?;
+        write!(f, "{}", Printable(&self.s[start..end]))?;
 
         if end != self.s.len() {
             f.write_str("…")?;
@@ -1103,6 +1252,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_init_middle() {
+        #[track_caller]
+        fn assert_init_middle(string: &str, space: isize, expected: &str, used_space: isize) {
+            let tsv = TruncatedStrView::init_middle(string, space);
+            assert_eq!(expected, rendered(string, &tsv));
+            assert_eq!(Some(used_space), tsv.used_space());
+        }
+
+        assert_init_middle("abcdefgh", 8, "abcdefgh", 8);
+        assert_init_middle("abcdefgh", 7, "abc…fgh", 7);
+        assert_init_middle("abcdefgh", 6, "abc…gh", 6);
+        assert_init_middle("abcdefgh", 3, "a…h", 3);
+        // Not enough room for characters on both sides.
+        assert_init_middle("abcdefgh", 2, "a…", 2);
+
+        // Wide characters that don't fit leave room on the other side.
+        assert_init_middle("🦀bcdefgh", 5, "🦀…gh", 5);
+        assert_init_middle("abcdefg🦀", 6, "abc…🦀", 6);
+        assert_init_middle("abcdef🦀g", 6, "abcd…g", 6);
+
+        let s = "abcdefgh";
+        let tsv = TruncatedStrView::init(s, 6, Truncation::Middle);
+        assert_eq!(Some(Truncation::Middle), tsv.truncation(s));
+        assert_eq!(
+            Some(Truncation::Start),
+            TruncatedStrView::init(s, 6, Truncation::Start).truncation(s)
+        );
+        assert_eq!(
+            None,
+            TruncatedStrView::init(s, 8, Truncation::Middle).truncation(s)
+        );
+
+        // Scrolling starts from either end of the string.
+        assert_eq!("…defgh", rendered(s, &tsv.scroll_right(s, 2)));
+        assert_eq!("abcde…", rendered(s, &tsv.scroll_left(s, 2)));
+        assert_eq!("…defgh", rendered(s, &tsv.jump_to_an_end(s)));
+        // Resizing keeps eliding the middle.
+        assert_eq!("ab…h", rendered(s, &tsv.resize(s, 4)));
+        assert_eq!("abcdefgh", rendered(s, &tsv.resize(s, 10)));
+
+        assert_focuses(
+            s,
+            tsv,
+            vec![
+                (&(1..2), "abc…gh"),
+                (&(6..8), "abc…gh"),
+                (&(3..4), "…cdef…"),
+            ],
+        );
+    }
+
     #[track_caller]
     fn assert_focuses(
         string: &str,
@@ -1,14 +1,39 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use clap::ArgEnum;
 
-use crate::flatjson::{FlatJson, Index, OptionIndex};
+use crate::flatjson::{parse_top_level_json, FlatJson, Index, OptionIndex, Row};
+use crate::jsonparser::{unescape_string, ParsedRows};
 use crate::types::TTYDimensions;
 
-#[derive(PartialEq, Eq, Copy, Clone, Debug, ArgEnum)]
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug, ArgEnum)]
 pub enum Mode {
     Line,
     Data,
 }
 
+/// Which values are left out to make large inputs easier to scan. They're
+/// hidden the same way rows that don't match a filter are.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum HiddenValues {
+    Nothing,
+    Nulls,
+    // Nulls, and empty objects and arrays.
+    NullsAndEmpty,
+}
+
+impl HiddenValues {
+    pub fn next(self) -> HiddenValues {
+        match self {
+            HiddenValues::Nothing => HiddenValues::Nulls,
+            HiddenValues::Nulls => HiddenValues::NullsAndEmpty,
+            HiddenValues::NullsAndEmpty => HiddenValues::Nothing,
+        }
+    }
+}
+
 const DEFAULT_SCROLLOFF: u16 = 3;
 
 pub struct JsonViewer {
@@ -30,6 +55,46 @@ pub struct JsonViewer {
     // Access the functional value via .scrolloff().
     pub scrolloff_setting: u16,
     pub mode: Mode,
+
+    // Rows bookmarked via marks, keyed by the mark's letter.
+    marks: BTreeMap<char, Index>,
+
+    // When object keys are sorted, the original FlatJson, and the index
+    // of the row in the original FlatJson for each row in the sorted one.
+    unsorted: Option<(FlatJson, Vec<Index>)>,
+
+    // When only rows matching a filter are shown, the unfiltered FlatJson,
+    // the index of the row in it for each row in the filtered one, and any
+    // marks set on rows that were hidden by the filter.
+    unfiltered: Option<(FlatJson, Vec<Index>, BTreeMap<char, Index>)>,
+    // Which values are hidden, when the rows are filtered by hide_values.
+    hidden_values: HiddenValues,
+
+    // While the input is being parsed in the background, the number of
+    // rows, and the length of the pretty printed input, that have been
+    // parsed so far. Any rows after those are placeholders for the ends of
+    // containers that haven't been completely parsed.
+    parsed_prefix: Option<(usize, usize)>,
+
+    // Strings whose JSON contents have been decoded into objects or arrays,
+    // keyed by the index of the decoded value in the original (unsorted and
+    // unfiltered) FlatJson, along with the original string literal.
+    decoded: BTreeMap<Index, String>,
+
+    // Identifies the current rows; it changes whenever they're replaced
+    // (e.g., by sorting or filtering them) or added to, so that anything
+    // that refers to them by their indexes can tell when it's out of date.
+    rows_id: usize,
+    // How many actions have (possibly) changed which objects and arrays
+    // are collapsed.
+    collapse_changes: usize,
+}
+
+// Viewers' rows are given a different ID every time they change, even
+// across viewers.
+fn new_rows_id() -> usize {
+    static NEXT_ROWS_ID: AtomicUsize = AtomicUsize::new(0);
+    NEXT_ROWS_ID.fetch_add(1, Ordering::Relaxed)
 }
 
 impl JsonViewer {
@@ -43,8 +108,131 @@ impl JsonViewer {
             dimensions: TTYDimensions::default(),
             scrolloff_setting: DEFAULT_SCROLLOFF,
             mode,
+            marks: BTreeMap::new(),
+            unsorted: None,
+            unfiltered: None,
+            hidden_values: HiddenValues::Nothing,
+            parsed_prefix: None,
+            decoded: BTreeMap::new(),
+            rows_id: new_rows_id(),
+            collapse_changes: 0,
+        }
+    }
+}
+
+/// The state of a viewer that can be restored when viewing the same input
+/// again later: which objects and arrays are collapsed, the focused row,
+/// and where it is on the screen. Rows are identified by the hashes of
+/// their paths, so this can be saved across sessions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedState {
+    pub collapsed: Vec<u64>,
+    // The focused row followed by its ancestors, so that the closest one
+    // that still exists can be focused instead.
+    pub focused: Vec<u64>,
+    pub lines_above: usize,
+}
+
+// FNV-1a, which unlike DefaultHasher is guaranteed to produce the same
+// hashes in every version of jless, and on every platform, so that saved
+// path hashes stay valid.
+struct PathHasher(u64);
+
+impl PathHasher {
+    fn new() -> PathHasher {
+        PathHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for PathHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+}
+
+// Identifies each row by a hash of its path (the keys and array indexes
+// leading to it), so rows can be matched up between different versions of
+// the same input. Closing rows are identified by their opening rows.
+pub fn path_hashes(flatjson: &FlatJson) -> Vec<u64> {
+    let mut hashes: Vec<u64> = Vec::with_capacity(flatjson.0.len());
+
+    for row in flatjson.0.iter() {
+        let hash = hash_path(flatjson, row, |index| hashes[index]);
+        hashes.push(hash);
+    }
+
+    hashes
+}
+
+// The path hash of a single row, without hashing every row before it.
+fn path_hash(flatjson: &FlatJson, index: Index) -> u64 {
+    hash_path(flatjson, &flatjson[index], |index| {
+        path_hash(flatjson, index)
+    })
+}
+
+// Hashes a row's path, given a way to look up the path hashes of its
+// parent and of its opening row.
+fn hash_path(flatjson: &FlatJson, row: &Row, path_hash_of: impl Fn(Index) -> u64) -> u64 {
+    let mut hasher = PathHasher::new();
+    if row.is_closing_of_container() {
+        path_hash_of(row.pair_index().unwrap()).hash(&mut hasher);
+        true.hash(&mut hasher);
+    } else {
+        if let OptionIndex::Index(parent) = row.parent {
+            path_hash_of(parent).hash(&mut hasher);
+        }
+        match &row.key_range {
+            Some(key_range) => flatjson.1[key_range.clone()].hash(&mut hasher),
+            None => row.index.hash(&mut hasher),
+        }
+    }
+    hasher.finish()
+}
+
+// Returns whether the string matches a pattern where '*' matches any
+// sequence of characters and '?' matches any single character.
+fn glob_matches(pattern: &str, s: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+
+    let (mut p, mut i) = (0, 0);
+    // Position of the last '*' in the pattern, and the position in the
+    // string that it was matched up to, so we can backtrack.
+    let mut last_star: Option<(usize, usize)> = None;
+
+    while i < s.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == s[i]) {
+            p += 1;
+            i += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            last_star = Some((p, i));
+            p += 1;
+        } else if let Some((star_p, star_i)) = last_star {
+            // Have the last '*' match one more character.
+            p = star_p + 1;
+            i = star_i + 1;
+            last_star = Some((star_p, star_i + 1));
+        } else {
+            return false;
         }
     }
+
+    pattern[p..].iter().all(|&ch| ch == '*')
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -58,6 +246,11 @@ pub enum Action {
     MoveLeft,
     MoveRight,
     MoveTo(Index),
+    // Focus the given row, expanding any collapsed ancestors so that it's
+    // visible.
+    RevealAndMoveTo(Index),
+
+    SetMark(char),
 
     // TODO: Come up with better names for these. Their behavior is
     // a little subtle. When moving down it'll move forward until
@@ -70,6 +263,9 @@ pub enum Action {
     MoveDownUntilDepthChange,
 
     FocusParent,
+    // Focus the container that the focused node is in, and collapse it.
+    // (A closing brace is treated as being in its own container.)
+    CollapseParent,
 
     // The behavior of these is subtle and stateful. These move to the
     // previous/next sibling of the focused element. If we are focused
@@ -79,6 +275,12 @@ pub enum Action {
     FocusPrevSibling(usize),
     FocusNextSibling(usize),
 
+    // Unlike the above, these never leave the focused node's parent, and
+    // just skip over the contents of any expanded containers. At the
+    // first or last sibling, the focus doesn't move.
+    SkipToPrevSibling(usize),
+    SkipToNextSibling(usize),
+
     FocusFirstSibling,
     FocusLastSibling,
     FocusTop,
@@ -113,6 +315,11 @@ pub enum Action {
     JumpUp(Option<usize>),
     JumpDown(Option<usize>),
 
+    // Scroll up or down by a page of the given number of rows (usually
+    // the height of the screen), moving the focused row along with it.
+    // Paging up stops at the start of the file, and paging down stops once
+    // the end of the file is at the bottom of the screen; after that, they
+    // focus the first or last row.
     PageUp(usize),
     PageDown(usize),
 
@@ -121,12 +328,32 @@ pub enum Action {
     MoveFocusedLineToBottom,
 
     Click(u16),
+    DoubleClick(u16),
 
     ToggleCollapsed,
     CollapseNodeAndSiblings,
     ExpandNodeAndSiblings,
+    // Collapse or expand the siblings of the focused container to match
+    // the focused container.
+    MatchSiblingsToCollapsedState,
+    // Collapse every sibling of the focused node, and expand the focused
+    // node.
+    CollapseSiblingsExceptFocused,
+    // Expand or collapse the focused container and every container inside
+    // it.
+    ExpandSubtree,
+    CollapseSubtree,
+
+    // Collapse every container at or below the given depth, and expand
+    // every container above it.
+    CollapseToDepth(usize),
+    // Expand every container above the given depth.
+    ExpandToDepth(usize),
 
     ToggleMode,
+    // Toggle between showing object keys in their original order and
+    // sorted alphabetically.
+    ToggleSortedKeys,
 
     ResizeViewerDimensions(TTYDimensions),
 }
@@ -135,6 +362,9 @@ impl JsonViewer {
     pub fn perform_action(&mut self, action: Action) {
         let track_window = JsonViewer::should_refocus_window(&action);
         let reset_desired_depth = JsonViewer::should_reset_desired_depth(&action);
+        if JsonViewer::may_change_collapsed_state(&action) {
+            self.collapse_changes += 1;
+        }
 
         match action {
             Action::NoOp => {}
@@ -143,11 +373,18 @@ impl JsonViewer {
             Action::MoveLeft => self.move_left(),
             Action::MoveRight => self.move_right(),
             Action::MoveTo(index) => self.focused_row = index,
+            Action::RevealAndMoveTo(index) => self.reveal_and_move_to(index),
+            Action::SetMark(mark) => {
+                self.marks.insert(mark, self.focused_row);
+            }
             Action::MoveUpUntilDepthChange => self.move_up_until_depth_change(),
             Action::MoveDownUntilDepthChange => self.move_down_until_depth_change(),
             Action::FocusParent => self.focus_parent(),
+            Action::CollapseParent => self.collapse_parent(),
             Action::FocusPrevSibling(n) => self.focus_prev_sibling(n),
             Action::FocusNextSibling(n) => self.focus_next_sibling(n),
+            Action::SkipToPrevSibling(n) => self.skip_to_sibling(n, false),
+            Action::SkipToNextSibling(n) => self.skip_to_sibling(n, true),
             Action::FocusFirstSibling => self.focus_first_sibling(),
             Action::FocusLastSibling => self.focus_last_sibling(),
             Action::FocusTop => self.focus_top(),
@@ -157,16 +394,24 @@ impl JsonViewer {
             Action::ScrollDown(n) => self.scroll_down(n),
             Action::JumpUp(option_n) => self.jump_up(option_n),
             Action::JumpDown(option_n) => self.jump_down(option_n),
-            Action::PageUp(n) => self.scroll_up(self.dimensions.height as usize * n),
-            Action::PageDown(n) => self.scroll_down(self.dimensions.height as usize * n),
+            Action::PageUp(rows) => self.page_up(rows),
+            Action::PageDown(rows) => self.page_down(rows),
             Action::MoveFocusedLineToTop => self.move_focused_line_to_top(),
             Action::MoveFocusedLineToCenter => self.move_focused_line_to_center(),
             Action::MoveFocusedLineToBottom => self.move_focused_line_to_bottom(),
             Action::Click(n) => self.click_row(n),
+            Action::DoubleClick(n) => self.double_click_row(n),
             Action::ToggleCollapsed => self.toggle_collapsed(),
             Action::CollapseNodeAndSiblings => self.collapse_node_and_siblings(),
             Action::ExpandNodeAndSiblings => self.expand_node_and_siblings(),
+            Action::MatchSiblingsToCollapsedState => self.match_siblings_to_collapsed_state(),
+            Action::CollapseSiblingsExceptFocused => self.collapse_siblings_except_focused(),
+            Action::ExpandSubtree => self.set_collapse_state_on_subtree(false),
+            Action::CollapseSubtree => self.set_collapse_state_on_subtree(true),
+            Action::CollapseToDepth(depth) => self.collapse_to_depth(depth),
+            Action::ExpandToDepth(depth) => self.expand_to_depth(depth),
             Action::ToggleMode => self.toggle_mode(),
+            Action::ToggleSortedKeys => self.toggle_sorted_keys(),
             Action::ResizeViewerDimensions(dims) => self.dimensions = dims,
         }
 
@@ -187,11 +432,16 @@ impl JsonViewer {
             Action::MoveLeft => true,
             Action::MoveRight => true,
             Action::MoveTo(_) => true,
+            Action::RevealAndMoveTo(_) => true,
+            Action::SetMark(_) => false,
             Action::MoveUpUntilDepthChange => true,
             Action::MoveDownUntilDepthChange => true,
             Action::FocusParent => true,
+            Action::CollapseParent => true,
             Action::FocusPrevSibling(_) => true,
             Action::FocusNextSibling(_) => true,
+            Action::SkipToPrevSibling(_) => true,
+            Action::SkipToNextSibling(_) => true,
             Action::FocusFirstSibling => true,
             Action::FocusLastSibling => true,
             Action::FocusTop => false, // Window refocusing is handled in focus_top.
@@ -207,18 +457,47 @@ impl JsonViewer {
             Action::MoveFocusedLineToCenter => false,
             Action::MoveFocusedLineToBottom => false,
             Action::Click(_) => true,
+            Action::DoubleClick(_) => true,
             Action::CollapseNodeAndSiblings => true,
             Action::ExpandNodeAndSiblings => true,
+            Action::MatchSiblingsToCollapsedState => true,
+            Action::CollapseSiblingsExceptFocused => true,
+            Action::ExpandSubtree => true,
+            Action::CollapseSubtree => true,
+            Action::CollapseToDepth(_) => true,
+            Action::ExpandToDepth(_) => true,
             Action::ToggleMode => false,
+            Action::ToggleSortedKeys => true,
             Action::ResizeViewerDimensions(_) => true,
             _ => false,
         }
     }
 
+    fn may_change_collapsed_state(action: &Action) -> bool {
+        matches!(
+            action,
+            Action::MoveLeft
+                | Action::MoveRight
+                | Action::CollapseParent
+                | Action::Click(_)
+                | Action::DoubleClick(_)
+                | Action::ToggleCollapsed
+                | Action::CollapseNodeAndSiblings
+                | Action::ExpandNodeAndSiblings
+                | Action::MatchSiblingsToCollapsedState
+                | Action::CollapseSiblingsExceptFocused
+                | Action::ExpandSubtree
+                | Action::CollapseSubtree
+                | Action::CollapseToDepth(_)
+                | Action::ExpandToDepth(_)
+        )
+    }
+
     fn should_reset_desired_depth(action: &Action) -> bool {
         !matches!(
             action,
             Action::NoOp
+                | Action::SetMark(_)
                 | Action::FocusPrevSibling(_)
                 | Action::FocusNextSibling(_)
                 | Action::ScrollUp(_)
@@ -399,6 +678,20 @@ impl JsonViewer {
         }
     }
 
+    fn collapse_parent(&mut self) {
+        let focused_row = &self.flatjson[self.focused_row];
+        let container = if focused_row.is_closing_of_container() {
+            OptionIndex::Index(focused_row.pair_index().unwrap())
+        } else {
+            focused_row.parent
+        };
+
+        if let OptionIndex::Index(container) = container {
+            self.focused_row = container;
+            self.flatjson.collapse(container);
+        }
+    }
+
     fn focus_prev_sibling(&mut self, rows: usize) {
         for _ in 0..rows {
             // The user is trying to move up in the file, but stay at the desired depth, so we just
@@ -444,6 +737,27 @@ impl JsonViewer {
         }
     }
 
+    fn skip_to_sibling(&mut self, siblings: usize, forward: bool) {
+        for _ in 0..siblings {
+            // Siblings are only linked from the openings of containers.
+            let mut row = &self.flatjson[self.focused_row];
+            if row.is_closing_of_container() {
+                row = &self.flatjson[row.pair_index().unwrap()];
+            }
+
+            let sibling = if forward {
+                row.next_sibling
+            } else {
+                row.prev_sibling
+            };
+
+            match sibling {
+                OptionIndex::Index(sibling) => self.focused_row = sibling,
+                OptionIndex::Nil => break,
+            }
+        }
+    }
+
     fn focus_first_sibling(&mut self) {
         match &self.flatjson[self.focused_row].parent {
             OptionIndex::Index(parent_index) => {
@@ -487,228 +801,1080 @@ impl JsonViewer {
         };
     }
 
-    fn focus_matching_pair(&mut self) {
-        if self.mode == Mode::Data {
-            return;
-        }
-        let current_row = &self.flatjson[self.focused_row];
-        if current_row.is_collapsed() {
-            return;
+    /// Returns the row for a mark, if it is set. Marks that refer to rows
+    /// that no longer exist (e.g., because the input was reloaded) are
+    /// removed.
+    pub fn get_mark(&mut self, mark: char) -> Option<Index> {
+        let index = *self.marks.get(&mark)?;
+        if index >= self.flatjson.0.len() {
+            self.marks.remove(&mark);
+            return None;
         }
+        Some(index)
+    }
 
-        match current_row.pair_index() {
-            // Do nothing; focused element isn't a container
-            OptionIndex::Nil => {}
-            OptionIndex::Index(matching_pair_index) => {
-                self.focused_row = matching_pair_index;
+    /// Collapses (or expands) every object or array whose key matches the
+    /// given pattern, which may use '*' and '?' as wildcards. Returns the
+    /// number of matching objects and arrays.
+    pub fn set_collapsed_state_by_key(&mut self, pattern: &str, collapsed: bool) -> usize {
+        let mut num_matches = 0;
+
+        for index in 0..self.flatjson.0.len() {
+            let row = &self.flatjson[index];
+            if !row.is_opening_of_container() {
+                continue;
             }
-        }
-    }
 
-    fn scroll_up(&mut self, rows: usize) {
-        self.top_row = self.count_n_lines_before(self.top_row, rows, self.mode);
-        let max_focused_row = self.count_n_lines_past(
-            self.top_row,
-            (self.dimensions.height - self.scrolloff() - 1) as usize,
-            self.mode,
-        );
+            let key_matches = match &row.key_range {
+                // Leave off the quotes around the key.
+                Some(range) => {
+                    glob_matches(pattern, &self.flatjson.1[range.start + 1..range.end - 1])
+                }
+                None => false,
+            };
 
-        if self.focused_row > max_focused_row {
-            self.focused_row = max_focused_row;
+            if key_matches {
+                num_matches += 1;
+                if collapsed {
+                    self.flatjson.collapse(index);
+                } else {
+                    self.flatjson.expand(index);
+                }
+            }
         }
-    }
 
-    fn scroll_down(&mut self, rows: usize) {
-        self.top_row = self.count_n_lines_past(self.top_row, rows, self.mode);
-        let first_focusable_row =
-            self.count_n_lines_past(self.top_row, self.scrolloff() as usize, self.mode);
+        self.focus_first_visible_ancestor();
+        self.ensure_focused_row_is_visible();
 
-        if self.focused_row < first_focusable_row {
-            self.focused_row = first_focusable_row;
-        }
+        num_matches
     }
 
-    fn jump_up(&mut self, distance: Option<usize>) {
-        let lines = self.determine_jump_distance(distance);
-
-        let original_top_row = self.top_row;
-        let num_visible_before_focused = self.index_of_focused_row_on_screen();
+    /// Collapses the objects in every array that can be shown as a table
+    /// (see FlatJson::is_table), so that each of them is shown as a row of
+    /// the table. Returns the number of tables.
+    pub fn collapse_tables(&mut self) -> usize {
+        let mut num_tables = 0;
 
-        self.top_row = self.count_n_lines_before(self.top_row, lines, self.mode);
+        for index in 0..self.flatjson.0.len() {
+            if !self.flatjson.is_table(index) {
+                continue;
+            }
 
-        // If the viewing window moved at all, then keep the focused line in the
-        // same place vertically. But if we're at the top of the file, then move
-        // the focused line by the expected amount. This prevents the viewing
-        // window and the focused line from both changing, but by different amounts.
-        if original_top_row != self.top_row {
-            self.focused_row = self.count_n_lines_past(
-                self.top_row,
-                num_visible_before_focused as usize,
-                self.mode,
-            );
-        } else {
-            self.focused_row = self.count_n_lines_before(self.focused_row, lines, self.mode);
+            num_tables += 1;
+            let mut element = self.flatjson[index].first_child();
+            while let OptionIndex::Index(i) = element {
+                self.flatjson.collapse(i);
+                element = self.flatjson[i].next_sibling;
+            }
         }
-    }
 
-    fn jump_down(&mut self, distance: Option<usize>) {
-        let lines = self.determine_jump_distance(distance);
+        self.focus_first_visible_ancestor();
+        self.ensure_focused_row_is_visible();
 
-        let original_top_row = self.top_row;
-        let num_visible_before_focused = self.index_of_focused_row_on_screen();
+        num_tables
+    }
 
-        self.top_row = self.count_n_lines_past(self.top_row, lines, self.mode);
+    pub fn sorted_keys(&self) -> bool {
+        self.unsorted.is_some()
+    }
 
-        let last_line = match self.mode {
-            Mode::Line => self.flatjson.last_visible_index(),
-            Mode::Data => self.flatjson.last_visible_item(),
+    /// Returns the input with object keys in their original order, and the
+    /// index in it of the given row, so values can be copied as they
+    /// originally appeared even when keys are sorted.
+    pub fn original_flatjson_and_index(&self, index: Index) -> (&FlatJson, Index) {
+        let (flatjson, index) = match &self.unfiltered {
+            Some((unfiltered, unfiltered_indexes, _)) => (unfiltered, unfiltered_indexes[index]),
+            None => (&self.flatjson, index),
         };
-        let top_row_if_last_row_is_at_bottom =
-            self.count_n_lines_before(last_line, self.dimensions.height as usize - 1, self.mode);
 
-        // When jumping, we won't show lines past EOF, unless we already
-        // are showing lines past EOF.
-        if self.top_row > top_row_if_last_row_is_at_bottom {
-            self.top_row = top_row_if_last_row_is_at_bottom.max(original_top_row);
-        }
-
-        // If the viewing window moved at all, then keep the focused line in the
-        // same place vertically. But if we're at the bottom of the file, then move
-        // the focused line by the expected amount. This prevents the viewing
-        // window and the focused line from both changing, but by different amounts.
-        if original_top_row != self.top_row {
-            self.focused_row = self.count_n_lines_past(
-                self.top_row,
-                num_visible_before_focused as usize,
-                self.mode,
-            );
-        } else {
-            self.focused_row = self.count_n_lines_past(self.focused_row, lines, self.mode);
+        match &self.unsorted {
+            Some((original, original_indexes)) => (original, original_indexes[index]),
+            None => (flatjson, index),
         }
     }
 
-    // If the user provided a count to a jump command, sets that as the the new
-    // jump distance. Otherwise, use the stored jump distance, or if none has
-    // been set yet, use the default of half a window size.
-    fn determine_jump_distance(&mut self, distance: Option<usize>) -> usize {
-        self.jump_distance = distance.or(self.jump_distance);
+    /// Returns the input before it was filtered or had its keys sorted.
+    pub fn original_flatjson(&self) -> &FlatJson {
+        let flatjson = match &self.unfiltered {
+            Some((unfiltered, _, _)) => unfiltered,
+            None => &self.flatjson,
+        };
 
-        match self.jump_distance {
-            Some(n) => n,
-            None => (self.dimensions.height as usize / 2).max(1),
+        match &self.unsorted {
+            Some((original, _)) => original,
+            None => flatjson,
         }
     }
 
-    fn move_focused_line_to_top(&mut self) {
-        let padding = self.scrolloff() as usize;
-        self.top_row = self.count_n_lines_before(self.focused_row, padding, self.mode);
+    /// The length of the pretty printed input that's been parsed so far,
+    /// not including any placeholders for the ends of containers.
+    pub fn parsed_len(&self) -> usize {
+        self.parsed_prefix
+            .map_or(self.flatjson.1.len(), |(_, len)| len)
     }
 
-    fn move_focused_line_to_center(&mut self) {
-        let padding = (self.dimensions.height / 2) as usize;
-        self.top_row = self.count_n_lines_before(self.focused_row, padding, self.mode);
+    /// Add more rows that have been parsed in the background. The viewer
+    /// may be created with an empty FlatJson, but rows must be added before
+    /// performing any actions.
+    pub fn append_parsed_rows(&mut self, mut parsed: ParsedRows) {
+        debug_assert!(!self.sorted_keys() && !self.filtered());
+
+        let (num_rows, len) = self
+            .parsed_prefix
+            .unwrap_or((self.flatjson.0.len(), self.flatjson.1.len()));
+
+        // The placeholders are going to be replaced, so remember which
+        // containers any rows referring to them are the end of.
+        let placeholder_container = |flatjson: &FlatJson, index: Index| {
+            if index >= num_rows && index < flatjson.0.len() {
+                Some(flatjson[index].pair_index().unwrap())
+            } else {
+                None
+            }
+        };
+        let focused_container = placeholder_container(&self.flatjson, self.focused_row);
+        let top_container = placeholder_container(&self.flatjson, self.top_row);
+        let marked_containers: Vec<(char, Index)> = self
+            .marks
+            .iter()
+            .filter_map(|(&mark, &index)| {
+                placeholder_container(&self.flatjson, index).map(|open| (mark, open))
+            })
+            .collect();
+
+        self.flatjson.0.truncate(num_rows);
+        self.flatjson.1.truncate(len);
+        let open_containers = std::mem::take(&mut parsed.open_containers);
+        self.flatjson.append_parsed_rows(parsed);
+        self.rows_id = new_rows_id();
+        self.parsed_prefix = Some((self.flatjson.0.len(), self.flatjson.1.len()));
+        self.flatjson.add_closing_placeholders(&open_containers);
+
+        let close_index = |flatjson: &FlatJson, open: Index| flatjson[open].pair_index().unwrap();
+        if let Some(open) = focused_container {
+            self.focused_row = close_index(&self.flatjson, open);
+        }
+        if let Some(open) = top_container {
+            self.top_row = close_index(&self.flatjson, open);
+        }
+        for (mark, open) in marked_containers {
+            self.marks.insert(mark, close_index(&self.flatjson, open));
+        }
     }
 
-    fn move_focused_line_to_bottom(&mut self) {
-        let padding = (self.dimensions.height - self.scrolloff() - 1) as usize;
-        self.top_row = self.count_n_lines_before(self.focused_row, padding, self.mode);
+    pub fn filtered(&self) -> bool {
+        self.unfiltered.is_some()
     }
 
-    fn click_row(&mut self, row: u16) {
-        self.focused_row = self.count_n_lines_past(self.top_row, (row - 1) as usize, self.mode);
-        if self.flatjson[self.focused_row].is_opening_of_container() {
-            self.toggle_collapsed();
-        }
-    }
+    /// Only show the given rows (which should be in order), along with their
+    /// ancestors and descendants, until the filter is cleared. Focus moves
+    /// to the first of the rows at or after the focused row. Filters can't
+    /// be nested, so any existing filter should be cleared first.
+    pub fn filter_rows(&mut self, rows: &[Index]) {
+        debug_assert!(!self.filtered());
 
-    fn toggle_collapsed(&mut self) {
-        let focused_row = &mut self.flatjson[self.focused_row];
-        if focused_row.is_primitive() {
+        if rows.is_empty() {
             return;
         }
 
-        if focused_row.is_closing_of_container() {
-            debug_assert!(
-                focused_row.is_expanded(),
-                "Focused on closing char when row is collapsed",
-            );
-            self.focused_row = self.flatjson[self.focused_row].pair_index().unwrap();
+        let first_row = rows
+            .iter()
+            .find(|&&index| index >= self.focused_row)
+            .unwrap_or(&rows[0]);
+
+        let (filtered, unfiltered_indexes) = self.flatjson.filtered(rows);
+        let filtered_indexes = self.show_filtered(filtered, unfiltered_indexes);
+
+        // Make sure none of the matching rows are hidden in collapsed
+        // containers.
+        for &index in rows.iter() {
+            let mut ancestor = self.flatjson[filtered_indexes[index].unwrap()].parent;
+            while let OptionIndex::Index(parent) = ancestor {
+                self.flatjson.expand(parent);
+                ancestor = self.flatjson[parent].parent;
+            }
         }
 
-        self.flatjson.toggle_collapsed(self.focused_row);
+        self.focused_row = filtered_indexes[*first_row].unwrap();
+        if self.mode == Mode::Data && self.flatjson[self.focused_row].is_closing_of_container() {
+            self.focused_row = self.flatjson[self.focused_row].pair_index().unwrap();
+        }
+        self.top_row = 0;
+        self.ensure_focused_row_is_visible();
     }
 
-    fn collapse_node_and_siblings(&mut self) {
-        // If we're collapsing a node, make sure we're focused on the open.
-        let focused_row = &mut self.flatjson[self.focused_row];
-        if focused_row.is_closing_of_container() {
-            debug_assert!(
-                focused_row.is_expanded(),
-                "Focused on closing char when row is collapsed",
-            );
+    /// Hide the members of objects whose values are null, and, depending on
+    /// which values are hidden, empty objects and arrays, in the same way
+    /// as filter_rows, until the filter is cleared. Any existing filter
+    /// should be cleared first. Returns whether there were any to hide.
+    pub fn hide_values(&mut self, hidden_values: HiddenValues) -> bool {
+        debug_assert!(!self.filtered());
+
+        let empty_containers = match hidden_values {
+            HiddenValues::Nothing => return false,
+            HiddenValues::Nulls => false,
+            HiddenValues::NullsAndEmpty => true,
+        };
+        let (filtered, unfiltered_indexes) =
+            match self.flatjson.without_empty_values(empty_containers) {
+                Some(without_empty_values) => without_empty_values,
+                None => return false,
+            };
+
+        let filtered_indexes = self.show_filtered(filtered, unfiltered_indexes);
+        self.hidden_values = hidden_values;
+
+        // If the focused row was hidden, focus the next row that wasn't (or
+        // the last one).
+        let focused_row = filtered_indexes[self.focused_row..]
+            .iter()
+            .flatten()
+            .next()
+            .copied();
+        self.focused_row = focused_row.unwrap_or_else(|| self.flatjson.last_visible_index());
+        self.top_row = filtered_indexes[..=self.top_row.min(filtered_indexes.len() - 1)]
+            .iter()
+            .rev()
+            .flatten()
+            .next()
+            .copied()
+            .unwrap_or(0);
+        self.focus_first_visible_ancestor();
+        if self.mode == Mode::Data && self.flatjson[self.focused_row].is_closing_of_container() {
             self.focused_row = self.flatjson[self.focused_row].pair_index().unwrap();
         }
-
-        self.set_collapse_state_on_node_and_siblings(true);
+        self.ensure_focused_row_is_visible();
+        true
     }
 
-    fn expand_node_and_siblings(&mut self) {
-        self.set_collapse_state_on_node_and_siblings(false);
+    /// Which values are hidden by hide_values.
+    pub fn hidden_values(&self) -> HiddenValues {
+        self.hidden_values
     }
 
-    fn set_collapse_state_on_node_and_siblings(&mut self, collapsed: bool) {
-        let first_sibling =
-            if let OptionIndex::Index(parent) = self.flatjson[self.focused_row].parent {
-                self.flatjson[parent].first_child().unwrap()
-            } else {
-                // If we don't have parent, that means we're at the top level, so the first
-                // sibling is the very first element.
-                0
+    // Shows a filtered copy of the rows, given the index of each of its rows
+    // in the current rows, until the filter is cleared. Returns the index
+    // of each of the current rows in the filtered copy, if it's kept. Marks
+    // on rows that aren't kept are restored when the filter is cleared.
+    fn show_filtered(
+        &mut self,
+        filtered: FlatJson,
+        unfiltered_indexes: Vec<Index>,
+    ) -> Vec<Option<Index>> {
+        let mut filtered_indexes = vec![None; self.flatjson.0.len()];
+        for (filtered_index, unfiltered_index) in unfiltered_indexes.iter().enumerate() {
+            filtered_indexes[*unfiltered_index] = Some(filtered_index);
+        }
+
+        let mut hidden_marks = BTreeMap::new();
+        for (mark, index) in std::mem::take(&mut self.marks) {
+            match filtered_indexes.get(index).copied().flatten() {
+                Some(filtered_index) => self.marks.insert(mark, filtered_index),
+                None => hidden_marks.insert(mark, index),
             };
+        }
 
-        let mut next_sibling = OptionIndex::Index(first_sibling);
+        let unfiltered = std::mem::replace(&mut self.flatjson, filtered);
+        self.rows_id = new_rows_id();
+        self.unfiltered = Some((unfiltered, unfiltered_indexes, hidden_marks));
+        filtered_indexes
+    }
 
-        while let OptionIndex::Index(next) = next_sibling {
-            if collapsed {
-                self.flatjson.collapse(next);
-            } else {
-                self.flatjson.expand(next);
-            }
-            next_sibling = self.flatjson[next].next_sibling;
+    /// Show all the rows again after filter_rows or hide_values.
+    pub fn clear_filter(&mut self) {
+        let (mut unfiltered, unfiltered_indexes, hidden_marks) = match self.unfiltered.take() {
+            Some(unfiltered) => unfiltered,
+            None => return,
+        };
+        self.hidden_values = HiddenValues::Nothing;
+
+        Self::copy_collapsed_state(&self.flatjson, &mut unfiltered, &unfiltered_indexes);
+        self.flatjson = unfiltered;
+        self.rows_id = new_rows_id();
+        self.remap_rows(&unfiltered_indexes);
+        for (mark, index) in hidden_marks {
+            self.marks.entry(mark).or_insert(index);
         }
-    }
 
-    fn toggle_mode(&mut self) {
-        let index_of_focused_row = self.index_of_focused_row_on_screen();
+        self.ensure_focused_row_is_visible();
+    }
 
-        // If we're transitioning from line mode to focused mode, and we're focused on
-        // the closing of a container, we need to move the focuse.
-        if self.mode == Mode::Line && self.flatjson[self.focused_row].is_closing_of_container() {
-            // We'll move focus to the next item, unless we're at the end of
-            // the file and have to move focus backwards.
-            //
-            // By focusing the next item, and ensuring that the focus stays in the
-            // same place on the screen, it will look the surrounding data is getting
-            // "pulled" towards the focused line.
-            if let OptionIndex::Index(next) = self.flatjson.next_item(self.focused_row) {
-                self.focused_row = next;
-            } else {
-                self.focused_row = self.flatjson.prev_item(self.focused_row).unwrap();
-            }
+    /// Restores the state of a viewer that was showing a previous version
+    /// of the same input: which objects and arrays are collapsed, the
+    /// focused row and where it is on the screen, marks, the mode, and
+    /// whether keys are sorted. Rows are matched up by their paths rather
+    /// than their indexes, since they may have moved. If the focused row no
+    /// longer exists, its closest ancestor that does is focused instead.
+    pub fn restore_state_from(&mut self, old: &JsonViewer) {
+        self.dimensions = old.dimensions;
+        self.scrolloff_setting = old.scrolloff_setting;
+        self.mode = old.mode;
+        if self.sorted_keys() != old.sorted_keys() {
+            self.toggle_sorted_keys();
         }
 
-        // Toggle the mode.
-        self.mode = match self.mode {
-            Mode::Line => Mode::Data,
-            Mode::Data => Mode::Line,
-        };
+        let old_hashes = path_hashes(&old.flatjson);
+        let new_indexes = self.restore_saved_state_with(&old.saved_state_with(&old_hashes));
 
-        // Ensure focused line stays in same place on the screen.
-        self.top_row =
-            self.count_n_lines_before(self.focused_row, index_of_focused_row as usize, self.mode);
+        self.marks = old
+            .marks()
+            .filter_map(|(mark, index)| Some((mark, *new_indexes.get(&old_hashes[index])?)))
+            .collect();
     }
 
-    fn scrolloff(&self) -> u16 {
+    /// Shows the same rows as a viewer of a similar input, e.g., the other
+    /// file being compared with --diff, given the row of this viewer with
+    /// the same path as each of the other's rows, if there is one: the
+    /// match for the other viewer's focused row (or its closest ancestor)
+    /// is focused, at the same place on the screen, and, if collapsed is
+    /// set, the same objects and arrays are collapsed. Marks, and whether
+    /// keys are sorted, are left alone.
+    pub fn show_same_rows_as(
+        &mut self,
+        other: &JsonViewer,
+        matching_rows: &[OptionIndex],
+        collapsed: bool,
+    ) {
+        self.dimensions = other.dimensions;
+        self.scrolloff_setting = other.scrolloff_setting;
+        self.mode = other.mode;
+
+        if collapsed {
+            for (index, row) in other.flatjson.0.iter().enumerate() {
+                let matching_row = match matching_rows[index] {
+                    OptionIndex::Index(i) if row.is_opening_of_container() => i,
+                    _ => continue,
+                };
+                if !self.flatjson[matching_row].is_opening_of_container() {
+                    continue;
+                }
+                if row.is_collapsed() {
+                    self.flatjson.collapse(matching_row);
+                } else {
+                    self.flatjson.expand(matching_row);
+                }
+            }
+        }
+
+        let mut index = OptionIndex::Index(other.focused_row);
+        let focused_row = loop {
+            match index {
+                OptionIndex::Index(i) => match matching_rows[i] {
+                    OptionIndex::Index(matching_row) => break matching_row,
+                    // The end of a container that's empty in this input
+                    // matches its start.
+                    OptionIndex::Nil if other.flatjson[i].is_closing_of_container() => {
+                        index = other.flatjson[i].pair_index()
+                    }
+                    OptionIndex::Nil => index = other.flatjson[i].parent,
+                },
+                OptionIndex::Nil => break 0,
+            }
+        };
+        self.reveal_and_move_to(focused_row);
+
+        let lines_above = other.index_of_focused_row_on_screen() as usize;
+        self.top_row = self.count_n_lines_before(self.focused_row, lines_above, self.mode);
+        self.ensure_focused_row_is_visible();
+    }
+
+    /// Identifies the current rows (see rows_id).
+    pub fn rows_id(&self) -> usize {
+        self.rows_id
+    }
+
+    /// How many actions have changed which objects and arrays are
+    /// collapsed, or at least might have.
+    pub fn collapse_changes(&self) -> usize {
+        self.collapse_changes
+    }
+
+    /// The hash of the path to a row, which identifies it across different
+    /// versions of the same input.
+    pub fn path_hash(&self, index: Index) -> u64 {
+        path_hash(&self.flatjson, index)
+    }
+
+    /// Returns the state to save so that it can be restored the next time
+    /// the same input is viewed.
+    pub fn saved_state(&self) -> SavedState {
+        self.saved_state_with(&path_hashes(&self.flatjson))
+    }
+
+    fn saved_state_with(&self, hashes: &[u64]) -> SavedState {
+        let collapsed = self
+            .flatjson
+            .0
+            .iter()
+            .zip(hashes.iter())
+            .filter(|(row, _)| row.is_opening_of_container() && row.is_collapsed())
+            .map(|(_, hash)| *hash)
+            .collect();
+
+        let mut focused = vec![];
+        let mut index = OptionIndex::Index(self.focused_row);
+        while let OptionIndex::Index(i) = index {
+            focused.push(hashes[i]);
+            index = self.flatjson[i].parent;
+        }
+
+        SavedState {
+            collapsed,
+            focused,
+            lines_above: self.index_of_focused_row_on_screen() as usize,
+        }
+    }
+
+    /// Collapses the containers and focuses the row in a saved state,
+    /// matching rows up by their paths.
+    pub fn restore_saved_state(&mut self, state: &SavedState) {
+        self.restore_saved_state_with(state);
+    }
+
+    // Returns the index of the row with each path hash, for restoring
+    // anything else that refers to rows.
+    fn restore_saved_state_with(&mut self, state: &SavedState) -> HashMap<u64, Index> {
+        let collapsed: HashSet<u64> = state.collapsed.iter().copied().collect();
+
+        let mut new_indexes = HashMap::new();
+        for (index, hash) in path_hashes(&self.flatjson).into_iter().enumerate() {
+            if collapsed.contains(&hash) && self.flatjson[index].is_opening_of_container() {
+                self.flatjson.collapse(index);
+            }
+            new_indexes.entry(hash).or_insert(index);
+        }
+
+        let focused_row = state
+            .focused
+            .iter()
+            .find_map(|hash| new_indexes.get(hash).copied())
+            .unwrap_or(0);
+        self.reveal_and_move_to(focused_row);
+
+        self.top_row = self.count_n_lines_before(self.focused_row, state.lines_above, self.mode);
+        self.ensure_focused_row_is_visible();
+
+        new_indexes
+    }
+
+    /// Adds elements to the end of the top-level array, e.g., records added
+    /// to a JSON Lines file that's being followed. If the last line was
+    /// focused, the new last line is focused, so that the newest elements
+    /// stay in view.
+    pub fn append_array_elements(&mut self, elements: FlatJson) {
+        debug_assert!(!self.filtered());
+
+        let old_close = self.flatjson.0.len() - 1;
+        let at_bottom = self.focused_row
+            == match self.mode {
+                Mode::Line => self.flatjson.last_visible_index(),
+                Mode::Data => self.flatjson.last_visible_item(),
+            };
+
+        // The elements are added to the rows in their original order.
+        let sorted = self.sorted_keys();
+        if sorted {
+            self.toggle_sorted_keys();
+        }
+        self.flatjson.append_array_elements(elements);
+        self.rows_id = new_rows_id();
+        if sorted {
+            self.toggle_sorted_keys();
+        }
+
+        // The closing bracket moved to the end.
+        let new_close = self.flatjson.0.len() - 1;
+        for index in self.marks.values_mut() {
+            if *index == old_close {
+                *index = new_close;
+            }
+        }
+        if at_bottom || self.focused_row == old_close {
+            self.focus_bottom();
+        }
+        self.ensure_focused_row_is_visible();
+    }
+
+    /// Replaces the focused string with the JSON object or array that it
+    /// contains, so it can be navigated like the rest of the input, or, if
+    /// the focused row isn't a string but is part of a decoded string,
+    /// restores the original string. Returns whether a string was decoded, or an error if the
+    /// focused string (or row) can't be decoded.
+    pub fn toggle_decoded_string(&mut self) -> Result<bool, String> {
+        // Strings are decoded in the original rows.
+        self.clear_filter();
+        let sorted = self.sorted_keys();
+        if sorted {
+            self.toggle_sorted_keys();
+        }
+        let result = self.toggle_decoded_string_in_original();
+        if sorted {
+            self.toggle_sorted_keys();
+        }
+
+        self.ensure_focused_row_is_visible();
+        result
+    }
+
+    fn toggle_decoded_string_in_original(&mut self) -> Result<bool, String> {
+        let mut index = self.focused_row;
+        if self.flatjson[index].is_closing_of_container() {
+            index = self.flatjson[index].pair_index().unwrap();
+        }
+
+        // Unless the focused row is a string to decode, restore the
+        // innermost decoded string that it's in.
+        let row = &self.flatjson[index];
+        if !row.is_string() {
+            let mut ancestor = OptionIndex::Index(index);
+            while let OptionIndex::Index(i) = ancestor {
+                if let Some(literal) = self.decoded.remove(&i) {
+                    self.replace_value(i, parse_top_level_json(literal).unwrap());
+                    return Ok(false);
+                }
+                ancestor = self.flatjson[i].parent;
+            }
+            return Err("Only strings can be decoded".to_string());
+        }
+
+        let literal = self.flatjson.1[row.range.clone()].to_owned();
+        let json = match unescape_string(&literal) {
+            Some(json) => json,
+            None => return Err("The string contains an invalid escape sequence".to_string()),
+        };
+        let value = match parse_top_level_json(json) {
+            Ok(value) => value,
+            Err(err) => return Err(format!("The string isn't valid JSON: {}", err)),
+        };
+        if !(value[0].is_container() && value[0].next_sibling.is_nil()) {
+            return Err("The string doesn't contain a JSON object or array".to_string());
+        }
+
+        self.replace_value(index, value);
+        self.decoded.insert(index, literal);
+        Ok(true)
+    }
+
+    // Replaces a value in the original FlatJson, updating the focused row,
+    // top row, marks, and decoded strings that refer to rows after it. Those
+    // that were part of the replaced value now refer to the new one.
+    fn replace_value(&mut self, index: Index, value: FlatJson) {
+        let old_last = match self.flatjson[index].pair_index() {
+            OptionIndex::Index(close_index) => close_index,
+            OptionIndex::Nil => index,
+        };
+        let new_last = index + value.0.len() - 1;
+        self.flatjson.replace_value(index, value);
+        self.rows_id = new_rows_id();
+
+        let new_index = |i: Index| {
+            if i <= index {
+                i
+            } else if i <= old_last {
+                index
+            } else {
+                i - old_last + new_last
+            }
+        };
+
+        self.focused_row = new_index(self.focused_row);
+        self.top_row = new_index(self.top_row);
+        for mark_index in self.marks.values_mut() {
+            *mark_index = new_index(*mark_index);
+        }
+        // Strings decoded inside the replaced value are gone.
+        self.decoded = std::mem::take(&mut self.decoded)
+            .into_iter()
+            .filter(|(i, _)| *i <= index || *i > old_last)
+            .map(|(i, literal)| (new_index(i), literal))
+            .collect();
+
+        self.focus_first_visible_ancestor();
+    }
+
+    /// Returns whether a row is the start of a decoded string.
+    pub fn is_decoded(&self, index: Index) -> bool {
+        if self.decoded.is_empty() {
+            return false;
+        }
+        let (_, original_index) = self.original_flatjson_and_index(index);
+        self.flatjson[index].is_opening_of_container() && self.decoded.contains_key(&original_index)
+    }
+
+    /// Returns all the currently set marks, in order.
+    pub fn marks(&self) -> impl Iterator<Item = (char, Index)> + '_ {
+        self.marks
+            .iter()
+            .map(|(mark, index)| (*mark, *index))
+            .filter(move |(_, index)| *index < self.flatjson.0.len())
+    }
+
+    fn reveal_and_move_to(&mut self, index: Index) {
+        let mut index = index;
+
+        // Closing rows aren't focusable in data mode, and aren't visible
+        // when their container is collapsed.
+        if self.flatjson[index].is_closing_of_container() {
+            let open_index = self.flatjson[index].pair_index().unwrap();
+            if self.mode == Mode::Data {
+                index = open_index;
+            } else if self.flatjson[open_index].is_collapsed() {
+                self.flatjson.expand(open_index);
+            }
+        }
+
+        let mut ancestor = self.flatjson[index].parent;
+        while let OptionIndex::Index(ancestor_index) = ancestor {
+            if self.flatjson[ancestor_index].is_collapsed() {
+                self.flatjson.expand(ancestor_index);
+            }
+            ancestor = self.flatjson[ancestor_index].parent;
+        }
+
+        self.focused_row = index;
+    }
+
+    // Jumps between the opening and closing of a container, or, when
+    // focused on a primitive value, to the container that encloses it.
+    // Closing delimiters aren't shown in data mode, so there this jumps
+    // from a container to its last child instead.
+    fn focus_matching_pair(&mut self) {
+        let current_row = &self.flatjson[self.focused_row];
+        if current_row.is_collapsed() {
+            return;
+        }
+
+        match current_row.pair_index() {
+            OptionIndex::Nil => self.focus_parent(),
+            OptionIndex::Index(matching_pair_index) => {
+                self.focused_row = match self.mode {
+                    Mode::Line => matching_pair_index,
+                    Mode::Data => match self.flatjson[matching_pair_index].last_child() {
+                        OptionIndex::Index(last_child) => last_child,
+                        OptionIndex::Nil => self.focused_row,
+                    },
+                };
+            }
+        }
+    }
+
+    fn scroll_up(&mut self, rows: usize) {
+        self.top_row = self.count_n_lines_before(self.top_row, rows, self.mode);
+        let max_focused_row = self.count_n_lines_past(
+            self.top_row,
+            (self.dimensions.height - self.scrolloff() - 1) as usize,
+            self.mode,
+        );
+
+        if self.focused_row > max_focused_row {
+            self.focused_row = max_focused_row;
+        }
+    }
+
+    fn scroll_down(&mut self, rows: usize) {
+        self.top_row = self.count_n_lines_past(self.top_row, rows, self.mode);
+        let first_focusable_row =
+            self.count_n_lines_past(self.top_row, self.scrolloff() as usize, self.mode);
+
+        if self.focused_row < first_focusable_row {
+            self.focused_row = first_focusable_row;
+        }
+    }
+
+    fn page_up(&mut self, rows: usize) {
+        if self.top_row == 0 {
+            self.focused_row = 0;
+            return;
+        }
+        self.scroll_up(rows);
+    }
+
+    fn page_down(&mut self, rows: usize) {
+        let original_top_row = self.top_row;
+        let last_line = match self.mode {
+            Mode::Line => self.flatjson.last_visible_index(),
+            Mode::Data => self.flatjson.last_visible_item(),
+        };
+        let top_row_if_last_row_is_at_bottom =
+            self.count_n_lines_before(last_line, self.dimensions.height as usize - 1, self.mode);
+
+        // Like when jumping, we won't show lines past EOF, unless we
+        // already are showing lines past EOF.
+        let max_top_row = top_row_if_last_row_is_at_bottom.max(original_top_row);
+        self.top_row = self
+            .count_n_lines_past(self.top_row, rows, self.mode)
+            .min(max_top_row);
+
+        if self.top_row == original_top_row {
+            self.focused_row = last_line;
+        } else {
+            // Moves the focused row down if it's now above the screen.
+            self.scroll_down(0);
+        }
+    }
+
+    fn jump_up(&mut self, distance: Option<usize>) {
+        let lines = self.determine_jump_distance(distance);
+
+        let original_top_row = self.top_row;
+        let num_visible_before_focused = self.index_of_focused_row_on_screen();
+
+        self.top_row = self.count_n_lines_before(self.top_row, lines, self.mode);
+
+        // If the viewing window moved at all, then keep the focused line in the
+        // same place vertically. But if we're at the top of the file, then move
+        // the focused line by the expected amount. This prevents the viewing
+        // window and the focused line from both changing, but by different amounts.
+        if original_top_row != self.top_row {
+            self.focused_row = self.count_n_lines_past(
+                self.top_row,
+                num_visible_before_focused as usize,
+                self.mode,
+            );
+        } else {
+            self.focused_row = self.count_n_lines_before(self.focused_row, lines, self.mode);
+        }
+    }
+
+    fn jump_down(&mut self, distance: Option<usize>) {
+        let lines = self.determine_jump_distance(distance);
+
+        let original_top_row = self.top_row;
+        let num_visible_before_focused = self.index_of_focused_row_on_screen();
+
+        self.top_row = self.count_n_lines_past(self.top_row, lines, self.mode);
+
+        let last_line = match self.mode {
+            Mode::Line => self.flatjson.last_visible_index(),
+            Mode::Data => self.flatjson.last_visible_item(),
+        };
+        let top_row_if_last_row_is_at_bottom =
+            self.count_n_lines_before(last_line, self.dimensions.height as usize - 1, self.mode);
+
+        // When jumping, we won't show lines past EOF, unless we already
+        // are showing lines past EOF.
+        if self.top_row > top_row_if_last_row_is_at_bottom {
+            self.top_row = top_row_if_last_row_is_at_bottom.max(original_top_row);
+        }
+
+        // If the viewing window moved at all, then keep the focused line in the
+        // same place vertically. But if we're at the bottom of the file, then move
+        // the focused line by the expected amount. This prevents the viewing
+        // window and the focused line from both changing, but by different amounts.
+        if original_top_row != self.top_row {
+            self.focused_row = self.count_n_lines_past(
+                self.top_row,
+                num_visible_before_focused as usize,
+                self.mode,
+            );
+        } else {
+            self.focused_row = self.count_n_lines_past(self.focused_row, lines, self.mode);
+        }
+    }
+
+    // If the user provided a count to a jump command, sets that as the the new
+    // jump distance. Otherwise, use the stored jump distance, or if none has
+    // been set yet, use the default of half a window size.
+    fn determine_jump_distance(&mut self, distance: Option<usize>) -> usize {
+        self.jump_distance = distance.or(self.jump_distance);
+
+        match self.jump_distance {
+            Some(n) => n,
+            None => (self.dimensions.height as usize / 2).max(1),
+        }
+    }
+
+    fn move_focused_line_to_top(&mut self) {
+        let padding = self.scrolloff() as usize;
+        self.move_focused_line_to(padding);
+    }
+
+    fn move_focused_line_to_center(&mut self) {
+        let padding = (self.dimensions.height / 2) as usize;
+        self.move_focused_line_to(padding);
+    }
+
+    fn move_focused_line_to_bottom(&mut self) {
+        let padding = (self.dimensions.height - self.scrolloff() - 1) as usize;
+        self.move_focused_line_to(padding);
+    }
+
+    // Scrolls so that the focused line is the given number of lines from
+    // the top of the screen, or as close as it can be at the start of the
+    // file. If the whole file fits on the screen, it stays at the top
+    // instead, rather than hiding the start of it.
+    fn move_focused_line_to(&mut self, padding: usize) {
+        let last_line_on_screen =
+            self.count_n_lines_past(0, self.dimensions.height as usize - 1, self.mode);
+        if self.count_n_lines_past(last_line_on_screen, 1, self.mode) == last_line_on_screen {
+            self.top_row = 0;
+            return;
+        }
+
+        self.top_row = self.count_n_lines_before(self.focused_row, padding, self.mode);
+    }
+
+    fn click_row(&mut self, row: u16) {
+        self.focused_row = self.count_n_lines_past(self.top_row, (row - 1) as usize, self.mode);
+        if self.flatjson[self.focused_row].is_opening_of_container() {
+            self.toggle_collapsed();
+        }
+    }
+
+    // The first click of a double click will already have toggled the
+    // container, so rather than toggling again, a double click always
+    // leaves the container expanded.
+    fn double_click_row(&mut self, row: u16) {
+        self.focused_row = self.count_n_lines_past(self.top_row, (row - 1) as usize, self.mode);
+        if self.flatjson[self.focused_row].is_collapsed() {
+            self.flatjson.expand(self.focused_row);
+        }
+    }
+
+    fn toggle_collapsed(&mut self) {
+        let focused_row = &mut self.flatjson[self.focused_row];
+        if focused_row.is_primitive() {
+            return;
+        }
+
+        if focused_row.is_closing_of_container() {
+            debug_assert!(
+                focused_row.is_expanded(),
+                "Focused on closing char when row is collapsed",
+            );
+            self.focused_row = self.flatjson[self.focused_row].pair_index().unwrap();
+        }
+
+        self.flatjson.toggle_collapsed(self.focused_row);
+    }
+
+    fn collapse_node_and_siblings(&mut self) {
+        // If we're collapsing a node, make sure we're focused on the open.
+        let focused_row = &mut self.flatjson[self.focused_row];
+        if focused_row.is_closing_of_container() {
+            debug_assert!(
+                focused_row.is_expanded(),
+                "Focused on closing char when row is collapsed",
+            );
+            self.focused_row = self.flatjson[self.focused_row].pair_index().unwrap();
+        }
+
+        self.set_collapse_state_on_node_and_siblings(true, None);
+    }
+
+    fn expand_node_and_siblings(&mut self) {
+        self.set_collapse_state_on_node_and_siblings(false, None);
+    }
+
+    fn match_siblings_to_collapsed_state(&mut self) {
+        if self.flatjson[self.focused_row].is_primitive() {
+            return;
+        }
+
+        if self.flatjson[self.focused_row].is_closing_of_container() {
+            self.focused_row = self.flatjson[self.focused_row].pair_index().unwrap();
+        }
+
+        let collapsed = self.flatjson[self.focused_row].is_collapsed();
+        self.set_collapse_state_on_node_and_siblings(collapsed, Some(self.focused_row));
+    }
+
+    fn collapse_siblings_except_focused(&mut self) {
+        if self.flatjson[self.focused_row].is_closing_of_container() {
+            self.focused_row = self.flatjson[self.focused_row].pair_index().unwrap();
+        }
+
+        self.set_collapse_state_on_node_and_siblings(true, Some(self.focused_row));
+        self.flatjson.expand(self.focused_row);
+    }
+
+    // Collapses or expands the focused node and all of its siblings,
+    // except for the node at the optional excluded index.
+    fn set_collapse_state_on_node_and_siblings(&mut self, collapsed: bool, except: Option<Index>) {
+        let first_sibling =
+            if let OptionIndex::Index(parent) = self.flatjson[self.focused_row].parent {
+                self.flatjson[parent].first_child().unwrap()
+            } else {
+                // If we don't have parent, that means we're at the top level, so the first
+                // sibling is the very first element.
+                0
+            };
+
+        let mut next_sibling = OptionIndex::Index(first_sibling);
+
+        while let OptionIndex::Index(next) = next_sibling {
+            if except == Some(next) {
+                // Leave as is.
+            } else if collapsed {
+                self.flatjson.collapse(next);
+            } else {
+                self.flatjson.expand(next);
+            }
+            next_sibling = self.flatjson[next].next_sibling;
+        }
+    }
+
+    // Expands or collapses every container from the opening to the closing
+    // of the focused one. When the closing is focused, the rows above it
+    // change, so the viewport is moved to keep the focused row (or the
+    // opening, after collapsing) on the same line of the screen.
+    fn set_collapse_state_on_subtree(&mut self, collapsed: bool) {
+        let focused_row = &self.flatjson[self.focused_row];
+        if focused_row.is_primitive() {
+            return;
+        }
+
+        let focused_closing = focused_row.is_closing_of_container();
+        let pair = focused_row.pair_index().unwrap();
+        let (start, end) = if focused_closing {
+            (pair, self.focused_row)
+        } else {
+            (self.focused_row, pair)
+        };
+        let line_on_screen = self.index_of_focused_row_on_screen() as usize;
+
+        for index in start..=end {
+            if self.flatjson[index].is_opening_of_container() {
+                if collapsed {
+                    self.flatjson.collapse(index);
+                } else {
+                    self.flatjson.expand(index);
+                }
+            }
+        }
+
+        if collapsed {
+            self.focused_row = start;
+        }
+        if focused_closing {
+            self.top_row = self.count_n_lines_before(self.focused_row, line_on_screen, self.mode);
+        }
+    }
+
+    fn collapse_to_depth(&mut self, depth: usize) {
+        for index in 0..self.flatjson.0.len() {
+            if self.flatjson[index].is_opening_of_container() {
+                if self.flatjson[index].depth >= depth {
+                    self.flatjson.collapse(index);
+                } else {
+                    self.flatjson.expand(index);
+                }
+            }
+        }
+
+        self.focus_first_visible_ancestor();
+    }
+
+    // Move the focus if the focused row is no longer visible because one
+    // of its ancestors was collapsed.
+    fn focus_first_visible_ancestor(&mut self) {
+        if self.flatjson[self.focused_row].is_closing_of_container()
+            && self.flatjson[self.focused_row].is_collapsed()
+        {
+            self.focused_row = self.flatjson[self.focused_row].pair_index().unwrap();
+        }
+        self.focused_row = self.flatjson.first_visible_ancestor(self.focused_row);
+    }
+
+    fn expand_to_depth(&mut self, depth: usize) {
+        for index in 0..self.flatjson.0.len() {
+            if self.flatjson[index].is_opening_of_container() && self.flatjson[index].depth < depth
+            {
+                self.flatjson.expand(index);
+            }
+        }
+    }
+
+    fn toggle_sorted_keys(&mut self) {
+        // Filtered rows are a copy of the (un)sorted rows, so we can't
+        // switch between them while filtering.
+        self.clear_filter();
+
+        // Maps row indexes from the currently displayed FlatJson to the
+        // one that will be displayed.
+        let new_indexes: Vec<Index> = match self.unsorted.take() {
+            Some((mut original, original_indexes)) => {
+                // Carry over any collapsing or expanding done while sorted.
+                Self::copy_collapsed_state(&self.flatjson, &mut original, &original_indexes);
+                self.flatjson = original;
+                original_indexes
+            }
+            None => {
+                let (sorted, original_indexes) = self.flatjson.sorted_by_key();
+                let mut sorted_indexes = vec![0; original_indexes.len()];
+                for (sorted_index, original_index) in original_indexes.iter().enumerate() {
+                    sorted_indexes[*original_index] = sorted_index;
+                }
+                let original = std::mem::replace(&mut self.flatjson, sorted);
+                self.unsorted = Some((original, original_indexes));
+                sorted_indexes
+            }
+        };
+
+        self.rows_id = new_rows_id();
+        self.remap_rows(&new_indexes);
+    }
+
+    // Collapse or expand the containers of another FlatJson to match a
+    // FlatJson derived from it (i.e., sorted or filtered), given the index
+    // in the other FlatJson of each row in the derived one.
+    fn copy_collapsed_state(derived: &FlatJson, other: &mut FlatJson, other_indexes: &[Index]) {
+        for (index, row) in derived.0.iter().enumerate() {
+            if row.is_opening_of_container() {
+                if row.is_collapsed() {
+                    other.collapse(other_indexes[index]);
+                } else {
+                    other.expand(other_indexes[index]);
+                }
+            }
+        }
+    }
+
+    // Update the focused row, top row, and marks after the FlatJson is
+    // replaced, given the new index of each row in the previous FlatJson.
+    fn remap_rows(&mut self, new_indexes: &[Index]) {
+        self.focused_row = new_indexes[self.focused_row];
+        self.top_row = new_indexes[self.top_row];
+        for index in self.marks.values_mut() {
+            if let Some(new_index) = new_indexes.get(*index) {
+                *index = *new_index;
+            }
+        }
+
+        self.focus_first_visible_ancestor();
+    }
+
+    fn toggle_mode(&mut self) {
+        let index_of_focused_row = self.index_of_focused_row_on_screen();
+
+        // If we're transitioning from line mode to focused mode, and we're focused on
+        // the closing of a container, we need to move the focuse.
+        if self.mode == Mode::Line && self.flatjson[self.focused_row].is_closing_of_container() {
+            // We'll move focus to the next item, unless we're at the end of
+            // the file and have to move focus backwards.
+            //
+            // By focusing the next item, and ensuring that the focus stays in the
+            // same place on the screen, it will look the surrounding data is getting
+            // "pulled" towards the focused line.
+            if let OptionIndex::Index(next) = self.flatjson.next_item(self.focused_row) {
+                self.focused_row = next;
+            } else {
+                self.focused_row = self.flatjson.prev_item(self.focused_row).unwrap();
+            }
+        }
+
+        // Toggle the mode.
+        self.mode = match self.mode {
+            Mode::Line => Mode::Data,
+            Mode::Data => Mode::Line,
+        };
+
+        // Ensure focused line stays in same place on the screen.
+        self.top_row =
+            self.count_n_lines_before(self.focused_row, index_of_focused_row as usize, self.mode);
+    }
+
+    pub fn scrolloff(&self) -> u16 {
         self.scrolloff_setting.min((self.dimensions.height - 1) / 2)
     }
 
@@ -913,10 +2079,10 @@ mod tests {
     use crate::flatjson::{parse_top_level_json, NIL};
 
     impl OptionIndex {
-        pub fn to_usize(&self) -> usize {
+        pub fn to_usize(self) -> usize {
             match self {
                 OptionIndex::Nil => NIL,
-                OptionIndex::Index(i) => *i,
+                OptionIndex::Index(i) => i,
             }
         }
     }
@@ -935,643 +2101,1119 @@ mod tests {
         "11": 11
     }"#;
 
-    // Same object as DATA, but formatted as it would appear in data mode
-    const DATA_OBJECT: &str = r#"{
-        "1": 1,
-        "2": [
-            3,
-            "4"],
-        "6": {
-            "7": null,
-            "8": true,
-            "9": 9},
-        "11": 11}"#;
+    // Same object as DATA, but formatted as it would appear in data mode
+    const DATA_OBJECT: &str = r#"{
+        "1": 1,
+        "2": [
+            3,
+            "4"],
+        "6": {
+            "7": null,
+            "8": true,
+            "9": 9},
+        "11": 11}"#;
+
+    #[test]
+    fn test_move_up_down_line_mode() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+
+        assert_movements(
+            &mut viewer,
+            vec![(Action::MoveDown(1), 1), (Action::MoveDown(2), 3)],
+        );
+
+        viewer.flatjson.collapse(6);
+        viewer.focused_row = 6;
+
+        assert_movements(
+            &mut viewer,
+            vec![
+                (Action::MoveDown(1), 11),
+                (Action::MoveDown(1), 12),
+                (Action::MoveDown(1), 12),
+            ],
+        );
+
+        assert_movements(
+            &mut viewer,
+            vec![
+                (Action::MoveUp(2), 6),
+                (Action::MoveUp(1), 5),
+                (Action::MoveUp(5), 0),
+                (Action::MoveUp(2), 0),
+            ],
+        );
+
+        viewer.flatjson.collapse(0);
+        assert_movements(
+            &mut viewer,
+            vec![(Action::MoveUp(1), 0), (Action::MoveDown(1), 0)],
+        );
+    }
+
+    #[test]
+    fn test_move_up_down_data_mode() {
+        let fj = parse_top_level_json(DATA_OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Data);
+
+        assert_movements(
+            &mut viewer,
+            vec![
+                (Action::MoveDown(1), 1),
+                (Action::MoveDown(3), 4),
+                (Action::MoveDown(1), 6),
+            ],
+        );
+
+        viewer.flatjson.collapse(6);
+
+        assert_movements(
+            &mut viewer,
+            vec![(Action::MoveDown(1), 11), (Action::MoveDown(1), 11)],
+        );
+
+        assert_movements(
+            &mut viewer,
+            vec![
+                (Action::MoveUp(1), 6),
+                (Action::MoveUp(3), 2),
+                (Action::MoveUp(1), 1),
+                (Action::MoveUp(1), 0),
+                (Action::MoveUp(1), 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_move_left_right_line_mode() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+
+        assert_movements(
+            &mut viewer,
+            vec![
+                (Action::MoveRight, 1),
+                (Action::MoveRight, 1),
+                (Action::MoveDown(1), 2),
+                (Action::MoveRight, 3),
+                (Action::MoveLeft, 2),
+                (Action::MoveLeft, 2),
+            ],
+        );
+
+        assert!(viewer.flatjson[2].is_collapsed());
+
+        viewer.focused_row = 10;
+        assert_movements(
+            &mut viewer,
+            vec![
+                // Right on closing brace takes you to previous line
+                (Action::MoveRight, 9),
+                (Action::MoveLeft, 6),
+                (Action::MoveDown(4), 10),
+                // Collapsing while on closing brace takes you to opening brace
+                (Action::MoveLeft, 6),
+            ],
+        );
+
+        assert!(viewer.flatjson[6].is_collapsed());
+
+        assert_movements(
+            &mut viewer,
+            vec![
+                (Action::MoveLeft, 0),
+                (Action::MoveLeft, 0),
+                (Action::MoveDown(1), 0),
+            ],
+        );
+
+        assert!(viewer.flatjson[0].is_collapsed());
+        assert_movements(&mut viewer, vec![(Action::MoveRight, 0)]);
+
+        assert!(viewer.flatjson[0].is_expanded());
+        assert_movements(&mut viewer, vec![(Action::MoveRight, 1)]);
+    }
 
     #[test]
-    fn test_move_up_down_line_mode() {
-        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
-        let mut viewer = JsonViewer::new(fj, Mode::Line);
+    fn test_move_left_right_data_mode() {
+        let fj = parse_top_level_json(DATA_OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Data);
 
         assert_movements(
             &mut viewer,
-            vec![(Action::MoveDown(1), 1), (Action::MoveDown(2), 3)],
+            vec![
+                (Action::MoveRight, 1),
+                (Action::MoveRight, 1),
+                (Action::MoveDown(5), 7),
+                (Action::MoveLeft, 6),
+                (Action::MoveLeft, 6),
+            ],
         );
 
-        viewer.flatjson.collapse(6);
-        viewer.focused_row = 6;
+        assert!(viewer.flatjson[6].is_collapsed());
 
         assert_movements(
             &mut viewer,
             vec![
-                (Action::MoveDown(1), 11),
-                (Action::MoveDown(1), 12),
-                (Action::MoveDown(1), 12),
+                (Action::MoveLeft, 0),
+                (Action::MoveRight, 1),
+                (Action::MoveLeft, 0),
+                (Action::MoveLeft, 0),
             ],
         );
 
+        assert!(viewer.flatjson[0].is_collapsed());
         assert_movements(
             &mut viewer,
-            vec![
-                (Action::MoveUp(2), 6),
-                (Action::MoveUp(1), 5),
-                (Action::MoveUp(5), 0),
-                (Action::MoveUp(2), 0),
-            ],
+            vec![(Action::MoveDown(1), 0), (Action::MoveRight, 0)],
         );
 
-        viewer.flatjson.collapse(0);
+        assert!(viewer.flatjson[0].is_expanded());
+        assert_movements(&mut viewer, vec![(Action::MoveLeft, 0)]);
+    }
+
+    #[test]
+    fn test_move_up_down_until_depth_change_line_mode() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+
         assert_movements(
             &mut viewer,
-            vec![(Action::MoveUp(1), 0), (Action::MoveDown(1), 0)],
+            vec![
+                (Action::MoveDownUntilDepthChange, 1),
+                (Action::MoveDownUntilDepthChange, 2),
+                (Action::MoveDownUntilDepthChange, 3),
+                (Action::MoveDownUntilDepthChange, 5),
+                (Action::MoveDownUntilDepthChange, 6),
+                (Action::MoveDownUntilDepthChange, 7),
+                (Action::MoveDownUntilDepthChange, 10),
+                (Action::MoveDownUntilDepthChange, 12),
+                (Action::MoveDownUntilDepthChange, 12),
+                (Action::MoveUpUntilDepthChange, 10),
+                (Action::MoveUpUntilDepthChange, 7),
+                (Action::MoveUpUntilDepthChange, 6),
+                (Action::MoveUpUntilDepthChange, 5),
+                (Action::MoveUpUntilDepthChange, 3),
+                (Action::MoveUpUntilDepthChange, 2),
+                (Action::MoveUpUntilDepthChange, 1),
+                (Action::MoveUpUntilDepthChange, 0),
+                (Action::MoveUpUntilDepthChange, 0),
+            ],
         );
     }
 
     #[test]
-    fn test_move_up_down_data_mode() {
+    fn test_move_up_down_until_depth_change_data_mode() {
         let fj = parse_top_level_json(DATA_OBJECT.to_owned()).unwrap();
         let mut viewer = JsonViewer::new(fj, Mode::Data);
 
         assert_movements(
             &mut viewer,
             vec![
-                (Action::MoveDown(1), 1),
-                (Action::MoveDown(3), 4),
-                (Action::MoveDown(1), 6),
+                (Action::MoveDownUntilDepthChange, 1),
+                (Action::MoveDownUntilDepthChange, 2),
+                (Action::MoveDownUntilDepthChange, 3),
+                (Action::MoveDownUntilDepthChange, 6),
+                (Action::MoveDownUntilDepthChange, 7),
+                (Action::MoveDownUntilDepthChange, 11),
+                (Action::MoveDownUntilDepthChange, 11),
+                (Action::MoveUpUntilDepthChange, 7),
+                (Action::MoveUpUntilDepthChange, 6),
+                (Action::MoveUpUntilDepthChange, 3),
+                (Action::MoveUpUntilDepthChange, 2),
+                (Action::MoveUpUntilDepthChange, 1),
+                (Action::MoveUpUntilDepthChange, 0),
+                (Action::MoveUpUntilDepthChange, 0),
             ],
         );
+    }
 
-        viewer.flatjson.collapse(6);
+    #[track_caller]
+    fn assert_movements(viewer: &mut JsonViewer, actions_and_focuses: Vec<(Action, Index)>) {
+        for (i, (action, expected_focused_row)) in actions_and_focuses.into_iter().enumerate() {
+            viewer.perform_action(action);
+            assert_eq!(
+                viewer.focused_row,
+                expected_focused_row,
+                "expected row {} to be focused after {} actions (last action: {:?})",
+                expected_focused_row,
+                i + 1,
+                action,
+            );
+        }
+    }
 
-        assert_movements(
+    #[test]
+    fn test_ensure_focused_line_is_visible_in_line_mode() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+        viewer.dimensions.height = 8;
+        viewer.scrolloff_setting = 2;
+
+        viewer.ensure_focused_row_is_visible();
+        assert_eq!(viewer.top_row, 0);
+
+        // Test pushing past bottom
+        assert_window_tracking(
             &mut viewer,
-            vec![(Action::MoveDown(1), 11), (Action::MoveDown(1), 11)],
+            vec![
+                (Action::MoveDown(1), 0, 1),
+                (Action::MoveDown(5), 1, 6),
+                (Action::MoveDown(1), 2, 7),
+            ],
         );
 
-        assert_movements(
+        // Test pushing past top
+        assert_window_tracking(
             &mut viewer,
             vec![
-                (Action::MoveUp(1), 6),
-                (Action::MoveUp(3), 2),
-                (Action::MoveUp(1), 1),
-                (Action::MoveUp(1), 0),
-                (Action::MoveUp(1), 0),
+                (Action::MoveUp(1), 2, 6),
+                (Action::MoveUp(3), 1, 3),
+                (Action::MoveUp(1), 0, 2),
+                // Top is now top of file
+                (Action::MoveUp(1), 0, 1),
+            ],
+        );
+
+        // Test pushing past bottom with scrolloff == 0
+        viewer.scrolloff_setting = 0;
+        assert_window_tracking(
+            &mut viewer,
+            vec![
+                (Action::MoveDown(6), 0, 7),
+                (Action::MoveDown(1), 1, 8),
+                (Action::MoveDown(4), 5, 12),
+                (Action::MoveDown(1), 5, 12),
+            ],
+        );
+
+        // Test pushing past top with scrolloff == 0
+        assert_window_tracking(
+            &mut viewer,
+            vec![
+                (Action::MoveUp(7), 5, 5),
+                (Action::MoveUp(1), 4, 4),
+                (Action::MoveUp(5), 0, 0),
+            ],
+        );
+
+        viewer.top_row = 0;
+        viewer.focused_row = 1;
+        viewer.scrolloff_setting = 2;
+
+        // Test pushing past bottom at end of file
+        assert_window_tracking(
+            &mut viewer,
+            vec![
+                // Move to bottom of file
+                (Action::MoveDown(9), 5, 10),
+                // Push past bottom
+                (Action::MoveDown(1), 5, 11),
+                (Action::MoveDown(1), 5, 12),
+            ],
+        );
+
+        // Put bottom of file on top of screen
+        viewer.top_row = 8;
+        viewer.focused_row = 10;
+
+        assert_window_tracking(
+            &mut viewer,
+            vec![
+                (Action::MoveDown(1), 8, 11),
+                (Action::MoveDown(1), 8, 12),
+                (Action::MoveUp(2), 8, 10),
+                (Action::MoveUp(1), 7, 9),
+            ],
+        );
+
+        viewer.top_row = 0;
+        viewer.focused_row = 0;
+        viewer.dimensions.height = 6;
+        viewer.flatjson.collapse(2);
+
+        // Test with collapsed items
+        assert_window_tracking(
+            &mut viewer,
+            vec![
+                (Action::MoveDown(3), 0, 6),
+                (Action::MoveDown(1), 1, 7),
+                (Action::MoveDown(1), 2, 8),
+                (Action::MoveDown(1), 6, 9),
+                // Back up now
+                (Action::MoveUp(2), 2, 7),
+                (Action::MoveUp(1), 1, 6),
+                (Action::MoveUp(1), 0, 2),
             ],
         );
     }
 
     #[test]
-    fn test_move_left_right_line_mode() {
-        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
-        let mut viewer = JsonViewer::new(fj, Mode::Line);
+    fn test_ensure_focused_line_is_visible_in_data_mode() {
+        let fj = parse_top_level_json(DATA_OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Data);
+        viewer.dimensions.height = 7;
+        viewer.scrolloff_setting = 2;
 
-        assert_movements(
+        viewer.ensure_focused_row_is_visible();
+        assert_eq!(viewer.top_row, 0);
+
+        // Test pushing past bottom
+        assert_window_tracking(
             &mut viewer,
             vec![
-                (Action::MoveRight, 1),
-                (Action::MoveRight, 1),
-                (Action::MoveDown(1), 2),
-                (Action::MoveRight, 3),
-                (Action::MoveLeft, 2),
-                (Action::MoveLeft, 2),
+                (Action::MoveDown(1), 0, 1),
+                (Action::MoveDown(4), 1, 6),
+                (Action::MoveDown(1), 2, 7),
             ],
         );
 
-        assert!(viewer.flatjson[2].is_collapsed());
+        // Test pushing past top
+        assert_window_tracking(
+            &mut viewer,
+            vec![
+                (Action::MoveUp(1), 2, 6),
+                (Action::MoveUp(2), 1, 3),
+                (Action::MoveUp(1), 0, 2),
+                // Top is now top of file
+                (Action::MoveUp(1), 0, 1),
+            ],
+        );
 
-        viewer.focused_row = 10;
-        assert_movements(
+        // Test pushing past bottom at end of file
+        assert_window_tracking(
             &mut viewer,
             vec![
-                // Right on closing brace takes you to previous line
-                (Action::MoveRight, 9),
-                (Action::MoveLeft, 6),
-                (Action::MoveDown(4), 10),
-                // Collapsing while on closing brace takes you to opening brace
-                (Action::MoveLeft, 6),
+                // Move to bottom of file
+                (Action::MoveDown(6), 3, 8),
+                // Push past bottom
+                (Action::MoveDown(1), 3, 9),
+                (Action::MoveDown(1), 3, 11),
             ],
         );
 
-        assert!(viewer.flatjson[6].is_collapsed());
+        // Put bottom of file on top of screen
+        viewer.top_row = 6;
+        viewer.focused_row = 8;
 
-        assert_movements(
+        assert_window_tracking(
             &mut viewer,
             vec![
-                (Action::MoveLeft, 0),
-                (Action::MoveLeft, 0),
-                (Action::MoveDown(1), 0),
+                (Action::MoveDown(1), 6, 9),
+                (Action::MoveDown(1), 6, 11),
+                (Action::MoveUp(2), 6, 8),
+                (Action::MoveUp(1), 4, 7),
             ],
         );
 
-        assert!(viewer.flatjson[0].is_collapsed());
-        assert_movements(&mut viewer, vec![(Action::MoveRight, 0)]);
-
-        assert!(viewer.flatjson[0].is_expanded());
-        assert_movements(&mut viewer, vec![(Action::MoveRight, 1)]);
-    }
-
-    #[test]
-    fn test_move_left_right_data_mode() {
-        let fj = parse_top_level_json(DATA_OBJECT.to_owned()).unwrap();
-        let mut viewer = JsonViewer::new(fj, Mode::Data);
+        viewer.top_row = 0;
+        viewer.focused_row = 0;
+        viewer.dimensions.height = 5;
+        viewer.flatjson.collapse(2);
 
-        assert_movements(
+        // Test with collapsed items
+        assert_window_tracking(
             &mut viewer,
             vec![
-                (Action::MoveRight, 1),
-                (Action::MoveRight, 1),
-                (Action::MoveDown(5), 7),
-                (Action::MoveLeft, 6),
-                (Action::MoveLeft, 6),
+                (Action::MoveDown(2), 0, 2),
+                (Action::MoveDown(1), 1, 6),
+                (Action::MoveDown(1), 2, 7),
+                (Action::MoveDown(1), 6, 8),
+                // Back up now
+                (Action::MoveUp(1), 2, 7),
+                (Action::MoveUp(1), 1, 6),
+                (Action::MoveUp(1), 0, 2),
             ],
         );
+    }
 
-        assert!(viewer.flatjson[6].is_collapsed());
+    const TALL_OBJECT: &str = "[1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18,19,20]";
 
-        assert_movements(
+    #[test]
+    fn test_ensure_focused_line_is_visible_centers_focus_line_after_big_jump() {
+        let fj = parse_top_level_json(TALL_OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+        viewer.dimensions.height = 9;
+        viewer.scrolloff_setting = 2;
+
+        assert_window_tracking(
             &mut viewer,
             vec![
-                (Action::MoveLeft, 0),
-                (Action::MoveRight, 1),
-                (Action::MoveLeft, 0),
-                (Action::MoveLeft, 0),
+                (Action::MoveDown(8), 2, 8),
+                (Action::FocusTop, 0, 0),
+                (Action::MoveDown(9), 3, 9),
+                (Action::FocusTop, 0, 0),
+                (Action::MoveDown(12), 6, 12),
+                (Action::FocusTop, 0, 0),
+                // Jumped far, so focused line will be 1/3 from top.
+                (Action::MoveDown(13), 11, 13),
             ],
         );
 
-        assert!(viewer.flatjson[0].is_collapsed());
-        assert_movements(
+        // Have to be careful to still obey top scrolloff setting though.
+        viewer.scrolloff_setting = 3;
+        assert_window_tracking(
             &mut viewer,
-            vec![(Action::MoveDown(1), 0), (Action::MoveRight, 0)],
+            vec![(Action::FocusTop, 0, 0), (Action::MoveDown(13), 10, 13)],
         );
-
-        assert!(viewer.flatjson[0].is_expanded());
-        assert_movements(&mut viewer, vec![(Action::MoveLeft, 0)]);
     }
 
     #[test]
-    fn test_move_up_down_until_depth_change_line_mode() {
+    fn test_scroll() {
         let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
         let mut viewer = JsonViewer::new(fj, Mode::Line);
+        viewer.dimensions.height = 8;
+        viewer.scrolloff_setting = 2;
 
-        assert_movements(
+        assert_window_tracking(
             &mut viewer,
             vec![
-                (Action::MoveDownUntilDepthChange, 1),
-                (Action::MoveDownUntilDepthChange, 2),
-                (Action::MoveDownUntilDepthChange, 3),
-                (Action::MoveDownUntilDepthChange, 5),
-                (Action::MoveDownUntilDepthChange, 6),
-                (Action::MoveDownUntilDepthChange, 7),
-                (Action::MoveDownUntilDepthChange, 10),
-                (Action::MoveDownUntilDepthChange, 12),
-                (Action::MoveDownUntilDepthChange, 12),
-                (Action::MoveUpUntilDepthChange, 10),
-                (Action::MoveUpUntilDepthChange, 7),
-                (Action::MoveUpUntilDepthChange, 6),
-                (Action::MoveUpUntilDepthChange, 5),
-                (Action::MoveUpUntilDepthChange, 3),
-                (Action::MoveUpUntilDepthChange, 2),
-                (Action::MoveUpUntilDepthChange, 1),
-                (Action::MoveUpUntilDepthChange, 0),
-                (Action::MoveUpUntilDepthChange, 0),
+                (Action::ScrollDown(1), 1, 3),
+                (Action::ScrollDown(1), 2, 4),
+                (Action::ScrollDown(3), 5, 7),
+                // Can scroll so end of file is in middle of screen
+                (Action::ScrollDown(1), 6, 8),
+                (Action::ScrollDown(4), 10, 12),
+                // Can scoll past scrolloff padding
+                (Action::ScrollDown(1), 11, 12),
+                (Action::ScrollDown(1), 12, 12),
+                // Can't scroll past last line
+                (Action::ScrollDown(1), 12, 12),
+                // Can scroll one up
+                (Action::ScrollUp(1), 11, 12),
+                (Action::ScrollDown(1), 12, 12),
+                // But moving up activates scrolloff
+                (Action::MoveUp(1), 9, 11),
             ],
         );
-    }
 
-    #[test]
-    fn test_move_up_down_until_depth_change_data_mode() {
-        let fj = parse_top_level_json(DATA_OBJECT.to_owned()).unwrap();
-        let mut viewer = JsonViewer::new(fj, Mode::Data);
+        viewer.top_row = 12;
+        viewer.focused_row = 12;
 
-        assert_movements(
+        assert_window_tracking(
             &mut viewer,
             vec![
-                (Action::MoveDownUntilDepthChange, 1),
-                (Action::MoveDownUntilDepthChange, 2),
-                (Action::MoveDownUntilDepthChange, 3),
-                (Action::MoveDownUntilDepthChange, 6),
-                (Action::MoveDownUntilDepthChange, 7),
-                (Action::MoveDownUntilDepthChange, 11),
-                (Action::MoveDownUntilDepthChange, 11),
-                (Action::MoveUpUntilDepthChange, 7),
-                (Action::MoveUpUntilDepthChange, 6),
-                (Action::MoveUpUntilDepthChange, 3),
-                (Action::MoveUpUntilDepthChange, 2),
-                (Action::MoveUpUntilDepthChange, 1),
-                (Action::MoveUpUntilDepthChange, 0),
-                (Action::MoveUpUntilDepthChange, 0),
+                (Action::ScrollUp(1), 11, 12),
+                (Action::ScrollUp(1), 10, 12),
+                (Action::ScrollUp(4), 6, 11),
+                (Action::ScrollUp(1), 5, 10),
+                // Can't scroll up past top of file
+                (Action::ScrollUp(6), 0, 5),
             ],
         );
     }
 
-    #[track_caller]
-    fn assert_movements(viewer: &mut JsonViewer, actions_and_focuses: Vec<(Action, Index)>) {
-        for (i, (action, expected_focused_row)) in actions_and_focuses.into_iter().enumerate() {
-            viewer.perform_action(action);
-            assert_eq!(
-                viewer.focused_row,
-                expected_focused_row,
-                "expected row {} to be focused after {} actions (last action: {:?})",
-                expected_focused_row,
-                i + 1,
-                action,
-            );
-        }
-    }
-
     #[test]
-    fn test_ensure_focused_line_is_visible_in_line_mode() {
+    fn test_page() {
         let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
         let mut viewer = JsonViewer::new(fj, Mode::Line);
         viewer.dimensions.height = 8;
         viewer.scrolloff_setting = 2;
 
-        viewer.ensure_focused_row_is_visible();
-        assert_eq!(viewer.top_row, 0);
-
-        // Test pushing past bottom
         assert_window_tracking(
             &mut viewer,
             vec![
-                (Action::MoveDown(1), 0, 1),
-                (Action::MoveDown(5), 1, 6),
-                (Action::MoveDown(1), 2, 7),
+                // Stops once the end of the file is at the bottom.
+                (Action::PageDown(8), 5, 7),
+                // And then focuses the last row.
+                (Action::PageDown(8), 5, 12),
+                (Action::PageUp(8), 0, 5),
+                // Likewise, focuses the first row at the top.
+                (Action::PageUp(8), 0, 0),
+                (Action::PageDown(3), 3, 5),
+                (Action::PageUp(2), 1, 5),
             ],
         );
 
-        // Test pushing past top
+        // Doesn't scroll back up if lines past the end are already shown.
+        viewer.top_row = 10;
+        viewer.focused_row = 12;
+        assert_window_tracking(&mut viewer, vec![(Action::PageDown(8), 10, 12)]);
+    }
+
+    #[test]
+    fn test_move_focused_line() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+        viewer.dimensions.height = 8;
+        viewer.scrolloff_setting = 2;
+        viewer.focused_row = 6;
+
         assert_window_tracking(
             &mut viewer,
             vec![
-                (Action::MoveUp(1), 2, 6),
+                (Action::MoveFocusedLineToTop, 4, 6),
+                (Action::MoveFocusedLineToCenter, 2, 6),
+                (Action::MoveFocusedLineToBottom, 1, 6),
+                // Near the start of the file, the focused line can't be
+                // moved all the way to the bottom.
                 (Action::MoveUp(3), 1, 3),
-                (Action::MoveUp(1), 0, 2),
-                // Top is now top of file
-                (Action::MoveUp(1), 0, 1),
+                (Action::MoveFocusedLineToBottom, 0, 3),
+                (Action::FocusBottom, 5, 12),
+                (Action::MoveFocusedLineToTop, 10, 12),
             ],
         );
 
-        // Test pushing past bottom with scrolloff == 0
-        viewer.scrolloff_setting = 0;
+        // When the whole file fits on the screen, it isn't scrolled.
+        viewer.dimensions.height = 13;
+        viewer.top_row = 0;
         assert_window_tracking(
             &mut viewer,
             vec![
-                (Action::MoveDown(6), 0, 7),
-                (Action::MoveDown(1), 1, 8),
-                (Action::MoveDown(4), 5, 12),
-                (Action::MoveDown(1), 5, 12),
+                (Action::MoveFocusedLineToTop, 0, 12),
+                (Action::MoveFocusedLineToCenter, 0, 12),
+                (Action::MoveFocusedLineToBottom, 0, 12),
             ],
         );
 
-        // Test pushing past top with scrolloff == 0
+        // But it is as soon as it doesn't.
+        viewer.dimensions.height = 12;
+        assert_window_tracking(&mut viewer, vec![(Action::MoveFocusedLineToTop, 10, 12)]);
+    }
+
+    #[test]
+    fn test_jump() {
+        const TALL_OBJECT: &str = r#"{
+            "1": [2],
+            "4": [5],
+            "7": [8],
+            "10": [11],
+            "13": [14],
+            "16": [17],
+        }"#; // 19
+
+        let fj = parse_top_level_json(TALL_OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+        viewer.dimensions.height = 5;
+        viewer.scrolloff_setting = 0;
+        viewer.focused_row = 3;
+
         assert_window_tracking(
             &mut viewer,
             vec![
-                (Action::MoveUp(7), 5, 5),
-                (Action::MoveUp(1), 4, 4),
-                (Action::MoveUp(5), 0, 0),
+                // Moves by half-screen rounded down.
+                (Action::JumpDown(None), 2, 5),
+                (Action::JumpDown(None), 4, 7),
+                (Action::JumpUp(None), 2, 5),
+                // Count moves by that many lines
+                (Action::JumpDown(Some(4)), 6, 9),
+                // And count is remembered
+                (Action::JumpDown(None), 10, 13),
+                // ... by both up and down
+                (Action::JumpUp(None), 6, 9),
             ],
         );
 
-        viewer.top_row = 0;
-        viewer.focused_row = 1;
-        viewer.scrolloff_setting = 2;
+        // Prioritize keeping focused line in same place, but once we're
+        // at the top or bottom of the file, we will move it.
+        viewer.dimensions.height = 8;
+        viewer.top_row = 3;
+        viewer.focused_row = 7;
+        viewer.scrolloff_setting = 3;
 
-        // Test pushing past bottom at end of file
         assert_window_tracking(
             &mut viewer,
             vec![
-                // Move to bottom of file
-                (Action::MoveDown(9), 5, 10),
-                // Push past bottom
-                (Action::MoveDown(1), 5, 11),
-                (Action::MoveDown(1), 5, 12),
+                (Action::JumpUp(Some(2)), 1, 5),
+                (Action::JumpUp(None), 0, 4),
+                (Action::JumpUp(None), 0, 2),
+                (Action::JumpUp(None), 0, 0),
+                // Scrolloff is ignored.
+                (Action::JumpDown(None), 2, 2),
             ],
         );
 
-        // Put bottom of file on top of screen
-        viewer.top_row = 8;
-        viewer.focused_row = 10;
+        viewer.dimensions.height = 8;
+        viewer.top_row = 9;
+        viewer.focused_row = 11;
 
         assert_window_tracking(
             &mut viewer,
             vec![
-                (Action::MoveDown(1), 8, 11),
-                (Action::MoveDown(1), 8, 12),
-                (Action::MoveUp(2), 8, 10),
-                (Action::MoveUp(1), 7, 9),
+                (Action::JumpDown(Some(2)), 11, 13),
+                (Action::JumpDown(None), 12, 14),
+                (Action::JumpDown(None), 12, 16),
+                (Action::JumpDown(None), 12, 18),
+                (Action::JumpDown(None), 12, 19),
+                // Scrolloff is ignored.
+                (Action::JumpUp(None), 10, 17),
             ],
         );
 
-        viewer.top_row = 0;
-        viewer.focused_row = 0;
-        viewer.dimensions.height = 6;
-        viewer.flatjson.collapse(2);
+        // We won't move past the end of the file (tested above),
+        // unless we're already past it.
+        viewer.top_row = 14;
+        viewer.focused_row = 15;
 
-        // Test with collapsed items
         assert_window_tracking(
             &mut viewer,
             vec![
-                (Action::MoveDown(3), 0, 6),
-                (Action::MoveDown(1), 1, 7),
-                (Action::MoveDown(1), 2, 8),
-                (Action::MoveDown(1), 6, 9),
-                // Back up now
-                (Action::MoveUp(2), 2, 7),
-                (Action::MoveUp(1), 1, 6),
-                (Action::MoveUp(1), 0, 2),
+                (Action::JumpDown(Some(2)), 14, 17),
+                (Action::JumpDown(None), 14, 19),
             ],
         );
     }
 
     #[test]
-    fn test_ensure_focused_line_is_visible_in_data_mode() {
-        let fj = parse_top_level_json(DATA_OBJECT.to_owned()).unwrap();
-        let mut viewer = JsonViewer::new(fj, Mode::Data);
-        viewer.dimensions.height = 7;
-        viewer.scrolloff_setting = 2;
-
-        viewer.ensure_focused_row_is_visible();
-        assert_eq!(viewer.top_row, 0);
+    fn test_move_focus() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+        viewer.dimensions.height = 5;
+        viewer.scrolloff_setting = 1;
 
-        // Test pushing past bottom
         assert_window_tracking(
             &mut viewer,
             vec![
-                (Action::MoveDown(1), 0, 1),
-                (Action::MoveDown(4), 1, 6),
-                (Action::MoveDown(1), 2, 7),
+                (Action::MoveFocusedLineToTop, 0, 0),
+                (Action::MoveFocusedLineToCenter, 0, 0),
+                (Action::MoveFocusedLineToBottom, 0, 0),
             ],
         );
 
-        // Test pushing past top
+        viewer.top_row = 10;
+        viewer.focused_row = 12;
+
         assert_window_tracking(
             &mut viewer,
             vec![
-                (Action::MoveUp(1), 2, 6),
-                (Action::MoveUp(2), 1, 3),
-                (Action::MoveUp(1), 0, 2),
-                // Top is now top of file
-                (Action::MoveUp(1), 0, 1),
+                (Action::MoveFocusedLineToTop, 11, 12),
+                (Action::MoveFocusedLineToCenter, 10, 12),
+                (Action::MoveFocusedLineToBottom, 9, 12),
             ],
         );
 
-        // Test pushing past bottom at end of file
+        viewer.top_row = 4;
+        viewer.focused_row = 6;
+        viewer.dimensions.height = 7;
+
         assert_window_tracking(
             &mut viewer,
             vec![
-                // Move to bottom of file
-                (Action::MoveDown(6), 3, 8),
-                // Push past bottom
-                (Action::MoveDown(1), 3, 9),
-                (Action::MoveDown(1), 3, 11),
+                (Action::MoveFocusedLineToTop, 5, 6),
+                (Action::MoveFocusedLineToCenter, 3, 6),
+                (Action::MoveFocusedLineToBottom, 1, 6),
             ],
         );
 
-        // Put bottom of file on top of screen
-        viewer.top_row = 6;
-        viewer.focused_row = 8;
+        viewer.dimensions.height = 8;
 
         assert_window_tracking(
             &mut viewer,
             vec![
-                (Action::MoveDown(1), 6, 9),
-                (Action::MoveDown(1), 6, 11),
-                (Action::MoveUp(2), 6, 8),
-                (Action::MoveUp(1), 4, 7),
+                (Action::MoveFocusedLineToTop, 5, 6),
+                (Action::MoveFocusedLineToCenter, 2, 6),
+                (Action::MoveFocusedLineToBottom, 0, 6),
             ],
         );
+    }
+
+    #[test]
+    fn test_reveal_and_move_to() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
 
-        viewer.top_row = 0;
-        viewer.focused_row = 0;
-        viewer.dimensions.height = 5;
         viewer.flatjson.collapse(2);
+        viewer.flatjson.collapse(0);
+        viewer.perform_action(Action::RevealAndMoveTo(4));
+        assert_eq!(viewer.focused_row, 4);
+        assert!(viewer.flatjson[0].is_expanded());
+        assert!(viewer.flatjson[2].is_expanded());
 
-        // Test with collapsed items
-        assert_window_tracking(
-            &mut viewer,
-            vec![
-                (Action::MoveDown(2), 0, 2),
-                (Action::MoveDown(1), 1, 6),
-                (Action::MoveDown(1), 2, 7),
-                (Action::MoveDown(1), 6, 8),
-                // Back up now
-                (Action::MoveUp(1), 2, 7),
-                (Action::MoveUp(1), 1, 6),
-                (Action::MoveUp(1), 0, 2),
-            ],
-        );
+        // Closing rows of collapsed containers get expanded in line mode...
+        viewer.flatjson.collapse(6);
+        viewer.perform_action(Action::RevealAndMoveTo(10));
+        assert_eq!(viewer.focused_row, 10);
+        assert!(viewer.flatjson[6].is_expanded());
+
+        // ...and the opening row is focused instead in data mode.
+        viewer.mode = Mode::Data;
+        viewer.flatjson.collapse(6);
+        viewer.perform_action(Action::RevealAndMoveTo(10));
+        assert_eq!(viewer.focused_row, 6);
+        assert!(viewer.flatjson[6].is_collapsed());
     }
 
-    const TALL_OBJECT: &str = "[1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18,19,20]";
+    #[test]
+    fn test_collapse_and_expand_to_depth() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+
+        viewer.focused_row = 8;
+        viewer.perform_action(Action::CollapseToDepth(1));
+        assert!(viewer.flatjson[0].is_expanded());
+        assert!(viewer.flatjson[2].is_collapsed());
+        assert!(viewer.flatjson[6].is_collapsed());
+        assert_eq!(viewer.focused_row, 6);
+
+        viewer.perform_action(Action::ExpandToDepth(1));
+        assert!(viewer.flatjson[6].is_collapsed());
+
+        viewer.perform_action(Action::ExpandToDepth(2));
+        assert!(viewer.flatjson[2].is_expanded());
+        assert!(viewer.flatjson[6].is_expanded());
+
+        // Focus moves off of closing rows that get hidden.
+        viewer.focused_row = 10;
+        viewer.perform_action(Action::CollapseToDepth(0));
+        assert!(viewer.flatjson[0].is_collapsed());
+        assert_eq!(viewer.focused_row, 0);
+
+        // Shallower containers are expanded.
+        viewer.perform_action(Action::CollapseToDepth(1));
+        assert!(viewer.flatjson[0].is_expanded());
+        assert!(viewer.flatjson[2].is_collapsed());
+    }
 
     #[test]
-    fn test_ensure_focused_line_is_visible_centers_focus_line_after_big_jump() {
-        let fj = parse_top_level_json(TALL_OBJECT.to_owned()).unwrap();
+    fn test_sibling_collapsed_states() {
+        const SIBLINGS: &str = r#"[
+            { "a": 1 },
+            [2],
+            3,
+            { "c": [4] }
+        ]"#;
+
+        let fj = parse_top_level_json(SIBLINGS.to_owned()).unwrap();
         let mut viewer = JsonViewer::new(fj, Mode::Line);
-        viewer.dimensions.height = 9;
-        viewer.scrolloff_setting = 2;
 
-        assert_window_tracking(
-            &mut viewer,
-            vec![
-                (Action::MoveDown(8), 2, 8),
-                (Action::FocusTop, 0, 0),
-                (Action::MoveDown(9), 3, 9),
-                (Action::FocusTop, 0, 0),
-                (Action::MoveDown(12), 6, 12),
-                (Action::FocusTop, 0, 0),
-                // Jumped far, so focused line will be 1/3 from top.
-                (Action::MoveDown(13), 11, 13),
-            ],
-        );
+        // Primitives have no state to match.
+        viewer.focused_row = 7;
+        viewer.perform_action(Action::MatchSiblingsToCollapsedState);
+        assert!(viewer.flatjson[1].is_expanded());
 
-        // Have to be careful to still obey top scrolloff setting though.
-        viewer.scrolloff_setting = 3;
-        assert_window_tracking(
-            &mut viewer,
-            vec![(Action::FocusTop, 0, 0), (Action::MoveDown(13), 10, 13)],
+        viewer.flatjson.collapse(1);
+        viewer.focused_row = 1;
+        viewer.perform_action(Action::MatchSiblingsToCollapsedState);
+        assert!(viewer.flatjson[1].is_collapsed());
+        assert!(viewer.flatjson[4].is_collapsed());
+        assert!(viewer.flatjson[8].is_collapsed());
+        assert!(viewer.flatjson[9].is_expanded());
+
+        viewer.flatjson.expand(4);
+        viewer.focused_row = 6;
+        viewer.perform_action(Action::MatchSiblingsToCollapsedState);
+        assert_eq!(viewer.focused_row, 4);
+        assert!(viewer.flatjson[1].is_expanded());
+        assert!(viewer.flatjson[8].is_expanded());
+
+        viewer.focused_row = 8;
+        viewer.perform_action(Action::CollapseSiblingsExceptFocused);
+        assert!(viewer.flatjson[1].is_collapsed());
+        assert!(viewer.flatjson[4].is_collapsed());
+        assert!(viewer.flatjson[8].is_expanded());
+
+        viewer.flatjson.collapse(8);
+        viewer.perform_action(Action::CollapseSiblingsExceptFocused);
+        assert!(viewer.flatjson[8].is_expanded());
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("metadata", "metadata"));
+        assert!(!glob_matches("metadata", "metadata2"));
+        assert!(glob_matches("meta*", "metadata"));
+        assert!(glob_matches("meta*", "meta"));
+        assert!(glob_matches("*data", "metadata"));
+        assert!(glob_matches("m*t*a", "metadata"));
+        assert!(glob_matches("?eta", "beta"));
+        assert!(!glob_matches("?eta", "eta"));
+        assert!(glob_matches("*", ""));
+        assert!(!glob_matches("a*b", "acbd"));
+    }
+
+    #[test]
+    fn test_set_collapsed_state_by_key() {
+        const OBJECTS: &str = r#"[
+            { "metadata": { "name": "a" }, "spec": [1] },
+            { "metadata": { "name": "b" }, "meta": 1 }
+        ]"#;
+
+        let fj = parse_top_level_json(OBJECTS.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+
+        viewer.focused_row = 3;
+        assert_eq!(viewer.set_collapsed_state_by_key("metadata", true), 2);
+        assert!(viewer.flatjson[2].is_collapsed());
+        assert!(viewer.flatjson[10].is_collapsed());
+        assert!(viewer.flatjson[5].is_expanded());
+        assert_eq!(viewer.focused_row, 2);
+
+        assert_eq!(viewer.set_collapsed_state_by_key("meta*", false), 2);
+        assert!(viewer.flatjson[2].is_expanded());
+        assert!(viewer.flatjson[10].is_expanded());
+
+        assert_eq!(viewer.set_collapsed_state_by_key("missing", true), 0);
+    }
+
+    #[test]
+    fn test_toggle_sorted_keys() {
+        const UNSORTED: &str = r#"{ "b": { "d": 1, "c": 2 }, "a": 3 }"#;
+
+        let fj = parse_top_level_json(UNSORTED.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+
+        viewer.focused_row = 2;
+        viewer.marks.insert('a', 5);
+        viewer.perform_action(Action::ToggleSortedKeys);
+        assert!(viewer.sorted_keys());
+        // { "a": 3, "b": { "c": 2, "d": 1 } }
+        assert_eq!(viewer.focused_row, 4);
+        assert_eq!(viewer.get_mark('a'), Some(1));
+
+        // Values are copied from the original input.
+        let (original, index) = viewer.original_flatjson_and_index(2);
+        assert_eq!(index, 1);
+        assert_eq!(
+            original.pretty_printed_value(index).unwrap(),
+            "{\n  \"d\": 1,\n  \"c\": 2\n}\n"
         );
+
+        viewer.flatjson.collapse(2);
+        viewer.perform_action(Action::ToggleSortedKeys);
+        assert!(!viewer.sorted_keys());
+        // The focused row was hidden when its parent was collapsed.
+        assert_eq!(viewer.focused_row, 1);
+        assert_eq!(viewer.get_mark('a'), Some(5));
+        assert!(viewer.flatjson[1].is_collapsed());
+        assert_eq!(viewer.original_flatjson_and_index(2).1, 2);
     }
 
     #[test]
-    fn test_scroll() {
+    fn test_filter_rows() {
         let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
         let mut viewer = JsonViewer::new(fj, Mode::Line);
-        viewer.dimensions.height = 8;
-        viewer.scrolloff_setting = 2;
 
-        assert_window_tracking(
-            &mut viewer,
-            vec![
-                (Action::ScrollDown(1), 1, 3),
-                (Action::ScrollDown(1), 2, 4),
-                (Action::ScrollDown(3), 5, 7),
-                // Can scroll so end of file is in middle of screen
-                (Action::ScrollDown(1), 6, 8),
-                (Action::ScrollDown(4), 10, 12),
-                // Can scoll past scrolloff padding
-                (Action::ScrollDown(1), 11, 12),
-                (Action::ScrollDown(1), 12, 12),
-                // Can't scroll past last line
-                (Action::ScrollDown(1), 12, 12),
-                // Can scroll one up
-                (Action::ScrollUp(1), 11, 12),
-                (Action::ScrollDown(1), 12, 12),
-                // But moving up activates scrolloff
-                (Action::MoveUp(1), 9, 11),
-            ],
-        );
+        viewer.focused_row = 11;
+        viewer.marks.insert('a', 1);
+        viewer.marks.insert('b', 8);
+        viewer.flatjson.collapse(6);
 
-        viewer.top_row = 12;
-        viewer.focused_row = 12;
+        // Filter to "4" and true.
+        viewer.filter_rows(&[4, 8]);
+        assert!(viewer.filtered());
+        // { "2": [ "4" ], "6": { "8": true } }
+        assert_eq!(viewer.flatjson.0.len(), 8);
+        // There are no matches after the focused row, so the first match
+        // is focused.
+        assert_eq!(viewer.focused_row, 2);
+        // Matches aren't hidden inside collapsed containers.
+        assert!(viewer.flatjson[4].is_expanded());
+        assert_eq!(viewer.get_mark('a'), None);
+        assert_eq!(viewer.get_mark('b'), Some(5));
+        assert_eq!(viewer.original_flatjson_and_index(5).1, 8);
+
+        viewer.flatjson.collapse(1);
+        viewer.clear_filter();
+        assert!(!viewer.filtered());
+        assert_eq!(viewer.flatjson.0.len(), 13);
+        // The focused row was hidden when its parent was collapsed.
+        assert_eq!(viewer.focused_row, 2);
+        assert!(viewer.flatjson[2].is_collapsed());
+        assert!(viewer.flatjson[6].is_expanded());
+        assert_eq!(viewer.get_mark('a'), Some(1));
+        assert_eq!(viewer.get_mark('b'), Some(8));
+    }
+
+    #[test]
+    fn test_hide_values() {
+        // 0   1          2      3          4    5         6       7
+        // { "a": null, "b": { "c": null }, "d": [], "e": 1 }
+        const JSON: &str = r#"{"a":null,"b":{"c":null},"d":[],"e":1}"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Data);
+        viewer.dimensions.height = 10;
+
+        viewer.focused_row = 1;
+        assert!(viewer.hide_values(HiddenValues::Nulls));
+        assert_eq!(viewer.hidden_values(), HiddenValues::Nulls);
+        assert_eq!(r#"{ "b": {}, "d": [], "e": 1 }"#, viewer.flatjson.1);
+        // The focused row was hidden, so the next one is focused.
+        assert_eq!(viewer.focused_row, 1);
+        assert_eq!(viewer.original_flatjson_and_index(1).1, 2);
+
+        viewer.clear_filter();
+        assert_eq!(viewer.hidden_values(), HiddenValues::Nothing);
+        assert_eq!(viewer.flatjson.0.len(), 8);
+        assert_eq!(viewer.focused_row, 2);
+
+        viewer.focused_row = 5;
+        assert!(viewer.hide_values(HiddenValues::NullsAndEmpty));
+        assert_eq!(r#"{ "e": 1 }"#, viewer.flatjson.1);
+        assert_eq!(viewer.focused_row, 1);
+        viewer.clear_filter();
+
+        // There's nothing to hide.
+        let fj = parse_top_level_json(r#"[null, {}]"#.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Data);
+        assert!(!viewer.hide_values(HiddenValues::NullsAndEmpty));
+        assert!(!viewer.filtered());
+    }
+
+    #[test]
+    fn test_restore_state_from() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut old = JsonViewer::new(fj, Mode::Line);
+        old.dimensions.height = 20;
+        old.flatjson.collapse(2);
+        old.focused_row = 8;
+        old.marks.insert('a', 10);
+        old.marks.insert('b', 4);
+
+        // "1" and "4" are gone, and "0" and "5" have been added.
+        let fj = parse_top_level_json(
+            r#"{
+                "0": 0,
+                "2": [3],
+                "6": { "5": 5, "7": null, "8": false, "9": 9 },
+                "11": 11
+            }"#
+            .to_owned(),
+        )
+        .unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Data);
+        viewer.restore_state_from(&old);
 
-        assert_window_tracking(
-            &mut viewer,
-            vec![
-                (Action::ScrollUp(1), 11, 12),
-                (Action::ScrollUp(1), 10, 12),
-                (Action::ScrollUp(4), 6, 11),
-                (Action::ScrollUp(1), 5, 10),
-                // Can't scroll up past top of file
-                (Action::ScrollUp(6), 0, 5),
-            ],
-        );
+        assert_eq!(viewer.mode, Mode::Line);
+        assert!(viewer.flatjson[2].is_collapsed());
+        assert!(viewer.flatjson[5].is_expanded());
+        assert_eq!(viewer.focused_row, 8);
+        // The focused row is still the sixth line on the screen.
+        assert_eq!(viewer.top_row, 1);
+        assert_eq!(viewer.get_mark('a'), Some(10));
+        assert_eq!(viewer.get_mark('b'), None);
+
+        // If the focused row no longer exists, its parent is focused.
+        let fj = parse_top_level_json(r#"{ "6": { "7": null } }"#.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Data);
+        viewer.restore_state_from(&old);
+        assert_eq!(viewer.focused_row, 1);
     }
 
     #[test]
-    fn test_jump() {
-        const TALL_OBJECT: &str = r#"{
-            "1": [2],
-            "4": [5],
-            "7": [8],
-            "10": [11],
-            "13": [14],
-            "16": [17],
-        }"#; // 19
+    fn test_toggle_decoded_string() {
+        const JSON: &str = r#"{ "b": "{\"y\": 1, \"x\": \"[2]\"}", "a": 3, "c": "{" }"#;
 
-        let fj = parse_top_level_json(TALL_OBJECT.to_owned()).unwrap();
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
         let mut viewer = JsonViewer::new(fj, Mode::Line);
-        viewer.dimensions.height = 5;
-        viewer.scrolloff_setting = 0;
-        viewer.focused_row = 3;
+        viewer.dimensions.height = 20;
 
-        assert_window_tracking(
-            &mut viewer,
-            vec![
-                // Moves by half-screen rounded down.
-                (Action::JumpDown(None), 2, 5),
-                (Action::JumpDown(None), 4, 7),
-                (Action::JumpUp(None), 2, 5),
-                // Count moves by that many lines
-                (Action::JumpDown(Some(4)), 6, 9),
-                // And count is remembered
-                (Action::JumpDown(None), 10, 13),
-                // ... by both up and down
-                (Action::JumpUp(None), 6, 9),
-            ],
-        );
+        viewer.focused_row = 1;
+        viewer.marks.insert('a', 2);
+        assert_eq!(viewer.toggle_decoded_string(), Ok(true));
+        let decoded =
+            parse_top_level_json(r#"{ "b": {"y": 1, "x": "[2]"}, "a": 3, "c": "{" }"#.to_owned())
+                .unwrap();
+        assert_eq!(viewer.flatjson.1, decoded.1);
+        assert!(viewer.is_decoded(1));
+        assert!(!viewer.is_decoded(0));
+        assert!(!viewer.is_decoded(4));
+        assert_eq!(viewer.focused_row, 1);
+        assert_eq!(viewer.get_mark('a'), Some(5));
+
+        // Strings in decoded strings can be decoded too.
+        viewer.focused_row = 3;
+        assert_eq!(viewer.toggle_decoded_string(), Ok(true));
+        assert!(viewer.is_decoded(3));
+        assert_eq!(viewer.get_mark('a'), Some(7));
 
-        // Prioritize keeping focused line in same place, but once we're
-        // at the top or bottom of the file, we will move it.
-        viewer.dimensions.height = 8;
-        viewer.top_row = 3;
+        // Errors don't change anything.
         viewer.focused_row = 7;
-        viewer.scrolloff_setting = 3;
-
-        assert_window_tracking(
-            &mut viewer,
-            vec![
-                (Action::JumpUp(Some(2)), 1, 5),
-                (Action::JumpUp(None), 0, 4),
-                (Action::JumpUp(None), 0, 2),
-                (Action::JumpUp(None), 0, 0),
-                // Scrolloff is ignored.
-                (Action::JumpDown(None), 2, 2),
-            ],
-        );
-
-        viewer.dimensions.height = 8;
-        viewer.top_row = 9;
-        viewer.focused_row = 11;
-
-        assert_window_tracking(
-            &mut viewer,
-            vec![
-                (Action::JumpDown(Some(2)), 11, 13),
-                (Action::JumpDown(None), 12, 14),
-                (Action::JumpDown(None), 12, 16),
-                (Action::JumpDown(None), 12, 18),
-                (Action::JumpDown(None), 12, 19),
-                // Scrolloff is ignored.
-                (Action::JumpUp(None), 10, 17),
-            ],
+        assert_eq!(
+            viewer.toggle_decoded_string(),
+            Err("Only strings can be decoded".to_string())
         );
+        viewer.focused_row = 8;
+        assert!(viewer
+            .toggle_decoded_string()
+            .unwrap_err()
+            .starts_with("The string isn't valid JSON"));
 
-        // We won't move past the end of the file (tested above),
-        // unless we're already past it.
-        viewer.top_row = 14;
-        viewer.focused_row = 15;
+        // { "a": 3, "b": { "x": [2], "y": 1 }, "c": "{" }
+        viewer.perform_action(Action::ToggleSortedKeys);
+        assert!(viewer.is_decoded(2));
+        assert!(viewer.is_decoded(3));
 
-        assert_window_tracking(
-            &mut viewer,
-            vec![
-                (Action::JumpDown(Some(2)), 14, 17),
-                (Action::JumpDown(None), 14, 19),
-            ],
+        // Restoring the outer string also restores the inner one.
+        viewer.focused_row = 7;
+        assert_eq!(viewer.toggle_decoded_string(), Ok(false));
+        assert!(viewer.sorted_keys());
+        assert_eq!(viewer.focused_row, 2);
+        assert!(!viewer.is_decoded(2));
+        viewer.perform_action(Action::ToggleSortedKeys);
+        assert_eq!(
+            viewer.flatjson.1,
+            parse_top_level_json(JSON.to_owned()).unwrap().1
         );
+        assert_eq!(viewer.get_mark('a'), Some(2));
     }
 
     #[test]
-    fn test_move_focus() {
+    fn test_saved_state() {
         let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
         let mut viewer = JsonViewer::new(fj, Mode::Line);
-        viewer.dimensions.height = 5;
-        viewer.scrolloff_setting = 1;
-
-        assert_window_tracking(
-            &mut viewer,
-            vec![
-                (Action::MoveFocusedLineToTop, 0, 0),
-                (Action::MoveFocusedLineToCenter, 0, 0),
-                (Action::MoveFocusedLineToBottom, 0, 0),
-            ],
-        );
-
-        viewer.top_row = 10;
-        viewer.focused_row = 12;
+        viewer.dimensions.height = 20;
+        viewer.flatjson.collapse(2);
+        viewer.focused_row = 8;
+        let state = viewer.saved_state();
+        assert_eq!(state.focused.len(), 3);
+        assert_eq!(state.lines_above, 5);
 
-        assert_window_tracking(
-            &mut viewer,
-            vec![
-                (Action::MoveFocusedLineToTop, 11, 12),
-                (Action::MoveFocusedLineToCenter, 10, 12),
-                (Action::MoveFocusedLineToBottom, 9, 12),
-            ],
-        );
+        // Path hashes are stable, so they can be saved across sessions.
+        assert_eq!(path_hashes(&viewer.flatjson)[8], 0xfe0b_d91a_01df_7301);
+        assert_eq!(viewer.path_hash(8), 0xfe0b_d91a_01df_7301);
 
-        viewer.top_row = 4;
-        viewer.focused_row = 6;
-        viewer.dimensions.height = 7;
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut restored = JsonViewer::new(fj, Mode::Line);
+        restored.dimensions.height = 20;
+        restored.restore_saved_state(&state);
+        assert!(restored.flatjson[2].is_collapsed());
+        assert_eq!(restored.focused_row, 8);
+        assert_eq!(restored.top_row, viewer.top_row);
+    }
 
-        assert_window_tracking(
-            &mut viewer,
-            vec![
-                (Action::MoveFocusedLineToTop, 5, 6),
-                (Action::MoveFocusedLineToCenter, 3, 6),
-                (Action::MoveFocusedLineToBottom, 1, 6),
-            ],
-        );
+    #[test]
+    fn test_append_array_elements() {
+        let fj = parse_top_level_json("[1, 2]".to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Data);
+        viewer.perform_action(Action::FocusBottom);
+        assert_eq!(viewer.focused_row, 2);
+
+        // The focus follows the new elements...
+        viewer.append_array_elements(parse_top_level_json("[3]".to_owned()).unwrap());
+        assert_eq!(viewer.focused_row, 3);
+        assert_eq!(viewer.flatjson[3].index, 2);
+
+        // ...unless it's moved away from the bottom.
+        viewer.perform_action(Action::MoveUp(1));
+        viewer.append_array_elements(parse_top_level_json("[4, 5]".to_owned()).unwrap());
+        assert_eq!(viewer.focused_row, 2);
+        assert_eq!(viewer.flatjson.1, "[1, 2, 3, 4, 5]");
+    }
 
-        viewer.dimensions.height = 8;
+    #[test]
+    fn test_marks() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
 
-        assert_window_tracking(
-            &mut viewer,
-            vec![
-                (Action::MoveFocusedLineToTop, 5, 6),
-                (Action::MoveFocusedLineToCenter, 2, 6),
-                (Action::MoveFocusedLineToBottom, 0, 6),
-            ],
-        );
+        viewer.perform_action(Action::MoveDown(3));
+        viewer.perform_action(Action::SetMark('a'));
+        viewer.perform_action(Action::MoveDown(4));
+        viewer.perform_action(Action::SetMark('b'));
+        viewer.perform_action(Action::MoveDown(1));
+        viewer.perform_action(Action::SetMark('a'));
+
+        assert_eq!(viewer.focused_row, 8);
+        assert_eq!(viewer.get_mark('a'), Some(8));
+        assert_eq!(viewer.get_mark('b'), Some(7));
+        assert_eq!(viewer.get_mark('c'), None);
+        assert_eq!(viewer.marks().collect::<Vec<_>>(), vec![('a', 8), ('b', 7)]);
+
+        // Marks past the end of the input are invalidated.
+        viewer.marks.insert('z', 100);
+        assert_eq!(viewer.marks().count(), 2);
+        assert_eq!(viewer.get_mark('z'), None);
+        assert!(!viewer.marks.contains_key(&'z'));
     }
 
     #[test]
@@ -1594,6 +3236,24 @@ mod tests {
         assert_window_tracking(&mut viewer, vec![(Action::Click(5), 1, 4)]);
     }
 
+    #[test]
+    fn test_double_click_row() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+        viewer.dimensions.height = 7;
+        viewer.scrolloff_setting = 3;
+
+        // First click collapses, then double click expands it again.
+        assert_window_tracking(&mut viewer, vec![(Action::Click(3), 0, 2)]);
+        assert!(viewer.flatjson[2].is_collapsed());
+        assert_window_tracking(&mut viewer, vec![(Action::DoubleClick(3), 0, 2)]);
+        assert!(viewer.flatjson[2].is_expanded());
+
+        // Double clicking an expanded container leaves it expanded.
+        assert_window_tracking(&mut viewer, vec![(Action::DoubleClick(3), 0, 2)]);
+        assert!(viewer.flatjson[2].is_expanded());
+    }
+
     #[test]
     fn test_focus_prev_next_sibling_line_mode() {
         let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
@@ -1712,6 +3372,76 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_skip_to_prev_next_sibling() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+
+        assert_movements(
+            &mut viewer,
+            vec![
+                (Action::SkipToNextSibling(1), 0),
+                (Action::SkipToPrevSibling(1), 0),
+            ],
+        );
+
+        viewer.focused_row = 1;
+        assert_movements(
+            &mut viewer,
+            vec![
+                (Action::SkipToNextSibling(1), 2),
+                (Action::SkipToNextSibling(1), 6),
+                (Action::SkipToNextSibling(1), 11),
+                // Stop at the last sibling, instead of moving to the parent.
+                (Action::SkipToNextSibling(1), 11),
+                (Action::SkipToPrevSibling(2), 2),
+                (Action::SkipToPrevSibling(5), 1),
+            ],
+        );
+
+        // Never leave the parent, even at a different depth than before.
+        viewer.focused_row = 3;
+        assert_movements(
+            &mut viewer,
+            vec![
+                (Action::SkipToNextSibling(1), 4),
+                (Action::SkipToNextSibling(1), 4),
+            ],
+        );
+
+        // Closing braces move relative to their container.
+        viewer.focused_row = 5;
+        assert_movements(&mut viewer, vec![(Action::SkipToNextSibling(1), 6)]);
+        viewer.focused_row = 10;
+        assert_movements(&mut viewer, vec![(Action::SkipToPrevSibling(1), 2)]);
+    }
+
+    #[test]
+    fn test_collapse_parent() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+
+        viewer.focused_row = 8;
+        viewer.perform_action(Action::CollapseParent);
+        assert_eq!(6, viewer.focused_row);
+        assert!(viewer.flatjson[6].is_collapsed());
+
+        viewer.perform_action(Action::CollapseParent);
+        assert_eq!(0, viewer.focused_row);
+        assert!(viewer.flatjson[0].is_collapsed());
+
+        // Nothing happens at the top level.
+        viewer.perform_action(Action::CollapseParent);
+        assert_eq!(0, viewer.focused_row);
+
+        // Closing braces collapse their own container.
+        viewer.flatjson.expand(0);
+        viewer.focused_row = 5;
+        viewer.perform_action(Action::CollapseParent);
+        assert_eq!(2, viewer.focused_row);
+        assert!(viewer.flatjson[2].is_collapsed());
+    }
+
     #[test]
     fn test_focus_first_last_sibling() {
         let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
@@ -1827,12 +3557,47 @@ mod tests {
             ],
         );
 
+        // Primitive values jump to the container enclosing them.
+        viewer.focused_row = 8;
+        assert_movements(
+            &mut viewer,
+            vec![
+                (Action::FocusMatchingPair, 6),
+                (Action::FocusMatchingPair, 10),
+            ],
+        );
+
         // Don't jump to closing brace if current node is collapsed.
         viewer.flatjson.collapse(6);
         viewer.focused_row = 6;
         assert_movements(&mut viewer, vec![(Action::FocusMatchingPair, 6)]);
     }
 
+    #[test]
+    fn test_focus_matching_pair_data_mode() {
+        let fj = parse_top_level_json(DATA_OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Data);
+
+        viewer.focused_row = 6;
+        assert_movements(
+            &mut viewer,
+            vec![
+                (Action::FocusMatchingPair, 9),
+                (Action::FocusMatchingPair, 6),
+                (Action::FocusMatchingPair, 9),
+            ],
+        );
+
+        viewer.focused_row = 1;
+        assert_movements(
+            &mut viewer,
+            vec![
+                (Action::FocusMatchingPair, 0),
+                (Action::FocusMatchingPair, 11),
+            ],
+        );
+    }
+
     const LOTS_OF_OBJECTS: &str = r#"{
         "1": {
             "2": 2
@@ -1893,6 +3658,59 @@ mod tests {
         assert!(viewer.flatjson[12].is_expanded());
     }
 
+    #[test]
+    fn test_expand_and_collapse_subtree() {
+        let fj = parse_top_level_json(LOTS_OF_OBJECTS.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+
+        viewer.dimensions.height = 8;
+        viewer.scrolloff_setting = 1;
+
+        viewer.flatjson.collapse(5);
+        viewer.flatjson.collapse(8);
+        viewer.focused_row = 4;
+        viewer.perform_action(Action::ExpandSubtree);
+        assert!(viewer.flatjson[4].is_expanded());
+        assert!(viewer.flatjson[5].is_expanded());
+        assert!(viewer.flatjson[8].is_expanded());
+        assert_eq!(4, viewer.focused_row);
+
+        viewer.focused_row = 0;
+        viewer.perform_action(Action::CollapseSubtree);
+        for index in [0, 1, 4, 5, 8, 12] {
+            assert!(viewer.flatjson[index].is_collapsed());
+        }
+        assert_eq!(0, viewer.focused_row);
+
+        // Collapsing from the closing of a container focuses its opening
+        // where the closing was on the screen.
+        viewer.perform_action(Action::ExpandSubtree);
+        viewer.top_row = 9;
+        viewer.focused_row = 11;
+        viewer.perform_action(Action::CollapseSubtree);
+        assert_eq!(4, viewer.focused_row);
+        assert_eq!(2, viewer.top_row);
+        assert!(viewer.flatjson[5].is_collapsed());
+        assert!(viewer.flatjson[8].is_collapsed());
+        assert!(viewer.flatjson[1].is_expanded());
+        assert!(viewer.flatjson[12].is_expanded());
+
+        // And expanding from the closing keeps it on the same line.
+        viewer.flatjson.expand(4);
+        viewer.top_row = 8;
+        viewer.focused_row = 11;
+        viewer.perform_action(Action::ExpandSubtree);
+        assert_eq!(11, viewer.focused_row);
+        assert_eq!(10, viewer.top_row);
+        assert!(viewer.flatjson[5].is_expanded());
+        assert!(viewer.flatjson[8].is_expanded());
+
+        // Primitives don't have subtrees.
+        viewer.focused_row = 13;
+        viewer.perform_action(Action::CollapseSubtree);
+        assert!(viewer.flatjson[12].is_expanded());
+    }
+
     #[test]
     fn test_toggle_mode() {
         let fj = parse_top_level_json(LOTS_OF_OBJECTS.to_owned()).unwrap();
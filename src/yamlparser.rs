@@ -1,26 +1,121 @@
-use yaml_rust::yaml::{Array, Hash, Yaml};
-use yaml_rust::YamlLoader;
+use std::collections::HashMap;
 
-use crate::flatjson::{ContainerType, Index, OptionIndex, Row, Value};
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, TScalarStyle, TokenType};
+use yaml_rust::Yaml;
+
+use crate::flatjson::{self, Anchor, ContainerType, Index, OptionIndex, Row, Value};
 
 struct YamlParser {
     parents: Vec<Index>,
     rows: Vec<Row>,
     pretty_printed: String,
     max_depth: usize,
+    // The names of anchors that aliases refer to, by id.
+    anchor_names: HashMap<usize, String>,
+}
+
+// A YAML value, like yaml_rust's Yaml. yaml_rust's YamlLoader replaces
+// aliases with copies of the values their anchors are on, without keeping
+// track of where they were, so values are loaded by AnchorLoader instead,
+// which does.
+#[derive(Clone)]
+struct Node {
+    value: NodeValue,
+    // The id of the anchor defined on the value, or that an alias refers
+    // to, and whether it's an alias.
+    anchor: Option<(usize, bool)>,
+    // Where the value starts in the source, or its key does, as a char
+    // offset, to find which comments are on it.
+    position: usize,
+    comment: Option<String>,
+}
+
+#[derive(Clone)]
+enum NodeValue {
+    Scalar(Yaml),
+    // A container that was nested too deeply to be loaded, with the
+    // placeholder shown in its place (see Value::TooDeep).
+    TooDeep(String),
+    Array(Vec<Node>),
+    // Like in yaml_rust's Hash, a later value for the same key replaces
+    // the earlier one, and moves to the end. Anchors on keys aren't shown.
+    Hash(Vec<(Yaml, Node)>),
+}
+
+struct AnchorLoader<'a> {
+    source: &'a str,
+    // The (char, byte) offset in the source of the last alias, since
+    // yaml_rust's markers are char offsets.
+    source_offset: (usize, usize),
+    docs: Vec<Node>,
+    // The containers being loaded, with the id of the anchor on each (or
+    // 0), and for hashes, the key whose value is being loaded, and where
+    // each key is.
+    stack: Vec<(Node, usize)>,
+    keys: Vec<(Option<Node>, HashMap<Yaml, usize>)>,
+    anchored: HashMap<usize, Node>,
+    anchor_names: HashMap<usize, String>,
+    // When loading comments, where each quoted and block scalar starts.
+    scalars: Option<Vec<(usize, TScalarStyle)>>,
+    // Containers nested more deeply than this are skipped, and replaced by
+    // a placeholder, so that converting the nodes to rows (and dropping
+    // them) doesn't recurse too deeply.
+    depth_limit: usize,
+    skipped: Option<Skipped>,
+}
+
+// A container that's being skipped because it's nested too deeply.
+struct Skipped {
+    // How many containers deep in it the events are.
+    depth: usize,
+    anchor_id: usize,
+    position: usize,
+    is_hash: bool,
+    empty: bool,
+}
+
+// A comment in the source, and whether it follows something else on the
+// same line.
+struct Comment {
+    position: usize,
+    trailing: bool,
+    text: String,
 }
 
-pub fn parse(yaml: String) -> Result<(Vec<Row>, String, usize), String> {
+// Parses YAML, optionally keeping the comments in it, which are attached
+// to the values they're next to.
+pub fn parse(
+    yaml: String,
+    comments: bool,
+    depth_limit: usize,
+) -> Result<(Vec<Row>, String, usize), String> {
+    let mut loader = AnchorLoader {
+        source: &yaml,
+        source_offset: (0, 0),
+        docs: vec![],
+        stack: vec![],
+        keys: vec![],
+        anchored: HashMap::new(),
+        anchor_names: HashMap::new(),
+        scalars: if comments { Some(vec![]) } else { None },
+        depth_limit,
+        skipped: None,
+    };
+    if let Err(err) = Parser::new(yaml.chars()).load(&mut loader, true) {
+        return Err(format!("{}", err));
+    }
+    let mut docs = loader.docs;
+    if let Some(scalars) = loader.scalars {
+        attach_comments(&mut docs, find_comments(&yaml, &scalars));
+    }
+
     let mut parser = YamlParser {
         parents: vec![],
         rows: vec![],
         pretty_printed: String::new(),
         max_depth: 0,
-    };
-
-    let docs = match YamlLoader::load_from_str(&yaml) {
-        Ok(yaml_docs) => yaml_docs,
-        Err(err) => return Err(format!("{}", err)),
+        anchor_names: loader.anchor_names,
     };
 
     let mut prev_sibling = OptionIndex::Nil;
@@ -43,25 +138,477 @@ pub fn parse(yaml: String) -> Result<(Vec<Row>, String, usize), String> {
     Ok((parser.rows, parser.pretty_printed, parser.max_depth))
 }
 
+impl<'a> MarkedEventReceiver for AnchorLoader<'a> {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        if self.skipped.is_some() {
+            self.skip_event(event);
+            return;
+        }
+
+        match event {
+            Event::SequenceStart(anchor_id) | Event::MappingStart(anchor_id)
+                if self.stack.len() >= self.depth_limit =>
+            {
+                self.skipped = Some(Skipped {
+                    depth: 1,
+                    anchor_id,
+                    position: mark.index(),
+                    is_hash: matches!(event, Event::MappingStart(_)),
+                    empty: true,
+                });
+            }
+            Event::DocumentEnd => match self.stack.pop() {
+                Some((node, _)) => self.docs.push(node),
+                // An empty document.
+                None => self.docs.push(Node::scalar(Yaml::BadValue, mark.index())),
+            },
+            Event::SequenceStart(anchor_id) => {
+                let node = Node::new(NodeValue::Array(vec![]), mark.index());
+                self.stack.push((node, anchor_id));
+            }
+            Event::MappingStart(anchor_id) => {
+                let node = Node::new(NodeValue::Hash(vec![]), mark.index());
+                self.stack.push((node, anchor_id));
+                self.keys.push((None, HashMap::new()));
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                if let Event::MappingEnd = event {
+                    self.keys.pop();
+                }
+                let (mut node, anchor_id) = self.stack.pop().unwrap();
+                // The start of a block mapping is after its first key.
+                if let Some(first) = node.first_position() {
+                    node.position = node.position.min(first);
+                }
+                self.insert_node(node, anchor_id);
+            }
+            Event::Scalar(value, style, anchor_id, tag) => {
+                if let Some(scalars) = &mut self.scalars {
+                    if style != TScalarStyle::Plain {
+                        scalars.push((mark.index(), style));
+                    }
+                }
+                let node = Node::scalar(scalar(value, style, tag), mark.index());
+                self.insert_node(node, anchor_id);
+            }
+            Event::Alias(anchor_id) => {
+                let name = self.alias_name(mark);
+                self.anchor_names.insert(anchor_id, name);
+                let node = match self.anchored.get(&anchor_id) {
+                    Some(anchored) => Node {
+                        anchor: Some((anchor_id, true)),
+                        position: mark.index(),
+                        ..anchored.without_anchors()
+                    },
+                    None => Node::scalar(Yaml::BadValue, mark.index()),
+                };
+                self.insert_node(node, 0);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<'a> AnchorLoader<'a> {
+    // Handles an event in a container that's being skipped, adding a
+    // placeholder once it ends, unless it's empty.
+    fn skip_event(&mut self, event: Event) {
+        let skipped = self.skipped.as_mut().unwrap();
+        match event {
+            Event::SequenceStart(_) | Event::MappingStart(_) => skipped.depth += 1,
+            Event::SequenceEnd | Event::MappingEnd => skipped.depth -= 1,
+            _ => {}
+        }
+        skipped.empty = skipped.empty && skipped.depth == 0;
+        if skipped.depth > 0 {
+            return;
+        }
+
+        let skipped = self.skipped.take().unwrap();
+        let node = if !skipped.empty {
+            let placeholder = flatjson::too_deep_placeholder(self.depth_limit);
+            Node::new(NodeValue::TooDeep(placeholder), skipped.position)
+        } else if skipped.is_hash {
+            Node::new(NodeValue::Hash(vec![]), skipped.position)
+        } else {
+            Node::new(NodeValue::Array(vec![]), skipped.position)
+        };
+        self.insert_node(node, skipped.anchor_id);
+    }
+
+    // Adds a node to the container being loaded, like YamlLoader.
+    fn insert_node(&mut self, mut node: Node, anchor_id: usize) {
+        if anchor_id != 0 {
+            node.anchor = Some((anchor_id, false));
+            self.anchored.insert(anchor_id, node.clone());
+        }
+
+        let parent = match self.stack.last_mut() {
+            Some((parent, _)) => parent,
+            None => {
+                self.stack.push((node, 0));
+                return;
+            }
+        };
+
+        match &mut parent.value {
+            NodeValue::Array(elements) => elements.push(node),
+            NodeValue::Hash(members) => {
+                let (key, positions) = self.keys.last_mut().unwrap();
+                match key.take() {
+                    None => *key = Some(node),
+                    Some(key) => {
+                        node.position = key.position;
+                        let key = key.into_yaml();
+                        if let Some(position) = positions.remove(&key) {
+                            members.remove(position);
+                            for later in positions.values_mut().filter(|p| **p > position) {
+                                *later -= 1;
+                            }
+                        }
+                        positions.insert(key.clone(), members.len());
+                        members.push((key, node));
+                    }
+                }
+            }
+            NodeValue::Scalar(_) | NodeValue::TooDeep(_) => unreachable!(),
+        }
+    }
+
+    // The name an alias at the given position in the source refers to,
+    // i.e., what follows the '*'.
+    fn alias_name(&mut self, mark: Marker) -> String {
+        let (char_offset, byte_offset) = self.source_offset;
+        let skipped: usize = self.source[byte_offset..]
+            .chars()
+            .take(mark.index() - char_offset)
+            .map(char::len_utf8)
+            .sum();
+        self.source_offset = (mark.index(), byte_offset + skipped);
+
+        self.source[byte_offset + skipped..]
+            .chars()
+            .skip(1)
+            .take_while(|&ch| !ch.is_whitespace() && !",[]{}".contains(ch))
+            .collect()
+    }
+}
+
+impl Node {
+    fn new(value: NodeValue, position: usize) -> Node {
+        Node {
+            value,
+            anchor: None,
+            position,
+            comment: None,
+        }
+    }
+
+    fn scalar(value: Yaml, position: usize) -> Node {
+        Node::new(NodeValue::Scalar(value), position)
+    }
+
+    fn first_position(&self) -> Option<usize> {
+        match &self.value {
+            NodeValue::Scalar(_) | NodeValue::TooDeep(_) => None,
+            NodeValue::Array(elements) => elements.first().map(|node| node.position),
+            NodeValue::Hash(members) => members.first().map(|(_, node)| node.position),
+        }
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Node> {
+        match &mut self.value {
+            NodeValue::Scalar(_) | NodeValue::TooDeep(_) => vec![],
+            NodeValue::Array(elements) => elements.iter_mut().collect(),
+            NodeValue::Hash(members) => members.iter_mut().map(|(_, node)| node).collect(),
+        }
+    }
+
+    // Where the node and its descendants start, in order, leaving out the
+    // descendants of aliases, which are copies of values elsewhere.
+    fn positions(&mut self, positions: &mut Vec<usize>) {
+        positions.push(self.position);
+        if !self.is_alias() {
+            for child in self.children_mut() {
+                child.positions(positions);
+            }
+        }
+    }
+
+    // Sets the comments on the node and its descendants, in the same order
+    // as positions.
+    fn set_comments(&mut self, comments: &mut impl Iterator<Item = Option<String>>) {
+        self.comment = comments.next().unwrap();
+        if !self.is_alias() {
+            for child in self.children_mut() {
+                child.set_comments(comments);
+            }
+        }
+    }
+
+    fn is_alias(&self) -> bool {
+        matches!(self.anchor, Some((_, true)))
+    }
+
+    // A copy of the node with the anchors defined on it and its children
+    // removed, for an alias, which isn't where they're defined. Aliases in
+    // it are kept.
+    fn without_anchors(&self) -> Node {
+        let value = match &self.value {
+            NodeValue::Scalar(value) => NodeValue::Scalar(value.clone()),
+            NodeValue::TooDeep(placeholder) => NodeValue::TooDeep(placeholder.clone()),
+            NodeValue::Array(elements) => {
+                NodeValue::Array(elements.iter().map(Node::without_anchors).collect())
+            }
+            NodeValue::Hash(members) => NodeValue::Hash(
+                members
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.without_anchors()))
+                    .collect(),
+            ),
+        };
+        let anchor = self.anchor.filter(|&(_, is_alias)| is_alias);
+        Node {
+            anchor,
+            ..Node::new(value, self.position)
+        }
+    }
+
+    fn into_yaml(self) -> Yaml {
+        match self.value {
+            NodeValue::Scalar(value) => value,
+            // Only keys are converted, and they're only shown.
+            NodeValue::TooDeep(placeholder) => Yaml::String(placeholder),
+            NodeValue::Array(elements) => {
+                Yaml::Array(elements.into_iter().map(Node::into_yaml).collect())
+            }
+            NodeValue::Hash(members) => Yaml::Hash(
+                members
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into_yaml()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+// Converts a scalar to a value, the same way as YamlLoader.
+fn scalar(value: String, style: TScalarStyle, tag: Option<TokenType>) -> Yaml {
+    if style != TScalarStyle::Plain {
+        return Yaml::String(value);
+    }
+
+    match tag {
+        Some(TokenType::Tag(handle, suffix)) if handle == "!!" => match suffix.as_ref() {
+            "bool" => value.parse().map_or(Yaml::BadValue, Yaml::Boolean),
+            "int" => value.parse().map_or(Yaml::BadValue, Yaml::Integer),
+            // Yaml::from_str also accepts .inf and .nan as floats.
+            "float" => match (Yaml::from_str(&value), value.parse::<f64>()) {
+                (Yaml::Real(_), _) | (_, Ok(_)) => Yaml::Real(value),
+                _ => Yaml::BadValue,
+            },
+            "null" => match value.as_ref() {
+                "~" | "null" => Yaml::Null,
+                _ => Yaml::BadValue,
+            },
+            _ => Yaml::String(value),
+        },
+        Some(TokenType::Tag(..)) => Yaml::String(value),
+        _ => Yaml::from_str(&value),
+    }
+}
+
+// Finds the comments in the source, given where the quoted and block
+// scalars in it start, which are the only places where a '#' after
+// whitespace doesn't start a comment.
+fn find_comments(source: &str, scalars: &[(usize, TScalarStyle)]) -> Vec<Comment> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut comments = vec![];
+    let mut scalars = scalars.iter().peekable();
+    // Whether something other than whitespace comes earlier on the line.
+    let mut trailing = false;
+
+    let mut i = 0;
+    while i < chars.len() {
+        while scalars.next_if(|(start, _)| *start < i).is_some() {}
+        if let Some((_, style)) = scalars.next_if(|(start, _)| *start == i) {
+            i = skip_scalar(&chars, i, *style);
+            trailing = chars.get(i.wrapping_sub(1)).map_or(false, |&ch| ch != '\n');
+            continue;
+        }
+
+        match chars[i] {
+            '\n' => trailing = false,
+            '#' if i == 0 || chars[i - 1].is_whitespace() => {
+                let end = line_end(&chars, i);
+                comments.push(Comment {
+                    position: i,
+                    trailing,
+                    text: chars[i..end]
+                        .iter()
+                        .collect::<String>()
+                        .trim_end()
+                        .to_owned(),
+                });
+                i = end;
+                continue;
+            }
+            ch if ch.is_whitespace() => {}
+            _ => trailing = true,
+        }
+        i += 1;
+    }
+
+    comments
+}
+
+// Skips over a quoted scalar, or the contents of a block scalar, which
+// start at the given position, returning where they end.
+fn skip_scalar(chars: &[char], start: usize, style: TScalarStyle) -> usize {
+    match style {
+        // The contents of the scalar are the lines after its '|' or '>' that
+        // are more indented than that line, and as indented as the first one.
+        TScalarStyle::Literal | TScalarStyle::Foled => {
+            let mut line_start = chars[..start]
+                .iter()
+                .rposition(|&ch| ch == '\n')
+                .map_or(0, |newline| newline + 1);
+            let header = chars[..line_start]
+                .split(|&ch| ch == '\n')
+                .rev()
+                .find(|line| !is_blank(line))
+                .unwrap_or(&[]);
+            let mut contents_indentation = None;
+            loop {
+                let line = &chars[line_start..line_end(chars, line_start)];
+                if !is_blank(line) {
+                    let line_indentation = indentation(line);
+                    if line_indentation < contents_indentation.unwrap_or(indentation(header) + 1) {
+                        return line_start;
+                    }
+                    contents_indentation.get_or_insert(line_indentation);
+                }
+                if line_start + line.len() == chars.len() {
+                    return chars.len();
+                }
+                line_start += line.len() + 1;
+            }
+        }
+        _ => {
+            let quote = chars[start];
+            let mut i = start + 1;
+            while i < chars.len() {
+                match chars[i] {
+                    '\\' if quote == '"' => i += 1,
+                    '\'' if quote == '\'' && chars.get(i + 1) == Some(&'\'') => i += 1,
+                    ch if ch == quote => return i + 1,
+                    _ => {}
+                }
+                i += 1;
+            }
+            i
+        }
+    }
+}
+
+fn line_end(chars: &[char], start: usize) -> usize {
+    chars[start..]
+        .iter()
+        .position(|&ch| ch == '\n')
+        .map_or(chars.len(), |len| start + len)
+}
+
+fn is_blank(line: &[char]) -> bool {
+    line.iter().all(|ch| ch.is_whitespace())
+}
+
+fn indentation(line: &[char]) -> usize {
+    line.iter().take_while(|&&ch| ch == ' ').count()
+}
+
+// Attaches each comment to a value: one after something else on a line is
+// attached to the last value starting before it, and others to the next
+// value, e.g., the key after a comment on the lines above it.
+fn attach_comments(docs: &mut [Node], comments: Vec<Comment>) {
+    let mut positions = vec![];
+    for doc in docs.iter_mut() {
+        doc.positions(&mut positions);
+    }
+    // Values with the same key as a later one are moved to where the later
+    // one is.
+    let mut order: Vec<usize> = (0..positions.len()).collect();
+    order.sort_by_key(|&node| positions[node]);
+
+    let mut attached: Vec<Option<String>> = vec![None; positions.len()];
+    for comment in comments {
+        let next = order.partition_point(|&node| positions[node] < comment.position);
+        let node = match (comment.trailing, next.checked_sub(1)) {
+            (true, Some(prev)) => order[prev],
+            _ => match order.get(next) {
+                Some(&node) => node,
+                None => continue,
+            },
+        };
+        match &mut attached[node] {
+            Some(text) => {
+                text.push(' ');
+                text.push_str(&comment.text);
+            }
+            None => attached[node] = Some(comment.text),
+        }
+    }
+
+    let mut attached = attached.into_iter();
+    for doc in docs.iter_mut() {
+        doc.set_comments(&mut attached);
+    }
+}
+
+// Escapes a string so that it's shown as it would be in JSON. Other
+// control characters are escaped when they're printed.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
 impl YamlParser {
-    fn parse_yaml_item(&mut self, item: Yaml) -> Result<usize, String> {
+    fn parse_yaml_item(&mut self, item: Node) -> Result<usize, String> {
         self.max_depth = self.max_depth.max(self.parents.len());
 
-        let index = match item {
-            Yaml::BadValue => return Err("Unknown YAML parse error".to_owned()),
-            Yaml::Null => self.parse_null(),
-            Yaml::Boolean(b) => self.parse_bool(b),
-            Yaml::Integer(i) => self.parse_number(i.to_string()),
-            Yaml::Real(real_str) => self.parse_number(real_str),
-            Yaml::String(s) => self.parse_string(s),
-            Yaml::Array(arr) => self.parse_array(arr)?,
-            Yaml::Hash(hash) => self.parse_hash(hash)?,
-            // The yaml_rust source says these are not supported yet.
-            // Aliases are automatically replaced by their anchors, so
-            // it's unclear what this would be used for.
-            Yaml::Alias(_) => return Err("YAML parser returned Alias value".to_owned()),
+        let index = match item.value {
+            NodeValue::Scalar(Yaml::BadValue) => return Err("Unknown YAML parse error".to_owned()),
+            NodeValue::Scalar(Yaml::Null) => self.parse_null(),
+            NodeValue::Scalar(Yaml::Boolean(b)) => self.parse_bool(b),
+            NodeValue::Scalar(Yaml::Integer(i)) => self.parse_number(i.to_string()),
+            NodeValue::Scalar(Yaml::Real(real_str)) => self.parse_number(real_str),
+            NodeValue::Scalar(Yaml::String(s)) => self.parse_string(s),
+            NodeValue::Scalar(_) => unreachable!(),
+            NodeValue::TooDeep(placeholder) => self.parse_too_deep(placeholder),
+            NodeValue::Array(arr) => self.parse_array(arr)?,
+            NodeValue::Hash(hash) => self.parse_hash(hash)?,
         };
 
+        self.rows[index].comment = item.comment.map(String::into_boxed_str);
+
+        // Anchors are only shown if there are aliases that refer to them.
+        if let Some((id, is_alias)) = item.anchor {
+            if let Some(name) = self.anchor_names.get(&id) {
+                self.rows[index].anchor = Some(Box::new(Anchor {
+                    name: name.clone(),
+                    id,
+                    is_alias,
+                }));
+            }
+        }
+
         Ok(index)
     }
 
@@ -94,8 +641,7 @@ impl YamlParser {
     fn parse_string(&mut self, s: String) -> usize {
         let row_index = self.create_row(Value::String);
 
-        // Escape newlines.
-        let s = s.replace('\n', "\\n");
+        let s = escape(&s);
 
         self.pretty_printed.push('"');
         self.pretty_printed.push_str(&s);
@@ -105,7 +651,14 @@ impl YamlParser {
         row_index
     }
 
-    fn parse_array(&mut self, arr: Array) -> Result<usize, String> {
+    fn parse_too_deep(&mut self, placeholder: String) -> usize {
+        let row_index = self.create_row(Value::TooDeep);
+        self.pretty_printed.push_str(&placeholder);
+        self.rows[row_index].range.end = self.pretty_printed.len();
+        row_index
+    }
+
+    fn parse_array(&mut self, arr: Vec<Node>) -> Result<usize, String> {
         if arr.is_empty() {
             let row_index = self.create_row(Value::EmptyArray);
             self.rows[row_index].range.end = self.rows[row_index].range.start + 2;
@@ -185,7 +738,7 @@ impl YamlParser {
         Ok(array_open_index)
     }
 
-    fn parse_hash(&mut self, hash: Hash) -> Result<usize, String> {
+    fn parse_hash(&mut self, hash: Vec<(Yaml, Node)>) -> Result<usize, String> {
         if hash.is_empty() {
             let row_index = self.create_row(Value::EmptyObject);
             self.rows[row_index].range.end = self.rows[row_index].range.start + 2;
@@ -289,8 +842,7 @@ impl YamlParser {
 
     fn pretty_print_key_item(&mut self, item: Yaml, is_key: bool) -> Result<(), String> {
         if let Yaml::String(s) = item {
-            // Replace newlines.
-            let s = s.replace('\n', "\\n");
+            let s = escape(&s);
             self.pretty_printed.push('"');
             self.pretty_printed.push_str(&s);
             self.pretty_printed.push('"');
@@ -385,6 +937,11 @@ impl YamlParser {
             next_sibling: OptionIndex::Nil,
             index: 0,
             key_range: None,
+            key_occurrence: 0,
+            // Only JSON inputs keep where their values were in the input.
+            source_range: None,
+            anchor: None,
+            comment: None,
         });
 
         index
@@ -396,6 +953,7 @@ mod tests {
     use indoc::indoc;
 
     use super::*;
+    use crate::flatjson::DEFAULT_MAX_DEPTH;
 
     #[test]
     fn test_basic() {
@@ -409,7 +967,7 @@ mod tests {
             ddd: []
         "#}
         .to_owned();
-        let (rows, _, _) = parse(yaml).unwrap();
+        let (rows, _, _) = parse(yaml, false, DEFAULT_MAX_DEPTH).unwrap();
 
         assert_eq!(rows[0].range, 0..43); // Object
         assert_eq!(rows[1].key_range, Some(2..5)); // "a": 1
@@ -431,7 +989,7 @@ mod tests {
             - {}
         "#}
         .to_owned();
-        let (rows, _, _) = parse(yaml).unwrap();
+        let (rows, _, _) = parse(yaml, false, DEFAULT_MAX_DEPTH).unwrap();
 
         assert_eq!(rows[0].range, 0..24); // Array
         assert_eq!(rows[1].range, 1..3); // 14
@@ -451,7 +1009,7 @@ mod tests {
             - false
         "#}
         .to_owned();
-        let (rows, _, _) = parse(yaml).unwrap();
+        let (rows, _, _) = parse(yaml, false, DEFAULT_MAX_DEPTH).unwrap();
 
         assert_eq!(rows[0].range, 0..52); // Array
         assert_eq!(rows[1].range, 1..38); // Object
@@ -467,6 +1025,26 @@ mod tests {
         assert_eq!(rows[8].range, 51..52); // ]
     }
 
+    #[test]
+    fn test_numbers() {
+        // Floats and integers too large for an i64 keep their original
+        // formatting.
+        let yaml = indoc! {r#"
+            - 1.0
+            - 1e3
+            - 12345678901234567890
+            - 42
+            - !!float 2
+            - !!str 3
+        "#}
+        .to_owned();
+        let (rows, pretty, _) = parse(yaml, false, DEFAULT_MAX_DEPTH).unwrap();
+
+        assert_eq!("[1.0, 1e3, 12345678901234567890, 42, 2, \"3\"]", pretty);
+        assert!(matches!(rows[1].value, Value::Number));
+        assert!(matches!(rows[3].value, Value::Number));
+    }
+
     #[test]
     fn test_non_scalar_keys() {
         let yaml = indoc! {r#"
@@ -477,7 +1055,7 @@ mod tests {
         .to_owned();
         //              0 2       1012 15                  3537   42
         let pretty = r#"{ [[1, 2]]: 1, [{ "a": 1, "b": 2 }]: true }"#;
-        let (rows, parsed_pretty, _) = parse(yaml).unwrap();
+        let (rows, parsed_pretty, _) = parse(yaml, false, DEFAULT_MAX_DEPTH).unwrap();
 
         assert_eq!(pretty, parsed_pretty);
 
@@ -488,6 +1066,86 @@ mod tests {
         assert_eq!(rows[2].range, 37..41); // [{ "a": 1, "b": 2 }]: true
     }
 
+    #[test]
+    fn test_anchors() {
+        let yaml = indoc! {r#"
+            base: &base
+              a: 1
+            name: &name jless
+            dev:
+              <<: *base
+              b: *name
+            unused: &unused 2
+            a: 0
+            a: &a [*name]
+        "#}
+        .to_owned();
+        let pretty = r#"{ "base": { "a": 1 }, "name": "jless", "dev": { "<<": { "a": 1 }, "b": "jless" }, "unused": 2, "a": ["jless"] }"#;
+        // The second "a" replaces the first.
+        let (rows, parsed_pretty, _) = parse(yaml, false, DEFAULT_MAX_DEPTH).unwrap();
+
+        assert_eq!(pretty, parsed_pretty);
+
+        let anchor = |name: &str, id, is_alias| {
+            Some(Box::new(Anchor {
+                name: name.to_owned(),
+                id,
+                is_alias,
+            }))
+        };
+        assert_eq!(rows[1].anchor, anchor("base", 1, false));
+        assert_eq!(rows[4].anchor, anchor("name", 2, false));
+        assert_eq!(rows[6].anchor, anchor("base", 1, true));
+        assert_eq!(rows[9].anchor, anchor("name", 2, true));
+        // Anchors that aren't referred to aren't shown.
+        assert_eq!(rows[11].anchor, None);
+        assert_eq!(rows[12].anchor, None);
+        assert_eq!(rows[13].anchor, anchor("name", 2, true));
+    }
+
+    #[test]
+    fn test_comments() {
+        let yaml = indoc! {r#"
+            # Settings
+            server:  # The server
+              # The port
+              port: 80 # HTTP
+              name: 'a # b' # Quoted
+              motd: |
+                # Not a comment
+              list:
+                - 1  # One
+                # Two
+                - 2
+            url: http://example.com/#top
+            # Nothing after this
+        "#}
+        .to_owned();
+        let (rows, _, _) = parse(yaml.clone(), true, DEFAULT_MAX_DEPTH).unwrap();
+
+        let comments: Vec<Option<&str>> = rows.iter().map(|row| row.comment.as_deref()).collect();
+        assert_eq!(
+            comments,
+            vec![
+                Some("# Settings"),
+                Some("# The server"),
+                Some("# The port # HTTP"),
+                Some("# Quoted"),
+                None,
+                None,
+                Some("# One"),
+                Some("# Two"),
+                None,
+                None,
+                None,
+                None,
+            ]
+        );
+
+        let (rows, _, _) = parse(yaml, false, DEFAULT_MAX_DEPTH).unwrap();
+        assert!(rows.iter().all(|row| row.comment.is_none()));
+    }
+
     #[test]
     fn test_multiline_strings() {
         let yaml = indoc! {r#"
@@ -509,8 +1167,27 @@ mod tests {
         .to_owned();
         let pretty =
             r#"{ "str1": "fl ow", "str2": "a\nb\n", "str3": "fol ded\n", "key\nstring\n": 1 }"#;
-        let (_, parsed_pretty, _) = parse(yaml).unwrap();
+        let (_, parsed_pretty, _) = parse(yaml, false, DEFAULT_MAX_DEPTH).unwrap();
 
         assert_eq!(pretty, parsed_pretty);
     }
+
+    #[test]
+    fn test_depth_limit() {
+        let yaml = indoc! {r#"
+            a:
+              - 1
+              - b: &deep
+                  c: [2]
+              - *deep
+            d: [{}, []]
+        "#}
+        .to_owned();
+        let (_, parsed_pretty, max_depth) = parse(yaml, false, 3).unwrap();
+        assert_eq!(
+            parsed_pretty,
+            r#"{ "a": [1, { "b": … max depth 3 reached }, … max depth 3 reached], "d": [{}, []] }"#
+        );
+        assert_eq!(max_depth, 3);
+    }
 }